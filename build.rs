@@ -19,6 +19,7 @@ fn main() {
         for feature in features.split(',').map(str::trim) {
             println!("cargo::rustc-cfg=ngx_feature=\"{feature}\"");
         }
+        println!("cargo::rustc-env=DEP_NGINX_FEATURES={features}");
     }
 
     // Specify acceptable values for `ngx_os`
@@ -31,12 +32,14 @@ fn main() {
     println!("cargo::rerun-if-env-changed=DEP_NGINX_OS");
     if let Ok(os) = std::env::var("DEP_NGINX_OS") {
         println!("cargo::rustc-cfg=ngx_os=\"{os}\"");
+        println!("cargo::rustc-env=DEP_NGINX_OS={os}");
     }
 
     // Generate cfg values for version checks
     const VERSION_CHECKS: &[(u64, &str)] = &[
         //
         (1_021_001, "nginx1_21_1"),
+        (1_023_002, "nginx1_23_2"),
         (1_025_001, "nginx1_25_1"),
     ];
     VERSION_CHECKS
@@ -1,18 +1,16 @@
+use std::cell::Cell;
 use std::ffi::{c_char, c_void};
 use std::ptr::{addr_of, addr_of_mut};
-use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
-use std::sync::{Arc, OnceLock};
+use std::sync::OnceLock;
 use std::time::Instant;
 
 use ngx::core;
 use ngx::ffi::{
-    ngx_array_push, ngx_command_t, ngx_conf_t, ngx_connection_t, ngx_event_t, ngx_http_handler_pt,
-    ngx_http_module_t, ngx_http_phases_NGX_HTTP_ACCESS_PHASE, ngx_int_t, ngx_module_t,
-    ngx_post_event, ngx_posted_events, ngx_posted_next_events, ngx_str_t, ngx_uint_t,
-    NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE, NGX_LOG_EMERG,
+    ngx_command_t, ngx_conf_t, ngx_http_module_t, ngx_http_request_t, ngx_int_t, ngx_module_t,
+    ngx_post_event, ngx_posted_events, ngx_str_t, ngx_uint_t, NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF,
+    NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE, NGX_LOG_EMERG,
 };
-use ngx::http::{self, HttpModule, MergeConfigError};
-use ngx::http::{HttpModuleLocationConf, HttpModuleMainConf, NgxHttpCoreModule};
+use ngx::http::{self, HttpModule, HttpModuleLocationConf, MergeConfigError};
 use ngx::{http_request_handler, ngx_conf_log_error, ngx_log_debug_http, ngx_string};
 use tokio::runtime::Runtime;
 
@@ -26,17 +24,11 @@ impl http::HttpModule for Module {
     unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
         // SAFETY: this function is called with non-NULL cf always
         let cf = &mut *cf;
-        let cmcf = NgxHttpCoreModule::main_conf_mut(cf).expect("http core main conf");
-
-        let h = ngx_array_push(
-            &mut cmcf.phases[ngx_http_phases_NGX_HTTP_ACCESS_PHASE as usize].handlers,
-        ) as *mut ngx_http_handler_pt;
-        if h.is_null() {
-            return core::Status::NGX_ERROR.into();
-        }
         // set an Access phase handler
-        *h = Some(async_access_handler);
-        core::Status::NGX_OK.into()
+        match http::Phases::register(cf, http::Phase::Access, async_access_handler) {
+            Ok(()) => core::Status::NGX_OK.into(),
+            Err(_) => core::Status::NGX_ERROR.into(),
+        }
     }
 }
 
@@ -96,35 +88,11 @@ impl http::Merge for ModuleConfig {
     }
 }
 
-unsafe extern "C" fn check_async_work_done(event: *mut ngx_event_t) {
-    let ctx = ngx::ngx_container_of!(event, RequestCTX, event);
-    let c: *mut ngx_connection_t = (*event).data.cast();
-
-    if (*ctx).done.load(Ordering::Relaxed) {
-        // Triggering async_access_handler again
-        ngx_post_event((*c).write, addr_of_mut!(ngx_posted_events));
-    } else {
-        // this doesn't have have good performance but works as a simple thread-safe example and
-        // doesn't causes segfault. The best method that provides both thread-safety and
-        // performance requires an nginx patch.
-        ngx_post_event(event, addr_of_mut!(ngx_posted_next_events));
-    }
-}
-
+#[derive(Default)]
 struct RequestCTX {
-    done: Arc<AtomicBool>,
-    event: ngx_event_t,
+    done: Cell<bool>,
     task: Option<tokio::task::JoinHandle<()>>,
-}
-
-impl Default for RequestCTX {
-    fn default() -> Self {
-        Self {
-            done: AtomicBool::new(false).into(),
-            event: unsafe { std::mem::zeroed() },
-            task: Default::default(),
-        }
-    }
+    waiter: Option<ngx::async_::Task<()>>,
 }
 
 impl Drop for RequestCTX {
@@ -132,10 +100,6 @@ impl Drop for RequestCTX {
         if let Some(handle) = self.task.take() {
             handle.abort();
         }
-
-        if self.event.posted() != 0 {
-            unsafe { ngx::ffi::ngx_delete_posted_event(&mut self.event) };
-        }
     }
 }
 
@@ -151,7 +115,7 @@ http_request_handler!(async_access_handler, |request: &mut http::Request| {
     if let Some(ctx) =
         unsafe { request.get_module_ctx::<RequestCTX>(&*addr_of!(ngx_http_async_module)) }
     {
-        if !ctx.done.load(Ordering::Relaxed) {
+        if !ctx.done.get() {
             return core::Status::NGX_AGAIN;
         }
 
@@ -165,33 +129,40 @@ http_request_handler!(async_access_handler, |request: &mut http::Request| {
     request.set_module_ctx(ctx.cast(), unsafe { &*addr_of!(ngx_http_async_module) });
 
     let ctx = unsafe { &mut *ctx };
-    ctx.event.handler = Some(check_async_work_done);
-    ctx.event.data = request.connection().cast();
-    ctx.event.log = unsafe { (*request.connection()).log };
-    unsafe { ngx_post_event(&mut ctx.event, addr_of_mut!(ngx_posted_next_events)) };
-
-    // Request is no longer needed and can be converted to something movable to the async block
-    let req = AtomicPtr::new(request.into());
-    let done_flag = ctx.done.clone();
+    let (tx, mut rx) = ngx::async_::channel();
 
     let rt = ngx_http_async_runtime();
     ctx.task = Some(rt.spawn(async move {
         let start = Instant::now();
         tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-        let req = unsafe { http::Request::from_ngx_http_request(req.load(Ordering::Relaxed)) };
-        // not really thread safe, we should apply all these operation in nginx thread
-        // but this is just an example. proper way would be storing these headers in the request ctx
-        // and apply them when we get back to the nginx thread.
-        req.add_header_out(
-            "X-Async-Time",
-            start.elapsed().as_millis().to_string().as_str(),
-        );
-
-        done_flag.store(true, Ordering::Release);
-        // there is a small issue here. If traffic is low we may get stuck behind a 300ms timer
-        // in the nginx event loop. To workaround it we can notify the event loop using
-        // pthread_kill( nginx_thread, SIGIO ) to wake up the event loop. (or patch nginx
-        // and use the same trick as the thread pool)
+        // The request must not be accessed from the tokio threads, send the result to the nginx
+        // thread instead.
+        let _ = tx.send(start.elapsed());
+    }));
+
+    let r: *mut ngx_http_request_t = request.into();
+    ctx.waiter = Some(ngx::async_::spawn(async move {
+        let Some(elapsed) = rx.recv().await else {
+            return;
+        };
+
+        // SAFETY: the task is owned by the request context and is dropped with the request.
+        let request = unsafe { http::Request::from_ngx_http_request(r) };
+        request.add_header_out("X-Async-Time", elapsed.as_millis().to_string().as_str());
+
+        if let Some(ctx) =
+            unsafe { request.get_module_ctx::<RequestCTX>(&*addr_of!(ngx_http_async_module)) }
+        {
+            ctx.done.set(true);
+        }
+
+        // Triggering async_access_handler again
+        unsafe {
+            ngx_post_event(
+                (*request.connection()).write,
+                addr_of_mut!(ngx_posted_events),
+            )
+        };
     }));
 
     core::Status::NGX_AGAIN
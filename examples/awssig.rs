@@ -1,15 +1,16 @@
 use std::ffi::{c_char, c_void};
 
-use http::HeaderMap;
+use hmac::{Hmac, Mac};
 use ngx::core;
 use ngx::ffi::{
-    ngx_array_push, ngx_command_t, ngx_conf_t, ngx_http_handler_pt, ngx_http_module_t,
-    ngx_http_phases_NGX_HTTP_PRECONTENT_PHASE, ngx_int_t, ngx_module_t, ngx_str_t, ngx_uint_t,
+    ngx_command_t, ngx_conf_t, ngx_http_module_t, ngx_int_t, ngx_module_t, ngx_str_t, ngx_uint_t,
     NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE,
     NGX_HTTP_SRV_CONF, NGX_LOG_EMERG,
 };
+use ngx::http::signing::{hex_encode, AwsV4Signer, CanonicalRequest, SigningAlgorithm};
 use ngx::http::*;
 use ngx::{http_request_handler, ngx_conf_log_error, ngx_log_debug_http, ngx_string};
+use sha2::{Digest, Sha256};
 
 struct Module;
 
@@ -21,17 +22,29 @@ impl HttpModule for Module {
     unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
         // SAFETY: this function is called with non-NULL cf always
         let cf = &mut *cf;
-        let cmcf = NgxHttpCoreModule::main_conf_mut(cf).expect("http core main conf");
-
-        let h = ngx_array_push(
-            &mut cmcf.phases[ngx_http_phases_NGX_HTTP_PRECONTENT_PHASE as usize].handlers,
-        ) as *mut ngx_http_handler_pt;
-        if h.is_null() {
-            return core::Status::NGX_ERROR.into();
+        // set a Precontent phase handler
+        match Phases::register(cf, Phase::Precontent, awssigv4_header_handler) {
+            Ok(()) => core::Status::NGX_OK.into(),
+            Err(_) => core::Status::NGX_ERROR.into(),
         }
-        // set an phase handler
-        *h = Some(awssigv4_header_handler);
-        core::Status::NGX_OK.into()
+    }
+}
+
+struct HmacSha256;
+
+impl SigningAlgorithm for HmacSha256 {
+    fn name(&self) -> &str {
+        "HMAC-SHA256"
+    }
+
+    fn hash(&self, data: &[u8]) -> Vec<u8> {
+        Sha256::digest(data).to_vec()
+    }
+
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC key of any size");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
     }
 }
 
@@ -275,61 +288,33 @@ http_request_handler!(awssigv4_header_handler, |request: &mut Request| {
         return core::Status::NGX_DECLINED;
     }
 
-    // TODO: build url properly from the original URL from client
     let method = request.method();
     if !matches!(method, ngx::http::Method::HEAD | ngx::http::Method::GET) {
         return HTTPStatus::FORBIDDEN.into();
     }
 
-    let datetime = chrono::Utc::now();
-    let uri = match request.unparsed_uri().to_str() {
-        Ok(v) => format!("https://{}.{}{}", conf.s3_bucket, conf.s3_endpoint, v),
+    let datetime = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    // GET and HEAD requests have no payload
+    let payload_hash = hex_encode(&HmacSha256.hash(b""));
+
+    // Copy only headers that will be used to sign the request
+    let canonical = match CanonicalRequest::new(method.as_str(), request.unparsed_uri())
+        .headers_from(request.headers_in_iterator(), &["host"])
+    {
+        Ok(canonical) => canonical
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("x-amz-date", &datetime)
+            .payload_hash(&payload_hash),
         Err(_) => return core::Status::NGX_DECLINED,
     };
+    ngx_log_debug_http!(request, "canonical request {:?}", canonical.to_string());
 
-    let datetime_now = datetime.format("%Y%m%dT%H%M%SZ");
-    let datetime_now = datetime_now.to_string();
-
-    let signature = {
-        // NOTE: aws_sign_v4::AwsSign::new() implementation requires a HeaderMap.
-        // Iterate over requests headers_in and copy into HeaderMap
-        // Copy only headers that will be used to sign the request
-        let mut headers = HeaderMap::new();
-        for (name, value) in request.headers_in_iterator() {
-            if let Ok(name) = name.to_str() {
-                if name.to_lowercase() == "host" {
-                    if let Ok(value) = http::HeaderValue::from_bytes(value.as_bytes()) {
-                        headers.insert(http::header::HOST, value);
-                    } else {
-                        return core::Status::NGX_DECLINED;
-                    }
-                }
-            } else {
-                return core::Status::NGX_DECLINED;
-            }
-        }
-        headers.insert("X-Amz-Date", datetime_now.parse().unwrap());
-        ngx_log_debug_http!(request, "headers {:?}", headers);
-        ngx_log_debug_http!(request, "method {:?}", method);
-        ngx_log_debug_http!(request, "uri {:?}", uri);
-        ngx_log_debug_http!(request, "datetime_now {:?}", datetime_now);
-
-        let s = aws_sign_v4::AwsSign::new(
-            method.as_str(),
-            &uri,
-            &datetime,
-            &headers,
-            "us-east-1",
-            conf.access_key.as_str(),
-            conf.secret_key.as_str(),
-            "s3",
-            "",
-        );
-        s.sign()
-    };
+    let signer = AwsV4Signer::new(&conf.access_key, &conf.secret_key, "us-east-1", "s3");
+    let signature = signer.authorization(&HmacSha256, &canonical, &datetime);
 
     request.add_header_in("authorization", signature.as_str());
-    request.add_header_in("X-Amz-Date", datetime_now.as_str());
+    request.add_header_in("X-Amz-Content-SHA256", payload_hash.as_str());
+    request.add_header_in("X-Amz-Date", datetime.as_str());
 
     for (name, value) in request.headers_out_iterator() {
         ngx_log_debug_http!(request, "headers_out {name}: {value}",);
@@ -0,0 +1,176 @@
+use std::ffi::{c_char, c_void};
+use std::fmt::Write;
+
+use ngx::core;
+use ngx::ffi::{
+    ngx_atoi, ngx_command_t, ngx_conf_t, ngx_http_module_t, ngx_int_t, ngx_module_t, ngx_str_t,
+    ngx_uint_t, NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE,
+    NGX_LOG_EMERG,
+};
+use ngx::http::{self, HttpModule, MergeConfigError};
+use ngx::http::{
+    BodyContinuation, BodyGenerator, ChunkWriter, Generated, HttpModuleCtx, HttpModuleLocationConf,
+    NgxHttpCoreModule,
+};
+use ngx::{http_request_handler, ngx_conf_log_error, ngx_log_debug_http, ngx_string};
+
+struct Module;
+
+impl http::HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_csv_module) }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ModuleConfig {
+    rows: Option<u64>,
+}
+
+unsafe impl HttpModuleLocationConf for Module {
+    type LocationConf = ModuleConfig;
+}
+
+unsafe impl HttpModuleCtx for Module {
+    type Ctx = BodyContinuation<CsvRows>;
+}
+
+static mut NGX_HTTP_CSV_COMMANDS: [ngx_command_t; 2] = [
+    ngx_command_t {
+        name: ngx_string!("csv_rows"),
+        type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
+        set: Some(ngx_http_csv_commands_set_rows),
+        conf: NGX_HTTP_LOC_CONF_OFFSET,
+        offset: 0,
+        post: std::ptr::null_mut(),
+    },
+    ngx_command_t::empty(),
+];
+
+static NGX_HTTP_CSV_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
+    preconfiguration: Some(Module::preconfiguration),
+    postconfiguration: Some(Module::postconfiguration),
+    create_main_conf: None,
+    init_main_conf: None,
+    create_srv_conf: None,
+    merge_srv_conf: None,
+    create_loc_conf: Some(Module::create_loc_conf),
+    merge_loc_conf: Some(Module::merge_loc_conf),
+};
+
+// Generate the `ngx_modules` table with exported modules.
+// This feature is required to build a 'cdylib' dynamic module outside of the NGINX buildsystem.
+#[cfg(feature = "export-modules")]
+ngx::ngx_modules!(ngx_http_csv_module);
+
+#[used]
+#[allow(non_upper_case_globals)]
+#[cfg_attr(not(feature = "export-modules"), no_mangle)]
+pub static mut ngx_http_csv_module: ngx_module_t = ngx_module_t {
+    ctx: std::ptr::addr_of!(NGX_HTTP_CSV_MODULE_CTX) as _,
+    commands: unsafe { &NGX_HTTP_CSV_COMMANDS[0] as *const _ as *mut _ },
+    type_: NGX_HTTP_MODULE as _,
+    ..ngx_module_t::default()
+};
+
+impl http::Merge for ModuleConfig {
+    fn merge(&mut self, prev: &ModuleConfig) -> Result<(), MergeConfigError> {
+        if self.rows.is_none() {
+            self.rows = prev.rows;
+        }
+        Ok(())
+    }
+}
+
+/// Generates the rows of a CSV document, one buffer at a time.
+struct CsvRows {
+    header: bool,
+    next: u64,
+    total: u64,
+}
+
+impl BodyGenerator for CsvRows {
+    fn chunk_size(&self) -> usize {
+        65536
+    }
+
+    fn generate(&mut self, _request: &mut http::Request, out: &mut ChunkWriter<'_>) -> Generated {
+        if !self.header {
+            if !out.write(b"id,name,value\n") {
+                return Generated::Error;
+            }
+            self.header = true;
+        }
+
+        while self.next < self.total {
+            let id = self.next + 1;
+            let value = id.wrapping_mul(2654435761) % 1000;
+            // the row is discarded if it does not fit and retried with the next buffer
+            if writeln!(out, "{id},item-{id:08},{value}").is_err() {
+                return Generated::More;
+            }
+            self.next = id;
+        }
+
+        Generated::Done
+    }
+}
+
+http_request_handler!(csv_content_handler, |request: &mut http::Request| {
+    let co = Module::location_conf(request).expect("module config is none");
+    let total = co.rows.unwrap_or(0);
+
+    ngx_log_debug_http!(request, "csv rows: {}", total);
+
+    if request.method() != http::Method::GET && request.method() != http::Method::HEAD {
+        return http::HTTPStatus::NOT_ALLOWED.into();
+    }
+
+    let rc = request.discard_request_body();
+    if rc != core::Status::NGX_OK {
+        return rc;
+    }
+
+    let r = request.as_mut();
+    r.headers_out.content_type = ngx_string!("text/csv");
+    r.headers_out.content_type_len = r.headers_out.content_type.len;
+    r.headers_out.content_length_n = -1;
+
+    request.set_status(http::HTTPStatus::OK);
+    let rc = request.send_header();
+    if rc == core::Status::NGX_ERROR || rc.0 > core::Status::NGX_OK.0 || request.header_only() {
+        return rc;
+    }
+
+    BodyContinuation::start::<Module>(
+        request,
+        CsvRows {
+            header: false,
+            next: 0,
+            total,
+        },
+    )
+});
+
+extern "C" fn ngx_http_csv_commands_set_rows(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    unsafe {
+        let conf = &mut *(conf as *mut ModuleConfig);
+        let args: &[ngx_str_t] = (*(*cf).args).as_slice();
+
+        let rows: ngx_int_t = ngx_atoi(args[1].data, args[1].len);
+        if rows < 0 {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid number of rows");
+            return core::NGX_CONF_ERROR;
+        }
+        conf.rows = Some(rows as u64);
+
+        let clcf = NgxHttpCoreModule::location_conf_mut(&*cf).expect("http core loc conf");
+        clcf.handler = Some(csv_content_handler);
+    };
+
+    core::NGX_CONF_OK
+}
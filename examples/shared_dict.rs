@@ -5,15 +5,15 @@ use ::core::{mem, ptr};
 use nginx_sys::{
     ngx_command_t, ngx_conf_t, ngx_http_add_variable, ngx_http_compile_complex_value_t,
     ngx_http_complex_value, ngx_http_complex_value_t, ngx_http_module_t, ngx_http_request_t,
-    ngx_http_variable_t, ngx_http_variable_value_t, ngx_int_t, ngx_module_t, ngx_parse_size,
-    ngx_shared_memory_add, ngx_shm_zone_t, ngx_str_t, ngx_uint_t, NGX_CONF_TAKE2, NGX_HTTP_DELETE,
-    NGX_HTTP_MAIN_CONF, NGX_HTTP_MAIN_CONF_OFFSET, NGX_HTTP_MODULE, NGX_HTTP_VAR_CHANGEABLE,
-    NGX_HTTP_VAR_NOCACHEABLE, NGX_LOG_EMERG,
+    ngx_http_variable_t, ngx_http_variable_value_t, ngx_int_t, ngx_module_t, ngx_shared_memory_add,
+    ngx_shm_zone_t, ngx_str_t, ngx_uint_t, NGX_CONF_TAKE23, NGX_HTTP_DELETE, NGX_HTTP_MAIN_CONF,
+    NGX_HTTP_MAIN_CONF_OFFSET, NGX_HTTP_MODULE, NGX_HTTP_VAR_CHANGEABLE, NGX_HTTP_VAR_NOCACHEABLE,
+    NGX_LOG_EMERG, NGX_LOG_ERR,
 };
 use ngx::collections::RbTreeMap;
-use ngx::core::{NgxStr, NgxString, Pool, SlabPool, Status, NGX_CONF_ERROR, NGX_CONF_OK};
+use ngx::core::{parse, NgxStr, NgxString, Pool, SlabPool, Status, NGX_CONF_ERROR, NGX_CONF_OK};
 use ngx::http::{HttpModule, HttpModuleMainConf};
-use ngx::{ngx_conf_log_error, ngx_log_debug, ngx_string};
+use ngx::{ngx_conf_log_error, ngx_log_debug, ngx_log_error, ngx_string};
 
 struct HttpSharedDictModule;
 
@@ -43,7 +43,7 @@ unsafe impl HttpModuleMainConf for HttpSharedDictModule {
 static mut NGX_HTTP_SHARED_DICT_COMMANDS: [ngx_command_t; 3] = [
     ngx_command_t {
         name: ngx_string!("shared_dict_zone"),
-        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE2) as ngx_uint_t,
+        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE23) as ngx_uint_t,
         set: Some(ngx_http_shared_dict_add_zone),
         conf: NGX_HTTP_MAIN_CONF_OFFSET,
         offset: 0,
@@ -51,7 +51,7 @@ static mut NGX_HTTP_SHARED_DICT_COMMANDS: [ngx_command_t; 3] = [
     },
     ngx_command_t {
         name: ngx_string!("shared_dict"),
-        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE2) as ngx_uint_t,
+        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE23) as ngx_uint_t,
         set: Some(ngx_http_shared_dict_add_variable),
         conf: NGX_HTTP_MAIN_CONF_OFFSET,
         offset: 0,
@@ -97,19 +97,56 @@ pub static mut ngx_http_shared_dict_module: ngx_module_t = ngx_module_t {
 
 type SharedData = ngx::sync::RwLock<RbTreeMap<NgxString<SlabPool>, NgxString<SlabPool>, SlabPool>>;
 
+/// A shared memory zone declared with the `shared_dict_zone` directive.
 #[derive(Debug)]
-struct SharedDictMainConfig {
+struct SharedDictZone {
     shm_zone: *mut ngx_shm_zone_t,
+    /// The maximum size of a value accepted by the zone.
+    max_value_size: Option<usize>,
+}
+
+impl SharedDictZone {
+    fn shared(&self) -> Result<&SharedData, Status> {
+        // SAFETY: the zone is created by `ngx_shared_memory_add` and lives as long as the cycle.
+        ngx_http_shared_dict_get_shared(unsafe { &mut *self.shm_zone })
+    }
+}
+
+struct SharedDictMainConfig {
+    /// The zones, addressable by name.
+    zones: Option<RbTreeMap<NgxString<Pool>, SharedDictZone, Pool>>,
+    /// The name of the first declared zone, used when the zone is not specified.
+    default_zone: ngx_str_t,
 }
 
 impl Default for SharedDictMainConfig {
     fn default() -> Self {
         Self {
-            shm_zone: ptr::null_mut(),
+            zones: None,
+            default_zone: ngx_str_t::empty(),
         }
     }
 }
 
+impl SharedDictMainConfig {
+    /// Returns the zone with the specified name, or the default zone if the name is empty.
+    fn dict(&self, name: &NgxStr) -> Option<&SharedDictZone> {
+        let name = if name.is_empty() {
+            unsafe { NgxStr::from_ngx_str(self.default_zone) }
+        } else {
+            name
+        };
+
+        self.zones.as_ref()?.get(name)
+    }
+}
+
+/// The data of a variable declared with the `shared_dict` directive.
+struct SharedDictVariable {
+    key: ngx_http_complex_value_t,
+    zone: ngx_str_t,
+}
+
 extern "C" fn ngx_http_shared_dict_add_zone(
     cf: *mut ngx_conf_t,
     _cmd: *mut ngx_command_t,
@@ -122,34 +159,83 @@ extern "C" fn ngx_http_shared_dict_add_zone(
             .as_mut()
             .expect("shared dict main config")
     };
+    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
 
     // SAFETY:
-    // - `cf.args` is guaranteed to be a pointer to an array with 3 elements (NGX_CONF_TAKE2).
+    // - `cf.args` is guaranteed to be a pointer to an array with 3 or 4 elements
+    //   (NGX_CONF_TAKE23).
     // - The pointers are well-aligned by construction method (`ngx_palloc`).
     debug_assert!(!cf.args.is_null() && unsafe { (*cf.args).nelts >= 3 });
-    let args = unsafe { (*cf.args).as_slice_mut() };
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
 
     let name: ngx_str_t = args[1];
-    let size = unsafe { ngx_parse_size(&mut args[2]) };
-    if size == -1 {
+    let Ok(size) = parse::parse_size(args[2].as_bytes()) else {
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid zone size \"{}\"", args[2]);
         return NGX_CONF_ERROR;
+    };
+
+    let mut max_value_size = None;
+
+    for arg in &args[3..] {
+        let Some(value) = arg.as_bytes().strip_prefix(b"max_value_size=") else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid parameter \"{arg}\"");
+            return NGX_CONF_ERROR;
+        };
+
+        let Ok(n) = parse::parse_size(value) else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid parameter \"{arg}\"");
+            return NGX_CONF_ERROR;
+        };
+        max_value_size = Some(n);
     }
 
-    smcf.shm_zone = unsafe {
+    if smcf.zones.is_none() {
+        let Ok(zones) = RbTreeMap::try_new_in(pool.clone()) else {
+            return NGX_CONF_ERROR;
+        };
+        smcf.zones = Some(zones);
+        smcf.default_zone = name;
+    }
+
+    let zones = smcf.zones.as_mut().expect("shared dict zones");
+    let key = unsafe { NgxStr::from_ngx_str(name) };
+
+    if zones.get(key).is_some() {
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "duplicate zone \"{key}\"");
+        return NGX_CONF_ERROR;
+    }
+
+    let shm_zone = unsafe {
         ngx_shared_memory_add(
             cf,
             ptr::addr_of!(name).cast_mut(),
-            size as usize,
+            size,
             ptr::addr_of_mut!(ngx_http_shared_dict_module).cast(),
         )
     };
 
-    let Some(shm_zone) = (unsafe { smcf.shm_zone.as_mut() }) else {
+    if shm_zone.is_null() {
+        return NGX_CONF_ERROR;
+    }
+
+    let Ok(key) = NgxString::try_from_bytes_in(key.as_bytes(), pool) else {
+        return NGX_CONF_ERROR;
+    };
+
+    let zone = SharedDictZone {
+        shm_zone,
+        max_value_size,
+    };
+
+    let Ok(zone) = zones.try_insert(key, zone) else {
         return NGX_CONF_ERROR;
     };
 
-    shm_zone.init = Some(ngx_http_shared_dict_zone_init);
-    shm_zone.data = ptr::from_mut(smcf).cast();
+    // SAFETY: the entries of the tree are not moved on insertion.
+    unsafe {
+        (*shm_zone).init = Some(ngx_http_shared_dict_zone_init);
+        (*shm_zone).data = ptr::from_mut(zone).cast();
+    }
 
     NGX_CONF_OK
 }
@@ -179,6 +265,26 @@ fn ngx_http_shared_dict_get_shared(shm_zone: &mut ngx_shm_zone_t) -> Result<&Sha
     }
 }
 
+/// Returns the zone referenced by a variable.
+fn ngx_http_shared_dict_zone<'a>(
+    r: &'a ngx_http_request_t,
+    zone: &ngx_str_t,
+) -> Option<&'a SharedDictZone> {
+    let smcf = HttpSharedDictModule::main_conf(r).expect("shared dict main config");
+    let name = unsafe { NgxStr::from_ngx_str(*zone) };
+
+    let zone = smcf.dict(name);
+    if zone.is_none() {
+        ngx_log_error!(
+            NGX_LOG_ERR,
+            unsafe { (*r.connection).log },
+            "shared dict: unknown zone \"{}\"",
+            name
+        );
+    }
+    zone
+}
+
 extern "C" fn ngx_http_shared_dict_zone_init(
     shm_zone: *mut ngx_shm_zone_t,
     _data: *mut c_void,
@@ -200,21 +306,26 @@ extern "C" fn ngx_http_shared_dict_add_variable(
     let cf = unsafe { cf.as_mut().unwrap() };
     let mut pool = unsafe { Pool::from_ngx_pool(cf.pool) };
 
-    let key = pool.calloc_type::<ngx_http_complex_value_t>();
-    if key.is_null() {
+    let data = pool.calloc_type::<SharedDictVariable>();
+    if data.is_null() {
         return NGX_CONF_ERROR;
     }
 
     // SAFETY:
-    // - `cf.args` is guaranteed to be a pointer to an array with 3 elements (NGX_CONF_TAKE2).
+    // - `cf.args` is guaranteed to be a pointer to an array with 3 or 4 elements
+    //   (NGX_CONF_TAKE23).
     // - The pointers are well-aligned by construction method (`ngx_palloc`).
     debug_assert!(!cf.args.is_null() && unsafe { (*cf.args).nelts >= 3 });
     let args = unsafe { (*cf.args).as_slice_mut() };
 
+    if let Some(zone) = args.get(3) {
+        unsafe { (*data).zone = *zone };
+    }
+
     let mut ccv: ngx_http_compile_complex_value_t = unsafe { mem::zeroed() };
     ccv.cf = cf;
     ccv.value = &mut args[1];
-    ccv.complex_value = key;
+    ccv.complex_value = unsafe { ptr::addr_of_mut!((*data).key) };
 
     if unsafe { nginx_sys::ngx_http_compile_complex_value(&mut ccv) } != Status::NGX_OK.into() {
         return NGX_CONF_ERROR;
@@ -244,7 +355,7 @@ extern "C" fn ngx_http_shared_dict_add_variable(
     unsafe {
         (*var).get_handler = Some(ngx_http_shared_dict_get_variable);
         (*var).set_handler = Some(ngx_http_shared_dict_set_variable);
-        (*var).data = key as usize;
+        (*var).data = data as usize;
     }
 
     NGX_CONF_OK
@@ -257,16 +368,20 @@ extern "C" fn ngx_http_shared_dict_get_variable(
 ) -> ngx_int_t {
     let r = unsafe { &mut *r };
     let v = unsafe { &mut *v };
-    let smcf = HttpSharedDictModule::main_conf_mut(r).expect("shared dict main config");
+    let data = unsafe { &*(data as *const SharedDictVariable) };
 
     let mut key = ngx_str_t::empty();
-    if unsafe { ngx_http_complex_value(r, data as _, &mut key) } != Status::NGX_OK.into() {
+    if unsafe { ngx_http_complex_value(r, &data.key, &mut key) } != Status::NGX_OK.into() {
         return Status::NGX_ERROR.into();
     }
 
     let key = unsafe { NgxStr::from_ngx_str(key) };
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
+    let Some(zone) = ngx_http_shared_dict_zone(r, &data.zone) else {
+        return Status::NGX_ERROR.into();
+    };
+
+    let Ok(shared) = zone.shared() else {
         return Status::NGX_ERROR.into();
     };
 
@@ -306,14 +421,18 @@ extern "C" fn ngx_http_shared_dict_set_variable(
 ) {
     let r = unsafe { &mut *r };
     let v = unsafe { &mut *v };
-    let smcf = HttpSharedDictModule::main_conf_mut(r).expect("shared dict main config");
+    let data = unsafe { &*(data as *const SharedDictVariable) };
     let mut key = ngx_str_t::empty();
 
-    if unsafe { ngx_http_complex_value(r, data as _, &mut key) } != Status::NGX_OK.into() {
+    if unsafe { ngx_http_complex_value(r, &data.key, &mut key) } != Status::NGX_OK.into() {
         return;
     }
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
+    let Some(zone) = ngx_http_shared_dict_zone(r, &data.zone) else {
+        return;
+    };
+
+    let Ok(shared) = zone.shared() else {
         return;
     };
 
@@ -330,7 +449,20 @@ extern "C" fn ngx_http_shared_dict_set_variable(
 
         let _ = shared.write().remove(key);
     } else {
-        let alloc = unsafe { SlabPool::from_shm_zone(&*smcf.shm_zone).expect("slab pool") };
+        if zone
+            .max_value_size
+            .is_some_and(|max| v.len() as usize > max)
+        {
+            ngx_log_error!(
+                NGX_LOG_ERR,
+                unsafe { (*r.connection).log },
+                "shared dict: value for \"{}\" is too large",
+                unsafe { NgxStr::from_ngx_str(key) }
+            );
+            return;
+        }
+
+        let alloc = unsafe { SlabPool::from_shm_zone(&*zone.shm_zone).expect("slab pool") };
 
         let Ok(key) = NgxString::try_from_bytes_in(key.as_bytes(), alloc.clone()) else {
             return;
@@ -363,14 +495,17 @@ extern "C" fn ngx_http_shared_dict_get_entries(
     let r = unsafe { &mut *r };
     let v = unsafe { &mut *v };
     let pool = unsafe { Pool::from_ngx_pool(r.pool) };
-    let smcf = HttpSharedDictModule::main_conf_mut(r).expect("shared dict main config");
 
     ngx_log_debug!(
         unsafe { (*r.connection).log },
         "shared dict: get all entries"
     );
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
+    let Some(zone) = ngx_http_shared_dict_zone(r, &ngx_str_t::empty()) else {
+        return Status::NGX_ERROR.into();
+    };
+
+    let Ok(shared) = zone.shared() else {
         return Status::NGX_ERROR.into();
     };
 
@@ -422,11 +557,14 @@ extern "C" fn ngx_http_shared_dict_set_entries(
     _data: usize,
 ) {
     let r = unsafe { &mut *r };
-    let smcf = HttpSharedDictModule::main_conf_mut(r).expect("shared dict main config");
 
     ngx_log_debug!(unsafe { (*r.connection).log }, "shared dict: clear");
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
+    let Some(zone) = ngx_http_shared_dict_zone(r, &ngx_str_t::empty()) else {
+        return;
+    };
+
+    let Ok(shared) = zone.shared() else {
         return;
     };
 
@@ -0,0 +1,65 @@
+#!/usr/bin/perl
+
+# (C) Nginx, Inc
+
+# Tests for ngx-rust example modules.
+
+###############################################################################
+
+use warnings;
+use strict;
+
+use Test::More;
+
+BEGIN { use FindBin; chdir($FindBin::Bin); }
+
+use lib 'lib';
+use Test::Nginx;
+
+###############################################################################
+
+select STDERR; $| = 1;
+select STDOUT; $| = 1;
+
+my $t = Test::Nginx->new()->has(qw/http/)->plan(3)
+	->write_file_expand('nginx.conf', <<'EOF');
+
+%%TEST_GLOBALS%%
+
+daemon off;
+
+events {
+}
+
+http {
+    %%TEST_GLOBALS_HTTP%%
+
+    server {
+        listen       127.0.0.1:8080;
+        server_name  localhost;
+
+        location = /small.csv {
+            csv_rows 3;
+        }
+
+        location = /large.csv {
+            csv_rows 200000;
+        }
+    }
+}
+
+EOF
+
+$t->run();
+
+###############################################################################
+
+my $r = http_get('/small.csv');
+like($r, qr!Content-Type: text/csv!, 'content type');
+like($r, qr!\x0d\x0a\x0d\x0aid,name,value\n1,item-00000001,761\n2,item-00000002,522\n3,item-00000003,283\n$!,
+	'small body');
+
+$r = http_get('/large.csv');
+like($r, qr!\n200000,item-00200000,\d+\n$!, 'large body last row');
+
+###############################################################################
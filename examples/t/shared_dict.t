@@ -21,7 +21,7 @@ use Test::Nginx;
 select STDERR; $| = 1;
 select STDOUT; $| = 1;
 
-my $t = Test::Nginx->new()->has(qw/http rewrite/)->plan(12)
+my $t = Test::Nginx->new()->has(qw/http rewrite/)->plan(16)
 	->write_file_expand('nginx.conf', <<'EOF');
 
 %%TEST_GLOBALS%%
@@ -39,11 +39,15 @@ http {
     shared_dict_zone z 64k;
     shared_dict $arg_key $foo;
 
+    shared_dict_zone z2 64k max_value_size=5;
+    shared_dict $arg_key $bar z2;
+
     server {
         listen       127.0.0.1:8080;
         server_name  localhost;
 
         add_header X-Value $foo;
+        add_header X-Value2 $bar;
         add_header X-Process $pid;
 
         location /set/ {
@@ -52,6 +56,12 @@ http {
             return 200;
         }
 
+        location /set2/ {
+            add_header X-Process $pid;
+            set $bar $arg_value;
+            return 200;
+        }
+
         location /entries/ {
             add_header X-Process $pid;
             return 200 $shared_dict_entries;
@@ -81,6 +91,15 @@ ok(check('/?key=snd', qr/X-Value: world/i), 'check value 2');
 like(http_get('/set/?key=fst&value=new_value'), qr/200 OK/, 'update value 1');
 ok(check('/?key=fst', qr/X-Value: new_value/i), 'check updated value');
 
+like(http_get('/set2/?key=fst&value=other'), qr/200 OK/, 'set value in zone 2');
+ok(check('/?key=fst', qr/X-Value: new_value.*X-Value2: other/ims),
+	'check value in zone 2');
+
+http_get('/set2/?key=fst&value=too_long');
+ok(check('/?key=fst', qr/X-Value2: other/i), 'check max value size');
+
+unlike(http_get('/?key=snd'), qr/X-Value2:/i, 'check zones are isolated');
+
 like(http_get('/entries/'), qr/^2; ((?:fst = new_value|snd = world); ){2}$/ms,
 	'get entries');
 
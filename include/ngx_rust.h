@@ -0,0 +1,52 @@
+/*
+ * C interface to the components of the ngx crate.
+ *
+ * The functions are exported by a Rust module built with the "capi" feature.
+ * Panics never unwind into the caller: these are either reported as NGX_ERROR
+ * or abort the process, depending on the panic strategy of the build.
+ * The input buffers are copied, and the output buffers are allocated from the
+ * pool passed by the caller.
+ *
+ * The exported symbols are versioned with the "ngx_rust_capi_v1_" prefix, and
+ * the macros below map the short names to the supported version.
+ */
+
+#ifndef _NGX_RUST_H_INCLUDED_
+#define _NGX_RUST_H_INCLUDED_
+
+
+#include <ngx_config.h>
+#include <ngx_core.h>
+
+
+#define ngx_rust_shared_dict_add     ngx_rust_capi_v1_shared_dict_add
+#define ngx_rust_shared_dict_get     ngx_rust_capi_v1_shared_dict_get
+#define ngx_rust_shared_dict_set     ngx_rust_capi_v1_shared_dict_set
+#define ngx_rust_shared_dict_delete  ngx_rust_capi_v1_shared_dict_delete
+
+
+/*
+ * Shared dictionary of byte strings with optional expiration time.
+ *
+ * A zone declared with a non-zero size defines the dictionary, and a zero size
+ * references a dictionary defined elsewhere in the configuration.
+ * The zones are shared with the Rust modules using the CSharedDict type.
+ */
+
+ngx_shm_zone_t *ngx_rust_shared_dict_add(ngx_conf_t *cf, ngx_str_t *name,
+    size_t size, ngx_module_t *module);
+
+/* NGX_OK, NGX_DECLINED if not found, or NGX_ERROR */
+ngx_int_t ngx_rust_shared_dict_get(ngx_shm_zone_t *zone, u_char *key,
+    size_t key_len, ngx_str_t *value, ngx_pool_t *pool);
+
+/* ttl in milliseconds, 0 for no expiration; NGX_OK or NGX_ERROR */
+ngx_int_t ngx_rust_shared_dict_set(ngx_shm_zone_t *zone, u_char *key,
+    size_t key_len, u_char *value, size_t value_len, ngx_msec_t ttl);
+
+/* NGX_OK, NGX_DECLINED if not found, or NGX_ERROR */
+ngx_int_t ngx_rust_shared_dict_delete(ngx_shm_zone_t *zone, u_char *key,
+    size_t key_len);
+
+
+#endif /* _NGX_RUST_H_INCLUDED_ */
@@ -0,0 +1,161 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use sha2::{Digest, Sha256};
+
+/// Calculates the SHA-256 digest of a file, as a lowercase hex string.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect())
+}
+
+/// The digests of the known source tarballs, used when the signature cannot be verified.
+pub static PINNED: LazyLock<Lockfile> = LazyLock::new(|| {
+    Lockfile::parse(include_str!("../sources.sha256")).expect("valid sources.sha256")
+});
+
+/// A list of expected source archive digests, in the `sha256sum` output format:
+///
+/// ```text
+/// <64 hex digits of the SHA-256 digest>  nginx-1.28.0.tar.gz
+/// ```
+///
+/// The entries are matched by the archive file name.
+pub struct Lockfile {
+    entries: Vec<(String, String)>,
+}
+
+impl Lockfile {
+    /// Reads the lockfile.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|err| {
+            io::Error::new(err.kind(), format!("lockfile {}: {err}", path.display()))
+        })
+    }
+
+    fn parse(data: &str) -> io::Result<Self> {
+        let mut entries = Vec::new();
+
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (digest, name) = line
+                .split_once(char::is_whitespace)
+                .map(|(digest, name)| (digest, name.trim_start().trim_start_matches('*')))
+                .filter(|(digest, name)| {
+                    digest.len() == 64
+                        && digest.bytes().all(|x| x.is_ascii_hexdigit())
+                        && !name.is_empty()
+                })
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid entry {line:?}"),
+                    )
+                })?;
+
+            entries.push((name.to_string(), digest.to_ascii_lowercase()));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Returns the expected digest for the archive file name.
+    pub fn digest(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|x| x.0 == name)
+            .map(|x| x.1.as_str())
+    }
+
+    /// Checks the archive against the expected digest.
+    ///
+    /// Returns `Ok(false)` if the lockfile has no entry for the archive.
+    pub fn verify(&self, archive: &Path) -> io::Result<bool> {
+        let name = archive
+            .file_name()
+            .and_then(|x| x.to_str())
+            .ok_or(io::ErrorKind::InvalidInput)?;
+
+        let Some(expected) = self.digest(name) else {
+            return Ok(false);
+        };
+
+        verify_digest(archive, expected)?;
+        Ok(true)
+    }
+}
+
+/// Checks that the SHA-256 digest of the file matches the `expected` hex string.
+pub fn verify_digest(path: &Path, expected: &str) -> io::Result<()> {
+    let actual = sha256_file(path)?;
+
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "SHA-256 mismatch for {}: expected {expected}, got {actual}",
+                path.display()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lockfile, PINNED};
+
+    #[test]
+    fn test_lockfile() {
+        let digest = "a".repeat(64);
+        let data = format!(
+            "# comment\n\n{digest}  nginx-1.28.0.tar.gz\n{}  *zlib-1.3.1.tar.gz\n",
+            "B".repeat(64)
+        );
+
+        let lockfile = Lockfile::parse(&data).unwrap();
+        assert_eq!(
+            lockfile.digest("nginx-1.28.0.tar.gz"),
+            Some(digest.as_str())
+        );
+        assert_eq!(
+            lockfile.digest("zlib-1.3.1.tar.gz"),
+            Some("b".repeat(64).as_str())
+        );
+        assert_eq!(lockfile.digest("openssl-3.5.0.tar.gz"), None);
+
+        assert!(Lockfile::parse("abcdef  nginx-1.28.0.tar.gz").is_err());
+        assert!(Lockfile::parse(&digest).is_err());
+    }
+
+    #[test]
+    fn test_pinned() {
+        // the bundled list is valid
+        let _ = PINNED.digest("nginx-1.28.0.tar.gz");
+    }
+
+    #[test]
+    #[ignore = "the digests must be added to sources.sha256 from the verified tarballs"]
+    fn test_pinned_defaults() {
+        for name in [
+            "nginx-1.28.0.tar.gz",
+            "openssl-3.2.4.tar.gz",
+            "pcre2-10.42.tar.gz",
+            "zlib-1.3.1.tar.gz",
+        ] {
+            assert!(PINNED.digest(name).is_some(), "missing digest for {name}");
+        }
+    }
+}
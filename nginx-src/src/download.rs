@@ -10,6 +10,7 @@ use std::{env, fs};
 use flate2::read::GzDecoder;
 use tar::Archive;
 
+use crate::checksum::{Lockfile, PINNED};
 use crate::verifier::SignatureVerifier;
 
 const NGINX_URL_PREFIX: &str = "https://nginx.org/download";
@@ -22,6 +23,8 @@ const UBUNTU_KEYSEVER: &str = "hkps://keyserver.ubuntu.com";
 struct SourceSpec<'a> {
     url: fn(&str) -> String,
     variable: &'a str,
+    /// The variable with a path to a local tarball, used instead of downloading.
+    tarball: &'a str,
     signature: &'a str,
     keyserver: &'a str,
     key_ids: &'a [&'a str],
@@ -30,6 +33,7 @@ struct SourceSpec<'a> {
 const NGINX_SOURCE: SourceSpec = SourceSpec {
     url: |version| format!("{NGINX_URL_PREFIX}/nginx-{version}.tar.gz"),
     variable: "NGX_VERSION",
+    tarball: "NGX_SOURCE_TARBALL",
     signature: "asc",
     keyserver: UBUNTU_KEYSEVER,
     key_ids: &[
@@ -57,6 +61,7 @@ const DEPENDENCIES: &[(&str, SourceSpec)] = &[
                 }
             },
             variable: "OPENSSL_VERSION",
+            tarball: "OPENSSL_TARBALL",
             signature: "asc",
             keyserver: UBUNTU_KEYSEVER,
             key_ids: &[
@@ -85,6 +90,7 @@ const DEPENDENCIES: &[(&str, SourceSpec)] = &[
                 }
             },
             variable: "PCRE2_VERSION",
+            tarball: "PCRE2_TARBALL",
             signature: "sig",
             keyserver: UBUNTU_KEYSEVER,
             key_ids: &[
@@ -100,6 +106,7 @@ const DEPENDENCIES: &[(&str, SourceSpec)] = &[
         SourceSpec {
             url: |version| format!("{ZLIB_URL_PREFIX}/v{version}/zlib-{version}.tar.gz"),
             variable: "ZLIB_VERSION",
+            tarball: "ZLIB_TARBALL",
             signature: "asc",
             keyserver: UBUNTU_KEYSEVER,
             key_ids: &[
@@ -116,6 +123,16 @@ static VERIFIER: LazyLock<Option<SignatureVerifier>> = LazyLock::new(|| {
         .ok()
 });
 
+/// The source archive digests from the file specified in `NGX_SOURCE_LOCKFILE`.
+static LOCKFILE: LazyLock<io::Result<Option<Lockfile>>> = LazyLock::new(|| {
+    let Some(path) = env::var_os("NGX_SOURCE_LOCKFILE") else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(path);
+    println!("cargo::rerun-if-changed={}", path.display());
+    Lockfile::open(&path).map(Some)
+});
+
 fn make_cache_dir() -> io::Result<PathBuf> {
     let base_dir = env::var("CARGO_MANIFEST_DIR")
         .map(PathBuf::from)
@@ -157,27 +174,114 @@ fn download(cache_dir: &Path, url: &str) -> Result<PathBuf, Box<dyn StdError + S
     Ok(file_path)
 }
 
+/// Returns the lockfile, if configured.
+fn lockfile() -> io::Result<Option<&'static Lockfile>> {
+    match &*LOCKFILE {
+        Ok(lockfile) => Ok(lockfile.as_ref()),
+        Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+    }
+}
+
+/// Returns a local tarball specified with the `source.tarball` variable.
+///
+/// The tarball is checked against the lockfile, if any; the signature cannot be verified without
+/// a network access.
+fn get_local_archive(source: &SourceSpec) -> io::Result<Option<PathBuf>> {
+    let Some(archive) = env::var_os(source.tarball).map(PathBuf::from) else {
+        return Ok(None);
+    };
+
+    if !archive.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: {} is not a file", source.tarball, archive.display()),
+        ));
+    }
+
+    println!("cargo::rerun-if-changed={}", archive.display());
+
+    if !lockfile()?
+        .map(|x| x.verify(&archive))
+        .transpose()?
+        .unwrap_or(false)
+        && !PINNED.verify(&archive)?
+    {
+        unverified(&archive)?;
+    }
+
+    Ok(Some(archive))
+}
+
+/// Handles an archive that cannot be verified: fails if `NGX_REQUIRE_VERIFICATION` is set to `1`,
+/// `true` or `yes`, and warns otherwise.
+fn unverified(archive: &Path) -> io::Result<()> {
+    let required = env::var("NGX_REQUIRE_VERIFICATION")
+        .is_ok_and(|x| matches!(x.to_ascii_lowercase().as_str(), "1" | "true" | "yes"));
+
+    if required {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "cannot verify {}: no GnuPG, NGX_SOURCE_LOCKFILE entry or known digest, \
+                 and NGX_REQUIRE_VERIFICATION is enabled",
+                archive.display()
+            ),
+        ));
+    }
+
+    println!("cargo::warning=using unverified {}", archive.display());
+    Ok(())
+}
+
 /// Gets a given tarball and signature file from a remote URL and copies it to the `.cache`
 /// directory.
 fn get_archive(cache_dir: &Path, source: &SourceSpec, version: &str) -> io::Result<PathBuf> {
     let archive_url = (source.url)(version);
     let archive = download(cache_dir, &archive_url).map_err(io::Error::other)?;
 
-    if let Some(verifier) = &*VERIFIER {
-        let signature = format!("{archive_url}.{}", source.signature);
+    // A lockfile entry replaces the signature check
+    let locked = match lockfile()?.map(|x| x.verify(&archive)).transpose() {
+        Ok(locked) => locked.unwrap_or(false),
+        Err(err) => {
+            let _ = fs::remove_file(&archive);
+            return Err(err);
+        }
+    };
+
+    if locked {
+        return Ok(archive);
+    }
 
+    let Some(verifier) = &*VERIFIER else {
+        // Without GnuPG, fall back to the digests of the known versions
         let verify = || -> io::Result<()> {
-            let signature = download(cache_dir, &signature).map_err(io::Error::other)?;
-            verifier.import_keys(source.keyserver, source.key_ids)?;
-            verifier.verify_signature(&archive, &signature)?;
+            if !PINNED.verify(&archive)? {
+                unverified(&archive)?;
+            }
             Ok(())
         };
 
         if let Err(err) = verify() {
             let _ = fs::remove_file(&archive);
-            let _ = fs::remove_file(&signature);
             return Err(err);
         }
+
+        return Ok(archive);
+    };
+
+    let signature = format!("{archive_url}.{}", source.signature);
+
+    let verify = || -> io::Result<()> {
+        let signature = download(cache_dir, &signature).map_err(io::Error::other)?;
+        verifier.import_keys(source.keyserver, source.key_ids)?;
+        verifier.verify_signature(&archive, &signature)?;
+        Ok(())
+    };
+
+    if let Err(err) = verify() {
+        let _ = fs::remove_file(&archive);
+        let _ = fs::remove_file(&signature);
+        return Err(err);
     }
 
     Ok(archive)
@@ -231,8 +335,11 @@ pub fn prepare(source_dir: &Path, build_dir: &Path) -> io::Result<(PathBuf, Vec<
     let cache_dir = make_cache_dir()?;
     let mut options = vec![];
 
-    // Download NGINX only if NGX_VERSION is set.
-    let source_dir = if let Ok(version) = env::var(NGINX_SOURCE.variable) {
+    // Use a local NGINX tarball, or download NGINX only if NGX_VERSION is set.
+    let source_dir = if let Some(archive_path) = get_local_archive(&NGINX_SOURCE)? {
+        let output_base_dir: PathBuf = env::var("OUT_DIR").unwrap().into();
+        extract_archive(&archive_path, &output_base_dir)?
+    } else if let Ok(version) = env::var(NGINX_SOURCE.variable) {
         let archive_path = get_archive(&cache_dir, &NGINX_SOURCE, version.as_str())?;
         let output_base_dir: PathBuf = env::var("OUT_DIR").unwrap().into();
         extract_archive(&archive_path, &output_base_dir)?
@@ -241,12 +348,16 @@ pub fn prepare(source_dir: &Path, build_dir: &Path) -> io::Result<(PathBuf, Vec<
     };
 
     for (name, source) in DEPENDENCIES {
-        // Download dependencies if a corresponding DEPENDENCY_VERSION is set.
-        let Ok(requested) = env::var(source.variable) else {
+        // Use local tarballs, or download dependencies if a corresponding DEPENDENCY_VERSION is
+        // set.
+        let archive_path = if let Some(archive_path) = get_local_archive(source)? {
+            archive_path
+        } else if let Ok(requested) = env::var(source.variable) {
+            get_archive(&cache_dir, source, &requested)?
+        } else {
             continue;
         };
 
-        let archive_path = get_archive(&cache_dir, source, &requested)?;
         let output_dir = extract_archive(&archive_path, &extract_output_base_dir)?;
         let output_dir = output_dir.to_string_lossy();
         options.push(format!("--with-{name}={output_dir}"));
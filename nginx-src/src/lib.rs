@@ -2,13 +2,18 @@
 #![warn(missing_docs)]
 
 use std::ffi::OsString;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Output;
 use std::{env, io, thread};
 
+mod checksum;
 mod download;
+mod profile;
 mod verifier;
 
+pub use profile::{BuildProfile, Sanitizer};
+
 static NGINX_DEFAULT_SOURCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/nginx");
 
 const NGINX_BUILD_INFO: &str = "last-build-info";
@@ -25,16 +30,24 @@ static NGINX_CONFIGURE_BASE: &[&str] = &[
     "--with-threads",
 ];
 
-const ENV_VARS_TRIGGERING_RECOMPILE: [&str; 10] = [
+const ENV_VARS_TRIGGERING_RECOMPILE: [&str; 18] = [
     "CACHE_DIR",
     "CARGO_MANIFEST_DIR",
     "CARGO_TARGET_TMPDIR",
     "NGX_CONFIGURE_ARGS",
     "NGX_CFLAGS",
+    "NGX_DEBUG",
     "NGX_LDFLAGS",
+    "NGX_REQUIRE_VERIFICATION",
+    "NGX_SANITIZE",
+    "NGX_SOURCE_LOCKFILE",
+    "NGX_SOURCE_TARBALL",
     "NGX_VERSION",
+    "OPENSSL_TARBALL",
     "OPENSSL_VERSION",
+    "PCRE2_TARBALL",
     "PCRE2_VERSION",
+    "ZLIB_TARBALL",
     "ZLIB_VERSION",
 ];
 
@@ -56,7 +69,13 @@ to do the following:
 
 /// Outputs cargo instructions required for using this crate from a buildscript.
 pub fn print_cargo_metadata() {
-    for file in ["lib.rs", "download.rs", "verifier.rs"] {
+    for file in [
+        "lib.rs",
+        "checksum.rs",
+        "download.rs",
+        "profile.rs",
+        "verifier.rs",
+    ] {
         println!(
             "cargo::rerun-if-changed={path}/src/{file}",
             path = env!("CARGO_MANIFEST_DIR")
@@ -69,61 +88,196 @@ pub fn print_cargo_metadata() {
 }
 
 /// Builds a copy of NGINX sources, either bundled with the crate or downloaded from the network.
+///
+/// The build profile is read from the environment, see [BuildProfile::from_env].
 pub fn build(build_dir: impl AsRef<Path>) -> io::Result<(PathBuf, PathBuf)> {
-    let source_dir = PathBuf::from(NGINX_DEFAULT_SOURCE_DIR);
-    let build_dir = build_dir.as_ref().to_owned();
+    Build::new(build_dir).build()
+}
+
+/// Builds a copy of NGINX sources with the specified build profile.
+pub fn build_with_profile(
+    build_dir: impl AsRef<Path>,
+    profile: &BuildProfile,
+) -> io::Result<(PathBuf, PathBuf)> {
+    Build::new(build_dir).profile(profile.clone()).build()
+}
+
+/// Builder for the NGINX build with additional options.
+///
+/// Example, for a buildscript:
+/// ```rust,no_run
+/// let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+/// let (source_dir, build_dir) = nginx_src::Build::new(out_dir.join("objs"))
+///     .add_module("third-party/ngx_http_echo_module")
+///     .add_dynamic_module("third-party/ngx_http_headers_more_module")
+///     .build()
+///     .expect("nginx-src build");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Build {
+    build_dir: PathBuf,
+    profile: Option<BuildProfile>,
+    /// Module directories and whether the module is dynamic.
+    modules: Vec<(PathBuf, bool)>,
+}
+
+impl Build {
+    /// Creates a build in `build_dir`.
+    pub fn new(build_dir: impl AsRef<Path>) -> Self {
+        Self {
+            build_dir: build_dir.as_ref().to_owned(),
+            profile: None,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Sets the build profile, instead of reading it from the environment.
+    pub fn profile(&mut self, profile: BuildProfile) -> &mut Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Adds a third-party module compiled into the NGINX binary, `--add-module=path`.
+    ///
+    /// `path` is the module directory containing the `config` file.
+    pub fn add_module(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.modules.push((path.as_ref().to_owned(), false));
+        self
+    }
+
+    /// Adds a third-party module compiled as a dynamic module, `--add-dynamic-module=path`.
+    ///
+    /// `path` is the module directory containing the `config` file.
+    pub fn add_dynamic_module(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.modules.push((path.as_ref().to_owned(), true));
+        self
+    }
 
-    let (source_dir, vendored_flags) = download::prepare(&source_dir, &build_dir)?;
+    /// Builds NGINX, returning the source and build directories.
+    ///
+    /// Asks cargo to rerun the buildscript on changes in the module directories. A change of the
+    /// module `config` file reconfigures NGINX.
+    pub fn build(&self) -> io::Result<(PathBuf, PathBuf)> {
+        let profile = match self.profile {
+            Some(ref profile) => profile.clone(),
+            None => BuildProfile::from_env()?,
+        };
+
+        let mut modules = Vec::with_capacity(self.modules.len());
+        for (path, dynamic) in &self.modules {
+            // the configure script is executed from the source directory
+            let path = path.canonicalize().map_err(|err| {
+                io::Error::new(err.kind(), format!("module {}: {err}", path.display()))
+            })?;
+
+            if !path.join("config").is_file() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("module {}: no \"config\" file", path.display()),
+                ));
+            }
+
+            println!("cargo::rerun-if-changed={}", path.display());
+            modules.push((path, *dynamic));
+        }
+
+        let source_dir = PathBuf::from(NGINX_DEFAULT_SOURCE_DIR);
+        let build_dir = self.build_dir.clone();
 
-    let flags = nginx_configure_flags(&vendored_flags);
+        let (source_dir, vendored_flags) = download::prepare(&source_dir, &build_dir)?;
 
-    configure(&source_dir, &build_dir, &flags)?;
+        let mut flags = nginx_configure_flags(&vendored_flags, &profile)?;
+        flags.extend(modules.iter().map(|(path, dynamic)| {
+            let opt = if *dynamic {
+                "--add-dynamic-module"
+            } else {
+                "--add-module"
+            };
+            format!("{opt}={}", path.display())
+        }));
 
-    make(&source_dir, &build_dir, ["build"])?;
+        configure(&source_dir, &build_dir, &profile, &modules, &flags)?;
 
-    Ok((source_dir, build_dir))
+        make(&source_dir, &build_dir, ["build"])?;
+
+        Ok((source_dir, build_dir))
+    }
 }
 
 /// Returns the options NGINX was built with
-fn build_info(source_dir: &Path, configure_flags: &[String]) -> String {
+fn build_info(
+    source_dir: &Path,
+    profile: &BuildProfile,
+    modules: &[(PathBuf, bool)],
+    configure_flags: &[String],
+) -> String {
     // Flags should contain strings pointing to OS/platform as well as dependency versions,
     // so if any of that changes, it can trigger a rebuild
-    format!("{:?}|{}", source_dir, configure_flags.join(" "))
+    let mut info = format!("{:?}|{}|{}", source_dir, profile, configure_flags.join(" "));
+
+    // make tracks the module sources, but the list of sources is in the module config file
+    for (path, _) in modules {
+        let modified = fs::metadata(path.join("config")).and_then(|x| x.modified());
+        info.push_str(&format!("|{:?}:{:?}", path, modified.ok()));
+    }
+
+    info
 }
 
 /// Generate the flags to use with autoconf `configure` for NGINX.
-fn nginx_configure_flags(vendored: &[String]) -> Vec<String> {
+fn nginx_configure_flags(vendored: &[String], profile: &BuildProfile) -> io::Result<Vec<String>> {
     let mut nginx_opts: Vec<String> = NGINX_CONFIGURE_BASE
         .iter()
         .map(|x| String::from(*x))
         .collect();
 
     nginx_opts.extend(vendored.iter().map(Into::into));
+    nginx_opts.extend(profile.configure_args());
 
     if let Ok(extra_args) = env::var("NGX_CONFIGURE_ARGS") {
-        // FIXME: shell style split?
-        nginx_opts.extend(extra_args.split_whitespace().map(Into::into));
+        nginx_opts.extend(split_args(&extra_args)?);
     }
 
-    if let Ok(cflags) = env::var("NGX_CFLAGS") {
-        nginx_opts.push(format!("--with-cc-opt={cflags}"));
+    // configure keeps only the last --with-cc-opt and --with-ld-opt, so the flags are merged
+    let mut cflags = profile.cflags();
+    cflags.extend(env::var("NGX_CFLAGS"));
+    if !cflags.is_empty() {
+        nginx_opts.push(format!("--with-cc-opt={}", cflags.join(" ")));
     }
 
-    if let Ok(ldflags) = env::var("NGX_LDFLAGS") {
-        nginx_opts.push(format!("--with-ld-opt={ldflags}"));
+    let mut ldflags = profile.ldflags();
+    ldflags.extend(env::var("NGX_LDFLAGS"));
+    if !ldflags.is_empty() {
+        nginx_opts.push(format!("--with-ld-opt={}", ldflags.join(" ")));
     }
 
-    nginx_opts
+    Ok(nginx_opts)
+}
+
+/// Splits the arguments with the POSIX shell quoting rules.
+fn split_args(args: &str) -> io::Result<Vec<String>> {
+    shlex::split(args).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("NGX_CONFIGURE_ARGS: unbalanced quotes in {args:?}"),
+        )
+    })
 }
 
 /// Runs external process invoking autoconf `configure` for NGINX.
-fn configure(source_dir: &Path, build_dir: &Path, flags: &[String]) -> io::Result<()> {
-    let build_info = build_info(source_dir, flags);
+fn configure(
+    source_dir: &Path,
+    build_dir: &Path,
+    profile: &BuildProfile,
+    modules: &[(PathBuf, bool)],
+    flags: &[String],
+) -> io::Result<()> {
+    let build_info = build_info(source_dir, profile, modules, flags);
 
     if build_dir.join("Makefile").is_file()
         && build_dir.join(NGINX_BINARY).is_file()
         && matches!(
-            std::fs::read_to_string(build_dir.join(NGINX_BUILD_INFO)).map(|x| x == build_info),
+            fs::read_to_string(build_dir.join(NGINX_BUILD_INFO)).map(|x| x == build_info),
             Ok(true)
         )
     {
@@ -131,7 +285,7 @@ fn configure(source_dir: &Path, build_dir: &Path, flags: &[String]) -> io::Resul
         return Ok(());
     }
 
-    println!("Using NGINX source at {source_dir:?}");
+    println!("Using NGINX source at {source_dir:?}, {profile} profile");
 
     let configure = ["configure", "auto/configure"]
         .into_iter()
@@ -160,7 +314,7 @@ fn configure(source_dir: &Path, build_dir: &Path, flags: &[String]) -> io::Resul
         return Err(io::ErrorKind::Other.into());
     }
 
-    let _ = std::fs::write(build_dir.join(NGINX_BUILD_INFO), build_info);
+    let _ = fs::write(build_dir.join(NGINX_BUILD_INFO), build_info);
 
     Ok(())
 }
@@ -224,3 +378,43 @@ where
 
     Err(io::ErrorKind::NotFound.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::split_args;
+
+    #[test]
+    fn test_split_args() {
+        let split = |x| split_args(x).unwrap();
+
+        assert_eq!(split(""), Vec::<String>::new());
+        assert_eq!(
+            split("  --with-debug\t--with-http_v3_module\n"),
+            ["--with-debug", "--with-http_v3_module"]
+        );
+
+        // quoted arguments
+        assert_eq!(
+            split("--with-cc-opt='-O2 -g' --with-ld-opt=\"-L/opt/ssl/lib -lssl\""),
+            ["--with-cc-opt=-O2 -g", "--with-ld-opt=-L/opt/ssl/lib -lssl"]
+        );
+        assert_eq!(
+            split("'--prefix=/opt/my nginx'"),
+            ["--prefix=/opt/my nginx"]
+        );
+        assert_eq!(
+            split("--with-cc-opt=\"-DNAME='\\\"x\\\"'\""),
+            ["--with-cc-opt=-DNAME='\"x\"'"]
+        );
+
+        // escaped characters
+        assert_eq!(
+            split("--prefix=/opt/my\\ nginx"),
+            ["--prefix=/opt/my nginx"]
+        );
+        assert_eq!(split("--with-cc-opt=-I\\$HOME"), ["--with-cc-opt=-I$HOME"]);
+
+        assert!(split_args("--with-cc-opt='-O2").is_err());
+        assert!(split_args("--with-cc-opt=\"-O2").is_err());
+    }
+}
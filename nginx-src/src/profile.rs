@@ -0,0 +1,123 @@
+use std::{env, fmt, io};
+
+/// A sanitizer instrumenting the NGINX build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Sanitizer {
+    /// AddressSanitizer, `-fsanitize=address`.
+    Address,
+    /// UndefinedBehaviorSanitizer, `-fsanitize=undefined`.
+    Undefined,
+}
+
+impl Sanitizer {
+    /// Returns the sanitizer name as used in the `-fsanitize=` compiler option.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Undefined => "undefined",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "address" | "asan" => Some(Sanitizer::Address),
+            "undefined" | "ubsan" => Some(Sanitizer::Undefined),
+            _ => None,
+        }
+    }
+}
+
+/// Build profile for the vendored NGINX.
+///
+/// The profile adds the configure arguments and the compiler flags on top of the base
+/// configuration. It is recorded in the build info, so switching profiles reconfigures the
+/// existing build directory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BuildProfile {
+    /// Build NGINX `--with-debug`.
+    pub debug: bool,
+    /// The sanitizers to instrument NGINX with.
+    pub sanitizers: Vec<Sanitizer>,
+}
+
+impl BuildProfile {
+    /// Reads the profile from the `NGX_DEBUG` and `NGX_SANITIZE` environment variables.
+    ///
+    /// `NGX_DEBUG` enables the debug build if set to `1`, `true` or `yes`. `NGX_SANITIZE` is a
+    /// comma-separated list of sanitizers: `address` (`asan`) and `undefined` (`ubsan`).
+    pub fn from_env() -> io::Result<Self> {
+        let debug = env::var("NGX_DEBUG")
+            .is_ok_and(|x| matches!(x.to_ascii_lowercase().as_str(), "1" | "true" | "yes"));
+
+        let mut sanitizers = Vec::new();
+
+        if let Ok(value) = env::var("NGX_SANITIZE") {
+            for name in value.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+                let sanitizer = Sanitizer::parse(&name.to_ascii_lowercase()).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("NGX_SANITIZE: unknown sanitizer \"{name}\""),
+                    )
+                })?;
+                sanitizers.push(sanitizer);
+            }
+        }
+
+        sanitizers.sort();
+        sanitizers.dedup();
+
+        Ok(Self { debug, sanitizers })
+    }
+
+    /// Returns the additional configure arguments.
+    pub fn configure_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.debug {
+            args.push("--with-debug".to_string());
+        }
+        args
+    }
+
+    /// Returns the additional C compiler flags.
+    pub fn cflags(&self) -> Vec<String> {
+        if self.sanitizers.is_empty() {
+            return Vec::new();
+        }
+
+        let mut flags = vec![
+            format!("-fsanitize={}", self.sanitizer_list()),
+            "-fno-omit-frame-pointer".to_string(),
+        ];
+
+        if self.sanitizers.contains(&Sanitizer::Address) {
+            // Pass the small allocations to the system allocator, so ASAN can see the pool memory.
+            flags.push("-DNGX_DEBUG_PALLOC=1".to_string());
+        }
+
+        flags
+    }
+
+    /// Returns the additional linker flags.
+    pub fn ldflags(&self) -> Vec<String> {
+        if self.sanitizers.is_empty() {
+            return Vec::new();
+        }
+
+        vec![format!("-fsanitize={}", self.sanitizer_list())]
+    }
+
+    fn sanitizer_list(&self) -> String {
+        let names: Vec<_> = self.sanitizers.iter().map(Sanitizer::name).collect();
+        names.join(",")
+    }
+}
+
+impl fmt::Display for BuildProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.debug { "debug" } else { "release" })?;
+        for sanitizer in &self.sanitizers {
+            write!(f, "+{}", sanitizer.name())?;
+        }
+        Ok(())
+    }
+}
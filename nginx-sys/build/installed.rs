@@ -0,0 +1,216 @@
+//! Support for building against the nginx headers installed from a package.
+//!
+//! OS distributions ship the headers of the packaged nginx build for third-party modules, e.g.
+//! in the `nginx-mod-devel` or `nginx-dev` packages, but not the `Makefile` generated by
+//! `configure`. The compiler flags missing from the headers are recovered from the output of
+//! `nginx -V`, and a synthetic build directory is generated for the rest of the build script.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{BoxError, NginxSource};
+
+/// The locations of the installed nginx headers in the known distributions.
+const NGX_INCLUDE_DIRS: &[&str] = &[
+    "/usr/include/nginx",
+    "/usr/local/include/nginx",
+    "/usr/src/nginx",
+    "/usr/share/nginx/src",
+];
+
+/// Headers generated by `configure` and expected in the build directory.
+const NGX_AUTO_HEADERS: &[&str] = &["ngx_auto_config.h", "ngx_auto_headers.h"];
+
+/// Information about an nginx binary, as reported by `nginx -V`.
+#[derive(Debug, Default)]
+struct NginxBinaryInfo {
+    version: Option<String>,
+    cc_opt: Vec<String>,
+}
+
+impl NginxBinaryInfo {
+    fn from_binary(binary: &Path) -> Result<Self, BoxError> {
+        let output = Command::new(binary)
+            .arg("-V")
+            .output()
+            .map_err(|err| format!("Unable to run nginx binary {}: {err}", binary.display()))?;
+
+        if !output.status.success() {
+            return Err(format!("{} -V failed: {}", binary.display(), output.status).into());
+        }
+
+        // nginx writes the version information to stderr
+        let text = String::from_utf8_lossy(&output.stderr);
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Result<Self, BoxError> {
+        let mut info = Self::default();
+
+        for line in text.lines() {
+            if let Some(version) = line.strip_prefix("nginx version: ") {
+                info.version = Some(version.trim().to_string());
+            } else if let Some(args) = line.strip_prefix("configure arguments:") {
+                let args = shlex::split(args).ok_or("Unable to parse configure arguments")?;
+                for arg in args {
+                    if let Some(opt) = arg.strip_prefix("--with-cc-opt=") {
+                        let opt = shlex::split(opt).ok_or("Unable to parse --with-cc-opt")?;
+                        info.cc_opt.extend(opt);
+                    }
+                }
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+impl NginxSource {
+    /// Configures the build from the installed headers and, optionally, an nginx binary.
+    ///
+    /// `NGINX_INCLUDE_DIR` points to the directory with the installed headers. If not set, the
+    /// well-known locations are checked. `NGINX_BINARY` is the nginx binary to take the compiler
+    /// flags from.
+    pub fn from_installed(include_dir: Option<PathBuf>, binary: Option<PathBuf>) -> Self {
+        Self::try_from_installed(include_dir, binary).expect("installed nginx headers")
+    }
+
+    fn try_from_installed(
+        include_dir: Option<PathBuf>,
+        binary: Option<PathBuf>,
+    ) -> Result<Self, BoxError> {
+        let include_dir = match include_dir {
+            Some(dir) => dunce::canonicalize(&dir)
+                .map_err(|err| format!("Invalid nginx include directory: {dir:?}. {err}"))?,
+            None => NGX_INCLUDE_DIRS
+                .iter()
+                .map(PathBuf::from)
+                .find(|x| find_header(x, "ngx_config.h").is_some())
+                .ok_or("Unable to find installed nginx headers, NGINX_INCLUDE_DIR is not set")?,
+        };
+
+        let info = match binary {
+            Some(binary) => NginxBinaryInfo::from_binary(&binary)?,
+            None => NginxBinaryInfo::default(),
+        };
+
+        let mut dirs = vec![];
+        collect_include_dirs(&include_dir, &mut dirs)?;
+
+        let build_dir = PathBuf::from(env::var("OUT_DIR")?).join("nginx-installed");
+        fs::create_dir_all(&build_dir)?;
+
+        // Copy the configure-generated headers to the synthetic build directory, as expected by
+        // the callers and the dependent crates.
+        for name in NGX_AUTO_HEADERS {
+            let header = find_header(&include_dir, name)
+                .ok_or_else(|| format!("{name} not found in {}", include_dir.display()))?;
+            println!("cargo:rerun-if-changed={}", header.display());
+            fs::copy(&header, build_dir.join(name))?;
+        }
+
+        if let Some(version) = info.version.as_deref() {
+            check_version(&include_dir, version);
+        }
+
+        write_makefile(&build_dir, &dirs, &info.cc_opt)?;
+
+        Ok(Self {
+            source_dir: include_dir,
+            build_dir,
+        })
+    }
+}
+
+/// Recursively finds a header in the directory.
+fn find_header(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
+    if path.is_file() {
+        return Some(path);
+    }
+
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|x| x.file_type().is_ok_and(|t| t.is_dir()))
+        .find_map(|x| find_header(&x.path(), name))
+}
+
+/// Collects all the directories with headers.
+fn collect_include_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) -> Result<(), BoxError> {
+    let mut has_headers = false;
+    let mut subdirs = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            subdirs.push(path);
+        } else if path.extension() == Some(OsStr::new("h")) {
+            has_headers = true;
+        }
+    }
+
+    if has_headers {
+        dirs.push(dir.to_owned());
+    }
+
+    subdirs.sort();
+    for subdir in subdirs {
+        collect_include_dirs(&subdir, dirs)?;
+    }
+
+    Ok(())
+}
+
+/// Warns if the headers do not match the version of the nginx binary.
+fn check_version(include_dir: &Path, version: &str) {
+    let Some(header) = find_header(include_dir, "nginx.h") else {
+        return;
+    };
+    let Ok(contents) = fs::read_to_string(header) else {
+        return;
+    };
+
+    let headers_version = contents.lines().find_map(|line| {
+        let value = line.strip_prefix("#define NGINX_VERSION ")?;
+        Some(value.trim().trim_matches('"').to_string())
+    });
+
+    if let Some(headers_version) = headers_version {
+        if version.strip_prefix("nginx/") != Some(&headers_version) {
+            println!(
+                "cargo:warning=nginx headers version {headers_version} does not match the \
+                 binary version {version}"
+            );
+        }
+    }
+}
+
+/// Writes a `Makefile` with the variables read by [crate::parse_makefile].
+fn write_makefile(build_dir: &Path, dirs: &[PathBuf], cc_opt: &[String]) -> Result<(), BoxError> {
+    let quote = |x: &str| -> Result<String, BoxError> { Ok(shlex::try_quote(x)?.into_owned()) };
+
+    let mut cflags = vec![];
+    for opt in cc_opt {
+        cflags.push(quote(opt)?);
+    }
+
+    let mut incs = vec![];
+    for dir in iter::once(build_dir).chain(dirs.iter().map(PathBuf::as_path)) {
+        let dir = dir.to_str().ok_or("Unicode include paths")?;
+        incs.push(format!("-I {}", quote(dir)?));
+    }
+
+    let mut writer = fs::File::create(build_dir.join("Makefile"))?;
+    writeln!(writer, "CFLAGS = {}", cflags.join(" "))?;
+    writeln!(writer, "ALL_INCS = {}", incs.join(" \\\n\t"))?;
+    writer.flush()?;
+
+    Ok(())
+}
@@ -6,7 +6,15 @@ use std::fs::{read_to_string, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
-const ENV_VARS_TRIGGERING_RECOMPILE: &[&str] = &["OUT_DIR", "NGINX_BUILD_DIR", "NGINX_SOURCE_DIR"];
+mod installed;
+
+const ENV_VARS_TRIGGERING_RECOMPILE: &[&str] = &[
+    "OUT_DIR",
+    "NGINX_BUILD_DIR",
+    "NGINX_SOURCE_DIR",
+    "NGINX_INCLUDE_DIR",
+    "NGINX_BINARY",
+];
 
 /// The feature flags set by the nginx configuration script.
 ///
@@ -24,7 +32,9 @@ const NGX_CONF_FEATURES: &[&str] = &[
     "have_kqueue",
     "have_memalign",
     "have_posix_memalign",
+    "have_sendfile",
     "have_sched_yield",
+    "have_transparent_proxy",
     "have_variadic_macros",
     "http",
     "http_cache",
@@ -37,9 +47,13 @@ const NGX_CONF_FEATURES: &[&str] = &[
     "http_v2",
     "http_v3",
     "http_x_forwarded_for",
+    "ktls",
+    "openssl",
     "pcre",
     "pcre2",
     "quic",
+    "quic_bpf",
+    "quic_openssl_compat",
     "ssl",
     "stream",
     "stream_ssl",
@@ -70,6 +84,8 @@ fn main() -> Result<(), BoxError> {
     }
     println!("cargo:rerun-if-changed=build/main.rs");
     println!("cargo:rerun-if-changed=build/wrapper.h");
+    println!("cargo:rerun-if-changed=build/shim.h");
+    println!("cargo:rerun-if-changed=build/shim.c");
 
     let nginx = NginxSource::from_env();
     println!(
@@ -109,7 +125,15 @@ impl NginxSource {
             (Some(source_dir), Some(build_dir)) => NginxSource::new(source_dir, build_dir),
             (Some(source_dir), None) => Self::from_source_dir(source_dir),
             (None, Some(build_dir)) => Self::from_build_dir(build_dir),
-            _ => Self::from_vendored(),
+            _ => match (
+                env::var_os("NGINX_INCLUDE_DIR"),
+                env::var_os("NGINX_BINARY"),
+            ) {
+                (None, None) => Self::from_vendored(),
+                (include_dir, binary) => {
+                    Self::from_installed(include_dir.map(PathBuf::from), binary.map(PathBuf::from))
+                }
+            },
         }
     }
 
@@ -147,8 +171,8 @@ impl NginxSource {
     #[cfg(not(feature = "vendored"))]
     pub fn from_vendored() -> Self {
         panic!(
-            "\"nginx-sys/vendored\" feature is disabled and neither NGINX_SOURCE_DIR nor \
-             NGINX_BUILD_DIR is set"
+            "\"nginx-sys/vendored\" feature is disabled and none of NGINX_SOURCE_DIR, \
+             NGINX_BUILD_DIR, NGINX_INCLUDE_DIR or NGINX_BINARY is set"
         );
     }
 
@@ -218,6 +242,8 @@ fn generate_binding(nginx: &NginxSource) {
 
     print_cargo_metadata(nginx, &includes, &defines).expect("cargo dependency metadata");
 
+    compile_shim(&includes, &defines).expect("C shim");
+
     // bindgen targets the latest known stable by default
     let rust_target: bindgen::RustTarget = env::var("CARGO_PKG_RUST_VERSION")
         .expect("rust-version set in Cargo.toml")
@@ -249,6 +275,27 @@ fn generate_binding(nginx: &NginxSource) {
         .expect("Couldn't write bindings!");
 }
 
+/// Compiles the wrappers for the NGINX macros and inline functions declared in `build/shim.h`.
+fn compile_shim<T: AsRef<Path>>(
+    includes: &[T],
+    defines: &[(String, Option<String>)],
+) -> Result<(), BoxError> {
+    let mut builder = cc::Build::new();
+
+    builder
+        .includes(includes)
+        .file("build/shim.c")
+        // NGINX headers are not warning-free with the default flags of all compilers
+        .warnings(false);
+
+    for def in defines {
+        builder.define(&def.0, def.1.as_deref());
+    }
+
+    builder.try_compile("ngx_rs_shim")?;
+    Ok(())
+}
+
 /// Reads through the makefile generated by autoconf and finds all of the includes
 /// and definitions used to compile nginx. This is used to generate the correct bindings
 /// for the nginx source code.
@@ -452,6 +499,11 @@ RUST_CONF_STREAM=1
 RUST_CONF_HTTP=1
 #endif
 
+/* SSL_sendfile() with kernel TLS offload, as checked in ngx_ssl_send_chain() */
+#if (NGX_OPENSSL) && defined(BIO_get_ktls_send) && !(NGX_WIN32)
+RUST_CONF_KTLS=1
+#endif
+
 RUST_CONF_NGINX_BUILD=NGINX_VER_BUILD
 RUST_CONF_NGINX_VERSION=NGINX_VER
 RUST_CONF_NGINX_VERSION_NUMBER=nginx_version
@@ -0,0 +1,324 @@
+#include <ngx_config.h>
+#include <ngx_core.h>
+#include <ngx_event.h>
+
+#if defined(__has_include)
+
+#if __has_include(<ngx_http.h>)
+#include <ngx_http.h>
+#endif
+
+#if __has_include(<ngx_stream.h>)
+#include <ngx_stream.h>
+#endif
+
+#else
+#include <ngx_http.h>
+#endif
+
+#include "shim.h"
+
+#if (NGX_LINUX)
+#include <sys/syscall.h>
+#elif (NGX_FREEBSD) || defined(__OpenBSD__)
+#include <pthread.h>
+#include <pthread_np.h>
+#elif !(NGX_WIN32)
+#include <pthread.h>
+#endif
+
+
+ngx_int_t
+ngx_rs_array_init(ngx_array_t *array, ngx_pool_t *pool, ngx_uint_t n,
+    size_t size)
+{
+    return ngx_array_init(array, pool, n, size);
+}
+
+
+ngx_uint_t
+ngx_rs_hash(ngx_uint_t key, u_char c)
+{
+    return ngx_hash(key, c);
+}
+
+
+void *
+ngx_rs_get_conf(void ****conf_ctx, ngx_module_t *module)
+{
+    return ngx_get_conf(conf_ctx, (*module));
+}
+
+
+ngx_atomic_uint_t
+ngx_rs_atomic_cmp_set(ngx_atomic_t *lock, ngx_atomic_uint_t old,
+    ngx_atomic_uint_t set)
+{
+    return ngx_atomic_cmp_set(lock, old, set);
+}
+
+
+ngx_atomic_int_t
+ngx_rs_atomic_fetch_add(ngx_atomic_t *value, ngx_atomic_int_t add)
+{
+    return ngx_atomic_fetch_add(value, add);
+}
+
+
+void
+ngx_rs_memory_barrier(void)
+{
+    ngx_memory_barrier();
+}
+
+
+void
+ngx_rs_cpu_pause(void)
+{
+    ngx_cpu_pause();
+}
+
+
+void
+ngx_rs_queue_insert_head(ngx_queue_t *h, ngx_queue_t *x)
+{
+    ngx_queue_insert_head(h, x);
+}
+
+
+void
+ngx_rs_queue_insert_tail(ngx_queue_t *h, ngx_queue_t *x)
+{
+    ngx_queue_insert_tail(h, x);
+}
+
+
+void
+ngx_rs_queue_remove(ngx_queue_t *x)
+{
+    ngx_queue_remove(x);
+}
+
+
+/*
+ * The main thread of a process runs the event loop and calls the module
+ * handlers; the other threads are started by thread pools and by libraries.
+ */
+
+#if (NGX_LINUX) && defined(SYS_gettid)
+
+ngx_uint_t
+ngx_rs_is_main_thread(void)
+{
+    return syscall(SYS_gettid) == getpid();
+}
+
+#elif (NGX_FREEBSD) || (NGX_DARWIN) || defined(__OpenBSD__)
+
+ngx_uint_t
+ngx_rs_is_main_thread(void)
+{
+    return pthread_main_np() == 1;
+}
+
+#elif (NGX_WIN32)
+
+static volatile LONG  ngx_rs_main_thread;
+
+
+ngx_uint_t
+ngx_rs_is_main_thread(void)
+{
+    LONG  tid;
+
+    /* the first caller is the thread running the event loop */
+
+    tid = (LONG) GetCurrentThreadId();
+
+    InterlockedCompareExchange(&ngx_rs_main_thread, tid, 0);
+
+    return ngx_rs_main_thread == tid;
+}
+
+#else
+
+static pthread_t  ngx_rs_main_thread;
+
+
+/*
+ * Modules are loaded by the main thread of the master process, and worker
+ * processes are forked from that thread.
+ */
+
+static void __attribute__((constructor))
+ngx_rs_main_thread_init(void)
+{
+    ngx_rs_main_thread = pthread_self();
+}
+
+
+ngx_uint_t
+ngx_rs_is_main_thread(void)
+{
+    return pthread_equal(pthread_self(), ngx_rs_main_thread) != 0;
+}
+
+#endif
+
+
+void *
+ngx_rs_event_get_conf(void ****conf_ctx, ngx_module_t *module)
+{
+    return ngx_event_get_conf(conf_ctx, (*module));
+}
+
+
+#ifdef _NGX_HTTP_H_INCLUDED_
+
+void *
+ngx_rs_http_get_module_ctx(ngx_http_request_t *r, ngx_module_t *module)
+{
+    return ngx_http_get_module_ctx(r, (*module));
+}
+
+
+void
+ngx_rs_http_set_ctx(ngx_http_request_t *r, void *c, ngx_module_t *module)
+{
+    ngx_http_set_ctx(r, c, (*module));
+}
+
+
+void *
+ngx_rs_http_get_module_main_conf(ngx_http_request_t *r, ngx_module_t *module)
+{
+    return ngx_http_get_module_main_conf(r, (*module));
+}
+
+
+void *
+ngx_rs_http_get_module_srv_conf(ngx_http_request_t *r, ngx_module_t *module)
+{
+    return ngx_http_get_module_srv_conf(r, (*module));
+}
+
+
+void *
+ngx_rs_http_get_module_loc_conf(ngx_http_request_t *r, ngx_module_t *module)
+{
+    return ngx_http_get_module_loc_conf(r, (*module));
+}
+
+
+void *
+ngx_rs_http_conf_get_module_main_conf(ngx_conf_t *cf, ngx_module_t *module)
+{
+    return ngx_http_conf_get_module_main_conf(cf, (*module));
+}
+
+
+void *
+ngx_rs_http_conf_get_module_srv_conf(ngx_conf_t *cf, ngx_module_t *module)
+{
+    return ngx_http_conf_get_module_srv_conf(cf, (*module));
+}
+
+
+void *
+ngx_rs_http_conf_get_module_loc_conf(ngx_conf_t *cf, ngx_module_t *module)
+{
+    return ngx_http_conf_get_module_loc_conf(cf, (*module));
+}
+
+
+void *
+ngx_rs_http_cycle_get_module_main_conf(ngx_cycle_t *cycle,
+    ngx_module_t *module)
+{
+    return ngx_http_cycle_get_module_main_conf(cycle, (*module));
+}
+
+
+void
+ngx_rs_http_clear_content_length(ngx_http_request_t *r)
+{
+    ngx_http_clear_content_length(r);
+}
+
+
+void
+ngx_rs_http_clear_accept_ranges(ngx_http_request_t *r)
+{
+    ngx_http_clear_accept_ranges(r);
+}
+
+
+void
+ngx_rs_http_clear_last_modified(ngx_http_request_t *r)
+{
+    ngx_http_clear_last_modified(r);
+}
+
+
+void
+ngx_rs_http_clear_location(ngx_http_request_t *r)
+{
+    ngx_http_clear_location(r);
+}
+
+
+void
+ngx_rs_http_clear_etag(ngx_http_request_t *r)
+{
+    ngx_http_clear_etag(r);
+}
+
+#endif
+
+
+#ifdef _NGX_STREAM_H_INCLUDED_
+
+void *
+ngx_rs_stream_get_module_ctx(ngx_stream_session_t *s, ngx_module_t *module)
+{
+    return ngx_stream_get_module_ctx(s, (*module));
+}
+
+
+void
+ngx_rs_stream_set_ctx(ngx_stream_session_t *s, void *c, ngx_module_t *module)
+{
+    ngx_stream_set_ctx(s, c, (*module));
+}
+
+
+void *
+ngx_rs_stream_get_module_main_conf(ngx_stream_session_t *s,
+    ngx_module_t *module)
+{
+    return ngx_stream_get_module_main_conf(s, (*module));
+}
+
+
+void *
+ngx_rs_stream_get_module_srv_conf(ngx_stream_session_t *s,
+    ngx_module_t *module)
+{
+    return ngx_stream_get_module_srv_conf(s, (*module));
+}
+
+
+void *
+ngx_rs_stream_conf_get_module_main_conf(ngx_conf_t *cf, ngx_module_t *module)
+{
+    return ngx_stream_conf_get_module_main_conf(cf, (*module));
+}
+
+
+void *
+ngx_rs_stream_conf_get_module_srv_conf(ngx_conf_t *cf, ngx_module_t *module)
+{
+    return ngx_stream_conf_get_module_srv_conf(cf, (*module));
+}
+
+#endif
@@ -0,0 +1,87 @@
+#ifndef _NGX_RS_SHIM_H_INCLUDED_
+#define _NGX_RS_SHIM_H_INCLUDED_
+
+/*
+ * Callable wrappers for the NGINX macros and static inline functions.
+ *
+ * The declarations are processed by bindgen, and the definitions are
+ * compiled from shim.c with the same configuration as NGINX.
+ */
+
+/* Core */
+
+ngx_int_t ngx_rs_array_init(ngx_array_t *array, ngx_pool_t *pool,
+    ngx_uint_t n, size_t size);
+ngx_uint_t ngx_rs_hash(ngx_uint_t key, u_char c);
+void *ngx_rs_get_conf(void ****conf_ctx, ngx_module_t *module);
+
+ngx_atomic_uint_t ngx_rs_atomic_cmp_set(ngx_atomic_t *lock,
+    ngx_atomic_uint_t old, ngx_atomic_uint_t set);
+ngx_atomic_int_t ngx_rs_atomic_fetch_add(ngx_atomic_t *value,
+    ngx_atomic_int_t add);
+void ngx_rs_memory_barrier(void);
+void ngx_rs_cpu_pause(void);
+
+void ngx_rs_queue_insert_head(ngx_queue_t *h, ngx_queue_t *x);
+void ngx_rs_queue_insert_tail(ngx_queue_t *h, ngx_queue_t *x);
+void ngx_rs_queue_remove(ngx_queue_t *x);
+
+ngx_uint_t ngx_rs_is_main_thread(void);
+
+/* Events */
+
+void *ngx_rs_event_get_conf(void ****conf_ctx, ngx_module_t *module);
+
+/* HTTP */
+
+#ifdef _NGX_HTTP_H_INCLUDED_
+
+void *ngx_rs_http_get_module_ctx(ngx_http_request_t *r, ngx_module_t *module);
+void ngx_rs_http_set_ctx(ngx_http_request_t *r, void *c, ngx_module_t *module);
+
+void *ngx_rs_http_get_module_main_conf(ngx_http_request_t *r,
+    ngx_module_t *module);
+void *ngx_rs_http_get_module_srv_conf(ngx_http_request_t *r,
+    ngx_module_t *module);
+void *ngx_rs_http_get_module_loc_conf(ngx_http_request_t *r,
+    ngx_module_t *module);
+
+void *ngx_rs_http_conf_get_module_main_conf(ngx_conf_t *cf,
+    ngx_module_t *module);
+void *ngx_rs_http_conf_get_module_srv_conf(ngx_conf_t *cf,
+    ngx_module_t *module);
+void *ngx_rs_http_conf_get_module_loc_conf(ngx_conf_t *cf,
+    ngx_module_t *module);
+void *ngx_rs_http_cycle_get_module_main_conf(ngx_cycle_t *cycle,
+    ngx_module_t *module);
+
+void ngx_rs_http_clear_content_length(ngx_http_request_t *r);
+void ngx_rs_http_clear_accept_ranges(ngx_http_request_t *r);
+void ngx_rs_http_clear_last_modified(ngx_http_request_t *r);
+void ngx_rs_http_clear_location(ngx_http_request_t *r);
+void ngx_rs_http_clear_etag(ngx_http_request_t *r);
+
+#endif
+
+/* Stream */
+
+#ifdef _NGX_STREAM_H_INCLUDED_
+
+void *ngx_rs_stream_get_module_ctx(ngx_stream_session_t *s,
+    ngx_module_t *module);
+void ngx_rs_stream_set_ctx(ngx_stream_session_t *s, void *c,
+    ngx_module_t *module);
+
+void *ngx_rs_stream_get_module_main_conf(ngx_stream_session_t *s,
+    ngx_module_t *module);
+void *ngx_rs_stream_get_module_srv_conf(ngx_stream_session_t *s,
+    ngx_module_t *module);
+
+void *ngx_rs_stream_conf_get_module_main_conf(ngx_conf_t *cf,
+    ngx_module_t *module);
+void *ngx_rs_stream_conf_get_module_srv_conf(ngx_conf_t *cf,
+    ngx_module_t *module);
+
+#endif
+
+#endif /* _NGX_RS_SHIM_H_INCLUDED_ */
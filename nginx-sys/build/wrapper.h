@@ -1,6 +1,10 @@
 #include <ngx_config.h>
 #include <ngx_core.h>
 
+#if (NGX_THREADS)
+#include <ngx_thread_pool.h>
+#endif
+
 /* __has_include was a compiler-specific extension until C23,
  * but it's safe to assume that bindgen supports it via libclang.
  */
@@ -18,6 +22,15 @@
 #include <ngx_http.h>
 #endif
 
+/* zlib is linked when the gzip module is enabled */
+#if (NGX_HTTP_GZIP) && defined(__has_include)
+#if __has_include(<zlib.h>)
+#include <zlib.h>
+#endif
+#endif
+
+#include "shim.h"
+
 const char *NGX_RS_MODULE_SIGNATURE = NGX_MODULE_SIGNATURE;
 
 // NGX_ALIGNMENT could be defined as a constant or an expression, with the
@@ -140,6 +140,32 @@ impl ngx_module_t {
             spare_hook7: 0,
         }
     }
+
+    /// Returns the module signature, describing the NGINX build options affecting the ABI.
+    pub fn signature(&self) -> Option<&core::ffi::CStr> {
+        if self.signature.is_null() {
+            return None;
+        }
+        // SAFETY: the signature is a static C string
+        Some(unsafe { core::ffi::CStr::from_ptr(self.signature) })
+    }
+}
+
+impl ngx_cycle_t {
+    /// Returns the modules of the cycle, including the dynamic modules.
+    ///
+    /// The list replaces the global `ngx_modules` array, which contains only the modules built
+    /// into the binary, and `ngx_modules_n`, which is not exported by NGINX.
+    ///
+    /// # Safety
+    ///
+    /// The cycle must be initialized, with the `modules` array allocated by `ngx_cycle_modules`.
+    pub unsafe fn modules(&self) -> &[*mut ngx_module_t] {
+        if self.modules.is_null() {
+            return &[];
+        }
+        core::slice::from_raw_parts(self.modules, self.modules_n)
+    }
 }
 
 impl ngx_variable_value_t {
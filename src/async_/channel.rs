@@ -0,0 +1,248 @@
+use core::error;
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{self, Poll};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::remote;
+
+/// Creates an unbounded channel for sending values from other threads to a task running on the
+/// NGINX event loop.
+///
+/// The [Sender] can be moved to and used from any thread, e.g. a thread of a tokio runtime or a task
+/// of an nginx thread pool. The [Receiver] must stay on the main thread of the worker process.
+///
+/// The wakeups of the receiving task are delivered through a pipe registered in the event loop, so
+/// the task runs as soon as the event loop processes the pipe event. If the pipe cannot be created,
+/// the receiver falls back to polling on every iteration of the event loop.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::async_::{channel, spawn};
+/// let (tx, mut rx) = channel();
+///
+/// std::thread::spawn(move || {
+///     let _ = tx.send(42);
+/// });
+///
+/// spawn(async move {
+///     while let Some(value) = rx.recv().await {
+///         // process the value on the event loop
+///     }
+/// })
+/// .detach();
+/// ```
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            waker: None,
+            senders: 1,
+            closed: false,
+        }),
+    });
+
+    (Sender(shared.clone()), Receiver(shared, PhantomData))
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    waker: Option<task::Waker>,
+    senders: usize,
+    closed: bool,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> State<T> {
+    /// Wakes the receiving task on the event loop.
+    fn wake(&mut self) {
+        // The waker must not be dropped on a foreign thread, as it may own the task.
+        if remote::is_ready() {
+            if let Some(waker) = self.waker.take() {
+                remote::wake(waker);
+            }
+        }
+    }
+}
+
+/// The sending half of a [channel].
+pub struct Sender<T>(Arc<Shared<T>>);
+
+impl<T> Sender<T> {
+    /// Sends a value to the [Receiver].
+    ///
+    /// Returns the value back if the receiver was closed or dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.0.lock();
+        if state.closed {
+            return Err(SendError(value));
+        }
+
+        state.queue.push_back(value);
+        state.wake();
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver was closed or dropped.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a [channel].
+pub struct Receiver<T>(Arc<Shared<T>>, PhantomData<*const ()>);
+
+impl<T> Receiver<T> {
+    /// Receives the next value, waiting until one is sent.
+    ///
+    /// Returns `None` once all the senders are dropped and the queued values are received.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv(self)
+    }
+
+    /// Attempts to receive the next value without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = self.0.lock();
+        match state.queue.pop_front() {
+            Some(value) => Ok(value),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Polls to receive the next value.
+    pub fn poll_recv(&mut self, cx: &mut task::Context<'_>) -> Poll<Option<T>> {
+        // Initialize before registering the waker, so the senders can see the stored waker only if
+        // the wakeup can be delivered.
+        let can_wake = remote::init();
+        let mut state = self.0.lock();
+
+        if let Some(value) = state.queue.pop_front() {
+            return Poll::Ready(Some(value));
+        }
+
+        if state.senders == 0 {
+            return Poll::Ready(None);
+        }
+
+        match state.waker {
+            Some(ref mut waker) => waker.clone_from(cx.waker()),
+            None => state.waker = Some(cx.waker().clone()),
+        }
+        drop(state);
+
+        if !can_wake {
+            cx.waker().wake_by_ref();
+        }
+
+        Poll::Pending
+    }
+
+    /// Closes the receiving half of the channel.
+    ///
+    /// The subsequent attempts to send fail, while the values sent before can still be received.
+    pub fn close(&mut self) {
+        let mut state = self.0.lock();
+        state.closed = true;
+        state.waker = None;
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// Future returned by [Receiver::recv].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, T>(&'a mut Receiver<T>);
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_recv(cx)
+    }
+}
+
+/// Error returned by [Sender::send] when the receiver is closed.
+///
+/// Contains the value that could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a closed channel")
+    }
+}
+
+impl<T> error::Error for SendError<T> {}
+
+/// Error returned by [Receiver::try_recv].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The channel is empty.
+    Empty,
+    /// The channel is empty and all the senders are dropped.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => f.write_str("receiving on a closed channel"),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {}
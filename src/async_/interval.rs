@@ -0,0 +1,135 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::{self, Poll};
+use core::time::Duration;
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use std::boxed::Box;
+
+use nginx_sys::ngx_log_t;
+
+use super::sleep::SleepEvent;
+use crate::event::{timer_msec, NGX_TIMER_DURATION_MAX};
+use crate::ngx_log_debug;
+
+/// Creates an [Interval] yielding a tick every `period`, starting after the first period.
+///
+/// The function is a shorthand for [Interval::new] using the global logger for debug output.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+#[inline]
+pub fn interval(period: Duration) -> Interval {
+    Interval::new(period, crate::log::ngx_cycle_log())
+}
+
+/// A source of periodic ticks for the async tasks.
+///
+/// The timer for the next tick is started as soon as the previous tick completes, so the time spent
+/// by the task between the ticks does not shift the schedule. If a tick is late, e.g. because the
+/// event loop was blocked, the next tick is still scheduled one period after the late tick.
+///
+/// Periods exceeding the maximum timer duration, which is about 24 days on 32-bit platforms, are
+/// waited for in several steps.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::time::Duration;
+/// # use ngx::async_::{interval, spawn};
+/// spawn(async {
+///     let mut ticker = interval(Duration::from_secs(10));
+///     loop {
+///         ticker.tick().await;
+///         // periodic work
+///     }
+/// })
+/// .detach();
+/// ```
+pub struct Interval {
+    timer: Pin<Box<SleepEvent>>,
+    period: Duration,
+    /// The time left until the next tick after the current timer step expires.
+    remaining: Duration,
+}
+
+impl Interval {
+    /// Creates an [Interval] with the specified period and logger for debug messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(period: Duration, log: NonNull<ngx_log_t>) -> Self {
+        assert!(!period.is_zero(), "interval period must be non-zero");
+
+        let timer = Box::pin(SleepEvent::new(log));
+        ngx_log_debug!(timer.event.log(), "async: interval every {period:?}");
+
+        Self {
+            timer,
+            period,
+            remaining: period,
+        }
+    }
+
+    /// Returns the period of the interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Restarts the interval, so the next tick completes one period from now.
+    pub fn reset(&mut self) {
+        self.timer.event.cancel();
+        self.timer.event.reset();
+        self.remaining = self.period;
+    }
+
+    /// Waits until the next tick.
+    pub fn tick(&mut self) -> Tick<'_> {
+        Tick(self)
+    }
+
+    /// Polls for the next tick.
+    ///
+    /// This method can be used to implement a stream of ticks on top of the interval.
+    pub fn poll_tick(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        loop {
+            let step = self.remaining.min(NGX_TIMER_DURATION_MAX);
+
+            if self
+                .timer
+                .as_mut()
+                .poll_sleep(step.as_millis() as _, cx)
+                .is_pending()
+            {
+                return Poll::Pending;
+            }
+
+            self.timer.event.reset();
+            self.remaining = self.remaining.saturating_sub(step);
+
+            if self.remaining.is_zero() {
+                self.remaining = self.period;
+                // Start the timer for the next tick right away, without waiting for the next poll.
+                // SAFETY: the event is pinned
+                unsafe { self.timer.event.add(timer_msec(self.remaining)) };
+                return Poll::Ready(());
+            }
+        }
+    }
+}
+
+/// Future returned by [Interval::tick].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Tick<'a>(&'a mut Interval);
+
+impl Future for Tick<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_tick(cx)
+    }
+}
@@ -1,6 +1,30 @@
 //! Async runtime and set of utilities on top of the NGINX event loop.
+#[cfg(feature = "std")]
+pub use self::channel::{channel, Receiver, Recv, SendError, Sender, TryRecvError};
+pub use self::interval::{interval, Interval, Tick};
+#[cfg(feature = "std")]
+pub use self::notify::{Notified, Notifier};
+pub(crate) use self::peer::SockAddr;
+pub use self::peer::{ConnectOptions, PeerConnection, PeerConnectionError};
+pub use self::semaphore::{
+    ngx_conf_set_concurrency_limit_slot, Acquire, ConcurrencyLimit, Semaphore, SemaphorePermit,
+};
 pub use self::sleep::{sleep, Sleep};
+pub(crate) use self::spawn::spawn_unscheduled;
 pub use self::spawn::{spawn, Task};
+pub use self::supervisor::{is_shutting_down, Supervisor};
 
+pub mod resolver;
+
+#[cfg(feature = "std")]
+mod channel;
+mod interval;
+#[cfg(feature = "std")]
+mod notify;
+mod peer;
+#[cfg(feature = "std")]
+mod remote;
+mod semaphore;
 mod sleep;
 mod spawn;
+mod supervisor;
@@ -0,0 +1,136 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{self, Poll, Waker};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::remote;
+
+/// A notification for waking a task on the NGINX event loop from other threads.
+///
+/// The notifier can be cloned and moved to any thread, e.g. a thread of a tokio runtime or a
+/// callback of a foreign library. A call to [Notifier::notify] wakes the task waiting in
+/// [Notifier::notified], or, if no task is waiting, lets the next wait complete immediately.
+/// Multiple notifications before the wait are coalesced into one.
+///
+/// The wakeups are delivered through the same pipe as with [channel](super::channel), so the task
+/// runs on the next iteration of the event loop instead of waiting for a timer. NGINX's own
+/// `ngx_notify` is not used, as it supports a single handler per process and is reserved for the
+/// thread pools. If the pipe cannot be created, the waiting task falls back to polling on every
+/// iteration of the event loop.
+///
+/// Only one task should wait for the notification at a time.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::async_::{spawn, Notifier};
+/// let notifier = Notifier::new();
+///
+/// let remote = notifier.clone();
+/// std::thread::spawn(move || {
+///     // ... complete the work
+///     remote.notify();
+/// });
+///
+/// spawn(async move {
+///     notifier.notified().await;
+///     // continue on the event loop
+/// })
+/// .detach();
+/// ```
+#[derive(Clone, Default)]
+pub struct Notifier(Arc<Mutex<State>>);
+
+#[derive(Default)]
+struct State {
+    notified: bool,
+    waker: Option<Waker>,
+}
+
+impl Notifier {
+    /// Creates a new notifier.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Notifies the waiting task.
+    ///
+    /// Can be called from any thread.
+    pub fn notify(&self) {
+        let mut state = self.lock();
+        state.notified = true;
+
+        // The waker must not be dropped on a foreign thread, as it may own the task.
+        if remote::is_ready() {
+            if let Some(waker) = state.waker.take() {
+                remote::wake(waker);
+            }
+        }
+    }
+
+    /// Polls for a notification.
+    ///
+    /// Must be called from the main thread of a worker process.
+    pub fn poll_notified(&self, cx: &mut task::Context<'_>) -> Poll<()> {
+        // Initialize before registering the waker, so the notifiers can see the stored waker only
+        // if the wakeup can be delivered.
+        let can_wake = remote::init();
+        let mut state = self.lock();
+
+        if state.notified {
+            state.notified = false;
+            state.waker = None;
+            return Poll::Ready(());
+        }
+
+        match state.waker {
+            Some(ref mut waker) => waker.clone_from(cx.waker()),
+            None => state.waker = Some(cx.waker().clone()),
+        }
+        drop(state);
+
+        if !can_wake {
+            cx.waker().wake_by_ref();
+        }
+
+        Poll::Pending
+    }
+
+    /// Waits for a notification.
+    ///
+    /// Must be awaited on the main thread of a worker process.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified(self)
+    }
+}
+
+impl fmt::Debug for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notifier")
+            .field("notified", &self.lock().notified)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future returned by [Notifier::notified].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Notified<'a>(&'a Notifier);
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_notified(cx)
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        // Release the waker on the event loop thread.
+        self.0.lock().waker = None;
+    }
+}
@@ -0,0 +1,627 @@
+//! Outgoing TCP connections on the NGINX event loop.
+use core::error;
+use core::ffi::c_void;
+use core::fmt;
+use core::future::{self, Future};
+use core::mem;
+use core::net::SocketAddr;
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::{self, Poll, Waker};
+use core::time::Duration;
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use std::boxed::Box;
+
+use nginx_sys::{
+    getsockopt, ngx_add_timer, ngx_addr_t, ngx_close_connection,
+    ngx_connection_log_error_e_NGX_ERROR_ERR, ngx_connection_t, ngx_del_timer, ngx_err_t,
+    ngx_event_connect_peer, ngx_event_get_peer, ngx_event_t, ngx_handle_read_event,
+    ngx_handle_write_event, ngx_log_t, ngx_msec_t, ngx_peer_connection_t, ngx_sock_ntop,
+    ngx_socket_errno, ngx_str_t, sockaddr, sockaddr_in, sockaddr_in6, socklen_t, AF_INET, AF_INET6,
+    NGX_LOG_ERR, SOL_SOCKET, SO_ERROR,
+};
+
+use crate::core::{socket_addr, NgxStr, Status};
+use crate::event::timer_msec;
+use crate::http::LocalAddress;
+use crate::ngx_log_debug;
+
+/// Default timeout for establishing a connection, matching `proxy_connect_timeout`.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Enough to hold a text representation of an IPv6 address with a port.
+const SOCKADDR_STRLEN: usize = 64;
+
+/// An error returned by the [PeerConnection].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PeerConnectionError {
+    /// Failed to establish a connection. The reason has already been logged.
+    Connect,
+    /// The operation timed out.
+    TimedOut,
+    /// An I/O error occurred. The reason has already been logged.
+    Io,
+}
+
+impl error::Error for PeerConnectionError {}
+
+impl fmt::Display for PeerConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerConnectionError::Connect => f.write_str("failed to connect to peer"),
+            PeerConnectionError::TimedOut => f.write_str("peer connection timed out"),
+            PeerConnectionError::Io => f.write_str("peer connection i/o error"),
+        }
+    }
+}
+
+/// An outgoing TCP connection driven by the NGINX event loop.
+///
+/// The connection is established with `ngx_event_connect_peer` and uses the read and write events
+/// of the underlying [ngx_connection_t] to wake the current task. This allows making outbound
+/// calls from a module without running a separate async runtime.
+///
+/// The connection is closed when dropped.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::async_::PeerConnection;
+/// # async fn example() -> Result<(), ngx::async_::PeerConnectionError> {
+/// let log = ngx::log::ngx_cycle_log();
+/// let mut conn = PeerConnection::connect("127.0.0.1:8080".parse().unwrap(), log).await?;
+///
+/// conn.write_all(b"GET / HTTP/1.0\r\n\r\n").await?;
+///
+/// let mut buf = [0u8; 1024];
+/// let n = conn.read(&mut buf).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PeerConnection {
+    state: Box<PeerState>,
+}
+
+impl fmt::Debug for PeerConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerConnection")
+            .field("peer", self.state.name())
+            .finish()
+    }
+}
+
+impl PeerConnection {
+    /// Opens a connection to the specified address.
+    ///
+    /// The connection attempt is aborted after 60 seconds.
+    pub fn connect(
+        addr: SocketAddr,
+        log: NonNull<ngx_log_t>,
+    ) -> impl Future<Output = Result<Self, PeerConnectionError>> {
+        Self::connect_timeout(addr, DEFAULT_CONNECT_TIMEOUT, log)
+    }
+
+    /// Opens a connection to the specified address with a connection timeout.
+    ///
+    /// The connection attempt is aborted if the returned future is dropped before completion.
+    pub fn connect_timeout(
+        addr: SocketAddr,
+        timeout: Duration,
+        log: NonNull<ngx_log_t>,
+    ) -> impl Future<Output = Result<Self, PeerConnectionError>> {
+        Self::connect_with(addr, ConnectOptions::new().timeout(timeout), log)
+    }
+
+    /// Opens a connection to the specified address with the specified options.
+    ///
+    /// The connection attempt is aborted if the returned future is dropped before completion.
+    pub fn connect_with(
+        addr: SocketAddr,
+        options: ConnectOptions,
+        log: NonNull<ngx_log_t>,
+    ) -> impl Future<Output = Result<Self, PeerConnectionError>> {
+        let mut state = PeerState::new(addr, log);
+
+        if let Some(local) = options.local {
+            state.pc.local = local.as_ptr();
+            #[cfg(ngx_feature = "have_transparent_proxy")]
+            state.pc.set_transparent(options.transparent.into());
+            #[cfg(not(ngx_feature = "have_transparent_proxy"))]
+            let _ = options.transparent;
+        }
+
+        Connect {
+            state: Some(state),
+            timeout: timer_msec(options.timeout),
+            started: false,
+        }
+    }
+
+    /// Returns the address of the peer.
+    pub fn peer_addr(&self) -> &NgxStr {
+        self.state.name()
+    }
+
+    /// Returns a raw pointer to the underlying connection.
+    pub fn as_ptr(&self) -> *mut ngx_connection_t {
+        self.state.pc.connection
+    }
+
+    /// Sets the log for the connection and its events.
+    ///
+    /// The log must outlive the connection or be replaced before it is released, e.g. when the
+    /// connection is kept after the request it was created for is finalized.
+    pub fn set_log(&mut self, log: NonNull<ngx_log_t>) {
+        let state = self.state.as_mut();
+        state.pc.log = log.as_ptr();
+
+        let c = state.pc.connection;
+        // SAFETY: the connection is valid until the state is dropped.
+        unsafe {
+            (*c).log = log.as_ptr();
+            (*(*c).read).log = log.as_ptr();
+            (*(*c).write).log = log.as_ptr();
+        }
+    }
+
+    /// Sets the timeout for read operations.
+    ///
+    /// The timer is armed when a read operation cannot complete immediately.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.state.read_timeout = timeout.map(timer_msec);
+    }
+
+    /// Sets the timeout for write operations.
+    ///
+    /// The timer is armed when a write operation cannot complete immediately.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.state.write_timeout = timeout.map(timer_msec);
+    }
+
+    /// Attempts to read data from the connection into `buf`.
+    ///
+    /// Returns the number of bytes read, or 0 if the peer closed the connection.
+    pub fn poll_read(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, PeerConnectionError>> {
+        let state = self.state.as_mut();
+        let c = state.pc.connection;
+
+        // SAFETY: the connection is valid until the state is dropped.
+        unsafe {
+            let rev = (*c).read;
+
+            if (*rev).timedout() != 0 {
+                (*rev).set_timedout(0);
+                return Poll::Ready(Err(PeerConnectionError::TimedOut));
+            }
+
+            let recv = (*c).recv.expect("recv");
+            let n = recv(c, buf.as_mut_ptr(), buf.len());
+
+            if n >= 0 {
+                if (*rev).timer_set() != 0 {
+                    ngx_del_timer(rev);
+                }
+                return Poll::Ready(Ok(n as usize));
+            }
+
+            if n != Status::NGX_AGAIN.into() {
+                return Poll::Ready(Err(PeerConnectionError::Io));
+            }
+
+            if ngx_handle_read_event(rev, 0) != Status::NGX_OK.into() {
+                return Poll::Ready(Err(PeerConnectionError::Io));
+            }
+
+            if let Some(timeout) = state.read_timeout {
+                ngx_add_timer(rev, timeout);
+            }
+        }
+
+        update_waker(&mut state.read_waker, cx.waker());
+        Poll::Pending
+    }
+
+    /// Attempts to write data from `buf` to the connection.
+    ///
+    /// Returns the number of bytes written.
+    pub fn poll_write(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, PeerConnectionError>> {
+        let state = self.state.as_mut();
+        let c = state.pc.connection;
+
+        // SAFETY: the connection is valid until the state is dropped.
+        unsafe {
+            let wev = (*c).write;
+
+            if (*wev).timedout() != 0 {
+                (*wev).set_timedout(0);
+                return Poll::Ready(Err(PeerConnectionError::TimedOut));
+            }
+
+            let send = (*c).send.expect("send");
+            let n = send(c, buf.as_ptr().cast_mut(), buf.len());
+
+            if n >= 0 {
+                if (*wev).timer_set() != 0 {
+                    ngx_del_timer(wev);
+                }
+                return Poll::Ready(Ok(n as usize));
+            }
+
+            if n != Status::NGX_AGAIN.into() {
+                return Poll::Ready(Err(PeerConnectionError::Io));
+            }
+
+            if ngx_handle_write_event(wev, 0) != Status::NGX_OK.into() {
+                return Poll::Ready(Err(PeerConnectionError::Io));
+            }
+
+            if let Some(timeout) = state.write_timeout {
+                ngx_add_timer(wev, timeout);
+            }
+        }
+
+        update_waker(&mut state.write_waker, cx.waker());
+        Poll::Pending
+    }
+
+    /// Reads data from the connection into `buf`.
+    ///
+    /// Returns the number of bytes read, or 0 if the peer closed the connection.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, PeerConnectionError> {
+        future::poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// Writes data from `buf` to the connection.
+    ///
+    /// Returns the number of bytes written.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, PeerConnectionError> {
+        future::poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    /// Writes the whole buffer to the connection.
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> Result<(), PeerConnectionError> {
+        while !buf.is_empty() {
+            let n = self.write(buf).await?;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+}
+
+/// Options for establishing a [PeerConnection].
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::time::Duration;
+/// # use ngx::async_::{ConnectOptions, PeerConnection};
+/// # use ngx::http::Request;
+/// # async fn example(request: &mut Request, bind: *const ngx::ffi::ngx_http_upstream_local_t) {
+/// let mut options = ConnectOptions::new().timeout(Duration::from_secs(5));
+/// if let Ok(Some(local)) = request.local_address(bind) {
+///     options = options.bind(local);
+/// }
+///
+/// let log = core::ptr::NonNull::new(request.log()).unwrap();
+/// let conn = PeerConnection::connect_with("127.0.0.1:8080".parse().unwrap(), options, log).await;
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConnectOptions {
+    timeout: Duration,
+    local: Option<NonNull<ngx_addr_t>>,
+    transparent: bool,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectOptions {
+    /// Creates options with the default connection timeout of 60 seconds.
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_CONNECT_TIMEOUT,
+            local: None,
+            transparent: false,
+        }
+    }
+
+    /// Sets the connection timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the local address to bind the connection to.
+    ///
+    /// The address must remain valid until the connection is established, e.g. be allocated from
+    /// the configuration or the request pool.
+    pub fn local_addr(mut self, addr: NonNull<ngx_addr_t>) -> Self {
+        self.local = Some(addr);
+        self
+    }
+
+    /// Allows binding to a non-local address, e.g. the client address.
+    ///
+    /// Requires the `IP_TRANSPARENT` or an equivalent socket option support and is ignored if
+    /// NGINX is built without it. Usually requires running worker processes with superuser
+    /// privileges.
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    /// Sets the local address and the transparent mode from a configured [LocalAddress].
+    pub fn bind(self, local: LocalAddress) -> Self {
+        // SAFETY: LocalAddress always holds a non-null pointer.
+        let addr = unsafe { NonNull::new_unchecked(local.as_ptr()) };
+        self.local_addr(addr).transparent(local.is_transparent())
+    }
+}
+
+struct PeerState {
+    pc: ngx_peer_connection_t,
+    sockaddr: SockAddr,
+    name: ngx_str_t,
+    name_buf: [u8; SOCKADDR_STRLEN],
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+    read_timeout: Option<ngx_msec_t>,
+    write_timeout: Option<ngx_msec_t>,
+}
+
+#[repr(C)]
+pub(crate) union SockAddr {
+    sin: sockaddr_in,
+    sin6: sockaddr_in6,
+}
+
+impl PeerState {
+    fn new(addr: SocketAddr, log: NonNull<ngx_log_t>) -> Box<Self> {
+        let mut state = Box::new(Self {
+            // SAFETY: plain C structures, all zeroes is a valid value.
+            pc: unsafe { mem::zeroed() },
+            sockaddr: unsafe { mem::zeroed() },
+            name: ngx_str_t::empty(),
+            name_buf: [0; SOCKADDR_STRLEN],
+            read_waker: None,
+            write_waker: None,
+            read_timeout: None,
+            write_timeout: None,
+        });
+
+        let socklen = state.sockaddr.set(&addr);
+
+        let sa = ptr::addr_of_mut!(state.sockaddr).cast::<sockaddr>();
+
+        // SAFETY: the buffer is large enough for any IPv4 or IPv6 address.
+        let len = unsafe {
+            ngx_sock_ntop(
+                sa,
+                socklen,
+                state.name_buf.as_mut_ptr(),
+                state.name_buf.len(),
+                1,
+            )
+        };
+
+        state.name.data = state.name_buf.as_mut_ptr();
+        state.name.len = len;
+
+        let pc = &mut state.pc;
+        pc.sockaddr = sa;
+        pc.socklen = socklen;
+        pc.name = ptr::addr_of_mut!(state.name);
+        pc.get = Some(ngx_event_get_peer);
+        pc.log = log.as_ptr();
+        pc.set_log_error(ngx_connection_log_error_e_NGX_ERROR_ERR as _);
+        pc.tries = 1;
+
+        state
+    }
+
+    fn name(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.name) }
+    }
+
+    /// Installs the event handlers on a freshly created connection.
+    unsafe fn attach(&mut self) {
+        let c = self.pc.connection;
+        (*c).data = ptr::from_mut(self).cast();
+        (*(*c).read).handler = Some(peer_read_handler);
+        (*(*c).write).handler = Some(peer_write_handler);
+    }
+}
+
+impl SockAddr {
+    pub(crate) fn set(&mut self, addr: &SocketAddr) -> socklen_t {
+        match addr {
+            SocketAddr::V4(addr) => {
+                // SAFETY: the union is zero-initialized.
+                let sin = unsafe { &mut self.sin };
+                sin.sin_family = AF_INET as _;
+                sin.sin_port = addr.port().to_be();
+                // SAFETY: in_addr is a 4 byte structure in the network byte order.
+                unsafe {
+                    ptr::addr_of_mut!(sin.sin_addr)
+                        .cast::<[u8; 4]>()
+                        .write(addr.ip().octets())
+                };
+                mem::size_of::<sockaddr_in>() as _
+            }
+            SocketAddr::V6(addr) => {
+                // SAFETY: the union is zero-initialized.
+                let sin6 = unsafe { &mut self.sin6 };
+                sin6.sin6_family = AF_INET6 as _;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo().to_be();
+                sin6.sin6_scope_id = addr.scope_id();
+                // SAFETY: in6_addr is a 16 byte structure in the network byte order.
+                unsafe {
+                    ptr::addr_of_mut!(sin6.sin6_addr)
+                        .cast::<[u8; 16]>()
+                        .write(addr.ip().octets())
+                };
+                mem::size_of::<sockaddr_in6>() as _
+            }
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<SocketAddr> {
+        let socklen = mem::size_of::<Self>() as socklen_t;
+        // SAFETY: the union is large enough for any IPv4 or IPv6 address.
+        unsafe { socket_addr(ptr::from_ref(self).cast(), socklen) }
+    }
+}
+
+impl Drop for PeerState {
+    fn drop(&mut self) {
+        let c = self.pc.connection;
+        if !c.is_null() {
+            ngx_log_debug!(self.pc.log, "async: close peer connection {}", self.name());
+            unsafe { ngx_close_connection(c) };
+            self.pc.connection = ptr::null_mut();
+        }
+    }
+}
+
+struct Connect {
+    state: Option<Box<PeerState>>,
+    timeout: ngx_msec_t,
+    started: bool,
+}
+
+impl Future for Connect {
+    type Output = Result<PeerConnection, PeerConnectionError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        // The future does not rely on pinning: the state shared with NGINX is boxed.
+        let this = self.get_mut();
+        let state = this.state.as_mut().expect("polled after completion");
+
+        if !this.started {
+            this.started = true;
+
+            ngx_log_debug!(state.pc.log, "async: connect to {}", state.name());
+
+            let rc = unsafe { ngx_event_connect_peer(&mut state.pc) };
+
+            if rc == Status::NGX_ERROR.into()
+                || rc == Status::NGX_BUSY.into()
+                || rc == Status::NGX_DECLINED.into()
+            {
+                // ngx_event_connect_peer closes the connection on errors
+                state.pc.connection = ptr::null_mut();
+                return Poll::Ready(Err(PeerConnectionError::Connect));
+            }
+
+            unsafe { state.attach() };
+
+            if rc == Status::NGX_OK.into() {
+                return this.ready();
+            }
+
+            // NGX_AGAIN: the connection is in progress
+            unsafe { ngx_add_timer((*state.pc.connection).write, this.timeout) };
+            update_waker(&mut state.write_waker, cx.waker());
+            return Poll::Pending;
+        }
+
+        let c = state.pc.connection;
+        let wev = unsafe { (*c).write };
+
+        if unsafe { (*wev).timedout() } != 0 {
+            unsafe { crate::log::log_error(NGX_LOG_ERR as _, (*c).log, 0, b"connect timed out") };
+            return Poll::Ready(Err(PeerConnectionError::TimedOut));
+        }
+
+        if unsafe { (*wev).ready() } == 0 {
+            update_waker(&mut state.write_waker, cx.waker());
+            return Poll::Pending;
+        }
+
+        if let Err(err) = unsafe { test_connect(c) } {
+            unsafe {
+                (*c).set_error(1);
+                crate::log::log_error(NGX_LOG_ERR as _, (*c).log, err, b"connect() failed");
+            }
+            return Poll::Ready(Err(PeerConnectionError::Connect));
+        }
+
+        this.ready()
+    }
+}
+
+impl Connect {
+    fn ready(&mut self) -> Poll<Result<PeerConnection, PeerConnectionError>> {
+        let mut state = self.state.take().expect("polled after completion");
+
+        let wev = unsafe { (*state.pc.connection).write };
+        if unsafe { (*wev).timer_set() } != 0 {
+            unsafe { ngx_del_timer(wev) };
+        }
+        state.write_waker = None;
+
+        Poll::Ready(Ok(PeerConnection { state }))
+    }
+}
+
+/// Checks the result of a non-blocking connect.
+unsafe fn test_connect(c: *mut ngx_connection_t) -> Result<(), ngx_err_t> {
+    let mut err: ngx_err_t = 0;
+    let mut len = mem::size_of::<ngx_err_t>() as socklen_t;
+
+    if getsockopt(
+        (*c).fd,
+        SOL_SOCKET as _,
+        SO_ERROR as _,
+        ptr::addr_of_mut!(err).cast::<c_void>(),
+        &mut len,
+    ) == -1
+    {
+        err = ngx_socket_errno();
+    }
+
+    if err != 0 {
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+fn update_waker(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(x) => x.clone_from(waker),
+        None => *slot = Some(waker.clone()),
+    }
+}
+
+unsafe extern "C" fn peer_read_handler(ev: *mut ngx_event_t) {
+    let c: *mut ngx_connection_t = (*ev).data.cast();
+    let state = &mut *(*c).data.cast::<PeerState>();
+
+    if let Some(waker) = state.read_waker.take() {
+        waker.wake();
+    }
+}
+
+unsafe extern "C" fn peer_write_handler(ev: *mut ngx_event_t) {
+    let c: *mut ngx_connection_t = (*ev).data.cast();
+    let state = &mut *(*c).data.cast::<PeerState>();
+
+    if let Some(waker) = state.write_waker.take() {
+        waker.wake();
+    }
+}
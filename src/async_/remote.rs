@@ -0,0 +1,173 @@
+//! Delivery of wakeups from foreign threads to the event loop.
+//!
+//! The wakers of the tasks running on the event loop must not be woken from other threads, as the
+//! scheduler is not thread-safe. Instead, the wakers are queued and a byte is written to a pipe
+//! registered in the event loop of the worker process; the read event handler wakes the queued
+//! tasks on the main thread.
+use core::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+use core::task::Waker;
+use std::sync::{Mutex, PoisonError};
+use std::vec::Vec;
+
+#[cfg(not(unix))]
+use self::fallback::{create, notify};
+#[cfg(unix)]
+use self::unix::{create, notify};
+
+const UNINIT: u8 = 0;
+const READY: u8 = 1;
+const FAILED: u8 = 2;
+
+static STATE: AtomicU8 = AtomicU8::new(UNINIT);
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+static PENDING: Mutex<Vec<Waker>> = Mutex::new(Vec::new());
+
+/// Initializes the wakeup pipe in the current worker process.
+///
+/// Returns `false` if the pipe could not be created, in which case the callers should fall back to
+/// polling. Must be called from the main thread of a worker process.
+pub(crate) fn init() -> bool {
+    match STATE.load(Ordering::Acquire) {
+        READY => return true,
+        FAILED => return false,
+        _ => {}
+    }
+
+    let ok = unsafe { create() }.is_ok();
+    STATE.store(if ok { READY } else { FAILED }, Ordering::Release);
+    ok
+}
+
+/// Returns `true` if the wakeups can be delivered with [wake].
+pub(crate) fn is_ready() -> bool {
+    WRITE_FD.load(Ordering::Acquire) != -1
+}
+
+/// Wakes `waker` on the main thread of the worker process.
+///
+/// Can be called from any thread once the pipe is initialized. Otherwise, the waker is dropped on
+/// the current thread, so the callers should check [is_ready] first.
+pub(crate) fn wake(waker: Waker) {
+    let fd = WRITE_FD.load(Ordering::Acquire);
+    if fd == -1 {
+        return;
+    }
+
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    let was_empty = pending.is_empty();
+    pending.push(waker);
+    drop(pending);
+
+    if was_empty {
+        unsafe { notify(fd) };
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use core::mem;
+    use core::sync::atomic::Ordering;
+    use std::sync::PoisonError;
+
+    use nginx_sys::{
+        close, fcntl, ngx_close_connection, ngx_connection_t, ngx_errno, ngx_event_t,
+        ngx_get_connection, ngx_handle_read_event, pipe, read, write, F_GETFL, F_SETFL,
+        NGX_LOG_ALERT, NGX_OK, O_NONBLOCK,
+    };
+
+    use super::{PENDING, WRITE_FD};
+    use crate::log::{log_error, ngx_cycle_log};
+    use crate::{ngx_log_debug, ngx_log_error};
+
+    pub(super) unsafe fn create() -> Result<(), ()> {
+        let log = ngx_cycle_log().as_ptr();
+        let mut fds = [-1; 2];
+
+        if pipe(fds.as_mut_ptr()) == -1 {
+            log_error(
+                NGX_LOG_ALERT as _,
+                log,
+                ngx_errno(),
+                b"async: pipe() failed",
+            );
+            return Err(());
+        }
+
+        for fd in fds {
+            let flags = fcntl(fd, F_GETFL as _);
+            if flags == -1 || fcntl(fd, F_SETFL as _, flags | O_NONBLOCK as i32) == -1 {
+                log_error(
+                    NGX_LOG_ALERT as _,
+                    log,
+                    ngx_errno(),
+                    b"async: fcntl() failed",
+                );
+                close(fds[0]);
+                close(fds[1]);
+                return Err(());
+            }
+        }
+
+        let c = ngx_get_connection(fds[0], log);
+        if c.is_null() {
+            close(fds[0]);
+            close(fds[1]);
+            return Err(());
+        }
+
+        let rev = (*c).read;
+        (*rev).handler = Some(read_handler);
+        (*rev).log = log;
+        // Like the master process channel, the pipe stays open until the worker exits.
+        (*rev).set_channel(1);
+
+        if ngx_handle_read_event(rev, 0) != NGX_OK as _ {
+            ngx_close_connection(c);
+            close(fds[1]);
+            return Err(());
+        }
+
+        WRITE_FD.store(fds[1], Ordering::Release);
+        Ok(())
+    }
+
+    pub(super) unsafe fn notify(fd: i32) {
+        // A full pipe already guarantees that the read handler will run, so the errors are ignored.
+        let _ = write(fd, [1u8].as_ptr().cast(), 1);
+    }
+
+    unsafe extern "C" fn read_handler(ev: *mut ngx_event_t) {
+        let c: *mut ngx_connection_t = (*ev).data.cast();
+        let mut buf = [0u8; 64];
+
+        while read((*c).fd, buf.as_mut_ptr().cast(), buf.len()) > 0 {}
+
+        let pending = mem::take(&mut *PENDING.lock().unwrap_or_else(PoisonError::into_inner));
+        ngx_log_debug!(
+            (*ev).log,
+            "async: processing {} remote wakeups",
+            pending.len()
+        );
+
+        for waker in pending {
+            waker.wake();
+        }
+
+        if ngx_handle_read_event(ev, 0) != NGX_OK as _ {
+            ngx_log_error!(
+                NGX_LOG_ALERT,
+                (*ev).log,
+                "async: failed to register wakeup pipe"
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    pub(super) unsafe fn create() -> Result<(), ()> {
+        Err(())
+    }
+
+    pub(super) unsafe fn notify(_fd: i32) {}
+}
@@ -0,0 +1,237 @@
+//! Asynchronous name resolution with the NGINX resolver.
+//!
+//! See <https://nginx.org/en/docs/http/ngx_http_core_module.html#resolver>.
+use core::error;
+use core::ffi::{c_char, CStr};
+use core::fmt;
+use core::future::Future;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::{self, Poll, Waker};
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "std")]
+use std::{boxed::Box, vec::Vec};
+
+use nginx_sys::{
+    ngx_int_t, ngx_msec_t, ngx_resolve_name, ngx_resolve_name_done, ngx_resolve_start,
+    ngx_resolver_addr_t, ngx_resolver_ctx_t, ngx_resolver_strerror, ngx_resolver_t, sockaddr,
+    sockaddr_in, sockaddr_in6, AF_INET, AF_INET6,
+};
+
+use crate::core::Status;
+use crate::ngx_log_debug;
+
+/// `ngx_resolve_start` returns this value if the resolver is not configured.
+const NGX_NO_RESOLVER: *mut ngx_resolver_ctx_t = usize::MAX as _;
+
+/// An error returned by the [Resolver].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResolverError {
+    /// The resolver is not configured.
+    NoResolver,
+    /// Failed to start resolving the name.
+    Start,
+    /// Name resolution failed with the specified NGINX resolver error code.
+    Failed(ngx_int_t),
+}
+
+impl error::Error for ResolverError {}
+
+impl fmt::Display for ResolverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolverError::NoResolver => f.write_str("no resolver defined"),
+            ResolverError::Start => f.write_str("failed to start name resolution"),
+            ResolverError::Failed(code) => {
+                // SAFETY: ngx_resolver_strerror always returns a static nul-terminated string.
+                let msg = unsafe { CStr::from_ptr(ngx_resolver_strerror(*code).cast::<c_char>()) };
+                write!(f, "resolver error: {}", msg.to_str().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Async wrapper over the [ngx_resolver_t].
+///
+/// The resolver is usually obtained from the core module configuration, e.g.
+/// `ngx_http_core_loc_conf_t.resolver` and `ngx_http_core_loc_conf_t.resolver_timeout`.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    resolver: NonNull<ngx_resolver_t>,
+    timeout: ngx_msec_t,
+}
+
+impl Resolver {
+    /// Creates a new resolver wrapper.
+    ///
+    /// # Safety
+    ///
+    /// `resolver` must be a valid pointer to an initialized resolver that outlives this wrapper
+    /// and all the futures created with it.
+    pub unsafe fn from_resolver(resolver: NonNull<ngx_resolver_t>, timeout: ngx_msec_t) -> Self {
+        Self { resolver, timeout }
+    }
+
+    /// Resolves the host name into a list of addresses.
+    ///
+    /// The port in the returned addresses is always set to 0. The resolution is cancelled if the
+    /// returned future is dropped before completion.
+    pub fn resolve_name(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Vec<SocketAddr>, ResolverError>> {
+        Resolution {
+            resolver: self.resolver,
+            timeout: self.timeout,
+            name: name.as_bytes().into(),
+            state: None,
+        }
+    }
+}
+
+struct Resolution {
+    resolver: NonNull<ngx_resolver_t>,
+    timeout: ngx_msec_t,
+    // The name is passed to NGINX and must not move until the resolution is complete.
+    name: Box<[u8]>,
+    state: Option<Box<ResolutionState>>,
+}
+
+struct ResolutionState {
+    ctx: Option<NonNull<ngx_resolver_ctx_t>>,
+    result: Option<Result<Vec<SocketAddr>, ResolverError>>,
+    waker: Option<Waker>,
+}
+
+impl Resolution {
+    fn start(&mut self, waker: &Waker) -> Result<(), ResolverError> {
+        let ctx = unsafe { ngx_resolve_start(self.resolver.as_ptr(), ptr::null_mut()) };
+        if ctx == NGX_NO_RESOLVER {
+            return Err(ResolverError::NoResolver);
+        }
+        let mut ctx = NonNull::new(ctx).ok_or(ResolverError::Start)?;
+
+        let state = self.state.insert(Box::new(ResolutionState {
+            ctx: Some(ctx),
+            result: None,
+            waker: Some(waker.clone()),
+        }));
+        let data: *mut ResolutionState = ptr::from_mut(state.as_mut());
+
+        // SAFETY: ctx is a valid pointer returned by ngx_resolve_start.
+        unsafe {
+            let ctx = ctx.as_mut();
+            ctx.name.len = self.name.len();
+            ctx.name.data = self.name.as_mut_ptr();
+            ctx.handler = Some(resolve_handler);
+            ctx.data = data.cast();
+            ctx.timeout = self.timeout;
+        }
+
+        // The handler can be invoked synchronously, e.g. for cached names.
+        if unsafe { ngx_resolve_name(ctx.as_ptr()) } != Status::NGX_OK.into() {
+            // ngx_resolve_name frees the context on error
+            if let Some(state) = self.state.as_mut() {
+                state.ctx = None;
+            }
+            return Err(ResolverError::Start);
+        }
+
+        Ok(())
+    }
+}
+
+impl Future for Resolution {
+    type Output = Result<Vec<SocketAddr>, ResolverError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        // The future does not rely on pinning: the state shared with NGINX is boxed.
+        let this = self.get_mut();
+
+        let Some(state) = this.state.as_mut() else {
+            if let Err(err) = this.start(cx.waker()) {
+                return Poll::Ready(Err(err));
+            }
+            return this.poll_result();
+        };
+
+        match state.waker.as_mut() {
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => state.waker = Some(cx.waker().clone()),
+        }
+
+        this.poll_result()
+    }
+}
+
+impl Resolution {
+    fn poll_result(&mut self) -> Poll<Result<Vec<SocketAddr>, ResolverError>> {
+        match self.state.as_mut().and_then(|x| x.result.take()) {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Resolution {
+    fn drop(&mut self) {
+        if let Some(ctx) = self.state.as_mut().and_then(|x| x.ctx.take()) {
+            // Cancels pending resolution and releases the context.
+            unsafe { ngx_resolve_name_done(ctx.as_ptr()) };
+        }
+    }
+}
+
+unsafe extern "C" fn resolve_handler(ctx: *mut ngx_resolver_ctx_t) {
+    let state = &mut *(*ctx).data.cast::<ResolutionState>();
+
+    let result = if (*ctx).state == 0 {
+        let addrs = if (*ctx).naddrs > 0 {
+            core::slice::from_raw_parts((*ctx).addrs, (*ctx).naddrs)
+        } else {
+            &[]
+        };
+        Ok(addrs.iter().filter_map(|x| to_socket_addr(x)).collect())
+    } else {
+        Err(ResolverError::Failed((*ctx).state))
+    };
+
+    ngx_log_debug!(
+        (*(*ctx).resolver).log,
+        "async: resolved \"{}\": {:?}",
+        crate::core::NgxStr::from_ngx_str((*ctx).name),
+        result
+    );
+
+    state.result = Some(result);
+    if let Some(ctx) = state.ctx.take() {
+        ngx_resolve_name_done(ctx.as_ptr());
+    }
+
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+}
+
+unsafe fn to_socket_addr(addr: &ngx_resolver_addr_t) -> Option<SocketAddr> {
+    let sa: *const sockaddr = addr.sockaddr;
+
+    let ip = match u32::from((*sa).sa_family) {
+        AF_INET => {
+            let sin = sa.cast::<sockaddr_in>();
+            let s_addr = ptr::addr_of!((*sin).sin_addr).cast::<[u8; 4]>().read();
+            IpAddr::V4(Ipv4Addr::from(s_addr))
+        }
+        AF_INET6 => {
+            let sin6 = sa.cast::<sockaddr_in6>();
+            let s6_addr = ptr::addr_of!((*sin6).sin6_addr).cast::<[u8; 16]>().read();
+            IpAddr::V6(Ipv6Addr::from(s6_addr))
+        }
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(ip, 0))
+}
@@ -0,0 +1,490 @@
+use core::cell::RefCell;
+use core::ffi::{c_char, c_void};
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{self, Poll, Waker};
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::collections::vec_deque::VecDeque;
+#[cfg(feature = "std")]
+use std::collections::vec_deque::VecDeque;
+
+use crate::core::NGX_CONF_OK;
+use crate::ffi::{ngx_atoi, ngx_command_t, ngx_conf_t, ngx_str_t};
+
+/// Asynchronous counting semaphore for the tasks running on the NGINX event loop.
+///
+/// Waiting tasks are granted permits in FIFO order. A released permit is handed over directly to
+/// the first waiting task, so that new callers cannot overtake the queue.
+///
+/// The semaphore is not thread-safe and the state is local to a worker process. A semaphore stored
+/// in a module configuration limits the concurrency within each worker independently. See
+/// [ConcurrencyLimit] for a limit configured with a directive.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::async_::Semaphore;
+/// struct ModuleConfig {
+///     // created in `create_loc_conf` with the configured limit
+///     limit: Semaphore,
+/// }
+///
+/// async fn handle(conf: &ModuleConfig) {
+///     let _permit = conf.limit.acquire().await;
+///     // expensive work
+/// }
+/// ```
+pub struct Semaphore {
+    inner: RefCell<SemaphoreInner>,
+}
+
+struct SemaphoreInner {
+    permits: usize,
+    next_id: usize,
+    waiters: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    id: usize,
+    granted: bool,
+    waker: Option<Waker>,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the specified number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            inner: RefCell::new(SemaphoreInner {
+                permits,
+                next_id: 0,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of permits available for immediate acquisition.
+    pub fn available_permits(&self) -> usize {
+        self.inner.borrow().permits
+    }
+
+    /// Returns the number of tasks waiting for a permit.
+    pub fn waiters(&self) -> usize {
+        let inner = self.inner.borrow();
+        inner.waiters.iter().filter(|x| !x.granted).count()
+    }
+
+    /// Adds permits to the semaphore, waking the waiting tasks if necessary.
+    pub fn add_permits(&self, n: usize) {
+        for _ in 0..n {
+            self.release();
+        }
+    }
+
+    /// Attempts to acquire a permit without waiting.
+    ///
+    /// Fails if there are no permits available or if there are other tasks waiting.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.permits == 0 || !inner.waiters.is_empty() {
+            return None;
+        }
+        inner.permits -= 1;
+        Some(SemaphorePermit { sem: self })
+    }
+
+    /// Acquires a permit, waiting until one becomes available.
+    ///
+    /// Dropping the returned future removes the task from the waiting queue.
+    pub fn acquire(&self) -> Acquire<'_> {
+        Acquire {
+            sem: self,
+            id: None,
+        }
+    }
+
+    /// Runs the future with a permit acquired from the semaphore.
+    ///
+    /// The permit is released once the future completes or is dropped.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        let _permit = self.acquire().await;
+        future.await
+    }
+
+    fn release(&self) {
+        let waker = {
+            let mut inner = self.inner.borrow_mut();
+            match inner.waiters.iter_mut().find(|x| !x.granted) {
+                Some(waiter) => {
+                    waiter.granted = true;
+                    waiter.waker.take()
+                }
+                None => {
+                    inner.permits += 1;
+                    None
+                }
+            }
+        };
+
+        // Wake outside of the borrow: the waker may poll the task synchronously.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("permits", &self.available_permits())
+            .field("waiters", &self.waiters())
+            .finish()
+    }
+}
+
+/// A permit acquired from the [Semaphore].
+///
+/// The permit is returned to the semaphore when dropped.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    sem: &'a Semaphore,
+}
+
+impl SemaphorePermit<'_> {
+    /// Releases the permit without returning it to the semaphore, reducing the number of permits.
+    pub fn forget(self) {
+        core::mem::forget(self)
+    }
+}
+
+impl fmt::Debug for SemaphorePermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphorePermit").finish_non_exhaustive()
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.sem.release();
+    }
+}
+
+/// Future returned by [Semaphore::acquire].
+pub struct Acquire<'a> {
+    sem: &'a Semaphore,
+    id: Option<usize>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut inner = this.sem.inner.borrow_mut();
+
+        let Some(id) = this.id else {
+            if inner.permits > 0 && inner.waiters.is_empty() {
+                inner.permits -= 1;
+                return Poll::Ready(SemaphorePermit { sem: this.sem });
+            }
+
+            let id = inner.next_id;
+            inner.next_id = inner.next_id.wrapping_add(1);
+            inner.waiters.push_back(Waiter {
+                id,
+                granted: false,
+                waker: Some(cx.waker().clone()),
+            });
+            this.id = Some(id);
+            return Poll::Pending;
+        };
+
+        let pos = inner
+            .waiters
+            .iter()
+            .position(|x| x.id == id)
+            .expect("semaphore waiter");
+
+        if inner.waiters[pos].granted {
+            inner.waiters.remove(pos);
+            this.id = None;
+            return Poll::Ready(SemaphorePermit { sem: this.sem });
+        }
+
+        match inner.waiters[pos].waker.as_mut() {
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => inner.waiters[pos].waker = Some(cx.waker().clone()),
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+
+        let granted = {
+            let mut inner = self.sem.inner.borrow_mut();
+            let pos = inner.waiters.iter().position(|x| x.id == id);
+            pos.and_then(|pos| inner.waiters.remove(pos))
+                .is_some_and(|x| x.granted)
+        };
+
+        // Pass the permit granted to this waiter to the next one.
+        if granted {
+            self.sem.release();
+        }
+    }
+}
+
+/// A concurrency limit for the asynchronous work, configured with a directive.
+///
+/// The directive takes the number of concurrent tasks or `off`, e.g.
+/// `example_concurrency 16;`. Each configuration level with the directive owns a [Semaphore]
+/// with the configured number of permits. A level inheriting the limit with
+/// [ConcurrencyLimit::merge] gets a semaphore of its own, so that the requests in each location
+/// are limited independently. As with the [Semaphore], the limit applies within each worker
+/// process.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::async_::{ngx_conf_set_concurrency_limit_slot, ConcurrencyLimit};
+/// # use ngx::ffi::{ngx_command_t, NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET};
+/// # use ngx::http::{Merge, MergeConfigError};
+/// # use ngx::ngx_string;
+/// #[derive(Debug, Default)]
+/// struct LocConfig {
+///     concurrency: ConcurrencyLimit,
+/// }
+///
+/// impl Merge for LocConfig {
+///     fn merge(&mut self, prev: &LocConfig) -> Result<(), MergeConfigError> {
+///         self.concurrency.merge(&prev.concurrency);
+///         Ok(())
+///     }
+/// }
+///
+/// static mut COMMAND: ngx_command_t = ngx_command_t {
+///     name: ngx_string!("example_concurrency"),
+///     type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as _,
+///     set: Some(ngx_conf_set_concurrency_limit_slot),
+///     conf: NGX_HTTP_LOC_CONF_OFFSET,
+///     offset: core::mem::offset_of!(LocConfig, concurrency),
+///     post: core::ptr::null_mut(),
+/// };
+///
+/// async fn handle(conf: &LocConfig) {
+///     conf.concurrency.run(async {
+///         // expensive work
+///     })
+///     .await
+/// }
+/// ```
+pub struct ConcurrencyLimit {
+    limit: Option<usize>,
+    is_set: bool,
+    sem: Semaphore,
+}
+
+impl ConcurrencyLimit {
+    /// Creates an unset limit.
+    pub const fn new() -> Self {
+        Self {
+            limit: None,
+            is_set: false,
+            sem: Semaphore::new(0),
+        }
+    }
+
+    /// Creates a limit of `limit` concurrent tasks, or no limit if `None`.
+    pub const fn with_limit(limit: Option<usize>) -> Self {
+        let permits = match limit {
+            Some(n) => n,
+            None => 0,
+        };
+
+        Self {
+            limit,
+            is_set: true,
+            sem: Semaphore::new(permits),
+        }
+    }
+
+    /// Returns `true` if the limit is configured, including `off`.
+    pub fn is_set(&self) -> bool {
+        self.is_set
+    }
+
+    /// Returns the configured number of concurrent tasks, `None` if unlimited.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Returns the semaphore enforcing the limit, `None` if unlimited.
+    ///
+    /// The semaphore allows to reject the work instead of waiting, e.g. with
+    /// [Semaphore::try_acquire].
+    pub fn semaphore(&self) -> Option<&Semaphore> {
+        self.limit.map(|_| &self.sem)
+    }
+
+    /// Inherits the limit from the previous configuration level if not set.
+    pub fn merge(&mut self, prev: &Self) {
+        if !self.is_set && prev.is_set {
+            *self = Self::with_limit(prev.limit);
+        }
+    }
+
+    /// Runs the future once a permit is acquired, or immediately if unlimited.
+    ///
+    /// Waiting tasks are resumed in FIFO order, see [Semaphore::acquire].
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        match self.semaphore() {
+            Some(sem) => sem.run(future).await,
+            None => future.await,
+        }
+    }
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimit")
+            .field("limit", &self.limit)
+            .field("semaphore", &self.semaphore())
+            .finish()
+    }
+}
+
+/// A directive handler storing the number of concurrent tasks or `off` in a [ConcurrencyLimit].
+///
+/// # Safety
+///
+/// The `conf` field at `cmd.offset` must be a [ConcurrencyLimit].
+pub unsafe extern "C" fn ngx_conf_set_concurrency_limit_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    let limit = &mut *conf
+        .cast::<u8>()
+        .add((*cmd).offset)
+        .cast::<ConcurrencyLimit>();
+    if limit.is_set() {
+        return c"is duplicate".as_ptr().cast_mut();
+    }
+
+    let value = &*(*(*cf).args).elts.cast::<ngx_str_t>().add(1);
+    if value.as_bytes() == b"off" {
+        *limit = ConcurrencyLimit::with_limit(None);
+        return NGX_CONF_OK;
+    }
+
+    let n = ngx_atoi(value.data, value.len);
+    if n <= 0 {
+        return c"invalid value".as_ptr().cast_mut();
+    }
+
+    *limit = ConcurrencyLimit::with_limit(Some(n as usize));
+    NGX_CONF_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+    use core::task::{Context, Poll};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use super::*;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn fifo_handover() {
+        let sem = Semaphore::new(1);
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let permit = sem.try_acquire().expect("permit");
+        assert!(sem.try_acquire().is_none());
+
+        let mut first = pin!(sem.acquire());
+        let mut second = pin!(sem.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(sem.waiters(), 2);
+
+        drop(permit);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(sem.available_permits(), 0);
+        // new callers cannot overtake the queue
+        assert!(sem.try_acquire().is_none());
+
+        let Poll::Ready(permit) = first.as_mut().poll(&mut cx) else {
+            panic!("first waiter is not ready");
+        };
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        drop(permit);
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(sem.available_permits(), 1);
+    }
+
+    #[test]
+    fn cancel_granted() {
+        let sem = Semaphore::new(0);
+        let waker = Waker::from(Arc::new(CountingWaker(AtomicUsize::new(0))));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut first = Box::pin(sem.acquire());
+        let mut second = pin!(sem.acquire());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        sem.add_permits(1);
+        drop(first);
+
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+        assert_eq!(sem.waiters(), 0);
+        assert_eq!(sem.available_permits(), 1);
+    }
+
+    #[test]
+    fn concurrency_limit_merge() {
+        let mut conf = ConcurrencyLimit::new();
+        conf.merge(&ConcurrencyLimit::new());
+        assert!(!conf.is_set());
+        assert!(conf.semaphore().is_none());
+
+        let prev = ConcurrencyLimit::with_limit(Some(2));
+        let _permit = prev.semaphore().and_then(Semaphore::try_acquire);
+
+        let mut conf = ConcurrencyLimit::new();
+        conf.merge(&prev);
+        assert_eq!(conf.limit(), Some(2));
+        // the inherited limit does not share the permits
+        assert_eq!(conf.semaphore().map(Semaphore::available_permits), Some(2));
+
+        let mut conf = ConcurrencyLimit::with_limit(None);
+        conf.merge(&prev);
+        assert!(conf.is_set());
+        assert!(conf.semaphore().is_none());
+    }
+}
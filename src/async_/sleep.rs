@@ -1,18 +1,17 @@
 use core::future::Future;
-use core::mem;
 use core::pin::Pin;
-use core::ptr::{self, NonNull};
+use core::ptr::NonNull;
 use core::task::{self, Poll};
 use core::time::Duration;
 
-use nginx_sys::{ngx_add_timer, ngx_del_timer, ngx_event_t, ngx_log_t, ngx_msec_int_t, ngx_msec_t};
+use nginx_sys::{ngx_event_t, ngx_log_t, ngx_msec_t};
 use pin_project_lite::pin_project;
 
+#[cfg(target_pointer_width = "32")]
+use crate::event::NGX_TIMER_DURATION_MAX;
+use crate::event::{timer_msec, TimerEvent};
 use crate::{ngx_container_of, ngx_log_debug};
 
-/// Maximum duration that can be achieved using [ngx_add_timer].
-const NGX_TIMER_DURATION_MAX: Duration = Duration::from_millis(ngx_msec_int_t::MAX as _);
-
 /// Puts the current task to sleep for at least the specified amount of time.
 ///
 /// The function is a shorthand for [Sleep::new] using the global logger for debug output.
@@ -25,7 +24,7 @@ pin_project! {
 /// Future returned by [sleep].
 pub struct Sleep {
     #[pin]
-    timer: TimerEvent,
+    timer: SleepEvent,
     duration: Duration,
 }
 }
@@ -33,8 +32,8 @@ pub struct Sleep {
 impl Sleep {
     /// Creates a new Sleep with the specified duration and logger for debug messages.
     pub fn new(duration: Duration, log: NonNull<ngx_log_t>) -> Self {
-        let timer = TimerEvent::new(log);
-        ngx_log_debug!(timer.event.log, "async: sleep for {duration:?}");
+        let timer = SleepEvent::new(log);
+        ngx_log_debug!(timer.event.log(), "async: sleep for {duration:?}");
         Sleep { timer, duration }
     }
 }
@@ -44,7 +43,7 @@ impl Future for Sleep {
 
     #[cfg(not(target_pointer_width = "32"))]
     fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        let msec = self.duration.min(NGX_TIMER_DURATION_MAX).as_millis() as ngx_msec_t;
+        let msec = timer_msec(self.duration);
         let this = self.project();
         this.timer.poll_sleep(msec, cx)
     }
@@ -63,7 +62,7 @@ impl Future for Sleep {
             Poll::Ready(()) if this.duration == &step => Poll::Ready(()),
             Poll::Ready(()) => {
                 *this.duration = this.duration.saturating_sub(step);
-                this.timer.event.set_timedout(0); // rearm
+                this.timer.event.reset(); // rearm
                 this.timer.as_mut().poll_sleep(step.as_millis() as _, cx)
             }
             x => x,
@@ -71,30 +70,19 @@ impl Future for Sleep {
     }
 }
 
-struct TimerEvent {
-    event: ngx_event_t,
+pub(crate) struct SleepEvent {
+    pub(crate) event: TimerEvent,
     waker: Option<task::Waker>,
 }
 
 // SAFETY: Timer will only be used in a single-threaded environment
-unsafe impl Send for TimerEvent {}
-unsafe impl Sync for TimerEvent {}
+unsafe impl Send for SleepEvent {}
+unsafe impl Sync for SleepEvent {}
 
-impl TimerEvent {
+impl SleepEvent {
     pub fn new(log: NonNull<ngx_log_t>) -> Self {
-        static IDENT: [usize; 4] = [
-            0, 0, 0, 0x4153594e, // ASYN
-        ];
-
-        let mut ev: ngx_event_t = unsafe { mem::zeroed() };
-        // The data is only used for `ngx_event_ident` and will not be mutated.
-        ev.data = ptr::addr_of!(IDENT).cast_mut().cast();
-        ev.handler = Some(Self::timer_handler);
-        ev.log = log.as_ptr();
-        ev.set_cancelable(1);
-
         Self {
-            event: ev,
+            event: TimerEvent::new(Some(Self::timer_handler), log),
             waker: None,
         }
     }
@@ -104,9 +92,9 @@ impl TimerEvent {
         duration: ngx_msec_t,
         context: &mut task::Context<'_>,
     ) -> Poll<()> {
-        if self.event.timedout() != 0 {
+        if self.event.is_timedout() {
             Poll::Ready(())
-        } else if self.event.timer_set() != 0 {
+        } else if self.event.is_set() {
             if let Some(waker) = self.waker.as_mut() {
                 waker.clone_from(context.waker());
             } else {
@@ -114,7 +102,8 @@ impl TimerEvent {
             }
             Poll::Pending
         } else {
-            unsafe { ngx_add_timer(ptr::addr_of_mut!(self.event), duration) };
+            // SAFETY: the event is pinned
+            unsafe { self.event.add(duration) };
             self.waker = Some(context.waker().clone());
             Poll::Pending
         }
@@ -128,11 +117,3 @@ impl TimerEvent {
         }
     }
 }
-
-impl Drop for TimerEvent {
-    fn drop(&mut self) {
-        if self.event.timer_set() != 0 {
-            unsafe { ngx_del_timer(ptr::addr_of_mut!(self.event)) };
-        }
-    }
-}
@@ -137,6 +137,19 @@ fn schedule(runnable: Runnable, info: ScheduleInfo) {
 
 /// Creates a new task running on the NGINX event loop.
 pub fn spawn<F, T>(future: F) -> Task<T>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    let (runnable, task) = spawn_unscheduled(future);
+    runnable.schedule();
+    task
+}
+
+/// Creates a new task without scheduling it.
+///
+/// The caller is responsible for calling [Runnable::schedule] to start the task.
+pub(crate) fn spawn_unscheduled<F, T>(future: F) -> (Runnable, Task<T>)
 where
     F: Future<Output = T> + 'static,
     T: 'static,
@@ -145,7 +158,5 @@ where
     let scheduler = WithInfo(schedule);
     // Safety: single threaded embedding takes care of send/sync requirements for future and
     // scheduler. Future and scheduler are both 'static.
-    let (runnable, task) = unsafe { async_task::spawn_unchecked(future, scheduler) };
-    runnable.schedule();
-    task
+    unsafe { async_task::spawn_unchecked(future, scheduler) }
 }
@@ -0,0 +1,190 @@
+use core::cell::RefCell;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::ptr;
+use core::task::{self, Poll};
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use pin_project_lite::pin_project;
+
+use super::spawn::{spawn, Task};
+use crate::core::assert_main_thread;
+use crate::log::ngx_cycle_log;
+use crate::ngx_log_debug;
+
+/// Returns `true` if the current process is shutting down.
+///
+/// The check covers both the graceful shutdown (`ngx_quit`, `ngx_exiting`) and the fast shutdown
+/// (`ngx_terminate`) of a worker or of the single process.
+pub fn is_shutting_down() -> bool {
+    // SAFETY: the flags are only read; they are set from the signal handlers and from the main
+    // thread of the process.
+    unsafe {
+        ptr::read_volatile(ptr::addr_of!(nginx_sys::ngx_quit)) != 0
+            || ptr::read_volatile(ptr::addr_of!(nginx_sys::ngx_exiting)) != 0
+            || ptr::read_volatile(ptr::addr_of!(nginx_sys::ngx_terminate)) != 0
+    }
+}
+
+/// An owner of the long-running background tasks of a module.
+///
+/// The tasks are normally started in [ProcessHooks::init_process], and [Supervisor::shutdown] is
+/// called from [ProcessHooks::exit_process] to cancel the tasks that are still running, so that
+/// their destructors run before the process exits.
+///
+/// Each task is additionally cancelled when it is woken after the shutdown of the process has
+/// started, see [is_shutting_down]. The timers used by the async utilities are cancelable and do
+/// not delay the graceful shutdown, so a task waiting for a timer is cancelled in `exit_process`.
+///
+/// A panic in a task is logged with [crate::panic::catch] and completes the task; the other tasks
+/// keep running.
+///
+/// The supervisor must only be used from the main thread of a process; the methods panic when
+/// called from another thread.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::time::Duration;
+/// # use ngx::async_::{interval, Supervisor};
+/// # use ngx::core::{ProcessHooks, Status};
+/// # use ngx::ffi::ngx_cycle_t;
+/// static SUPERVISOR: Supervisor = Supervisor::new("ngx_http_example_module");
+///
+/// struct Module;
+///
+/// impl ProcessHooks for Module {
+///     fn init_process(_cycle: &mut ngx_cycle_t) -> Status {
+///         SUPERVISOR.spawn("sync", async {
+///             let mut ticker = interval(Duration::from_secs(30));
+///             loop {
+///                 ticker.tick().await;
+///                 // synchronize the state
+///             }
+///         });
+///         Status::NGX_OK
+///     }
+///
+///     fn exit_process(_cycle: &mut ngx_cycle_t) {
+///         SUPERVISOR.shutdown();
+///     }
+/// }
+/// ```
+///
+/// [ProcessHooks::init_process]: crate::core::ProcessHooks::init_process
+/// [ProcessHooks::exit_process]: crate::core::ProcessHooks::exit_process
+pub struct Supervisor {
+    module: &'static str,
+    tasks: RefCell<Vec<(&'static str, Task<()>)>>,
+}
+
+// SAFETY: the tasks are only accessed from the main thread of a process, which is checked by each
+// method. The supervisor is not `Send`, so the tasks are dropped on the thread that created them.
+unsafe impl Sync for Supervisor {}
+
+impl Supervisor {
+    /// Creates a supervisor without tasks.
+    ///
+    /// `module` identifies the tasks in the log messages.
+    pub const fn new(module: &'static str) -> Self {
+        Self {
+            module,
+            tasks: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Starts a supervised task.
+    ///
+    /// `name` identifies the task in the log messages.
+    pub fn spawn<F>(&self, name: &'static str, future: F)
+    where
+        F: Future<Output = ()> + 'static,
+    {
+        assert_main_thread("Supervisor");
+
+        let task = spawn(Supervised {
+            module: self.module,
+            name,
+            future,
+        });
+
+        let finished = {
+            let mut tasks = self.tasks.borrow_mut();
+            let (finished, running): (Vec<_>, Vec<_>) = mem::take(&mut *tasks)
+                .into_iter()
+                .partition(|(_, task)| task.is_finished());
+            *tasks = running;
+            tasks.push((name, task));
+            finished
+        };
+
+        // release the completed tasks outside of the borrow
+        drop(finished);
+    }
+
+    /// Returns the number of tasks that are still running.
+    pub fn running(&self) -> usize {
+        assert_main_thread("Supervisor");
+        self.tasks
+            .borrow()
+            .iter()
+            .filter(|(_, task)| !task.is_finished())
+            .count()
+    }
+
+    /// Cancels all the tasks.
+    ///
+    /// The futures of the tasks are dropped, unless a task is currently running, in which case it
+    /// is dropped once it yields.
+    pub fn shutdown(&self) {
+        assert_main_thread("Supervisor");
+        let tasks = mem::take(&mut *self.tasks.borrow_mut());
+
+        for (name, task) in tasks {
+            if !task.is_finished() {
+                ngx_log_debug!(
+                    ngx_cycle_log().as_ptr(),
+                    "async: {}: cancelling task \"{name}\"",
+                    self.module
+                );
+            }
+            drop(task);
+        }
+    }
+}
+
+pin_project! {
+/// A task future that stops on shutdown and contains panics.
+struct Supervised<F> {
+    module: &'static str,
+    name: &'static str,
+    #[pin]
+    future: F,
+}
+}
+
+impl<F: Future<Output = ()>> Future for Supervised<F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        if is_shutting_down() {
+            ngx_log_debug!(
+                ngx_cycle_log().as_ptr(),
+                "async: {}: task \"{}\" stopped on shutdown",
+                this.module,
+                this.name
+            );
+            return Poll::Ready(());
+        }
+
+        let future = this.future;
+        // A panic is logged by `catch`; the future is not polled again.
+        crate::panic::catch(*this.module, *this.name, || future.poll(cx)).unwrap_or(Poll::Ready(()))
+    }
+}
@@ -0,0 +1,109 @@
+use core::ffi::CStr;
+use core::fmt;
+
+use crate::ffi::{ngx_io, ngx_uint_t, NGINX_VERSION, NGX_IO_SENDFILE};
+
+/// The capabilities of the NGINX build the module is compiled against.
+///
+/// The same capabilities are available at compile time as the `ngx_feature` cfg values, e.g.
+/// `#[cfg(ngx_feature = "ktls")]`, see the [nginx-sys] documentation for the build script setup.
+///
+/// Example:
+/// ```rust,no_run
+/// let info = ngx::build_info();
+/// if info.ktls && info.sendfile_available() {
+///     // large TLS responses can be sent from files without copying to userspace
+/// }
+/// ```
+///
+/// [nginx-sys]: https://docs.rs/nginx-sys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BuildInfo {
+    /// The NGINX version, e.g. `1.28.0`.
+    pub version: &'static str,
+    /// The operating system detected by the NGINX configure script.
+    pub os: Option<&'static str>,
+    /// NGINX is built with the debug logging.
+    pub debug: bool,
+    /// NGINX is built with the thread pools.
+    pub threads: bool,
+    /// The asynchronous file I/O is available.
+    pub file_aio: bool,
+    /// The `sendfile()` system call is available.
+    pub sendfile: bool,
+    /// NGINX is built with SSL support.
+    pub ssl: bool,
+    /// The SSL library is OpenSSL or a compatible library.
+    pub openssl: bool,
+    /// The SSL library supports sending files with the kernel TLS offload, `SSL_sendfile()`.
+    pub ktls: bool,
+    /// NGINX is built with QUIC support.
+    pub quic: bool,
+    /// The QUIC packets are routed with eBPF, for `quic_bpf`.
+    pub quic_bpf: bool,
+    /// QUIC uses the OpenSSL compatibility layer instead of the native QUIC API of the library.
+    pub quic_openssl_compat: bool,
+    /// The HTTP/2 module is built.
+    pub http_v2: bool,
+    /// The HTTP/3 module is built.
+    pub http_v3: bool,
+}
+
+/// Returns the capabilities of the NGINX build.
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: CStr::from_bytes_with_nul(NGINX_VERSION)
+            .ok()
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or("unknown"),
+        os: option_env!("DEP_NGINX_OS").filter(|x| !x.is_empty()),
+        debug: cfg!(ngx_feature = "debug"),
+        threads: cfg!(ngx_feature = "threads"),
+        file_aio: cfg!(ngx_feature = "have_file_aio"),
+        sendfile: cfg!(ngx_feature = "have_sendfile"),
+        ssl: cfg!(ngx_feature = "ssl"),
+        openssl: cfg!(ngx_feature = "openssl"),
+        ktls: cfg!(ngx_feature = "ktls"),
+        quic: cfg!(ngx_feature = "quic"),
+        quic_bpf: cfg!(ngx_feature = "quic_bpf"),
+        quic_openssl_compat: cfg!(ngx_feature = "quic_openssl_compat"),
+        http_v2: cfg!(ngx_feature = "http_v2"),
+        http_v3: cfg!(ngx_feature = "http_v3"),
+    }
+}
+
+impl BuildInfo {
+    /// Returns the names of all the `ngx_feature` values enabled for the build.
+    pub fn features(&self) -> impl Iterator<Item = &'static str> {
+        option_env!("DEP_NGINX_FEATURES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+    }
+
+    /// Returns `true` if the `ngx_feature` value is enabled for the build.
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features().any(|x| x == name)
+    }
+
+    /// Returns `true` if the I/O module selected by NGINX at runtime sends files with
+    /// `sendfile()`.
+    ///
+    /// The value is known after the event module initialization.
+    pub fn sendfile_available(&self) -> bool {
+        // SAFETY: the global is only modified during the process initialization.
+        unsafe { ngx_io.flags & NGX_IO_SENDFILE as ngx_uint_t != 0 }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nginx {}", self.version)?;
+        if let Some(os) = self.os {
+            write!(f, " ({os})")?;
+        }
+        Ok(())
+    }
+}
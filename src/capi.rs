@@ -0,0 +1,248 @@
+//! C-callable interface to the Rust components.
+//!
+//! The functions allow the modules written in C to reuse the components of this crate, e.g. a
+//! [SharedDict] zone shared with a Rust module. The declarations are available in
+//! `include/ngx_rust.h`.
+//!
+//! The interface follows these contracts:
+//!
+//! * panics never unwind into the caller. With the unwind panic strategy, a panic is logged and
+//!   reported as `NGX_ERROR`; otherwise the process is aborted, as with any other panic in the
+//!   module code, see [crate::panic].
+//! * the input buffers are borrowed for the duration of the call, and the stored data is copied.
+//! * the output buffers are allocated from the pool passed by the caller, so no memory is owned
+//!   across the interface.
+//!
+//! The interface is only exported when the `capi` feature is enabled. The exported symbols are
+//! prefixed with `ngx_rust_capi_v1_`, so that these do not clash with other libraries and a
+//! module built with an incompatible version of the interface is not silently linked against.
+//! The header defines the unversioned names used in the examples below.
+use core::ffi::c_void;
+use core::ptr;
+use core::slice;
+use core::time::Duration;
+
+use crate::core::{NgxStr, NgxString, Pool, SlabPool, Status};
+use crate::ffi::{
+    ngx_conf_t, ngx_int_t, ngx_module_t, ngx_msec_t, ngx_pool_t, ngx_shm_zone_t, ngx_str_t, u_char,
+    NGX_LOG_EMERG,
+};
+use crate::ngx_conf_log_error;
+use crate::shm::{SharedDict, SharedZone, SharedZoneBuilder, SharedZoneError};
+
+const MODULE: &str = "ngx_rust_capi";
+
+/// The byte string dictionary exported with the `ngx_rust_capi_v1_shared_dict_*` functions.
+///
+/// A Rust module can access the same zone with [SharedZone::from_ptr].
+pub type CSharedDict = SharedDict<NgxString<SlabPool>, NgxString<SlabPool>>;
+
+/// Returns the tag of a zone declared with [ngx_rust_capi_v1_shared_dict_add].
+///
+/// NGINX rejects a zone referenced with a different tag, and each module built with this crate
+/// has its own copy of any static variable. The tag is thus derived from the zone name and the
+/// interface version instead of an address, so that the zone can be referenced from multiple
+/// modules. The value is odd and never matches the address of a tag used by another module.
+fn shared_dict_tag(name: &[u8]) -> *mut c_void {
+    const PREFIX: &[u8] = b"ngx_rust_capi_v1_shared_dict:";
+
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in PREFIX.iter().chain(name) {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    ((hash ^ (hash >> 32)) as usize | 1) as *mut c_void
+}
+
+/// Converts a pointer and length pair to a slice.
+///
+/// # Safety
+///
+/// `data` is either NULL with zero `len`, or a valid pointer to `len` bytes.
+unsafe fn bytes<'a>(data: *const u_char, len: usize) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    slice::from_raw_parts(data, len)
+}
+
+/// Returns the dictionary of the zone, `None` until the zone is initialized.
+///
+/// # Safety
+///
+/// `zone` is NULL or a valid pointer to a zone declared with [ngx_rust_capi_v1_shared_dict_add].
+unsafe fn shared_dict<'a>(zone: *mut ngx_shm_zone_t) -> Option<&'a CSharedDict> {
+    let zone: SharedZone<CSharedDict> = SharedZone::from_ptr(zone)?;
+    // SAFETY: the zone data lives as long as the cycle.
+    zone.get().map(|x| &*ptr::from_ref(x))
+}
+
+/// Declares a shared dictionary zone.
+///
+/// `size` is 0 to reference a zone defined elsewhere in the configuration. Returns NULL on
+/// error, with the reason logged.
+///
+/// ```c
+/// ngx_shm_zone_t *ngx_rust_shared_dict_add(ngx_conf_t *cf, ngx_str_t *name, size_t size,
+///     ngx_module_t *module);
+/// ```
+///
+/// # Safety
+///
+/// The arguments are valid pointers, and the name is allocated from the configuration pool.
+#[no_mangle]
+pub unsafe extern "C" fn ngx_rust_capi_v1_shared_dict_add(
+    cf: *mut ngx_conf_t,
+    name: *mut ngx_str_t,
+    size: usize,
+    module: *mut ngx_module_t,
+) -> *mut ngx_shm_zone_t {
+    crate::panic::catch(MODULE, "ngx_rust_capi_v1_shared_dict_add", || {
+        let tag = shared_dict_tag(NgxStr::from_ngx_str(*name).as_bytes());
+        let res = SharedZoneBuilder::new(*name, size, &*module)
+            .tag(tag)
+            .build::<CSharedDict>(&mut *cf);
+
+        match res {
+            Ok(zone) => zone.as_ptr(),
+            Err(SharedZoneError::Duplicate) => {
+                let name = NgxStr::from_ngx_str(*name);
+                ngx_conf_log_error!(NGX_LOG_EMERG, cf, "duplicate zone \"{name}\"");
+                ptr::null_mut()
+            }
+            Err(SharedZoneError::Add) => ptr::null_mut(),
+        }
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Looks up a key in a shared dictionary.
+///
+/// On success, `value` is set to a copy of the value allocated from `pool`.
+/// Returns `NGX_OK`, `NGX_DECLINED` if the key is not found or expired, or `NGX_ERROR`.
+///
+/// ```c
+/// ngx_int_t ngx_rust_shared_dict_get(ngx_shm_zone_t *zone, u_char *key, size_t key_len,
+///     ngx_str_t *value, ngx_pool_t *pool);
+/// ```
+///
+/// # Safety
+///
+/// `zone` is declared with [ngx_rust_capi_v1_shared_dict_add], `key` points to `key_len` bytes,
+/// and `value` and `pool` are valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn ngx_rust_capi_v1_shared_dict_get(
+    zone: *mut ngx_shm_zone_t,
+    key: *const u_char,
+    key_len: usize,
+    value: *mut ngx_str_t,
+    pool: *mut ngx_pool_t,
+) -> ngx_int_t {
+    crate::panic::catch(MODULE, "ngx_rust_capi_v1_shared_dict_get", || {
+        let Some(dict) = shared_dict(zone) else {
+            return Status::NGX_ERROR;
+        };
+
+        let mut pool = Pool::from_ngx_pool(pool);
+        let copy = dict.get_with(bytes(key, key_len), |v| {
+            let v = v.as_bytes();
+            let data = pool.alloc_unaligned(v.len()).cast::<u8>();
+            if data.is_null() {
+                return None;
+            }
+            ptr::copy_nonoverlapping(v.as_ptr(), data, v.len());
+            Some(ngx_str_t { len: v.len(), data })
+        });
+
+        match copy {
+            Some(Some(copy)) => {
+                *value = copy;
+                Status::NGX_OK
+            }
+            Some(None) => Status::NGX_ERROR,
+            None => Status::NGX_DECLINED,
+        }
+    })
+    .unwrap_or(Status::NGX_ERROR)
+    .into()
+}
+
+/// Inserts or replaces a value in a shared dictionary.
+///
+/// The entry expires after `ttl` milliseconds, or never if `ttl` is 0.
+/// Returns `NGX_OK`, or `NGX_ERROR` if the zone memory is exhausted.
+///
+/// ```c
+/// ngx_int_t ngx_rust_shared_dict_set(ngx_shm_zone_t *zone, u_char *key, size_t key_len,
+///     u_char *value, size_t value_len, ngx_msec_t ttl);
+/// ```
+///
+/// # Safety
+///
+/// `zone` is declared with [ngx_rust_capi_v1_shared_dict_add], and `key` and `value` point to
+/// `key_len` and `value_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ngx_rust_capi_v1_shared_dict_set(
+    zone: *mut ngx_shm_zone_t,
+    key: *const u_char,
+    key_len: usize,
+    value: *const u_char,
+    value_len: usize,
+    ttl: ngx_msec_t,
+) -> ngx_int_t {
+    crate::panic::catch(MODULE, "ngx_rust_capi_v1_shared_dict_set", || {
+        let Some(dict) = shared_dict(zone) else {
+            return Status::NGX_ERROR;
+        };
+
+        let alloc = dict.allocator();
+        let (Ok(key), Ok(value)) = (
+            NgxString::try_from_bytes_in(bytes(key, key_len), alloc.clone()),
+            NgxString::try_from_bytes_in(bytes(value, value_len), alloc.clone()),
+        ) else {
+            return Status::NGX_ERROR;
+        };
+
+        let ttl = (ttl > 0).then(|| Duration::from_millis(ttl as u64));
+        match dict.insert(key, value, ttl) {
+            Ok(()) => Status::NGX_OK,
+            Err(_) => Status::NGX_ERROR,
+        }
+    })
+    .unwrap_or(Status::NGX_ERROR)
+    .into()
+}
+
+/// Removes a key from a shared dictionary.
+///
+/// Returns `NGX_OK`, `NGX_DECLINED` if the key is not found or expired, or `NGX_ERROR`.
+///
+/// ```c
+/// ngx_int_t ngx_rust_shared_dict_delete(ngx_shm_zone_t *zone, u_char *key, size_t key_len);
+/// ```
+///
+/// # Safety
+///
+/// `zone` is declared with [ngx_rust_capi_v1_shared_dict_add], and `key` points to `key_len`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ngx_rust_capi_v1_shared_dict_delete(
+    zone: *mut ngx_shm_zone_t,
+    key: *const u_char,
+    key_len: usize,
+) -> ngx_int_t {
+    crate::panic::catch(MODULE, "ngx_rust_capi_v1_shared_dict_delete", || {
+        let Some(dict) = shared_dict(zone) else {
+            return Status::NGX_ERROR;
+        };
+
+        match dict.remove(bytes(key, key_len)) {
+            Some(_) => Status::NGX_OK,
+            None => Status::NGX_DECLINED,
+        }
+    })
+    .unwrap_or(Status::NGX_ERROR)
+    .into()
+}
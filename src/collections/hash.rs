@@ -0,0 +1,316 @@
+//! Types and utilities for working with [ngx_hash_t], a static hash table built at configuration
+//! time.
+//!
+//! See <https://nginx.org/en/docs/dev/development_guide.html#hash>.
+
+use core::error;
+use core::ffi::{c_char, c_void, CStr};
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use nginx_sys::{
+    ngx_array_t, ngx_cacheline_size, ngx_dns_strcmp, ngx_hash_add_key, ngx_hash_combined_t,
+    ngx_hash_find, ngx_hash_find_combined, ngx_hash_find_wc_head, ngx_hash_find_wc_tail,
+    ngx_hash_init, ngx_hash_init_t, ngx_hash_key, ngx_hash_key_lc, ngx_hash_key_t,
+    ngx_hash_keys_array_init, ngx_hash_keys_arrays_t, ngx_hash_wildcard_init, ngx_hash_wildcard_t,
+    ngx_str_t, ngx_uint_t, NGX_BUSY, NGX_DECLINED, NGX_HASH_LARGE, NGX_HASH_SMALL,
+    NGX_HASH_WILDCARD_KEY, NGX_OK,
+};
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+
+/// An error returned when building a [NgxHash].
+#[derive(Debug, PartialEq, Eq)]
+pub enum NgxHashError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The key was already added.
+    Duplicate,
+    /// The key is not a valid wildcard name.
+    InvalidWildcard,
+    /// The hash could not be built with the configured size limits. The reason has already been
+    /// logged.
+    Size,
+}
+
+impl error::Error for NgxHashError {}
+
+impl fmt::Display for NgxHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NgxHashError::Alloc => f.write_str("hash allocation failed"),
+            NgxHashError::Duplicate => f.write_str("duplicate hash key"),
+            NgxHashError::InvalidWildcard => f.write_str("invalid wildcard hash key"),
+            NgxHashError::Size => f.write_str("could not build hash"),
+        }
+    }
+}
+
+impl From<AllocError> for NgxHashError {
+    fn from(_: AllocError) -> Self {
+        NgxHashError::Alloc
+    }
+}
+
+/// The values are stored with the alignment that leaves the two lowest bits of the pointer free
+/// for the wildcard hash flags.
+#[repr(align(4))]
+struct Slot<T>(T);
+
+/// A builder for [NgxHash], collecting the keys into `ngx_hash_keys_arrays_t`.
+///
+/// The keys and values are copied to the pool and live as long as the pool. The keys are
+/// converted to lowercase.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::collections::hash::{NgxHash, NgxHashBuilder, NgxHashError};
+/// # use ngx::core::Pool;
+/// fn build(pool: &Pool, temp_pool: &Pool) -> Result<NgxHash<u32>, NgxHashError> {
+///     let mut builder = NgxHashBuilder::new(pool, temp_pool)?
+///         .max_size(512)
+///         .bucket_size(64)
+///         .name(c"example_hash");
+///     builder.insert("example.com", 1)?;
+///     builder.insert_wildcard("*.example.org", 2)?;
+///     builder.insert_wildcard(".example.net", 3)?;
+///     builder.build()
+/// }
+/// ```
+pub struct NgxHashBuilder<T> {
+    keys: ngx_hash_keys_arrays_t,
+    pool: Pool,
+    max_size: ngx_uint_t,
+    bucket_size: ngx_uint_t,
+    name: *mut c_char,
+    _type: PhantomData<T>,
+}
+
+impl<T> NgxHashBuilder<T> {
+    /// Creates a builder for a hash with a small number of keys.
+    ///
+    /// `temp_pool` is used for the temporary arrays and can be destroyed once the hash is built.
+    pub fn new(pool: &Pool, temp_pool: &Pool) -> Result<Self, AllocError> {
+        Self::with_type(pool, temp_pool, NGX_HASH_SMALL as _)
+    }
+
+    /// Creates a builder for a hash with a large number of keys, e.g. thousands of entries.
+    ///
+    /// `temp_pool` is used for the temporary arrays and can be destroyed once the hash is built.
+    pub fn new_large(pool: &Pool, temp_pool: &Pool) -> Result<Self, AllocError> {
+        Self::with_type(pool, temp_pool, NGX_HASH_LARGE as _)
+    }
+
+    fn with_type(pool: &Pool, temp_pool: &Pool, type_: ngx_uint_t) -> Result<Self, AllocError> {
+        // SAFETY: ngx_hash_keys_arrays_t is a plain C structure, all zeroes is a valid value.
+        let mut keys: ngx_hash_keys_arrays_t = unsafe { mem::zeroed() };
+        keys.pool = pool.as_ptr();
+        keys.temp_pool = temp_pool.as_ptr();
+
+        if unsafe { ngx_hash_keys_array_init(&mut keys, type_) } != NGX_OK as _ {
+            return Err(AllocError);
+        }
+
+        Ok(Self {
+            keys,
+            pool: pool.clone(),
+            max_size: 512,
+            // SAFETY: the cache line size is detected at startup and is not modified later.
+            bucket_size: unsafe { ngx_cacheline_size },
+            name: c"hash".as_ptr().cast_mut(),
+            _type: PhantomData,
+        })
+    }
+
+    /// Sets the maximum number of buckets.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size as _;
+        self
+    }
+
+    /// Sets the maximum size of a bucket, in bytes.
+    ///
+    /// The value is rounded up to the cache line size.
+    pub fn bucket_size(mut self, bucket_size: usize) -> Self {
+        self.bucket_size = bucket_size as _;
+        self
+    }
+
+    /// Sets the name used in the error messages, e.g. `"could not build example_hash, you should
+    /// increase example_hash_bucket_size"`.
+    pub fn name(mut self, name: &'static CStr) -> Self {
+        self.name = name.as_ptr().cast_mut();
+        self
+    }
+
+    /// Adds an exact key.
+    pub fn insert(&mut self, key: impl AsRef<[u8]>, value: T) -> Result<(), NgxHashError> {
+        self.add_key(key.as_ref(), value, 0)
+    }
+
+    /// Adds a key that may contain a wildcard.
+    ///
+    /// Accepts the same names as the `server_name` directive: `*.example.com`, `example.*`, or
+    /// `.example.com`, which matches both `example.com` and its subdomains. Names without wildcards
+    /// are added as exact keys.
+    pub fn insert_wildcard(&mut self, key: impl AsRef<[u8]>, value: T) -> Result<(), NgxHashError> {
+        self.add_key(key.as_ref(), value, NGX_HASH_WILDCARD_KEY as _)
+    }
+
+    fn add_key(&mut self, key: &[u8], value: T, flags: ngx_uint_t) -> Result<(), NgxHashError> {
+        let mut key =
+            unsafe { ngx_str_t::from_bytes(self.pool.as_ptr(), key) }.ok_or(NgxHashError::Alloc)?;
+        let value = self.pool.allocate_with_cleanup(Slot(value))?;
+
+        // The value stays in the pool on errors and is dropped with it.
+        match unsafe { ngx_hash_add_key(&mut self.keys, &mut key, value.as_ptr().cast(), flags) } {
+            x if x == NGX_OK as _ => Ok(()),
+            x if x == NGX_BUSY as _ => Err(NgxHashError::Duplicate),
+            x if x == NGX_DECLINED as _ => Err(NgxHashError::InvalidWildcard),
+            _ => Err(NgxHashError::Alloc),
+        }
+    }
+
+    /// Builds the hash.
+    pub fn build(mut self) -> Result<NgxHash<T>, NgxHashError> {
+        // SAFETY: ngx_hash_combined_t is a plain C structure, all zeroes is a valid value.
+        let mut hash: ngx_hash_combined_t = unsafe { mem::zeroed() };
+
+        let mut hinit = ngx_hash_init_t {
+            hash: &mut hash.hash,
+            key: Some(ngx_hash_key_lc),
+            max_size: self.max_size,
+            bucket_size: self.bucket_size,
+            name: self.name,
+            pool: self.pool.as_ptr(),
+            temp_pool: self.keys.temp_pool,
+        };
+
+        let keys = &self.keys.keys;
+        if unsafe { ngx_hash_init(&mut hinit, keys.elts.cast(), keys.nelts) } != NGX_OK as _ {
+            return Err(NgxHashError::Size);
+        }
+
+        hash.wc_head = build_wildcard(&mut hinit, &mut self.keys.dns_wc_head)?;
+        hash.wc_tail = build_wildcard(&mut hinit, &mut self.keys.dns_wc_tail)?;
+
+        Ok(NgxHash(hash, PhantomData))
+    }
+}
+
+/// Sorts the wildcard keys and builds a wildcard hash, if there are any.
+fn build_wildcard(
+    hinit: &mut ngx_hash_init_t,
+    keys: &mut ngx_array_t,
+) -> Result<*mut ngx_hash_wildcard_t, NgxHashError> {
+    if keys.nelts == 0 {
+        return Ok(ptr::null_mut());
+    }
+
+    // SAFETY: the array is initialized with ngx_hash_key_t elements by ngx_hash_keys_array_init.
+    let elts = unsafe { slice::from_raw_parts_mut(keys.elts.cast::<ngx_hash_key_t>(), keys.nelts) };
+    elts.sort_unstable_by(|a, b| {
+        // SAFETY: the wildcard keys are null-terminated.
+        unsafe { ngx_dns_strcmp(a.key.data, b.key.data) }.cmp(&0)
+    });
+
+    hinit.hash = ptr::null_mut();
+    if unsafe { ngx_hash_wildcard_init(hinit, elts.as_mut_ptr(), keys.nelts) } != NGX_OK as _ {
+        return Err(NgxHashError::Size);
+    }
+
+    Ok(hinit.hash.cast())
+}
+
+impl<T> fmt::Debug for NgxHashBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NgxHashBuilder")
+            .field("keys", &self.keys.keys.nelts)
+            .field("wc_head", &self.keys.dns_wc_head.nelts)
+            .field("wc_tail", &self.keys.dns_wc_tail.nelts)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A wrapper over `ngx_hash_combined_t`, a static hash table with optional wildcard parts.
+///
+/// The table and the values are allocated from the pool passed to [NgxHashBuilder] and are valid
+/// as long as the pool.
+///
+/// The keys are stored in lowercase. The lookup methods expect a lowercase name and do not
+/// convert it.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#hash>.
+pub struct NgxHash<T>(ngx_hash_combined_t, PhantomData<T>);
+
+impl<T> NgxHash<T> {
+    /// Looks up an exact key.
+    pub fn find(&self, name: impl AsRef<[u8]>) -> Option<&T> {
+        let name = name.as_ref();
+        let key = unsafe { ngx_hash_key(name.as_ptr().cast_mut(), name.len()) };
+        let hash = ptr::addr_of!(self.0.hash).cast_mut();
+        // SAFETY: the hash is not modified by the lookup.
+        let value = unsafe { ngx_hash_find(hash, key, name.as_ptr().cast_mut(), name.len()) };
+        unsafe { Self::value(value) }
+    }
+
+    /// Looks up a key matching a wildcard in front, e.g. `*.example.com`.
+    pub fn find_wc_head(&self, name: impl AsRef<[u8]>) -> Option<&T> {
+        let name = name.as_ref();
+        if self.0.wc_head.is_null() {
+            return None;
+        }
+
+        let value =
+            unsafe { ngx_hash_find_wc_head(self.0.wc_head, name.as_ptr().cast_mut(), name.len()) };
+        unsafe { Self::value(value) }
+    }
+
+    /// Looks up a key matching a wildcard at the end, e.g. `example.*`.
+    pub fn find_wc_tail(&self, name: impl AsRef<[u8]>) -> Option<&T> {
+        let name = name.as_ref();
+        if self.0.wc_tail.is_null() {
+            return None;
+        }
+
+        let value =
+            unsafe { ngx_hash_find_wc_tail(self.0.wc_tail, name.as_ptr().cast_mut(), name.len()) };
+        unsafe { Self::value(value) }
+    }
+
+    /// Looks up an exact key, then a wildcard in front, then a wildcard at the end.
+    ///
+    /// This is the lookup order used for `server_name` and `map` with the `hostnames` parameter.
+    pub fn find_combined(&self, name: impl AsRef<[u8]>) -> Option<&T> {
+        let name = name.as_ref();
+        let key = unsafe { ngx_hash_key(name.as_ptr().cast_mut(), name.len()) };
+        let hash = ptr::addr_of!(self.0).cast_mut();
+        // SAFETY: the hash is not modified by the lookup.
+        let value =
+            unsafe { ngx_hash_find_combined(hash, key, name.as_ptr().cast_mut(), name.len()) };
+        unsafe { Self::value(value) }
+    }
+
+    /// Returns a reference to the underlying `ngx_hash_combined_t`.
+    pub fn as_raw(&self) -> &ngx_hash_combined_t {
+        &self.0
+    }
+
+    unsafe fn value<'a>(p: *mut c_void) -> Option<&'a T> {
+        NonNull::new(p.cast::<Slot<T>>()).map(|x| &x.as_ref().0)
+    }
+}
+
+impl<T> fmt::Debug for NgxHash<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NgxHash")
+            .field("size", &self.0.hash.size)
+            .field("wc_head", &!self.0.wc_head.is_null())
+            .field("wc_tail", &!self.0.wc_tail.is_null())
+            .finish()
+    }
+}
@@ -0,0 +1,220 @@
+//! Types and utilities for working with [ngx_list_t], a list of arrays.
+//!
+//! See <https://nginx.org/en/docs/dev/development_guide.html#list>.
+
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use nginx_sys::{
+    ngx_list_init, ngx_list_part_t, ngx_list_push, ngx_list_t, ngx_uint_t, NGX_ALIGNMENT, NGX_OK,
+};
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+
+/// A wrapper over a raw `ngx_list_t`, a list of fixed-size arrays allocated from a pool.
+///
+/// The list never moves or deallocates the elements, and the elements are never dropped. The type
+/// `T` should not require a destructor or the caller is responsible for running it.
+///
+/// Example:
+/// ```rust,no_run
+/// # use nginx_sys::ngx_table_elt_t;
+/// # use ngx::collections::list::NgxList;
+/// # use ngx::http::Request;
+/// # fn example(r: &Request) {
+/// // SAFETY: `headers_in.headers` is initialized with `ngx_table_elt_t` elements.
+/// let headers: &NgxList<ngx_table_elt_t> =
+///     unsafe { NgxList::from_ptr(&r.as_ref().headers_in.headers) };
+/// for h in headers.iter().filter(|x| x.hash != 0) {
+///     // ...
+/// }
+/// # }
+/// ```
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#list>.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct NgxList<T> {
+    raw: ngx_list_t,
+    _type: PhantomData<T>,
+}
+
+impl<T> NgxList<T> {
+    /// Initializes a list in place, with space for `n` elements in each part.
+    ///
+    /// # Safety
+    ///
+    /// `list` is a valid pointer to a writable `ngx_list_t`. Any previous contents are overwritten
+    /// and leaked.
+    pub unsafe fn init<'a>(
+        list: *mut ngx_list_t,
+        pool: &Pool,
+        n: usize,
+    ) -> Result<&'a mut Self, AllocError> {
+        debug_assert!(mem::align_of::<T>() <= NGX_ALIGNMENT);
+
+        if ngx_list_init(list, pool.as_ptr(), n as ngx_uint_t, mem::size_of::<T>()) != NGX_OK as _ {
+            return Err(AllocError);
+        }
+
+        Ok(Self::from_ptr_mut(list))
+    }
+
+    /// Creates a list reference from a pointer to [ngx_list_t].
+    ///
+    /// # Safety
+    ///
+    /// `list` is a valid pointer to an initialized list with elements of type `T`.
+    pub unsafe fn from_ptr<'a>(list: *const ngx_list_t) -> &'a Self {
+        &*list.cast()
+    }
+
+    /// Creates a mutable list reference from a pointer to [ngx_list_t].
+    ///
+    /// # Safety
+    ///
+    /// `list` is a valid pointer to an initialized list with elements of type `T`.
+    pub unsafe fn from_ptr_mut<'a>(list: *mut ngx_list_t) -> &'a mut Self {
+        &mut *list.cast()
+    }
+
+    /// Returns a raw pointer to the underlying `ngx_list_t`.
+    pub fn as_ptr(&self) -> *const ngx_list_t {
+        &self.raw
+    }
+
+    /// Returns a mutable raw pointer to the underlying `ngx_list_t`.
+    pub fn as_mut_ptr(&mut self) -> *mut ngx_list_t {
+        &mut self.raw
+    }
+
+    /// Returns `true` if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.parts().all(|x| x.is_empty())
+    }
+
+    /// Returns the number of elements in the list.
+    ///
+    /// This method walks the list and has linear complexity in the number of parts.
+    pub fn len(&self) -> usize {
+        self.parts().map(|x| x.len()).sum()
+    }
+
+    /// Appends an element to the back of the list.
+    ///
+    /// Returns a reference to the stored element, or an error if the allocation of a new part
+    /// failed.
+    pub fn push(&mut self, value: T) -> Result<&mut T, AllocError> {
+        debug_assert_eq!(self.raw.size, mem::size_of::<T>());
+
+        let p = unsafe { ngx_list_push(&mut self.raw) }.cast::<T>();
+        let p = NonNull::new(p).ok_or(AllocError)?;
+        // SAFETY: ngx_list_push returns an uninitialized properly sized slot
+        unsafe {
+            ptr::write(p.as_ptr(), value);
+            Ok(&mut *p.as_ptr())
+        }
+    }
+
+    /// Returns an iterator over the list parts as slices.
+    pub fn parts(&self) -> NgxListParts<'_, T> {
+        NgxListParts {
+            part: self.part_ptr(),
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the list parts as mutable slices.
+    pub fn parts_mut(&mut self) -> NgxListPartsMut<'_, T> {
+        NgxListPartsMut {
+            part: self.part_ptr(),
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the elements of the list.
+    pub fn iter(&self) -> NgxListIter<'_, T> {
+        self.parts().flatten()
+    }
+
+    /// Returns a mutable iterator over the elements of the list.
+    pub fn iter_mut(&mut self) -> NgxListIterMut<'_, T> {
+        self.parts_mut().flatten()
+    }
+
+    fn part_ptr(&self) -> *mut ngx_list_part_t {
+        // An uninitialized (zeroed) list has no elements and no last part.
+        if self.raw.last.is_null() {
+            return ptr::null_mut();
+        }
+
+        ptr::addr_of!(self.raw.part).cast_mut()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a NgxList<T> {
+    type Item = &'a T;
+    type IntoIter = NgxListIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut NgxList<T> {
+    type Item = &'a mut T;
+    type IntoIter = NgxListIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator over the elements of the list.
+pub type NgxListIter<'a, T> = core::iter::Flatten<NgxListParts<'a, T>>;
+
+/// A mutable iterator over the elements of the list.
+pub type NgxListIterMut<'a, T> = core::iter::Flatten<NgxListPartsMut<'a, T>>;
+
+/// An iterator over the list parts.
+pub struct NgxListParts<'a, T> {
+    part: *mut ngx_list_part_t,
+    _lifetime: PhantomData<&'a T>,
+}
+
+impl<'a, T: 'a> Iterator for NgxListParts<'a, T> {
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let part = unsafe { self.part.as_ref()? };
+        self.part = part.next;
+
+        if part.nelts == 0 {
+            return Some(&[]);
+        }
+        Some(unsafe { slice::from_raw_parts(part.elts.cast(), part.nelts) })
+    }
+}
+
+/// A mutable iterator over the list parts.
+pub struct NgxListPartsMut<'a, T> {
+    part: *mut ngx_list_part_t,
+    _lifetime: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: 'a> Iterator for NgxListPartsMut<'a, T> {
+    type Item = &'a mut [T];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let part = unsafe { self.part.as_mut()? };
+        self.part = part.next;
+
+        if part.nelts == 0 {
+            return Some(&mut []);
+        }
+        Some(unsafe { slice::from_raw_parts_mut(part.elts.cast(), part.nelts) })
+    }
+}
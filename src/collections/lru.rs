@@ -0,0 +1,480 @@
+//! A least recently used cache built on [ngx_rbtree_t] and [ngx_queue_t].
+//!
+//! See [LruCache].
+
+use core::alloc::Layout;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::time::Duration;
+
+use nginx_sys::{
+    ngx_msec_t, ngx_queue_data, ngx_queue_init, ngx_queue_insert_after, ngx_queue_remove,
+    ngx_queue_t, ngx_rbt_red, ngx_rbtree_data, ngx_rbtree_delete, ngx_rbtree_init,
+    ngx_rbtree_insert, ngx_rbtree_key_t, ngx_rbtree_node_t, ngx_rbtree_t,
+};
+
+use crate::allocator::{self, AllocError, Allocator};
+use crate::collections::queue::{NgxQueueEntry, NgxQueueIter};
+use crate::collections::rbtree::BuildMapHasher;
+use crate::event::{current_msec, expires_at, is_expired};
+
+/// A map with a limited number of entries, evicting the least recently used entry on overflow.
+///
+/// The cache follows the layout used by the NGINX modules, such as `limit_req` or
+/// `ssl_session_cache`: each entry is linked both to a red-black tree for the lookups and to a
+/// queue ordered by the access time. Entries can optionally expire after a fixed time since the
+/// last update.
+///
+/// With a [SlabPool](crate::core::SlabPool) allocator the cache can be placed in a shared memory
+/// zone, protected with a [RwLock](crate::sync::RwLock) or a [Mutex](crate::sync::Mutex). If the
+/// zone is full, [LruCache::try_insert] evicts the least recently used entries to free the memory.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::time::Duration;
+/// # use ngx::allocator::AllocError;
+/// # use ngx::collections::LruCache;
+/// # use ngx::core::SlabPool;
+/// # fn example(alloc: SlabPool) -> Result<(), AllocError> {
+/// let mut sessions: LruCache<u64, [u8; 48], SlabPool> = LruCache::try_new_in(1024, alloc)?;
+/// sessions.set_ttl(Some(Duration::from_secs(300)));
+///
+/// sessions.try_insert(1, [0; 48])?;
+/// assert!(sessions.get(&1).is_some());
+/// # Ok(())
+/// # }
+/// ```
+pub struct LruCache<K, V, A>
+where
+    A: Allocator,
+{
+    tree: ngx_rbtree_t,
+    sentinel: NonNull<ngx_rbtree_node_t>,
+    // The address of the queue head has to be stable, as the entries contain pointers to the head.
+    queue: NonNull<ngx_queue_t>,
+    _type: PhantomData<LruEntry<K, V>>,
+    len: usize,
+    capacity: usize,
+    ttl: Option<Duration>,
+    alloc: A,
+}
+
+struct LruEntry<K, V> {
+    node: ngx_rbtree_node_t,
+    queue: ngx_queue_t,
+    expires: Option<ngx_msec_t>,
+    key: K,
+    value: V,
+}
+
+impl<K, V> LruEntry<K, V> {
+    fn is_expired(&self, now: ngx_msec_t) -> bool {
+        self.expires.is_some_and(|x| is_expired(x, now))
+    }
+}
+
+unsafe impl<K, V> NgxQueueEntry for LruEntry<K, V> {
+    fn from_queue(queue: NonNull<ngx_queue_t>) -> NonNull<Self> {
+        unsafe { ngx_queue_data!(queue, Self, queue) }
+    }
+
+    fn to_queue(&mut self) -> &mut ngx_queue_t {
+        &mut self.queue
+    }
+}
+
+impl<K, V, A> LruCache<K, V, A>
+where
+    A: Allocator,
+{
+    /// Returns a reference to the underlying allocator.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Returns the maximum number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of entries in the cache, including the expired ones.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the cache contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sets the lifetime of the entries inserted or updated after the call.
+    ///
+    /// Lifetime is limited to approximately 24 days.
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Returns an iterator over the unexpired entries, from the most recently used.
+    pub fn iter(&self) -> LruIter<'_, K, V> {
+        LruIter {
+            // SAFETY: the queue head is allocated with the cache.
+            inner: NgxQueueIter::new(unsafe { self.queue.as_ref() }),
+            now: current_msec(),
+            _type: PhantomData,
+        }
+    }
+
+    /// Removes the least recently used entry and returns it, even if the entry is expired.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let entry = self.last()?;
+        // SAFETY: the entry belongs to the cache.
+        Some(unsafe { self.remove_entry_ptr(entry) })
+    }
+
+    /// Removes up to `max` expired entries, starting from the least recently used.
+    ///
+    /// Returns the number of removed entries.
+    pub fn expire(&mut self, max: usize) -> usize {
+        let now = current_msec();
+        let mut removed = 0;
+
+        while removed < max {
+            let Some(entry) = self.last() else {
+                break;
+            };
+
+            // The entries are ordered by the last update time only when the lifetime is the same
+            // for all of them, so scanning stops at the first unexpired entry.
+            if !unsafe { entry.as_ref() }.is_expired(now) {
+                break;
+            }
+
+            // SAFETY: the entry belongs to the cache.
+            drop(unsafe { self.remove_entry_ptr(entry) });
+            removed += 1;
+        }
+
+        removed
+    }
+
+    /// Removes all the entries.
+    pub fn clear(&mut self) {
+        while self.pop_lru().is_some() {}
+    }
+
+    /// Returns the least recently used entry.
+    fn last(&self) -> Option<NonNull<LruEntry<K, V>>> {
+        if self.is_empty() {
+            return None;
+        }
+        // SAFETY: the queue head is allocated with the cache.
+        let last = NonNull::new(unsafe { self.queue.as_ref() }.prev)?;
+        Some(LruEntry::from_queue(last))
+    }
+
+    /// Moves the entry to the front of the queue.
+    ///
+    /// # Safety
+    ///
+    /// `entry` is an element of this cache.
+    unsafe fn touch(&mut self, mut entry: NonNull<LruEntry<K, V>>) {
+        ngx_queue_remove(&mut entry.as_mut().queue);
+        ngx_queue_insert_after(self.queue.as_ptr(), &mut entry.as_mut().queue);
+    }
+
+    /// Unlinks the entry and releases the memory, returning the key and value.
+    ///
+    /// # Safety
+    ///
+    /// `entry` is an element of this cache.
+    unsafe fn remove_entry_ptr(&mut self, mut entry: NonNull<LruEntry<K, V>>) -> (K, V) {
+        ngx_queue_remove(&mut entry.as_mut().queue);
+        ngx_rbtree_delete(&mut self.tree, &mut entry.as_mut().node);
+        self.len -= 1;
+
+        let layout = Layout::for_value(entry.as_ref());
+        // SAFETY: we make a bitwise copy of the entry and dispose of the original value without
+        // dropping it.
+        let copy = entry.as_ptr().read();
+        self.allocator().deallocate(entry.cast(), layout);
+        (copy.key, copy.value)
+    }
+}
+
+impl<K, V, A> LruCache<K, V, A>
+where
+    A: Allocator,
+    K: Hash + Ord,
+{
+    /// Attempts to create a new cache for up to `capacity` entries with specified allocator.
+    pub fn try_new_in(capacity: usize, alloc: A) -> Result<Self, AllocError> {
+        let layout = Layout::new::<ngx_rbtree_node_t>();
+        let sentinel: NonNull<ngx_rbtree_node_t> = alloc.allocate_zeroed(layout)?.cast();
+
+        let queue: ngx_queue_t = unsafe { mem::zeroed() };
+        let queue = match allocator::allocate(queue, &alloc) {
+            Ok(queue) => queue,
+            Err(err) => {
+                unsafe { alloc.deallocate(sentinel.cast(), layout) };
+                return Err(err);
+            }
+        };
+
+        let mut this = Self {
+            tree: unsafe { mem::zeroed() },
+            sentinel,
+            queue,
+            _type: PhantomData,
+            len: 0,
+            capacity,
+            ttl: None,
+            alloc,
+        };
+
+        unsafe {
+            ngx_queue_init(this.queue.as_ptr());
+            ngx_rbtree_init(&mut this.tree, this.sentinel.as_ptr(), Some(Self::insert));
+        };
+
+        Ok(this)
+    }
+
+    /// Returns a mutable reference to the unexpired value corresponding to the key, and marks the
+    /// entry as the most recently used.
+    ///
+    /// An expired entry is removed.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let mut entry = self.lookup(key)?;
+
+        if unsafe { entry.as_ref() }.is_expired(current_msec()) {
+            // SAFETY: the entry was found in this cache.
+            drop(unsafe { self.remove_entry_ptr(entry) });
+            return None;
+        }
+
+        // SAFETY: the entry was found in this cache.
+        unsafe {
+            self.touch(entry);
+            Some(&mut entry.as_mut().value)
+        }
+    }
+
+    /// Returns a reference to the unexpired value corresponding to the key, without updating the
+    /// access order.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let entry = unsafe { self.lookup(key)?.as_ref() };
+        if entry.is_expired(current_msec()) {
+            return None;
+        }
+        Some(&entry.value)
+    }
+
+    /// Returns `true` if the cache contains an unexpired value for the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// Removes a key from the cache, returning the value if it was present and not expired.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let entry = self.lookup(key)?;
+        let expired = unsafe { entry.as_ref() }.is_expired(current_msec());
+        // SAFETY: the entry was found in this cache.
+        let (_, value) = unsafe { self.remove_entry_ptr(entry) };
+        (!expired).then_some(value)
+    }
+
+    /// Inserts a key-value pair into the cache and marks the entry as the most recently used.
+    ///
+    /// If the key is already present, the value is replaced and the expiration time is updated.
+    /// Otherwise, if the cache is full, the least recently used entry is evicted. If the allocation
+    /// fails, the least recently used entries are evicted to free the memory until the allocation
+    /// succeeds, as NGINX does for the shared memory zones.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&mut V, AllocError> {
+        let expires = self.ttl.map(expires_at);
+
+        if let Some(mut entry) = self.lookup(&key) {
+            // SAFETY: the entry was found in this cache.
+            unsafe {
+                let e = entry.as_mut();
+                e.value = value;
+                e.expires = expires;
+                self.touch(entry);
+                return Ok(&mut entry.as_mut().value);
+            }
+        }
+
+        if self.len >= self.capacity {
+            if self.capacity == 0 {
+                return Err(AllocError);
+            }
+            self.pop_lru();
+        }
+
+        let mut node: ngx_rbtree_node_t = unsafe { mem::zeroed() };
+        node.key = BuildMapHasher::default().hash_one(&key) as ngx_rbtree_key_t;
+
+        let mut ptr = loop {
+            match self.alloc.allocate(Layout::new::<LruEntry<K, V>>()) {
+                Ok(ptr) => break ptr.cast::<LruEntry<K, V>>(),
+                Err(err) if self.is_empty() => return Err(err),
+                Err(_) => {
+                    // Free the memory and try again. Two entries are removed at once, as a
+                    // single slot of a smaller size may not be enough for the new entry.
+                    self.pop_lru();
+                    self.pop_lru();
+                }
+            }
+        };
+
+        // SAFETY: the memory is allocated for the entry.
+        unsafe {
+            ptr.as_ptr().write(LruEntry {
+                node,
+                queue: mem::zeroed(),
+                expires,
+                key,
+                value,
+            });
+
+            let entry = ptr.as_mut();
+            ngx_rbtree_insert(&mut self.tree, &mut entry.node);
+            ngx_queue_insert_after(self.queue.as_ptr(), &mut entry.queue);
+            self.len += 1;
+            Ok(&mut entry.value)
+        }
+    }
+
+    extern "C" fn insert(
+        mut temp: *mut ngx_rbtree_node_t,
+        node: *mut ngx_rbtree_node_t,
+        sentinel: *mut ngx_rbtree_node_t,
+    ) {
+        let n = unsafe { &mut *ngx_rbtree_data!(node, LruEntry<K, V>, node) };
+
+        loop {
+            let t = unsafe { &mut *ngx_rbtree_data!(temp, LruEntry<K, V>, node) };
+            let p = match Ord::cmp(&n.node.key, &t.node.key) {
+                Ordering::Less => &mut t.node.left,
+                Ordering::Greater => &mut t.node.right,
+                Ordering::Equal => match Ord::cmp(&n.key, &t.key) {
+                    Ordering::Less => &mut t.node.left,
+                    // equal keys are handled in try_insert
+                    _ => &mut t.node.right,
+                },
+            };
+
+            if ptr::addr_eq(*p, sentinel) {
+                *p = node;
+                break;
+            }
+
+            temp = *p;
+        }
+
+        n.node.parent = temp;
+        n.node.left = sentinel;
+        n.node.right = sentinel;
+        unsafe { ngx_rbt_red(node) };
+    }
+
+    fn lookup<Q>(&self, key: &Q) -> Option<NonNull<LruEntry<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let tree = &self.tree;
+        let mut node = tree.root;
+        let hash = BuildMapHasher::default().hash_one(key) as ngx_rbtree_key_t;
+
+        while !ptr::addr_eq(node, tree.sentinel) {
+            let n = unsafe { NonNull::new_unchecked(ngx_rbtree_data!(node, LruEntry<K, V>, node)) };
+            let nr = unsafe { n.as_ref() };
+
+            node = match Ord::cmp(&hash, &nr.node.key) {
+                Ordering::Less => nr.node.left,
+                Ordering::Greater => nr.node.right,
+                Ordering::Equal => match Ord::cmp(key, nr.key.borrow()) {
+                    Ordering::Less => nr.node.left,
+                    Ordering::Greater => nr.node.right,
+                    Ordering::Equal => return Some(n),
+                },
+            }
+        }
+
+        None
+    }
+}
+
+impl<K, V, A> Drop for LruCache<K, V, A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        self.clear();
+
+        unsafe {
+            self.allocator()
+                .deallocate(self.queue.cast(), Layout::for_value(self.queue.as_ref()));
+            self.allocator().deallocate(
+                self.sentinel.cast(),
+                Layout::for_value(self.sentinel.as_ref()),
+            );
+        }
+    }
+}
+
+unsafe impl<K, V, A> Send for LruCache<K, V, A>
+where
+    A: Send + Allocator,
+    K: Send,
+    V: Send,
+{
+}
+
+unsafe impl<K, V, A> Sync for LruCache<K, V, A>
+where
+    A: Sync + Allocator,
+    K: Sync,
+    V: Sync,
+{
+}
+
+/// An iterator for the [LruCache], from the most recently used entry.
+pub struct LruIter<'a, K: 'a, V: 'a> {
+    inner: NgxQueueIter<'a, LruEntry<K, V>>,
+    now: ngx_msec_t,
+    _type: PhantomData<(K, V)>,
+}
+
+impl<'a, K: 'a, V: 'a> Iterator for LruIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = self.inner.next()?;
+            if !entry.is_expired(self.now) {
+                return Some((&entry.key, &entry.value));
+            }
+        }
+    }
+}
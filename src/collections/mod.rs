@@ -10,8 +10,14 @@ pub use allocator_api2::{
     vec::Vec,
 };
 
+pub use hash::{NgxHash, NgxHashBuilder};
+pub use list::NgxList;
+pub use lru::LruCache;
 pub use queue::Queue;
 pub use rbtree::RbTreeMap;
 
+pub mod hash;
+pub mod list;
+pub mod lru;
 pub mod queue;
 pub mod rbtree;
@@ -6,13 +6,14 @@
 //! See <https://nginx.org/en/docs/dev/development_guide.html#queue>.
 
 use core::alloc::Layout;
+use core::cmp::Ordering;
 use core::marker::PhantomData;
 use core::mem;
 use core::ptr::{self, NonNull};
 
 use nginx_sys::{
-    ngx_queue_data, ngx_queue_empty, ngx_queue_init, ngx_queue_insert_after,
-    ngx_queue_insert_before, ngx_queue_remove, ngx_queue_t,
+    ngx_queue_add, ngx_queue_data, ngx_queue_empty, ngx_queue_init, ngx_queue_insert_after,
+    ngx_queue_insert_before, ngx_queue_middle, ngx_queue_remove, ngx_queue_split, ngx_queue_t,
 };
 
 use crate::allocator::{AllocError, Allocator};
@@ -140,6 +141,181 @@ where
     pub fn iter_mut(&mut self) -> NgxQueueIterMut<'_, T> {
         NgxQueueIterMut::new(&mut self.head)
     }
+
+    /// Returns the middle element of the queue.
+    ///
+    /// For a queue with an even number of elements, returns the first element of the second half.
+    pub fn middle(&self) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        // SAFETY: the queue is initialized and not empty. ngx_queue_middle does not modify it.
+        let q = unsafe { ngx_queue_middle(ptr::from_ref(&self.head).cast_mut()) };
+        Some(unsafe { T::from_queue(NonNull::new_unchecked(q)).as_ref() })
+    }
+
+    /// Moves all the elements of `other` to the end of the queue, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        if other.is_empty() {
+            return;
+        }
+
+        if self.head.prev.is_null() {
+            unsafe { ngx_queue_init(&mut self.head) }
+        }
+
+        unsafe {
+            ngx_queue_add(&mut self.head, &mut other.head);
+            ngx_queue_init(&mut other.head);
+        }
+    }
+
+    /// Splits the queue in two at the given element.
+    ///
+    /// `other` is reinitialized and receives `at` and all the following elements.
+    ///
+    /// # Safety
+    ///
+    /// `at` must be an element of this queue.
+    pub unsafe fn split(&mut self, at: &mut T, other: &mut Self) {
+        ngx_queue_split(&mut self.head, at.to_queue(), &mut other.head);
+    }
+
+    /// Sorts the queue with a comparator function.
+    ///
+    /// The sort is stable, and is performed in place by relinking the nodes, with O(n log n)
+    /// comparisons. If the comparator panics, the queue retains its original order.
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        if self.is_empty() {
+            return;
+        }
+
+        let head = ptr::addr_of_mut!(self.head);
+
+        // SAFETY: the queue is not empty and the nodes are valid elements of the queue.
+        unsafe {
+            let restore = RestoreOrder(head);
+
+            (*(*head).prev).next = ptr::null_mut();
+            let mut node = merge_sort((*head).next, |a, b| {
+                let a = T::from_queue(NonNull::new_unchecked(a));
+                let b = T::from_queue(NonNull::new_unchecked(b));
+                compare(a.as_ref(), b.as_ref()) == Ordering::Greater
+            });
+
+            mem::forget(restore);
+
+            let mut prev = head;
+            while !node.is_null() {
+                (*node).prev = prev;
+                (*prev).next = node;
+                prev = node;
+                node = (*node).next;
+            }
+            (*prev).next = head;
+            (*head).prev = prev;
+        }
+    }
+
+    /// Sorts the queue with a key extraction function.
+    ///
+    /// See [NgxQueue::sort_by].
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)))
+    }
+}
+
+/// Sorts a null-terminated list linked with the `next` pointers of the nodes.
+///
+/// This is a bottom-up merge sort: the runs of 1, 2, 4... nodes are merged until a single run
+/// remains. A node from the left run is taken first unless it is greater than the node from the
+/// right run, which makes the sort stable. The `prev` pointers are not modified.
+unsafe fn merge_sort<F>(mut list: *mut ngx_queue_t, mut is_greater: F) -> *mut ngx_queue_t
+where
+    F: FnMut(*mut ngx_queue_t, *mut ngx_queue_t) -> bool,
+{
+    let mut run = 1;
+
+    loop {
+        let mut p = list;
+        let mut tail: *mut ngx_queue_t = ptr::null_mut();
+        let mut merges = 0;
+
+        list = ptr::null_mut();
+
+        while !p.is_null() {
+            merges += 1;
+
+            let mut q = p;
+            let mut psize = 0;
+            while psize < run && !q.is_null() {
+                psize += 1;
+                q = (*q).next;
+            }
+            let mut qsize = run;
+
+            while psize > 0 || (qsize > 0 && !q.is_null()) {
+                let e;
+                if psize == 0 || (qsize > 0 && !q.is_null() && is_greater(p, q)) {
+                    e = q;
+                    q = (*q).next;
+                    qsize -= 1;
+                } else {
+                    e = p;
+                    p = (*p).next;
+                    psize -= 1;
+                }
+
+                if tail.is_null() {
+                    list = e;
+                } else {
+                    (*tail).next = e;
+                }
+                tail = e;
+            }
+
+            p = q;
+        }
+
+        (*tail).next = ptr::null_mut();
+
+        if merges <= 1 {
+            return list;
+        }
+
+        run *= 2;
+    }
+}
+
+/// Restores the original order of a queue if the sort is interrupted by a panic.
+///
+/// The sort only modifies the `next` pointers until it completes, so the order is recovered from
+/// the `prev` pointers.
+struct RestoreOrder(*mut ngx_queue_t);
+
+impl Drop for RestoreOrder {
+    fn drop(&mut self) {
+        let head = self.0;
+        // SAFETY: the `prev` pointers still link the nodes of the queue in the original order.
+        unsafe {
+            let mut next = head;
+            let mut node = (*head).prev;
+            while node != head {
+                (*node).next = next;
+                next = node;
+                node = (*node).prev;
+            }
+            (*head).next = next;
+        }
+    }
 }
 
 /// An iterator for the queue.
@@ -335,6 +511,73 @@ impl<T, A: Allocator> Queue<T, A> {
         Ok(&mut entry.item)
     }
 
+    /// Returns the middle element of the list.
+    ///
+    /// For a list with an even number of elements, returns the first element of the second half.
+    pub fn middle(&self) -> Option<&T> {
+        Some(&self.raw().middle()?.item)
+    }
+
+    /// Splits the list in two at the given index.
+    ///
+    /// Returns a new list with the elements starting from `at`, allocated with a clone of the
+    /// list allocator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Result<Self, AllocError>
+    where
+        A: Clone,
+    {
+        assert!(at <= self.len, "split index out of bounds");
+
+        let mut other = Self::try_new_in(self.alloc.clone())?;
+        if at == self.len {
+            return Ok(other);
+        }
+
+        let mut node = NonNull::from(self.raw_mut().iter_mut().nth(at).expect("split node"));
+        // SAFETY: the node is an element of this list.
+        unsafe { self.raw.as_mut().split(node.as_mut(), other.raw.as_mut()) };
+
+        other.len = self.len - at;
+        self.len = at;
+
+        Ok(other)
+    }
+
+    /// Sorts the list with a comparator function.
+    ///
+    /// See [NgxQueue::sort_by].
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.raw_mut().sort_by(|a, b| compare(&a.item, &b.item))
+    }
+
+    /// Sorts the list with a key extraction function.
+    ///
+    /// See [NgxQueue::sort_by].
+    pub fn sort_by_key<K, F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        self.sort_by(|a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Sorts the list.
+    ///
+    /// See [NgxQueue::sort_by].
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.sort_by(T::cmp)
+    }
+
     fn raw(&self) -> &NgxQueue<QueueEntry<T>> {
         // SAFETY: we allocated this pointer as well-aligned and convertible to reference.
         unsafe { self.raw.as_ref() }
@@ -156,7 +156,7 @@ impl Iterator for NgxRbTreeIter<'_> {
 }
 
 #[allow(deprecated)]
-type BuildMapHasher = core::hash::BuildHasherDefault<hash::SipHasher>;
+pub(crate) type BuildMapHasher = core::hash::BuildHasherDefault<hash::SipHasher>;
 
 /// A map type based on the `ngx_rbtree_t`.
 ///
@@ -164,6 +164,8 @@ type BuildMapHasher = core::hash::BuildHasherDefault<hash::SipHasher>;
 /// The order of the elements is an undocumented implementation detail.
 ///
 /// This is a `ngx`-specific high-level type with no direct counterpart in the NGINX code.
+///
+/// The elements are ordered by the hash of the key, and thus range queries are not supported.
 #[derive(Debug)]
 pub struct RbTreeMap<K, V, A>
 where
@@ -171,6 +173,7 @@ where
 {
     tree: NgxRbTree<MapEntry<K, V>>,
     sentinel: NonNull<ngx_rbtree_node_t>,
+    len: usize,
     alloc: A,
 }
 
@@ -265,6 +268,11 @@ where
         &self.alloc
     }
 
+    /// Returns the number of elements in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
     /// Clears the tree, removing all elements.
     pub fn clear(&mut self) {
         // SAFETY: the iter lives until the end of the scope
@@ -280,6 +288,48 @@ where
                 self.allocator().deallocate(data.cast(), layout)
             }
         }
+
+        self.len = 0;
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// Removes all pairs `(k, v)` for which `f(&k, &mut v)` returns `false`. The elements are
+    /// visited in the tree order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        // SAFETY: the iterator keeps a pointer to the next node and remains valid if the current
+        // node is removed.
+        let iter = unsafe { NgxRbTreeIter::new(NonNull::from(&self.tree.inner)) };
+
+        for node in iter {
+            let mut data = MapEntry::<K, V>::from_rbtree_node(node);
+            let entry = unsafe { data.as_mut() };
+
+            if !f(&entry.key, &mut entry.value) {
+                // SAFETY: the node belongs to this tree.
+                drop(unsafe { self.remove_node(data) });
+            }
+        }
+    }
+
+    /// Unlinks the node from the tree and releases the memory, returning the key and value.
+    ///
+    /// # Safety
+    ///
+    /// `node` is an element of this tree.
+    unsafe fn remove_node(&mut self, mut node: NonNull<MapEntry<K, V>>) -> (K, V) {
+        self.tree.remove(node.as_mut());
+        self.len -= 1;
+
+        let layout = Layout::for_value(node.as_ref());
+        // SAFETY: we make a bitwise copy of the node and dispose of the original value without
+        // dropping it.
+        let copy = node.as_ptr().read();
+        self.allocator().deallocate(node.cast(), layout);
+        copy.into_kv()
     }
 
     /// Returns true if the tree contains no entries.
@@ -318,6 +368,7 @@ where
         let mut this = RbTreeMap {
             tree,
             sentinel,
+            len: 0,
             alloc,
         };
 
@@ -368,17 +419,9 @@ where
         K: borrow::Borrow<Q>,
         Q: Hash + Ord + ?Sized,
     {
-        let mut node = self.lookup(key)?;
-        unsafe {
-            self.tree.remove(node.as_mut());
-
-            let layout = Layout::for_value(node.as_ref());
-            // SAFETY: we make a bitwise copy of the node and dispose of the original value without
-            // dropping it.
-            let copy = node.as_ptr().read();
-            self.allocator().deallocate(node.cast(), layout);
-            Some(copy.into_kv())
-        }
+        let node = self.lookup(key)?;
+        // SAFETY: the node was found in this tree.
+        Some(unsafe { self.remove_node(node) })
     }
 
     /// Attempts to insert a new element into the tree.
@@ -387,15 +430,40 @@ where
             unsafe { node.as_mut().value = value };
             node
         } else {
-            let node = MapEntry::new(key, value);
-            let mut node = allocator::allocate(node, self.allocator())?;
-            self.tree.insert(unsafe { node.as_mut() });
-            node
+            self.insert_new(key, value)?
         };
 
         Ok(unsafe { &mut node.as_mut().value })
     }
 
+    /// Gets the entry for the key for in-place manipulation.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::allocator::AllocError;
+    /// # use ngx::collections::RbTreeMap;
+    /// # use ngx::core::Pool;
+    /// # fn example(map: &mut RbTreeMap<u64, usize, Pool>) -> Result<(), AllocError> {
+    /// let hits = map.entry(42).or_try_insert_with(|| 0)?;
+    /// *hits += 1;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A> {
+        match self.lookup(&key) {
+            Some(node) => Entry::Occupied(OccupiedEntry { map: self, node }),
+            None => Entry::Vacant(VacantEntry { map: self, key }),
+        }
+    }
+
+    fn insert_new(&mut self, key: K, value: V) -> Result<NonNull<MapEntry<K, V>>, AllocError> {
+        let node = MapEntry::new(key, value);
+        let mut node = allocator::allocate(node, self.allocator())?;
+        self.tree.insert(unsafe { node.as_mut() });
+        self.len += 1;
+        Ok(node)
+    }
+
     extern "C" fn insert(
         mut temp: *mut ngx_rbtree_node_t,
         node: *mut ngx_rbtree_node_t,
@@ -457,6 +525,143 @@ where
     }
 }
 
+/// A view into a single entry of the [RbTreeMap], either vacant or occupied.
+///
+/// Constructed with [RbTreeMap::entry].
+pub enum Entry<'a, K, V, A>
+where
+    A: Allocator,
+{
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, A>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, A>),
+}
+
+impl<'a, K, V, A> Entry<'a, K, V, A>
+where
+    A: Allocator,
+    K: Hash + Ord,
+{
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Modifies the value of an occupied entry.
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+
+    /// Attempts to insert `default` if the entry is vacant, and returns a mutable reference to
+    /// the value.
+    pub fn or_try_insert(self, default: V) -> Result<&'a mut V, AllocError> {
+        self.or_try_insert_with(|| default)
+    }
+
+    /// Attempts to insert the result of `f` if the entry is vacant, and returns a mutable
+    /// reference to the value.
+    pub fn or_try_insert_with<F>(self, f: F) -> Result<&'a mut V, AllocError>
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.try_insert(f()),
+        }
+    }
+}
+
+/// An occupied entry of the [RbTreeMap].
+pub struct OccupiedEntry<'a, K, V, A>
+where
+    A: Allocator,
+{
+    map: &'a mut RbTreeMap<K, V, A>,
+    node: NonNull<MapEntry<K, V>>,
+}
+
+impl<'a, K, V, A> OccupiedEntry<'a, K, V, A>
+where
+    A: Allocator,
+{
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &K {
+        unsafe { &self.node.as_ref().key }
+    }
+
+    /// Returns a reference to the value.
+    pub fn get(&self) -> &V {
+        unsafe { &self.node.as_ref().value }
+    }
+
+    /// Returns a mutable reference to the value.
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut self.node.as_mut().value }
+    }
+
+    /// Converts the entry into a mutable reference to the value with the lifetime of the map.
+    pub fn into_mut(mut self) -> &'a mut V {
+        unsafe { &mut self.node.as_mut().value }
+    }
+
+    /// Replaces the value, returning the old value.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry from the map, returning the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the map, returning the key and the value.
+    pub fn remove_entry(self) -> (K, V) {
+        // SAFETY: the node belongs to the map.
+        unsafe { self.map.remove_node(self.node) }
+    }
+}
+
+/// A vacant entry of the [RbTreeMap].
+pub struct VacantEntry<'a, K, V, A>
+where
+    A: Allocator,
+{
+    map: &'a mut RbTreeMap<K, V, A>,
+    key: K,
+}
+
+impl<'a, K, V, A> VacantEntry<'a, K, V, A>
+where
+    A: Allocator,
+    K: Hash + Ord,
+{
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes the ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Attempts to insert the value, and returns a mutable reference to it.
+    pub fn try_insert(self, value: V) -> Result<&'a mut V, AllocError> {
+        let mut node = self.map.insert_new(self.key, value)?;
+        Ok(unsafe { &mut node.as_mut().value })
+    }
+}
+
 impl<K, V, A> Drop for RbTreeMap<K, V, A>
 where
     A: Allocator,
@@ -0,0 +1,265 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::fmt;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use crate::allocator::{dangling_for_layout, AllocError, Allocator};
+use crate::core::Pool;
+
+/// A bump allocator carving allocations out of a single block of memory.
+///
+/// The arena is intended for short-lived temporary data, such as the intermediate state of a
+/// parser or a matcher. All the allocations made from the arena are released at once with
+/// [Arena::reset], so the same block can be reused between the processing phases of a request
+/// without growing the memory pool or registering cleanup handlers.
+///
+/// The block is allocated from `A`, usually a [Pool]. When the block is exhausted, the allocations
+/// are forwarded to `A` and released according to its rules.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::allocator::{AllocError, Box};
+/// # use ngx::core::{Arena, Pool};
+/// # fn example(pool: Pool) -> Result<(), AllocError> {
+/// let mut arena = Arena::with_capacity_in(16384, pool)?;
+///
+/// for _ in 0..3 {
+///     let tokens = Box::try_new_in([0u32; 64], &arena)?;
+///     // ...
+///     drop(tokens);
+///     arena.reset();
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Arena<A: Allocator = Pool> {
+    block: NonNull<u8>,
+    capacity: usize,
+    pos: Cell<usize>,
+    alloc: A,
+}
+
+impl<A: Allocator> Arena<A> {
+    /// Creates an arena with a block of `capacity` bytes allocated from `alloc`.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Result<Self, AllocError> {
+        let block = alloc.allocate(Self::block_layout(capacity)?)?.cast();
+
+        Ok(Self {
+            block,
+            capacity,
+            pos: Cell::new(0),
+            alloc,
+        })
+    }
+
+    /// Returns the size of the block.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of bytes used in the block, including the alignment padding.
+    pub fn used(&self) -> usize {
+        self.pos.get()
+    }
+
+    /// Returns the number of bytes remaining in the block.
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.pos.get()
+    }
+
+    /// Returns a reference to the underlying allocator.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Releases all the allocations made from the block.
+    ///
+    /// The allocations forwarded to the underlying allocator are not affected.
+    pub fn reset(&mut self) {
+        self.pos.set(0);
+    }
+
+    fn block_layout(capacity: usize) -> Result<Layout, AllocError> {
+        Layout::from_size_align(capacity, mem::align_of::<usize>()).map_err(|_| AllocError)
+    }
+
+    /// Returns the offset of `ptr` in the block, if it belongs to the block.
+    fn offset_of(&self, ptr: NonNull<u8>) -> Option<usize> {
+        let offset = (ptr.as_ptr() as usize).checked_sub(self.block.as_ptr() as usize)?;
+        (offset < self.capacity).then_some(offset)
+    }
+
+    /// Returns `true` if the allocation at `offset` is the last one in the block.
+    fn is_last(&self, offset: usize, layout: Layout) -> bool {
+        offset + layout.size() == self.pos.get()
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for Arena<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            return Ok(NonNull::slice_from_raw_parts(
+                dangling_for_layout(&layout),
+                0,
+            ));
+        }
+
+        let base = self.block.as_ptr() as usize;
+        let start = (base + self.pos.get())
+            .checked_next_multiple_of(layout.align())
+            .map(|x| x - base);
+
+        match start {
+            Some(start) if layout.size() <= self.capacity - start.min(self.capacity) => {
+                self.pos.set(start + layout.size());
+                // SAFETY: the range is within the block
+                let ptr = unsafe { NonNull::new_unchecked(self.block.as_ptr().add(start)) };
+                Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+            }
+            _ => self.alloc.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+
+        match self.offset_of(ptr) {
+            // reclaim the space if the allocation is the last one
+            Some(offset) if self.is_last(offset, layout) => self.pos.set(offset),
+            Some(_) => {}
+            None => self.alloc.deallocate(ptr, layout),
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if old_layout.size() > 0 {
+            if let Some(offset) = self.offset_of(ptr) {
+                // extend the last allocation in place
+                if self.is_last(offset, old_layout)
+                    && ptr.as_ptr().align_offset(new_layout.align()) == 0
+                    && new_layout.size() <= self.capacity - offset
+                {
+                    self.pos.set(offset + new_layout.size());
+                    return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+                }
+            } else {
+                return self.alloc.grow(ptr, old_layout, new_layout);
+            }
+        }
+
+        let new = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), new.cast().as_ptr(), old_layout.size());
+        self.deallocate(ptr, old_layout);
+        Ok(new)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        match self.offset_of(ptr) {
+            Some(offset)
+                if old_layout.size() > 0 && ptr.as_ptr().align_offset(new_layout.align()) == 0 =>
+            {
+                if self.is_last(offset, old_layout) {
+                    self.pos.set(offset + new_layout.size());
+                }
+                Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+            }
+            None if old_layout.size() > 0 => self.alloc.shrink(ptr, old_layout, new_layout),
+            _ => {
+                let new = self.allocate(new_layout)?;
+                ptr::copy_nonoverlapping(ptr.as_ptr(), new.cast().as_ptr(), new_layout.size());
+                self.deallocate(ptr, old_layout);
+                Ok(new)
+            }
+        }
+    }
+}
+
+impl<A: Allocator> Drop for Arena<A> {
+    fn drop(&mut self) {
+        // SAFETY: the block was allocated from `alloc` with the same layout
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(self.capacity, mem::align_of::<usize>());
+            self.alloc.deallocate(self.block, layout);
+        }
+    }
+}
+
+impl<A: Allocator> fmt::Debug for Arena<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Arena")
+            .field("capacity", &self.capacity)
+            .field("used", &self.pos.get())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::allocator::{Box, Global};
+
+    #[test]
+    fn test_arena_allocate() {
+        let mut arena = Arena::with_capacity_in(64, Global).unwrap();
+
+        let a = Box::try_new_in(1u8, &arena).unwrap();
+        let b = Box::try_new_in(2u64, &arena).unwrap();
+        assert_eq!(arena.used(), 16);
+        assert_eq!((*a, *b), (1, 2));
+
+        // only the last allocation is reclaimed
+        drop(b);
+        assert_eq!(arena.used(), 8);
+        drop(a);
+        assert_eq!(arena.used(), 8);
+
+        arena.reset();
+        assert_eq!(arena.used(), 0);
+        assert_eq!(arena.remaining(), 64);
+
+        // exhausted block falls back to the allocator
+        let c = Box::try_new_in([3u8; 48], &arena).unwrap();
+        let d = Box::try_new_in([4u8; 48], &arena).unwrap();
+        assert_eq!(arena.used(), 48);
+        assert_eq!((c[47], d[47]), (3, 4));
+
+        drop(d);
+        assert_eq!(arena.used(), 48);
+        drop(c);
+        assert_eq!(arena.used(), 0);
+    }
+
+    #[test]
+    fn test_arena_grow() {
+        let arena = Arena::with_capacity_in(64, Global).unwrap();
+
+        let mut v = allocator_api2::vec::Vec::<u8, _>::new_in(&arena);
+        v.extend_from_slice(b"hello");
+        let ptr = v.as_ptr();
+        v.extend_from_slice(b", world");
+        assert_eq!(v.as_ptr(), ptr);
+        assert_eq!(v.as_slice(), b"hello, world");
+
+        // grown past the block
+        v.extend_from_slice(&[b'!'; 100]);
+        assert_eq!(v.len(), 112);
+        assert_eq!(arena.used(), 0);
+
+        v.truncate(4);
+        v.shrink_to_fit();
+        assert_eq!(v.as_slice(), b"hell");
+    }
+}
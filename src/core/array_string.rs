@@ -0,0 +1,215 @@
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::str;
+
+/// A fixed-capacity string stored inline, e.g. on the stack.
+///
+/// The string is intended for formatting messages without an allocator. A write exceeding the
+/// capacity is truncated at a character boundary, so the contents always remain valid UTF-8, and
+/// the [fmt::Write] implementation never fails. Use [ArrayString::is_truncated] to detect the
+/// truncation.
+///
+/// Example:
+/// ```rust
+/// # use core::fmt::Write;
+/// # use ngx::core::ArrayString;
+/// let mut s = ArrayString::<16>::new();
+/// write!(s, "worker {}", 3).unwrap();
+/// assert_eq!(s.as_str(), "worker 3");
+///
+/// write!(s, ": {}", "connection refused").unwrap();
+/// assert_eq!(s.as_str(), "worker 3: connec");
+/// assert!(s.is_truncated());
+/// ```
+pub struct ArrayString<const N: usize> {
+    buf: [MaybeUninit<u8>; N],
+    len: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> ArrayString<N> {
+    /// Creates an empty string.
+    pub const fn new() -> Self {
+        Self {
+            buf: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Formats the arguments into a new string, truncating the output to the capacity.
+    pub fn from_fmt(args: fmt::Arguments<'_>) -> Self {
+        let mut s = Self::new();
+        if let Some(x) = args.as_str() {
+            s.push_str(x);
+        } else {
+            // the implementation of `fmt::Write` does not fail
+            let _ = fmt::Write::write_fmt(&mut s, args);
+        }
+        s
+    }
+
+    /// Returns the capacity of the string in bytes.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the length of the string in bytes.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the string is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if a write was truncated since the string was created or cleared.
+    pub const fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Returns the contents as a byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: valid bytes have been written to self.buf[..self.len]
+        unsafe { &*(self.buf.get_unchecked(..self.len) as *const [MaybeUninit<u8>] as *const [u8]) }
+    }
+
+    /// Returns the contents as a string slice.
+    pub fn as_str(&self) -> &str {
+        // SAFETY: only complete UTF-8 sequences are appended to the buffer
+        unsafe { str::from_utf8_unchecked(self.as_bytes()) }
+    }
+
+    /// Appends as much of `s` as fits into the remaining capacity.
+    ///
+    /// Returns `false` if `s` was truncated.
+    pub fn push_str(&mut self, s: &str) -> bool {
+        let mut n = s.len().min(N - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+
+        for (dst, src) in self.buf[self.len..self.len + n]
+            .iter_mut()
+            .zip(s.as_bytes())
+        {
+            dst.write(*src);
+        }
+        self.len += n;
+
+        if n < s.len() {
+            self.truncated = true;
+            return false;
+        }
+        true
+    }
+
+    /// Truncates the string to zero length and resets the truncation flag.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+}
+
+impl<const N: usize> Default for ArrayString<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Clone for ArrayString<N> {
+    fn clone(&self) -> Self {
+        let mut s = Self::new();
+        s.push_str(self.as_str());
+        s.truncated = self.truncated;
+        s
+    }
+}
+
+impl<const N: usize> Deref for ArrayString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<str> for ArrayString<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for ArrayString<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<const N: usize> PartialEq<str> for ArrayString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for ArrayString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const N: usize> fmt::Debug for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Display for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Write for ArrayString<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::ArrayString;
+
+    #[test]
+    fn test_array_string() {
+        let mut s = ArrayString::<8>::new();
+        assert!(s.is_empty());
+        assert_eq!(s.capacity(), 8);
+
+        write!(s, "{}-{}", 12, 34).unwrap();
+        assert_eq!(s, "12-34");
+        assert!(!s.is_truncated());
+
+        // multi-byte characters are not split
+        write!(s, "ÿÿ").unwrap();
+        assert_eq!(s, "12-34ÿ");
+        assert_eq!(s.len(), 7);
+        assert!(s.is_truncated());
+
+        // following writes are still safe
+        write!(s, "{}", usize::MAX).unwrap();
+        assert_eq!(s, "12-34ÿ1");
+
+        s.clear();
+        assert!(s.is_empty() && !s.is_truncated());
+
+        let s = ArrayString::<4>::from_fmt(format_args!("{:?}", "abc"));
+        assert_eq!(s, "\"abc");
+        assert_eq!(ArrayString::<4>::from_fmt(format_args!("abcdef")), "abcd");
+    }
+}
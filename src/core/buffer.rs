@@ -1,5 +1,8 @@
-use core::slice;
+use core::ops::Range;
+use core::{cmp, fmt, ptr, slice};
 
+use crate::allocator::AllocError;
+use crate::core::Pool;
 use crate::ffi::*;
 
 /// The `Buffer` trait provides methods for working with an nginx buffer (`ngx_buf_t`).
@@ -127,3 +130,233 @@ impl Buffer for MemoryBuffer {
         self.0
     }
 }
+
+/// Builder for a chain of buffers (`ngx_chain_t`) allocated from a memory pool.
+///
+/// The chain links are allocated with `ngx_alloc_chain_link` and may be reused by NGINX after the
+/// chain is sent. Bytes written with [ChainBuilder::write] or [fmt::Write] are copied into
+/// temporary buffers of [ChainBuilder::chunk_size] bytes, other buffers are linked as is.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::{ChainBuilder, Status};
+/// # use ngx::http::Request;
+/// # fn example(request: &mut Request) -> Result<Status, ngx::allocator::AllocError> {
+/// use core::fmt::Write;
+///
+/// let mut chain = ChainBuilder::new(request.pool());
+/// chain.write(b"Hello, ")?;
+/// write!(chain, "{}!\n", "world").map_err(|_| ngx::allocator::AllocError)?;
+/// chain.last_buf(true).last_in_chain(true);
+///
+/// let chain = chain.finish()?;
+/// Ok(request.output_filter(unsafe { &mut *chain }))
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChainBuilder {
+    pool: Pool,
+    head: *mut ngx_chain_t,
+    tail: *mut ngx_chain_t,
+    /// The last buffer, if it was allocated by the builder and has space for writing.
+    writable: *mut ngx_buf_t,
+    chunk_size: usize,
+    size: usize,
+    last_buf: bool,
+    last_in_chain: bool,
+}
+
+impl ChainBuilder {
+    /// The default size of the temporary buffers allocated for [ChainBuilder::write].
+    pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+    /// Creates an empty chain builder allocating from `pool`.
+    pub fn new(pool: Pool) -> Self {
+        Self {
+            pool,
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            writable: ptr::null_mut(),
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            size: 0,
+            last_buf: false,
+            last_in_chain: false,
+        }
+    }
+
+    /// Sets the minimal size of the temporary buffers allocated for [ChainBuilder::write].
+    pub fn chunk_size(&mut self, size: usize) -> &mut Self {
+        self.chunk_size = size;
+        self
+    }
+
+    /// Sets the `last_buf` flag on the final buffer of the chain, marking the end of the response.
+    pub fn last_buf(&mut self, last: bool) -> &mut Self {
+        self.last_buf = last;
+        self
+    }
+
+    /// Sets the `last_in_chain` flag on the final buffer of the chain, marking the end of the
+    /// (sub)request output.
+    pub fn last_in_chain(&mut self, last: bool) -> &mut Self {
+        self.last_in_chain = last;
+        self
+    }
+
+    /// Returns the pool the chain is allocated from.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    /// Returns `true` if no buffers were added to the chain.
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Returns the total size of the data in the chain, including the file ranges.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Appends a buffer to the chain.
+    pub fn push(&mut self, mut buf: impl Buffer) -> Result<&mut Self, AllocError> {
+        // SAFETY: the Buffer implementations wrap valid non-null buffer pointers
+        unsafe { self.push_buf(buf.as_ngx_buf_mut()) }
+    }
+
+    /// Appends a raw buffer to the chain.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be a valid pointer to a buffer that outlives the chain.
+    pub unsafe fn push_buf(&mut self, buf: *mut ngx_buf_t) -> Result<&mut Self, AllocError> {
+        let cl = ngx_alloc_chain_link(self.pool.as_ptr());
+        if cl.is_null() {
+            return Err(AllocError);
+        }
+
+        (*cl).buf = buf;
+        (*cl).next = ptr::null_mut();
+
+        if self.tail.is_null() {
+            self.head = cl;
+        } else {
+            (*self.tail).next = cl;
+        }
+
+        self.tail = cl;
+        self.writable = ptr::null_mut();
+        self.size += buf_size(buf);
+
+        Ok(self)
+    }
+
+    /// Appends a buffer referencing the `range` of an open file.
+    ///
+    /// # Safety
+    ///
+    /// `file` must be a valid pointer to an open file that outlives the chain, e.g. a file with a
+    /// pool cleanup handler registered in the same pool.
+    pub unsafe fn push_file(
+        &mut self,
+        file: *mut ngx_file_t,
+        range: Range<off_t>,
+    ) -> Result<&mut Self, AllocError> {
+        let buf = self.pool.calloc_type::<ngx_buf_t>();
+        if buf.is_null() {
+            return Err(AllocError);
+        }
+
+        (*buf).file = file;
+        (*buf).file_pos = range.start;
+        (*buf).file_last = range.end;
+        (*buf).set_in_file(1);
+
+        self.push_buf(buf)
+    }
+
+    /// Copies `data` to the temporary buffers at the end of the chain.
+    ///
+    /// Fills the remaining space of the last buffer allocated by a previous write before
+    /// allocating a new one.
+    pub fn write(&mut self, mut data: &[u8]) -> Result<&mut Self, AllocError> {
+        if let Some(buf) = unsafe { self.writable.as_mut() } {
+            let n = cmp::min(buf.end as usize - buf.last as usize, data.len());
+            unsafe {
+                ptr::copy_nonoverlapping(data.as_ptr(), buf.last, n);
+                buf.last = buf.last.add(n);
+            }
+            self.size += n;
+            data = &data[n..];
+        }
+
+        if data.is_empty() {
+            return Ok(self);
+        }
+
+        let mut buf = self
+            .pool
+            .create_buffer(cmp::max(data.len(), self.chunk_size))
+            .ok_or(AllocError)?;
+        let buf = buf.as_ngx_buf_mut();
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), (*buf).last, data.len());
+            (*buf).last = (*buf).last.add(data.len());
+            self.push_buf(buf)?;
+        }
+        self.writable = buf;
+
+        Ok(self)
+    }
+
+    /// Sets the requested flags on the final buffer and returns the first link of the chain.
+    ///
+    /// If the chain is empty and any of the flags is set, an empty buffer carrying the flags is
+    /// added. Otherwise, an empty chain is returned as a null pointer.
+    pub fn finish(mut self) -> Result<*mut ngx_chain_t, AllocError> {
+        if !self.last_buf && !self.last_in_chain {
+            return Ok(self.head);
+        }
+
+        if self.tail.is_null() {
+            let buf = self.pool.calloc_type::<ngx_buf_t>();
+            if buf.is_null() {
+                return Err(AllocError);
+            }
+            unsafe { self.push_buf(buf)? };
+        }
+
+        unsafe {
+            let buf = (*self.tail).buf;
+            if self.last_buf {
+                (*buf).set_last_buf(1);
+            }
+            if self.last_in_chain {
+                (*buf).set_last_in_chain(1);
+            }
+        }
+
+        Ok(self.head)
+    }
+}
+
+impl fmt::Write for ChainBuilder {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes()).map_err(|_| fmt::Error)?;
+        Ok(())
+    }
+}
+
+/// Returns the size of the buffer data in the same way as `ngx_buf_size()`.
+///
+/// # Safety
+///
+/// `buf` must be a valid buffer pointer.
+unsafe fn buf_size(buf: *const ngx_buf_t) -> usize {
+    let buf = &*buf;
+    if buf.temporary() != 0 || buf.memory() != 0 || buf.mmap() != 0 {
+        buf.last as usize - buf.pos as usize
+    } else {
+        (buf.file_last - buf.file_pos) as usize
+    }
+}
@@ -0,0 +1,115 @@
+use core::ffi::c_void;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use nginx_sys::{ngx_event_handler_pt, ngx_event_t};
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+
+/// A Rust closure stored in a memory pool, exposed as a C handler and data pointer pair.
+///
+/// Many NGINX APIs accept a function pointer along with an opaque `data` pointer passed back to
+/// the function: pool cleanups, event handlers, variable getters, `post_subrequest` handlers and
+/// others. `Callback` allocates the closure from a [Pool] and registers a pool cleanup dropping
+/// it, so the data pointer remains valid for the lifetime of the pool.
+///
+/// The handlers for the common signatures are provided by [Callback::handler] and
+/// [Callback::event_handler]. Other signatures can be implemented on top of
+/// [Callback::from_ptr].
+///
+/// The closure is invoked from the event loop and must not re-enter itself, e.g. by synchronously
+/// triggering the same handler.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::{Callback, Pool};
+/// # use ngx::ffi::ngx_event_t;
+/// # fn example(pool: &mut Pool, ev: &mut ngx_event_t) -> Result<(), ngx::allocator::AllocError> {
+/// let mut fired = 0;
+/// let cb = Callback::new_in(
+///     move |_ev: &mut ngx_event_t| {
+///         fired += 1;
+///     },
+///     pool,
+/// )?;
+/// cb.bind_event(ev);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Callback<F> {
+    data: NonNull<F>,
+    _type: PhantomData<F>,
+}
+
+impl<F> Clone for Callback<F> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<F> Copy for Callback<F> {}
+
+impl<F: 'static> Callback<F> {
+    /// Moves the closure to the pool and registers a cleanup handler to drop it.
+    pub fn new_in(f: F, pool: &mut Pool) -> Result<Self, AllocError> {
+        Ok(Self {
+            data: pool.allocate_with_cleanup(f)?,
+            _type: PhantomData,
+        })
+    }
+
+    /// Returns the data pointer to pass to the C API along with the handler.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.data.as_ptr().cast()
+    }
+
+    /// Returns the closure stored at the data pointer.
+    ///
+    /// Intended for implementing handlers with the signatures not covered by this type.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be a pointer obtained from [Callback::as_ptr] of a `Callback<F>` with the same
+    /// `F`, the pool must be alive, and the closure must not be accessed concurrently.
+    pub unsafe fn from_ptr<'a>(data: *mut c_void) -> &'a mut F {
+        &mut *data.cast::<F>()
+    }
+}
+
+impl<F: FnMut() + 'static> Callback<F> {
+    /// Returns a handler with the `void (*)(void *data)` signature, e.g. for
+    /// `ngx_pool_cleanup_t` or `ngx_thread_task_t` completion.
+    pub fn handler(&self) -> unsafe extern "C" fn(*mut c_void) {
+        callback_handler::<F>
+    }
+}
+
+impl<F: FnMut(&mut ngx_event_t) + 'static> Callback<F> {
+    /// Returns an event handler invoking the closure stored in `ev->data`.
+    pub fn event_handler(&self) -> ngx_event_handler_pt {
+        Some(event_handler::<F>)
+    }
+
+    /// Sets the event handler and data to invoke the closure.
+    pub fn bind_event(&self, ev: &mut ngx_event_t) {
+        ev.handler = self.event_handler();
+        ev.data = self.as_ptr();
+    }
+}
+
+impl<F> fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Callback").field(&self.data).finish()
+    }
+}
+
+unsafe extern "C" fn callback_handler<F: FnMut()>(data: *mut c_void) {
+    (*data.cast::<F>())()
+}
+
+unsafe extern "C" fn event_handler<F: FnMut(&mut ngx_event_t)>(ev: *mut ngx_event_t) {
+    let f = &mut *(*ev).data.cast::<F>();
+    f(&mut *ev)
+}
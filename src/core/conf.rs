@@ -0,0 +1,381 @@
+use core::error;
+use core::ffi::{c_char, c_void, CStr};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+
+use nginx_sys::{
+    ngx_command_t, ngx_conf_check_num_bounds, ngx_conf_deprecated, ngx_conf_deprecated_t,
+    ngx_conf_enum_t, ngx_conf_num_bounds_t, ngx_conf_parse, ngx_conf_post_t, ngx_conf_t, ngx_int_t,
+    ngx_log_t, ngx_str_t,
+};
+
+use crate::core::{Pool, NGX_CONF_OK};
+
+/// An error returned by [ConfRef::parse_block].
+///
+/// The error is already logged by the configuration parser.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfParseError;
+
+impl error::Error for ConfParseError {}
+
+impl fmt::Display for ConfParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("configuration parse error")
+    }
+}
+
+/// A reference to the configuration parser state, as passed to the directive handlers.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::ffi::{c_char, c_void};
+/// # use ngx::core::{ConfRef, NGX_CONF_ERROR, NGX_CONF_OK};
+/// # use ngx::ffi::{ngx_command_t, ngx_conf_t};
+/// // example_routes {
+/// //     /api  backend;
+/// //     /     static;
+/// // }
+/// extern "C" fn example_routes(
+///     cf: *mut ngx_conf_t,
+///     _cmd: *mut ngx_command_t,
+///     _conf: *mut c_void,
+/// ) -> *mut c_char {
+///     let mut cf = unsafe { ConfRef::from_ptr(cf) };
+///
+///     let res = cf.parse_block(|cf| {
+///         let [prefix, target] = cf.args() else {
+///             return Err(c"invalid number of arguments in route");
+///         };
+///         // store the route
+///         let _ = (prefix, target);
+///         Ok(())
+///     });
+///
+///     match res {
+///         Ok(()) => NGX_CONF_OK,
+///         Err(_) => NGX_CONF_ERROR,
+///     }
+/// }
+/// ```
+pub struct ConfRef<'a> {
+    cf: NonNull<ngx_conf_t>,
+    _lifetime: PhantomData<&'a mut ngx_conf_t>,
+}
+
+impl<'a> ConfRef<'a> {
+    /// Creates a reference from a pointer to [ngx_conf_t].
+    ///
+    /// # Safety
+    ///
+    /// `cf` is a valid pointer to the configuration parser state, alive for `'a`.
+    pub unsafe fn from_ptr(cf: *mut ngx_conf_t) -> Self {
+        debug_assert!(!cf.is_null());
+        Self {
+            cf: NonNull::new_unchecked(cf),
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns a raw pointer to the underlying [ngx_conf_t].
+    pub fn as_ptr(&self) -> *mut ngx_conf_t {
+        self.cf.as_ptr()
+    }
+
+    /// Returns the arguments of the current directive, including the directive name.
+    pub fn args(&self) -> &[ngx_str_t] {
+        // SAFETY: args is an array of ngx_str_t, valid while the directive is processed.
+        unsafe { (*self.as_ref().args).as_slice() }
+    }
+
+    /// Returns the configuration pool.
+    ///
+    /// The allocations live as long as the configuration cycle.
+    pub fn pool(&self) -> Pool {
+        unsafe { Pool::from_ngx_pool(self.as_ref().pool) }
+    }
+
+    /// Returns the temporary pool, destroyed after the configuration is parsed.
+    pub fn temp_pool(&self) -> Pool {
+        unsafe { Pool::from_ngx_pool(self.as_ref().temp_pool) }
+    }
+
+    /// Returns the configuration log.
+    pub fn log(&self) -> *mut ngx_log_t {
+        self.as_ref().log
+    }
+
+    /// Parses the block of the current directive, calling `handler` for each directive in it.
+    ///
+    /// The handler is called with the arguments of the nested directive available in
+    /// [ConfRef::args], and the registered module directives are not recognized in the block.
+    /// The error message returned by the handler is logged as is, with the file name and line.
+    ///
+    /// The current directive must be declared with `NGX_CONF_BLOCK`.
+    pub fn parse_block<F>(&mut self, mut handler: F) -> Result<(), ConfParseError>
+    where
+        F: FnMut(&mut ConfRef<'_>) -> Result<(), &'static CStr>,
+    {
+        let cf = self.as_ptr();
+
+        // SAFETY: the parser state is restored after the block is parsed.
+        unsafe {
+            let saved = ptr::read(cf);
+
+            (*cf).handler = Some(conf_block_handler::<F>);
+            (*cf).handler_conf = ptr::from_mut(&mut handler).cast();
+
+            let rv = ngx_conf_parse(cf, ptr::null_mut());
+
+            ptr::write(cf, saved);
+
+            if rv != NGX_CONF_OK {
+                return Err(ConfParseError);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AsRef<ngx_conf_t> for ConfRef<'_> {
+    fn as_ref(&self) -> &ngx_conf_t {
+        // SAFETY: the pointer is valid for 'a.
+        unsafe { self.cf.as_ref() }
+    }
+}
+
+impl AsMut<ngx_conf_t> for ConfRef<'_> {
+    fn as_mut(&mut self) -> &mut ngx_conf_t {
+        // SAFETY: the pointer is valid for 'a.
+        unsafe { self.cf.as_mut() }
+    }
+}
+
+impl fmt::Debug for ConfRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfRef").field("cf", &self.cf).finish()
+    }
+}
+
+unsafe extern "C" fn conf_block_handler<F>(
+    cf: *mut ngx_conf_t,
+    _dummy: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char
+where
+    F: FnMut(&mut ConfRef<'_>) -> Result<(), &'static CStr>,
+{
+    let handler = &mut *conf.cast::<F>();
+    match handler(&mut ConfRef::from_ptr(cf)) {
+        Ok(()) => NGX_CONF_OK,
+        Err(msg) => msg.as_ptr().cast_mut(),
+    }
+}
+
+/// A typed post handler for the `ngx_command_t.post` field.
+///
+/// The standard setter slots, e.g. `ngx_conf_set_num_slot` or `ngx_conf_set_str_slot`, call the
+/// post handler with the parsed value once it is stored in the configuration. `T` must match the
+/// type of the field set by the slot: `ngx_int_t` for `num`, `usize` for `size`, `ngx_msec_t` for
+/// `msec`, `ngx_flag_t` for `flag` and `ngx_str_t` for `str`.
+///
+/// The handler can validate or normalize the value in place. The error message is appended to
+/// the directive name, e.g. `"example_mode" directive must be lowercase`.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::ffi::CStr;
+/// # use nginx_sys::{ngx_command_t, ngx_conf_set_str_slot, ngx_conf_t, ngx_str_t};
+/// # use nginx_sys::{NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET};
+/// # use ngx::core::ConfPost;
+/// # use ngx::ngx_string;
+/// struct LocConfig {
+///     prefix: ngx_str_t,
+/// }
+///
+/// fn check_prefix(_cf: &mut ngx_conf_t, value: &mut ngx_str_t) -> Result<(), &'static CStr> {
+///     if !value.as_bytes().starts_with(b"/") {
+///         return Err(c"must start with \"/\"");
+///     }
+///     Ok(())
+/// }
+///
+/// static PREFIX_POST: ConfPost<ngx_str_t> = ConfPost::new(check_prefix);
+///
+/// static mut COMMAND: ngx_command_t = ngx_command_t {
+///     name: ngx_string!("example_prefix"),
+///     type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as _,
+///     set: Some(ngx_conf_set_str_slot),
+///     conf: NGX_HTTP_LOC_CONF_OFFSET,
+///     offset: core::mem::offset_of!(LocConfig, prefix),
+///     post: PREFIX_POST.as_post(),
+/// };
+/// ```
+#[repr(C)]
+pub struct ConfPost<T> {
+    // must be the first field, as NGINX passes a pointer to `ngx_conf_post_t` to the handler
+    raw: ngx_conf_post_t,
+    handler: ConfPostHandler<T>,
+}
+
+/// A function validating the value of a directive for [ConfPost].
+pub type ConfPostHandler<T> = fn(&mut ngx_conf_t, &mut T) -> Result<(), &'static CStr>;
+
+impl<T> ConfPost<T> {
+    /// Creates a post handler calling `handler` with the stored value.
+    pub const fn new(handler: ConfPostHandler<T>) -> Self {
+        Self {
+            raw: ngx_conf_post_t {
+                post_handler: Some(conf_post_handler::<T>),
+            },
+            handler,
+        }
+    }
+
+    /// Returns a pointer suitable for the `ngx_command_t.post` field.
+    pub const fn as_post(&'static self) -> *mut c_void {
+        ptr::from_ref(self).cast_mut().cast()
+    }
+}
+
+unsafe extern "C" fn conf_post_handler<T>(
+    cf: *mut ngx_conf_t,
+    post: *mut c_void,
+    data: *mut c_void,
+) -> *mut c_char {
+    let post = &*post.cast::<ConfPost<T>>();
+    match (post.handler)(&mut *cf, &mut *data.cast::<T>()) {
+        Ok(()) => NGX_CONF_OK,
+        Err(msg) => msg.as_ptr().cast_mut(),
+    }
+}
+
+/// A post handler checking the bounds of a numeric directive value.
+///
+/// Applicable to the `ngx_conf_set_num_slot` directives. Wraps `ngx_conf_num_bounds_t` with the
+/// `ngx_conf_check_num_bounds` handler, which reports the values out of bounds as
+/// `"example_workers" directive value must be between 1 and 64`.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::ConfNumBounds;
+/// static WORKERS_BOUNDS: ConfNumBounds = ConfNumBounds::new(1, 64);
+/// static RETRIES_BOUNDS: ConfNumBounds = ConfNumBounds::at_least(0);
+/// ```
+#[repr(transparent)]
+pub struct ConfNumBounds(ngx_conf_num_bounds_t);
+
+impl ConfNumBounds {
+    /// Creates a post handler accepting the values between `low` and `high`, inclusive.
+    pub const fn new(low: ngx_int_t, high: ngx_int_t) -> Self {
+        assert!(low <= high && high != -1, "invalid bounds");
+        Self(ngx_conf_num_bounds_t {
+            post_handler: Some(ngx_conf_check_num_bounds),
+            low,
+            high,
+        })
+    }
+
+    /// Creates a post handler accepting the values greater or equal to `low`.
+    pub const fn at_least(low: ngx_int_t) -> Self {
+        Self(ngx_conf_num_bounds_t {
+            post_handler: Some(ngx_conf_check_num_bounds),
+            low,
+            // no upper bound
+            high: -1,
+        })
+    }
+
+    /// Returns a pointer suitable for the `ngx_command_t.post` field.
+    pub const fn as_post(&'static self) -> *mut c_void {
+        ptr::from_ref(self).cast_mut().cast()
+    }
+}
+
+/// A post handler warning about the use of a deprecated directive.
+///
+/// Wraps `ngx_conf_deprecated_t` with the `ngx_conf_deprecated` handler, which logs
+/// `the "old_name" directive is deprecated, use the "new_name" directive instead` and keeps the
+/// value.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::ConfDeprecated;
+/// static OLD_TIMEOUT: ConfDeprecated =
+///     ConfDeprecated::new(c"example_timeout", c"example_read_timeout");
+/// ```
+#[repr(transparent)]
+pub struct ConfDeprecated(ngx_conf_deprecated_t);
+
+// SAFETY: the names are immutable static strings.
+unsafe impl Sync for ConfDeprecated {}
+
+impl ConfDeprecated {
+    /// Creates a post handler for the directive `old_name` replaced with `new_name`.
+    pub const fn new(old_name: &'static CStr, new_name: &'static CStr) -> Self {
+        Self(ngx_conf_deprecated_t {
+            post_handler: Some(ngx_conf_deprecated),
+            old_name: old_name.as_ptr().cast_mut(),
+            new_name: new_name.as_ptr().cast_mut(),
+        })
+    }
+
+    /// Returns a pointer suitable for the `ngx_command_t.post` field.
+    pub const fn as_post(&'static self) -> *mut c_void {
+        ptr::from_ref(self).cast_mut().cast()
+    }
+}
+
+/// A table of the values accepted by `ngx_conf_set_enum_slot`.
+///
+/// The slot stores the value of the entry matching the directive argument, ignoring the case, so
+/// the configuration always sees one of the canonical values. The last entry must be a
+/// terminator with an empty name.
+///
+/// Example:
+/// ```rust,no_run
+/// # use nginx_sys::{ngx_conf_enum_t, ngx_str_t};
+/// # use ngx::core::ConfEnum;
+/// # use ngx::ngx_string;
+/// const MODE_OFF: usize = 0;
+/// const MODE_ON: usize = 1;
+/// const MODE_STRICT: usize = 2;
+///
+/// static MODES: ConfEnum<4> = ConfEnum::new([
+///     ngx_conf_enum_t { name: ngx_string!("off"), value: MODE_OFF },
+///     ngx_conf_enum_t { name: ngx_string!("on"), value: MODE_ON },
+///     ngx_conf_enum_t { name: ngx_string!("strict"), value: MODE_STRICT },
+///     ngx_conf_enum_t { name: ngx_str_t::empty(), value: 0 },
+/// ]);
+/// ```
+#[repr(transparent)]
+pub struct ConfEnum<const N: usize>([ngx_conf_enum_t; N]);
+
+// SAFETY: the names are immutable static strings.
+unsafe impl<const N: usize> Sync for ConfEnum<N> {}
+
+impl<const N: usize> ConfEnum<N> {
+    /// Creates a table from the entries, including the terminator.
+    pub const fn new(values: [ngx_conf_enum_t; N]) -> Self {
+        assert!(
+            N > 0 && values[N - 1].name.len == 0,
+            "missing enum terminator"
+        );
+        Self(values)
+    }
+
+    /// Returns the canonical name of the value.
+    pub fn name(&self, value: usize) -> Option<&[u8]> {
+        self.0[..N - 1]
+            .iter()
+            .find(|x| x.value == value)
+            .map(|x| x.name.as_bytes())
+    }
+
+    /// Returns a pointer suitable for the `ngx_command_t.post` field.
+    pub const fn as_post(&'static self) -> *mut c_void {
+        ptr::from_ref(self).cast_mut().cast()
+    }
+}
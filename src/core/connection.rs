@@ -0,0 +1,207 @@
+use core::fmt;
+use core::mem;
+use core::net::{SocketAddr, SocketAddrV6};
+use core::ptr::{self, NonNull};
+
+use crate::core::{NgxStr, Pool, ProxyProtocol, Status};
+use crate::ffi::{
+    ngx_connection_local_sockaddr, ngx_connection_t, ngx_log_t, ngx_socket_t, sockaddr,
+    sockaddr_in, sockaddr_in6, socklen_t, AF_INET, AF_INET6,
+};
+
+/// Converts a socket address to [SocketAddr].
+///
+/// Returns `None` for the address families other than `AF_INET` and `AF_INET6`, e.g. for the
+/// UNIX-domain sockets.
+///
+/// # Safety
+///
+/// `sa` is a valid pointer to an address of `socklen` bytes.
+pub(crate) unsafe fn socket_addr(sa: *const sockaddr, socklen: socklen_t) -> Option<SocketAddr> {
+    if sa.is_null() || (socklen as usize) < mem::size_of::<sockaddr>() {
+        return None;
+    }
+
+    match (*sa).sa_family as u32 {
+        AF_INET if socklen as usize >= mem::size_of::<sockaddr_in>() => {
+            let sin = &*sa.cast::<sockaddr_in>();
+            // in_addr is a 4 byte structure in the network byte order.
+            let ip = ptr::addr_of!(sin.sin_addr)
+                .cast::<[u8; 4]>()
+                .read_unaligned();
+            Some(SocketAddr::from((ip, u16::from_be(sin.sin_port))))
+        }
+        AF_INET6 if socklen as usize >= mem::size_of::<sockaddr_in6>() => {
+            let sin6 = &*sa.cast::<sockaddr_in6>();
+            // in6_addr is a 16 byte structure in the network byte order.
+            let ip = ptr::addr_of!(sin6.sin6_addr)
+                .cast::<[u8; 16]>()
+                .read_unaligned();
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                ip.into(),
+                u16::from_be(sin6.sin6_port),
+                u32::from_be(sin6.sin6_flowinfo),
+                sin6.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+/// A client or upstream connection, [ngx_connection_t].
+///
+/// The same structure is used by the HTTP, stream and mail modules, so the helpers are available
+/// wherever the module has access to the connection, e.g. with [Request::connection] or
+/// `ngx_stream_session_t.connection`.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Connection;
+/// # use ngx::http::Request;
+/// # fn handler(request: &Request) {
+/// let c = unsafe { Connection::from_ptr(request.connection()) };
+///
+/// if let Some(addr) = c.remote_addr() {
+///     let trusted = addr.ip().is_loopback() && !c.is_ssl();
+/// }
+/// # }
+/// ```
+///
+/// [Request::connection]: crate::http::Request::connection
+#[repr(transparent)]
+pub struct Connection(ngx_connection_t);
+
+impl Connection {
+    /// Creates a connection reference from a pointer to [ngx_connection_t].
+    ///
+    /// # Safety
+    ///
+    /// `c` is a valid pointer to a connection.
+    pub unsafe fn from_ptr<'a>(c: *const ngx_connection_t) -> &'a Self {
+        &*c.cast()
+    }
+
+    /// Creates a mutable connection reference from a pointer to [ngx_connection_t].
+    ///
+    /// # Safety
+    ///
+    /// `c` is a valid pointer to a connection.
+    pub unsafe fn from_ptr_mut<'a>(c: *mut ngx_connection_t) -> &'a mut Self {
+        &mut *c.cast()
+    }
+
+    /// Returns a raw pointer to the underlying [ngx_connection_t].
+    pub fn as_ptr(&self) -> *const ngx_connection_t {
+        &self.0
+    }
+
+    /// Returns a mutable raw pointer to the underlying [ngx_connection_t].
+    pub fn as_mut_ptr(&mut self) -> *mut ngx_connection_t {
+        &mut self.0
+    }
+
+    /// Returns the socket descriptor.
+    pub fn fd(&self) -> ngx_socket_t {
+        self.0.fd
+    }
+
+    /// Returns the connection number, unique within the running NGINX instance.
+    pub fn number(&self) -> usize {
+        self.0.number as _
+    }
+
+    /// Returns the number of requests processed on the connection.
+    pub fn requests(&self) -> usize {
+        self.0.requests as _
+    }
+
+    /// Returns the log of the connection.
+    pub fn log(&self) -> NonNull<ngx_log_t> {
+        NonNull::new(self.0.log).expect("connection log")
+    }
+
+    /// Returns the pool of the connection.
+    ///
+    /// The pool lives until the connection is closed.
+    pub fn pool(&self) -> Pool {
+        // SAFETY: the pool is created with the connection.
+        unsafe { Pool::from_ngx_pool(self.0.pool) }
+    }
+
+    /// Returns the address of the peer.
+    ///
+    /// Returns `None` for the UNIX-domain sockets.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        // SAFETY: the address is set when the connection is accepted or connected.
+        unsafe { socket_addr(self.0.sockaddr, self.0.socklen) }
+    }
+
+    /// Returns the text representation of the peer address, as in the `$remote_addr` variable.
+    pub fn remote_addr_text(&self) -> &NgxStr {
+        // SAFETY: the string is allocated from the connection pool.
+        unsafe { NgxStr::from_ngx_str(self.0.addr_text) }
+    }
+
+    /// Returns the local address of the connection.
+    ///
+    /// For the sockets listening on a wildcard address, the address is retrieved with
+    /// `getsockname()` on the first call. Returns `None` for the UNIX-domain sockets, or if the
+    /// address cannot be retrieved.
+    pub fn local_addr(&mut self) -> Option<SocketAddr> {
+        // SAFETY: the connection is valid, and the function only updates the local address.
+        let rc = unsafe { ngx_connection_local_sockaddr(&mut self.0, ptr::null_mut(), 0) };
+        if rc != Status::NGX_OK.into() {
+            return None;
+        }
+
+        // SAFETY: the local address is set by ngx_connection_local_sockaddr.
+        unsafe { socket_addr(self.0.local_sockaddr, self.0.local_socklen) }
+    }
+
+    /// Returns `true` if the connection uses TLS.
+    pub fn is_ssl(&self) -> bool {
+        #[cfg(any(ngx_feature = "ssl", ngx_feature = "compat"))]
+        {
+            !self.0.ssl.is_null()
+        }
+        #[cfg(not(any(ngx_feature = "ssl", ngx_feature = "compat")))]
+        {
+            false
+        }
+    }
+
+    /// Returns `true` if the TLS handshake on the connection is complete.
+    #[cfg(ngx_feature = "ssl")]
+    pub fn ssl_handshaked(&self) -> bool {
+        // SAFETY: the TLS state lives as long as the connection.
+        unsafe { self.0.ssl.as_ref() }.is_some_and(|ssl| ssl.handshaked() != 0)
+    }
+
+    /// Returns the information received with the PROXY protocol header, if any.
+    pub fn proxy_protocol(&self) -> Option<&ProxyProtocol> {
+        // SAFETY: the connection is valid.
+        unsafe { ProxyProtocol::from_connection(&self.0) }
+    }
+}
+
+impl AsRef<ngx_connection_t> for Connection {
+    fn as_ref(&self) -> &ngx_connection_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_connection_t> for Connection {
+    fn as_mut(&mut self) -> &mut ngx_connection_t {
+        &mut self.0
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("number", &self.number())
+            .field("fd", &self.fd())
+            .field("remote_addr", &self.remote_addr_text())
+            .finish()
+    }
+}
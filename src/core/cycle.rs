@@ -0,0 +1,172 @@
+use core::ffi::CStr;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::collections::list::NgxList;
+use crate::core::NgxStr;
+use crate::ffi::{ngx_cycle_t, ngx_log_t, ngx_module_t, ngx_path_t, ngx_shm_zone_t};
+
+/// A reference to a configuration cycle, [ngx_cycle_t].
+///
+/// A cycle holds the state created from a configuration: the module configurations, the shared
+/// memory zones, the paths and the open files. A new cycle is created on each configuration
+/// reload, and the previous one is destroyed once the old worker processes exit.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::CycleRef;
+/// let cycle = CycleRef::current().expect("cycle");
+///
+/// for zone in cycle.shared_zones() {
+///     let name = ngx::core::NgxStr::from_bytes(zone.shm.name.as_bytes());
+///     ngx::ngx_log_debug!(cycle.log().as_ptr(), "zone {name}, size {}", zone.shm.size);
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct CycleRef<'a> {
+    cycle: NonNull<ngx_cycle_t>,
+    _lifetime: PhantomData<&'a ngx_cycle_t>,
+}
+
+impl<'a> CycleRef<'a> {
+    /// Returns the current cycle, the global `ngx_cycle`.
+    ///
+    /// Returns `None` before the first cycle is created. In a worker process the current cycle
+    /// never changes; in the master or the single process it is replaced on configuration reload,
+    /// and the returned reference must not be kept across the event loop iterations.
+    pub fn current() -> Option<CycleRef<'static>> {
+        // SAFETY: the global cycle pointer is either null or points to a valid cycle.
+        let cycle = NonNull::new(unsafe { nginx_sys::ngx_cycle })?;
+        Some(CycleRef {
+            cycle,
+            _lifetime: PhantomData,
+        })
+    }
+
+    /// Creates a reference from a pointer to [ngx_cycle_t].
+    ///
+    /// # Safety
+    ///
+    /// `cycle` is a valid pointer to a cycle, alive for `'a`.
+    pub unsafe fn from_ptr(cycle: *mut ngx_cycle_t) -> Self {
+        debug_assert!(!cycle.is_null());
+        Self {
+            cycle: NonNull::new_unchecked(cycle),
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns a raw pointer to the underlying [ngx_cycle_t].
+    pub fn as_ptr(&self) -> *mut ngx_cycle_t {
+        self.cycle.as_ptr()
+    }
+
+    /// Returns the error log of the cycle.
+    pub fn log(&self) -> NonNull<ngx_log_t> {
+        NonNull::new(self.raw().log).expect("cycle log")
+    }
+
+    /// Returns the installation prefix, as set with the `-p` command line option.
+    pub fn prefix(&self) -> &'a NgxStr {
+        // SAFETY: the string is allocated from the cycle pool.
+        unsafe { NgxStr::from_ngx_str(self.raw().prefix) }
+    }
+
+    /// Returns the configuration prefix, the directory of the main configuration file.
+    pub fn conf_prefix(&self) -> &'a NgxStr {
+        // SAFETY: the string is allocated from the cycle pool.
+        unsafe { NgxStr::from_ngx_str(self.raw().conf_prefix) }
+    }
+
+    /// Returns the full path of the main configuration file.
+    pub fn conf_file(&self) -> &'a NgxStr {
+        // SAFETY: the string is allocated from the cycle pool.
+        unsafe { NgxStr::from_ngx_str(self.raw().conf_file) }
+    }
+
+    /// Returns the host name of the machine.
+    pub fn hostname(&self) -> &'a NgxStr {
+        // SAFETY: the string is allocated from the cycle pool.
+        unsafe { NgxStr::from_ngx_str(self.raw().hostname) }
+    }
+
+    /// Returns the configuration of a core module, such as `ngx_core_module` or
+    /// `ngx_events_module`.
+    ///
+    /// Use [HttpModuleConfExt](crate::http::HttpModuleConfExt) for the HTTP modules.
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    pub unsafe fn core_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        let conf_ctx = self.raw().conf_ctx;
+        if conf_ctx.is_null() {
+            return None;
+        }
+        NonNull::new((*conf_ctx.add(module.index)).cast())
+    }
+
+    /// Returns an iterator over the modules of the cycle, including the dynamic modules.
+    pub fn modules(&self) -> impl Iterator<Item = &'a ngx_module_t> {
+        // SAFETY: the modules array is allocated when the cycle is created.
+        let modules: &'a [*mut ngx_module_t] = unsafe { self.raw().modules() };
+        // SAFETY: the modules are static or loaded for the lifetime of the cycle.
+        modules.iter().filter_map(|x| unsafe { x.as_ref() })
+    }
+
+    /// Finds a module by name, e.g. `c"ngx_http_gzip_filter_module"`.
+    pub fn module(&self, name: &CStr) -> Option<&'a ngx_module_t> {
+        self.modules().find(|module| {
+            // SAFETY: the module names are static C strings.
+            !module.name.is_null() && unsafe { CStr::from_ptr(module.name) } == name
+        })
+    }
+
+    /// Returns an iterator over the shared memory zones of the cycle.
+    ///
+    /// The zones are added during the configuration parsing and are initialized after the
+    /// configuration is complete. See [SharedZone](crate::shm::SharedZone) for the typed access to
+    /// the zones created by this crate.
+    pub fn shared_zones(&self) -> impl Iterator<Item = &'a ngx_shm_zone_t> {
+        // SAFETY: the list is initialized with ngx_shm_zone_t elements when the cycle is created.
+        let list: &'a NgxList<ngx_shm_zone_t> =
+            unsafe { NgxList::from_ptr(&self.raw().shared_memory) };
+        list.iter()
+    }
+
+    /// Finds a shared memory zone by name.
+    pub fn shared_zone(&self, name: &[u8]) -> Option<&'a ngx_shm_zone_t> {
+        self.shared_zones()
+            .find(|zone| zone.shm.name.as_bytes() == name)
+    }
+
+    /// Returns an iterator over the paths of the cycle, such as the temporary and cache
+    /// directories.
+    pub fn paths(&self) -> impl Iterator<Item = &'a ngx_path_t> {
+        // SAFETY: the array is initialized with ngx_path_t pointers when the cycle is created.
+        let paths: &'a [*mut ngx_path_t] = unsafe { self.raw().paths.as_slice() };
+        // SAFETY: the paths are allocated from the cycle pool.
+        paths.iter().filter_map(|x| unsafe { x.as_ref() })
+    }
+
+    fn raw(&self) -> &'a ngx_cycle_t {
+        // SAFETY: the pointer is valid for 'a.
+        unsafe { self.cycle.as_ref() }
+    }
+}
+
+impl AsRef<ngx_cycle_t> for CycleRef<'_> {
+    fn as_ref(&self) -> &ngx_cycle_t {
+        self.raw()
+    }
+}
+
+impl fmt::Debug for CycleRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CycleRef")
+            .field("cycle", &self.cycle)
+            .field("conf_file", &self.conf_file())
+            .finish()
+    }
+}
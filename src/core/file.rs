@@ -0,0 +1,510 @@
+use core::error;
+use core::fmt;
+use core::ops::Range;
+use core::ptr::{self, NonNull};
+
+use crate::allocator::AllocError;
+use crate::core::{ChainBuilder, NgxStr, Pool};
+use crate::ffi::*;
+
+/// An error returned by the [File] operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The file or one of the path components does not exist.
+    NotFound,
+    /// Access to the file is denied, e.g. by the permissions or the `disable_symlinks` directive.
+    Forbidden,
+    /// An I/O error with the system error code.
+    Io(ngx_err_t),
+}
+
+impl FileError {
+    fn from_errno(err: ngx_err_t) -> Self {
+        match err as u32 {
+            ENOENT | ENOTDIR | ENAMETOOLONG => FileError::NotFound,
+            EACCES | ELOOP => FileError::Forbidden,
+            _ => FileError::Io(err),
+        }
+    }
+}
+
+impl error::Error for FileError {}
+
+impl From<AllocError> for FileError {
+    fn from(_: AllocError) -> Self {
+        FileError::Alloc
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Alloc => f.write_str("file allocation failed"),
+            FileError::NotFound => f.write_str("file not found"),
+            FileError::Forbidden => f.write_str("file access forbidden"),
+            FileError::Io(err) => write!(f, "file i/o error ({err})"),
+        }
+    }
+}
+
+/// Options for opening a [File], with an optional open file cache.
+///
+/// The settings correspond to the `open_file_cache*`, `directio` and `read_ahead` directives of
+/// the HTTP core module, see [Request::open_file](crate::http::Request::open_file).
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::{FileError, OpenOptions, Pool};
+/// # fn open(mut pool: Pool) -> Result<(), FileError> {
+/// let file = OpenOptions::new().open(&mut pool, "/usr/share/nginx/html/index.html")?;
+/// let size = file.metadata().len();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OpenOptions {
+    cache: *mut ngx_open_file_cache_t,
+    of: ngx_open_file_info_t,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    /// Creates the options for opening a file without a cache.
+    pub fn new() -> Self {
+        // SAFETY: ngx_open_file_info_t is a plain C structure, all zeroes is a valid value.
+        let mut of: ngx_open_file_info_t = unsafe { core::mem::zeroed() };
+        // NGX_OPEN_FILE_DIRECTIO_OFF
+        of.directio = off_t::MAX;
+
+        Self {
+            cache: ptr::null_mut(),
+            of,
+        }
+    }
+
+    /// Sets the open file cache, e.g. `ngx_http_core_loc_conf_t.open_file_cache`.
+    ///
+    /// The cache must be null or valid for the lifetime of the options.
+    pub fn cache(mut self, cache: *mut ngx_open_file_cache_t) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Sets the time after which the cached information should be validated.
+    pub fn valid(mut self, secs: time_t) -> Self {
+        self.of.valid = secs;
+        self
+    }
+
+    /// Sets the minimum number of uses for the file to remain in the cache.
+    pub fn min_uses(mut self, min_uses: ngx_uint_t) -> Self {
+        self.of.min_uses = min_uses;
+        self
+    }
+
+    /// Enables caching of the file lookup errors.
+    pub fn errors(mut self, errors: bool) -> Self {
+        self.of.set_errors(errors.into());
+        self
+    }
+
+    /// Enables caching of the events for the open file descriptors.
+    pub fn events(mut self, events: bool) -> Self {
+        self.of.set_events(events.into());
+        self
+    }
+
+    /// Sets the minimum file size to use direct I/O for.
+    pub fn directio(mut self, size: off_t) -> Self {
+        self.of.directio = size;
+        self
+    }
+
+    /// Sets the read-ahead size hint.
+    pub fn read_ahead(mut self, size: usize) -> Self {
+        self.of.read_ahead = size;
+        self
+    }
+
+    /// Opens a file for reading.
+    ///
+    /// The file descriptor is closed or released to the cache when `pool` is destroyed.
+    pub fn open(&self, pool: &mut Pool, path: impl AsRef<[u8]>) -> Result<File, FileError> {
+        self.open_with(pool, path.as_ref(), |_, _| Ok(()))
+    }
+
+    /// Returns the file metadata without opening the file.
+    pub fn stat(&self, pool: &mut Pool, path: impl AsRef<[u8]>) -> Result<Metadata, FileError> {
+        let mut of = self.of;
+        of.set_test_only(1);
+
+        let mut name = path_to_cstr(pool, path.as_ref())?;
+        let rc = unsafe { ngx_open_cached_file(self.cache, &mut name, &mut of, pool.as_ptr()) };
+        if rc != NGX_OK as ngx_int_t {
+            return Err(FileError::from_errno(of.err));
+        }
+
+        Ok(Metadata::from_info(&of))
+    }
+
+    /// Opens a file, calling `prepare` with the NUL-terminated path and the file info first.
+    pub(crate) fn open_with(
+        &self,
+        pool: &mut Pool,
+        path: &[u8],
+        prepare: impl FnOnce(&mut ngx_str_t, &mut ngx_open_file_info_t) -> Result<(), FileError>,
+    ) -> Result<File, FileError> {
+        let mut of = self.of;
+        of.fd = NGX_INVALID_FILE;
+
+        let mut name = path_to_cstr(pool, path)?;
+        prepare(&mut name, &mut of)?;
+
+        let rc = unsafe { ngx_open_cached_file(self.cache, &mut name, &mut of, pool.as_ptr()) };
+        if rc != NGX_OK as ngx_int_t {
+            return Err(FileError::from_errno(of.err));
+        }
+
+        let file = NonNull::new(pool.calloc_type::<ngx_file_t>()).ok_or(FileError::Alloc)?;
+        // SAFETY: the file structure was just allocated and zeroed.
+        unsafe {
+            let f = file.as_ptr();
+            (*f).fd = of.fd;
+            (*f).name = name;
+            (*f).log = (*pool.as_ptr()).log;
+            (*f).set_directio(of.is_directio());
+        }
+
+        Ok(File {
+            file,
+            pool: pool.clone(),
+            metadata: Metadata::from_info(&of),
+        })
+    }
+}
+
+/// Copies the path to the pool, with a terminating NUL character not included in the length.
+fn path_to_cstr(pool: &mut Pool, path: &[u8]) -> Result<ngx_str_t, AllocError> {
+    let data = pool.alloc_unaligned(path.len() + 1).cast::<u8>();
+    if data.is_null() {
+        return Err(AllocError);
+    }
+
+    unsafe {
+        ptr::copy_nonoverlapping(path.as_ptr(), data, path.len());
+        *data.add(path.len()) = 0;
+    }
+
+    Ok(ngx_str_t {
+        len: path.len(),
+        data,
+    })
+}
+
+/// Metadata of a file, as returned by the open file cache.
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    uniq: ngx_file_uniq_t,
+    mtime: time_t,
+    size: off_t,
+    is_dir: bool,
+    is_file: bool,
+    is_link: bool,
+    is_exec: bool,
+}
+
+impl Metadata {
+    fn from_info(of: &ngx_open_file_info_t) -> Self {
+        Self {
+            uniq: of.uniq,
+            mtime: of.mtime,
+            size: of.size,
+            is_dir: of.is_dir() != 0,
+            is_file: of.is_file() != 0,
+            is_link: of.is_link() != 0,
+            is_exec: of.is_exec() != 0,
+        }
+    }
+
+    /// Returns the size of the file.
+    pub fn len(&self) -> off_t {
+        self.size
+    }
+
+    /// Returns `true` if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the last modification time.
+    pub fn modified(&self) -> time_t {
+        self.mtime
+    }
+
+    /// Returns the unique file identifier, i.e. the inode number.
+    pub fn uniq(&self) -> ngx_file_uniq_t {
+        self.uniq
+    }
+
+    /// Returns `true` if the path is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns `true` if the path is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.is_file
+    }
+
+    /// Returns `true` if the path is a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.is_link
+    }
+
+    /// Returns `true` if the file is executable.
+    pub fn is_exec(&self) -> bool {
+        self.is_exec
+    }
+}
+
+/// A file opened with [OpenOptions] and owned by a memory pool.
+///
+/// The file is valid until the pool is destroyed. The contents can be sent in a response by adding
+/// file-backed buffers to a chain with [File::push_to]. The output filters use `sendfile` or read
+/// the buffers, with AIO if enabled by the `aio` directive, as for the static files.
+#[derive(Debug)]
+pub struct File {
+    file: NonNull<ngx_file_t>,
+    pool: Pool,
+    metadata: Metadata,
+}
+
+impl File {
+    /// Returns the file metadata obtained when opening the file.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the file name.
+    pub fn name(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str((*self.file.as_ptr()).name) }
+    }
+
+    /// Returns the file descriptor.
+    pub fn fd(&self) -> ngx_fd_t {
+        unsafe { (*self.file.as_ptr()).fd }
+    }
+
+    /// Returns `true` if the file was opened for direct I/O.
+    pub fn is_directio(&self) -> bool {
+        unsafe { (*self.file.as_ptr()).directio() != 0 }
+    }
+
+    /// Returns a raw pointer to the underlying `ngx_file_t`.
+    pub fn as_ptr(&self) -> *mut ngx_file_t {
+        self.file.as_ptr()
+    }
+
+    /// Appends a buffer referencing the `range` of the file to the chain.
+    ///
+    /// An empty range is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chain is not allocated from the same pool as the file.
+    pub fn push_to(&self, chain: &mut ChainBuilder, range: Range<off_t>) -> Result<(), AllocError> {
+        assert_eq!(
+            chain.pool().as_ptr(),
+            self.pool.as_ptr(),
+            "file and chain pools differ"
+        );
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        // SAFETY: the file is valid for the lifetime of the pool, which owns the chain.
+        unsafe { chain.push_file(self.as_ptr(), range) }?;
+        Ok(())
+    }
+
+    /// Reads from the file at `offset`, blocking the worker process.
+    ///
+    /// Returns the number of bytes read, or zero at the end of the file.
+    pub fn read_at(&self, buf: &mut [u8], offset: off_t) -> Result<usize, FileError> {
+        let n = unsafe { ngx_read_file(self.as_ptr(), buf.as_mut_ptr(), buf.len(), offset) };
+        if n < 0 {
+            // ngx_read_file already logged the error
+            return Err(FileError::Io(ngx_errno()));
+        }
+        Ok(n as usize)
+    }
+
+    /// Reads up to `size` bytes at `offset` with file AIO.
+    ///
+    /// The data is read to a temporary buffer allocated from the file pool. Falls back to a
+    /// blocking read if file AIO is not available at runtime.
+    ///
+    /// Only one read per file can be in progress, and the pool must not be destroyed before the
+    /// read completes.
+    #[cfg(all(feature = "async", ngx_feature = "have_file_aio"))]
+    pub fn read_aio(&self, offset: off_t, size: usize) -> aio::AioRead {
+        aio::AioRead::new(self.file, self.pool.clone(), offset, size)
+    }
+}
+
+#[cfg(all(feature = "async", ngx_feature = "have_file_aio"))]
+mod aio {
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::ptr::{self, NonNull};
+    use core::task::{self, Poll};
+
+    #[cfg(all(not(feature = "std"), feature = "alloc"))]
+    use alloc::boxed::Box;
+    #[cfg(feature = "std")]
+    use std::boxed::Box;
+
+    use super::FileError;
+    use crate::core::{Buffer, Pool, TemporaryBuffer};
+    use crate::ffi::{
+        ngx_buf_t, ngx_errno, ngx_event_aio_t, ngx_event_t, ngx_file_aio_read, ngx_file_t, off_t,
+        NGX_AGAIN,
+    };
+
+    /// Future returned by [File::read_aio](super::File::read_aio).
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct AioRead {
+        file: NonNull<ngx_file_t>,
+        pool: Pool,
+        offset: off_t,
+        size: usize,
+        buf: *mut ngx_buf_t,
+        /// Stable location of the waker, referenced by `ngx_event_aio_t.data`.
+        waker: Box<Option<task::Waker>>,
+    }
+
+    impl AioRead {
+        pub(super) fn new(
+            file: NonNull<ngx_file_t>,
+            pool: Pool,
+            offset: off_t,
+            size: usize,
+        ) -> Self {
+            Self {
+                file,
+                pool,
+                offset,
+                size,
+                buf: ptr::null_mut(),
+                waker: Box::new(None),
+            }
+        }
+
+        fn waker_ptr(&mut self) -> *mut Option<task::Waker> {
+            &mut *self.waker
+        }
+    }
+
+    impl Future for AioRead {
+        type Output = Result<TemporaryBuffer, FileError>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+            if self.buf.is_null() {
+                let size = self.size;
+                let Some(mut buf) = self.pool.create_buffer(size) else {
+                    return Poll::Ready(Err(FileError::Alloc));
+                };
+                self.buf = buf.as_ngx_buf_mut();
+            }
+
+            let file = self.file.as_ptr();
+            let buf = self.buf;
+
+            // SAFETY: the file is valid for the lifetime of the pool, and the aio structure is
+            // either NULL or allocated by ngx_file_aio_read.
+            unsafe {
+                let aio = (*file).aio;
+                if !aio.is_null()
+                    && (*aio).data == self.waker_ptr().cast()
+                    && (*aio).event.active() != 0
+                    && (*aio).event.complete() == 0
+                {
+                    // the read is still in progress; resubmitting it would fail with an alert
+                    match self.waker.as_mut() {
+                        Some(waker) => waker.clone_from(cx.waker()),
+                        None => *self.waker = Some(cx.waker().clone()),
+                    }
+                    return Poll::Pending;
+                }
+            }
+
+            // submits the read, or claims the result of the completed one
+            let n = unsafe {
+                ngx_file_aio_read(file, (*buf).pos, self.size, self.offset, self.pool.as_ptr())
+            };
+
+            if n == NGX_AGAIN as isize {
+                *self.waker = Some(cx.waker().clone());
+                let waker = self.waker_ptr();
+                // SAFETY: the aio structure is allocated by ngx_file_aio_read.
+                unsafe {
+                    let aio = (*file).aio;
+                    (*aio).data = waker.cast();
+                    (*aio).handler = Some(aio_event_handler);
+                }
+                return Poll::Pending;
+            }
+
+            if n < 0 {
+                return Poll::Ready(Err(FileError::Io(ngx_errno())));
+            }
+
+            unsafe { (*buf).last = (*buf).pos.add(n as usize) };
+            Poll::Ready(Ok(TemporaryBuffer::from_ngx_buf(buf)))
+        }
+    }
+
+    impl Drop for AioRead {
+        fn drop(&mut self) {
+            let waker = self.waker_ptr();
+
+            // SAFETY: the file is valid for the lifetime of the pool.
+            unsafe {
+                let aio = (*self.file.as_ptr()).aio;
+                if aio.is_null() || (*aio).data != waker.cast() {
+                    return;
+                }
+
+                // Discard the pending or the unclaimed result, so the next read starts anew.
+                (*aio).data = ptr::null_mut();
+                (*aio).event.set_complete(0);
+            }
+        }
+    }
+
+    unsafe extern "C" fn aio_event_handler(ev: *mut ngx_event_t) {
+        let aio: *mut ngx_event_aio_t = (*ev).data.cast();
+        let waker = (*aio).data.cast::<Option<task::Waker>>();
+
+        if waker.is_null() {
+            // the future was dropped while the read was in progress
+            (*ev).set_complete(0);
+            return;
+        }
+
+        if let Some(waker) = (*waker).take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(all(feature = "async", ngx_feature = "have_file_aio"))]
+pub use aio::AioRead;
@@ -0,0 +1,23 @@
+/// Returns `true` if called from the main thread of the process.
+///
+/// The main thread runs the event loop and all the module handlers. The other threads are started
+/// by the [thread pools] and by third-party libraries, and must not access the state of the event
+/// loop.
+///
+/// [thread pools]: https://nginx.org/en/docs/ngx_core_module.html#thread_pool
+pub fn is_main_thread() -> bool {
+    // SAFETY: the function has no preconditions
+    unsafe { nginx_sys::ngx_rs_is_main_thread() != 0 }
+}
+
+/// Panics if called from a thread other than the main thread of the process.
+///
+/// Used by the types that are shared as `static` items, but only allow access to their state from
+/// the main thread.
+#[track_caller]
+pub(crate) fn assert_main_thread(what: &str) {
+    assert!(
+        is_main_thread(),
+        "{what} accessed outside of the main thread"
+    );
+}
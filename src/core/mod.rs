@@ -1,11 +1,43 @@
+mod arena;
+mod array_string;
 mod buffer;
+mod callback;
+mod conf;
+mod connection;
+mod cycle;
+mod file;
+mod main_thread;
+mod module;
+pub mod parse;
 mod pool;
+#[cfg(feature = "alloc")]
+mod pool_box;
+pub mod proxy_protocol;
+#[cfg(feature = "std")]
+mod reader;
 pub mod slab;
 mod status;
 mod string;
+#[cfg(feature = "std")]
+pub mod watchdog;
 
+pub use arena::*;
+pub use array_string::ArrayString;
 pub use buffer::*;
+pub use callback::*;
+pub use conf::*;
+pub use connection::*;
+pub use cycle::*;
+pub use file::*;
+pub(crate) use main_thread::assert_main_thread;
+pub use main_thread::is_main_thread;
+pub use module::*;
 pub use pool::*;
+#[cfg(feature = "alloc")]
+pub use pool_box::*;
+pub use proxy_protocol::ProxyProtocol;
+#[cfg(feature = "std")]
+pub use reader::*;
 pub use slab::SlabPool;
 pub use status::*;
 pub use string::*;
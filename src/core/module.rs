@@ -0,0 +1,192 @@
+use core::any::type_name;
+use core::error;
+use core::fmt;
+use core::ptr;
+
+use crate::core::Status;
+use crate::ffi::{
+    nginx_version, ngx_core_module, ngx_cycle_t, ngx_int_t, ngx_log_t, ngx_module_t, ngx_uint_t,
+    NGX_LOG_EMERG, NGX_RS_MODULE_SIGNATURE,
+};
+use crate::ngx_log_error;
+
+/// An error returned by [check_compatibility].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompatibilityError {
+    /// The NGINX binary version differs from the version the module is built against.
+    Version {
+        /// The version of the bindings, as in `nginx_version`.
+        expected: ngx_uint_t,
+        /// The version of the running binary.
+        found: ngx_uint_t,
+    },
+    /// The NGINX binary is built with options changing the structure layouts.
+    Signature,
+}
+
+impl error::Error for CompatibilityError {}
+
+impl fmt::Display for CompatibilityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Version(ngx_uint_t);
+
+        impl fmt::Display for Version {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let v = self.0;
+                write!(f, "{}.{}.{}", v / 1_000_000, v / 1000 % 1000, v % 1000)
+            }
+        }
+
+        match self {
+            CompatibilityError::Version { expected, found } => write!(
+                f,
+                "nginx version {} does not match the module build version {}",
+                Version(*found),
+                Version(*expected)
+            ),
+            CompatibilityError::Signature => {
+                f.write_str("nginx binary signature does not match the module build")
+            }
+        }
+    }
+}
+
+/// Checks that the running NGINX binary matches the version and the build options the module is
+/// compiled against.
+///
+/// NGINX performs the same check for the modules loaded with `load_module`, but a module linked
+/// in a different way, or a module loading other code compiled against these bindings, may use
+/// this function to fail with an error instead of crashing on a structure layout mismatch.
+///
+/// The check is performed automatically before [ProcessHooks::init_module].
+pub fn check_compatibility() -> Result<(), CompatibilityError> {
+    // SAFETY: the core module is defined in the binary and is not modified after the startup.
+    let core = unsafe { &*ptr::addr_of!(ngx_core_module) };
+
+    let expected = nginx_version as ngx_uint_t;
+    if core.version != expected {
+        return Err(CompatibilityError::Version {
+            expected,
+            found: core.version,
+        });
+    }
+
+    if core.signature() != Some(NGX_RS_MODULE_SIGNATURE) {
+        return Err(CompatibilityError::Signature);
+    }
+
+    Ok(())
+}
+
+/// The process lifecycle hooks of a module.
+///
+/// The hooks are called for modules of any type and are the place to start and stop the
+/// per-process state, such as timers, background tasks or connections to external services.
+/// Use [with_process_hooks] to install the hooks into the [ngx_module_t].
+///
+/// A panic in a hook is handled with [crate::panic::catch] and reported as `NGX_ERROR`.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::time::Duration;
+/// # use ngx::core::{watchdog, with_process_hooks, ProcessHooks, Status};
+/// # use ngx::ffi::{ngx_cycle_t, ngx_module_t, NGX_HTTP_MODULE};
+/// struct Module;
+///
+/// impl ProcessHooks for Module {
+///     fn init_process(_cycle: &mut ngx_cycle_t) -> Status {
+///         watchdog::start(Duration::from_millis(100), ngx::log::ngx_cycle_log());
+///         Status::NGX_OK
+///     }
+/// }
+///
+/// #[used]
+/// #[allow(non_upper_case_globals)]
+/// #[no_mangle]
+/// pub static mut ngx_http_example_module: ngx_module_t =
+///     with_process_hooks::<Module>(ngx_module_t {
+///         type_: NGX_HTTP_MODULE as _,
+///         ..ngx_module_t::default()
+///     });
+/// ```
+pub trait ProcessHooks {
+    /// Called in the master process before the configuration is read.
+    ///
+    /// Note that NGINX does not currently call this hook.
+    fn init_master(_log: &mut ngx_log_t) -> Status {
+        Status::NGX_OK
+    }
+
+    /// Called after the configuration is read, in the master process or in the single process.
+    ///
+    /// The hook is called again on each configuration reload, with the new cycle.
+    fn init_module(_cycle: &mut ngx_cycle_t) -> Status {
+        Status::NGX_OK
+    }
+
+    /// Called on the start of each worker process, or of the single process.
+    ///
+    /// An error stops the process.
+    fn init_process(_cycle: &mut ngx_cycle_t) -> Status {
+        Status::NGX_OK
+    }
+
+    /// Called on the graceful or fast exit of a worker process, or of the single process.
+    fn exit_process(_cycle: &mut ngx_cycle_t) {}
+
+    /// Called on the exit of the master process.
+    fn exit_master(_cycle: &mut ngx_cycle_t) {}
+}
+
+/// Installs the [ProcessHooks] of `M` into `module`.
+///
+/// The existing hooks of the module are replaced.
+pub const fn with_process_hooks<M: ProcessHooks>(mut module: ngx_module_t) -> ngx_module_t {
+    module.init_master = Some(init_master::<M>);
+    module.init_module = Some(init_module::<M>);
+    module.init_process = Some(init_process::<M>);
+    module.exit_process = Some(exit_process::<M>);
+    module.exit_master = Some(exit_master::<M>);
+    module
+}
+
+unsafe extern "C" fn init_master<M: ProcessHooks>(log: *mut ngx_log_t) -> ngx_int_t {
+    crate::panic::catch(type_name::<M>(), "init_master", || {
+        M::init_master(&mut *log)
+    })
+    .unwrap_or(Status::NGX_ERROR)
+    .into()
+}
+
+unsafe extern "C" fn init_module<M: ProcessHooks>(cycle: *mut ngx_cycle_t) -> ngx_int_t {
+    if let Err(err) = check_compatibility() {
+        ngx_log_error!(NGX_LOG_EMERG, (*cycle).log, "{}: {err}", type_name::<M>());
+        return Status::NGX_ERROR.into();
+    }
+
+    crate::panic::catch(type_name::<M>(), "init_module", || {
+        M::init_module(&mut *cycle)
+    })
+    .unwrap_or(Status::NGX_ERROR)
+    .into()
+}
+
+unsafe extern "C" fn init_process<M: ProcessHooks>(cycle: *mut ngx_cycle_t) -> ngx_int_t {
+    crate::panic::catch(type_name::<M>(), "init_process", || {
+        M::init_process(&mut *cycle)
+    })
+    .unwrap_or(Status::NGX_ERROR)
+    .into()
+}
+
+unsafe extern "C" fn exit_process<M: ProcessHooks>(cycle: *mut ngx_cycle_t) {
+    let _ = crate::panic::catch(type_name::<M>(), "exit_process", || {
+        M::exit_process(&mut *cycle)
+    });
+}
+
+unsafe extern "C" fn exit_master<M: ProcessHooks>(cycle: *mut ngx_cycle_t) {
+    let _ = crate::panic::catch(type_name::<M>(), "exit_master", || {
+        M::exit_master(&mut *cycle)
+    });
+}
@@ -0,0 +1,113 @@
+//! Parsers for the numeric values in the NGINX configuration syntax.
+//!
+//! The functions wrap the corresponding NGINX parsers and accept the same values as the built-in
+//! directives, e.g. `client_max_body_size` or `keepalive_timeout`.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use core::time::Duration;
+//! # use ngx::core::parse;
+//! assert_eq!(parse::parse_size(b"16k"), Ok(16 * 1024));
+//! assert_eq!(parse::parse_time(b"1m 30s"), Ok(Duration::from_secs(90)));
+//! assert!(parse::parse_size(b"16x").is_err());
+//! ```
+//!
+//! See <https://nginx.org/en/docs/syntax.html>.
+use core::error;
+use core::fmt;
+use core::time::Duration;
+
+use nginx_sys::{
+    ngx_atoi, ngx_hextoi, ngx_int_t, ngx_parse_offset, ngx_parse_size, ngx_parse_time, ngx_str_t,
+    off_t, NGX_ERROR,
+};
+
+/// An error returned when a value cannot be parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError;
+
+impl error::Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid value")
+    }
+}
+
+/// Calls a parser with a temporary `ngx_str_t` over the value.
+///
+/// The NGINX parsers take a mutable pointer, but do not modify the data.
+fn with_ngx_str<T>(value: &[u8], f: impl FnOnce(&mut ngx_str_t) -> T) -> Result<T, ParseError> {
+    // ngx_parse_size and ngx_parse_offset read the unit from the last byte unconditionally
+    if value.is_empty() {
+        return Err(ParseError);
+    }
+
+    let mut s = ngx_str_t {
+        len: value.len(),
+        data: value.as_ptr().cast_mut(),
+    };
+    Ok(f(&mut s))
+}
+
+fn check(rc: ngx_int_t) -> Result<ngx_int_t, ParseError> {
+    if rc == NGX_ERROR as ngx_int_t {
+        return Err(ParseError);
+    }
+    Ok(rc)
+}
+
+/// Parses a size with an optional `k` or `m` suffix, e.g. `512`, `16k` or `1m`.
+///
+/// Same as `ngx_parse_size`.
+pub fn parse_size(value: impl AsRef<[u8]>) -> Result<usize, ParseError> {
+    let rc = with_ngx_str(value.as_ref(), |s| unsafe { ngx_parse_size(s) })?;
+    check(rc as ngx_int_t).map(|x| x as usize)
+}
+
+/// Parses an offset with an optional `k`, `m` or `g` suffix, e.g. `10m` or `4g`.
+///
+/// Same as `ngx_parse_offset`.
+pub fn parse_offset(value: impl AsRef<[u8]>) -> Result<off_t, ParseError> {
+    let rc = with_ngx_str(value.as_ref(), |s| unsafe { ngx_parse_offset(s) })?;
+    if rc == NGX_ERROR as off_t {
+        return Err(ParseError);
+    }
+    Ok(rc)
+}
+
+/// Parses a time interval with millisecond precision, e.g. `500ms`, `30s` or `1h 30m`.
+///
+/// A value without a unit is in seconds. Same as `ngx_parse_time` with `is_sec = 0`, as used
+/// for the timeout directives.
+pub fn parse_time(value: impl AsRef<[u8]>) -> Result<Duration, ParseError> {
+    let rc = with_ngx_str(value.as_ref(), |s| unsafe { ngx_parse_time(s, 0) })?;
+    check(rc).map(|x| Duration::from_millis(x as u64))
+}
+
+/// Parses a time interval with second precision, e.g. `30s`, `1h 30m` or `7d`.
+///
+/// A value without a unit is in seconds, and the `ms` unit is not allowed. Same as
+/// `ngx_parse_time` with `is_sec = 1`, as used for the cache validity directives.
+pub fn parse_time_sec(value: impl AsRef<[u8]>) -> Result<Duration, ParseError> {
+    let rc = with_ngx_str(value.as_ref(), |s| unsafe { ngx_parse_time(s, 1) })?;
+    check(rc).map(|x| Duration::from_secs(x as u64))
+}
+
+/// Parses a non-negative decimal integer.
+///
+/// Same as `ngx_atoi`.
+pub fn atoi(value: impl AsRef<[u8]>) -> Result<usize, ParseError> {
+    let value = value.as_ref();
+    let rc = unsafe { ngx_atoi(value.as_ptr().cast_mut(), value.len()) };
+    check(rc).map(|x| x as usize)
+}
+
+/// Parses a non-negative hexadecimal integer, without a prefix.
+///
+/// Same as `ngx_hextoi`.
+pub fn hextoi(value: impl AsRef<[u8]>) -> Result<usize, ParseError> {
+    let value = value.as_ref();
+    let rc = unsafe { ngx_hextoi(value.as_ptr().cast_mut(), value.len()) };
+    check(rc).map(|x| x as usize)
+}
@@ -5,10 +5,10 @@ use core::ptr::{self, NonNull};
 
 use nginx_sys::{
     ngx_buf_t, ngx_create_temp_buf, ngx_palloc, ngx_pcalloc, ngx_pfree, ngx_pmemalign, ngx_pnalloc,
-    ngx_pool_cleanup_add, ngx_pool_t, NGX_ALIGNMENT,
+    ngx_pool_cleanup_add, ngx_pool_cleanup_t, ngx_pool_t, NGX_ALIGNMENT,
 };
 
-use crate::allocator::{dangling_for_layout, AllocError, Allocator};
+use crate::allocator::{self, dangling_for_layout, AllocError, Allocator};
 use crate::core::buffer::{Buffer, MemoryBuffer, TemporaryBuffer};
 
 /// Non-owning wrapper for an [`ngx_pool_t`] pointer, providing methods for working with memory pools.
@@ -97,6 +97,11 @@ impl Pool {
         Pool(NonNull::new_unchecked(pool))
     }
 
+    /// Returns a raw pointer to the underlying `ngx_pool_t`.
+    pub fn as_ptr(&self) -> *mut ngx_pool_t {
+        self.0.as_ptr()
+    }
+
     /// Creates a buffer of the specified size in the memory pool.
     ///
     /// Returns `Some(TemporaryBuffer)` if the buffer is successfully created, or `None` if
@@ -151,12 +156,15 @@ impl Pool {
 
     /// Adds a cleanup handler for a value in the memory pool.
     ///
-    /// Returns `Ok(())` if the cleanup handler is successfully added, or `Err(())` if the cleanup
-    /// handler cannot be added.
+    /// Returns the cleanup entry if the cleanup handler is successfully added, or `Err(())` if the
+    /// cleanup handler cannot be added.
     ///
     /// # Safety
     /// This function is marked as unsafe because it involves raw pointer manipulation.
-    unsafe fn add_cleanup_for_value<T>(&mut self, value: *mut T) -> Result<(), ()> {
+    pub(crate) unsafe fn add_cleanup_for_value<T>(
+        &mut self,
+        value: *mut T,
+    ) -> Result<NonNull<ngx_pool_cleanup_t>, ()> {
         let cln = ngx_pool_cleanup_add(self.0.as_ptr(), 0);
         if cln.is_null() {
             return Err(());
@@ -164,7 +172,7 @@ impl Pool {
         (*cln).handler = Some(cleanup_type::<T>);
         (*cln).data = value as *mut c_void;
 
-        Ok(())
+        Ok(NonNull::new_unchecked(cln))
     }
 
     /// Allocates memory from the pool of the specified size.
@@ -213,11 +221,25 @@ impl Pool {
         self.alloc_unaligned(mem::size_of::<T>()) as *mut T
     }
 
+    /// Moves the value to the pool and adds a cleanup handler dropping it with the pool.
+    pub(crate) fn allocate_with_cleanup<T>(&mut self, value: T) -> Result<NonNull<T>, AllocError> {
+        let p = allocator::allocate(value, &*self)?;
+        unsafe {
+            if self.add_cleanup_for_value(p.as_ptr()).is_err() {
+                ptr::drop_in_place(p.as_ptr());
+                return Err(AllocError);
+            }
+        }
+        Ok(p)
+    }
+
     /// Allocates memory for a value of a specified type and adds a cleanup handler to the memory
     /// pool.
     ///
     /// Returns a typed pointer to the allocated memory if successful, or a null pointer if
     /// allocation or cleanup handler addition fails.
+    ///
+    /// See [PoolBox](crate::core::PoolBox) for an owned alternative.
     pub fn allocate<T>(&mut self, value: T) -> *mut T {
         unsafe {
             let p = self.alloc(mem::size_of::<T>()) as *mut T;
@@ -242,6 +264,6 @@ impl Pool {
 /// # Arguments
 ///
 /// * `data` - A raw pointer to the value of type `T` to be cleaned up.
-unsafe extern "C" fn cleanup_type<T>(data: *mut c_void) {
+pub(crate) unsafe extern "C" fn cleanup_type<T>(data: *mut c_void) {
     ptr::drop_in_place(data as *mut T);
 }
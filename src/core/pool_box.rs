@@ -0,0 +1,245 @@
+use core::cell::Cell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use nginx_sys::ngx_pool_cleanup_t;
+
+use crate::allocator::{AllocError, Box};
+use crate::core::Pool;
+
+/// Moves `value` to a [Box] in the pool and registers a cleanup handler dropping it with the pool.
+///
+/// Returns the leaked box, to be dropped with [drop_with_cleanup].
+fn box_with_cleanup<T>(
+    value: T,
+    pool: &Pool,
+) -> Result<(NonNull<T>, NonNull<ngx_pool_cleanup_t>), AllocError> {
+    let value = Box::try_new_in(value, pool.clone())?;
+    let ptr = NonNull::from(Box::leak(value));
+
+    // SAFETY: the value is valid until the cleanup handler runs or is disabled.
+    match unsafe { pool.clone().add_cleanup_for_value(ptr.as_ptr()) } {
+        Ok(cln) => Ok((ptr, cln)),
+        Err(_) => {
+            // SAFETY: the pointer is obtained from a box allocated in the same pool.
+            drop(unsafe { Box::from_raw_in(ptr.as_ptr(), pool.clone()) });
+            Err(AllocError)
+        }
+    }
+}
+
+/// Drops a value owned by a pool cleanup handler and releases the memory to the pool.
+///
+/// # Safety
+///
+/// `ptr` and `cln` are returned by [box_with_cleanup] for the same pool, and the pool is not
+/// destroyed yet.
+unsafe fn drop_with_cleanup<T>(pool: &Pool, ptr: NonNull<T>, mut cln: NonNull<ngx_pool_cleanup_t>) {
+    // the cleanup entry cannot be removed, but an entry without handler is skipped
+    cln.as_mut().handler = None;
+    drop(Box::from_raw_in(ptr.as_ptr(), pool.clone()));
+}
+
+/// An owned value allocated from a [Pool].
+///
+/// The value is dropped either with the box, or, if the box is released with
+/// [PoolBox::into_pool], with the pool. Unlike [Pool::allocate], the value cannot be dropped twice
+/// or leaked if the pool outlives the owner.
+///
+/// As with any other pointer obtained from a [Pool], the box must not outlive the pool, and the
+/// constructor is unsafe for this reason. The box is usually stored in the objects allocated from
+/// the same pool, e.g. a request context.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::allocator::AllocError;
+/// # use ngx::core::{Pool, PoolBox, PoolRc};
+/// # fn example(pool: Pool) -> Result<(), AllocError> {
+/// // SAFETY: the values are dropped before the pool.
+/// let mut buf = unsafe { PoolBox::try_new_in(Vec::<u8>::new(), &pool)? };
+/// buf.extend_from_slice(b"example");
+///
+/// let shared = unsafe { PoolRc::try_new_in(String::from("shared"), &pool)? };
+/// let other = shared.clone();
+/// assert_eq!(PoolRc::strong_count(&other), 2);
+/// # Ok(())
+/// # }
+/// ```
+pub struct PoolBox<T> {
+    ptr: NonNull<T>,
+    cln: NonNull<ngx_pool_cleanup_t>,
+    pool: Pool,
+}
+
+impl<T> PoolBox<T> {
+    /// Moves `value` to the pool.
+    ///
+    /// # Safety
+    ///
+    /// The box must not outlive the pool.
+    pub unsafe fn try_new_in(value: T, pool: &Pool) -> Result<Self, AllocError> {
+        let (ptr, cln) = box_with_cleanup(value, pool)?;
+        Ok(Self {
+            ptr,
+            cln,
+            pool: pool.clone(),
+        })
+    }
+
+    /// Returns a raw pointer to the value.
+    pub fn as_ptr(this: &Self) -> *mut T {
+        this.ptr.as_ptr()
+    }
+
+    /// Releases the ownership of the value to the pool.
+    ///
+    /// The value is dropped when the pool is destroyed.
+    pub fn into_pool(this: Self) -> NonNull<T> {
+        let ptr = this.ptr;
+        core::mem::forget(this);
+        ptr
+    }
+}
+
+impl<T> Deref for PoolBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the value is valid until the box is dropped.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PoolBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the value is valid until the box is dropped.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for PoolBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the box does not outlive the pool.
+        unsafe { drop_with_cleanup(&self.pool, self.ptr, self.cln) }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for PoolBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+struct PoolRcInner<T> {
+    strong: Cell<usize>,
+    cln: NonNull<ngx_pool_cleanup_t>,
+    value: T,
+}
+
+/// A reference-counted value allocated from a [Pool].
+///
+/// The value is dropped with the last reference or with the pool, whichever happens first. The
+/// references must not outlive the pool, same as with [PoolBox].
+pub struct PoolRc<T> {
+    ptr: NonNull<PoolRcInner<T>>,
+    pool: Pool,
+}
+
+impl<T> PoolRc<T> {
+    /// Moves `value` to the pool.
+    ///
+    /// # Safety
+    ///
+    /// The references must not outlive the pool.
+    pub unsafe fn try_new_in(value: T, pool: &Pool) -> Result<Self, AllocError> {
+        let inner = PoolRcInner {
+            strong: Cell::new(1),
+            cln: NonNull::dangling(),
+            value,
+        };
+        let (mut ptr, cln) = box_with_cleanup(inner, pool)?;
+        ptr.as_mut().cln = cln;
+
+        Ok(Self {
+            ptr,
+            pool: pool.clone(),
+        })
+    }
+
+    /// Returns the number of references to the value.
+    pub fn strong_count(this: &Self) -> usize {
+        this.inner().strong.get()
+    }
+
+    /// Returns `true` if both references point to the same value.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        ptr::eq(this.ptr.as_ptr(), other.ptr.as_ptr())
+    }
+
+    /// Returns a mutable reference to the value, if there are no other references.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if Self::strong_count(this) == 1 {
+            // SAFETY: the reference is unique.
+            Some(unsafe { &mut this.ptr.as_mut().value })
+        } else {
+            None
+        }
+    }
+
+    fn inner(&self) -> &PoolRcInner<T> {
+        // SAFETY: the value is valid while there are references.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> Clone for PoolRc<T> {
+    fn clone(&self) -> Self {
+        let strong = &self.inner().strong;
+        strong.set(strong.get() + 1);
+
+        Self {
+            ptr: self.ptr,
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<T> Deref for PoolRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T> Drop for PoolRc<T> {
+    fn drop(&mut self) {
+        let strong = &self.inner().strong;
+        strong.set(strong.get() - 1);
+
+        if strong.get() == 0 {
+            let cln = self.inner().cln;
+            // SAFETY: this is the last reference, and the references do not outlive the pool.
+            unsafe { drop_with_cleanup(&self.pool, self.ptr, cln) }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for PoolRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
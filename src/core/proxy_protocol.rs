@@ -0,0 +1,199 @@
+//! PROXY protocol connection information.
+use core::fmt;
+use core::net::IpAddr;
+
+use nginx_sys::{ngx_connection_t, ngx_proxy_protocol_t};
+
+use crate::core::NgxStr;
+
+/// PROXY protocol v2 TLV types.
+///
+/// See <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>.
+pub mod tlv {
+    /// Application-Layer Protocol Negotiation protocol name.
+    pub const ALPN: u8 = 0x01;
+    /// Host name provided by the client, e.g. from the TLS SNI extension.
+    pub const AUTHORITY: u8 = 0x02;
+    /// CRC32c checksum of the header.
+    pub const CRC32C: u8 = 0x03;
+    /// Ignored padding.
+    pub const NOOP: u8 = 0x04;
+    /// Opaque unique identifier of the connection.
+    pub const UNIQUE_ID: u8 = 0x05;
+    /// TLS information, with nested TLVs of the `SSL_*` types.
+    pub const SSL: u8 = 0x20;
+    /// TLS version, nested in [SSL].
+    pub const SSL_VERSION: u8 = 0x21;
+    /// Common name of the client certificate subject, nested in [SSL].
+    pub const SSL_CN: u8 = 0x22;
+    /// Cipher name, nested in [SSL].
+    pub const SSL_CIPHER: u8 = 0x23;
+    /// Signature algorithm of the client certificate, nested in [SSL].
+    pub const SSL_SIG_ALG: u8 = 0x24;
+    /// Key algorithm of the client certificate, nested in [SSL].
+    pub const SSL_KEY_ALG: u8 = 0x25;
+    /// Network namespace name.
+    pub const NETNS: u8 = 0x30;
+}
+
+/// Connection information received with the PROXY protocol header.
+///
+/// Available when the `proxy_protocol` parameter is set on a listening socket.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::proxy_protocol::tlv;
+/// # use ngx::http::Request;
+/// # fn handler(request: &Request) {
+/// if let Some(pp) = request.proxy_protocol() {
+///     let client = (pp.src_ip(), pp.src_port());
+///     let authority = pp.tlv(tlv::AUTHORITY);
+/// }
+/// # }
+/// ```
+#[repr(transparent)]
+pub struct ProxyProtocol(ngx_proxy_protocol_t);
+
+impl ProxyProtocol {
+    /// Returns the PROXY protocol information of a connection, if received.
+    ///
+    /// # Safety
+    ///
+    /// `c` must be a valid pointer to a connection.
+    pub unsafe fn from_connection<'a>(c: *const ngx_connection_t) -> Option<&'a Self> {
+        (*c).proxy_protocol.cast::<Self>().as_ref()
+    }
+
+    /// Returns the source address as received in the header.
+    pub fn src_addr(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.src_addr) }
+    }
+
+    /// Returns the destination address as received in the header.
+    pub fn dst_addr(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.dst_addr) }
+    }
+
+    /// Returns the parsed source address.
+    ///
+    /// Returns `None` if the address is not an IP address, e.g. for the `UNKNOWN` protocol.
+    pub fn src_ip(&self) -> Option<IpAddr> {
+        self.src_addr().to_str().ok()?.parse().ok()
+    }
+
+    /// Returns the parsed destination address.
+    ///
+    /// Returns `None` if the address is not an IP address, e.g. for the `UNKNOWN` protocol.
+    pub fn dst_ip(&self) -> Option<IpAddr> {
+        self.dst_addr().to_str().ok()?.parse().ok()
+    }
+
+    /// Returns the source port.
+    pub fn src_port(&self) -> u16 {
+        self.0.src_port
+    }
+
+    /// Returns the destination port.
+    pub fn dst_port(&self) -> u16 {
+        self.0.dst_port
+    }
+
+    /// Returns an iterator over the TLVs of a PROXY protocol v2 header.
+    #[cfg(nginx1_23_2)]
+    pub fn tlvs(&self) -> ProxyProtocolTlvs<'_> {
+        ProxyProtocolTlvs(self.0.tlvs.as_bytes())
+    }
+
+    /// Returns the value of the first TLV of the specified type.
+    ///
+    /// See [tlv] for the known types.
+    #[cfg(nginx1_23_2)]
+    pub fn tlv(&self, type_: u8) -> Option<&[u8]> {
+        self.tlvs().find(|x| x.0 == type_).map(|x| x.1)
+    }
+
+    /// Returns the value of the first TLV of the specified type nested in the SSL TLV.
+    #[cfg(nginx1_23_2)]
+    pub fn ssl_tlv(&self, type_: u8) -> Option<&[u8]> {
+        ssl_tlvs(self.tlv(tlv::SSL)?)?
+            .find(|x| x.0 == type_)
+            .map(|x| x.1)
+    }
+
+    /// Returns `true` if the client connected over TLS, according to the SSL TLV.
+    #[cfg(nginx1_23_2)]
+    pub fn ssl_client(&self) -> bool {
+        // PP2_CLIENT_SSL
+        self.tlv(tlv::SSL)
+            .and_then(|x| x.first())
+            .is_some_and(|client| client & 0x01 != 0)
+    }
+}
+
+impl fmt::Debug for ProxyProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyProtocol")
+            .field("src_addr", &self.src_addr())
+            .field("src_port", &self.src_port())
+            .field("dst_addr", &self.dst_addr())
+            .field("dst_port", &self.dst_port())
+            .finish()
+    }
+}
+
+/// Iterator over the `(type, value)` pairs of PROXY protocol v2 TLVs.
+///
+/// The iteration stops at the first malformed entry.
+#[derive(Clone, Debug)]
+pub struct ProxyProtocolTlvs<'a>(&'a [u8]);
+
+impl<'a> Iterator for ProxyProtocolTlvs<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [type_, hi, lo, rest @ ..] = self.0 else {
+            self.0 = &[];
+            return None;
+        };
+
+        let len = u16::from_be_bytes([*hi, *lo]) as usize;
+        let Some((value, rest)) = rest.split_at_checked(len) else {
+            self.0 = &[];
+            return None;
+        };
+
+        self.0 = rest;
+        Some((*type_, value))
+    }
+}
+
+/// Returns the nested TLVs of an SSL TLV value.
+#[cfg_attr(not(nginx1_23_2), allow(dead_code))]
+fn ssl_tlvs(value: &[u8]) -> Option<ProxyProtocolTlvs<'_>> {
+    // struct pp2_tlv_ssl { uint8_t client; uint32_t verify; struct pp2_tlv sub_tlv[0]; }
+    value.get(5..).map(ProxyProtocolTlvs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tlvs() {
+        let data = b"\x01\x00\x02h2\x20\x00\x0b\x01\x00\x00\x00\x00\x22\x00\x03abc\x05\x00\x05ab";
+
+        let mut it = ProxyProtocolTlvs(data);
+        assert_eq!(it.next(), Some((tlv::ALPN, &b"h2"[..])));
+
+        let (type_, ssl) = it.next().unwrap();
+        assert_eq!(type_, tlv::SSL);
+        assert!(ssl_tlvs(ssl).unwrap().eq([(tlv::SSL_CN, &b"abc"[..])]));
+
+        // truncated value
+        assert_eq!(it.next(), None);
+        assert_eq!(it.next(), None);
+
+        assert_eq!(ProxyProtocolTlvs(b"\x01\x00").next(), None);
+        assert!(ssl_tlvs(b"\x01\x00").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use core::marker::PhantomData;
+use core::{cmp, slice};
+use std::io;
+use std::vec::Vec;
+
+use crate::ffi::{ngx_buf_t, ngx_chain_t, ngx_read_file, off_t};
+
+/// Size of the intermediate buffer for the parts of a chain stored in files.
+const FILE_BUFFER_SIZE: usize = 16384;
+
+/// An [io::BufRead] adapter over a chain of nginx buffers.
+///
+/// The contents of memory buffers are returned without copying. The buffers stored in files, such
+/// as the parts of a request body written to a temporary file, are read through an intermediate
+/// buffer allocated on the first use.
+///
+/// Special buffers with no data, e.g. flush or last buffer markers, are skipped.
+///
+/// Example:
+/// ```rust,no_run
+/// # use std::io::BufRead;
+/// # use ngx::http::Request;
+/// # fn handler(request: &mut Request) -> std::io::Result<()> {
+/// if let Some(body) = request.request_body() {
+///     for line in body.reader().lines() {
+///         let _line = line?;
+///         // ...
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ChainReader<'a> {
+    cl: *const ngx_chain_t,
+    /// Position in the current buffer, in memory or in the file.
+    offset: usize,
+    /// Data read from a file.
+    file_buf: Vec<u8>,
+    /// Unconsumed range of `file_buf`.
+    file_pos: usize,
+    file_end: usize,
+    _lifetime: PhantomData<&'a ngx_chain_t>,
+}
+
+impl<'a> ChainReader<'a> {
+    /// Creates a reader over a chain of buffers.
+    ///
+    /// # Safety
+    ///
+    /// `cl` must be null or a valid pointer to a chain of buffers, and the chain must not be
+    /// modified for the lifetime `'a`.
+    pub unsafe fn from_ptr(cl: *const ngx_chain_t) -> Self {
+        Self {
+            cl,
+            offset: 0,
+            file_buf: Vec::new(),
+            file_pos: 0,
+            file_end: 0,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Moves to the next link of the chain.
+    fn advance(&mut self) {
+        // SAFETY: the chain is valid for the lifetime 'a
+        self.cl = unsafe { (*self.cl).next };
+        self.offset = 0;
+    }
+
+    /// Reads the next part of a buffer stored in a file to the intermediate buffer.
+    fn read_file(&mut self, b: &ngx_buf_t) -> io::Result<usize> {
+        let size = (b.file_last - b.file_pos) as usize - self.offset;
+        let size = cmp::min(size, FILE_BUFFER_SIZE);
+
+        if self.file_buf.is_empty() {
+            self.file_buf
+                .try_reserve_exact(FILE_BUFFER_SIZE)
+                .map_err(|_| io::ErrorKind::OutOfMemory)?;
+            self.file_buf.resize(FILE_BUFFER_SIZE, 0);
+        }
+
+        let n = unsafe {
+            ngx_read_file(
+                b.file,
+                self.file_buf.as_mut_ptr(),
+                size,
+                b.file_pos + self.offset as off_t,
+            )
+        };
+
+        match n {
+            n if n < 0 => Err(io::Error::other("failed to read buffer file")),
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            n => {
+                self.offset += n as usize;
+                self.file_pos = 0;
+                self.file_end = n as usize;
+                Ok(n as usize)
+            }
+        }
+    }
+}
+
+impl io::BufRead for ChainReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        loop {
+            if self.file_pos < self.file_end {
+                return Ok(&self.file_buf[self.file_pos..self.file_end]);
+            }
+
+            // SAFETY: the chain is valid for the lifetime 'a
+            let Some(cl) = (unsafe { self.cl.as_ref() }) else {
+                return Ok(&[]);
+            };
+
+            let Some(b) = (unsafe { cl.buf.as_ref() }) else {
+                self.advance();
+                continue;
+            };
+
+            if !b.pos.is_null() && (b.temporary() != 0 || b.memory() != 0 || b.mmap() != 0) {
+                // SAFETY: pos and last point to the same allocated memory
+                let len = unsafe { b.last.offset_from(b.pos) } as usize;
+
+                if self.offset < len {
+                    let data =
+                        unsafe { slice::from_raw_parts(b.pos.add(self.offset), len - self.offset) };
+                    return Ok(data);
+                }
+            } else if b.in_file() != 0
+                && !b.file.is_null()
+                && ((b.file_last - b.file_pos) as usize) > self.offset
+            {
+                self.read_file(b)?;
+                continue;
+            }
+
+            self.advance();
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.file_pos < self.file_end {
+            self.file_pos = cmp::min(self.file_pos + amt, self.file_end);
+        } else {
+            self.offset += amt;
+        }
+    }
+}
+
+impl io::Read for ChainReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = io::BufRead::fill_buf(self)?;
+        let n = cmp::min(data.len(), buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        io::BufRead::consume(self, n);
+        Ok(n)
+    }
+}
@@ -3,11 +3,13 @@
 //! See <https://nginx.org/en/docs/dev/development_guide.html#shared_memory>.
 use core::alloc::Layout;
 use core::cmp;
+use core::ffi::CStr;
 use core::ptr::{self, NonNull};
+use core::slice;
 
 use nginx_sys::{
-    ngx_shm_zone_t, ngx_shmtx_lock, ngx_shmtx_unlock, ngx_slab_alloc_locked, ngx_slab_free_locked,
-    ngx_slab_pool_t,
+    ngx_pagesize_shift, ngx_shm_zone_t, ngx_shmtx_lock, ngx_shmtx_unlock, ngx_slab_alloc_locked,
+    ngx_slab_free_locked, ngx_slab_pool_t, ngx_slab_stat_t,
 };
 
 use crate::allocator::{dangling_for_layout, AllocError, Allocator};
@@ -108,6 +110,63 @@ impl SlabPool {
         unsafe { ngx_shmtx_lock(ptr::addr_of_mut!((*shpool).mutex)) };
         LockedSlabPool(self.0)
     }
+
+    /// Returns the name of the shared zone.
+    ///
+    /// The name is only available for the pools initialized by NGINX for the zones added to the
+    /// cycle.
+    pub fn zone_name(&self) -> Option<&[u8]> {
+        let log_ctx = self.as_ref().log_ctx;
+        if log_ctx.is_null() {
+            return None;
+        }
+        // SAFETY: log_ctx is a NUL-terminated string in the pool, formatted as ` in zone "name"`.
+        let ctx = unsafe { CStr::from_ptr(log_ctx.cast()) }.to_bytes();
+        ctx.strip_prefix(b" in zone \"")?.strip_suffix(b"\"")
+    }
+
+    /// Returns the page usage statistics of the pool.
+    ///
+    /// This method locks the pool mutex. Use [`LockedSlabPool::stats`] to collect the statistics
+    /// together with [`LockedSlabPool::slots`].
+    pub fn stats(&self) -> SlabPoolStats {
+        self.lock().stats()
+    }
+}
+
+/// Page usage statistics of a [`SlabPool`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SlabPoolStats {
+    /// Size of the shared zone in bytes.
+    pub size: usize,
+    /// Number of the pages available for allocations.
+    pub pages: usize,
+    /// Number of the free pages.
+    pub free_pages: usize,
+}
+
+impl SlabPoolStats {
+    /// Returns the number of the pages in use.
+    pub fn used_pages(&self) -> usize {
+        self.pages - self.free_pages
+    }
+}
+
+/// Statistics of a slab slot, i.e. allocations of the same size class.
+///
+/// Corresponds to [`ngx_slab_stat_t`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SlabSlotStats {
+    /// Size of the allocations served by the slot.
+    pub size: usize,
+    /// Total number of the chunks in the pages assigned to the slot.
+    pub total: usize,
+    /// Number of the chunks in use.
+    pub used: usize,
+    /// Number of the allocation requests.
+    pub requests: usize,
+    /// Number of the failed allocation requests.
+    pub failures: usize,
 }
 
 /// Wrapper for a locked [`ngx_slab_pool_t`] pointer.
@@ -146,6 +205,50 @@ unsafe impl Allocator for LockedSlabPool {
     }
 }
 
+impl LockedSlabPool {
+    /// Returns the page usage statistics of the pool.
+    pub fn stats(&self) -> SlabPoolStats {
+        // SAFETY: the pool is locked and the fields are initialized by ngx_slab_init.
+        let pool = unsafe { self.0.as_ref() };
+        let pages = unsafe { pool.last.offset_from(pool.pages) };
+
+        SlabPoolStats {
+            size: pool.end as usize - pool.addr as usize,
+            pages: pages.try_into().unwrap_or(0),
+            free_pages: pool.pfree,
+        }
+    }
+
+    /// Returns the statistics of the slab slots, in the ascending order of the allocation size.
+    ///
+    /// The allocations larger than half of the page size are served with whole pages and are not
+    /// accounted in the slots.
+    pub fn slots(&self) -> impl Iterator<Item = SlabSlotStats> + '_ {
+        // SAFETY: the pool is locked and the fields are initialized by ngx_slab_init.
+        let pool = unsafe { self.0.as_ref() };
+        let stats: &[ngx_slab_stat_t] = if pool.stats.is_null() {
+            &[]
+        } else {
+            // SAFETY: ngx_slab_init allocates a statistics entry per slot, and
+            // ngx_pagesize_shift is set once on the process start.
+            let n = unsafe { ngx_pagesize_shift }.saturating_sub(pool.min_shift);
+            unsafe { slice::from_raw_parts(pool.stats, n) }
+        };
+        let min_shift = pool.min_shift;
+
+        stats
+            .iter()
+            .enumerate()
+            .map(move |(i, stat)| SlabSlotStats {
+                size: 1 << (min_shift + i),
+                total: stat.total,
+                used: stat.used,
+                requests: stat.reqs,
+                failures: stat.fails,
+            })
+    }
+}
+
 impl Drop for LockedSlabPool {
     fn drop(&mut self) {
         let shpool = unsafe { self.0.as_mut() };
@@ -6,7 +6,8 @@ use core::str::{self, Utf8Error};
 #[cfg(feature = "std")]
 use std::{borrow::Cow, string::String};
 
-use crate::ffi::{ngx_str_t, u_char};
+use crate::core::Pool;
+use crate::ffi::{ngx_pnalloc, ngx_str_t, u_char};
 
 /// Static string initializer for [`ngx_str_t`].
 ///
@@ -23,8 +24,35 @@ macro_rules! ngx_string {
     }};
 }
 
+/// Formats the arguments into a string allocated from the pool.
+///
+/// Returns an [`ngx_str_t`], or `None` on allocation or formatting failure.
+/// See [`NgxStr::format_in`].
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Pool;
+/// # use ngx::ngx_format;
+/// # fn example(pool: Pool, hits: usize) -> Option<()> {
+/// let value = ngx_format!(pool, "hits={hits}, pid={}", std::process::id())?;
+/// # Some(())
+/// # }
+/// ```
+///
+/// [`ngx_str_t`]: https://nginx.org/en/docs/dev/development_guide.html#string_overview
+#[macro_export]
+macro_rules! ngx_format {
+    ($pool:expr, $($arg:tt)+) => {
+        $crate::core::NgxStr::format_in(&$pool, format_args!($($arg)+))
+    };
+}
+
 #[cfg(feature = "alloc")]
 pub use self::_alloc::NgxString;
+#[cfg(feature = "serde")]
+pub use self::_serde::serialize_ngx_str;
+#[cfg(all(feature = "serde", feature = "alloc"))]
+pub use self::_serde::NgxStringSeed;
 
 /// Representation of a borrowed [Nginx string].
 ///
@@ -84,6 +112,99 @@ impl NgxStr {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+
+    /// Checks that two strings are an ASCII case-insensitive match.
+    pub fn eq_ignore_ascii_case(&self, other: impl AsRef<[u8]>) -> bool {
+        self.0.eq_ignore_ascii_case(other.as_ref())
+    }
+
+    /// Returns `true` if the string starts with `prefix`, ignoring ASCII case.
+    pub fn starts_with_ignore_case(&self, prefix: impl AsRef<[u8]>) -> bool {
+        let prefix = prefix.as_ref();
+        self.0
+            .get(..prefix.len())
+            .is_some_and(|x| x.eq_ignore_ascii_case(prefix))
+    }
+
+    /// Returns `true` if the string ends with `suffix`, ignoring ASCII case.
+    pub fn ends_with_ignore_case(&self, suffix: impl AsRef<[u8]>) -> bool {
+        let suffix = suffix.as_ref();
+        self.0
+            .len()
+            .checked_sub(suffix.len())
+            .is_some_and(|start| self.0[start..].eq_ignore_ascii_case(suffix))
+    }
+
+    /// Converts the string to ASCII lower case in place.
+    pub fn make_ascii_lowercase(&mut self) {
+        self.0.make_ascii_lowercase()
+    }
+
+    /// Formats the arguments into a string allocated from the pool, as with `ngx_sprintf`.
+    ///
+    /// The arguments are formatted twice, first to calculate the length and then to write the
+    /// string, so that exactly the required amount of memory is allocated. Returns `None` on
+    /// allocation or formatting failure.
+    ///
+    /// The string is freed with the pool, which makes it suitable for the variable values and the
+    /// header values.
+    pub fn format_in(pool: &Pool, args: fmt::Arguments<'_>) -> Option<ngx_str_t> {
+        if let Some(s) = args.as_str() {
+            // SAFETY: the pool is valid.
+            return unsafe { ngx_str_t::from_bytes(pool.as_ptr(), s.as_bytes()) };
+        }
+
+        let mut counter = FmtCounter(0);
+        fmt::Write::write_fmt(&mut counter, args).ok()?;
+
+        let len = counter.0;
+        if len == 0 {
+            return Some(ngx_str_t::empty());
+        }
+
+        // SAFETY: the pool is valid.
+        let data = unsafe { ngx_pnalloc(pool.as_ptr(), len) }.cast::<u8>();
+        if data.is_null() {
+            return None;
+        }
+
+        // SAFETY: the buffer is allocated above with `len` bytes.
+        let buf = unsafe { core::slice::from_raw_parts_mut(data, len) };
+        let mut writer = FmtSlice { buf, pos: 0 };
+        fmt::Write::write_fmt(&mut writer, args).ok()?;
+
+        // The second pass may produce a shorter output with an inconsistent Display implementation.
+        Some(ngx_str_t {
+            len: writer.pos,
+            data,
+        })
+    }
+}
+
+/// Calculates the length of the formatted output.
+struct FmtCounter(usize);
+
+impl fmt::Write for FmtCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 = self.0.checked_add(s.len()).ok_or(fmt::Error)?;
+        Ok(())
+    }
+}
+
+/// Writes the formatted output into a fixed size buffer.
+struct FmtSlice<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl fmt::Write for FmtSlice<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.pos + s.len();
+        let dst = self.buf.get_mut(self.pos..end).ok_or(fmt::Error)?;
+        dst.copy_from_slice(s.as_bytes());
+        self.pos = end;
+        Ok(())
+    }
 }
 
 impl AsRef<[u8]> for NgxStr {
@@ -364,6 +485,18 @@ mod _alloc {
         }
     }
 
+    impl NgxStr {
+        /// Returns a copy of the string converted to ASCII lower case, allocated with `alloc`.
+        pub fn to_lowercase_in<A>(&self, alloc: A) -> Result<NgxString<A>, TryReserveError>
+        where
+            A: Allocator + Clone,
+        {
+            let mut this = NgxString::try_from_bytes_in(self, alloc)?;
+            this.0.make_ascii_lowercase();
+            Ok(this)
+        }
+    }
+
     impl<A> AsRef<NgxStr> for NgxString<A>
     where
         A: Allocator + Clone,
@@ -543,6 +676,220 @@ mod _alloc {
     impl_partial_ord_eq_from!(NgxStr, &'a String);
 }
 
+#[cfg(all(feature = "std", unix))]
+mod _std {
+    use core::ptr;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use super::*;
+
+    impl NgxStr {
+        /// Create an [NgxStr] from an [OsStr].
+        ///
+        /// The OS strings on Unix are arbitrary byte sequences, and are converted without copying.
+        #[inline]
+        pub fn from_os_str(s: &OsStr) -> &Self {
+            Self::from_bytes(s.as_bytes())
+        }
+
+        /// Create an [NgxStr] from a [Path].
+        #[inline]
+        pub fn from_path(path: &Path) -> &Self {
+            Self::from_os_str(path.as_os_str())
+        }
+
+        /// Access the [NgxStr] as an [OsStr].
+        #[inline]
+        pub fn as_os_str(&self) -> &OsStr {
+            OsStr::from_bytes(self.as_bytes())
+        }
+
+        /// Access the [NgxStr] as a [Path].
+        #[inline]
+        pub fn as_path(&self) -> &Path {
+            Path::new(self.as_os_str())
+        }
+
+        /// Copies the path into a string allocated from the pool.
+        ///
+        /// The copy is nul-terminated, as expected by the NGINX file APIs, such as
+        /// `ngx_open_file`; the terminator is not included in the length. Returns `None` on
+        /// allocation failure.
+        pub fn from_path_in(pool: &Pool, path: &Path) -> Option<ngx_str_t> {
+            let bytes = path.as_os_str().as_bytes();
+
+            // SAFETY: the pool is valid.
+            let data = unsafe { ngx_pnalloc(pool.as_ptr(), bytes.len() + 1) }.cast::<u8>();
+            if data.is_null() {
+                return None;
+            }
+
+            // SAFETY: the buffer is allocated above with `bytes.len() + 1` bytes.
+            unsafe {
+                ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+                *data.add(bytes.len()) = 0;
+            }
+
+            Some(ngx_str_t {
+                len: bytes.len(),
+                data,
+            })
+        }
+    }
+
+    impl AsRef<OsStr> for NgxStr {
+        fn as_ref(&self) -> &OsStr {
+            self.as_os_str()
+        }
+    }
+
+    impl AsRef<Path> for NgxStr {
+        fn as_ref(&self) -> &Path {
+            self.as_path()
+        }
+    }
+
+    impl<'a> From<&'a Path> for &'a NgxStr {
+        fn from(path: &'a Path) -> Self {
+            NgxStr::from_path(path)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod _serde {
+    #[cfg(feature = "alloc")]
+    use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+    use serde::{Serialize, Serializer};
+
+    use super::*;
+
+    #[cfg(feature = "alloc")]
+    use crate::allocator::Allocator;
+
+    /// Serializes the string as `str` in human-readable formats, replacing invalid UTF-8
+    /// sequences, and as bytes otherwise.
+    impl Serialize for NgxStr {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if !serializer.is_human_readable() {
+                return serializer.serialize_bytes(self.as_bytes());
+            }
+
+            match self.to_str() {
+                Ok(s) => serializer.serialize_str(s),
+                Err(_) => serializer.collect_str(self),
+            }
+        }
+    }
+
+    /// Serializes an [`ngx_str_t`] in the same way as [`NgxStr`].
+    ///
+    /// Intended for use with `#[serde(serialize_with = "ngx::core::serialize_ngx_str")]`.
+    pub fn serialize_ngx_str<S>(value: &ngx_str_t, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        NgxStr::from_bytes(value.as_bytes()).serialize(serializer)
+    }
+
+    #[cfg(feature = "alloc")]
+    impl<A> Serialize for NgxString<A>
+    where
+        A: Allocator + Clone,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            (**self).serialize(serializer)
+        }
+    }
+
+    /// A [`DeserializeSeed`] producing an [`NgxString`] in the specified allocator.
+    ///
+    /// Accepts strings, byte strings and sequences of bytes, and copies the data directly to the
+    /// allocator memory, e.g. to a request [`Pool`](crate::core::Pool).
+    ///
+    /// Example:
+    /// ```rust,ignore
+    /// use serde::de::DeserializeSeed;
+    ///
+    /// let value = NgxStringSeed::new(request.pool()).deserialize(&mut deserializer)?;
+    /// ```
+    #[cfg(feature = "alloc")]
+    #[derive(Clone, Debug)]
+    pub struct NgxStringSeed<A>(A)
+    where
+        A: Allocator + Clone;
+
+    #[cfg(feature = "alloc")]
+    impl<A> NgxStringSeed<A>
+    where
+        A: Allocator + Clone,
+    {
+        /// Creates a new seed for the allocator.
+        pub fn new(alloc: A) -> Self {
+            Self(alloc)
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl<'de, A> DeserializeSeed<'de> for NgxStringSeed<A>
+    where
+        A: Allocator + Clone,
+    {
+        type Value = NgxString<A>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_bytes(self)
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl<'de, A> Visitor<'de> for NgxStringSeed<A>
+    where
+        A: Allocator + Clone,
+    {
+        type Value = NgxString<A>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a string or a byte string")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            self.visit_bytes(v.as_bytes())
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            NgxString::try_from_bytes_in(v, self.0)
+                .map_err(|_| E::custom("failed to allocate string"))
+        }
+
+        fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+            let mut s = NgxString::new_in(self.0);
+            if let Some(n) = seq.size_hint() {
+                s.try_reserve_exact(n)
+                    .map_err(|_| de::Error::custom("failed to allocate string"))?;
+            }
+
+            while let Some(b) = seq.next_element::<u8>()? {
+                s.try_append([b])
+                    .map_err(|_| de::Error::custom("failed to allocate string"))?;
+            }
+
+            Ok(s)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate alloc;
@@ -577,6 +924,50 @@ mod tests {
         assert_eq!(ns, "test");
     }
 
+    #[test]
+    fn test_ignore_case() {
+        let ns = NgxStr::from_bytes(b"Content-Type");
+
+        assert!(ns.eq_ignore_ascii_case("content-type"));
+        assert!(!ns.eq_ignore_ascii_case("content-typ"));
+        assert!(ns.starts_with_ignore_case("CONTENT-"));
+        assert!(ns.starts_with_ignore_case(""));
+        assert!(!ns.starts_with_ignore_case("Content-Type-Options"));
+        assert!(ns.ends_with_ignore_case("-TYPE"));
+        assert!(!ns.ends_with_ignore_case("X-Content-Type"));
+
+        #[cfg(feature = "alloc")]
+        {
+            let lower = ns.to_lowercase_in(crate::allocator::Global).unwrap();
+            assert_eq!(lower, b"content-type");
+        }
+    }
+
+    #[test]
+    fn test_format_writers() {
+        use core::fmt::Write;
+
+        let mut counter = FmtCounter(0);
+        write!(counter, "{}-{:04}", "key", 7).unwrap();
+        assert_eq!(counter.0, 8);
+
+        let mut buf = [0u8; 8];
+        let mut writer = FmtSlice {
+            buf: &mut buf,
+            pos: 0,
+        };
+        write!(writer, "{}-{:04}", "key", 7).unwrap();
+        assert_eq!(writer.pos, 8);
+        assert_eq!(&buf, b"key-0007");
+
+        let mut buf = [0u8; 4];
+        let mut writer = FmtSlice {
+            buf: &mut buf,
+            pos: 0,
+        };
+        assert!(write!(writer, "overflow").is_err());
+    }
+
     #[test]
     #[cfg(feature = "alloc")]
     fn test_string_comparisons() {
@@ -630,6 +1021,45 @@ mod tests {
         assert_eq!((s.as_bytes().as_ptr(), s.capacity()), saved);
     }
 
+    #[test]
+    #[cfg(all(feature = "std", unix))]
+    fn test_path_conversions() {
+        use std::ffi::OsStr;
+        use std::path::Path;
+
+        let path = Path::new("/usr/local/nginx/html/index.html");
+        let s = NgxStr::from_path(path);
+
+        assert_eq!(s, "/usr/local/nginx/html/index.html");
+        assert_eq!(s.as_path(), path);
+        assert_eq!(s.as_path().file_name(), Some(OsStr::new("index.html")));
+        assert_eq!(NgxStr::from_os_str(path.as_os_str()), s);
+
+        // non-UTF-8 bytes are preserved
+        let s = NgxStr::from_bytes(b"/tmp/\xff.txt");
+        assert_eq!(s.as_os_str().as_encoded_bytes(), s.as_bytes());
+        assert_eq!(s.as_path().extension(), Some(OsStr::new("txt")));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_seed() {
+        use serde::de::value::{BytesDeserializer, Error, StrDeserializer};
+        use serde::de::DeserializeSeed;
+
+        use crate::allocator::Global;
+
+        let s = NgxStringSeed::new(Global)
+            .deserialize(StrDeserializer::<Error>::new("Hello"))
+            .expect("deserialize str");
+        assert_eq!(s, b"Hello");
+
+        let s = NgxStringSeed::new(Global)
+            .deserialize(BytesDeserializer::<Error>::new(b"\xffworld"))
+            .expect("deserialize bytes");
+        assert_eq!(s, b"\xffworld");
+    }
+
     #[test]
     fn test_lifetimes() {
         let a: &NgxStr = "Hello World!".into();
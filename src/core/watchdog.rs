@@ -0,0 +1,203 @@
+//! Detection of handlers blocking the event loop.
+//!
+//! A worker process runs all the requests on a single thread, so a handler that takes too long to
+//! complete delays every other connection served by the worker. The watchdog helps to locate such
+//! handlers during development and troubleshooting:
+//!
+//!  * [watch] measures the duration of a handler and logs a warning with the name of the handler if
+//!    it exceeds the threshold;
+//!  * a periodic timer measures the delay of its own expiration, detecting the blocking code that
+//!    is not covered by [watch]. The name of the last watched handler is logged as a hint.
+//!
+//! The watchdog is disabled until [start] is called, and [watch] costs little more than a check of
+//! a flag in that state. The watchdog only measures the main thread of the process: [start] and
+//! [stop] panic when called from another thread, and [watch] does nothing there.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use core::time::Duration;
+//! # use ngx::core::{watchdog, Status};
+//! # use ngx::http::Request;
+//! // in the module `init_process` handler
+//! watchdog::start(Duration::from_millis(100), ngx::log::ngx_cycle_log());
+//!
+//! fn handler(request: &mut Request) -> Status {
+//!     let _watch = watchdog::watch("ngx_http_example_module: content handler");
+//!     // ...
+//!     Status::NGX_OK
+//! }
+//! ```
+use core::cell::{Cell, UnsafeCell};
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use std::time::Instant;
+
+use nginx_sys::{ngx_add_timer, ngx_del_timer, ngx_event_t, ngx_log_t, ngx_msec_int_t, ngx_msec_t};
+
+use crate::core::{assert_main_thread, is_main_thread};
+use crate::ngx_log_warn;
+
+struct Watchdog {
+    /// Set while the watchdog is running; can be read from any thread.
+    running: AtomicBool,
+    event: UnsafeCell<ngx_event_t>,
+    threshold: Cell<Option<Duration>>,
+    /// The expected expiration time of the timer.
+    expires: Cell<Option<Instant>>,
+    /// The name of the last watched handler.
+    last: Cell<Option<&'static str>>,
+}
+
+// SAFETY: the fields other than `running` are only accessed from the main thread of a process,
+// which is checked by the public functions and by `Watch::drop`.
+unsafe impl Sync for Watchdog {}
+
+static WATCHDOG: Watchdog = Watchdog {
+    running: AtomicBool::new(false),
+    // SAFETY: an all-zero ngx_event_t is a valid inactive event
+    event: UnsafeCell::new(unsafe { mem::zeroed() }),
+    threshold: Cell::new(None),
+    expires: Cell::new(None),
+    last: Cell::new(None),
+};
+
+/// Starts the watchdog in the current worker process.
+///
+/// The warnings are logged when a handler or an iteration of the event loop takes longer than
+/// `threshold`. If the watchdog is already running, only the threshold is updated.
+///
+/// # Panics
+///
+/// Panics if called outside of the main thread of the process.
+pub fn start(threshold: Duration, log: NonNull<ngx_log_t>) {
+    assert_main_thread("watchdog");
+
+    static IDENT: [usize; 4] = [
+        0, 0, 0, 0x57444f47, // WDOG
+    ];
+
+    let threshold = threshold.max(Duration::from_millis(1));
+    WATCHDOG.threshold.set(Some(threshold));
+    WATCHDOG.running.store(true, Ordering::Relaxed);
+
+    let ev = WATCHDOG.event.get();
+    // SAFETY: the event is only accessed from the main thread
+    unsafe {
+        (*ev).log = log.as_ptr();
+
+        if (*ev).timer_set() != 0 {
+            return;
+        }
+
+        // The data is only used for `ngx_event_ident` and will not be mutated.
+        (*ev).data = ptr::addr_of!(IDENT).cast_mut().cast();
+        (*ev).handler = Some(watchdog_handler);
+        (*ev).set_cancelable(1);
+    }
+
+    schedule(threshold);
+}
+
+/// Stops the watchdog in the current worker process.
+///
+/// # Panics
+///
+/// Panics if called outside of the main thread of the process.
+pub fn stop() {
+    assert_main_thread("watchdog");
+
+    WATCHDOG.running.store(false, Ordering::Relaxed);
+    WATCHDOG.threshold.set(None);
+    WATCHDOG.expires.set(None);
+
+    let ev = WATCHDOG.event.get();
+    // SAFETY: the event is only accessed from the main thread
+    unsafe {
+        if (*ev).timer_set() != 0 {
+            ngx_del_timer(ev);
+        }
+    }
+}
+
+/// Returns `true` if the watchdog is running.
+pub fn is_running() -> bool {
+    WATCHDOG.running.load(Ordering::Relaxed)
+}
+
+/// Starts measuring the duration of a handler.
+///
+/// The measurement ends when the returned guard is dropped. `name` should identify both the module
+/// and the handler. Outside of the main thread of the process the guard does not measure anything.
+#[inline]
+pub fn watch(name: &'static str) -> Watch {
+    Watch {
+        name,
+        start: (is_running() && is_main_thread()).then(Instant::now),
+    }
+}
+
+/// A guard returned by [watch].
+#[derive(Debug)]
+#[must_use = "if unused the measurement will immediately end"]
+pub struct Watch {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        // the guard can be sent to and dropped on another thread
+        let Some(start) = self.start.filter(|_| is_main_thread()) else {
+            return;
+        };
+
+        let Some(threshold) = WATCHDOG.threshold.get() else {
+            return;
+        };
+
+        WATCHDOG.last.set(Some(self.name));
+
+        let elapsed = start.elapsed();
+        if elapsed > threshold {
+            ngx_log_warn!(
+                unsafe { (*WATCHDOG.event.get()).log },
+                "watchdog: \"{}\" blocked the event loop for {:?}",
+                self.name,
+                elapsed
+            );
+        }
+    }
+}
+
+fn schedule(interval: Duration) {
+    let msec = interval
+        .min(Duration::from_millis(ngx_msec_int_t::MAX as _))
+        .as_millis() as ngx_msec_t;
+    WATCHDOG.expires.set(Some(Instant::now() + interval));
+    unsafe { ngx_add_timer(WATCHDOG.event.get(), msec) };
+}
+
+unsafe extern "C" fn watchdog_handler(ev: *mut ngx_event_t) {
+    (*ev).set_timedout(0);
+
+    let Some(threshold) = WATCHDOG.threshold.get() else {
+        return;
+    };
+
+    if let Some(expires) = WATCHDOG.expires.get() {
+        let delay = expires.elapsed();
+
+        if delay > threshold {
+            ngx_log_warn!(
+                (*ev).log,
+                "watchdog: event loop was blocked for {:?}, last watched handler: {}",
+                delay,
+                WATCHDOG.last.get().unwrap_or("none")
+            );
+        }
+    }
+
+    schedule(threshold);
+}
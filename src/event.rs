@@ -0,0 +1,298 @@
+//! Event loop primitives.
+//!
+//! See <https://nginx.org/en/docs/dev/development_guide.html#events>.
+use core::cell::UnsafeCell;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::time::Duration;
+
+use nginx_sys::{
+    ngx_add_timer, ngx_current_msec, ngx_del_timer, ngx_event_handler_pt, ngx_event_t, ngx_log_t,
+    ngx_msec_int_t, ngx_msec_t,
+};
+
+#[cfg(feature = "alloc")]
+pub use self::timer::Timer;
+
+/// Maximum duration that can be achieved using [ngx_add_timer].
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+pub(crate) const NGX_TIMER_DURATION_MAX: Duration = Duration::from_millis(ngx_msec_int_t::MAX as _);
+
+/// Converts a duration to timer milliseconds, saturating at the maximum timer duration.
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+pub(crate) fn timer_msec(duration: Duration) -> ngx_msec_t {
+    duration.min(NGX_TIMER_DURATION_MAX).as_millis() as ngx_msec_t
+}
+
+/// Returns the cached time of the current event loop iteration, `ngx_current_msec`.
+///
+/// The value is based on a monotonic clock shared by all the processes and wraps around.
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+pub(crate) fn current_msec() -> ngx_msec_t {
+    // SAFETY: the value is only updated by the current process in the event loop.
+    unsafe { ngx_current_msec }
+}
+
+/// Returns the expiration time for an entry with the lifetime `ttl`, to be checked with
+/// [is_expired].
+///
+/// The lifetime is limited to approximately 24 days, so that the wrapping values can be compared.
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+pub(crate) fn expires_at(ttl: Duration) -> ngx_msec_t {
+    current_msec().wrapping_add(timer_msec(ttl))
+}
+
+/// Checks if the expiration time returned by [expires_at] has passed at `now`.
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+pub(crate) fn is_expired(expires: ngx_msec_t, now: ngx_msec_t) -> bool {
+    expires.wrapping_sub(now) as ngx_msec_int_t <= 0
+}
+
+/// An owned [`ngx_event_t`] used as a timer.
+///
+/// The event is registered in the timer tree by address, so it must not be moved while the timer
+/// is set. The timer is removed from the tree on drop.
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+#[repr(transparent)]
+pub(crate) struct TimerEvent(UnsafeCell<ngx_event_t>);
+
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+impl TimerEvent {
+    pub fn new(handler: ngx_event_handler_pt, log: NonNull<ngx_log_t>) -> Self {
+        static IDENT: [usize; 4] = [
+            0, 0, 0, 0x4153594e, // ASYN
+        ];
+
+        let mut ev: ngx_event_t = unsafe { mem::zeroed() };
+        // The data is only used for `ngx_event_ident` and will not be mutated.
+        ev.data = ptr::addr_of!(IDENT).cast_mut().cast();
+        ev.handler = handler;
+        ev.log = log.as_ptr();
+        ev.set_cancelable(1);
+
+        Self(UnsafeCell::new(ev))
+    }
+
+    pub fn as_ptr(&self) -> *mut ngx_event_t {
+        self.0.get()
+    }
+
+    pub fn log(&self) -> *mut ngx_log_t {
+        unsafe { (*self.as_ptr()).log }
+    }
+
+    /// Returns `true` if the timer is set.
+    pub fn is_set(&self) -> bool {
+        unsafe { (*self.as_ptr()).timer_set() != 0 }
+    }
+
+    /// Returns `true` if the timer has expired since the last [TimerEvent::reset].
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub fn is_timedout(&self) -> bool {
+        unsafe { (*self.as_ptr()).timedout() != 0 }
+    }
+
+    /// Clears the expiration flag.
+    pub fn reset(&self) {
+        unsafe { (*self.as_ptr()).set_timedout(0) }
+    }
+
+    /// Sets the timer, replacing the previous expiration time.
+    ///
+    /// # Safety
+    ///
+    /// The event must not be moved until the timer expires or is removed.
+    pub unsafe fn add(&self, msec: ngx_msec_t) {
+        ngx_add_timer(self.as_ptr(), msec)
+    }
+
+    /// Removes the timer, if set.
+    pub fn cancel(&self) {
+        if self.is_set() {
+            unsafe { ngx_del_timer(self.as_ptr()) }
+        }
+    }
+}
+
+impl Drop for TimerEvent {
+    fn drop(&mut self) {
+        self.cancel()
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod timer {
+    use core::cell::Cell;
+    use core::fmt;
+    use core::ptr::NonNull;
+    use core::time::Duration;
+
+    #[cfg(all(not(feature = "std"), feature = "alloc"))]
+    use alloc::{boxed::Box, rc::Rc};
+    #[cfg(feature = "std")]
+    use std::{boxed::Box, rc::Rc};
+
+    use nginx_sys::{ngx_event_t, ngx_log_t};
+
+    use super::{timer_msec, TimerEvent, NGX_TIMER_DURATION_MAX};
+    use crate::{ngx_container_of, ngx_log_debug};
+
+    type Callback = Box<dyn FnMut()>;
+
+    struct TimerInner {
+        event: TimerEvent,
+        callback: Cell<Option<Callback>>,
+        interval: Cell<Option<Duration>>,
+        /// The remaining delay after the current timer expiration.
+        remaining: Cell<Duration>,
+    }
+
+    impl TimerInner {
+        /// Sets the timer to expire after `delay`, in several steps if needed.
+        fn arm(&self, delay: Duration) {
+            let step = delay.min(NGX_TIMER_DURATION_MAX);
+            self.remaining.set(delay - step);
+            self.event.reset();
+            // SAFETY: the event is allocated in the Rc and will not be moved
+            unsafe { self.event.add(timer_msec(step)) };
+        }
+    }
+
+    /// A timer running a callback on the nginx event loop.
+    ///
+    /// The timer can be one-shot, started with [Timer::start], or periodic, started with
+    /// [Timer::start_periodic]. The callback is owned by the timer and is dropped along with it;
+    /// dropping the timer also removes the pending expiration, so no cleanup handlers are required.
+    ///
+    /// Timers are cancelable: a pending timer does not delay the graceful shutdown of a worker
+    /// process.
+    ///
+    /// The delays are not limited by the maximum duration of an nginx timer: a longer delay is
+    /// split into several timer expirations.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use core::time::Duration;
+    /// # use ngx::event::Timer;
+    /// # use ngx::ffi::ngx_cycle_t;
+    /// # use ngx::once::CycleLocal;
+    /// static HOUSEKEEPING: CycleLocal<Timer> = CycleLocal::new();
+    ///
+    /// // in the module `init_process` handler
+    /// # fn init_process(cycle: &mut ngx_cycle_t) {
+    /// let mut timer = Timer::new(ngx::log::ngx_cycle_log());
+    /// timer.start_periodic(Duration::from_secs(10), || {
+    ///     // housekeeping
+    /// });
+    /// // the timer is stopped when dropped, e.g. in `exit_process`
+    /// HOUSEKEEPING.set(cycle, timer);
+    /// # }
+    /// ```
+    pub struct Timer(Rc<TimerInner>);
+
+    impl Timer {
+        /// Creates an inactive timer with the specified logger for debug messages.
+        pub fn new(log: NonNull<ngx_log_t>) -> Self {
+            Self(Rc::new(TimerInner {
+                event: TimerEvent::new(Some(timer_handler), log),
+                callback: Cell::new(None),
+                interval: Cell::new(None),
+                remaining: Cell::new(Duration::ZERO),
+            }))
+        }
+
+        /// Runs `callback` once after `delay`.
+        ///
+        /// Replaces the callback and the schedule of the timer.
+        pub fn start(&mut self, delay: Duration, callback: impl FnMut() + 'static) {
+            self.0.interval.set(None);
+            self.0.callback.set(Some(Box::new(callback)));
+            self.rearm(delay);
+        }
+
+        /// Runs `callback` every `interval`, starting after the first interval.
+        ///
+        /// Replaces the callback and the schedule of the timer.
+        pub fn start_periodic(&mut self, interval: Duration, callback: impl FnMut() + 'static) {
+            self.0.interval.set(Some(interval));
+            self.0.callback.set(Some(Box::new(callback)));
+            self.rearm(interval);
+        }
+
+        /// Reschedules the timer to run the current callback after `delay`.
+        ///
+        /// Periodic timers continue with the original interval after the next expiration.
+        pub fn rearm(&mut self, delay: Duration) {
+            ngx_log_debug!(self.0.event.log(), "timer: arm for {delay:?}");
+            self.0.arm(delay);
+        }
+
+        /// Cancels the pending expiration. The callback is kept and can be rescheduled with
+        /// [Timer::rearm].
+        pub fn cancel(&mut self) {
+            self.0.event.cancel();
+        }
+
+        /// Returns `true` if the timer is scheduled to run.
+        pub fn is_active(&self) -> bool {
+            self.0.event.is_set()
+        }
+
+        /// Returns `true` if the timer runs periodically.
+        pub fn is_periodic(&self) -> bool {
+            self.0.interval.get().is_some()
+        }
+    }
+
+    impl Drop for Timer {
+        fn drop(&mut self) {
+            self.0.event.cancel();
+            // Break the ownership of the data captured by the callback, even if the callback is
+            // being executed at the moment.
+            self.0.callback.set(None);
+        }
+    }
+
+    impl fmt::Debug for Timer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Timer")
+                .field("active", &self.is_active())
+                .field("interval", &self.0.interval.get())
+                .finish_non_exhaustive()
+        }
+    }
+
+    unsafe extern "C" fn timer_handler(ev: *mut ngx_event_t) {
+        let inner = ngx_container_of!(ev, TimerInner, event).cast_const();
+
+        // Keep the timer alive until the callback returns, as the callback may drop the `Timer`.
+        Rc::increment_strong_count(inner);
+        let inner = Rc::from_raw(inner);
+
+        let remaining = inner.remaining.get();
+        if !remaining.is_zero() {
+            ngx_log_debug!(inner.event.log(), "timer: continue for {remaining:?}");
+            inner.arm(remaining);
+            return;
+        }
+
+        ngx_log_debug!(inner.event.log(), "timer: expired");
+
+        // Rearm before running the callback, so the callback can cancel or replace the schedule.
+        if let Some(interval) = inner.interval.get() {
+            inner.arm(interval);
+        }
+
+        let Some(mut callback) = inner.callback.take() else {
+            return;
+        };
+
+        callback();
+
+        // Restore the callback unless it was replaced or the timer was dropped.
+        if Rc::strong_count(&inner) > 1 {
+            let replaced = inner.callback.take();
+            inner.callback.set(replaced.or(Some(callback)));
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use core::error;
+use core::fmt;
+use core::ptr::NonNull;
+
+use crate::core::NgxStr;
+use crate::ffi::{
+    ngx_conf_open_file, ngx_conf_t, ngx_err_t, ngx_errno, ngx_open_file_t, ngx_str_t, write,
+};
+
+/// An error returned by the [LogFile] operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFileError {
+    /// The file could not be added to the cycle, e.g. because of an allocation failure.
+    Open,
+    /// An I/O error with the system error code.
+    Io(ngx_err_t),
+    /// Only the specified number of bytes was written.
+    Incomplete(usize),
+}
+
+impl error::Error for LogFileError {}
+
+impl fmt::Display for LogFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFileError::Open => f.write_str("failed to open log file"),
+            LogFileError::Io(err) => write!(f, "log file i/o error ({err})"),
+            LogFileError::Incomplete(n) => write!(f, "log entry incomplete, {n} bytes written"),
+        }
+    }
+}
+
+/// A log file opened with the cycle, as used by the [access_log] directive.
+///
+/// The files are opened by NGINX after the configuration is parsed and reopened on the `USR1`
+/// signal, so a module can implement a custom access log, e.g. with JSON entries. The values
+/// available to `log_format` are the variables, including the ones added with
+/// [Variable](crate::http::Variable), and the entries are usually written from a
+/// [Phase::Log](crate::http::Phase::Log) handler.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::{ngx_conf_t, ngx_str_t};
+/// # use ngx::http::{LogFile, Request};
+/// struct LocConfig {
+///     log: Option<LogFile>,
+/// }
+///
+/// // example_json_log /var/log/nginx/access.json;
+/// fn set_log(cf: &mut ngx_conf_t, conf: &mut LocConfig, path: &ngx_str_t) -> bool {
+///     conf.log = LogFile::open(cf, path).ok();
+///     conf.log.is_some()
+/// }
+///
+/// fn log_request(request: &Request, conf: &LocConfig) {
+///     if let Some(log) = conf.log {
+///         let entry = format!("uri={}\n", request.unparsed_uri());
+///         let _ = log.write(entry.as_bytes());
+///     }
+/// }
+/// ```
+///
+/// [access_log]: https://nginx.org/en/docs/http/ngx_http_log_module.html#access_log
+#[derive(Clone, Copy)]
+pub struct LogFile(NonNull<ngx_open_file_t>);
+
+impl LogFile {
+    /// Adds a log file to the cycle, or returns the file already opened with the same path.
+    ///
+    /// The path is relative to the prefix, and `/dev/stderr` is the standard error of the
+    /// process.
+    pub fn open(cf: &mut ngx_conf_t, path: &ngx_str_t) -> Result<Self, LogFileError> {
+        let mut name = *path;
+        // SAFETY: ngx_conf_open_file copies the name to the cycle pool.
+        let file = unsafe { ngx_conf_open_file(cf.cycle, &mut name) };
+        NonNull::new(file).map(Self).ok_or(LogFileError::Open)
+    }
+
+    /// Creates a log file from a pointer to [ngx_open_file_t].
+    ///
+    /// # Safety
+    ///
+    /// `file` is a valid pointer to a file in the cycle `open_files` list.
+    pub unsafe fn from_ptr(file: NonNull<ngx_open_file_t>) -> Self {
+        Self(file)
+    }
+
+    /// Returns a raw pointer to the underlying [ngx_open_file_t].
+    pub fn as_ptr(&self) -> *mut ngx_open_file_t {
+        self.0.as_ptr()
+    }
+
+    /// Returns the full path of the file.
+    pub fn name(&self) -> &NgxStr {
+        // SAFETY: the file lives as long as the cycle.
+        unsafe { NgxStr::from_ngx_str(self.0.as_ref().name) }
+    }
+
+    /// Writes an entry to the file.
+    ///
+    /// The entry is written with a single `write()` call to the file opened with `O_APPEND`, same
+    /// as with the `access_log` directive without the `buffer` parameter.
+    pub fn write(&self, entry: &[u8]) -> Result<(), LogFileError> {
+        // SAFETY: the descriptor is updated by NGINX when the file is reopened.
+        let fd = unsafe { self.0.as_ref().fd };
+        let n = unsafe { write(fd, entry.as_ptr().cast(), entry.len()) };
+
+        if n < 0 {
+            return Err(LogFileError::Io(ngx_errno()));
+        }
+
+        if n as usize != entry.len() {
+            return Err(LogFileError::Incomplete(n as usize));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for LogFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LogFile").field(&self.name()).finish()
+    }
+}
@@ -0,0 +1,531 @@
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+
+use crate::core::{ChainBuilder, MemoryBuffer, Status};
+use crate::ffi::*;
+use crate::http::{HTTPStatus, Method, Request};
+
+/// Embeds files from a directory of the crate into the module binary.
+///
+/// The directory is relative to the crate root (`CARGO_MANIFEST_DIR`) and the files are listed
+/// explicitly. A file followed by `gzip` is also sent compressed to the clients that accept gzip
+/// encoding. The result is an [Assets] table and must be assigned to a `static`.
+///
+/// The files are compressed on first use in each process with the zlib library linked to NGINX,
+/// and sent as is if NGINX is built without the gzip module or if the compression does not reduce
+/// the size.
+///
+/// Example:
+/// ```rust,no_run
+/// use ngx::http::Assets;
+///
+/// static ASSETS: Assets = ngx::embed_assets!("tests/assets", [
+///     "index.html" gzip,
+///     "app.js" gzip,
+///     "robots.txt",
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! embed_assets {
+    ($dir:literal, [ $( $file:literal $($encoding:ident)? ),* $(,)? ]) => {
+        $crate::http::Assets::new(&[
+            $(
+                $crate::http::Asset::__new(
+                    $file,
+                    include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $file)),
+                    $crate::embed_assets!(@gzip $($encoding)?),
+                    {
+                        static CACHE: $crate::http::AssetCache = $crate::http::AssetCache::new();
+                        &CACHE
+                    },
+                )
+            ),*
+        ])
+    };
+    (@gzip) => {
+        false
+    };
+    (@gzip gzip) => {
+        true
+    };
+    (@gzip $encoding:ident) => {
+        compile_error!(concat!("unsupported asset encoding: ", stringify!($encoding)))
+    };
+}
+
+/// Values of an [Asset] calculated on first use.
+#[doc(hidden)]
+pub struct AssetCache {
+    etag: AtomicU64,
+    /// The compressed data, empty if the data cannot be compressed.
+    gzip: AtomicPtr<&'static [u8]>,
+}
+
+impl AssetCache {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            etag: AtomicU64::new(0),
+            gzip: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+/// A file embedded with [embed_assets](crate::embed_assets).
+pub struct Asset {
+    path: &'static str,
+    content_type: &'static str,
+    data: &'static [u8],
+    gzip: bool,
+    cache: &'static AssetCache,
+}
+
+impl Asset {
+    #[doc(hidden)]
+    pub const fn __new(
+        path: &'static str,
+        data: &'static [u8],
+        gzip: bool,
+        cache: &'static AssetCache,
+    ) -> Self {
+        Self {
+            path,
+            content_type: content_type(path.as_bytes()),
+            data,
+            gzip,
+            cache,
+        }
+    }
+
+    /// Returns the path of the file relative to the assets directory.
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    /// Returns the MIME type of the file, determined by the extension.
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    /// Returns the file contents.
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+
+    /// Returns the gzip-compressed file contents.
+    ///
+    /// The contents are compressed on the first call. Returns `None` if the compression is not
+    /// enabled for the file, not supported or does not reduce the size.
+    pub fn gzip(&self) -> Option<&'static [u8]> {
+        if !self.gzip {
+            return None;
+        }
+
+        encoding::cached(&self.cache.gzip, self.data)
+    }
+
+    /// Returns the hash of the file contents used as an entity tag.
+    ///
+    /// The hash is calculated on the first use. The entity tag of the compressed contents has a
+    /// `-gzip` suffix.
+    pub fn etag(&self) -> u64 {
+        let etag = self.cache.etag.load(Ordering::Relaxed);
+        if etag != 0 {
+            return etag;
+        }
+
+        // FNV-1a, with the zero value reserved for "not calculated"
+        let etag = self
+            .data
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |h, &b| {
+                (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+            .max(1);
+        self.cache.etag.store(etag, Ordering::Relaxed);
+        etag
+    }
+
+    /// Sends the file as a response to the request.
+    ///
+    /// Sets the `Content-Type`, `Content-Length` and `ETag` response headers. Conditional
+    /// requests with `If-None-Match` or `If-Match` are handled by the NGINX not modified filter.
+    /// The compressed copy is sent if available and allowed by `ngx_http_gzip_ok()`, and the
+    /// response varies by `Accept-Encoding` in this case.
+    ///
+    /// Returns the status for the content handler.
+    pub fn send(&self, request: &mut Request) -> Status {
+        let gzip = match self.gzip() {
+            Some(gzip) => {
+                let Ok(vary) = request.headers_out_mut().add("Vary", "Accept-Encoding") else {
+                    return Status::NGX_ERROR;
+                };
+                vary.hash = 1;
+                encoding::is_accepted(request).then_some(gzip)
+            }
+            None => None,
+        };
+
+        let mut etag = [0u8; 23];
+        etag[0] = b'"';
+        for (i, x) in etag[1..17].iter_mut().enumerate() {
+            *x = b"0123456789abcdef"[(self.etag() >> (60 - i * 4)) as usize & 0xf];
+        }
+        let len = if gzip.is_some() {
+            etag[17..23].copy_from_slice(b"-gzip\"");
+            23
+        } else {
+            etag[17] = b'"';
+            18
+        };
+
+        let Ok(etag) = request.headers_out_mut().add("ETag", &etag[..len]) else {
+            return Status::NGX_ERROR;
+        };
+        etag.hash = 1;
+        let etag: *mut ngx_table_elt_t = etag;
+
+        let data = match gzip {
+            Some(gzip) => {
+                let Ok(encoding) = request.headers_out_mut().add("Content-Encoding", "gzip") else {
+                    return Status::NGX_ERROR;
+                };
+                encoding.hash = 1;
+                let encoding: *mut ngx_table_elt_t = encoding;
+                request.as_mut().headers_out.content_encoding = encoding;
+                gzip
+            }
+            None => self.data,
+        };
+
+        let r = request.as_mut();
+        r.headers_out.status = HTTPStatus::OK.into();
+        r.headers_out.content_length_n = data.len() as off_t;
+        r.headers_out.content_type = ngx_str_t {
+            len: self.content_type.len(),
+            data: self.content_type.as_ptr().cast_mut(),
+        };
+        r.headers_out.content_type_len = self.content_type.len();
+        r.headers_out.etag = etag;
+        r.set_allow_ranges(1);
+
+        let rc = request.send_header();
+        if rc == Status::NGX_ERROR || rc.0 > NGX_OK as ngx_int_t || request.header_only() {
+            return rc;
+        }
+
+        let mut pool = request.pool();
+        let buf = pool.calloc_type::<ngx_buf_t>();
+        if buf.is_null() {
+            return Status::NGX_ERROR;
+        }
+        unsafe {
+            (*buf).pos = data.as_ptr().cast_mut();
+            (*buf).last = (*buf).pos.add(data.len());
+            (*buf).set_memory(1);
+        }
+
+        let mut chain = ChainBuilder::new(pool);
+        if chain.push(MemoryBuffer::from_ngx_buf(buf)).is_err() {
+            return Status::NGX_ERROR;
+        }
+        chain.last_buf(request.is_main()).last_in_chain(true);
+
+        let Ok(chain) = chain.finish() else {
+            return Status::NGX_ERROR;
+        };
+        request.output_filter(unsafe { &mut *chain })
+    }
+}
+
+impl fmt::Debug for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Asset")
+            .field("path", &self.path)
+            .field("content_type", &self.content_type)
+            .field("len", &self.data.len())
+            .field("gzip", &self.gzip)
+            .finish()
+    }
+}
+
+/// A table of files embedded with [embed_assets](crate::embed_assets).
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{Assets, Request};
+/// # static ASSETS: Assets = ngx::embed_assets!("tests/assets", ["index.html"]);
+/// fn content_handler(request: &mut Request) -> Status {
+///     // the part of the URI after the location prefix
+///     let path = request.path().as_bytes().strip_prefix(b"/dashboard/").unwrap_or_default();
+///     ASSETS.serve(request, path)
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Assets(&'static [Asset]);
+
+impl Assets {
+    /// Creates a table from the list of assets.
+    pub const fn new(assets: &'static [Asset]) -> Self {
+        Self(assets)
+    }
+
+    /// Returns the asset with the specified path.
+    ///
+    /// The leading slash is ignored, and a path ending with a slash refers to the `index.html`
+    /// file in the directory.
+    pub fn get(&self, path: impl AsRef<[u8]>) -> Option<&Asset> {
+        let path = path.as_ref();
+        let path = path.strip_prefix(b"/").unwrap_or(path);
+        let index = path.is_empty() || path.ends_with(b"/");
+
+        self.0.iter().find(|x| {
+            let name = x.path.as_bytes();
+            if index {
+                name.strip_prefix(path) == Some(b"index.html")
+            } else {
+                name == path
+            }
+        })
+    }
+
+    /// Returns an iterator over the assets.
+    pub fn iter(&self) -> impl Iterator<Item = &Asset> {
+        self.0.iter()
+    }
+
+    /// Sends the asset with the specified path as a response to a `GET` or `HEAD` request.
+    ///
+    /// Returns `NGX_HTTP_NOT_FOUND` for unknown paths and `NGX_HTTP_NOT_ALLOWED` for other
+    /// request methods. See [Asset::send].
+    pub fn serve(&self, request: &mut Request, path: impl AsRef<[u8]>) -> Status {
+        let method = request.method();
+        if method != Method::GET && method != Method::HEAD {
+            return HTTPStatus::NOT_ALLOWED.into();
+        }
+
+        let Some(asset) = self.get(path) else {
+            return HTTPStatus::NOT_FOUND.into();
+        };
+
+        let rc = request.discard_request_body();
+        if rc != Status::NGX_OK {
+            return rc;
+        }
+
+        asset.send(request)
+    }
+}
+
+impl<'a> IntoIterator for &'a Assets {
+    type Item = &'a Asset;
+    type IntoIter = core::slice::Iter<'a, Asset>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(all(feature = "alloc", ngx_feature = "http_gzip"))]
+mod encoding {
+    use core::ffi::c_int;
+    use core::mem;
+    use core::ptr;
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    #[cfg(all(not(feature = "std"), feature = "alloc"))]
+    use alloc::{boxed::Box, vec::Vec};
+    #[cfg(feature = "std")]
+    use std::{boxed::Box, vec::Vec};
+
+    use crate::ffi::{
+        deflate, deflateBound, deflateEnd, deflateInit2_, ngx_http_gzip_ok, ngx_int_t, uInt, uLong,
+        z_stream, MAX_MEM_LEVEL, MAX_WBITS, NGX_OK, ZLIB_VERSION, Z_BEST_COMPRESSION,
+        Z_DEFAULT_STRATEGY, Z_DEFLATED, Z_FINISH, Z_OK, Z_STREAM_END,
+    };
+    use crate::http::Request;
+
+    /// Returns `true` if the client accepts the gzip encoding.
+    pub fn is_accepted(request: &mut Request) -> bool {
+        unsafe { ngx_http_gzip_ok(request.as_mut()) == NGX_OK as ngx_int_t }
+    }
+
+    /// Returns the compressed `data`, compressing it on the first call.
+    pub fn cached(slot: &AtomicPtr<&'static [u8]>, data: &[u8]) -> Option<&'static [u8]> {
+        let mut current = slot.load(Ordering::Acquire);
+        if current.is_null() {
+            // an empty value marks the data that cannot be compressed
+            let value: &'static [u8] = match compress(data) {
+                Some(x) => Box::leak(x),
+                None => &[],
+            };
+            let value = Box::into_raw(Box::new(value));
+
+            current = match slot.compare_exchange(
+                ptr::null_mut(),
+                value,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => value,
+                Err(current) => {
+                    // SAFETY: the value was not published and is owned by this call.
+                    let value = unsafe { Box::from_raw(value) };
+                    if !value.is_empty() {
+                        drop(unsafe { Box::from_raw(ptr::from_ref(*value).cast_mut()) });
+                    }
+                    current
+                }
+            };
+        }
+
+        // SAFETY: a published value is never released.
+        let value: &'static [u8] = unsafe { *current };
+        (!value.is_empty()).then_some(value)
+    }
+
+    /// Compresses `data` with gzip. Returns `None` if the compressed data is not smaller.
+    fn compress(data: &[u8]) -> Option<Box<[u8]>> {
+        // the embedded files are expected to be small
+        if uInt::try_from(data.len()).is_err() {
+            return None;
+        }
+
+        // SAFETY: a zeroed stream with null allocation functions selects the zlib defaults.
+        let mut stream: z_stream = unsafe { mem::zeroed() };
+
+        // 16 selects the gzip header and trailer
+        let rc = unsafe {
+            deflateInit2_(
+                &mut stream,
+                Z_BEST_COMPRESSION as c_int,
+                Z_DEFLATED as c_int,
+                MAX_WBITS as c_int + 16,
+                MAX_MEM_LEVEL as c_int,
+                Z_DEFAULT_STRATEGY as c_int,
+                ZLIB_VERSION.as_ptr(),
+                mem::size_of::<z_stream>() as c_int,
+            )
+        };
+        if rc != Z_OK as c_int {
+            return None;
+        }
+
+        let bound = unsafe { deflateBound(&mut stream, data.len() as uLong) } as usize;
+        let mut out = Vec::with_capacity(bound);
+
+        stream.next_in = data.as_ptr().cast_mut();
+        stream.avail_in = data.len() as uInt;
+        stream.next_out = out.as_mut_ptr();
+        stream.avail_out = uInt::try_from(bound).unwrap_or(uInt::MAX);
+
+        let rc = unsafe { deflate(&mut stream, Z_FINISH as c_int) };
+        unsafe { deflateEnd(&mut stream) };
+
+        if rc != Z_STREAM_END as c_int || stream.total_out as usize >= data.len() {
+            return None;
+        }
+
+        // SAFETY: zlib has written total_out bytes to the buffer.
+        unsafe { out.set_len(stream.total_out as usize) };
+        Some(out.into_boxed_slice())
+    }
+}
+
+#[cfg(not(all(feature = "alloc", ngx_feature = "http_gzip")))]
+mod encoding {
+    use core::sync::atomic::AtomicPtr;
+
+    use crate::http::Request;
+
+    pub fn is_accepted(_request: &mut Request) -> bool {
+        false
+    }
+
+    pub fn cached(_slot: &AtomicPtr<&'static [u8]>, _data: &[u8]) -> Option<&'static [u8]> {
+        None
+    }
+}
+
+/// Returns the MIME type for the file extension.
+const fn content_type(path: &[u8]) -> &'static str {
+    const TYPES: &[(&str, &str)] = &[
+        (".html", "text/html"),
+        (".htm", "text/html"),
+        (".css", "text/css"),
+        (".txt", "text/plain"),
+        (".xml", "text/xml"),
+        (".js", "application/javascript"),
+        (".mjs", "application/javascript"),
+        (".json", "application/json"),
+        (".map", "application/json"),
+        (".wasm", "application/wasm"),
+        (".svg", "image/svg+xml"),
+        (".png", "image/png"),
+        (".jpg", "image/jpeg"),
+        (".jpeg", "image/jpeg"),
+        (".gif", "image/gif"),
+        (".webp", "image/webp"),
+        (".ico", "image/x-icon"),
+        (".woff", "font/woff"),
+        (".woff2", "font/woff2"),
+    ];
+
+    let mut i = 0;
+    while i < TYPES.len() {
+        if ends_with_ignore_case(path, TYPES[i].0.as_bytes()) {
+            return TYPES[i].1;
+        }
+        i += 1;
+    }
+
+    "application/octet-stream"
+}
+
+const fn ends_with_ignore_case(s: &[u8], suffix: &[u8]) -> bool {
+    if s.len() < suffix.len() {
+        return false;
+    }
+
+    let off = s.len() - suffix.len();
+    let mut i = 0;
+    while i < suffix.len() {
+        if s[off + i].to_ascii_lowercase() != suffix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CACHE: AssetCache = AssetCache::new();
+    static ASSETS: Assets = Assets::new(&[
+        Asset::__new("index.html", b"<html></html>", false, &CACHE),
+        Asset::__new("js/app.JS", b"", false, &CACHE),
+        Asset::__new("js/index.html", b"", false, &CACHE),
+        Asset::__new("blob", b"", false, &CACHE),
+    ]);
+
+    #[test]
+    fn test_assets_get() {
+        assert_eq!(ASSETS.get("/").map(Asset::path), Some("index.html"));
+        assert_eq!(ASSETS.get("").map(Asset::path), Some("index.html"));
+        assert_eq!(ASSETS.get("/js/").map(Asset::path), Some("js/index.html"));
+        assert_eq!(ASSETS.get("js/app.JS").map(Asset::path), Some("js/app.JS"));
+        assert!(ASSETS.get("/index").is_none());
+        assert!(ASSETS.get("/css/").is_none());
+
+        assert!(ASSETS.iter().map(Asset::content_type).eq([
+            "text/html",
+            "application/javascript",
+            "text/html",
+            "application/octet-stream"
+        ]));
+    }
+}
@@ -0,0 +1,260 @@
+use core::error;
+use core::fmt;
+use core::task::{self, Poll};
+use std::io::{self, Read};
+
+use crate::core::ChainReader;
+use crate::http::{Request, RequestBody};
+
+/// An error returned by [BodyReader::new].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyReaderError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The `Content-Encoding` of the body is not supported.
+    ///
+    /// The request is usually rejected with the `415 Unsupported Media Type` status.
+    UnsupportedEncoding,
+}
+
+impl error::Error for BodyReaderError {}
+
+impl fmt::Display for BodyReaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyReaderError::Alloc => f.write_str("body reader allocation failed"),
+            BodyReaderError::UnsupportedEncoding => f.write_str("unsupported content encoding"),
+        }
+    }
+}
+
+/// A reader over the decoded payload of the client request body.
+///
+/// The `Transfer-Encoding: chunked` framing is removed by NGINX when reading the body, so the
+/// reader only has to handle the `Content-Encoding`. The `gzip` encoding is decoded if NGINX is
+/// built with the gzip module, and the body is read as is for the `identity` encoding or if the
+/// header is missing.
+///
+/// Besides [io::Read], the reader provides [BodyReader::poll_read] for the code written against
+/// the asynchronous I/O traits. The body is already in memory or in a temporary file by the time
+/// the reader is created, so the reads never return [Poll::Pending].
+///
+/// Example:
+/// ```rust,no_run
+/// # use std::io::Read;
+/// # use ngx::http::{BodyReader, HTTPStatus, Request};
+/// # fn handler(request: &mut Request) -> Result<Vec<u8>, HTTPStatus> {
+/// let Some(body) = request.request_body() else {
+///     return Ok(Vec::new());
+/// };
+///
+/// let mut payload = Vec::new();
+/// BodyReader::new(request, body)
+///     .map_err(|_| HTTPStatus::UNSUPPORTED_MEDIA_TYPE)?
+///     .limit(1024 * 1024)
+///     .read_to_end(&mut payload)
+///     .map_err(|_| HTTPStatus::BAD_REQUEST)?;
+/// # Ok(payload)
+/// # }
+/// ```
+pub struct BodyReader<'a> {
+    decoder: Decoder<'a>,
+    /// The remaining number of decoded bytes allowed.
+    limit: Option<u64>,
+}
+
+enum Decoder<'a> {
+    Identity(ChainReader<'a>),
+    #[cfg(ngx_feature = "http_gzip")]
+    Gzip(gzip::GzipDecoder<'a>),
+}
+
+impl<'a> BodyReader<'a> {
+    /// Creates a reader decoding the body according to the request `Content-Encoding`.
+    pub fn new(request: &Request, body: RequestBody<'a>) -> Result<Self, BodyReaderError> {
+        let encoding = request
+            .headers_in()
+            .get("Content-Encoding")
+            .map(|x| x.as_bytes().trim_ascii())
+            .unwrap_or_default();
+
+        let decoder = if encoding.is_empty() || encoding.eq_ignore_ascii_case(b"identity") {
+            Decoder::Identity(body.reader())
+        } else if encoding.eq_ignore_ascii_case(b"gzip") || encoding.eq_ignore_ascii_case(b"x-gzip")
+        {
+            Self::gzip(body)?
+        } else {
+            return Err(BodyReaderError::UnsupportedEncoding);
+        };
+
+        Ok(Self {
+            decoder,
+            limit: None,
+        })
+    }
+
+    /// Creates a reader returning the body as is.
+    pub fn identity(body: RequestBody<'a>) -> Self {
+        Self {
+            decoder: Decoder::Identity(body.reader()),
+            limit: None,
+        }
+    }
+
+    #[cfg(ngx_feature = "http_gzip")]
+    fn gzip(body: RequestBody<'a>) -> Result<Decoder<'a>, BodyReaderError> {
+        gzip::GzipDecoder::new(body.reader()).map(Decoder::Gzip)
+    }
+
+    #[cfg(not(ngx_feature = "http_gzip"))]
+    fn gzip(_body: RequestBody<'a>) -> Result<Decoder<'a>, BodyReaderError> {
+        Err(BodyReaderError::UnsupportedEncoding)
+    }
+
+    /// Limits the size of the decoded payload.
+    ///
+    /// A read exceeding the limit fails with [io::ErrorKind::InvalidData], protecting the handler
+    /// from the compressed bodies expanding to an excessive size.
+    pub fn limit(mut self, max: u64) -> Self {
+        self.limit = Some(max);
+        self
+    }
+
+    /// Returns `true` if the body is decoded.
+    pub fn is_decoded(&self) -> bool {
+        !matches!(self.decoder, Decoder::Identity(_))
+    }
+
+    /// Attempts to read the decoded payload into `buf`, in the manner of `AsyncRead::poll_read`.
+    ///
+    /// Always returns [Poll::Ready].
+    pub fn poll_read(
+        &mut self,
+        _cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.read(buf))
+    }
+}
+
+impl io::Read for BodyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match self.decoder {
+            Decoder::Identity(ref mut r) => r.read(buf)?,
+            #[cfg(ngx_feature = "http_gzip")]
+            Decoder::Gzip(ref mut r) => r.read(buf)?,
+        };
+
+        if let Some(ref mut limit) = self.limit {
+            *limit = limit
+                .checked_sub(n as u64)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "body too large"))?;
+        }
+
+        Ok(n)
+    }
+}
+
+impl fmt::Debug for BodyReader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyReader")
+            .field("decoded", &self.is_decoded())
+            .field("limit", &self.limit)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(ngx_feature = "http_gzip")]
+mod gzip {
+    use core::ffi::c_int;
+    use core::mem;
+    use std::boxed::Box;
+    use std::io::{self, BufRead};
+
+    use super::BodyReaderError;
+    use crate::core::ChainReader;
+    use crate::ffi::{
+        inflate, inflateEnd, inflateInit2_, z_stream, MAX_WBITS, ZLIB_VERSION, Z_BUF_ERROR,
+        Z_NO_FLUSH, Z_OK, Z_STREAM_END,
+    };
+
+    /// A gzip decoder using the zlib library linked to NGINX.
+    pub(super) struct GzipDecoder<'a> {
+        input: ChainReader<'a>,
+        // zlib keeps a pointer to the stream, so the stream must not move.
+        stream: Box<z_stream>,
+        done: bool,
+    }
+
+    impl<'a> GzipDecoder<'a> {
+        pub fn new(input: ChainReader<'a>) -> Result<Self, BodyReaderError> {
+            // SAFETY: a zeroed stream with null allocation functions selects the zlib defaults.
+            let mut stream: Box<z_stream> = Box::new(unsafe { mem::zeroed() });
+
+            // 16 selects the gzip header and trailer
+            let rc = unsafe {
+                inflateInit2_(
+                    &mut *stream,
+                    MAX_WBITS as c_int + 16,
+                    ZLIB_VERSION.as_ptr(),
+                    mem::size_of::<z_stream>() as c_int,
+                )
+            };
+            if rc != Z_OK as c_int {
+                return Err(BodyReaderError::Alloc);
+            }
+
+            Ok(Self {
+                input,
+                stream,
+                done: false,
+            })
+        }
+
+        pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while !self.done && !buf.is_empty() {
+                let input = self.input.fill_buf()?;
+                let eof = input.is_empty();
+
+                self.stream.next_in = input.as_ptr().cast_mut();
+                self.stream.avail_in = input.len().try_into().unwrap_or(u32::MAX) as _;
+                self.stream.next_out = buf.as_mut_ptr();
+                self.stream.avail_out = buf.len().try_into().unwrap_or(u32::MAX) as _;
+
+                let rc = unsafe { inflate(&mut *self.stream, Z_NO_FLUSH as c_int) };
+
+                let consumed = input.len() - self.stream.avail_in as usize;
+                let produced = buf.len() - self.stream.avail_out as usize;
+                self.input.consume(consumed);
+
+                match rc {
+                    // the data after the end of the stream is ignored
+                    rc if rc == Z_STREAM_END as c_int => self.done = true,
+                    rc if rc == Z_OK as c_int => {}
+                    rc if rc == Z_BUF_ERROR as c_int && eof => {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    rc if rc == Z_BUF_ERROR as c_int => {}
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "invalid gzip data",
+                        ));
+                    }
+                }
+
+                if produced > 0 {
+                    return Ok(produced);
+                }
+            }
+
+            Ok(0)
+        }
+    }
+
+    impl Drop for GzipDecoder<'_> {
+        fn drop(&mut self) {
+            unsafe { inflateEnd(&mut *self.stream) };
+        }
+    }
+}
@@ -0,0 +1,1118 @@
+//! A minimal HTTP/1.1 client running on the NGINX event loop.
+//!
+//! The client is intended for modules calling auxiliary services, such as authorization or
+//! metadata endpoints, from an async task. Connections are established with [PeerConnection]
+//! and kept in a pool of the client between the requests if the server allows it.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use ngx::http::client::{Client, ClientError, ClientRequest};
+//! # async fn example() -> Result<(), ClientError> {
+//! let client = Client::new();
+//! let request = ClientRequest::get("/auth?token=abc").host("auth.internal");
+//!
+//! let log = ngx::log::ngx_cycle_log();
+//! let response = client.send("127.0.0.1:8080".parse().unwrap(), &request, log).await?;
+//!
+//! if response.status() == 200 {
+//!     let user = response.header("X-User");
+//!     // ...
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use core::cell::RefCell;
+use core::error;
+use core::fmt::{self, Write};
+use core::mem;
+use core::net::SocketAddr;
+use core::ops::Range;
+use core::ptr::NonNull;
+use core::time::Duration;
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+
+use crate::allocator::AllocError;
+use crate::async_::{sleep, spawn, PeerConnection, PeerConnectionError, Task};
+use crate::ffi::{ngx_current_msec, ngx_log_t, ngx_msec_t};
+use crate::http::header;
+use crate::log::ngx_cycle_log;
+use crate::ngx_log_debug;
+
+/// Size of the buffer used to read the response.
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// An error returned by the [Client].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientError {
+    /// Memory allocation failed.
+    Alloc,
+    /// Failed to connect to the server. The reason has already been logged.
+    Connect,
+    /// The operation timed out.
+    TimedOut,
+    /// An I/O error occurred. The reason has already been logged.
+    Io,
+    /// The server closed the connection before sending a complete response.
+    Closed,
+    /// The server sent a malformed response.
+    InvalidResponse,
+    /// The response exceeds [Client::max_response_size].
+    TooLarge,
+    /// A request header name or value is not valid, see [header::validate].
+    InvalidHeader,
+    /// The request method or target contains characters not allowed in the request line.
+    InvalidRequest,
+}
+
+impl error::Error for ClientError {}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Alloc => f.write_str("http client allocation failed"),
+            ClientError::Connect => f.write_str("http client failed to connect"),
+            ClientError::TimedOut => f.write_str("http client timed out"),
+            ClientError::Io => f.write_str("http client i/o error"),
+            ClientError::Closed => f.write_str("server prematurely closed connection"),
+            ClientError::InvalidResponse => f.write_str("server sent invalid response"),
+            ClientError::TooLarge => f.write_str("server sent too large response"),
+            ClientError::InvalidHeader => f.write_str("invalid request header"),
+            ClientError::InvalidRequest => f.write_str("invalid request line"),
+        }
+    }
+}
+
+impl From<AllocError> for ClientError {
+    fn from(_: AllocError) -> Self {
+        ClientError::Alloc
+    }
+}
+
+impl From<PeerConnectionError> for ClientError {
+    fn from(err: PeerConnectionError) -> Self {
+        match err {
+            PeerConnectionError::Connect => ClientError::Connect,
+            PeerConnectionError::TimedOut => ClientError::TimedOut,
+            PeerConnectionError::Io => ClientError::Io,
+        }
+    }
+}
+
+/// An HTTP/1.1 client with a pool of idle connections.
+///
+/// The client is cheap to clone. The idle connections are shared by the client and its clones,
+/// and are reused for the requests to the same address; a client kept for the lifetime of a worker
+/// process, e.g. in a [CycleLocal](crate::once::CycleLocal), shares them between the requests.
+/// The idle connections are closed once the keepalive timeout expires, or when the last clone is
+/// dropped.
+///
+/// The client must only be used from the main thread of a process.
+#[derive(Clone, Debug)]
+pub struct Client {
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    keepalive: bool,
+    keepalive_timeout: Duration,
+    keepalive_connections: usize,
+    max_response_size: usize,
+    idle: Rc<IdleConnections>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// Creates a client with the default settings.
+    pub fn new() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(60),
+            read_timeout: Duration::from_secs(60),
+            write_timeout: Duration::from_secs(60),
+            keepalive: true,
+            keepalive_timeout: Duration::from_secs(60),
+            keepalive_connections: 8,
+            max_response_size: 1024 * 1024,
+            idle: Rc::default(),
+        }
+    }
+
+    /// Sets the timeout for establishing a connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout between two successive read operations.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout between two successive write operations.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Enables or disables reusing the connections.
+    pub fn keepalive(mut self, keepalive: bool) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Sets the time an idle connection is kept in the pool.
+    pub fn keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of idle connections to the same address kept in the pool.
+    pub fn keepalive_connections(mut self, n: usize) -> Self {
+        self.keepalive_connections = n;
+        self
+    }
+
+    /// Sets the maximum size of the response header and body.
+    pub fn max_response_size(mut self, size: usize) -> Self {
+        self.max_response_size = size;
+        self
+    }
+
+    /// Sends the request to `addr` and reads the response.
+    ///
+    /// An idle connection from the pool is used if available. If the pooled connection turns out
+    /// to be closed by the server before any part of the response is received, an idempotent
+    /// request is retried once with a new connection.
+    pub async fn send(
+        &self,
+        addr: SocketAddr,
+        request: &ClientRequest<'_>,
+        log: NonNull<ngx_log_t>,
+    ) -> Result<ClientResponse, ClientError> {
+        let head = request.encode(addr, self.keepalive)?;
+        let mut retry = request.is_idempotent();
+
+        loop {
+            let pooled = if self.keepalive {
+                self.idle.take(addr)
+            } else {
+                None
+            };
+            let reused = pooled.is_some();
+
+            let mut conn = match pooled {
+                Some(conn) => conn,
+                None => PeerConnection::connect_timeout(addr, self.connect_timeout, log).await?,
+            };
+
+            conn.set_log(log);
+            conn.set_read_timeout(Some(self.read_timeout));
+            conn.set_write_timeout(Some(self.write_timeout));
+
+            ngx_log_debug!(
+                log.as_ptr(),
+                "http client: {} {} to {} reused:{}",
+                request.method,
+                request.target,
+                addr,
+                reused
+            );
+
+            match self.exchange(&mut conn, &head, request).await {
+                Ok((response, reusable)) => {
+                    if reusable && self.keepalive {
+                        IdleConnections::put(
+                            &self.idle,
+                            addr,
+                            conn,
+                            self.keepalive_timeout,
+                            self.keepalive_connections,
+                        );
+                    }
+                    return Ok(response);
+                }
+                Err(ExchangeError {
+                    received: false, ..
+                }) if reused && retry => {
+                    ngx_log_debug!(log.as_ptr(), "http client: stale connection to {}", addr);
+                    retry = false;
+                }
+                Err(err) => return Err(err.error),
+            }
+        }
+    }
+
+    /// Writes the request to the connection and reads the response.
+    ///
+    /// Returns the response and whether the connection can be reused.
+    async fn exchange(
+        &self,
+        conn: &mut PeerConnection,
+        head: &[u8],
+        request: &ClientRequest<'_>,
+    ) -> Result<(ClientResponse, bool), ExchangeError> {
+        let fail = |received| move |error: ClientError| ExchangeError { error, received };
+
+        conn.write_all(head)
+            .await
+            .map_err(|e| fail(false)(e.into()))?;
+        conn.write_all(request.body)
+            .await
+            .map_err(|e| fail(false)(e.into()))?;
+
+        let mut buf = Vec::new();
+        let mut tmp = [0u8; READ_BUFFER_SIZE];
+        let mut parser = HeadParser::default();
+
+        let head = loop {
+            match parser.parse(&buf).map_err(fail(true))? {
+                // skip the interim responses
+                Some(head) if (100..200).contains(&head.status) => {
+                    buf.drain(..head.len);
+                    continue;
+                }
+                Some(head) => break head,
+                None => {}
+            }
+
+            if buf.len() >= self.max_response_size {
+                return Err(fail(true)(ClientError::TooLarge));
+            }
+
+            let n = conn
+                .read(&mut tmp)
+                .await
+                .map_err(|e| fail(!buf.is_empty())(e.into()))?;
+            if n == 0 {
+                return Err(fail(!buf.is_empty())(ClientError::Closed));
+            }
+
+            extend(&mut buf, &tmp[..n]).map_err(fail(true))?;
+        };
+
+        let fail = fail(true);
+
+        let framing = if request.method == "HEAD" || head.status == 204 || head.status == 304 {
+            Framing::Length(0)
+        } else {
+            head.framing(&buf).map_err(fail)?
+        };
+
+        let mut body = Vec::new();
+        let mut data = &buf[head.len..];
+        let mut reusable = head.keepalive(&buf);
+
+        match framing {
+            Framing::Length(len) => {
+                if len > self.max_response_size {
+                    return Err(fail(ClientError::TooLarge));
+                }
+
+                body.try_reserve_exact(len)
+                    .map_err(|_| fail(ClientError::Alloc))?;
+
+                loop {
+                    let n = data.len().min(len - body.len());
+                    body.extend_from_slice(&data[..n]);
+                    // extra data after the response
+                    reusable &= n == data.len();
+
+                    if body.len() == len {
+                        break;
+                    }
+
+                    let n = conn.read(&mut tmp).await.map_err(|e| fail(e.into()))?;
+                    if n == 0 {
+                        return Err(fail(ClientError::Closed));
+                    }
+                    data = &tmp[..n];
+                }
+            }
+
+            Framing::Chunked => {
+                let mut decoder = ChunkedDecoder::default();
+
+                loop {
+                    let n = decoder.decode(data, &mut body).map_err(fail)?;
+                    if body.len() > self.max_response_size {
+                        return Err(fail(ClientError::TooLarge));
+                    }
+
+                    if decoder.is_done() {
+                        reusable &= n == data.len();
+                        break;
+                    }
+
+                    let n = conn.read(&mut tmp).await.map_err(|e| fail(e.into()))?;
+                    if n == 0 {
+                        return Err(fail(ClientError::Closed));
+                    }
+                    data = &tmp[..n];
+                }
+            }
+
+            Framing::Close => {
+                reusable = false;
+
+                loop {
+                    if body.len() + data.len() > self.max_response_size {
+                        return Err(fail(ClientError::TooLarge));
+                    }
+                    extend(&mut body, data).map_err(fail)?;
+
+                    let n = conn.read(&mut tmp).await.map_err(|e| fail(e.into()))?;
+                    if n == 0 {
+                        break;
+                    }
+                    data = &tmp[..n];
+                }
+            }
+        }
+
+        buf.truncate(head.len);
+
+        let response = ClientResponse {
+            status: head.status,
+            head: buf,
+            headers: head.headers,
+            body,
+        };
+
+        Ok((response, reusable))
+    }
+}
+
+/// A request sent with the [Client].
+#[derive(Clone, Debug)]
+pub struct ClientRequest<'a> {
+    method: &'a str,
+    target: &'a str,
+    host: Option<&'a str>,
+    headers: Vec<(&'a str, &'a [u8])>,
+    body: &'a [u8],
+}
+
+impl<'a> ClientRequest<'a> {
+    /// Creates a request with the method and the request target, e.g. `/path?query`.
+    pub fn new(method: &'a str, target: &'a str) -> Self {
+        Self {
+            method,
+            target,
+            host: None,
+            headers: Vec::new(),
+            body: &[],
+        }
+    }
+
+    /// Creates a `GET` request.
+    pub fn get(target: &'a str) -> Self {
+        Self::new("GET", target)
+    }
+
+    /// Creates a `POST` request with the body.
+    pub fn post(target: &'a str, body: &'a [u8]) -> Self {
+        Self::new("POST", target).body(body)
+    }
+
+    /// Sets the `Host` header. Defaults to the server address.
+    pub fn host(mut self, host: &'a str) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Adds a request header.
+    ///
+    /// The `Host`, `Content-Length` and `Connection` headers are set by the client. A header that
+    /// does not pass [header::validate] fails the request with [ClientError::InvalidHeader].
+    pub fn header(mut self, name: &'a str, value: &'a (impl AsRef<[u8]> + ?Sized)) -> Self {
+        self.headers.push((name, value.as_ref()));
+        self
+    }
+
+    /// Sets the request body.
+    pub fn body(mut self, body: &'a [u8]) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Returns `true` if the request can be safely repeated.
+    fn is_idempotent(&self) -> bool {
+        ["GET", "HEAD", "OPTIONS", "PUT", "DELETE"].contains(&self.method)
+    }
+
+    /// Serializes the request line and the header.
+    fn encode(&self, addr: SocketAddr, keepalive: bool) -> Result<Vec<u8>, ClientError> {
+        // the method is a token, and the target is a sequence of visible ASCII characters
+        if !header::is_valid_name(self.method.as_bytes())
+            || self.target.is_empty()
+            || !self.target.bytes().all(|x| x.is_ascii_graphic())
+        {
+            return Err(ClientError::InvalidRequest);
+        }
+
+        let mut out = HeadWriter(Vec::new(), Ok(()));
+
+        let _ = write!(out, "{} {} HTTP/1.1\r\n", self.method, self.target);
+
+        match self.host {
+            Some(host) if !header::is_valid_value(host.as_bytes()) => {
+                return Err(ClientError::InvalidHeader);
+            }
+            Some(host) => {
+                let _ = write!(out, "Host: {host}\r\n");
+            }
+            None => {
+                let _ = write!(out, "Host: {addr}\r\n");
+            }
+        }
+
+        for (name, value) in &self.headers {
+            header::validate(name, value).map_err(|_| ClientError::InvalidHeader)?;
+            out.push(name.as_bytes());
+            out.push(b": ");
+            out.push(value);
+            out.push(b"\r\n");
+        }
+
+        if !self.body.is_empty() || !["GET", "HEAD"].contains(&self.method) {
+            let _ = write!(out, "Content-Length: {}\r\n", self.body.len());
+        }
+
+        if !keepalive {
+            out.push(b"Connection: close\r\n");
+        }
+
+        out.push(b"\r\n");
+
+        out.1.map(|_| out.0)
+    }
+}
+
+/// A response received with the [Client].
+pub struct ClientResponse {
+    status: u16,
+    head: Vec<u8>,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    body: Vec<u8>,
+}
+
+impl ClientResponse {
+    /// Returns the response status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the value of the first header with the specified name.
+    ///
+    /// The name is compared case-insensitively.
+    pub fn header(&self, name: impl AsRef<[u8]>) -> Option<&[u8]> {
+        let name = name.as_ref();
+        self.headers()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns an iterator over the response headers.
+    pub fn headers(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.headers
+            .iter()
+            .map(|(key, value)| (&self.head[key.clone()], &self.head[value.clone()]))
+    }
+
+    /// Returns the response body, with the transfer encoding removed.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Consumes the response and returns the body.
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+impl fmt::Debug for ClientResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers.len())
+            .field("body", &self.body.len())
+            .finish()
+    }
+}
+
+struct ExchangeError {
+    error: ClientError,
+    /// Whether any part of the response was received.
+    received: bool,
+}
+
+/// A pool of idle connections, shared by the clones of a [Client].
+#[derive(Default)]
+struct IdleConnections {
+    conns: RefCell<Vec<IdleConnection>>,
+    /// The task closing the expired connections.
+    reaper: RefCell<Option<Task<()>>>,
+}
+
+struct IdleConnection {
+    addr: SocketAddr,
+    conn: PeerConnection,
+    expires: ngx_msec_t,
+}
+
+impl IdleConnections {
+    /// Returns an idle connection to `addr`.
+    fn take(&self, addr: SocketAddr) -> Option<PeerConnection> {
+        let mut conns = self.conns.borrow_mut();
+        let index = conns
+            .iter()
+            .rposition(|x| x.addr == addr && x.is_usable())?;
+        Some(conns.swap_remove(index).conn)
+    }
+
+    /// Keeps the connection to `addr` for the later requests.
+    fn put(
+        this: &Rc<Self>,
+        addr: SocketAddr,
+        mut conn: PeerConnection,
+        timeout: Duration,
+        max: usize,
+    ) {
+        // the log of the request may not outlive the connection
+        conn.set_log(ngx_cycle_log());
+
+        let timeout = timeout.as_millis().min(ngx_msec_t::MAX as _) as ngx_msec_t;
+
+        {
+            let mut conns = this.conns.borrow_mut();
+            if conns.iter().filter(|x| x.addr == addr).count() >= max
+                || conns.try_reserve(1).is_err()
+            {
+                return;
+            }
+
+            conns.push(IdleConnection {
+                addr,
+                conn,
+                expires: unsafe { ngx_current_msec }.wrapping_add(timeout),
+            });
+        }
+
+        let mut reaper = this.reaper.borrow_mut();
+        if reaper.as_ref().map_or(true, |x| x.is_finished()) {
+            *reaper = Some(spawn(Self::reap(Rc::downgrade(this))));
+        }
+    }
+
+    /// Closes the idle connections once they expire, until the pool is empty or dropped.
+    async fn reap(this: Weak<Self>) {
+        loop {
+            let wait = {
+                let Some(this) = this.upgrade() else {
+                    return;
+                };
+
+                let now = unsafe { ngx_current_msec };
+                let closed = {
+                    let mut conns = this.conns.borrow_mut();
+                    let (open, closed): (Vec<_>, Vec<_>) = mem::take(&mut *conns)
+                        .into_iter()
+                        .partition(|x| x.remaining(now) > 0 && x.is_usable());
+                    *conns = open;
+                    closed
+                };
+                // close the connections outside of the borrow
+                drop(closed);
+
+                let conns = this.conns.borrow();
+                let Some(wait) = conns.iter().map(|x| x.remaining(now)).min() else {
+                    return;
+                };
+                wait
+            };
+
+            sleep(Duration::from_millis(wait as _)).await;
+        }
+    }
+}
+
+impl IdleConnection {
+    /// Returns the time until the connection expires.
+    fn remaining(&self, now: ngx_msec_t) -> ngx_msec_t {
+        let left = self.expires.wrapping_sub(now);
+        // the difference is "negative" once the time has passed
+        if left > ngx_msec_t::MAX / 2 {
+            0
+        } else {
+            left
+        }
+    }
+
+    /// Returns `true` if the connection was not closed by the server.
+    ///
+    /// A read event on an idle connection means it was closed or the server sent unexpected data.
+    fn is_usable(&self) -> bool {
+        unsafe { (*(*self.conn.as_ptr()).read).ready() == 0 }
+    }
+}
+
+impl fmt::Debug for IdleConnections {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleConnections")
+            .field("len", &self.conns.try_borrow().map(|x| x.len()))
+            .finish_non_exhaustive()
+    }
+}
+
+/// The way the response body length is determined.
+#[derive(Debug, PartialEq, Eq)]
+enum Framing {
+    Length(usize),
+    Chunked,
+    Close,
+}
+
+/// The parsed status line and header of a response.
+#[derive(Debug)]
+struct ResponseHead {
+    /// The length of the status line and the header, including the terminating empty line.
+    len: usize,
+    status: u16,
+    version: u8,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl ResponseHead {
+    /// Returns the values of the headers with the specified name.
+    fn find<'b>(&'b self, buf: &'b [u8], name: &'b [u8]) -> impl Iterator<Item = &'b [u8]> + 'b {
+        self.headers
+            .iter()
+            .filter(move |(key, _)| buf[key.clone()].eq_ignore_ascii_case(name))
+            .map(move |(_, value)| &buf[value.clone()])
+    }
+
+    fn framing(&self, buf: &[u8]) -> Result<Framing, ClientError> {
+        if self
+            .find(buf, b"transfer-encoding")
+            .any(|x| has_token(x, b"chunked"))
+        {
+            return Ok(Framing::Chunked);
+        }
+
+        let mut length = None;
+        for value in self.find(buf, b"content-length") {
+            let len = parse_decimal(value).ok_or(ClientError::InvalidResponse)?;
+            if length.is_some_and(|x| x != len) {
+                return Err(ClientError::InvalidResponse);
+            }
+            length = Some(len);
+        }
+
+        Ok(length.map_or(Framing::Close, Framing::Length))
+    }
+
+    fn keepalive(&self, buf: &[u8]) -> bool {
+        self.version >= 1
+            && !self
+                .find(buf, b"connection")
+                .any(|x| has_token(x, b"close"))
+    }
+}
+
+/// An incremental parser of the status line and the header of a response.
+///
+/// The parser keeps the offset of the first incomplete line, so that each part of the data
+/// received with several reads is parsed only once.
+#[derive(Debug, Default)]
+struct HeadParser {
+    pos: usize,
+    /// The version and the status code from the status line.
+    status: Option<(u8, u16)>,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl HeadParser {
+    /// Continues parsing the response in `buf`, which contains the previously parsed data.
+    ///
+    /// Returns `None` if the header is incomplete. Once the header is complete, the parser is
+    /// reset to parse the next response, e.g. after an interim one, from the start of the buffer.
+    fn parse(&mut self, buf: &[u8]) -> Result<Option<ResponseHead>, ClientError> {
+        let mut lines = Lines { buf, pos: self.pos };
+
+        while let Some(line) = lines.next() {
+            self.pos = lines.pos;
+
+            let Some((version, status)) = self.status else {
+                self.status = Some(parse_status_line(&buf[line])?);
+                continue;
+            };
+
+            if line.is_empty() {
+                let head = ResponseHead {
+                    len: self.pos,
+                    status,
+                    version,
+                    headers: mem::take(&mut self.headers),
+                };
+                *self = Self::default();
+                return Ok(Some(head));
+            }
+
+            let header = parse_header_line(buf, line)?;
+            self.headers
+                .try_reserve(1)
+                .map_err(|_| ClientError::Alloc)?;
+            self.headers.push(header);
+        }
+
+        Ok(None)
+    }
+}
+
+/// Parses the status line, `HTTP/1.x SSS [reason]`, and returns the minor version and the status.
+fn parse_status_line(line: &[u8]) -> Result<(u8, u16), ClientError> {
+    match line {
+        [b'H', b'T', b'T', b'P', b'/', b'1', b'.', v, b' ', s @ ..]
+            if v.is_ascii_digit()
+                && s.len() >= 3
+                && s[..3].iter().all(u8::is_ascii_digit)
+                && (s.len() == 3 || s[3] == b' ') =>
+        {
+            let status = s[..3].iter().fold(0, |acc, x| acc * 10 + (x - b'0') as u16);
+            Ok((v - b'0', status))
+        }
+        _ => Err(ClientError::InvalidResponse),
+    }
+}
+
+/// Parses a header line and returns the ranges of the name and the value in `buf`.
+fn parse_header_line(
+    buf: &[u8],
+    line: Range<usize>,
+) -> Result<(Range<usize>, Range<usize>), ClientError> {
+    let colon = buf[line.clone()]
+        .iter()
+        .position(|&x| x == b':')
+        .filter(|&x| x > 0)
+        .ok_or(ClientError::InvalidResponse)?;
+
+    let key = line.start..line.start + colon;
+    if buf[key.clone()].iter().any(|x| x.is_ascii_whitespace()) {
+        return Err(ClientError::InvalidResponse);
+    }
+
+    let mut value = key.end + 1..line.end;
+    while value.start < value.end && matches!(buf[value.start], b' ' | b'\t') {
+        value.start += 1;
+    }
+    while value.start < value.end && matches!(buf[value.end - 1], b' ' | b'\t') {
+        value.end -= 1;
+    }
+
+    Ok((key, value))
+}
+
+/// An iterator over the complete lines in a buffer, without the line terminators.
+struct Lines<'b> {
+    buf: &'b [u8],
+    pos: usize,
+}
+
+impl Iterator for Lines<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.pos;
+        let end = start + self.buf[start..].iter().position(|&x| x == b'\n')?;
+        self.pos = end + 1;
+
+        if end > start && self.buf[end - 1] == b'\r' {
+            Some(start..end - 1)
+        } else {
+            Some(start..end)
+        }
+    }
+}
+
+/// An incremental decoder of the chunked transfer encoding.
+#[derive(Debug, Default)]
+struct ChunkedDecoder {
+    state: ChunkedState,
+    size: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+enum ChunkedState {
+    #[default]
+    SizeStart,
+    Size,
+    Extension,
+    SizeLf,
+    Data,
+    DataCr,
+    DataLf,
+    Trailer,
+    TrailerLine,
+    LastLf,
+    Done,
+}
+
+impl ChunkedDecoder {
+    fn is_done(&self) -> bool {
+        self.state == ChunkedState::Done
+    }
+
+    /// Decodes `input`, appending the data to `out`.
+    ///
+    /// Returns the number of bytes consumed, less than the input length only if the body is
+    /// complete.
+    fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<usize, ClientError> {
+        use ChunkedState::*;
+
+        let mut pos = 0;
+
+        while pos < input.len() && self.state != Done {
+            let ch = input[pos];
+
+            if self.state == Data {
+                let n = self.size.min(input.len() - pos);
+                extend(out, &input[pos..pos + n])?;
+                self.size -= n;
+                pos += n;
+                if self.size == 0 {
+                    self.state = DataCr;
+                }
+                continue;
+            }
+
+            self.state = match (&self.state, ch) {
+                (SizeStart | Size, b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') => {
+                    let digit = (ch as char).to_digit(16).unwrap_or_default() as usize;
+                    self.size = self
+                        .size
+                        .checked_mul(16)
+                        .and_then(|x| x.checked_add(digit))
+                        .ok_or(ClientError::InvalidResponse)?;
+                    Size
+                }
+                (Size, b';' | b' ' | b'\t') => Extension,
+                (Size | Extension, b'\r') => SizeLf,
+                (Size | Extension | SizeLf, b'\n') if self.size == 0 => Trailer,
+                (Size | Extension | SizeLf, b'\n') => Data,
+                (Extension, _) => Extension,
+                (DataCr, b'\r') => DataLf,
+                (DataCr | DataLf, b'\n') => SizeStart,
+                (Trailer, b'\r') => LastLf,
+                (Trailer | LastLf, b'\n') => Done,
+                (Trailer | TrailerLine, _) if ch != b'\n' => TrailerLine,
+                (TrailerLine, b'\n') => Trailer,
+                _ => return Err(ClientError::InvalidResponse),
+            };
+
+            pos += 1;
+        }
+
+        Ok(pos)
+    }
+}
+
+/// Checks if a comma-separated header value contains the token.
+fn has_token(value: &[u8], token: &[u8]) -> bool {
+    value
+        .split(|&x| x == b',')
+        .any(|x| x.trim_ascii().eq_ignore_ascii_case(token))
+}
+
+fn parse_decimal(value: &[u8]) -> Option<usize> {
+    if value.is_empty() {
+        return None;
+    }
+
+    value.iter().try_fold(0usize, |acc, &x| {
+        if !x.is_ascii_digit() {
+            return None;
+        }
+        acc.checked_mul(10)?.checked_add((x - b'0') as usize)
+    })
+}
+
+fn extend(buf: &mut Vec<u8>, data: &[u8]) -> Result<(), ClientError> {
+    buf.try_reserve(data.len())
+        .map_err(|_| ClientError::Alloc)?;
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+/// A [fmt::Write] adapter for the request header, remembering allocation failures.
+struct HeadWriter(Vec<u8>, Result<(), ClientError>);
+
+impl HeadWriter {
+    fn push(&mut self, data: &[u8]) {
+        if self.1.is_ok() {
+            self.1 = extend(&mut self.0, data);
+        }
+    }
+}
+
+impl Write for HeadWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        self.1.as_ref().map_err(|_| fmt::Error).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_head(buf: &[u8]) -> Result<Option<ResponseHead>, ClientError> {
+        HeadParser::default().parse(buf)
+    }
+
+    #[test]
+    fn test_parse_head() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Empty:\r\n\
+                    Connection:  Keep-Alive \r\n\r\nhello";
+
+        let head = parse_head(buf).unwrap().unwrap();
+        assert_eq!(head.status, 200);
+        assert_eq!(head.len, buf.len() - 5);
+        assert_eq!(head.headers.len(), 3);
+        assert_eq!(head.framing(buf), Ok(Framing::Length(5)));
+        assert!(head.keepalive(buf));
+        assert_eq!(head.find(buf, b"x-empty").next(), Some(&b""[..]));
+        assert_eq!(
+            head.find(buf, b"connection").next(),
+            Some(&b"Keep-Alive"[..])
+        );
+
+        assert!(parse_head(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n")
+            .unwrap()
+            .is_none());
+        assert!(parse_head(b"HTTP/1.1 20").unwrap().is_none());
+
+        let buf = b"HTTP/1.0 204\nConnection: close\n\n";
+        let head = parse_head(buf).unwrap().unwrap();
+        assert_eq!((head.status, head.len), (204, buf.len()));
+        assert!(!head.keepalive(buf));
+
+        let buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, Chunked\r\n\r\n";
+        let head = parse_head(buf).unwrap().unwrap();
+        assert_eq!(head.framing(buf), Ok(Framing::Chunked));
+
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n";
+        let head = parse_head(buf).unwrap().unwrap();
+        assert_eq!(head.framing(buf), Err(ClientError::InvalidResponse));
+
+        let buf = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n";
+        let head = parse_head(buf).unwrap().unwrap();
+        assert_eq!(head.framing(buf), Ok(Framing::Close));
+
+        assert!(parse_head(b"HTTP/2 200\r\n\r\n").is_err());
+        assert!(parse_head(b"HTTP/1.1 2000\r\n\r\n").is_err());
+        assert!(parse_head(b"HTTP/1.1 200 OK\r\nBad Header: x\r\n\r\n").is_err());
+        assert!(parse_head(b"HTTP/1.1 200 OK\r\n: x\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_head_incremental() {
+        let buf = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nX-A: 1\r\n\r\n";
+        let mut parser = HeadParser::default();
+
+        // the parser resumes after the last complete line
+        assert!(parser.parse(&buf[..20]).unwrap().is_none());
+        assert_eq!(parser.pos, 0);
+        assert!(parser.parse(&buf[..24]).unwrap().is_none());
+        assert_eq!(parser.pos, 23);
+
+        let head = parser.parse(buf).unwrap().unwrap();
+        assert_eq!((head.status, head.len), (100, 25));
+
+        // the next response starts at the beginning of the drained buffer
+        let buf = &buf[head.len..];
+        assert!(parser.parse(&buf[..24]).unwrap().is_none());
+        let head = parser.parse(buf).unwrap().unwrap();
+        assert_eq!((head.status, head.len), (200, buf.len()));
+        assert_eq!(head.find(buf, b"x-a").next(), Some(&b"1"[..]));
+    }
+
+    #[test]
+    fn test_chunked_decoder() {
+        let input = b"5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nX-Trailer: 1\r\n\r\nnext";
+
+        // split at every position to exercise the incremental state
+        for split in 0..input.len() - 4 {
+            let mut decoder = ChunkedDecoder::default();
+            let mut out = Vec::new();
+
+            let n = decoder.decode(&input[..split], &mut out).unwrap();
+            assert_eq!(n, split);
+
+            let n = decoder.decode(&input[split..], &mut out).unwrap();
+            assert!(decoder.is_done());
+            assert_eq!(split + n, input.len() - 4);
+            assert_eq!(out, b"hello, world");
+        }
+
+        let mut decoder = ChunkedDecoder::default();
+        assert!(decoder.decode(b"x\r\n", &mut Vec::new()).is_err());
+
+        let mut decoder = ChunkedDecoder::default();
+        assert!(decoder.decode(b"1\r\nab\r\n", &mut Vec::new()).is_err());
+
+        let mut decoder = ChunkedDecoder::default();
+        let overflow = b"fffffffffffffffffffff\r\n";
+        assert!(decoder.decode(overflow, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_encode_request() {
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+
+        let req = ClientRequest::get("/auth").header("X-Token", "abc");
+        assert_eq!(
+            req.encode(addr, true).unwrap(),
+            b"GET /auth HTTP/1.1\r\nHost: 127.0.0.1:8080\r\nX-Token: abc\r\n\r\n"
+        );
+
+        let req = ClientRequest::post("/", b"{}").host("example.com");
+        assert_eq!(
+            req.encode(addr, false).unwrap(),
+            &b"POST / HTTP/1.1\r\nHost: example.com\r\nContent-Length: 2\r\n\
+               Connection: close\r\n\r\n"[..]
+        );
+        assert!(!req.is_idempotent());
+
+        let req = ClientRequest::get("/").header("X-Token", "a\r\nX-Injected: b");
+        assert_eq!(req.encode(addr, true), Err(ClientError::InvalidHeader));
+
+        let req = ClientRequest::get("/").host("example.com\r\n");
+        assert_eq!(req.encode(addr, true), Err(ClientError::InvalidHeader));
+
+        let req = ClientRequest::get("/ HTTP/1.1\r\nX-Injected: b\r\n\r\nGET /");
+        assert_eq!(req.encode(addr, true), Err(ClientError::InvalidRequest));
+
+        let req = ClientRequest::get("/a\tb");
+        assert_eq!(req.encode(addr, true), Err(ClientError::InvalidRequest));
+
+        let req = ClientRequest::new("GET /admin HTTP/1.1\r\n", "/");
+        assert_eq!(req.encode(addr, true), Err(ClientError::InvalidRequest));
+
+        let req = ClientRequest::get("");
+        assert_eq!(req.encode(addr, true), Err(ClientError::InvalidRequest));
+    }
+}
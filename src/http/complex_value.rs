@@ -0,0 +1,247 @@
+use core::error;
+use core::ffi::{c_char, c_void};
+use core::fmt;
+use core::mem;
+use core::ptr;
+use core::slice;
+
+use crate::core::{NgxStr, NGX_CONF_ERROR, NGX_CONF_OK};
+use crate::ffi::{
+    ngx_command_t, ngx_conf_t, ngx_http_compile_complex_value, ngx_http_compile_complex_value_t,
+    ngx_http_complex_value_t, ngx_palloc, ngx_pcalloc, ngx_str_t, NGX_OK,
+};
+use crate::http::Request;
+
+/// An error returned by [ComplexValue] and [ComplexValues].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComplexValueError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The value failed to compile. The error is logged by the configuration parser.
+    Compile,
+    /// The value failed to evaluate, e.g. because of an allocation failure.
+    Evaluate,
+}
+
+impl error::Error for ComplexValueError {}
+
+impl fmt::Display for ComplexValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComplexValueError::Alloc => f.write_str("complex value allocation failed"),
+            ComplexValueError::Compile => f.write_str("invalid complex value"),
+            ComplexValueError::Evaluate => f.write_str("complex value evaluation failed"),
+        }
+    }
+}
+
+/// A directive argument that may contain variables, compiled to a [complex value].
+///
+/// The value is compiled once at configuration time and evaluated for each request. As with
+/// `proxy_pass`, an argument without variables is stored as is and evaluated without the script
+/// engine, see [ComplexValue::as_static].
+///
+/// The layout is the same as of the `ngx_http_complex_value_t *` field, so the value can be set
+/// with the NGINX `ngx_http_set_complex_value_slot` handler.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::{ngx_command_t, ngx_http_set_complex_value_slot};
+/// # use ngx::ffi::{NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET};
+/// # use ngx::http::{ComplexValue, Request};
+/// # use ngx::ngx_string;
+/// #[derive(Debug, Default)]
+/// struct LocConfig {
+///     backend: ComplexValue,
+/// }
+///
+/// static mut COMMAND: ngx_command_t = ngx_command_t {
+///     name: ngx_string!("example_backend"),
+///     type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as _,
+///     set: Some(ngx_http_set_complex_value_slot),
+///     conf: NGX_HTTP_LOC_CONF_OFFSET,
+///     offset: core::mem::offset_of!(LocConfig, backend),
+///     post: core::ptr::null_mut(),
+/// };
+///
+/// fn handler(request: &Request, conf: &LocConfig) {
+///     if let Ok(Some(backend)) = conf.backend.evaluate(request) {
+///         println!("backend {backend}");
+///     }
+/// }
+/// ```
+///
+/// [complex value]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct ComplexValue(*mut ngx_http_complex_value_t);
+
+impl Default for ComplexValue {
+    fn default() -> Self {
+        Self(ptr::null_mut())
+    }
+}
+
+impl ComplexValue {
+    /// Compiles the value, allocating from the configuration pool.
+    pub fn compile(cf: &mut ngx_conf_t, value: &ngx_str_t) -> Result<Self, ComplexValueError> {
+        let cv = unsafe { ngx_pcalloc(cf.pool, mem::size_of::<ngx_http_complex_value_t>()) };
+        let cv = cv.cast::<ngx_http_complex_value_t>();
+        if cv.is_null() {
+            return Err(ComplexValueError::Alloc);
+        }
+
+        unsafe { compile(cf, value, cv)? };
+        Ok(Self(cv))
+    }
+
+    /// Returns `true` if the value is configured.
+    pub fn is_set(&self) -> bool {
+        !self.0.is_null()
+    }
+
+    /// Returns the value if it contains no variables.
+    pub fn as_static(&self) -> Option<&NgxStr> {
+        // SAFETY: the value is allocated from the configuration pool.
+        let cv = unsafe { self.0.as_ref()? };
+        if !cv.lengths.is_null() {
+            return None;
+        }
+        Some(unsafe { NgxStr::from_ngx_str(cv.value) })
+    }
+
+    /// Returns a pointer to the compiled value, or null if the value is not configured.
+    pub fn as_ptr(&self) -> *mut ngx_http_complex_value_t {
+        self.0
+    }
+
+    /// Evaluates the value for the request.
+    ///
+    /// Returns `Ok(None)` if the value is not configured. The result is allocated from the request
+    /// pool unless the value contains no variables.
+    pub fn evaluate<'r>(
+        &self,
+        request: &'r Request,
+    ) -> Result<Option<&'r NgxStr>, ComplexValueError> {
+        // SAFETY: the value is allocated from the configuration pool.
+        let Some(cv) = (unsafe { self.0.as_ref() }) else {
+            return Ok(None);
+        };
+
+        request
+            .get_complex_value(cv)
+            .map(Some)
+            .ok_or(ComplexValueError::Evaluate)
+    }
+}
+
+/// A list of directive arguments compiled to [ComplexValue]s.
+///
+/// Set with [ngx_http_set_complex_values_slot] for directives taking a variable number of
+/// arguments, e.g. `example_upstreams $primary backup.example.com;`.
+#[derive(Debug)]
+pub struct ComplexValues {
+    values: *const ComplexValue,
+    len: usize,
+}
+
+impl Default for ComplexValues {
+    fn default() -> Self {
+        Self {
+            values: ptr::null(),
+            len: 0,
+        }
+    }
+}
+
+impl ComplexValues {
+    /// Compiles the directive arguments, allocating from the configuration pool.
+    pub fn compile(cf: &mut ngx_conf_t, args: &[ngx_str_t]) -> Result<Self, ComplexValueError> {
+        if args.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let size = mem::size_of::<ngx_http_complex_value_t>() * args.len();
+        let parts = unsafe { ngx_pcalloc(cf.pool, size) }.cast::<ngx_http_complex_value_t>();
+        let size = mem::size_of::<ComplexValue>() * args.len();
+        let values = unsafe { ngx_palloc(cf.pool, size) }.cast::<ComplexValue>();
+        if parts.is_null() || values.is_null() {
+            return Err(ComplexValueError::Alloc);
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            // SAFETY: both arrays are allocated with `args.len()` elements.
+            unsafe {
+                compile(cf, arg, parts.add(i))?;
+                values.add(i).write(ComplexValue(parts.add(i)));
+            }
+        }
+
+        Ok(Self {
+            values,
+            len: args.len(),
+        })
+    }
+
+    /// Returns `true` if the values are configured.
+    pub fn is_set(&self) -> bool {
+        self.len > 0
+    }
+
+    /// Returns the compiled values.
+    pub fn as_slice(&self) -> &[ComplexValue] {
+        if self.values.is_null() {
+            return &[];
+        }
+        // SAFETY: the values are allocated from the configuration pool.
+        unsafe { slice::from_raw_parts(self.values, self.len) }
+    }
+}
+
+/// Compiles `value` into the zeroed `cv`.
+unsafe fn compile(
+    cf: &mut ngx_conf_t,
+    value: &ngx_str_t,
+    cv: *mut ngx_http_complex_value_t,
+) -> Result<(), ComplexValueError> {
+    let mut value = *value;
+
+    // SAFETY: the compiler state is zero-initialized, as expected by NGINX.
+    let mut ccv: ngx_http_compile_complex_value_t = mem::zeroed();
+    ccv.cf = cf;
+    ccv.value = &mut value;
+    ccv.complex_value = cv;
+
+    if ngx_http_compile_complex_value(&mut ccv) != NGX_OK as _ {
+        return Err(ComplexValueError::Compile);
+    }
+
+    Ok(())
+}
+
+/// A directive handler compiling the directive arguments to [ComplexValues].
+///
+/// # Safety
+///
+/// The `conf` field at `cmd.offset` must be a [ComplexValues].
+pub unsafe extern "C" fn ngx_http_set_complex_values_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    let values = &mut *conf.cast::<u8>().add((*cmd).offset).cast::<ComplexValues>();
+    if values.is_set() {
+        return c"is duplicate".as_ptr().cast_mut();
+    }
+
+    let cf = &mut *cf;
+    let args: &[ngx_str_t] = (*cf.args).as_slice();
+
+    match ComplexValues::compile(cf, &args[1..]) {
+        Ok(value) => {
+            *values = value;
+            NGX_CONF_OK
+        }
+        Err(_) => NGX_CONF_ERROR,
+    }
+}
@@ -1,7 +1,9 @@
 use ::core::ptr::NonNull;
+use ::core::time::Duration;
 
+use crate::core::NgxStr;
 use crate::ffi::{
-    ngx_http_conf_ctx_t, ngx_http_core_srv_conf_t, ngx_http_request_t,
+    ngx_http_conf_ctx_t, ngx_http_core_loc_conf_t, ngx_http_core_srv_conf_t, ngx_http_request_t,
     ngx_http_upstream_srv_conf_t, ngx_module_t,
 };
 use crate::http::HttpModule;
@@ -51,6 +53,13 @@ impl HttpModuleConfExt for crate::ffi::ngx_cycle_t {
     }
 }
 
+impl HttpModuleConfExt for crate::core::CycleRef<'_> {
+    #[inline]
+    unsafe fn http_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        self.as_ref().http_main_conf_unchecked(module)
+    }
+}
+
 impl HttpModuleConfExt for crate::ffi::ngx_conf_t {
     #[inline]
     unsafe fn http_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
@@ -205,6 +214,144 @@ mod core {
 
 pub use core::NgxHttpCoreModule;
 
+/// Typed accessors for the `ngx_http_core_module` location configuration.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::http::{HttpCoreLocationConfExt, HttpModuleLocationConf, NgxHttpCoreModule, Request};
+/// # fn example(request: &Request) -> Option<()> {
+/// let clcf = NgxHttpCoreModule::location_conf(request)?;
+///
+/// if clcf.sendfile() && clcf.alias().is_none() {
+///     // serve the file with sendfile
+/// }
+/// # Some(())
+/// # }
+/// ```
+pub trait HttpCoreLocationConfExt {
+    /// Returns the location name.
+    fn name(&self) -> &NgxStr;
+
+    /// Returns the document root set with the `root` or `alias` directive.
+    ///
+    /// The value may contain variables, see [HttpCoreLocationConfExt::root_has_variables].
+    /// Use [Request::map_uri_to_path](crate::http::Request::map_uri_to_path) to resolve the path
+    /// of a request.
+    fn root(&self) -> &NgxStr;
+
+    /// Returns `true` if the document root contains variables.
+    fn root_has_variables(&self) -> bool;
+
+    /// Returns the `alias` value, if the document root is set with the `alias` directive.
+    fn alias(&self) -> Option<&NgxStr>;
+
+    /// Returns `true` if the location is marked as `internal`.
+    fn is_internal(&self) -> bool;
+
+    /// Returns the `client_max_body_size` value. Zero disables the check.
+    fn client_max_body_size(&self) -> u64;
+
+    /// Returns the `client_body_buffer_size` value.
+    fn client_body_buffer_size(&self) -> usize;
+
+    /// Returns the `client_body_timeout` value.
+    fn client_body_timeout(&self) -> Duration;
+
+    /// Returns the `send_timeout` value.
+    fn send_timeout(&self) -> Duration;
+
+    /// Returns the `keepalive_timeout` value. Zero disables the keep-alive connections.
+    fn keepalive_timeout(&self) -> Duration;
+
+    /// Returns the `keepalive_requests` value.
+    fn keepalive_requests(&self) -> usize;
+
+    /// Returns `true` if `sendfile` is enabled.
+    fn sendfile(&self) -> bool;
+
+    /// Returns `true` if `tcp_nopush` is enabled.
+    fn tcp_nopush(&self) -> bool;
+
+    /// Returns `true` if `tcp_nodelay` is enabled.
+    fn tcp_nodelay(&self) -> bool;
+
+    /// Returns the `directio` threshold, if enabled.
+    fn directio(&self) -> Option<u64>;
+
+    /// Returns the `output_buffers` number and size.
+    fn output_buffers(&self) -> (usize, usize);
+}
+
+impl HttpCoreLocationConfExt for ngx_http_core_loc_conf_t {
+    fn name(&self) -> &NgxStr {
+        // SAFETY: the name is allocated from the configuration pool.
+        unsafe { NgxStr::from_ngx_str(self.name) }
+    }
+
+    fn root(&self) -> &NgxStr {
+        // SAFETY: the root is allocated from the configuration pool or static.
+        unsafe { NgxStr::from_ngx_str(self.root) }
+    }
+
+    fn root_has_variables(&self) -> bool {
+        !self.root_lengths.is_null()
+    }
+
+    fn alias(&self) -> Option<&NgxStr> {
+        // `alias` is the length of the location prefix replaced by the alias
+        (self.alias != 0).then(|| self.root())
+    }
+
+    fn is_internal(&self) -> bool {
+        self.internal() != 0
+    }
+
+    fn client_max_body_size(&self) -> u64 {
+        self.client_max_body_size.max(0) as u64
+    }
+
+    fn client_body_buffer_size(&self) -> usize {
+        self.client_body_buffer_size
+    }
+
+    fn client_body_timeout(&self) -> Duration {
+        Duration::from_millis(self.client_body_timeout as u64)
+    }
+
+    fn send_timeout(&self) -> Duration {
+        Duration::from_millis(self.send_timeout as u64)
+    }
+
+    fn keepalive_timeout(&self) -> Duration {
+        Duration::from_millis(self.keepalive_timeout as u64)
+    }
+
+    fn keepalive_requests(&self) -> usize {
+        self.keepalive_requests as usize
+    }
+
+    fn sendfile(&self) -> bool {
+        self.sendfile != 0
+    }
+
+    fn tcp_nopush(&self) -> bool {
+        self.tcp_nopush != 0
+    }
+
+    fn tcp_nodelay(&self) -> bool {
+        self.tcp_nodelay != 0
+    }
+
+    fn directio(&self) -> Option<u64> {
+        // NGX_OPEN_FILE_DIRECTIO_OFF
+        (self.directio != crate::ffi::off_t::MAX).then_some(self.directio as u64)
+    }
+
+    fn output_buffers(&self) -> (usize, usize) {
+        (self.bufs.num as usize, self.bufs.size)
+    }
+}
+
 #[cfg(ngx_feature = "http_ssl")]
 mod ssl {
     use crate::ffi::{ngx_http_ssl_module, ngx_http_ssl_srv_conf_t};
@@ -0,0 +1,346 @@
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr;
+
+use crate::core::Status;
+use crate::ffi::{
+    ngx_add_timer, ngx_buf_t, ngx_chain_get_free_buf, ngx_chain_t, ngx_chain_update_chains,
+    ngx_del_timer, ngx_handle_write_event, ngx_http_finalize_request, ngx_http_output_filter,
+    ngx_http_request_t, ngx_int_t, ngx_post_event, ngx_posted_next_events, NGX_AGAIN, NGX_ERROR,
+    NGX_HTTP_REQUEST_TIME_OUT, NGX_LOG_INFO, NGX_OK,
+};
+use crate::http::{HttpModuleCtx, HttpModuleLocationConf, NgxHttpCoreModule, Request};
+use crate::{ngx_log_debug_http, ngx_log_error};
+
+/// The result of [BodyGenerator::generate].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Generated {
+    /// The chunk is ready, and the generator should be called again once it is sent.
+    More,
+    /// The chunk is the last part of the response body.
+    Done,
+    /// The response cannot be completed. The request is terminated.
+    Error,
+}
+
+/// A source of a response body produced in chunks.
+///
+/// See [BodyContinuation].
+pub trait BodyGenerator {
+    /// Returns the size of the buffers passed to [BodyGenerator::generate].
+    fn chunk_size(&self) -> usize {
+        32768
+    }
+
+    /// Writes the next part of the response body to `out`.
+    ///
+    /// The generator is expected to fill the buffer with as much data as fits in it. A chunk with
+    /// no data only flushes the output.
+    fn generate(&mut self, request: &mut Request, out: &mut ChunkWriter<'_>) -> Generated;
+}
+
+/// A writer over the buffer for the next chunk of a [BodyGenerator].
+///
+/// The writes never grow the buffer: [ChunkWriter::write] and [fmt::Write] fail if the data does
+/// not fit in the remaining space, leaving the buffer unchanged.
+pub struct ChunkWriter<'a>(&'a mut ngx_buf_t);
+
+impl ChunkWriter<'_> {
+    /// Returns the number of bytes written to the buffer.
+    pub fn len(&self) -> usize {
+        self.0.last as usize - self.0.pos as usize
+    }
+
+    /// Returns `true` if nothing was written to the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bytes that can be written to the buffer.
+    pub fn remaining(&self) -> usize {
+        self.0.end as usize - self.0.last as usize
+    }
+
+    /// Appends `data` to the buffer.
+    ///
+    /// Returns `false` if the data does not fit in the remaining space.
+    pub fn write(&mut self, data: &[u8]) -> bool {
+        if data.len() > self.remaining() {
+            return false;
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.0.last, data.len());
+            self.0.last = self.0.last.add(data.len());
+        }
+        true
+    }
+}
+
+impl fmt::Write for ChunkWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.write(s.as_bytes()) {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        // discard the partially formatted output on overflow
+        let last = self.0.last;
+        let rc = fmt::write(self, args);
+        if rc.is_err() {
+            self.0.last = last;
+        }
+        rc
+    }
+}
+
+impl fmt::Debug for ChunkWriter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChunkWriter")
+            .field("len", &self.len())
+            .field("remaining", &self.remaining())
+            .finish()
+    }
+}
+
+/// The state of a response body generated incrementally from the request write events.
+///
+/// A content handler sends the response header and starts the continuation with
+/// [BodyContinuation::start]. The continuation is stored as the request context of the module and
+/// calls the [BodyGenerator] each time the previous chunk is sent to the client, reusing the
+/// chunk buffers. At most one chunk is pending at a time, so the memory usage does not depend on
+/// the body size, and other connections are served between the chunks.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::ngx_module_t;
+/// # use ngx::http::{
+/// #     BodyContinuation, BodyGenerator, ChunkWriter, Generated, HttpModule, HttpModuleCtx,
+/// #     HTTPStatus, Request,
+/// # };
+/// # struct MyModule;
+/// # impl HttpModule for MyModule {
+/// #     fn module() -> &'static ngx_module_t { unimplemented!() }
+/// # }
+/// struct Counter(u64);
+///
+/// impl BodyGenerator for Counter {
+///     fn generate(&mut self, _r: &mut Request, out: &mut ChunkWriter<'_>) -> Generated {
+///         use core::fmt::Write;
+///
+///         while self.0 < 1_000_000 {
+///             if writeln!(out, "{}", self.0).is_err() {
+///                 return Generated::More;
+///             }
+///             self.0 += 1;
+///         }
+///         Generated::Done
+///     }
+/// }
+///
+/// unsafe impl HttpModuleCtx for MyModule {
+///     type Ctx = BodyContinuation<Counter>;
+/// }
+///
+/// fn content_handler(request: &mut Request) -> Status {
+///     request.set_status(HTTPStatus::OK);
+///     let rc = request.send_header();
+///     if rc == Status::NGX_ERROR || rc.0 > Status::NGX_OK.0 || request.header_only() {
+///         return rc;
+///     }
+///
+///     BodyContinuation::start::<MyModule>(request, Counter(0))
+/// }
+/// ```
+pub struct BodyContinuation<G> {
+    generator: G,
+    free: *mut ngx_chain_t,
+    busy: *mut ngx_chain_t,
+    done: bool,
+}
+
+impl<G> BodyContinuation<G> {
+    /// Returns a reference to the generator.
+    pub fn generator(&self) -> &G {
+        &self.generator
+    }
+
+    /// Returns a mutable reference to the generator.
+    pub fn generator_mut(&mut self) -> &mut G {
+        &mut self.generator
+    }
+}
+
+impl<G: BodyGenerator + 'static> BodyContinuation<G> {
+    /// Stores the continuation as the request context of `M` and sends the first chunk.
+    ///
+    /// The response header must be sent before starting the continuation. The function
+    /// increments the main request reference count and returns `NGX_DONE`, which should be
+    /// returned from the content handler. The request is finalized once the last chunk is
+    /// passed to the output filters or the generator fails.
+    pub fn start<M>(request: &mut Request, generator: G) -> Status
+    where
+        M: HttpModuleCtx<Ctx = Self>,
+    {
+        let ctx = BodyContinuation {
+            generator,
+            free: ptr::null_mut(),
+            busy: ptr::null_mut(),
+            done: false,
+        };
+
+        let Ok(ctx) = request.pool().allocate_with_cleanup(ctx) else {
+            return Status::NGX_ERROR;
+        };
+        request.set_module_ctx(ctx.as_ptr().cast(), M::module());
+
+        let r = request.as_mut();
+        // SAFETY: the main request pointer is always valid.
+        unsafe { (*r.main).set_count((*r.main).count() + 1) };
+        r.write_event_handler = Some(write_event_handler::<M, G>);
+
+        Self::run::<M>(request);
+
+        Status::NGX_DONE
+    }
+
+    fn run<M>(request: &mut Request)
+    where
+        M: HttpModuleCtx<Ctx = Self>,
+    {
+        let c = request.connection();
+        // SAFETY: the request connection and its events are valid.
+        let wev = unsafe { (*c).write };
+
+        if unsafe { (*wev).timedout() } != 0 {
+            ngx_log_error!(NGX_LOG_INFO, request.log(), "client timed out");
+            unsafe { (*c).set_timedout(1) };
+            return finalize(request, NGX_HTTP_REQUEST_TIME_OUT as ngx_int_t);
+        }
+
+        if unsafe { (*wev).delayed() } != 0 {
+            // the output is rate limited, wait for the timer
+            if unsafe { ngx_handle_write_event(wev, 0) } != NGX_OK as ngx_int_t {
+                finalize(request, NGX_ERROR as ngx_int_t);
+            }
+            return;
+        }
+
+        let Some(ctx) = request.module_ctx_mut::<M>() else {
+            return finalize(request, NGX_ERROR as ngx_int_t);
+        };
+        // SAFETY: the context is allocated from the request pool and is not accessed through the
+        // request while the reference is alive.
+        let ctx = unsafe { &mut *ptr::from_mut(ctx) };
+        let tag = ptr::from_mut(ctx).cast::<c_void>();
+        let mut pool = request.pool();
+
+        let mut out: *mut ngx_chain_t = ptr::null_mut();
+
+        // generate the next chunk once the previous one is sent, otherwise only flush the output
+        if ctx.busy.is_null() && !ctx.done {
+            let cl = unsafe { ngx_chain_get_free_buf(pool.as_ptr(), &mut ctx.free) };
+            if cl.is_null() {
+                return finalize(request, NGX_ERROR as ngx_int_t);
+            }
+
+            // SAFETY: ngx_chain_get_free_buf returns a link with a valid buffer.
+            let b = unsafe { &mut *(*cl).buf };
+
+            if b.start.is_null() {
+                let size = ctx.generator.chunk_size();
+                let p = pool.alloc_unaligned(size).cast::<u8>();
+                if p.is_null() {
+                    return finalize(request, NGX_ERROR as ngx_int_t);
+                }
+
+                b.start = p;
+                b.pos = p;
+                b.last = p;
+                b.end = unsafe { p.add(size) };
+                b.set_temporary(1);
+                b.tag = tag;
+            }
+
+            b.set_flush(0);
+
+            match ctx.generator.generate(request, &mut ChunkWriter(&mut *b)) {
+                Generated::More => {}
+                Generated::Done => {
+                    ctx.done = true;
+                    b.set_last_buf(if request.is_main() { 1 } else { 0 });
+                    b.set_last_in_chain(1);
+                }
+                Generated::Error => return finalize(request, NGX_ERROR as ngx_int_t),
+            }
+
+            if b.last == b.pos {
+                b.set_flush(1);
+            }
+
+            out = cl;
+        }
+
+        let r: *mut ngx_http_request_t = request.as_mut();
+        let rc = unsafe { ngx_http_output_filter(r, out) };
+
+        unsafe {
+            ngx_chain_update_chains(pool.as_ptr(), &mut ctx.free, &mut ctx.busy, &mut out, tag)
+        };
+
+        ngx_log_debug_http!(request, "http body continuation rc:{rc} done:{}", ctx.done);
+
+        if ctx.done || rc == NGX_ERROR as ngx_int_t {
+            return finalize(request, rc);
+        }
+
+        let blocked = rc == NGX_AGAIN as ngx_int_t
+            || request.as_ref().buffered() != 0
+            || (request.is_main() && unsafe { (*c).buffered() } != 0);
+
+        if blocked {
+            let clcf = NgxHttpCoreModule::location_conf(request).expect("http core loc conf");
+
+            if unsafe { (*wev).delayed() } == 0 {
+                unsafe { ngx_add_timer(wev, clcf.send_timeout) };
+            }
+
+            if unsafe { ngx_handle_write_event(wev, clcf.send_lowat) } != NGX_OK as ngx_int_t {
+                finalize(request, NGX_ERROR as ngx_int_t);
+            }
+            return;
+        }
+
+        if unsafe { (*wev).timer_set() } != 0 {
+            unsafe { ngx_del_timer(wev) };
+        }
+
+        // let the other connections run before producing the next chunk
+        unsafe { ngx_post_event(wev, ptr::addr_of_mut!(ngx_posted_next_events)) };
+    }
+}
+
+impl<G: fmt::Debug> fmt::Debug for BodyContinuation<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyContinuation")
+            .field("generator", &self.generator)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe extern "C" fn write_event_handler<M, G>(r: *mut ngx_http_request_t)
+where
+    M: HttpModuleCtx<Ctx = BodyContinuation<G>>,
+    G: BodyGenerator + 'static,
+{
+    BodyContinuation::<G>::run::<M>(Request::from_ngx_http_request(r))
+}
+
+fn finalize(request: &mut Request, rc: ngx_int_t) {
+    unsafe { ngx_http_finalize_request(request.as_mut(), rc) }
+}
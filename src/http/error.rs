@@ -0,0 +1,124 @@
+use core::error;
+use core::fmt;
+use core::str::Utf8Error;
+
+use crate::allocator::AllocError;
+use crate::core::Status;
+use crate::ffi::NGX_LOG_ERR;
+use crate::http::{HTTPStatus, Request};
+
+/// Errors returned from the request handlers.
+///
+/// The error is logged and converted to the response status by
+/// [http_request_handler](crate::http_request_handler). Use [NgxError::Status] to finalize the
+/// request with a specific status without logging.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{HTTPStatus, HandlerResult, Request};
+/// # use ngx::http_request_handler;
+/// http_request_handler!(content_handler, |request: &mut Request| -> HandlerResult {
+///     let path = core::str::from_utf8(request.path().as_bytes())?;
+///     if !path.starts_with("/api/") {
+///         return Err(HTTPStatus::NOT_FOUND.into());
+///     }
+///     request.discard_body().map_err(|_| HTTPStatus::INTERNAL_SERVER_ERROR)?;
+///     // ... send the response
+///     Ok(Status::NGX_OK)
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NgxError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The request data is not valid UTF-8.
+    Utf8(Utf8Error),
+    /// The request is finalized with the status.
+    Status(HTTPStatus),
+}
+
+/// Result of a request handler defined with [http_request_handler](crate::http_request_handler).
+pub type HandlerResult = Result<Status, NgxError>;
+
+impl NgxError {
+    /// Returns the response status for the error.
+    pub fn status(&self) -> HTTPStatus {
+        match self {
+            NgxError::Alloc => HTTPStatus::INTERNAL_SERVER_ERROR,
+            NgxError::Utf8(_) => HTTPStatus::BAD_REQUEST,
+            NgxError::Status(status) => *status,
+        }
+    }
+}
+
+impl error::Error for NgxError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NgxError::Utf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for NgxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NgxError::Alloc => write!(f, "memory allocation failed"),
+            NgxError::Utf8(err) => write!(f, "invalid utf-8: {err}"),
+            NgxError::Status(status) => write!(f, "status {}", status.0),
+        }
+    }
+}
+
+impl From<AllocError> for NgxError {
+    fn from(_: AllocError) -> Self {
+        NgxError::Alloc
+    }
+}
+
+impl From<Utf8Error> for NgxError {
+    fn from(err: Utf8Error) -> Self {
+        NgxError::Utf8(err)
+    }
+}
+
+impl From<HTTPStatus> for NgxError {
+    fn from(status: HTTPStatus) -> Self {
+        NgxError::Status(status)
+    }
+}
+
+/// Conversion of a request handler return value to the handler return code.
+///
+/// Implemented for [Status] and [HandlerResult].
+pub trait IntoHandlerStatus {
+    /// Converts the value to the return code, logging the errors to the request log.
+    fn into_handler_status(self, request: &mut Request) -> Status;
+}
+
+impl IntoHandlerStatus for Status {
+    fn into_handler_status(self, _request: &mut Request) -> Status {
+        self
+    }
+}
+
+impl IntoHandlerStatus for HandlerResult {
+    fn into_handler_status(self, request: &mut Request) -> Status {
+        let err = match self {
+            Ok(status) => return status,
+            Err(err) => err,
+        };
+
+        if !matches!(err, NgxError::Status(_)) {
+            crate::ngx_log_error!(NGX_LOG_ERR, request.log(), "request failed: {err}");
+        }
+
+        if request.as_ref().header_sent() != 0 {
+            // The response cannot be replaced with an error page.
+            return Status::NGX_ERROR;
+        }
+
+        err.status().into()
+    }
+}
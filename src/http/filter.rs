@@ -0,0 +1,253 @@
+use core::cell::UnsafeCell;
+
+use crate::core::Status;
+use crate::ffi::*;
+use crate::http::Request;
+
+/// Define a static header filter.
+///
+/// Filters are expected to take a single [`Request`] argument and return a [`Status`]. The
+/// requests outside of the [`FilterScope`] are passed to the next filter stored in the
+/// [`HeaderFilterChain`] without calling the filter.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{FilterScope, HeaderFilterChain, Request};
+/// # use ngx::http_header_filter;
+/// static NEXT_HEADER_FILTER: HeaderFilterChain = HeaderFilterChain::new();
+///
+/// http_header_filter!(
+///     example_header_filter,
+///     NEXT_HEADER_FILTER,
+///     FilterScope::new(),
+///     |request: &mut Request| {
+///         request.add_header_out("X-Example", "1");
+///         NEXT_HEADER_FILTER.next(request)
+///     }
+/// );
+///
+/// // in the module postconfiguration handler
+/// unsafe { NEXT_HEADER_FILTER.install(example_header_filter) };
+/// ```
+#[macro_export]
+macro_rules! http_header_filter {
+    ( $name: ident, $next: expr, $scope: expr, $handler: expr ) => {
+        extern "C" fn $name(r: *mut $crate::ffi::ngx_http_request_t) -> $crate::ffi::ngx_int_t {
+            $crate::panic::catch(module_path!(), stringify!($name), || {
+                let request = unsafe { $crate::http::Request::from_ngx_http_request(r) };
+                let scope: $crate::http::FilterScope = $scope;
+                let status: $crate::core::Status = if scope.applies_to(request) {
+                    $handler(&mut *request)
+                } else {
+                    $next.next(request)
+                };
+                status.0
+            })
+            .unwrap_or($crate::core::Status::NGX_ERROR.0)
+        }
+    };
+}
+
+/// Define a static body filter.
+///
+/// Filters are expected to take a [`Request`] and the `*mut ngx_chain_t` with the buffers to
+/// send, and return a [`Status`]. The requests outside of the [`FilterScope`] are passed to the
+/// next filter stored in the [`BodyFilterChain`] without calling the filter.
+#[macro_export]
+macro_rules! http_body_filter {
+    ( $name: ident, $next: expr, $scope: expr, $handler: expr ) => {
+        extern "C" fn $name(
+            r: *mut $crate::ffi::ngx_http_request_t,
+            chain: *mut $crate::ffi::ngx_chain_t,
+        ) -> $crate::ffi::ngx_int_t {
+            $crate::panic::catch(module_path!(), stringify!($name), || {
+                let request = unsafe { $crate::http::Request::from_ngx_http_request(r) };
+                let scope: $crate::http::FilterScope = $scope;
+                let next: &$crate::http::BodyFilterChain = &$next;
+                let status: $crate::core::Status = if scope.applies_to(request) {
+                    $handler(&mut *request, chain)
+                } else {
+                    unsafe { next.next(request, chain) }
+                };
+                status.0
+            })
+            .unwrap_or($crate::core::Status::NGX_ERROR.0)
+        }
+    };
+}
+
+/// The next header filter in the chain.
+///
+/// A filter is installed by replacing `ngx_http_top_header_filter` in the module
+/// postconfiguration handler, and the previous value is the filter it should pass the response
+/// to.
+#[derive(Debug)]
+pub struct HeaderFilterChain(UnsafeCell<ngx_http_output_header_filter_pt>);
+
+// SAFETY: the filter chain is only modified during the configuration parsing, and the value is
+// read-only after that.
+unsafe impl Sync for HeaderFilterChain {}
+
+impl HeaderFilterChain {
+    /// Creates an empty filter chain.
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new(None))
+    }
+
+    /// Installs the filter as the top header filter and stores the previous top filter.
+    ///
+    /// # Safety
+    ///
+    /// Must be called from the module postconfiguration handler, once per configuration cycle.
+    pub unsafe fn install(
+        &self,
+        filter: unsafe extern "C" fn(*mut ngx_http_request_t) -> ngx_int_t,
+    ) {
+        *self.0.get() = ngx_http_top_header_filter;
+        ngx_http_top_header_filter = Some(filter);
+    }
+
+    /// Passes the response header to the next filter.
+    pub fn next(&self, request: &mut Request) -> Status {
+        // SAFETY: the value is not modified after the configuration parsing.
+        match unsafe { *self.0.get() } {
+            Some(next) => Status(unsafe { next(request.as_mut()) }),
+            None => Status::NGX_ERROR,
+        }
+    }
+}
+
+impl Default for HeaderFilterChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The next body filter in the chain.
+///
+/// See [`HeaderFilterChain`].
+#[derive(Debug)]
+pub struct BodyFilterChain(UnsafeCell<ngx_http_output_body_filter_pt>);
+
+// SAFETY: the filter chain is only modified during the configuration parsing, and the value is
+// read-only after that.
+unsafe impl Sync for BodyFilterChain {}
+
+impl BodyFilterChain {
+    /// Creates an empty filter chain.
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new(None))
+    }
+
+    /// Installs the filter as the top body filter and stores the previous top filter.
+    ///
+    /// # Safety
+    ///
+    /// Must be called from the module postconfiguration handler, once per configuration cycle.
+    pub unsafe fn install(
+        &self,
+        filter: unsafe extern "C" fn(*mut ngx_http_request_t, *mut ngx_chain_t) -> ngx_int_t,
+    ) {
+        *self.0.get() = ngx_http_top_body_filter;
+        ngx_http_top_body_filter = Some(filter);
+    }
+
+    /// Passes the buffers to the next filter.
+    ///
+    /// # Safety
+    ///
+    /// `chain` is NULL or a valid pointer to the buffer chain passed to the filter.
+    pub unsafe fn next(&self, request: &mut Request, chain: *mut ngx_chain_t) -> Status {
+        // SAFETY: the value is not modified after the configuration parsing.
+        match *self.0.get() {
+            Some(next) => Status(next(request.as_mut(), chain)),
+            None => Status::NGX_ERROR,
+        }
+    }
+}
+
+impl Default for BodyFilterChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Selects the requests processed by a header or body filter.
+///
+/// By default a filter skips:
+///  - header-only responses, e.g. to `HEAD` requests, as these have no body to transform and
+///    the response headers describe the body that would have been sent;
+///  - internal requests, i.e. subrequests and the requests processed after an internal redirect,
+///    as the filter usually has already seen the main request or the response of a subrequest is
+///    embedded into the parent response.
+///
+/// Each of these can be enabled explicitly. The [`http_header_filter`](crate::http_header_filter)
+/// and [`http_body_filter`](crate::http_body_filter) filters pass the requests outside of the
+/// scope to the next filter unchanged.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::ngx_chain_t;
+/// # use ngx::http::{BodyFilterChain, FilterScope, Request};
+/// # use ngx::http_body_filter;
+/// static NEXT_BODY_FILTER: BodyFilterChain = BodyFilterChain::new();
+///
+/// http_body_filter!(
+///     example_body_filter,
+///     NEXT_BODY_FILTER,
+///     FilterScope::new().with_subrequests(true),
+///     |request: &mut Request, chain: *mut ngx_chain_t| {
+///         // transform the buffers of the main request and subrequests
+///         unsafe { NEXT_BODY_FILTER.next(request, chain) }
+///     }
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FilterScope {
+    header_only: bool,
+    internal: bool,
+    subrequests: bool,
+}
+
+impl FilterScope {
+    /// Creates a scope with the default exclusions.
+    pub const fn new() -> Self {
+        Self {
+            header_only: false,
+            internal: false,
+            subrequests: false,
+        }
+    }
+
+    /// Sets whether the filter processes header-only responses.
+    pub const fn with_header_only(mut self, value: bool) -> Self {
+        self.header_only = value;
+        self
+    }
+
+    /// Sets whether the filter processes the main request after an internal redirect.
+    pub const fn with_internal(mut self, value: bool) -> Self {
+        self.internal = value;
+        self
+    }
+
+    /// Sets whether the filter processes subrequests.
+    pub const fn with_subrequests(mut self, value: bool) -> Self {
+        self.subrequests = value;
+        self
+    }
+
+    /// Checks if the filter should process the request.
+    pub fn applies_to(&self, request: &Request) -> bool {
+        if request.header_only() && !self.header_only {
+            return false;
+        }
+
+        if !request.is_main() {
+            return self.subrequests;
+        }
+
+        !request.is_internal() || self.internal
+    }
+}
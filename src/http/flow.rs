@@ -0,0 +1,174 @@
+use core::ptr;
+
+use crate::ffi::{ngx_http_core_loc_conf_t, ngx_post_event, ngx_posted_next_events};
+use crate::http::{HttpModuleLocationConf, NgxHttpCoreModule, Request};
+
+/// Output flow control limits for module-produced response bodies.
+///
+/// The limits mirror the [`postpone_output`] and [`sendfile_max_chunk`] directives and can be
+/// either read from the request location configuration or set explicitly.
+///
+/// [`postpone_output`]: https://nginx.org/en/docs/http/ngx_http_core_module.html#postpone_output
+/// [`sendfile_max_chunk`]: https://nginx.org/en/docs/http/ngx_http_core_module.html#sendfile_max_chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputLimits {
+    postpone_output: usize,
+    max_chunk: usize,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        // Same as the `ngx_http_core_module` defaults.
+        Self {
+            postpone_output: 1460,
+            max_chunk: 2 * 1024 * 1024,
+        }
+    }
+}
+
+impl OutputLimits {
+    /// Reads the limits from the `ngx_http_core_module` location configuration.
+    pub fn from_location_conf(clcf: &ngx_http_core_loc_conf_t) -> Self {
+        Self {
+            postpone_output: clcf.postpone_output,
+            max_chunk: clcf.sendfile_max_chunk,
+        }
+    }
+
+    /// Reads the limits applicable to the request.
+    pub fn for_request(request: &Request) -> Self {
+        NgxHttpCoreModule::location_conf(request)
+            .map(Self::from_location_conf)
+            .unwrap_or_default()
+    }
+
+    /// Sets the minimum amount of data to accumulate before sending it to the client.
+    ///
+    /// Zero disables postponing.
+    pub fn with_postpone_output(mut self, size: usize) -> Self {
+        self.postpone_output = size;
+        self
+    }
+
+    /// Sets the maximum amount of data to send in a single event loop iteration.
+    ///
+    /// Zero means no limit.
+    pub fn with_max_chunk(mut self, size: usize) -> Self {
+        self.max_chunk = size;
+        self
+    }
+
+    /// Returns the minimum amount of data to accumulate before sending it to the client.
+    pub fn postpone_output(&self) -> usize {
+        self.postpone_output
+    }
+
+    /// Returns the maximum amount of data to send in a single event loop iteration.
+    pub fn max_chunk(&self) -> usize {
+        self.max_chunk
+    }
+
+    /// Checks if the buffered data should be passed to the output filters.
+    ///
+    /// `last` indicates that no more data is expected and any buffered data should be sent.
+    pub fn should_flush(&self, buffered: usize, last: bool) -> bool {
+        last || buffered >= self.postpone_output
+    }
+}
+
+/// Paces a large generated response body according to the [OutputLimits].
+///
+/// The producer asks for the [budget](ChunkPacer::budget) of the current round, reports the
+/// amount of data sent with [advance](ChunkPacer::advance) and yields to the event loop, e.g. with
+/// [defer_write], once the round is exhausted. This prevents a single response from monopolizing
+/// the worker process and delaying the processing of other connections.
+#[derive(Clone, Debug)]
+pub struct ChunkPacer {
+    limits: OutputLimits,
+    sent: usize,
+}
+
+impl ChunkPacer {
+    /// Creates a new pacer with the specified limits.
+    pub fn new(limits: OutputLimits) -> Self {
+        Self { limits, sent: 0 }
+    }
+
+    /// Returns the limits used by the pacer.
+    pub fn limits(&self) -> &OutputLimits {
+        &self.limits
+    }
+
+    /// Returns the amount of data that can be sent in the current round.
+    pub fn budget(&self) -> usize {
+        match self.limits.max_chunk {
+            0 => usize::MAX,
+            n => n.saturating_sub(self.sent),
+        }
+    }
+
+    /// Returns the size of the next chunk for the `remaining` amount of data.
+    pub fn next_chunk(&self, remaining: usize) -> usize {
+        remaining.min(self.budget())
+    }
+
+    /// Records `n` bytes sent in the current round.
+    ///
+    /// Returns `true` if the round is exhausted and the producer should yield.
+    pub fn advance(&mut self, n: usize) -> bool {
+        self.sent = self.sent.saturating_add(n);
+        self.budget() == 0
+    }
+
+    /// Starts a new round.
+    ///
+    /// Should be called when the producer resumes after yielding to the event loop.
+    pub fn reset(&mut self) {
+        self.sent = 0;
+    }
+}
+
+/// Schedules the client connection write event handler for the next event loop iteration.
+///
+/// Allows a content producer to give up the control after sending a chunk of the response and
+/// continue from the request write event handler.
+pub fn defer_write(request: &mut Request) {
+    // SAFETY: a request always has a valid connection with allocated events.
+    unsafe {
+        let c = request.connection();
+        ngx_post_event((*c).write, ptr::addr_of_mut!(ngx_posted_next_events));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_limits() {
+        let limits = OutputLimits::default().with_postpone_output(100);
+        assert!(!limits.should_flush(99, false));
+        assert!(limits.should_flush(99, true));
+        assert!(limits.should_flush(100, false));
+
+        let limits = limits.with_postpone_output(0);
+        assert!(limits.should_flush(0, false));
+    }
+
+    #[test]
+    fn test_chunk_pacer() {
+        let mut pacer = ChunkPacer::new(OutputLimits::default().with_max_chunk(1000));
+        assert_eq!(pacer.next_chunk(4000), 1000);
+        assert!(!pacer.advance(600));
+        assert_eq!(pacer.next_chunk(3400), 400);
+        assert!(pacer.advance(400));
+        assert_eq!(pacer.next_chunk(3000), 0);
+
+        pacer.reset();
+        assert_eq!(pacer.next_chunk(3000), 1000);
+
+        let mut pacer = ChunkPacer::new(OutputLimits::default().with_max_chunk(0));
+        assert_eq!(pacer.next_chunk(usize::MAX - 1), usize::MAX - 1);
+        assert!(!pacer.advance(1 << 30));
+    }
+}
@@ -0,0 +1,819 @@
+//! HTTP header field names.
+//!
+//! The module provides [StaticHeaderName] constants for the permanent field names in the
+//! [IANA HTTP Field Name Registry] and a few commonly used non-standard headers. Each constant
+//! stores the hash of the lowercase name, computed at compile time with the same algorithm as
+//! `ngx_hash_key_lc`. This allows fast comparison with the `hash` field of the parsed request
+//! headers.
+//!
+//! Downstream modules can define their own constants with [StaticHeaderName::new]:
+//! ```
+//! use ngx::http::header::StaticHeaderName;
+//!
+//! const X_REQUEST_ID: StaticHeaderName = StaticHeaderName::new("X-Request-ID");
+//! ```
+//!
+//! [ByteRanges] and [Authorization] parse the values of the corresponding request headers, see
+//! [Request::range](crate::http::Request::range) and
+//! [Request::authorization](crate::http::Request::authorization).
+//!
+//! [IANA HTTP Field Name Registry]: https://www.iana.org/assignments/http-fields/http-fields.xhtml
+use core::error;
+use core::fmt;
+use core::hash;
+use core::ops::Range;
+
+use crate::allocator::AllocError;
+use crate::core::parse::ParseError;
+use crate::core::NgxStr;
+use crate::ffi::{ngx_table_elt_t, ngx_uint_t};
+
+/// Computes the NGINX hash of the lowercase version of the key.
+///
+/// The result is the same as `ngx_hash_key_lc`, or the `hash` value of a parsed header line.
+pub const fn ngx_hash_key_lc(key: &[u8]) -> ngx_uint_t {
+    let mut hash: ngx_uint_t = 0;
+    let mut i = 0;
+    while i < key.len() {
+        // ngx_hash(key, c) ((ngx_uint_t) key * 31 + c)
+        hash = hash
+            .wrapping_mul(31)
+            .wrapping_add(key[i].to_ascii_lowercase() as ngx_uint_t);
+        i += 1;
+    }
+    hash
+}
+
+/// An HTTP header field name with a precomputed hash.
+#[derive(Clone, Copy)]
+pub struct StaticHeaderName {
+    name: &'static str,
+    hash: ngx_uint_t,
+}
+
+impl StaticHeaderName {
+    /// Creates a new header name and computes its hash.
+    ///
+    /// # Panics
+    ///
+    /// Panics (or fails to compile in a const context) if the name is empty or contains
+    /// characters not allowed in an HTTP field name token.
+    pub const fn new(name: &'static str) -> Self {
+        let bytes = name.as_bytes();
+        assert!(is_valid_name(bytes), "invalid header name");
+
+        Self {
+            name,
+            hash: ngx_hash_key_lc(bytes),
+        }
+    }
+
+    /// Returns the header name in canonical case.
+    pub const fn as_str(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the hash of the lowercase header name.
+    pub const fn hash(&self) -> ngx_uint_t {
+        self.hash
+    }
+
+    /// Checks if the name matches the specified string, ignoring ASCII case.
+    pub fn eq_ignore_case(&self, other: impl AsRef<[u8]>) -> bool {
+        self.name.as_bytes().eq_ignore_ascii_case(other.as_ref())
+    }
+
+    /// Checks if the header entry has this name.
+    ///
+    /// Compares the hash first and then the name. Intended for the request headers, where the
+    /// `hash` field is always set by the parser. Response headers added by the NGINX modules
+    /// often use `1` as a hash value and should be compared by name.
+    pub fn matches(&self, elt: &ngx_table_elt_t) -> bool {
+        elt.hash == self.hash && self.eq_ignore_case(elt.key.as_bytes())
+    }
+}
+
+impl AsRef<[u8]> for StaticHeaderName {
+    fn as_ref(&self) -> &[u8] {
+        self.name.as_bytes()
+    }
+}
+
+impl AsRef<str> for StaticHeaderName {
+    fn as_ref(&self) -> &str {
+        self.name
+    }
+}
+
+impl PartialEq for StaticHeaderName {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.eq_ignore_case(other.name)
+    }
+}
+
+impl Eq for StaticHeaderName {}
+
+impl fmt::Debug for StaticHeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StaticHeaderName").field(&self.name).finish()
+    }
+}
+
+impl fmt::Display for StaticHeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// A borrowed HTTP header field name with a precomputed hash.
+///
+/// The dynamic counterpart of [StaticHeaderName], e.g. for the names from the configuration. The
+/// names are compared ignoring ASCII case, and the hash is the same as for the parsed request
+/// headers.
+#[derive(Clone, Copy)]
+pub struct HeaderName<'a> {
+    name: &'a NgxStr,
+    hash: ngx_uint_t,
+}
+
+impl<'a> HeaderName<'a> {
+    /// Creates a new header name and computes its hash.
+    pub fn new(name: &'a [u8]) -> Result<Self, HeaderError> {
+        if !is_valid_name(name) {
+            return Err(HeaderError::InvalidName);
+        }
+
+        Ok(Self {
+            name: NgxStr::from_bytes(name),
+            hash: ngx_hash_key_lc(name),
+        })
+    }
+
+    /// Returns the name of the header entry.
+    pub fn from_elt(elt: &'a ngx_table_elt_t) -> Self {
+        // SAFETY: the key of a header entry lives as long as the entry.
+        let name = unsafe { NgxStr::from_ngx_str(elt.key) };
+        Self {
+            name,
+            hash: ngx_hash_key_lc(name.as_bytes()),
+        }
+    }
+
+    /// Returns the header name.
+    pub fn as_ngx_str(&self) -> &'a NgxStr {
+        self.name
+    }
+
+    /// Returns the header name as bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.name.as_bytes()
+    }
+
+    /// Returns the hash of the lowercase header name.
+    pub fn hash(&self) -> ngx_uint_t {
+        self.hash
+    }
+
+    /// Checks if the name matches the specified string, ignoring ASCII case.
+    pub fn eq_ignore_case(&self, other: impl AsRef<[u8]>) -> bool {
+        self.name.eq_ignore_ascii_case(other)
+    }
+
+    /// Checks if the header entry has this name.
+    ///
+    /// See [StaticHeaderName::matches].
+    pub fn matches(&self, elt: &ngx_table_elt_t) -> bool {
+        elt.hash == self.hash && self.eq_ignore_case(elt.key.as_bytes())
+    }
+}
+
+impl From<StaticHeaderName> for HeaderName<'static> {
+    fn from(value: StaticHeaderName) -> Self {
+        Self {
+            name: NgxStr::from_bytes(value.name.as_bytes()),
+            hash: value.hash,
+        }
+    }
+}
+
+impl AsRef<[u8]> for HeaderName<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.name.as_bytes()
+    }
+}
+
+impl PartialEq for HeaderName<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.eq_ignore_case(other.name)
+    }
+}
+
+impl Eq for HeaderName<'_> {}
+
+impl PartialEq<StaticHeaderName> for HeaderName<'_> {
+    fn eq(&self, other: &StaticHeaderName) -> bool {
+        self.hash == other.hash && self.eq_ignore_case(other.name)
+    }
+}
+
+impl hash::Hash for HeaderName<'_> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        // consistent with the case-insensitive Eq
+        state.write_usize(self.hash as usize);
+    }
+}
+
+impl fmt::Debug for HeaderName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HeaderName").field(&self.name).finish()
+    }
+}
+
+impl fmt::Display for HeaderName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.name, f)
+    }
+}
+
+/// An error returned when setting a header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The header name is empty or contains characters not allowed in a token.
+    InvalidName,
+    /// The header value contains CR, LF or other control characters.
+    InvalidValue,
+    /// Memory allocation failed.
+    Alloc,
+}
+
+impl error::Error for HeaderError {}
+
+impl From<AllocError> for HeaderError {
+    fn from(_: AllocError) -> Self {
+        HeaderError::Alloc
+    }
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::InvalidName => f.write_str("invalid header name"),
+            HeaderError::InvalidValue => f.write_str("invalid header value"),
+            HeaderError::Alloc => f.write_str("header allocation failed"),
+        }
+    }
+}
+
+/// Checks if the bytes are a valid header field name (RFC 9110, Section 5.1).
+///
+/// A valid name is a non-empty `token`, and cannot contain whitespace, separators or control
+/// characters.
+pub const fn is_valid_name(name: &[u8]) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < name.len() {
+        if !is_token_char(name[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Checks if the bytes are a valid header field value (RFC 9110, Section 5.5).
+///
+/// Any visible ASCII characters, spaces, horizontal tabs and non-ASCII bytes are allowed. CR, LF,
+/// NUL and other control characters are rejected, as the value could otherwise terminate the
+/// header line and inject additional headers or a response body.
+pub const fn is_valid_value(value: &[u8]) -> bool {
+    let mut i = 0;
+    while i < value.len() {
+        if !is_value_char(value[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Validates a header field before adding it to a request or a response.
+///
+/// Modules can use this to check user-supplied values, e.g. from variables or configuration,
+/// before use. All the header-setting methods of [Request](crate::http::Request) and
+/// [Headers](crate::http::Headers) perform the same checks.
+pub fn validate(name: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<(), HeaderError> {
+    if !is_valid_name(name.as_ref()) {
+        return Err(HeaderError::InvalidName);
+    }
+
+    if !is_valid_value(value.as_ref()) {
+        return Err(HeaderError::InvalidValue);
+    }
+
+    Ok(())
+}
+
+/// Checks if the character is allowed in a `token` (RFC 9110, Section 5.6.2).
+const fn is_token_char(c: u8) -> bool {
+    matches!(c,
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`'
+        | b'|' | b'~' | b'0'..=b'9' | b'A'..=b'Z' | b'a'..=b'z')
+}
+
+/// Checks if the character is allowed in a `field-value` (RFC 9110, Section 5.5).
+const fn is_value_char(c: u8) -> bool {
+    // VCHAR / obs-text / SP / HTAB
+    matches!(c, b'\t' | b' '..=b'~' | 0x80..=0xff)
+}
+
+/// A range in the `Range` request header (RFC 9110, Section 14.1.2).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `first-last`, both positions inclusive.
+    Bounded(u64, u64),
+    /// `first-`, from the position to the end of the representation.
+    From(u64),
+    /// `-length`, the final bytes of the representation.
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses a single `range-spec`.
+    pub fn parse(value: impl AsRef<[u8]>) -> Result<Self, ParseError> {
+        let value = value.as_ref().trim_ascii();
+        let dash = value.iter().position(|&x| x == b'-').ok_or(ParseError)?;
+        let (first, last) = (&value[..dash], &value[dash + 1..]);
+
+        match (parse_u64(first), parse_u64(last)) {
+            (Some(first), Some(last)) if first <= last => Ok(ByteRange::Bounded(first, last)),
+            (Some(first), None) if last.is_empty() => Ok(ByteRange::From(first)),
+            (None, Some(length)) if first.is_empty() => Ok(ByteRange::Suffix(length)),
+            _ => Err(ParseError),
+        }
+    }
+
+    /// Resolves the range against the length of the representation.
+    ///
+    /// Returns the half-open range of the selected bytes, or `None` if the range is not
+    /// satisfiable.
+    pub fn resolve(&self, len: u64) -> Option<Range<u64>> {
+        let range = match *self {
+            ByteRange::Bounded(first, last) => first..last.saturating_add(1).min(len),
+            ByteRange::From(first) => first..len,
+            ByteRange::Suffix(length) => len.saturating_sub(length)..len,
+        };
+        (range.start < range.end).then_some(range)
+    }
+}
+
+/// An iterator over the ranges of a `Range` request header.
+///
+/// Example:
+/// ```
+/// # use ngx::http::header::{ByteRange, ByteRanges};
+/// let ranges = ByteRanges::parse(b"bytes=0-499, -500").unwrap();
+/// assert_eq!(
+///     ranges.collect::<Result<Vec<_>, _>>(),
+///     Ok(vec![ByteRange::Bounded(0, 499), ByteRange::Suffix(500)])
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct ByteRanges<'a> {
+    specs: core::slice::Split<'a, u8, fn(&u8) -> bool>,
+}
+
+impl<'a> ByteRanges<'a> {
+    /// Parses the value of a `Range` header.
+    ///
+    /// Only the `bytes` unit is accepted. The individual ranges are parsed while iterating.
+    pub fn parse(value: &'a [u8]) -> Result<Self, ParseError> {
+        let value = value.trim_ascii();
+        let (unit, specs) = value
+            .iter()
+            .position(|&x| x == b'=')
+            .map(|x| (&value[..x], &value[x + 1..]))
+            .ok_or(ParseError)?;
+
+        if !unit.trim_ascii().eq_ignore_ascii_case(b"bytes") {
+            return Err(ParseError);
+        }
+
+        Ok(Self {
+            specs: specs.split(is_comma as fn(&u8) -> bool),
+        })
+    }
+}
+
+impl Iterator for ByteRanges<'_> {
+    type Item = Result<ByteRange, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // empty list elements are allowed and ignored
+        let spec = self.specs.find(|x| !x.trim_ascii().is_empty())?;
+        Some(ByteRange::parse(spec))
+    }
+}
+
+/// The credentials in an `Authorization` request header (RFC 9110, Section 11.6.2).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Authorization<'a> {
+    scheme: &'a NgxStr,
+    credentials: &'a NgxStr,
+}
+
+impl<'a> Authorization<'a> {
+    /// Parses the value of an `Authorization` header.
+    pub fn parse(value: &'a [u8]) -> Result<Self, ParseError> {
+        let value = value.trim_ascii();
+        let (scheme, credentials) = match value.iter().position(|&x| x == b' ' || x == b'\t') {
+            Some(x) => (&value[..x], value[x..].trim_ascii_start()),
+            None => (value, &value[value.len()..]),
+        };
+
+        if !is_valid_name(scheme) {
+            return Err(ParseError);
+        }
+
+        Ok(Self {
+            scheme: NgxStr::from_bytes(scheme),
+            credentials: NgxStr::from_bytes(credentials),
+        })
+    }
+
+    /// Returns the authentication scheme, e.g. `Basic` or `Bearer`.
+    pub fn scheme(&self) -> &'a NgxStr {
+        self.scheme
+    }
+
+    /// Returns `true` if the scheme matches `name`, ignoring ASCII case.
+    pub fn is_scheme(&self, name: impl AsRef<[u8]>) -> bool {
+        self.scheme.eq_ignore_ascii_case(name)
+    }
+
+    /// Returns the credentials following the scheme, e.g. a token. May be empty.
+    pub fn credentials(&self) -> &'a NgxStr {
+        self.credentials
+    }
+}
+
+fn is_comma(c: &u8) -> bool {
+    *c == b','
+}
+
+fn parse_u64(value: &[u8]) -> Option<u64> {
+    if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    value.iter().try_fold(0u64, |acc, &x| {
+        acc.checked_mul(10)?.checked_add(u64::from(x - b'0'))
+    })
+}
+
+macro_rules! standard_headers {
+    (
+        $(#[$list_attr:meta])*
+        $list:ident;
+
+        $( $name:ident => $value:literal; )+
+    ) => {
+        $(
+            #[doc = concat!("`", $value, "`")]
+            pub const $name: StaticHeaderName = StaticHeaderName::new($value);
+        )+
+
+        $(#[$list_attr])*
+        pub const $list: &[StaticHeaderName] = &[ $( $name, )+ ];
+    };
+}
+
+standard_headers! {
+    /// Permanent HTTP field names from the IANA registry.
+    IANA_HEADERS;
+
+    A_IM => "A-IM";
+    ACCEPT => "Accept";
+    ACCEPT_CH => "Accept-CH";
+    ACCEPT_CHARSET => "Accept-Charset";
+    ACCEPT_DATETIME => "Accept-Datetime";
+    ACCEPT_ENCODING => "Accept-Encoding";
+    ACCEPT_LANGUAGE => "Accept-Language";
+    ACCEPT_PATCH => "Accept-Patch";
+    ACCEPT_POST => "Accept-Post";
+    ACCEPT_RANGES => "Accept-Ranges";
+    ACCEPT_SIGNATURE => "Accept-Signature";
+    ACCESS_CONTROL_ALLOW_CREDENTIALS => "Access-Control-Allow-Credentials";
+    ACCESS_CONTROL_ALLOW_HEADERS => "Access-Control-Allow-Headers";
+    ACCESS_CONTROL_ALLOW_METHODS => "Access-Control-Allow-Methods";
+    ACCESS_CONTROL_ALLOW_ORIGIN => "Access-Control-Allow-Origin";
+    ACCESS_CONTROL_EXPOSE_HEADERS => "Access-Control-Expose-Headers";
+    ACCESS_CONTROL_MAX_AGE => "Access-Control-Max-Age";
+    ACCESS_CONTROL_REQUEST_HEADERS => "Access-Control-Request-Headers";
+    ACCESS_CONTROL_REQUEST_METHOD => "Access-Control-Request-Method";
+    AGE => "Age";
+    ALLOW => "Allow";
+    ALPN => "ALPN";
+    ALT_SVC => "Alt-Svc";
+    ALT_USED => "Alt-Used";
+    AUTHENTICATION_INFO => "Authentication-Info";
+    AUTHORIZATION => "Authorization";
+    CACHE_CONTROL => "Cache-Control";
+    CACHE_STATUS => "Cache-Status";
+    CAL_MANAGED_ID => "Cal-Managed-ID";
+    CALDAV_TIMEZONES => "CalDAV-Timezones";
+    CDN_CACHE_CONTROL => "CDN-Cache-Control";
+    CDN_LOOP => "CDN-Loop";
+    CERT_NOT_AFTER => "Cert-Not-After";
+    CERT_NOT_BEFORE => "Cert-Not-Before";
+    CLEAR_SITE_DATA => "Clear-Site-Data";
+    CLIENT_CERT => "Client-Cert";
+    CLIENT_CERT_CHAIN => "Client-Cert-Chain";
+    CLOSE => "Close";
+    CONNECTION => "Connection";
+    CONTENT_DIGEST => "Content-Digest";
+    CONTENT_DISPOSITION => "Content-Disposition";
+    CONTENT_ENCODING => "Content-Encoding";
+    CONTENT_LANGUAGE => "Content-Language";
+    CONTENT_LENGTH => "Content-Length";
+    CONTENT_LOCATION => "Content-Location";
+    CONTENT_RANGE => "Content-Range";
+    CONTENT_SECURITY_POLICY => "Content-Security-Policy";
+    CONTENT_SECURITY_POLICY_REPORT_ONLY => "Content-Security-Policy-Report-Only";
+    CONTENT_TYPE => "Content-Type";
+    COOKIE => "Cookie";
+    CROSS_ORIGIN_EMBEDDER_POLICY => "Cross-Origin-Embedder-Policy";
+    CROSS_ORIGIN_OPENER_POLICY => "Cross-Origin-Opener-Policy";
+    CROSS_ORIGIN_RESOURCE_POLICY => "Cross-Origin-Resource-Policy";
+    DASL => "DASL";
+    DATE => "Date";
+    DAV => "DAV";
+    DELTA_BASE => "Delta-Base";
+    DEPTH => "Depth";
+    DESTINATION => "Destination";
+    DPOP => "DPoP";
+    DPOP_NONCE => "DPoP-Nonce";
+    EARLY_DATA => "Early-Data";
+    ETAG => "ETag";
+    EXPECT => "Expect";
+    EXPIRES => "Expires";
+    FORWARDED => "Forwarded";
+    FROM => "From";
+    HOBAREG => "Hobareg";
+    HOST => "Host";
+    IF => "If";
+    IF_MATCH => "If-Match";
+    IF_MODIFIED_SINCE => "If-Modified-Since";
+    IF_NONE_MATCH => "If-None-Match";
+    IF_RANGE => "If-Range";
+    IF_SCHEDULE_TAG_MATCH => "If-Schedule-Tag-Match";
+    IF_UNMODIFIED_SINCE => "If-Unmodified-Since";
+    IM => "IM";
+    INCLUDE_REFERRED_TOKEN_BINDING_ID => "Include-Referred-Token-Binding-ID";
+    KEEP_ALIVE => "Keep-Alive";
+    LABEL => "Label";
+    LAST_EVENT_ID => "Last-Event-ID";
+    LAST_MODIFIED => "Last-Modified";
+    LINK => "Link";
+    LOCATION => "Location";
+    LOCK_TOKEN => "Lock-Token";
+    MAX_FORWARDS => "Max-Forwards";
+    MEMENTO_DATETIME => "Memento-Datetime";
+    METER => "Meter";
+    MIME_VERSION => "MIME-Version";
+    NEGOTIATE => "Negotiate";
+    NEL => "NEL";
+    ODATA_ENTITYID => "OData-EntityId";
+    ODATA_ISOLATION => "OData-Isolation";
+    ODATA_MAXVERSION => "OData-MaxVersion";
+    ODATA_VERSION => "OData-Version";
+    OPTIONAL_WWW_AUTHENTICATE => "Optional-WWW-Authenticate";
+    ORDERING_TYPE => "Ordering-Type";
+    ORIGIN => "Origin";
+    ORIGIN_AGENT_CLUSTER => "Origin-Agent-Cluster";
+    OSCORE => "OSCORE";
+    OSLC_CORE_VERSION => "OSLC-Core-Version";
+    OVERWRITE => "Overwrite";
+    PING_FROM => "Ping-From";
+    PING_TO => "Ping-To";
+    POSITION => "Position";
+    PREFER => "Prefer";
+    PREFERENCE_APPLIED => "Preference-Applied";
+    PRIORITY => "Priority";
+    PROXY_AUTHENTICATE => "Proxy-Authenticate";
+    PROXY_AUTHENTICATION_INFO => "Proxy-Authentication-Info";
+    PROXY_AUTHORIZATION => "Proxy-Authorization";
+    PROXY_STATUS => "Proxy-Status";
+    PUBLIC_KEY_PINS => "Public-Key-Pins";
+    PUBLIC_KEY_PINS_REPORT_ONLY => "Public-Key-Pins-Report-Only";
+    RANGE => "Range";
+    REDIRECT_REF => "Redirect-Ref";
+    REFERER => "Referer";
+    REFRESH => "Refresh";
+    REPLAY_NONCE => "Replay-Nonce";
+    REPR_DIGEST => "Repr-Digest";
+    RETRY_AFTER => "Retry-After";
+    SCHEDULE_REPLY => "Schedule-Reply";
+    SCHEDULE_TAG => "Schedule-Tag";
+    SEC_PURPOSE => "Sec-Purpose";
+    SEC_TOKEN_BINDING => "Sec-Token-Binding";
+    SEC_WEBSOCKET_ACCEPT => "Sec-WebSocket-Accept";
+    SEC_WEBSOCKET_EXTENSIONS => "Sec-WebSocket-Extensions";
+    SEC_WEBSOCKET_KEY => "Sec-WebSocket-Key";
+    SEC_WEBSOCKET_PROTOCOL => "Sec-WebSocket-Protocol";
+    SEC_WEBSOCKET_VERSION => "Sec-WebSocket-Version";
+    SERVER => "Server";
+    SERVER_TIMING => "Server-Timing";
+    SET_COOKIE => "Set-Cookie";
+    SIGNATURE => "Signature";
+    SIGNATURE_INPUT => "Signature-Input";
+    SLUG => "SLUG";
+    SOAPACTION => "SoapAction";
+    STATUS_URI => "Status-URI";
+    STRICT_TRANSPORT_SECURITY => "Strict-Transport-Security";
+    SUNSET => "Sunset";
+    SURROGATE_CAPABILITY => "Surrogate-Capability";
+    SURROGATE_CONTROL => "Surrogate-Control";
+    TCN => "TCN";
+    TE => "TE";
+    TIMEOUT => "Timeout";
+    TOPIC => "Topic";
+    TRACEPARENT => "Traceparent";
+    TRACESTATE => "Tracestate";
+    TRAILER => "Trailer";
+    TRANSFER_ENCODING => "Transfer-Encoding";
+    TTL => "TTL";
+    UPGRADE => "Upgrade";
+    URGENCY => "Urgency";
+    USER_AGENT => "User-Agent";
+    VARIANT_VARY => "Variant-Vary";
+    VARY => "Vary";
+    VIA => "Via";
+    WANT_CONTENT_DIGEST => "Want-Content-Digest";
+    WANT_REPR_DIGEST => "Want-Repr-Digest";
+    WWW_AUTHENTICATE => "WWW-Authenticate";
+    X_CONTENT_TYPE_OPTIONS => "X-Content-Type-Options";
+    X_FRAME_OPTIONS => "X-Frame-Options";
+}
+
+standard_headers! {
+    /// Non-standard field names commonly used with NGINX.
+    NGINX_HEADERS;
+
+    X_FORWARDED_FOR => "X-Forwarded-For";
+    X_FORWARDED_HOST => "X-Forwarded-Host";
+    X_FORWARDED_PROTO => "X-Forwarded-Proto";
+    X_REAL_IP => "X-Real-IP";
+    X_ACCEL_REDIRECT => "X-Accel-Redirect";
+    X_ACCEL_BUFFERING => "X-Accel-Buffering";
+    X_ACCEL_CHARSET => "X-Accel-Charset";
+    X_ACCEL_EXPIRES => "X-Accel-Expires";
+    X_ACCEL_LIMIT_RATE => "X-Accel-Limit-Rate";
+}
+
+/// Finds a known header name in [IANA_HEADERS] and [NGINX_HEADERS], ignoring ASCII case.
+pub fn find_known(name: impl AsRef<[u8]>) -> Option<&'static StaticHeaderName> {
+    let name = name.as_ref();
+    let hash = ngx_hash_key_lc(name);
+
+    IANA_HEADERS
+        .iter()
+        .chain(NGINX_HEADERS)
+        .find(|x| x.hash == hash && x.eq_ignore_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ngx_hash_strlow(s: &str) -> ngx_uint_t {
+        s.bytes().fold(0, |hash: ngx_uint_t, c| {
+            hash.wrapping_mul(31)
+                .wrapping_add(c.to_ascii_lowercase().into())
+        })
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(ngx_hash_key_lc(b""), 0);
+        assert_eq!(ngx_hash_key_lc(b"a"), b'a' as ngx_uint_t);
+
+        // the values of `ngx_hash_key_lc()` in NGINX
+        assert_eq!(ngx_hash_key_lc(b"Host"), 3208616);
+        assert_eq!(HOST.hash(), 3208616);
+        assert_eq!(ETAG.hash(), 3123477);
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            assert_eq!(ngx_hash_key_lc(b"Content-Length"), 17349979291134928570);
+            assert_eq!(CONTENT_LENGTH.hash(), 17349979291134928570);
+            assert_eq!(LOCATION.hash(), 3072802660277);
+        }
+
+        #[cfg(target_pointer_width = "32")]
+        {
+            assert_eq!(ngx_hash_key_lc(b"Content-Length"), 3162187450);
+            assert_eq!(CONTENT_LENGTH.hash(), 3162187450);
+            assert_eq!(LOCATION.hash(), 1901043637);
+        }
+
+        for h in IANA_HEADERS.iter().chain(NGINX_HEADERS) {
+            assert_eq!(h.hash(), ngx_hash_strlow(h.as_str()), "{h}");
+        }
+    }
+
+    #[test]
+    fn test_find_known() {
+        assert_eq!(ngx_hash_key_lc(b"CONTENT-TYPE"), CONTENT_TYPE.hash());
+
+        assert_eq!(find_known("content-type"), Some(&CONTENT_TYPE));
+        assert_eq!(find_known("X-REAL-IP"), Some(&X_REAL_IP));
+        assert_eq!(find_known("x-unknown"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(is_valid_name(b"X-Request-ID"));
+        assert!(!is_valid_name(b""));
+        assert!(!is_valid_name(b"X Request"));
+        assert!(!is_valid_name(b"X-Request:"));
+        assert!(!is_valid_name(b"X-Request\r\n"));
+
+        assert!(is_valid_value(b""));
+        assert!(is_valid_value(b"text/html; charset=utf-8"));
+        assert!(is_valid_value(b"a\tb"));
+        assert!(is_valid_value("\u{444}\u{430}\u{439}\u{43b}".as_bytes()));
+        assert!(!is_valid_value(b"a\r\nSet-Cookie: x=y"));
+        assert!(!is_valid_value(b"a\nb"));
+        assert!(!is_valid_value(b"a\0b"));
+        assert!(!is_valid_value(b"a\x7fb"));
+
+        assert_eq!(validate("Location", "/path"), Ok(()));
+        assert_eq!(validate("Location ", "/"), Err(HeaderError::InvalidName));
+        assert_eq!(
+            validate("Location", "/\r\n"),
+            Err(HeaderError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_header_name() {
+        let name = HeaderName::new(b"content-TYPE").unwrap();
+        assert_eq!(name.hash(), CONTENT_TYPE.hash());
+        assert_eq!(name, CONTENT_TYPE);
+        assert_eq!(name, HeaderName::from(CONTENT_TYPE));
+        assert!(name.eq_ignore_case("Content-Type"));
+
+        assert_eq!(HeaderName::new(b"X Request"), Err(HeaderError::InvalidName));
+    }
+
+    #[test]
+    fn test_byte_ranges() {
+        let ranges = ByteRanges::parse(b"bytes=0-99, ,200-, -50").unwrap();
+        assert!(ranges.eq([
+            Ok(ByteRange::Bounded(0, 99)),
+            Ok(ByteRange::From(200)),
+            Ok(ByteRange::Suffix(50)),
+        ]));
+
+        let mut ranges = ByteRanges::parse(b"Bytes = 1-2,x").unwrap();
+        assert_eq!(ranges.next(), Some(Ok(ByteRange::Bounded(1, 2))));
+        assert_eq!(ranges.next(), Some(Err(ParseError)));
+        assert_eq!(ranges.next(), None);
+
+        assert!(ByteRanges::parse(b"items=0-1").is_err());
+        assert!(ByteRanges::parse(b"0-1").is_err());
+        assert_eq!(ByteRange::parse("5-1"), Err(ParseError));
+        assert_eq!(ByteRange::parse("-"), Err(ParseError));
+        assert_eq!(ByteRange::parse("1-+2"), Err(ParseError));
+        assert_eq!(ByteRange::parse("99999999999999999999-"), Err(ParseError));
+
+        assert_eq!(ByteRange::Bounded(0, 99).resolve(50), Some(0..50));
+        assert_eq!(ByteRange::From(10).resolve(50), Some(10..50));
+        assert_eq!(ByteRange::Suffix(100).resolve(50), Some(0..50));
+        assert_eq!(ByteRange::From(50).resolve(50), None);
+        assert_eq!(ByteRange::Suffix(0).resolve(50), None);
+    }
+
+    #[test]
+    fn test_authorization() {
+        let auth = Authorization::parse(b"Bearer  abc.def ").unwrap();
+        assert!(auth.is_scheme("bearer"));
+        assert_eq!(auth.scheme(), "Bearer");
+        assert_eq!(auth.credentials(), "abc.def");
+
+        let auth = Authorization::parse(b"Negotiate").unwrap();
+        assert_eq!(auth.credentials(), "");
+
+        assert!(Authorization::parse(b"").is_err());
+        assert!(Authorization::parse(b"Basic: x").is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_name() {
+        let _ = StaticHeaderName::new("Invalid Name");
+    }
+}
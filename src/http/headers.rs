@@ -0,0 +1,166 @@
+use core::fmt;
+
+use crate::collections::list::{NgxList, NgxListIter, NgxListIterMut};
+use crate::core::NgxStr;
+use crate::ffi::{add_to_ngx_table, ngx_list_t, ngx_table_elt_t};
+use crate::http::header::{self, HeaderError};
+
+/// A view over a list of HTTP header fields, such as `headers_in.headers` or
+/// `headers_out.headers` of a request.
+///
+/// Header lookups compare names case-insensitively, using the `hash` of the lowercase name to skip
+/// the entries with a different name. Entries with zero `hash` are considered deleted and are
+/// skipped, following the convention of the NGINX header filters.
+#[repr(transparent)]
+pub struct Headers(NgxList<ngx_table_elt_t>);
+
+impl Headers {
+    /// Creates a header list reference from a pointer to [ngx_list_t].
+    ///
+    /// # Safety
+    ///
+    /// `list` is a valid pointer to an initialized list of `ngx_table_elt_t`.
+    pub unsafe fn from_ptr<'a>(list: *const ngx_list_t) -> &'a Self {
+        &*list.cast()
+    }
+
+    /// Creates a mutable header list reference from a pointer to [ngx_list_t].
+    ///
+    /// # Safety
+    ///
+    /// `list` is a valid pointer to an initialized list of `ngx_table_elt_t`, allocated from a pool
+    /// that outlives the headers.
+    pub unsafe fn from_ptr_mut<'a>(list: *mut ngx_list_t) -> &'a mut Self {
+        &mut *list.cast()
+    }
+
+    /// Returns the underlying list.
+    pub fn as_list(&self) -> &NgxList<ngx_table_elt_t> {
+        &self.0
+    }
+
+    /// Returns an iterator over the header entries.
+    pub fn iter(&self) -> HeadersIter<'_> {
+        HeadersIter(self.0.iter())
+    }
+
+    /// Returns a mutable iterator over the header entries.
+    pub fn iter_mut(&mut self) -> HeadersIterMut<'_> {
+        HeadersIterMut(self.0.iter_mut())
+    }
+
+    /// Returns the value of the first header with the specified name.
+    pub fn get(&self, name: impl AsRef<[u8]>) -> Option<&NgxStr> {
+        self.get_all(name).next()
+    }
+
+    /// Returns an iterator over the values of all the headers with the specified name.
+    ///
+    /// The hash of the lowercase name is compared first, and the name is only compared for the
+    /// entries with a matching hash or with the placeholder hash `1`, which NGINX modules often use
+    /// for the response headers.
+    pub fn get_all<'a>(&'a self, name: impl AsRef<[u8]> + 'a) -> impl Iterator<Item = &'a NgxStr> {
+        let hash = header::ngx_hash_key_lc(name.as_ref());
+
+        self.0.iter().filter_map(move |h| {
+            if h.hash == 0 || (h.hash != hash && h.hash != 1) {
+                return None;
+            }
+            // SAFETY: non-deleted entries always have valid key and value.
+            let (k, v) = unsafe { (NgxStr::from_ngx_str(h.key), NgxStr::from_ngx_str(h.value)) };
+            k.as_bytes()
+                .eq_ignore_ascii_case(name.as_ref())
+                .then_some(v)
+        })
+    }
+
+    /// Returns `true` if the list contains a header with the specified name.
+    pub fn contains(&self, name: impl AsRef<[u8]>) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Appends a header to the list.
+    ///
+    /// The key, value and lowercase key are copied to the memory pool of the list. Returns an error
+    /// without modifying the list if the key or the value is not valid, see [header::validate].
+    pub fn add(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) -> Result<&mut ngx_table_elt_t, HeaderError> {
+        header::validate(&key, &value)?;
+
+        let pool = unsafe { (*self.0.as_ptr()).pool };
+        // SAFETY: ngx_table_elt_t is a plain C structure, all zeroes is a valid value.
+        let elt = self.0.push(unsafe { core::mem::zeroed() })?;
+        // SAFETY: list pool is valid for the lifetime of the list.
+        if unsafe { add_to_ngx_table(elt, pool, key, value) }.is_none() {
+            // leave the slot in the list, but mark it as deleted
+            elt.hash = 0;
+            return Err(HeaderError::Alloc);
+        }
+        Ok(elt)
+    }
+
+    /// Removes all the headers with the specified name.
+    ///
+    /// The entries are marked as deleted. Returns `true` if any header was removed.
+    pub fn remove(&mut self, name: impl AsRef<[u8]>) -> bool {
+        let name = name.as_ref();
+        let mut found = false;
+
+        for h in self.iter_mut() {
+            // SAFETY: non-deleted entries always have valid key.
+            if unsafe { NgxStr::from_ngx_str(h.key) }
+                .as_bytes()
+                .eq_ignore_ascii_case(name)
+            {
+                h.hash = 0;
+                found = true;
+            }
+        }
+
+        found
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a NgxStr, &'a NgxStr);
+    type IntoIter = HeadersIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the header names and values.
+pub struct HeadersIter<'a>(NgxListIter<'a, ngx_table_elt_t>);
+
+impl<'a> Iterator for HeadersIter<'a> {
+    type Item = (&'a NgxStr, &'a NgxStr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let h = self.0.find(|h| h.hash != 0)?;
+        // SAFETY: non-deleted entries always have valid key and value.
+        unsafe { Some((NgxStr::from_ngx_str(h.key), NgxStr::from_ngx_str(h.value))) }
+    }
+}
+
+/// A mutable iterator over the header entries.
+///
+/// Setting `hash` to zero marks the entry as deleted.
+pub struct HeadersIterMut<'a>(NgxListIterMut<'a, ngx_table_elt_t>);
+
+impl<'a> Iterator for HeadersIterMut<'a> {
+    type Item = &'a mut ngx_table_elt_t;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find(|h| h.hash != 0)
+    }
+}
@@ -0,0 +1,98 @@
+use core::fmt;
+use core::ptr;
+
+use crate::core::NgxStr;
+use crate::ffi::{ngx_hash_find, ngx_http_core_loc_conf_t, ngx_str_t};
+use crate::http::header::ngx_hash_key_lc;
+use crate::http::{HttpModuleLocationConf, NgxHttpCoreModule, Request};
+
+/// The maximum length of an extension accepted by [MimeTypes::find].
+const MAX_EXTENSION_LEN: usize = 64;
+
+/// The MIME types configured with the [types] and `default_type` directives.
+///
+/// The lookup is the same as in `ngx_http_set_content_type`: the extension is matched without
+/// the leading dot and ignoring the case.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::http::header::HeaderError;
+/// # use ngx::http::{MimeTypes, Request};
+/// # fn example(request: &mut Request) -> Result<(), HeaderError> {
+/// if let Some(types) = MimeTypes::for_request(request) {
+///     request.set_content_type(types.content_type(b"html"))?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [types]: https://nginx.org/en/docs/http/ngx_http_core_module.html#types
+#[derive(Clone, Copy)]
+pub struct MimeTypes<'a>(&'a ngx_http_core_loc_conf_t);
+
+impl<'a> MimeTypes<'a> {
+    /// Creates a wrapper over the types of the `ngx_http_core_module` location configuration.
+    ///
+    /// The types hash is built when the configuration is merged, so the wrapper should not be
+    /// used during the configuration parsing.
+    pub fn from_location_conf(clcf: &'a ngx_http_core_loc_conf_t) -> Self {
+        Self(clcf)
+    }
+
+    /// Returns the types applicable to the request.
+    pub fn for_request(request: &Request) -> Option<MimeTypes<'static>> {
+        NgxHttpCoreModule::location_conf(request).map(MimeTypes)
+    }
+
+    /// Returns the MIME type for the file name extension, without the leading dot.
+    pub fn find(&self, ext: impl AsRef<[u8]>) -> Option<&'a NgxStr> {
+        let ext = ext.as_ref();
+        if ext.is_empty() || ext.len() > MAX_EXTENSION_LEN {
+            return None;
+        }
+
+        // the keys of the types hash are lowercase
+        let mut buf = [0u8; MAX_EXTENSION_LEN];
+        let lowcase = &mut buf[..ext.len()];
+        lowcase.copy_from_slice(ext);
+        lowcase.make_ascii_lowercase();
+
+        let key = ngx_hash_key_lc(lowcase);
+        let hash = ptr::addr_of!(self.0.types_hash).cast_mut();
+        // SAFETY: the hash is not modified by the lookup.
+        let value = unsafe { ngx_hash_find(hash, key, lowcase.as_mut_ptr(), lowcase.len()) };
+
+        // SAFETY: the values of the types hash are strings allocated from the configuration pool.
+        let value = unsafe { value.cast::<ngx_str_t>().as_ref()? };
+        Some(unsafe { NgxStr::from_ngx_str(*value) })
+    }
+
+    /// Returns the MIME type for the file name extension, or the `default_type`.
+    pub fn content_type(&self, ext: impl AsRef<[u8]>) -> &'a NgxStr {
+        self.find(ext).unwrap_or_else(|| self.default_type())
+    }
+
+    /// Returns the `default_type` value.
+    pub fn default_type(&self) -> &'a NgxStr {
+        // SAFETY: the value is allocated from the configuration pool or static.
+        unsafe { NgxStr::from_ngx_str(self.0.default_type) }
+    }
+}
+
+impl fmt::Debug for MimeTypes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MimeTypes")
+            .field("default_type", &self.default_type())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Request {
+    /// Returns the MIME type configured with the `types` directive for the file name extension,
+    /// without the leading dot.
+    ///
+    /// See [MimeTypes] for the details.
+    pub fn content_type_for_extension(&self, ext: impl AsRef<[u8]>) -> Option<&NgxStr> {
+        MimeTypes::for_request(self)?.find(ext)
+    }
+}
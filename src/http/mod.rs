@@ -1,10 +1,57 @@
+#[cfg(feature = "async")]
+pub mod client;
+pub mod header;
+#[cfg(feature = "alloc")]
+pub mod signing;
+
+#[cfg(unix)]
+mod access_log;
+mod assets;
+#[cfg(feature = "std")]
+mod body_reader;
+mod complex_value;
 mod conf;
+mod continuation;
+mod error;
+mod filter;
+mod flow;
+mod headers;
+mod mime;
 mod module;
+mod phase;
 mod request;
+mod request_body;
+mod request_key;
+mod response_writer;
+mod server_name;
 mod status;
+#[cfg(feature = "async")]
+mod subrequest;
 mod upstream;
+mod variable;
 
+#[cfg(unix)]
+pub use access_log::*;
+pub use assets::*;
+#[cfg(feature = "std")]
+pub use body_reader::*;
+pub use complex_value::*;
 pub use conf::*;
+pub use continuation::*;
+pub use error::*;
+pub use filter::*;
+pub use flow::*;
+pub use headers::*;
+pub use mime::*;
 pub use module::*;
+pub use phase::*;
 pub use request::*;
+pub use request_body::*;
+pub use request_key::*;
+pub use response_writer::*;
+pub use server_name::*;
 pub use status::*;
+#[cfg(feature = "async")]
+pub use subrequest::*;
+pub use upstream::*;
+pub use variable::*;
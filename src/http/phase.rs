@@ -0,0 +1,240 @@
+use core::error;
+use core::fmt;
+
+use crate::core::Status;
+use crate::ffi::*;
+use crate::http::{HTTPStatus, HttpModuleMainConf, NgxHttpCoreModule, Request};
+
+/// Result of a phase handler defined with [`http_phase_handler`](crate::http_phase_handler).
+///
+/// NGINX interprets the return codes of a phase handler differently depending on the phase:
+/// `NGX_OK` skips the remaining handlers of a phase, `NGX_DECLINED` passes the request to the
+/// next handler, and a content handler must increment the request reference count before
+/// suspending. The variants describe the intent instead, and [PhaseResult::resolve] converts them
+/// to the code expected by the current phase.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::http::{HTTPStatus, PhaseResult, Request};
+/// # use ngx::http_phase_handler;
+/// http_phase_handler!(access_handler, |request: &mut Request| {
+///     match request.user_agent() {
+///         Some(ua) if ua.as_bytes().starts_with(b"curl") => {
+///             PhaseResult::Finalize(HTTPStatus::FORBIDDEN)
+///         }
+///         _ => PhaseResult::Continue,
+///     }
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhaseResult {
+    /// Passes the request to the next handler or phase.
+    Continue,
+    /// Suspends the request processing.
+    ///
+    /// The handler is called again when the request write event is posted or triggered, e.g. with
+    /// `ngx_post_event(r->connection->write, &ngx_posted_events)` once an asynchronous operation
+    /// completes.
+    Again,
+    /// Finalizes the request with the status.
+    ///
+    /// If the response header is not sent, NGINX generates the response for the status, e.g. an
+    /// error page or a redirect to the `Location` set in the response headers. Otherwise the
+    /// response is considered complete.
+    Finalize(HTTPStatus),
+    /// Finalizes the request with an error status.
+    ///
+    /// If the response header is already sent and the error page cannot be generated, the
+    /// connection is closed.
+    Error(HTTPStatus),
+}
+
+impl PhaseResult {
+    /// Converts the result to the return code of a phase handler, updating the request state as
+    /// necessary.
+    pub fn resolve(self, request: &mut Request) -> Status {
+        match self {
+            PhaseResult::Continue => Status::NGX_DECLINED,
+            PhaseResult::Again if is_content_phase(request) => {
+                let r = request.as_mut();
+                // Keep the request alive when the content phase checker finalizes it with
+                // NGX_DONE, and restart the phase on the next write event.
+                // SAFETY: the main request pointer is always valid.
+                unsafe { (*r.main).set_count((*r.main).count() + 1) };
+                r.write_event_handler = Some(ngx_http_core_run_phases);
+                Status::NGX_DONE
+            }
+            PhaseResult::Again => Status::NGX_AGAIN,
+            PhaseResult::Finalize(_) if request.as_ref().header_sent() != 0 => {
+                // Any code below NGX_HTTP_SPECIAL_RESPONSE other than NGX_OK is a normal
+                // finalization in every phase, while NGX_OK advances to the next phase.
+                HTTPStatus::OK.into()
+            }
+            PhaseResult::Finalize(status) if !has_special_response(status) => {
+                // NGINX only generates the response for the error, redirect and empty statuses.
+                request.set_status(status);
+                request.as_mut().set_header_only(1);
+                let rc = request.send_header();
+                if rc == Status::NGX_ERROR || rc.0 > Status::NGX_OK.0 {
+                    return rc;
+                }
+                HTTPStatus::OK.into()
+            }
+            PhaseResult::Finalize(status) => status.into(),
+            PhaseResult::Error(_) if request.as_ref().header_sent() != 0 => Status::NGX_ERROR,
+            PhaseResult::Error(status) => status.into(),
+        }
+    }
+}
+
+/// HTTP request processing phases, as in `ngx_http_phases`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// `NGX_HTTP_POST_READ_PHASE`, the first phase after reading the request header.
+    PostRead,
+    /// `NGX_HTTP_SERVER_REWRITE_PHASE`, the rewrite directives in the server block.
+    ServerRewrite,
+    /// `NGX_HTTP_FIND_CONFIG_PHASE`, the location lookup. Does not accept module handlers.
+    FindConfig,
+    /// `NGX_HTTP_REWRITE_PHASE`, the rewrite directives in the location block.
+    Rewrite,
+    /// `NGX_HTTP_POST_REWRITE_PHASE`, the redirect to a new location after rewrite. Does not
+    /// accept module handlers.
+    PostRewrite,
+    /// `NGX_HTTP_PREACCESS_PHASE`, e.g. the request and connection limits.
+    Preaccess,
+    /// `NGX_HTTP_ACCESS_PHASE`, the access checks, subject to the `satisfy` directive.
+    Access,
+    /// `NGX_HTTP_POST_ACCESS_PHASE`, the processing of the access checks result. Does not accept
+    /// module handlers.
+    PostAccess,
+    /// `NGX_HTTP_PRECONTENT_PHASE`, e.g. `try_files` and `mirror`.
+    Precontent,
+    /// `NGX_HTTP_CONTENT_PHASE`, the response generation.
+    Content,
+    /// `NGX_HTTP_LOG_PHASE`, the request logging.
+    Log,
+}
+
+impl Phase {
+    /// Returns the corresponding `ngx_http_phases` value.
+    pub const fn as_raw(self) -> ngx_http_phases {
+        match self {
+            Phase::PostRead => ngx_http_phases_NGX_HTTP_POST_READ_PHASE,
+            Phase::ServerRewrite => ngx_http_phases_NGX_HTTP_SERVER_REWRITE_PHASE,
+            Phase::FindConfig => ngx_http_phases_NGX_HTTP_FIND_CONFIG_PHASE,
+            Phase::Rewrite => ngx_http_phases_NGX_HTTP_REWRITE_PHASE,
+            Phase::PostRewrite => ngx_http_phases_NGX_HTTP_POST_REWRITE_PHASE,
+            Phase::Preaccess => ngx_http_phases_NGX_HTTP_PREACCESS_PHASE,
+            Phase::Access => ngx_http_phases_NGX_HTTP_ACCESS_PHASE,
+            Phase::PostAccess => ngx_http_phases_NGX_HTTP_POST_ACCESS_PHASE,
+            Phase::Precontent => ngx_http_phases_NGX_HTTP_PRECONTENT_PHASE,
+            Phase::Content => ngx_http_phases_NGX_HTTP_CONTENT_PHASE,
+            Phase::Log => ngx_http_phases_NGX_HTTP_LOG_PHASE,
+        }
+    }
+
+    /// Checks if the phase runs the handlers registered by modules.
+    ///
+    /// The handlers added to the other phases are silently ignored by
+    /// `ngx_http_init_phase_handlers`.
+    pub const fn accepts_handlers(self) -> bool {
+        !matches!(
+            self,
+            Phase::FindConfig | Phase::PostRewrite | Phase::PostAccess
+        )
+    }
+}
+
+/// The signature of a phase handler, e.g. defined with
+/// [`http_phase_handler`](crate::http_phase_handler).
+pub type PhaseHandler = unsafe extern "C" fn(*mut ngx_http_request_t) -> ngx_int_t;
+
+/// An error returned by [Phases::register].
+#[derive(Debug, PartialEq, Eq)]
+pub enum PhaseError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The http core module configuration is not available.
+    NoCoreConf,
+    /// The phase does not accept module handlers.
+    Unsupported(Phase),
+}
+
+impl error::Error for PhaseError {}
+
+impl fmt::Display for PhaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhaseError::Alloc => f.write_str("phase handler allocation failed"),
+            PhaseError::NoCoreConf => f.write_str("http core module configuration not found"),
+            PhaseError::Unsupported(phase) => write!(f, "{phase:?} phase does not accept handlers"),
+        }
+    }
+}
+
+/// Phase handler registration.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::{ngx_conf_t, ngx_int_t};
+/// # use ngx::http::{Phase, PhaseResult, Phases, Request};
+/// # use ngx::http_phase_handler;
+/// http_phase_handler!(access_handler, |_request: &mut Request| PhaseResult::Continue);
+///
+/// unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+///     match Phases::register(unsafe { &mut *cf }, Phase::Access, access_handler) {
+///         Ok(()) => Status::NGX_OK.into(),
+///         Err(_) => Status::NGX_ERROR.into(),
+///     }
+/// }
+/// ```
+pub struct Phases;
+
+impl Phases {
+    /// Adds a handler to the phase.
+    ///
+    /// Must be called from the module `postconfiguration` handler, after the phase handler arrays
+    /// are initialized and before the phase engine is built. The handlers of a phase are called in
+    /// the reverse order of registration.
+    pub fn register(
+        cf: &mut ngx_conf_t,
+        phase: Phase,
+        handler: PhaseHandler,
+    ) -> Result<(), PhaseError> {
+        if !phase.accepts_handlers() {
+            return Err(PhaseError::Unsupported(phase));
+        }
+
+        let cmcf = NgxHttpCoreModule::main_conf_mut(cf).ok_or(PhaseError::NoCoreConf)?;
+        let handlers = &mut cmcf.phases[phase.as_raw() as usize].handlers;
+
+        // SAFETY: the handlers array is initialized with ngx_http_handler_pt elements in
+        // ngx_http_init_phases.
+        let h = unsafe { ngx_array_push(handlers) }.cast::<ngx_http_handler_pt>();
+        if h.is_null() {
+            return Err(PhaseError::Alloc);
+        }
+        unsafe { h.write(Some(handler)) };
+        Ok(())
+    }
+}
+
+/// Checks if NGINX generates a response for the status in `ngx_http_finalize_request`.
+fn has_special_response(status: HTTPStatus) -> bool {
+    status.0 >= NGX_HTTP_SPECIAL_RESPONSE as ngx_uint_t
+        || status == HTTPStatus::CREATED
+        || status == HTTPStatus::NO_CONTENT
+}
+
+/// Checks if the request is processed by the content phase handlers.
+fn is_content_phase(request: &Request) -> bool {
+    let Some(cmcf) = NgxHttpCoreModule::main_conf(request) else {
+        return false;
+    };
+    let r = request.as_ref();
+    // SAFETY: phase_handler is an index in the phase engine handlers while running the phases.
+    let ph = unsafe { &*cmcf.phase_engine.handlers.add(r.phase_handler as usize) };
+    ph.checker.map(|x| x as usize) == Some(ngx_http_core_content_phase as usize)
+}
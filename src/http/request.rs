@@ -1,24 +1,58 @@
 use core::error;
-use core::ffi::c_void;
+use core::ffi::{c_void, CStr};
 use core::fmt;
 use core::ptr::NonNull;
 use core::slice;
 use core::str::FromStr;
 
+use crate::allocator::AllocError;
 use crate::core::*;
 use crate::ffi::*;
+use crate::http::header::{self, Authorization, ByteRanges, HeaderError};
 use crate::http::status::*;
+use crate::http::{
+    Headers, HttpModule, HttpModuleLocationConf, NgxHttpCoreModule, VariableIndex, VariableValue,
+};
 
 /// Define a static request handler.
 ///
-/// Handlers are expected to take a single [`Request`] argument and return a [`Status`].
+/// Handlers are expected to take a single [`Request`] argument and return either a [`Status`] or
+/// a [`HandlerResult`]. The errors are logged and converted to the response status, see
+/// [`NgxError`].
+///
+/// A panic in the handler is logged and converted to `NGX_ERROR`, see [`crate::panic::catch`].
+/// The same applies to the other handler macros.
 #[macro_export]
 macro_rules! http_request_handler {
     ( $name: ident, $handler: expr ) => {
         extern "C" fn $name(r: *mut $crate::ffi::ngx_http_request_t) -> $crate::ffi::ngx_int_t {
-            let status: $crate::core::Status =
-                $handler(unsafe { &mut $crate::http::Request::from_ngx_http_request(r) });
-            status.0
+            $crate::panic::catch(module_path!(), stringify!($name), || {
+                let request = unsafe { $crate::http::Request::from_ngx_http_request(r) };
+                let result = $handler(&mut *request);
+                let status: $crate::core::Status =
+                    $crate::http::IntoHandlerStatus::into_handler_status(result, request);
+                status.0
+            })
+            .unwrap_or($crate::core::Status::NGX_ERROR.0)
+        }
+    };
+}
+
+/// Define a static phase handler.
+///
+/// Handlers are expected to take a single [`Request`] argument and return a
+/// [`PhaseResult`](crate::http::PhaseResult), which is converted to the return code expected by
+/// the current phase.
+#[macro_export]
+macro_rules! http_phase_handler {
+    ( $name: ident, $handler: expr ) => {
+        extern "C" fn $name(r: *mut $crate::ffi::ngx_http_request_t) -> $crate::ffi::ngx_int_t {
+            $crate::panic::catch(module_path!(), stringify!($name), || {
+                let request = unsafe { $crate::http::Request::from_ngx_http_request(r) };
+                let result: $crate::http::PhaseResult = $handler(&mut *request);
+                result.resolve(request).0
+            })
+            .unwrap_or($crate::core::Status::NGX_ERROR.0)
         }
     };
 }
@@ -34,7 +68,8 @@ macro_rules! http_subrequest_handler {
             data: *mut ::core::ffi::c_void,
             rc: $crate::ffi::ngx_int_t,
         ) -> $crate::ffi::ngx_int_t {
-            $handler(r, data, rc)
+            $crate::panic::catch(module_path!(), stringify!($name), || $handler(r, data, rc))
+                .unwrap_or($crate::core::Status::NGX_ERROR.0)
         }
     };
 }
@@ -52,11 +87,13 @@ macro_rules! http_variable_set {
             v: *mut $crate::ffi::ngx_variable_value_t,
             data: usize,
         ) {
-            $handler(
-                unsafe { &mut $crate::http::Request::from_ngx_http_request(r) },
-                v,
-                data,
-            );
+            let _ = $crate::panic::catch(module_path!(), stringify!($name), || {
+                $handler(
+                    unsafe { &mut $crate::http::Request::from_ngx_http_request(r) },
+                    v,
+                    data,
+                );
+            });
         }
     };
 }
@@ -75,16 +112,56 @@ macro_rules! http_variable_get {
             v: *mut $crate::ffi::ngx_variable_value_t,
             data: usize,
         ) -> $crate::ffi::ngx_int_t {
-            let status: $crate::core::Status = $handler(
-                unsafe { &mut $crate::http::Request::from_ngx_http_request(r) },
-                v,
-                data,
-            );
-            status.0
+            $crate::panic::catch(module_path!(), stringify!($name), || {
+                let status: $crate::core::Status = $handler(
+                    unsafe { &mut $crate::http::Request::from_ngx_http_request(r) },
+                    v,
+                    data,
+                );
+                status.0
+            })
+            .unwrap_or($crate::core::Status::NGX_ERROR.0)
         }
     };
 }
 
+/// Trait to define and access the request context of a module.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::ngx_module_t;
+/// # use ngx::http::{HttpModule, HttpModuleCtx, Request};
+/// # struct MyModule;
+/// # impl HttpModule for MyModule {
+/// #     fn module() -> &'static ngx_module_t { unimplemented!() }
+/// # }
+/// #[derive(Default)]
+/// struct RequestCtx {
+///     attempts: usize,
+/// }
+///
+/// unsafe impl HttpModuleCtx for MyModule {
+///     type Ctx = RequestCtx;
+/// }
+///
+/// fn handler(request: &mut Request) -> Status {
+///     let Ok(ctx) = request.module_ctx_or_default::<MyModule>() else {
+///         return Status::NGX_ERROR;
+///     };
+///     ctx.attempts += 1;
+///     Status::NGX_DECLINED
+/// }
+/// ```
+///
+/// # Safety
+/// Caller must ensure that type `HttpModuleCtx::Ctx` matches the context type stored by the
+/// module with [Request::set_module_ctx].
+pub unsafe trait HttpModuleCtx: HttpModule {
+    /// Type for the module request context
+    type Ctx: 'static;
+}
+
 /// Wrapper struct for an [`ngx_http_request_t`] pointer, providing methods for working with HTTP
 /// requests.
 ///
@@ -133,12 +210,114 @@ impl Request {
         core::ptr::eq(self, main)
     }
 
+    /// Is this an internal request?
+    ///
+    /// Subrequests and the requests redirected with an internal redirect, e.g. by `error_page`,
+    /// `try_files` or `index`, are internal. Such requests can access the `internal` locations.
+    pub fn is_internal(&self) -> bool {
+        self.0.internal() != 0
+    }
+
+    /// Returns the nesting level of a subrequest.
+    ///
+    /// The main request has depth 0, its subrequests have depth 1, and so on.
+    pub fn subrequest_depth(&self) -> usize {
+        let mut depth = 0;
+        let mut r: *const ngx_http_request_t = &self.0;
+        // SAFETY: the parent requests outlive their subrequests.
+        while let Some(parent) = unsafe { (*r).parent.as_ref() } {
+            depth += 1;
+            r = parent;
+        }
+        depth
+    }
+
     /// Request pool.
     pub fn pool(&self) -> Pool {
         // SAFETY: This request is allocated from `pool`, thus must be a valid pool.
         unsafe { Pool::from_ngx_pool(self.0.pool) }
     }
 
+    /// Opens a file for reading from the request pool.
+    ///
+    /// Applies the `open_file_cache`, `directio`, `read_ahead` and `disable_symlinks` settings of
+    /// the current location, in the same way as the static module.
+    pub fn open_file(&mut self, path: impl AsRef<[u8]>) -> Result<File, FileError> {
+        let clcf = NgxHttpCoreModule::location_conf(self).expect("http core loc conf");
+
+        let options = OpenOptions::new()
+            .cache(clcf.open_file_cache)
+            .valid(clcf.open_file_cache_valid)
+            .min_uses(clcf.open_file_cache_min_uses)
+            .errors(clcf.open_file_cache_errors != 0)
+            .events(clcf.open_file_cache_events != 0)
+            .directio(clcf.directio)
+            .read_ahead(clcf.read_ahead);
+
+        let r: *mut ngx_http_request_t = &mut self.0;
+        let clcf = core::ptr::from_ref(clcf).cast_mut();
+
+        options.open_with(&mut self.pool(), path.as_ref(), |name, of| {
+            if unsafe { ngx_http_set_disable_symlinks(r, clcf, name, of) } != NGX_OK as ngx_int_t {
+                return Err(FileError::Alloc);
+            }
+            Ok(())
+        })
+    }
+
+    /// Adds a handler to be called when the main request is finalized.
+    ///
+    /// Request cleanup handlers run from `ngx_http_free_request` or `ngx_http_terminate_request`,
+    /// before the request pool is destroyed and before any pool cleanup handlers. This makes them
+    /// suitable for aborting operations that still reference the request, e.g. pending timers or
+    /// network calls.
+    ///
+    /// The handlers are always attached to the main request and are called in the reverse order of
+    /// registration.
+    ///
+    /// See <https://nginx.org/en/docs/dev/development_guide.html#http_request_finalization>
+    pub fn add_cleanup<F>(&mut self, f: F) -> Result<(), AllocError>
+    where
+        F: FnOnce() + 'static,
+    {
+        let cln = unsafe { ngx_http_cleanup_add(&mut self.0, 0) };
+        if cln.is_null() {
+            return Err(AllocError);
+        }
+
+        // The closure is stored separately to ensure correct alignment.
+        let data = crate::allocator::allocate(f, &self.pool())?;
+
+        // SAFETY: cln is a valid pointer returned by ngx_http_cleanup_add. NGINX ignores the entries
+        // with no handler set.
+        unsafe {
+            (*cln).handler = Some(request_cleanup_handler::<F>);
+            (*cln).data = data.as_ptr().cast();
+        }
+
+        Ok(())
+    }
+
+    /// Spawns a task bound to the lifetime of the request.
+    ///
+    /// The task is cancelled when the main request is finalized, if it is still running at that
+    /// point. Dropping the task stops polling the future, so the future will not access the freed
+    /// request.
+    ///
+    /// See [crate::async_::spawn].
+    #[cfg(feature = "async")]
+    pub fn spawn<F>(&mut self, future: F) -> Result<(), AllocError>
+    where
+        F: core::future::Future<Output = ()> + 'static,
+    {
+        let (runnable, task) = crate::async_::spawn_unscheduled(future);
+        // The cleanup must be registered before the first poll, as the future can finalize the
+        // request synchronously.
+        self.add_cleanup(move || drop(task))?;
+        runnable.schedule();
+        Ok(())
+    }
+
     /// Returns the result as an `Option` if it exists, otherwise `None`.
     ///
     /// The option wraps an ngx_http_upstream_t instance, it will be none when the underlying NGINX
@@ -160,6 +339,11 @@ impl Request {
         self.0.connection
     }
 
+    /// Returns the information received with the PROXY protocol header on the client connection.
+    pub fn proxy_protocol(&self) -> Option<&ProxyProtocol> {
+        unsafe { ProxyProtocol::from_connection(self.connection()) }
+    }
+
     /// Pointer to a [`ngx_log_t`].
     ///
     /// [`ngx_log_t`]: https://nginx.org/en/docs/dev/development_guide.html#logging
@@ -189,6 +373,50 @@ impl Request {
         };
     }
 
+    /// Returns the request context of the module `M`.
+    pub fn module_ctx<M: HttpModuleCtx>(&self) -> Option<&M::Ctx> {
+        let ctx = self.get_module_ctx_ptr(M::module()).cast::<M::Ctx>();
+        // SAFETY: the context type is guaranteed by the HttpModuleCtx implementation.
+        unsafe { ctx.as_ref() }
+    }
+
+    /// Returns the mutable request context of the module `M`.
+    pub fn module_ctx_mut<M: HttpModuleCtx>(&mut self) -> Option<&mut M::Ctx> {
+        let ctx = self.get_module_ctx_ptr(M::module()).cast::<M::Ctx>();
+        // SAFETY: the context type is guaranteed by the HttpModuleCtx implementation.
+        unsafe { ctx.as_mut() }
+    }
+
+    /// Returns the request context of the module `M`, creating it with [Default] if not set.
+    ///
+    /// The context is allocated from the request pool and dropped when the request is finalized.
+    pub fn module_ctx_or_default<M: HttpModuleCtx>(&mut self) -> Result<&mut M::Ctx, AllocError>
+    where
+        M::Ctx: Default,
+    {
+        let module = M::module();
+        let mut ctx = self.get_module_ctx_ptr(module).cast::<M::Ctx>();
+
+        if ctx.is_null() {
+            ctx = self
+                .pool()
+                .allocate_with_cleanup(M::Ctx::default())?
+                .as_ptr();
+            self.set_module_ctx(ctx.cast(), module);
+        }
+
+        // SAFETY: the context type is guaranteed by the HttpModuleCtx implementation.
+        Ok(unsafe { &mut *ctx })
+    }
+
+    /// Resets the request context of the module `M`.
+    ///
+    /// The previous value remains in the request pool until the request is finalized. NGINX
+    /// also resets the module contexts on an internal redirect.
+    pub fn clear_module_ctx<M: HttpModuleCtx>(&mut self) {
+        self.set_module_ctx(core::ptr::null_mut(), M::module());
+    }
+
     /// Get the value of a [complex value].
     ///
     /// [complex value]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
@@ -206,13 +434,91 @@ impl Request {
         }
     }
 
+    /// Returns the value of the variable with the specified name, without the leading `$`.
+    ///
+    /// The name is matched case-insensitively and may refer to any variable, including the
+    /// prefixed ones such as `$http_*` or `$arg_*`. Returns `None` if the variable is unknown or
+    /// not found, or if the evaluation failed.
+    ///
+    /// Prefer [Request::indexed_variable] for variables known at configuration time.
+    pub fn variable(&mut self, name: &NgxStr) -> Option<&VariableValue> {
+        let name = name.as_bytes();
+        if name.is_empty() {
+            return None;
+        }
+
+        // The variables hash stores lowercase names.
+        let data = unsafe { ngx_pnalloc(self.0.pool, name.len()) }.cast::<u8>();
+        if data.is_null() {
+            return None;
+        }
+        // SAFETY: the destination buffer is allocated with the source length.
+        let key = unsafe { ngx_hash_strlow(data, name.as_ptr().cast_mut(), name.len()) };
+
+        let mut name = ngx_str_t {
+            len: name.len(),
+            data,
+        };
+        let v = unsafe { ngx_http_get_variable(&mut self.0, &mut name, key) };
+        Self::found_variable(v)
+    }
+
+    /// Returns the value of the variable with the specified index.
+    ///
+    /// Non-cacheable variables are evaluated again on each call. Returns `None` if the variable is
+    /// not found or if the evaluation failed.
+    pub fn indexed_variable(&mut self, index: VariableIndex) -> Option<&VariableValue> {
+        let v = unsafe { ngx_http_get_flushed_variable(&mut self.0, index.get()) };
+        Self::found_variable(v)
+    }
+
+    fn found_variable<'a>(v: *mut ngx_http_variable_value_t) -> Option<&'a VariableValue> {
+        // SAFETY: a non-null value is allocated from the request pool or cached in the request.
+        let v = unsafe { VariableValue::from_ptr_mut(v.as_mut()?) };
+        v.as_bytes().is_some().then_some(&*v)
+    }
+
     /// Discard (read and ignore) the [request body].
     ///
+    /// See also [Request::discard_body].
+    ///
     /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
     pub fn discard_request_body(&mut self) -> Status {
         unsafe { Status(ngx_http_discard_request_body(&mut self.0)) }
     }
 
+    /// Discards the [request body] before sending a response without reading the body.
+    ///
+    /// A handler that responds without reading the body must discard it, so that the body is not
+    /// interpreted as the next request on a keepalive connection. The body is read and ignored in
+    /// the background. The special responses sent by NGINX, e.g. with a status code returned from
+    /// an access phase handler, discard the body automatically.
+    ///
+    /// Returns the status to finalize the request with on error.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::{HTTPStatus, Request};
+    /// fn handler(request: &mut Request) -> Status {
+    ///     if let Err(rc) = request.discard_body() {
+    ///         return rc;
+    ///     }
+    ///
+    ///     request.set_status(HTTPStatus::NO_CONTENT);
+    ///     request.set_content_length_n(0);
+    ///     request.send_header()
+    /// }
+    /// ```
+    ///
+    /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+    pub fn discard_body(&mut self) -> Result<(), Status> {
+        match self.discard_request_body() {
+            Status::NGX_OK => Ok(()),
+            rc => Err(rc),
+        }
+    }
+
     /// Client HTTP [User-Agent].
     ///
     /// [User-Agent]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/User-Agent
@@ -224,6 +530,68 @@ impl Request {
         }
     }
 
+    /// Returns the `Host` request header.
+    pub fn host(&self) -> Option<&NgxStr> {
+        let host = self.0.headers_in.host;
+        // SAFETY: the header is allocated from the request pool.
+        (!host.is_null()).then(|| unsafe { NgxStr::from_ngx_str((*host).value) })
+    }
+
+    /// Sets the `Host` request header.
+    ///
+    /// See [Request::set_header_in].
+    pub fn set_host(&mut self, host: impl AsRef<[u8]>) -> Result<(), HeaderError> {
+        self.set_header_in("Host", host)
+    }
+
+    /// Returns the `Referer` request header.
+    pub fn referer(&self) -> Option<&NgxStr> {
+        let referer = self.0.headers_in.referer;
+        // SAFETY: the header is allocated from the request pool.
+        (!referer.is_null()).then(|| unsafe { NgxStr::from_ngx_str((*referer).value) })
+    }
+
+    /// Returns the request body length from the `Content-Length` header.
+    ///
+    /// Returns `None` if the header is missing, e.g. with the chunked transfer encoding.
+    pub fn content_length(&self) -> Option<u64> {
+        u64::try_from(self.0.headers_in.content_length_n).ok()
+    }
+
+    /// Returns the `If-Modified-Since` request header as a Unix timestamp.
+    ///
+    /// Returns `None` if the header is missing or is not a valid HTTP date; NGINX ignores such
+    /// headers as well.
+    pub fn if_modified_since(&self) -> Option<time_t> {
+        let ims = self.0.headers_in.if_modified_since;
+        if ims.is_null() {
+            return None;
+        }
+
+        // SAFETY: the header is allocated from the request pool.
+        let value = unsafe { (*ims).value };
+        let time = unsafe { ngx_parse_http_time(value.data, value.len) };
+        (time != NGX_ERROR as time_t).then_some(time)
+    }
+
+    /// Returns the ranges of the `Range` request header.
+    ///
+    /// Returns `None` if the header is missing, and an error if the range unit is not `bytes`.
+    pub fn range(&self) -> Option<Result<ByteRanges<'_>, parse::ParseError>> {
+        let range = self.0.headers_in.range;
+        // SAFETY: the header is allocated from the request pool.
+        (!range.is_null()).then(|| ByteRanges::parse(unsafe { (*range).value.as_bytes() }))
+    }
+
+    /// Returns the scheme and the credentials of the `Authorization` request header.
+    ///
+    /// Returns `None` if the header is missing, and an error if the header is malformed.
+    pub fn authorization(&self) -> Option<Result<Authorization<'_>, parse::ParseError>> {
+        let auth = self.0.headers_in.authorization;
+        // SAFETY: the header is allocated from the request pool.
+        (!auth.is_null()).then(|| Authorization::parse(unsafe { (*auth).value.as_bytes() }))
+    }
+
     /// Set HTTP status of response.
     pub fn set_status(&mut self, status: HTTPStatus) {
         self.0.headers_out.status = status.into();
@@ -231,8 +599,11 @@ impl Request {
 
     /// Add header to the `headers_in` object.
     ///
+    /// Returns `None` if the header is not valid, see [header::validate].
+    ///
     /// See <https://nginx.org/en/docs/dev/development_guide.html#http_request>
     pub fn add_header_in(&mut self, key: &str, value: &str) -> Option<()> {
+        header::validate(key, value).ok()?;
         let table: *mut ngx_table_elt_t =
             unsafe { ngx_list_push(&mut self.0.headers_in.headers) as _ };
         unsafe { add_to_ngx_table(table, self.0.pool, key, value) }
@@ -240,13 +611,149 @@ impl Request {
 
     /// Add header to the `headers_out` object.
     ///
+    /// Returns `None` if the header is not valid, see [header::validate].
+    ///
     /// See <https://nginx.org/en/docs/dev/development_guide.html#http_request>
     pub fn add_header_out(&mut self, key: &str, value: &str) -> Option<()> {
+        header::validate(key, value).ok()?;
         let table: *mut ngx_table_elt_t =
             unsafe { ngx_list_push(&mut self.0.headers_out.headers) as _ };
         unsafe { add_to_ngx_table(table, self.0.pool, key, value) }
     }
 
+    /// Sets a request header, replacing all the headers with the same name.
+    ///
+    /// The `headers_in` pointers to the well-known headers, such as `headers_in.host` or
+    /// `headers_in.user_agent`, are updated to the new entry. `headers_in.server` and
+    /// `headers_in.content_length_n` are recalculated for `Host` and `Content-Length`.
+    ///
+    /// Returns an error without modifying the headers if the name or the value is not valid, see
+    /// [header::validate].
+    pub fn set_header_in(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) -> Result<(), HeaderError> {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        header::validate(key, value)?;
+        self.remove_header_in(key);
+
+        let elt: *mut ngx_table_elt_t = self.headers_in_mut().add(key, value)?;
+        // SAFETY: the entry was just added and has a valid value.
+        let value = unsafe { (*elt).value };
+
+        if let Some(field) = header_field(HEADERS_IN, key) {
+            unsafe { *field.of(&mut self.0.headers_in) = elt };
+        }
+
+        if key.eq_ignore_ascii_case(b"Host") {
+            self.0.headers_in.server = unsafe { server_name(self.0.pool, value.as_bytes()) }?;
+        } else if key.eq_ignore_ascii_case(b"Content-Length") {
+            self.0.headers_in.content_length_n = unsafe { ngx_atoof(value.data, value.len) };
+        }
+
+        Ok(())
+    }
+
+    /// Removes all the request headers with the specified name.
+    ///
+    /// The `headers_in` fields derived from the header are reset. Returns `true` if any header
+    /// was removed.
+    pub fn remove_header_in(&mut self, key: impl AsRef<[u8]>) -> bool {
+        let key = key.as_ref();
+        let found = self.headers_in_mut().remove(key);
+
+        if let Some(field) = header_field(HEADERS_IN, key) {
+            unsafe { *field.of(&mut self.0.headers_in) = core::ptr::null_mut() };
+        }
+
+        if key.eq_ignore_ascii_case(b"Host") {
+            // $host falls back to the server name
+            self.0.headers_in.server = ngx_str_t::empty();
+        } else if key.eq_ignore_ascii_case(b"Content-Length") {
+            self.0.headers_in.content_length_n = -1;
+        }
+
+        found
+    }
+
+    /// Sets a response header, replacing all the headers with the same name.
+    ///
+    /// `Content-Type` and `Content-Length` are stored in the dedicated `headers_out` fields, and
+    /// an invalid `Content-Length` value is ignored. The `headers_out` pointers to the well-known
+    /// headers, such as `headers_out.location` or `headers_out.etag`, are updated to the new
+    /// entry, and `headers_out.last_modified_time` is parsed from `Last-Modified`.
+    ///
+    /// Returns an error without modifying the headers if the name or the value is not valid, see
+    /// [header::validate].
+    pub fn set_header_out(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) -> Result<(), HeaderError> {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        header::validate(key, value)?;
+        self.remove_header_out(key);
+
+        if key.eq_ignore_ascii_case(b"Content-Type") {
+            return self.set_content_type(value);
+        }
+
+        if key.eq_ignore_ascii_case(b"Content-Length") {
+            let n = unsafe { ngx_atoof(value.as_ptr().cast_mut(), value.len()) };
+            self.0.headers_out.content_length_n = n.max(-1);
+            return Ok(());
+        }
+
+        let elt: *mut ngx_table_elt_t = self.headers_out_mut().add(key, value)?;
+
+        if let Some(field) = header_field(HEADERS_OUT, key) {
+            unsafe { *field.of(&mut self.0.headers_out) = elt };
+        }
+
+        if key.eq_ignore_ascii_case(b"Last-Modified") {
+            let value = unsafe { (*elt).value };
+            self.0.headers_out.last_modified_time =
+                unsafe { ngx_parse_http_time(value.data, value.len) };
+        }
+
+        Ok(())
+    }
+
+    /// Removes all the response headers with the specified name.
+    ///
+    /// The `headers_out` fields derived from the header are reset. Returns `true` if any header
+    /// was removed.
+    pub fn remove_header_out(&mut self, key: impl AsRef<[u8]>) -> bool {
+        let key = key.as_ref();
+        let headers_out = &mut self.0.headers_out;
+
+        if key.eq_ignore_ascii_case(b"Content-Type") {
+            let found = headers_out.content_type.len != 0;
+            headers_out.content_type = ngx_str_t::empty();
+            headers_out.content_type_len = 0;
+            headers_out.content_type_lowcase = core::ptr::null_mut();
+            return found;
+        }
+
+        let mut found = false;
+
+        if key.eq_ignore_ascii_case(b"Content-Length") {
+            found = headers_out.content_length_n >= 0;
+            headers_out.content_length_n = -1;
+            headers_out.content_length = core::ptr::null_mut();
+        } else if key.eq_ignore_ascii_case(b"Last-Modified") {
+            found = headers_out.last_modified_time != -1;
+            headers_out.last_modified_time = -1;
+        }
+
+        if let Some(field) = header_field(HEADERS_OUT, key) {
+            unsafe { *field.of(headers_out) = core::ptr::null_mut() };
+        }
+
+        self.headers_out_mut().remove(key) || found
+    }
+
     /// Set response body [Content-Length].
     ///
     /// [Content-Length]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Length
@@ -261,6 +768,172 @@ impl Request {
         unsafe { Status(ngx_http_send_header(&mut self.0)) }
     }
 
+    /// Set the response `Content-Type`.
+    ///
+    /// The value is copied to the request pool. Returns [HeaderError::InvalidValue] if the value
+    /// contains CR, LF or other control characters.
+    pub fn set_content_type(&mut self, value: impl AsRef<[u8]>) -> Result<(), HeaderError> {
+        let value = value.as_ref();
+        if !header::is_valid_value(value) {
+            return Err(HeaderError::InvalidValue);
+        }
+
+        let value = unsafe { ngx_str_t::from_bytes(self.0.pool, value) }.ok_or(AllocError)?;
+        self.0.headers_out.content_type_len = value.len;
+        self.0.headers_out.content_type = value;
+        self.0.headers_out.content_type_lowcase = core::ptr::null_mut();
+        Ok(())
+    }
+
+    /// Set the response status and header fields, and send the output header.
+    ///
+    /// `Content-Type` and `Content-Length` are stored in the dedicated `headers_out` fields,
+    /// other fields are appended to the `headers_out` list.
+    ///
+    /// Returns `NGX_ERROR` if a header is not valid (see [header::validate]), cannot be allocated or
+    /// the `Content-Length` value is invalid, or the result of [Request::send_header].
+    pub fn send_header_with<K, V>(
+        &mut self,
+        status: HTTPStatus,
+        headers: impl IntoIterator<Item = (K, V)>,
+    ) -> Status
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.set_status(status);
+
+        for (key, value) in headers {
+            let (key, value) = (key.as_ref(), value.as_ref());
+
+            if key.eq_ignore_ascii_case(b"Content-Type") {
+                if self.set_content_type(value).is_err() {
+                    return Status::NGX_ERROR;
+                }
+            } else if key.eq_ignore_ascii_case(b"Content-Length") {
+                let n = unsafe { ngx_atoof(value.as_ptr().cast_mut(), value.len()) };
+                if n < 0 {
+                    return Status::NGX_ERROR;
+                }
+                self.0.headers_out.content_length_n = n;
+            } else if self.headers_out_mut().add(key, value).is_err() {
+                return Status::NGX_ERROR;
+            }
+        }
+
+        self.send_header()
+    }
+
+    /// Send `data` as the final part of the response body.
+    ///
+    /// The data is copied to the request pool, and the buffer is marked as the last one.
+    pub fn send_body(&mut self, data: &[u8]) -> Status {
+        let mut chain = ChainBuilder::new(self.pool());
+        if !data.is_empty() && chain.chunk_size(0).write(data).is_err() {
+            return Status::NGX_ERROR;
+        }
+        self.send_chain(chain)
+    }
+
+    /// Send the buffers of `chain` as the final part of the response body.
+    ///
+    /// Sets the `last_buf` flag for the main request and the `last_in_chain` flag on the last
+    /// buffer of the chain.
+    pub fn send_chain(&mut self, mut chain: ChainBuilder) -> Status {
+        chain.last_buf(self.is_main()).last_in_chain(true);
+        match chain.finish() {
+            Ok(cl) => unsafe { Status(ngx_http_output_filter(&mut self.0, cl)) },
+            Err(_) => Status::NGX_ERROR,
+        }
+    }
+
+    /// Send a complete response with the status, header fields and body.
+    ///
+    /// The `Content-Length` is set to the body length. The body is omitted if NGINX decides
+    /// that the response has no body, e.g. for `HEAD` requests or `304` responses.
+    ///
+    /// Returns the status to return from a content handler.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::http::{HTTPStatus, Request};
+    /// # use ngx::http_request_handler;
+    /// http_request_handler!(hello_handler, |request: &mut Request| {
+    ///     request.send_response(
+    ///         HTTPStatus::OK,
+    ///         [("Content-Type", "text/plain")],
+    ///         b"Hello, world!\n",
+    ///     )
+    /// });
+    /// ```
+    pub fn send_response<K, V>(
+        &mut self,
+        status: HTTPStatus,
+        headers: impl IntoIterator<Item = (K, V)>,
+        body: &[u8],
+    ) -> Status
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.set_content_length_n(body.len());
+
+        let rc = self.send_header_with(status, headers);
+        if rc == Status::NGX_ERROR || rc.0 > Status::NGX_OK.0 || self.header_only() {
+            return rc;
+        }
+
+        self.send_body(body)
+    }
+
+    /// Sends the response for an error or a special status code, such as 404 or 503.
+    ///
+    /// The response is generated by NGINX: the page configured with the [error_page] directive, if
+    /// any, or the built-in error page. The request body is discarded. Returns the result of the
+    /// output, which the handler passes to [Request::finalize] or returns from the content phase.
+    ///
+    /// Usually a handler returns the status code instead, and NGINX generates the response when
+    /// the request is finalized.
+    ///
+    /// [error_page]: https://nginx.org/en/docs/http/ngx_http_core_module.html#error_page
+    pub fn send_special_response(&mut self, status: HTTPStatus) -> Status {
+        let rc = unsafe { ngx_http_special_response_handler(&mut self.0, status.0 as _) };
+        Status(rc)
+    }
+
+    /// Finalizes the request with `rc`, and runs the subrequests posted on the connection.
+    ///
+    /// The method is intended for the request processing outside of the phase handlers, e.g. in a
+    /// task spawned with `Request::spawn`, in a timer or in a custom event handler. A phase
+    /// handler returns the status instead, and NGINX finalizes the request.
+    ///
+    /// `rc` is a status returned from a handler: `NGX_OK` or `NGX_DONE` once the response is sent,
+    /// `NGX_ERROR` to close the connection, or an HTTP status code to send the special response
+    /// with. The request may be freed by this call, and must not be accessed afterwards unless
+    /// another reference to it is held, e.g. with `r->main->count`.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::ffi::ngx_event_t;
+    /// # use ngx::http::{HTTPStatus, Request};
+    /// // a timer added by the content handler, with the request in `ev.data`
+    /// unsafe extern "C" fn timeout_handler(ev: *mut ngx_event_t) {
+    ///     let request = Request::from_ngx_http_request((*ev).data.cast());
+    ///     request.finalize(HTTPStatus::GATEWAY_TIME_OUT);
+    /// }
+    /// ```
+    ///
+    /// See <https://nginx.org/en/docs/dev/development_guide.html#http_request_finalization>
+    pub fn finalize(&mut self, rc: impl Into<Status>) {
+        let c = self.0.connection;
+        // SAFETY: the connection outlives the request, and ngx_http_run_posted_requests checks if
+        // the connection is closed.
+        unsafe {
+            ngx_http_finalize_request(&mut self.0, rc.into().into());
+            ngx_http_run_posted_requests(c);
+        }
+    }
+
     /// Flag indicating that the output does not require a body.
     ///
     /// For example, this flag is used by `HTTP HEAD` requests.
@@ -268,11 +941,144 @@ impl Request {
         self.0.header_only() != 0
     }
 
+    /// Flag indicating that the client connection will be kept alive after the response.
+    ///
+    /// The value is initially set by NGINX from the request protocol version, `Connection` header
+    /// and [keepalive_timeout] configuration, and is always tracked on the main request.
+    ///
+    /// [keepalive_timeout]: https://nginx.org/en/docs/http/ngx_http_core_module.html#keepalive_timeout
+    pub fn keepalive(&self) -> bool {
+        unsafe { (*self.0.main).keepalive() != 0 }
+    }
+
+    /// Sets whether the client connection should be kept alive after the response.
+    ///
+    /// Disabling keepalive makes NGINX send `Connection: close` if the response header is not sent
+    /// yet, and close the connection once the request is finalized. Enabling keepalive for a
+    /// request that NGINX did not consider eligible is not recommended, as the client may not
+    /// expect a persistent connection.
+    ///
+    /// The flag has no effect on HTTP/2 and HTTP/3 requests, where connection management is
+    /// independent from individual requests.
+    pub fn set_keepalive(&mut self, keepalive: bool) {
+        unsafe { (*self.0.main).set_keepalive(keepalive.into()) }
+    }
+
+    /// Flag indicating that the unread client data will be drained before closing the connection.
+    ///
+    /// See [lingering_close].
+    ///
+    /// [lingering_close]: https://nginx.org/en/docs/http/ngx_http_core_module.html#lingering_close
+    pub fn lingering_close(&self) -> bool {
+        unsafe { (*self.0.main).lingering_close() != 0 }
+    }
+
+    /// Sets whether NGINX should drain unread client data before closing the connection.
+    ///
+    /// Lingering close prevents the client from receiving a TCP reset instead of the response
+    /// when it keeps sending data, e.g. a large request body which was not read. Disabling it
+    /// closes the connection immediately after the response is sent.
+    pub fn set_lingering_close(&mut self, lingering_close: bool) {
+        unsafe { (*self.0.main).set_lingering_close(lingering_close.into()) }
+    }
+
+    /// Closes the client connection as soon as the response is sent.
+    ///
+    /// Disables both keepalive and lingering close, so that the connection is not reused for
+    /// subsequent requests and any unread client data is discarded. This is useful for terminating
+    /// connections of misbehaving clients after responding with an error.
+    pub fn force_close_after_response(&mut self) {
+        self.set_keepalive(false);
+        self.set_lingering_close(false);
+    }
+
+    /// Returns the response rate limit in bytes per second, 0 if the rate is not limited.
+    ///
+    /// The value is set from the [limit_rate] directive when the response is sent, unless it is
+    /// overridden with [Request::set_limit_rate] or the `$limit_rate` variable.
+    ///
+    /// [limit_rate]: https://nginx.org/en/docs/http/ngx_http_core_module.html#limit_rate
+    pub fn limit_rate(&self) -> usize {
+        self.0.limit_rate
+    }
+
+    /// Limits the rate of the response transmission to the client, in bytes per second.
+    ///
+    /// Overrides the [limit_rate] directive for the request. Set 0 to disable the limit.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::http::Request;
+    /// fn access_handler(request: &mut Request) {
+    ///     if request.user_agent().is_some_and(|ua| ua.as_bytes().starts_with(b"curl/")) {
+    ///         request.set_limit_rate(64 * 1024);
+    ///         request.set_limit_rate_after(1024 * 1024);
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [limit_rate]: https://nginx.org/en/docs/http/ngx_http_core_module.html#limit_rate
+    pub fn set_limit_rate(&mut self, bytes_per_sec: usize) {
+        self.0.limit_rate = bytes_per_sec;
+        self.0.set_limit_rate_set(1);
+    }
+
+    /// Sets the amount of the response data sent before the rate limit applies.
+    ///
+    /// Overrides the [limit_rate_after] directive for the request.
+    ///
+    /// [limit_rate_after]: https://nginx.org/en/docs/http/ngx_http_core_module.html#limit_rate_after
+    pub fn set_limit_rate_after(&mut self, bytes: usize) {
+        self.0.limit_rate_after = bytes;
+        self.0.set_limit_rate_after_set(1);
+    }
+
     /// request method
     pub fn method(&self) -> Method {
         Method::from_ngx(self.0.method)
     }
 
+    /// Returns the HTTP protocol version of the request.
+    pub fn http_version(&self) -> HttpVersion {
+        HttpVersion::from_ngx(self.0.http_version)
+    }
+
+    /// Returns the id of the HTTP/2 or HTTP/3 stream carrying the request.
+    ///
+    /// Returns `None` for the HTTP/1.x requests, or if the support for the protocol is not
+    /// compiled in.
+    pub fn stream_id(&self) -> Option<u64> {
+        // SAFETY: the stream is set for the HTTP/2 requests only, and outlives the request.
+        #[cfg(ngx_feature = "http_v2")]
+        if let Some(stream) = unsafe { self.0.stream.as_ref() } {
+            // SAFETY: the stream node lives as long as the stream.
+            return unsafe { stream.node.as_ref() }.map(|node| node.id as u64);
+        }
+
+        #[cfg(ngx_feature = "quic")]
+        if self.http_version() == HttpVersion::Http3 {
+            // SAFETY: the connection of an HTTP/3 request is a QUIC stream.
+            return unsafe { (*self.0.connection).quic.as_ref() }.map(|qs| qs.id);
+        }
+
+        None
+    }
+
+    /// Returns `true` if the response can use the HTTP/2 server push.
+    ///
+    /// The server push was removed in NGINX 1.25.1, and is never available with the later
+    /// versions.
+    pub fn can_push(&self) -> bool {
+        #[cfg(all(ngx_feature = "http_v2", not(nginx1_25_1)))]
+        if let Some(stream) = unsafe { self.0.stream.as_ref() } {
+            // SAFETY: the HTTP/2 connection outlives the stream.
+            let h2c = unsafe { &*stream.connection };
+            return h2c.push_disabled() == 0;
+        }
+
+        false
+    }
+
     /// path part of request only
     pub fn path(&self) -> &NgxStr {
         unsafe { NgxStr::from_ngx_str(self.0.uri) }
@@ -283,6 +1089,31 @@ impl Request {
         unsafe { NgxStr::from_ngx_str(self.0.unparsed_uri) }
     }
 
+    /// Maps the request URI to a file system path with the `root` or `alias` of the location, in
+    /// the same way as the static module.
+    ///
+    /// The path is allocated from the request pool. Returns `None` if the root with variables
+    /// failed to evaluate or the allocation failed.
+    pub fn map_uri_to_path(&mut self) -> Option<MappedPath<'_>> {
+        let mut path = ngx_str_t::default();
+        let mut root = 0;
+
+        // SAFETY: the request is valid; evaluating the root can modify the request, e.g. the
+        // variable values cache.
+        let last = unsafe { ngx_http_map_uri_to_path(&mut self.0, &mut path, &mut root, 0) };
+        if last.is_null() {
+            return None;
+        }
+
+        // `last` points to the NUL terminator, not included in the returned length
+        path.len = last as usize - path.data as usize;
+
+        Some(MappedPath {
+            path: unsafe { NgxStr::from_ngx_str(path) },
+            root,
+        })
+    }
+
     /// Send the [response body].
     ///
     /// This function can be called multiple times.
@@ -315,6 +1146,82 @@ impl Request {
         Status::NGX_DONE
     }
 
+    /// Redirects the request to `uri` with the arguments `args`.
+    ///
+    /// The location is searched again from the server rewrite phase, as with the [internal
+    /// redirect] in the `try_files`, `error_page` or `X-Accel-Redirect` processing. The
+    /// configuration and the module contexts of the current location are reset. NGINX limits the
+    /// number of the internal redirects per request and finalizes the request with the 500 status
+    /// when the limit is exceeded.
+    ///
+    /// The redirect takes place immediately and keeps a reference to the request, so the handler
+    /// must stop processing the request and return the result, `NGX_DONE`:
+    ///  * a content handler returns the status as is;
+    ///  * a handler of the other phases calls `ngx_http_finalize_request(r, NGX_DONE)` before
+    ///    returning `NGX_DONE`, as the phase engine does not finalize the request for this status.
+    ///
+    /// Returns `NGX_ERROR` if the URI is empty or cannot be copied to the request pool.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::Request;
+    /// fn content_handler(request: &mut Request) -> Status {
+    ///     if request.path().as_bytes().ends_with(b"/") {
+    ///         return request.redirect_internal("/index.html", Some(b"from=dir"));
+    ///     }
+    ///     // ...
+    /// #   Status::NGX_DECLINED
+    /// }
+    /// ```
+    ///
+    /// [internal redirect]: https://nginx.org/en/docs/dev/development_guide.html#http_request_redirection
+    pub fn redirect_internal(&mut self, uri: impl AsRef<[u8]>, args: Option<&[u8]>) -> Status {
+        let uri = uri.as_ref();
+        if uri.is_empty() {
+            return Status::NGX_ERROR;
+        }
+
+        // SAFETY: the strings are copied to the request pool, as they are referenced by the
+        // request after the redirect.
+        let Some(mut uri) = (unsafe { ngx_str_t::from_bytes(self.0.pool, uri) }) else {
+            return Status::NGX_ERROR;
+        };
+
+        let mut args = match args {
+            Some(args) => match unsafe { ngx_str_t::from_bytes(self.0.pool, args) } {
+                Some(args) => Some(args),
+                None => return Status::NGX_ERROR,
+            },
+            None => None,
+        };
+        let args = args
+            .as_mut()
+            .map_or(core::ptr::null_mut(), core::ptr::from_mut);
+
+        unsafe { Status(ngx_http_internal_redirect(&mut self.0, &mut uri, args)) }
+    }
+
+    /// Redirects the request to a [named location], `@name`.
+    ///
+    /// The URI and the arguments are preserved, and the processing continues from the rewrite
+    /// phase of the named location. The result is handled the same way as with
+    /// [Request::redirect_internal]. If the location is not found, the request is finalized with
+    /// the 500 status.
+    ///
+    /// Returns `NGX_ERROR` if the name cannot be copied to the request pool.
+    ///
+    /// [named location]: https://nginx.org/en/docs/http/ngx_http_core_module.html#location
+    pub fn goto_named(&mut self, location: impl AsRef<[u8]>) -> Status {
+        // SAFETY: the name is copied to the request pool, as it may be referenced in the logs.
+        let Some(mut name) = (unsafe { ngx_str_t::from_bytes(self.0.pool, location.as_ref()) })
+        else {
+            return Status::NGX_ERROR;
+        };
+
+        unsafe { Status(ngx_http_named_location(&mut self.0, &mut name)) }
+    }
+
     /// Send a subrequest
     pub fn subrequest(
         &self,
@@ -374,6 +1281,28 @@ impl Request {
         Status(r)
     }
 
+    /// Request header fields.
+    pub fn headers_in(&self) -> &Headers {
+        // SAFETY: `headers_in.headers` is initialized with `ngx_table_elt_t` on request creation.
+        unsafe { Headers::from_ptr(&self.0.headers_in.headers) }
+    }
+
+    /// Mutable request header fields.
+    pub fn headers_in_mut(&mut self) -> &mut Headers {
+        unsafe { Headers::from_ptr_mut(&mut self.0.headers_in.headers) }
+    }
+
+    /// Response header fields.
+    pub fn headers_out(&self) -> &Headers {
+        // SAFETY: `headers_out.headers` is initialized with `ngx_table_elt_t` on request creation.
+        unsafe { Headers::from_ptr(&self.0.headers_out.headers) }
+    }
+
+    /// Mutable response header fields.
+    pub fn headers_out_mut(&mut self) -> &mut Headers {
+        unsafe { Headers::from_ptr_mut(&mut self.0.headers_out.headers) }
+    }
+
     /// Iterate over headers_in
     /// each header item is (&str, &str) (borrowed)
     pub fn headers_in_iterator(&self) -> NgxListIterator<'_> {
@@ -387,6 +1316,91 @@ impl Request {
     }
 }
 
+/// A `*mut ngx_table_elt_t` field in `ngx_http_headers_in_t` or `ngx_http_headers_out_t`.
+#[derive(Clone, Copy)]
+struct HeaderField(usize);
+
+impl HeaderField {
+    /// Returns a pointer to the field in `headers`.
+    ///
+    /// # Safety
+    ///
+    /// The field offset must belong to the type of `headers`.
+    unsafe fn of<T>(self, headers: &mut T) -> *mut *mut ngx_table_elt_t {
+        core::ptr::from_mut(headers)
+            .byte_add(self.0)
+            .cast::<*mut ngx_table_elt_t>()
+    }
+}
+
+macro_rules! header_fields {
+    ($type:ty; $($name:literal => $field:ident),+ $(,)?) => {
+        &[ $( ($name, HeaderField(core::mem::offset_of!($type, $field))) ),+ ]
+    };
+}
+
+/// The request headers referenced from `ngx_http_headers_in_t`.
+static HEADERS_IN: &[(&str, HeaderField)] = header_fields!(ngx_http_headers_in_t;
+    "Host" => host,
+    "Connection" => connection,
+    "If-Modified-Since" => if_modified_since,
+    "If-Unmodified-Since" => if_unmodified_since,
+    "If-Match" => if_match,
+    "If-None-Match" => if_none_match,
+    "User-Agent" => user_agent,
+    "Referer" => referer,
+    "Content-Length" => content_length,
+    "Content-Range" => content_range,
+    "Content-Type" => content_type,
+    "Range" => range,
+    "If-Range" => if_range,
+    "Transfer-Encoding" => transfer_encoding,
+    "TE" => te,
+    "Expect" => expect,
+    "Upgrade" => upgrade,
+    "Authorization" => authorization,
+    "Keep-Alive" => keep_alive,
+);
+
+/// The response headers referenced from `ngx_http_headers_out_t`.
+static HEADERS_OUT: &[(&str, HeaderField)] = header_fields!(ngx_http_headers_out_t;
+    "Server" => server,
+    "Date" => date,
+    "Content-Encoding" => content_encoding,
+    "Location" => location,
+    "Refresh" => refresh,
+    "Last-Modified" => last_modified,
+    "Content-Range" => content_range,
+    "Accept-Ranges" => accept_ranges,
+    "WWW-Authenticate" => www_authenticate,
+    "Expires" => expires,
+    "ETag" => etag,
+);
+
+fn header_field(fields: &[(&str, HeaderField)], key: &[u8]) -> Option<HeaderField> {
+    fields
+        .iter()
+        .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(key))
+        .map(|(_, field)| *field)
+}
+
+/// Returns a lowercase copy of the `Host` header value without the port.
+unsafe fn server_name(pool: *mut ngx_pool_t, host: &[u8]) -> Result<ngx_str_t, AllocError> {
+    let end = if host.starts_with(b"[") {
+        host.iter()
+            .position(|&x| x == b']')
+            .map_or(host.len(), |x| x + 1)
+    } else {
+        host.iter().position(|&x| x == b':').unwrap_or(host.len())
+    };
+    let host = &host[..end];
+    let host = host.strip_suffix(b".").unwrap_or(host);
+
+    let mut server = ngx_str_t::from_bytes(pool, host).ok_or(AllocError)?;
+    server.as_bytes_mut().make_ascii_lowercase();
+    Ok(server)
+}
+
 impl crate::http::HttpModuleConfExt for Request {
     #[inline]
     unsafe fn http_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
@@ -414,6 +1428,13 @@ impl crate::http::HttpModuleConfExt for Request {
 
 // }
 
+/// Request cleanup handler for a closure of type `F`.
+unsafe extern "C" fn request_cleanup_handler<F: FnOnce()>(data: *mut c_void) {
+    // SAFETY: data is a pointer to F written by Request::add_cleanup. The handler is called once.
+    let f = core::ptr::read(data.cast::<F>());
+    f()
+}
+
 impl fmt::Debug for Request {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Request")
@@ -487,6 +1508,78 @@ impl<'a> Iterator for NgxListIterator<'a> {
     }
 }
 
+/// A file system path of a request returned by [Request::map_uri_to_path].
+#[derive(Clone, Copy, Debug)]
+pub struct MappedPath<'a> {
+    path: &'a NgxStr,
+    root: usize,
+}
+
+impl<'a> MappedPath<'a> {
+    /// Returns the full path.
+    pub fn path(&self) -> &'a NgxStr {
+        self.path
+    }
+
+    /// Returns the document root part of the path.
+    pub fn root(&self) -> &'a NgxStr {
+        NgxStr::from_bytes(&self.path.as_bytes()[..self.root])
+    }
+
+    /// Returns the full path as a C string.
+    pub fn as_c_str(&self) -> &'a CStr {
+        let bytes = self.path.as_bytes();
+        // SAFETY: the path is followed by a NUL terminator in memory.
+        let bytes = unsafe { slice::from_raw_parts(bytes.as_ptr(), bytes.len() + 1) };
+        CStr::from_bytes_until_nul(bytes).unwrap_or_default()
+    }
+}
+
+/// HTTP protocol version of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HttpVersion {
+    /// HTTP/0.9
+    Http09,
+    /// HTTP/1.0
+    Http10,
+    /// HTTP/1.1
+    Http11,
+    /// HTTP/2
+    Http2,
+    /// HTTP/3
+    Http3,
+}
+
+impl HttpVersion {
+    /// Returns the protocol name, as in the `$server_protocol` variable.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpVersion::Http09 => "HTTP/0.9",
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+            HttpVersion::Http2 => "HTTP/2.0",
+            HttpVersion::Http3 => "HTTP/3.0",
+        }
+    }
+
+    fn from_ngx(v: ngx_uint_t) -> HttpVersion {
+        // NGX_HTTP_VERSION_30 is not defined before 1.25.0
+        match v as u32 {
+            3000.. => HttpVersion::Http3,
+            NGX_HTTP_VERSION_20.. => HttpVersion::Http2,
+            NGX_HTTP_VERSION_11.. => HttpVersion::Http11,
+            NGX_HTTP_VERSION_10.. => HttpVersion::Http10,
+            _ => HttpVersion::Http09,
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// A possible error value when converting `Method`
 pub struct InvalidMethod {
     _priv: (),
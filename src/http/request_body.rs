@@ -0,0 +1,393 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::slice;
+
+#[cfg(feature = "std")]
+use crate::core::ChainReader;
+use crate::ffi::{ngx_buf_t, ngx_chain_t, ngx_http_request_body_t};
+use crate::http::Request;
+
+#[cfg(feature = "async")]
+pub use self::_async::*;
+
+/// A view over the client request body read by NGINX.
+///
+/// Depending on the configuration and the body size, the body can be stored in one or more memory
+/// buffers, or in a temporary file. See [client_body_buffer_size] and
+/// [client_body_in_file_only].
+///
+/// [client_body_buffer_size]: https://nginx.org/en/docs/http/ngx_http_core_module.html#client_body_buffer_size
+/// [client_body_in_file_only]: https://nginx.org/en/docs/http/ngx_http_core_module.html#client_body_in_file_only
+pub struct RequestBody<'a> {
+    body: NonNull<ngx_http_request_body_t>,
+    _lifetime: PhantomData<&'a ngx_http_request_body_t>,
+}
+
+impl<'a> RequestBody<'a> {
+    /// Creates a request body view from a pointer to [ngx_http_request_body_t].
+    ///
+    /// # Safety
+    ///
+    /// `body` must be a valid pointer to the request body structure of a request that outlives
+    /// `'a`.
+    pub unsafe fn from_ptr(body: NonNull<ngx_http_request_body_t>) -> Self {
+        Self {
+            body,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns a raw pointer to the underlying [ngx_http_request_body_t].
+    pub fn as_ptr(&self) -> *mut ngx_http_request_body_t {
+        self.body.as_ptr()
+    }
+
+    /// Returns an iterator over the body buffers.
+    pub fn bufs(&self) -> RequestBodyBufs<'a> {
+        RequestBodyBufs {
+            cl: unsafe { self.body.as_ref().bufs },
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns a reader over the body contents, including the parts stored in a temporary file.
+    #[cfg(feature = "std")]
+    pub fn reader(&self) -> ChainReader<'a> {
+        // SAFETY: the chain links and buffers are allocated from the request pool.
+        unsafe { ChainReader::from_ptr(self.body.as_ref().bufs) }
+    }
+
+    /// Returns `true` if the body, or a part of it, is stored in a temporary file.
+    pub fn in_file(&self) -> bool {
+        !unsafe { self.body.as_ref().temp_file }.is_null()
+    }
+
+    /// Returns the total size of the body.
+    pub fn len(&self) -> usize {
+        self.bufs()
+            .map(|b| {
+                if b.in_file() != 0 && b.temporary() == 0 && b.memory() == 0 {
+                    (b.file_last - b.file_pos) as usize
+                } else {
+                    buf_bytes(b).len()
+                }
+            })
+            .sum()
+    }
+
+    /// Returns `true` if the body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the body contents if it is stored in a single memory buffer.
+    ///
+    /// Returns `None` if the body is split between multiple buffers or written to a file. Use
+    /// [Request::read_body], which requests a single buffer, to avoid the split for bodies that
+    /// fit in [client_body_buffer_size].
+    ///
+    /// [client_body_buffer_size]: https://nginx.org/en/docs/http/ngx_http_core_module.html#client_body_buffer_size
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        if self.in_file() {
+            return None;
+        }
+
+        let mut bufs = self.bufs().filter(|b| !buf_bytes(b).is_empty());
+        match (bufs.next(), bufs.next()) {
+            (None, _) => Some(&[]),
+            (Some(b), None) => Some(buf_bytes(b)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for RequestBody<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBody")
+            .field("len", &self.len())
+            .field("in_file", &self.in_file())
+            .finish()
+    }
+}
+
+/// Returns the in-memory part of the buffer.
+fn buf_bytes(b: &ngx_buf_t) -> &[u8] {
+    if b.pos.is_null() || (b.temporary() == 0 && b.memory() == 0 && b.mmap() == 0) {
+        return &[];
+    }
+    // SAFETY: pos and last point to the same allocated memory.
+    unsafe { slice::from_raw_parts(b.pos, b.last.offset_from(b.pos) as usize) }
+}
+
+/// An iterator over the request body buffers.
+pub struct RequestBodyBufs<'a> {
+    cl: *mut ngx_chain_t,
+    _lifetime: PhantomData<&'a ngx_chain_t>,
+}
+
+impl<'a> Iterator for RequestBodyBufs<'a> {
+    type Item = &'a ngx_buf_t;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.cl.is_null() {
+            // SAFETY: the chain links and buffers are allocated from the request pool.
+            let cl = unsafe { &*self.cl };
+            self.cl = cl.next;
+            if let Some(buf) = unsafe { cl.buf.as_ref() } {
+                return Some(buf);
+            }
+        }
+        None
+    }
+}
+
+impl Request {
+    /// Returns the client request body, if it was read.
+    pub fn request_body(&self) -> Option<RequestBody<'_>> {
+        let body = NonNull::new(self.as_ref().request_body)?;
+        // SAFETY: the request body is allocated from the request pool.
+        Some(unsafe { RequestBody::from_ptr(body) })
+    }
+}
+
+#[cfg(feature = "async")]
+mod _async {
+    use core::error;
+    use core::fmt;
+    use core::future::Future;
+    use core::marker::PhantomData;
+    use core::pin::Pin;
+    use core::ptr::{self, NonNull};
+    use core::task::{self, Poll, Waker};
+
+    use super::RequestBody;
+    use crate::allocator::AllocError;
+    use crate::ffi::{
+        ngx_http_read_client_request_body, ngx_http_request_t, ngx_int_t, NGX_HTTP_SPECIAL_RESPONSE,
+    };
+    use crate::http::{HTTPStatus, HttpModuleCtx, Request};
+    use crate::ngx_log_debug_http;
+
+    /// An error returned by [Request::read_body].
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum ReadBodyError {
+        /// Memory allocation failed.
+        Alloc,
+        /// NGINX failed to read the body. The request should be finalized with the status.
+        Status(HTTPStatus),
+    }
+
+    impl error::Error for ReadBodyError {}
+
+    impl fmt::Display for ReadBodyError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ReadBodyError::Alloc => f.write_str("request body allocation failed"),
+                ReadBodyError::Status(status) => {
+                    write!(f, "failed to read request body: {}", status.0)
+                }
+            }
+        }
+    }
+
+    impl From<AllocError> for ReadBodyError {
+        fn from(_: AllocError) -> Self {
+            ReadBodyError::Alloc
+        }
+    }
+
+    /// The state of a pending [Request::read_body], stored in the module request context.
+    ///
+    /// The body `post_handler` receives only the request pointer, so the task waiting for the body
+    /// is found through the request context of the module.
+    #[derive(Debug, Default)]
+    pub struct BodyWaker {
+        done: bool,
+        waker: Option<Waker>,
+    }
+
+    impl Request {
+        /// Reads the client request body and returns a future resolving once it is complete.
+        ///
+        /// The state of the read is kept in the request context of the module `M`, which is
+        /// created with [Default] if not set. The context is expected to embed a [BodyWaker]:
+        ///
+        /// ```rust,no_run
+        /// # use ngx::ffi::ngx_module_t;
+        /// # use ngx::http::{BodyWaker, HttpModule, HttpModuleCtx, Request};
+        /// # struct MyModule;
+        /// # impl HttpModule for MyModule {
+        /// #     fn module() -> &'static ngx_module_t { unimplemented!() }
+        /// # }
+        /// #[derive(Default)]
+        /// struct RequestCtx {
+        ///     body: BodyWaker,
+        /// }
+        ///
+        /// impl AsMut<BodyWaker> for RequestCtx {
+        ///     fn as_mut(&mut self) -> &mut BodyWaker {
+        ///         &mut self.body
+        ///     }
+        /// }
+        ///
+        /// unsafe impl HttpModuleCtx for MyModule {
+        ///     type Ctx = RequestCtx;
+        /// }
+        ///
+        /// async fn body_len(request: &mut Request) -> usize {
+        ///     match request.read_body::<MyModule>().await {
+        ///         Ok(body) => body.len(),
+        ///         Err(_) => 0,
+        ///     }
+        /// }
+        /// ```
+        ///
+        /// The body is requested in a single memory buffer when possible, see
+        /// [RequestBody::as_bytes].
+        ///
+        /// Reading the body increments the main request reference count. A handler starting the
+        /// read is expected to return `NGX_DONE`, and the request must be finalized with
+        /// `ngx_http_finalize_request` once the body is processed.
+        ///
+        /// If NGINX fails to read the body, e.g. due to the client timeout, it terminates the request
+        /// and the future never completes. Use [Request::spawn] to ensure the task is cancelled in
+        /// such case.
+        ///
+        /// See <https://nginx.org/en/docs/dev/development_guide.html#http_request_body>
+        pub fn read_body<M>(
+            &mut self,
+        ) -> impl Future<Output = Result<RequestBody<'_>, ReadBodyError>> + '_
+        where
+            M: HttpModuleCtx,
+            M::Ctx: Default + AsMut<BodyWaker>,
+        {
+            let result = self.start_read_body::<M>();
+            ReadBody::<M> {
+                request: NonNull::from(self.as_mut()),
+                result: Some(result),
+                _request: PhantomData,
+            }
+        }
+
+        fn start_read_body<M>(&mut self) -> Result<(), ReadBodyError>
+        where
+            M: HttpModuleCtx,
+            M::Ctx: Default + AsMut<BodyWaker>,
+        {
+            let waker = self.module_ctx_or_default::<M>()?.as_mut();
+            waker.done = false;
+            waker.waker = None;
+
+            self.as_mut().set_request_body_in_single_buf(1);
+
+            let r = ptr::from_mut(self.as_mut());
+            let rc = unsafe { ngx_http_read_client_request_body(r, Some(read_body_handler::<M>)) };
+
+            ngx_log_debug_http!(self, "http read body async rc:{rc}");
+
+            if rc >= NGX_HTTP_SPECIAL_RESPONSE as ngx_int_t {
+                return Err(ReadBodyError::Status(HTTPStatus(rc as _)));
+            }
+
+            Ok(())
+        }
+    }
+
+    struct ReadBody<'r, M>
+    where
+        M: HttpModuleCtx,
+        M::Ctx: AsMut<BodyWaker>,
+    {
+        request: NonNull<ngx_http_request_t>,
+        result: Option<Result<(), ReadBodyError>>,
+        _request: PhantomData<(&'r mut Request, M)>,
+    }
+
+    impl<M> ReadBody<'_, M>
+    where
+        M: HttpModuleCtx,
+        M::Ctx: AsMut<BodyWaker>,
+    {
+        fn body_waker(&mut self) -> Option<&mut BodyWaker> {
+            // SAFETY: the request is borrowed by the future.
+            let request = unsafe { Request::from_ngx_http_request(self.request.as_ptr()) };
+            Some(request.module_ctx_mut::<M>()?.as_mut())
+        }
+    }
+
+    impl<'r, M> Future for ReadBody<'r, M>
+    where
+        M: HttpModuleCtx,
+        M::Ctx: AsMut<BodyWaker>,
+    {
+        type Output = Result<RequestBody<'r>, ReadBodyError>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+            // SAFETY: the future does not contain self-references.
+            let this = unsafe { self.get_unchecked_mut() };
+
+            if let Err(err) = this.result.take().expect("polled after completion") {
+                return Poll::Ready(Err(err));
+            }
+
+            // The context is only reset by an internal redirect, which does not happen while the
+            // body is being read.
+            let Some(state) = this.body_waker() else {
+                return Poll::Ready(Err(ReadBodyError::Status(
+                    HTTPStatus::INTERNAL_SERVER_ERROR,
+                )));
+            };
+
+            if state.done {
+                // SAFETY: the request body is set once the body is read, and is valid while the
+                // request is borrowed.
+                let body = unsafe { NonNull::new_unchecked((*this.request.as_ptr()).request_body) };
+                return Poll::Ready(Ok(unsafe { RequestBody::from_ptr(body) }));
+            }
+
+            match state.waker.as_mut() {
+                Some(waker) => waker.clone_from(cx.waker()),
+                None => state.waker = Some(cx.waker().clone()),
+            }
+
+            this.result = Some(Ok(()));
+            Poll::Pending
+        }
+    }
+
+    impl<M> Drop for ReadBody<'_, M>
+    where
+        M: HttpModuleCtx,
+        M::Ctx: AsMut<BodyWaker>,
+    {
+        fn drop(&mut self) {
+            if let Some(Ok(())) = self.result.take() {
+                // release the waker of the cancelled task
+                if let Some(state) = self.body_waker() {
+                    state.waker = None;
+                }
+            }
+        }
+    }
+
+    unsafe extern "C" fn read_body_handler<M>(r: *mut ngx_http_request_t)
+    where
+        M: HttpModuleCtx,
+        M::Ctx: AsMut<BodyWaker>,
+    {
+        let request = Request::from_ngx_http_request(r);
+
+        ngx_log_debug_http!(request, "http read body async done");
+
+        let Some(ctx) = request.module_ctx_mut::<M>() else {
+            return;
+        };
+        let state = ctx.as_mut();
+        state.done = true;
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
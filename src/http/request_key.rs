@@ -0,0 +1,231 @@
+use core::error;
+use core::ffi::{c_char, c_void};
+use core::fmt;
+use core::mem;
+use core::slice;
+
+use crate::core::{NGX_CONF_ERROR, NGX_CONF_OK};
+use crate::ffi::{ngx_command_t, ngx_conf_t, ngx_murmur_hash2, ngx_str_t};
+use crate::http::{ComplexValue, ComplexValueError, ComplexValues, Request};
+
+/// An error returned by [RequestKey].
+#[derive(Debug, PartialEq, Eq)]
+pub enum RequestKeyError {
+    /// Memory allocation failed.
+    Alloc,
+    /// A complex value failed to compile. The error is logged by the configuration parser.
+    Compile,
+    /// A complex value failed to evaluate, e.g. because of an allocation failure.
+    Evaluate,
+}
+
+impl error::Error for RequestKeyError {}
+
+impl From<ComplexValueError> for RequestKeyError {
+    fn from(err: ComplexValueError) -> Self {
+        match err {
+            ComplexValueError::Alloc => RequestKeyError::Alloc,
+            ComplexValueError::Compile => RequestKeyError::Compile,
+            ComplexValueError::Evaluate => RequestKeyError::Evaluate,
+        }
+    }
+}
+
+impl fmt::Display for RequestKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestKeyError::Alloc => f.write_str("request key allocation failed"),
+            RequestKeyError::Compile => f.write_str("invalid request key"),
+            RequestKeyError::Evaluate => f.write_str("request key evaluation failed"),
+        }
+    }
+}
+
+/// A configurable key identifying a request, e.g. for rate limiting or caching.
+///
+/// The key is configured from one or more [complex values], such as
+/// `example_key $binary_remote_addr $uri;`, and evaluated for each request. Multiple parts are
+/// joined with a NUL byte, so that the parts cannot be confused with each other.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::{ngx_command_t, NGX_CONF_1MORE, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET};
+/// # use ngx::http::{ngx_http_set_request_key_slot, Request, RequestKey};
+/// # use ngx::ngx_string;
+/// #[derive(Debug, Default)]
+/// struct LocConfig {
+///     key: RequestKey,
+/// }
+///
+/// static mut COMMAND: ngx_command_t = ngx_command_t {
+///     name: ngx_string!("example_key"),
+///     type_: (NGX_HTTP_LOC_CONF | NGX_CONF_1MORE) as _,
+///     set: Some(ngx_http_set_request_key_slot),
+///     conf: NGX_HTTP_LOC_CONF_OFFSET,
+///     offset: core::mem::offset_of!(LocConfig, key),
+///     post: core::ptr::null_mut(),
+/// };
+///
+/// fn handler(request: &Request, conf: &LocConfig) {
+///     if let Ok(Some(key)) = conf.key.evaluate(request) {
+///         println!("key hash {:08x}", key.hash());
+///     }
+/// }
+/// ```
+///
+/// [complex values]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
+#[derive(Debug, Default)]
+pub struct RequestKey(ComplexValues);
+
+impl RequestKey {
+    /// Compiles a key from the directive arguments, allocating from the configuration pool.
+    pub fn compile(cf: &mut ngx_conf_t, args: &[ngx_str_t]) -> Result<Self, RequestKeyError> {
+        Ok(Self(ComplexValues::compile(cf, args)?))
+    }
+
+    /// Returns `true` if the key is configured.
+    pub fn is_set(&self) -> bool {
+        self.0.is_set()
+    }
+
+    /// Returns the compiled parts of the key.
+    pub fn parts(&self) -> &[ComplexValue] {
+        self.0.as_slice()
+    }
+
+    /// Evaluates the key for the request.
+    ///
+    /// Returns `Ok(None)` if the key is not configured. The joined value of a multipart key is
+    /// allocated from the request pool.
+    pub fn evaluate<'r>(
+        &self,
+        request: &'r Request,
+    ) -> Result<Option<RequestKeyValue<'r>>, RequestKeyError> {
+        let bytes: &[u8] = match self.parts() {
+            [] => return Ok(None),
+            [part] => part
+                .evaluate(request)?
+                .ok_or(RequestKeyError::Evaluate)?
+                .as_bytes(),
+            parts => join_parts(request, parts)?,
+        };
+
+        Ok(Some(RequestKeyValue::new(bytes)))
+    }
+}
+
+/// Evaluates the parts and joins them with zero bytes in a buffer from the request pool.
+fn join_parts<'r>(
+    request: &'r Request,
+    parts: &[ComplexValue],
+) -> Result<&'r [u8], RequestKeyError> {
+    let mut pool = request.pool();
+
+    let values = pool
+        .alloc(parts.len() * mem::size_of::<ngx_str_t>())
+        .cast::<ngx_str_t>();
+    if values.is_null() {
+        return Err(RequestKeyError::Alloc);
+    }
+
+    // the joined length, including the separators
+    let mut len = parts.len() - 1;
+    for (i, part) in parts.iter().enumerate() {
+        let value = part
+            .evaluate(request)?
+            .ok_or(RequestKeyError::Evaluate)?
+            .as_bytes();
+        len += value.len();
+        let value = ngx_str_t {
+            len: value.len(),
+            data: value.as_ptr().cast_mut(),
+        };
+        // SAFETY: values is an allocation of parts.len() elements.
+        unsafe { values.add(i).write(value) };
+    }
+    // SAFETY: all the elements were initialized above.
+    let values = unsafe { slice::from_raw_parts(values, parts.len()) };
+
+    let buf = pool.alloc_unaligned(len).cast::<u8>();
+    if buf.is_null() {
+        return Err(RequestKeyError::Alloc);
+    }
+
+    // SAFETY: buf is a fresh allocation of len bytes.
+    let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
+    let mut pos = 0;
+
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            buf[pos] = 0;
+            pos += 1;
+        }
+
+        let value = value.as_bytes();
+        buf[pos..pos + value.len()].copy_from_slice(value);
+        pos += value.len();
+    }
+
+    Ok(buf)
+}
+
+/// A value of the [RequestKey] for a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestKeyValue<'a> {
+    bytes: &'a [u8],
+    hash: u32,
+}
+
+impl<'a> RequestKeyValue<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        // SAFETY: the hash function does not modify the data.
+        let hash = unsafe { ngx_murmur_hash2(bytes.as_ptr().cast_mut(), bytes.len()) };
+        Self { bytes, hash }
+    }
+
+    /// Returns the raw key.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the hash of the key, stable across the worker processes and restarts.
+    ///
+    /// Same as `ngx_murmur_hash2` of the raw key.
+    pub fn hash(&self) -> u32 {
+        self.hash
+    }
+
+    /// Returns `true` if the key is empty.
+    ///
+    /// The NGINX modules usually skip the requests with an empty key, e.g. `limit_req`.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+/// A directive handler compiling the directive arguments to a [RequestKey].
+///
+/// # Safety
+///
+/// The `conf` field at `cmd.offset` must be a [RequestKey].
+pub unsafe extern "C" fn ngx_http_set_request_key_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    let key = &mut *conf.cast::<u8>().add((*cmd).offset).cast::<RequestKey>();
+    if key.is_set() {
+        return c"is duplicate".as_ptr().cast_mut();
+    }
+
+    let cf = &mut *cf;
+    let args: &[ngx_str_t] = (*cf.args).as_slice();
+
+    match RequestKey::compile(cf, &args[1..]) {
+        Ok(value) => {
+            *key = value;
+            NGX_CONF_OK
+        }
+        Err(_) => NGX_CONF_ERROR,
+    }
+}
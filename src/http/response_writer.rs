@@ -0,0 +1,411 @@
+use core::ffi::c_void;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use crate::allocator::AllocError;
+use crate::core::{ChainBuilder, Status};
+use crate::ffi::{
+    ngx_buf_t, ngx_chain_t, ngx_http_output_filter, ngx_http_request_t, ngx_int_t,
+    ngx_output_chain, ngx_output_chain_ctx_t, NGX_AGAIN, NGX_ERROR,
+};
+use crate::http::{HttpModuleLocationConf, NgxHttpCoreModule, OutputLimits, Request};
+
+#[cfg(feature = "async")]
+pub use self::_async::*;
+
+/// A buffered writer streaming a generated response body through the output filters.
+///
+/// The data is collected in the buffers allocated from the request pool and passed to
+/// `ngx_output_chain` once the amount of buffered data reaches the `postpone_output` limit, or on
+/// an explicit [flush](ResponseWriter::flush). The result of the last output operation reports
+/// the backpressure: `NGX_AGAIN` means that the data is accepted, but the client does not keep up
+/// and the producer should wait before writing more. With the `async` feature, the writer can
+/// wait for the client connection with [ResponseWriter::ready]. The waker of the waiting task is
+/// kept in the request context of a module, which embeds a [WriteWaker].
+///
+/// The response header must be sent before writing the body. The writer does not finalize the
+/// request.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::ngx_module_t;
+/// # use ngx::http::{HTTPStatus, HttpModule, HttpModuleCtx, Request, ResponseWriter, WriteWaker};
+/// # struct MyModule;
+/// # impl HttpModule for MyModule {
+/// #     fn module() -> &'static ngx_module_t { unimplemented!() }
+/// # }
+/// #[derive(Default)]
+/// struct RequestCtx {
+///     writer: WriteWaker,
+/// }
+///
+/// impl AsMut<WriteWaker> for RequestCtx {
+///     fn as_mut(&mut self) -> &mut WriteWaker {
+///         &mut self.writer
+///     }
+/// }
+///
+/// unsafe impl HttpModuleCtx for MyModule {
+///     type Ctx = RequestCtx;
+/// }
+///
+/// async fn example(request: &mut Request) -> Result<(), Status> {
+///     request.set_status(HTTPStatus::OK);
+///     request.send_header();
+///
+///     let mut writer = ResponseWriter::new(request).map_err(|_| Status::NGX_ERROR)?;
+///     for i in 0..1_000_000 {
+///         writer.write_all::<MyModule>(format!("{i}\n").as_bytes()).await?;
+///     }
+///     writer.close::<MyModule>().await
+/// }
+/// ```
+pub struct ResponseWriter<'r> {
+    request: NonNull<ngx_http_request_t>,
+    ctx: NonNull<ngx_output_chain_ctx_t>,
+    pending: ChainBuilder,
+    limits: OutputLimits,
+    finished: bool,
+    /// The write event handler replaced while waiting for the connection.
+    #[cfg(feature = "async")]
+    saved_handler: Option<crate::ffi::ngx_http_event_handler_pt>,
+    _request: PhantomData<&'r mut Request>,
+}
+
+impl<'r> ResponseWriter<'r> {
+    /// Creates a writer for the response body of the request.
+    ///
+    /// The output limits and buffers are read from the location configuration.
+    pub fn new(request: &'r mut Request) -> Result<Self, AllocError> {
+        let clcf = NgxHttpCoreModule::location_conf(request).expect("http core loc conf");
+        let mut pool = request.pool();
+
+        let ctx = NonNull::new(pool.calloc_type::<ngx_output_chain_ctx_t>()).ok_or(AllocError)?;
+        let r = request.as_mut();
+
+        // SAFETY: the context is a fresh allocation from the request pool.
+        unsafe {
+            let octx = &mut *ctx.as_ptr();
+            octx.pool = r.pool;
+            octx.bufs = clcf.bufs;
+            octx.tag = ctx.as_ptr().cast();
+            octx.output_filter = Some(output_filter);
+            octx.filter_ctx = ptr::from_mut(r).cast();
+            octx.alignment = clcf.directio_alignment;
+            octx.set_sendfile((*r.connection).sendfile());
+        }
+
+        Ok(Self {
+            request: NonNull::from(r),
+            ctx,
+            pending: ChainBuilder::new(pool),
+            limits: OutputLimits::from_location_conf(clcf),
+            finished: false,
+            #[cfg(feature = "async")]
+            saved_handler: None,
+            _request: PhantomData,
+        })
+    }
+
+    /// Sets the output limits.
+    pub fn with_limits(mut self, limits: OutputLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the amount of data buffered in the writer.
+    pub fn buffered(&self) -> usize {
+        self.pending.size()
+    }
+
+    /// Returns `true` if the previously written data is not yet sent to the client.
+    pub fn is_blocked(&self) -> bool {
+        // SAFETY: the request and the context are valid while the writer is alive.
+        unsafe {
+            let r = self.request.as_ref();
+            let ctx = self.ctx.as_ref();
+
+            !ctx.in_.is_null()
+                || !ctx.busy.is_null()
+                || r.buffered() != 0
+                || (r.main == self.request.as_ptr() && (*r.connection).buffered() != 0)
+        }
+    }
+
+    /// Appends `data` to the response body.
+    ///
+    /// Passes the buffered data to the output filters if the amount reaches the `postpone_output`
+    /// limit. Returns `NGX_AGAIN` if the data is accepted, but the output is blocked, and
+    /// `NGX_ERROR` on failure.
+    pub fn write(&mut self, data: &[u8]) -> Status {
+        if self.finished {
+            return Status::NGX_ERROR;
+        }
+
+        if self.pending.write(data).is_err() {
+            return Status::NGX_ERROR;
+        }
+
+        if self.limits.should_flush(self.pending.size(), false) {
+            return self.send(false, false);
+        }
+
+        Status::NGX_OK
+    }
+
+    /// Passes the buffered data to the output filters and asks them to send it to the client.
+    pub fn flush(&mut self) -> Status {
+        if self.finished {
+            return Status::NGX_OK;
+        }
+        self.send(true, false)
+    }
+
+    /// Passes the buffered data to the output filters as the last part of the response body.
+    ///
+    /// The writer does not accept data after this call. The data that cannot be sent immediately
+    /// is sent by NGINX when the request is finalized.
+    pub fn finish(&mut self) -> Status {
+        if self.finished {
+            return Status::NGX_OK;
+        }
+        self.finished = true;
+        self.send(false, true)
+    }
+
+    fn send(&mut self, flush: bool, last: bool) -> Status {
+        let mut pool = self.pending.pool().clone();
+        let mut chain = mem::replace(&mut self.pending, ChainBuilder::new(pool.clone()));
+
+        if flush && !last {
+            let b = pool.calloc_type::<ngx_buf_t>();
+            if b.is_null() {
+                return Status::NGX_ERROR;
+            }
+            // SAFETY: the buffer is a fresh allocation from the request pool.
+            unsafe {
+                (*b).set_flush(1);
+                if chain.push_buf(b).is_err() {
+                    return Status::NGX_ERROR;
+                }
+            }
+        }
+
+        if last {
+            // SAFETY: the request is valid while the writer is alive.
+            let is_main = unsafe { self.request.as_ref().main == self.request.as_ptr() };
+            chain.last_buf(is_main).last_in_chain(true);
+        }
+
+        match chain.finish() {
+            Ok(cl) => self.output(cl),
+            Err(_) => Status::NGX_ERROR,
+        }
+    }
+
+    fn output(&mut self, cl: *mut ngx_chain_t) -> Status {
+        // SAFETY: the context is valid while the writer is alive.
+        let rc = unsafe { ngx_output_chain(self.ctx.as_ptr(), cl) };
+        if rc == NGX_ERROR as ngx_int_t {
+            return Status::NGX_ERROR;
+        }
+
+        if rc == NGX_AGAIN as ngx_int_t || self.is_blocked() {
+            return Status::NGX_AGAIN;
+        }
+
+        Status::NGX_OK
+    }
+}
+
+impl fmt::Write for ResponseWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.write(s.as_bytes()) == Status::NGX_ERROR {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ResponseWriter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseWriter")
+            .field("buffered", &self.buffered())
+            .field("limits", &self.limits)
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe extern "C" fn output_filter(data: *mut c_void, cl: *mut ngx_chain_t) -> ngx_int_t {
+    ngx_http_output_filter(data.cast(), cl)
+}
+
+#[cfg(feature = "async")]
+mod _async {
+    use core::future;
+    use core::ptr;
+    use core::task::{self, Poll, Waker};
+
+    use super::ResponseWriter;
+    use crate::core::Status;
+    use crate::ffi::{
+        ngx_add_timer, ngx_del_timer, ngx_handle_write_event, ngx_http_request_t, ngx_int_t,
+        NGX_HTTP_REQUEST_TIME_OUT, NGX_LOG_INFO, NGX_OK,
+    };
+    use crate::http::{HttpModuleCtx, HttpModuleLocationConf, NgxHttpCoreModule, Request};
+    use crate::ngx_log_error;
+
+    /// The waker of a task waiting in [ResponseWriter::ready], stored in the module request
+    /// context.
+    ///
+    /// The request write event handler receives only the request pointer, so the waiting task is
+    /// found through the request context of the module.
+    #[derive(Debug, Default)]
+    pub struct WriteWaker(Option<Waker>);
+
+    impl ResponseWriter<'_> {
+        /// Polls for the client connection to accept more data.
+        ///
+        /// Sends the data blocked in the output filters and waits for the connection write event
+        /// if the data cannot be sent. The wait is limited by the `send_timeout`; the status on
+        /// the timeout is `NGX_HTTP_REQUEST_TIME_OUT`.
+        ///
+        /// The waker is stored in the request context of the module `M`, created with [Default]
+        /// if not set. The request write event handler is replaced while the writer is alive.
+        pub fn poll_ready<M>(&mut self, cx: &mut task::Context<'_>) -> Poll<Result<(), Status>>
+        where
+            M: HttpModuleCtx,
+            M::Ctx: Default + AsMut<WriteWaker>,
+        {
+            let r = self.request.as_ptr();
+
+            // SAFETY: the request, its connection and events are valid while the writer is alive.
+            unsafe {
+                let c = (*r).connection;
+                let wev = (*c).write;
+
+                if (*wev).timedout() != 0 {
+                    ngx_log_error!(NGX_LOG_INFO, (*c).log, "client timed out");
+                    (*c).set_timedout(1);
+                    return Poll::Ready(Err(Status(NGX_HTTP_REQUEST_TIME_OUT as ngx_int_t)));
+                }
+
+                if self.is_blocked() && (*wev).delayed() == 0 {
+                    // push the data blocked in the filters
+                    if self.output(ptr::null_mut()) == Status::NGX_ERROR {
+                        return Poll::Ready(Err(Status::NGX_ERROR));
+                    }
+                }
+
+                if !self.is_blocked() {
+                    if (*wev).timer_set() != 0 {
+                        ngx_del_timer(wev);
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+
+                let request = Request::from_ngx_http_request(r);
+                let clcf = NgxHttpCoreModule::location_conf(request).expect("http core loc conf");
+
+                if (*wev).delayed() == 0 {
+                    ngx_add_timer(wev, clcf.send_timeout);
+                }
+
+                if ngx_handle_write_event(wev, clcf.send_lowat) != NGX_OK as ngx_int_t {
+                    return Poll::Ready(Err(Status::NGX_ERROR));
+                }
+
+                let Ok(ctx) = request.module_ctx_or_default::<M>() else {
+                    return Poll::Ready(Err(Status::NGX_ERROR));
+                };
+                match ctx.as_mut().0.as_mut() {
+                    Some(waker) => waker.clone_from(cx.waker()),
+                    None => ctx.as_mut().0 = Some(cx.waker().clone()),
+                }
+
+                if self.saved_handler.is_none() {
+                    self.saved_handler = Some((*r).write_event_handler);
+                }
+                (*r).write_event_handler = Some(write_event_handler::<M>);
+            }
+
+            Poll::Pending
+        }
+
+        /// Waits for the client connection to accept more data.
+        ///
+        /// See [ResponseWriter::poll_ready].
+        pub async fn ready<M>(&mut self) -> Result<(), Status>
+        where
+            M: HttpModuleCtx,
+            M::Ctx: Default + AsMut<WriteWaker>,
+        {
+            future::poll_fn(|cx| self.poll_ready::<M>(cx)).await
+        }
+
+        /// Appends `data` to the response body and waits if the output is blocked.
+        pub async fn write_all<M>(&mut self, data: &[u8]) -> Result<(), Status>
+        where
+            M: HttpModuleCtx,
+            M::Ctx: Default + AsMut<WriteWaker>,
+        {
+            match self.write(data) {
+                Status::NGX_ERROR => Err(Status::NGX_ERROR),
+                Status::NGX_AGAIN => self.ready::<M>().await,
+                _ => Ok(()),
+            }
+        }
+
+        /// Sends the buffered data as the last part of the response body and waits until all the
+        /// data is sent to the client.
+        pub async fn close<M>(&mut self) -> Result<(), Status>
+        where
+            M: HttpModuleCtx,
+            M::Ctx: Default + AsMut<WriteWaker>,
+        {
+            if self.finish() == Status::NGX_ERROR {
+                return Err(Status::NGX_ERROR);
+            }
+            self.ready::<M>().await
+        }
+    }
+
+    impl Drop for ResponseWriter<'_> {
+        fn drop(&mut self) {
+            let Some(handler) = self.saved_handler.take() else {
+                return;
+            };
+
+            let r = self.request.as_ptr();
+
+            // SAFETY: the request, its connection and events are valid while the writer is alive.
+            unsafe {
+                (*r).write_event_handler = handler;
+
+                // delete the send_timeout timer; the timer of a delayed event is set by limit_rate
+                let wev = (*(*r).connection).write;
+                if (*wev).timer_set() != 0 && (*wev).delayed() == 0 {
+                    ngx_del_timer(wev);
+                }
+            }
+        }
+    }
+
+    unsafe extern "C" fn write_event_handler<M>(r: *mut ngx_http_request_t)
+    where
+        M: HttpModuleCtx,
+        M::Ctx: Default + AsMut<WriteWaker>,
+    {
+        let request = Request::from_ngx_http_request(r);
+        let Some(ctx) = request.module_ctx_mut::<M>() else {
+            return;
+        };
+        if let Some(waker) = ctx.as_mut().0.take() {
+            waker.wake();
+        }
+    }
+}
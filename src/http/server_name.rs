@@ -0,0 +1,445 @@
+//! Matching of host names against `server_name`-style patterns.
+//!
+//! See <https://nginx.org/en/docs/http/server_names.html> for the description of the syntax and
+//! lookup order.
+use core::error;
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+pub use self::_alloc::{ServerNameMatcher, ServerNameMatcherBuilder};
+
+/// A parsed `server_name`-style pattern.
+///
+/// The pattern borrows from the source string and stores the parts used for matching:
+///  - `Exact("example.com")` for exact names,
+///  - `Head(".example.com")` for `*.example.com`,
+///  - `Dot(".example.com")` for `.example.com`, matching both `example.com` and `*.example.com`,
+///  - `Tail("www.example.")` for `www.example.*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerNamePattern<'a> {
+    /// Exact name.
+    Exact(&'a [u8]),
+    /// Wildcard name starting with an asterisk, e.g. `*.example.com`.
+    Head(&'a [u8]),
+    /// Special wildcard name in the form `.example.com`.
+    Dot(&'a [u8]),
+    /// Wildcard name ending with an asterisk, e.g. `www.example.*`.
+    Tail(&'a [u8]),
+}
+
+/// An error returned when a `server_name`-style pattern cannot be parsed or added to a matcher.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ServerNameError {
+    /// The pattern is empty or contains an asterisk in an unsupported position.
+    Invalid,
+    /// The pattern conflicts with a previously added one.
+    Duplicate,
+    /// Memory allocation failed.
+    Alloc,
+    /// The lookup table could not be built with the configured size limits. The reason has
+    /// already been logged.
+    Size,
+}
+
+impl error::Error for ServerNameError {}
+
+impl fmt::Display for ServerNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerNameError::Invalid => f.write_str("invalid server name or wildcard"),
+            ServerNameError::Duplicate => f.write_str("conflicting server name"),
+            ServerNameError::Alloc => f.write_str("memory allocation failed"),
+            ServerNameError::Size => f.write_str("could not build server names hash"),
+        }
+    }
+}
+
+impl<'a> ServerNamePattern<'a> {
+    /// Parses a pattern using the same rules as `ngx_hash_add_key` with `NGX_HASH_WILDCARD_KEY`.
+    ///
+    /// An asterisk is only allowed at the start or at the end of the name and only on a dot
+    /// border.
+    pub fn parse(name: &'a [u8]) -> Result<Self, ServerNameError> {
+        let len = name.len();
+
+        let pattern = if len > 2 && name[0] == b'*' && name[1] == b'.' {
+            ServerNamePattern::Head(&name[1..])
+        } else if len > 1 && name[0] == b'.' {
+            ServerNamePattern::Dot(name)
+        } else if len > 2 && name[len - 2] == b'.' && name[len - 1] == b'*' {
+            ServerNamePattern::Tail(&name[..len - 1])
+        } else if len > 0 {
+            ServerNamePattern::Exact(name)
+        } else {
+            return Err(ServerNameError::Invalid);
+        };
+
+        let body = pattern.as_bytes();
+        if body.contains(&b'*') || body.contains(&0) {
+            return Err(ServerNameError::Invalid);
+        }
+
+        Ok(pattern)
+    }
+
+    /// Returns the part of the pattern used for matching.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self {
+            ServerNamePattern::Exact(x)
+            | ServerNamePattern::Head(x)
+            | ServerNamePattern::Dot(x)
+            | ServerNamePattern::Tail(x) => x,
+        }
+    }
+
+    /// Checks if both patterns add the same name to the exact or wildcard hash, as reported by
+    /// `ngx_hash_add_key`.
+    pub fn conflicts_with(&self, other: &ServerNamePattern<'_>) -> bool {
+        fn eq(a: Option<&[u8]>, b: Option<&[u8]>) -> bool {
+            matches!((a, b), (Some(a), Some(b)) if a.eq_ignore_ascii_case(b))
+        }
+
+        eq(self.exact_key(), other.exact_key())
+            || eq(self.head_key(), other.head_key())
+            || eq(self.tail_key(), other.tail_key())
+    }
+
+    fn exact_key(&self) -> Option<&'a [u8]> {
+        match self {
+            ServerNamePattern::Exact(x) => Some(x),
+            ServerNamePattern::Dot(x) => Some(&x[1..]),
+            _ => None,
+        }
+    }
+
+    fn head_key(&self) -> Option<&'a [u8]> {
+        match self {
+            ServerNamePattern::Head(x) | ServerNamePattern::Dot(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn tail_key(&self) -> Option<&'a [u8]> {
+        match self {
+            ServerNamePattern::Tail(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the pattern contains a wildcard.
+    pub fn is_wildcard(&self) -> bool {
+        !matches!(self, ServerNamePattern::Exact(_))
+    }
+
+    /// Checks if the host name matches the pattern, ignoring ASCII case.
+    pub fn matches(&self, host: impl AsRef<[u8]>) -> bool {
+        let host = host.as_ref();
+        match self {
+            ServerNamePattern::Exact(x) => host.eq_ignore_ascii_case(x),
+            ServerNamePattern::Head(x) => host.len() > x.len() && ends_with_ignore_case(host, x),
+            ServerNamePattern::Dot(x) => {
+                ends_with_ignore_case(host, x) || host.eq_ignore_ascii_case(&x[1..])
+            }
+            ServerNamePattern::Tail(x) => host.len() > x.len() && starts_with_ignore_case(host, x),
+        }
+    }
+}
+
+#[inline]
+fn starts_with_ignore_case(s: &[u8], prefix: &[u8]) -> bool {
+    s.len() >= prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[inline]
+fn ends_with_ignore_case(s: &[u8], suffix: &[u8]) -> bool {
+    s.len() >= suffix.len() && s[s.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+}
+
+#[cfg(feature = "alloc")]
+mod _alloc {
+    use super::*;
+
+    use crate::allocator::Allocator;
+    use crate::collections::hash::NgxHashError;
+    use crate::collections::{NgxHash, NgxHashBuilder, Vec};
+    use crate::core::{NgxString, Pool};
+
+    impl From<NgxHashError> for ServerNameError {
+        fn from(err: NgxHashError) -> Self {
+            match err {
+                NgxHashError::Alloc => ServerNameError::Alloc,
+                NgxHashError::Duplicate => ServerNameError::Duplicate,
+                NgxHashError::InvalidWildcard => ServerNameError::Invalid,
+                NgxHashError::Size => ServerNameError::Size,
+            }
+        }
+    }
+
+    /// A builder for [ServerNameMatcher], collecting and validating the patterns.
+    ///
+    /// The patterns are checked for conflicts as they are added, so that an error can be reported
+    /// for the directive that adds the pattern.
+    pub struct ServerNameMatcherBuilder<T, A>
+    where
+        A: Allocator + Clone,
+    {
+        patterns: Vec<(NgxString<A>, T), A>,
+        max_size: usize,
+        bucket_size: Option<usize>,
+    }
+
+    impl<T, A> ServerNameMatcherBuilder<T, A>
+    where
+        A: Allocator + Clone,
+    {
+        /// Creates a new, empty builder with the specified allocator for the patterns.
+        pub fn new_in(alloc: A) -> Self {
+            Self {
+                patterns: Vec::new_in(alloc),
+                max_size: 512,
+                bucket_size: None,
+            }
+        }
+
+        /// Sets the maximum number of hash buckets, as `server_names_hash_max_size`.
+        pub fn max_size(mut self, max_size: usize) -> Self {
+            self.max_size = max_size;
+            self
+        }
+
+        /// Sets the maximum size of a hash bucket, as `server_names_hash_bucket_size`.
+        ///
+        /// The default is the processor cache line size.
+        pub fn bucket_size(mut self, bucket_size: usize) -> Self {
+            self.bucket_size = Some(bucket_size);
+            self
+        }
+
+        /// Returns `true` if the builder contains no patterns.
+        pub fn is_empty(&self) -> bool {
+            self.patterns.is_empty()
+        }
+
+        /// Parses and adds a pattern with an associated value.
+        ///
+        /// Returns [ServerNameError::Duplicate] if the same name was already added. Note that
+        /// `.example.com` conflicts with both `example.com` and `*.example.com`. The builder is not
+        /// modified on errors.
+        pub fn try_insert(
+            &mut self,
+            pattern: impl AsRef<[u8]>,
+            value: T,
+        ) -> Result<(), ServerNameError> {
+            let pattern = pattern.as_ref();
+            let parsed = ServerNamePattern::parse(pattern)?;
+
+            let conflict = self.patterns.iter().any(|(x, _)| {
+                ServerNamePattern::parse(x.as_bytes()).is_ok_and(|x| x.conflicts_with(&parsed))
+            });
+            if conflict {
+                return Err(ServerNameError::Duplicate);
+            }
+
+            let mut name = NgxString::try_from_bytes_in(pattern, self.patterns.allocator().clone())
+                .map_err(|_| ServerNameError::Alloc)?;
+            name.as_bytes_mut().make_ascii_lowercase();
+
+            self.patterns
+                .try_reserve(1)
+                .map_err(|_| ServerNameError::Alloc)?;
+            self.patterns.push((name, value));
+            Ok(())
+        }
+
+        /// Builds the matcher as a wildcard hash allocated from `pool`.
+        ///
+        /// `temp_pool` is used for the temporary arrays and can be destroyed once the matcher is
+        /// built. Returns [ServerNameError::Size] if the hash cannot be built with the configured
+        /// size limits; the reason is logged.
+        pub fn build(
+            self,
+            pool: &Pool,
+            temp_pool: &Pool,
+        ) -> Result<ServerNameMatcher<T>, ServerNameError> {
+            let mut hash = NgxHashBuilder::new(pool, temp_pool)
+                .map_err(|_| ServerNameError::Alloc)?
+                .max_size(self.max_size)
+                .name(c"server_names_hash");
+
+            if let Some(bucket_size) = self.bucket_size {
+                hash = hash.bucket_size(bucket_size);
+            }
+
+            for (name, value) in self.patterns {
+                hash.insert_wildcard(name.as_bytes(), value)?;
+            }
+
+            Ok(ServerNameMatcher(hash.build()?))
+        }
+    }
+
+    impl<T, A> fmt::Debug for ServerNameMatcherBuilder<T, A>
+    where
+        A: Allocator + Clone,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ServerNameMatcherBuilder")
+                .field("patterns", &self.patterns.len())
+                .finish_non_exhaustive()
+        }
+    }
+
+    /// A lookup table for host names built from `server_name`-style patterns.
+    ///
+    /// The matcher is a combined wildcard [NgxHash], built and searched in the same way as the
+    /// virtual server names. The lookup order is:
+    ///  1. exact name,
+    ///  2. the longest wildcard name starting with an asterisk, e.g. `*.example.com`,
+    ///  3. the longest wildcard name ending with an asterisk, e.g. `mail.*`.
+    ///
+    /// The matcher is intended to be constructed at configuration time with a
+    /// [ServerNameMatcherBuilder], and is valid as long as the configuration pool.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::allocator::Global;
+    /// # use ngx::core::Pool;
+    /// # use ngx::http::{ServerNameError, ServerNameMatcher, ServerNameMatcherBuilder};
+    /// fn build(pool: &Pool, temp_pool: &Pool) -> Result<ServerNameMatcher<u32>, ServerNameError> {
+    ///     let mut builder = ServerNameMatcherBuilder::new_in(Global);
+    ///     builder.try_insert("example.com", 1)?;
+    ///     builder.try_insert("*.example.org", 2)?;
+    ///     builder.try_insert("mail.*", 3)?;
+    ///     builder.build(pool, temp_pool)
+    /// }
+    /// ```
+    pub struct ServerNameMatcher<T>(NgxHash<T>);
+
+    impl<T> ServerNameMatcher<T> {
+        /// Finds the value associated with the best matching pattern for the host name.
+        ///
+        /// The host name is expected in lowercase, e.g. as in the `$host` variable.
+        pub fn find(&self, host: impl AsRef<[u8]>) -> Option<&T> {
+            self.0.find_combined(host)
+        }
+    }
+
+    impl<T> fmt::Debug for ServerNameMatcher<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("ServerNameMatcher").field(&self.0).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        use ServerNamePattern::*;
+
+        assert_eq!(
+            ServerNamePattern::parse(b"example.com"),
+            Ok(Exact(b"example.com"))
+        );
+        assert_eq!(
+            ServerNamePattern::parse(b"*.example.com"),
+            Ok(Head(b".example.com"))
+        );
+        assert_eq!(
+            ServerNamePattern::parse(b".example.com"),
+            Ok(Dot(b".example.com"))
+        );
+        assert_eq!(
+            ServerNamePattern::parse(b"www.example.*"),
+            Ok(Tail(b"www.example."))
+        );
+
+        for invalid in [
+            &b""[..],
+            b"*",
+            b"*.",
+            b".*",
+            b"www.*.com",
+            b"w*.example.com",
+            b"*.a.*",
+        ] {
+            assert_eq!(
+                ServerNamePattern::parse(invalid),
+                Err(ServerNameError::Invalid),
+                "{:?}",
+                core::str::from_utf8(invalid)
+            );
+        }
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        let head = ServerNamePattern::parse(b"*.example.com").unwrap();
+        assert!(head.matches("www.example.com"));
+        assert!(head.matches("a.b.Example.COM"));
+        assert!(!head.matches("example.com"));
+        assert!(!head.matches("www.example.org"));
+
+        let dot = ServerNamePattern::parse(b".example.com").unwrap();
+        assert!(dot.matches("example.com"));
+        assert!(dot.matches("www.example.com"));
+        assert!(!dot.matches("xexample.com"));
+
+        let tail = ServerNamePattern::parse(b"www.example.*").unwrap();
+        assert!(tail.matches("www.example.org"));
+        assert!(tail.matches("www.example.co.uk"));
+        assert!(!tail.matches("www.example."));
+        assert!(!tail.matches("mail.example.org"));
+    }
+
+    #[test]
+    fn test_pattern_conflicts() {
+        let parse = |x: &'static str| ServerNamePattern::parse(x.as_bytes()).unwrap();
+
+        let dot = parse(".example.com");
+        assert!(dot.conflicts_with(&parse("Example.COM")));
+        assert!(dot.conflicts_with(&parse("*.example.com")));
+        assert!(!dot.conflicts_with(&parse("www.example.com")));
+        assert!(!dot.conflicts_with(&parse("example.*")));
+        assert!(parse("mail.*").conflicts_with(&parse("MAIL.*")));
+        assert!(!parse("mail.*").conflicts_with(&parse("*.mail")));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_matcher_builder() {
+        use crate::allocator::Global;
+
+        let mut b = ServerNameMatcherBuilder::new_in(Global);
+        b.try_insert("example.com", 1).unwrap();
+        b.try_insert("*.example.com", 2).unwrap();
+        b.try_insert("*.www.example.com", 3).unwrap();
+        b.try_insert("www.example.*", 4).unwrap();
+        b.try_insert(".example.org", 5).unwrap();
+        b.try_insert("mail.*", 6).unwrap();
+
+        assert_eq!(
+            b.try_insert("Example.COM", 7),
+            Err(ServerNameError::Duplicate)
+        );
+        assert_eq!(
+            b.try_insert(".example.com", 7),
+            Err(ServerNameError::Duplicate)
+        );
+        assert_eq!(
+            b.try_insert("*.example.org", 7),
+            Err(ServerNameError::Duplicate)
+        );
+        assert_eq!(b.try_insert("www.*.com", 7), Err(ServerNameError::Invalid));
+
+        // a rejected pattern leaves no entries behind
+        b.try_insert("example.net", 8).unwrap();
+        assert_eq!(
+            b.try_insert(".example.net", 9),
+            Err(ServerNameError::Duplicate)
+        );
+        b.try_insert("*.example.net", 9).unwrap();
+    }
+}
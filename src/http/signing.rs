@@ -0,0 +1,660 @@
+//! Outbound request signing.
+//!
+//! The module provides the building blocks for the request signing schemes based on a canonical
+//! form of the request, such as [AWS Signature Version 4]:
+//!
+//!  * [CanonicalRequest] normalizes the method, path, query and the selected headers;
+//!  * [SigningAlgorithm] abstracts the hash and MAC functions, so the module does not depend on a
+//!    particular cryptographic library;
+//!  * [AwsV4Signer] implements the AWS Signature Version 4 on top of these.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use ngx::http::signing::{hex_encode, AwsV4Signer, CanonicalRequest, SigningAlgorithm};
+//! # fn example(alg: &impl SigningAlgorithm) {
+//! let datetime = "20150830T123600Z";
+//! let payload_hash = hex_encode(&alg.hash(b""));
+//!
+//! let request = CanonicalRequest::new("GET", "/bucket/key?list-type=2")
+//!     .header("Host", "example.amazonaws.com")
+//!     .header("X-Amz-Date", datetime)
+//!     .payload_hash(&payload_hash);
+//!
+//! let signer = AwsV4Signer::new("access key", "secret key", "us-east-1", "s3");
+//! let authorization = signer.authorization(alg, &request, datetime);
+//! # }
+//! ```
+//!
+//! [AWS Signature Version 4]: https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv.html
+use core::error;
+use core::fmt::{self, Write};
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Error type for request signing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SigningError {
+    /// A header selected for signing has a name or a value that is not valid UTF-8.
+    InvalidHeader,
+}
+
+impl fmt::Display for SigningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigningError::InvalidHeader => f.write_str("invalid header for signing"),
+        }
+    }
+}
+
+impl error::Error for SigningError {}
+
+/// Hash and message authentication functions used to sign a request.
+pub trait SigningAlgorithm {
+    /// Returns the name of the algorithm as used in the signature, e.g. `HMAC-SHA256`.
+    fn name(&self) -> &str;
+
+    /// Computes the digest of `data`.
+    fn hash(&self, data: &[u8]) -> Vec<u8>;
+
+    /// Computes the message authentication code of `data` with `key`.
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8>;
+}
+
+/// Canonical form of an HTTP request.
+///
+/// The [Display](fmt::Display) implementation produces the canonical request string:
+/// ```text
+/// METHOD
+/// /canonical/path
+/// canonical=query&string=
+/// header:value
+/// ...
+///
+/// header;...
+/// payload hash
+/// ```
+///
+/// The path and the query arguments are percent-decoded and then encoded again, with every byte
+/// except the unreserved characters of RFC 3986 written as an uppercase escape. The query arguments
+/// are sorted by name and value. The header names are converted to lowercase, the values are
+/// trimmed and the sequences of whitespace characters are replaced with a single space. The values
+/// of the repeated headers are joined with a comma.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanonicalRequest {
+    method: String,
+    path: String,
+    query: String,
+    /// Headers sorted by the lowercase name.
+    headers: Vec<(String, String)>,
+    payload_hash: String,
+}
+
+impl CanonicalRequest {
+    /// Creates a canonical request from the method and the request target, e.g. the unparsed URI
+    /// of a request.
+    pub fn new(method: &str, uri: impl AsRef<[u8]>) -> Self {
+        let uri = uri.as_ref();
+        let (path, query) = match uri.iter().position(|&c| c == b'?') {
+            Some(pos) => (&uri[..pos], &uri[pos + 1..]),
+            None => (uri, &b""[..]),
+        };
+
+        Self {
+            method: method.to_string(),
+            path: canonical_path(path),
+            query: canonical_query(query),
+            headers: Vec::new(),
+            payload_hash: String::new(),
+        }
+    }
+
+    /// Returns the canonical path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the canonical query string.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Adds a header to the signed headers.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.add_header(name, value);
+        self
+    }
+
+    /// Adds the headers with the names from `names` to the signed headers.
+    ///
+    /// The names are compared case-insensitively. This can be used to select the headers of an
+    /// nginx request returned by [Request::headers_in_iterator].
+    ///
+    /// [Request::headers_in_iterator]: crate::http::Request::headers_in_iterator
+    pub fn headers_from<I, N, V>(mut self, headers: I, names: &[&str]) -> Result<Self, SigningError>
+    where
+        I: IntoIterator<Item = (N, V)>,
+        N: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        for (name, value) in headers {
+            let name = name.as_ref();
+            if !names
+                .iter()
+                .any(|x| x.as_bytes().eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+
+            let name = core::str::from_utf8(name).map_err(|_| SigningError::InvalidHeader)?;
+            let value =
+                core::str::from_utf8(value.as_ref()).map_err(|_| SigningError::InvalidHeader)?;
+            self.add_header(name, value);
+        }
+
+        Ok(self)
+    }
+
+    /// Sets the hex-encoded hash of the request payload.
+    pub fn payload_hash(mut self, hash: &str) -> Self {
+        self.payload_hash = hash.to_string();
+        self
+    }
+
+    /// Returns the list of signed header names, separated by semicolons.
+    pub fn signed_headers(&self) -> String {
+        let mut out = String::new();
+        for (i, (name, _)) in self.headers.iter().enumerate() {
+            if i > 0 {
+                out.push(';');
+            }
+            out.push_str(name);
+        }
+        out
+    }
+
+    fn add_header(&mut self, name: &str, value: &str) {
+        let name = name.to_ascii_lowercase();
+
+        let mut canonical = String::with_capacity(value.len());
+        for word in value.split_ascii_whitespace() {
+            if !canonical.is_empty() {
+                canonical.push(' ');
+            }
+            canonical.push_str(word);
+        }
+
+        match self.headers.binary_search_by(|(x, _)| x.cmp(&name)) {
+            Ok(i) => {
+                let prev = &mut self.headers[i].1;
+                prev.push(',');
+                prev.push_str(&canonical);
+            }
+            Err(i) => self.headers.insert(i, (name, canonical)),
+        }
+    }
+}
+
+impl fmt::Display for CanonicalRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.method)?;
+        writeln!(f, "{}", self.path)?;
+        writeln!(f, "{}", self.query)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{name}:{value}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{}", self.signed_headers())?;
+        f.write_str(&self.payload_hash)
+    }
+}
+
+/// AWS Signature Version 4 signer.
+///
+/// The algorithm is expected to be `HMAC-SHA256`, but any [SigningAlgorithm] accepted by the
+/// service can be used.
+#[derive(Clone, Copy)]
+pub struct AwsV4Signer<'a> {
+    access_key: &'a str,
+    secret_key: &'a str,
+    region: &'a str,
+    service: &'a str,
+}
+
+impl<'a> AwsV4Signer<'a> {
+    /// Creates a signer with the specified credentials and the service scope.
+    pub fn new(
+        access_key: &'a str,
+        secret_key: &'a str,
+        region: &'a str,
+        service: &'a str,
+    ) -> Self {
+        Self {
+            access_key,
+            secret_key,
+            region,
+            service,
+        }
+    }
+
+    /// Returns the credential scope for the `datetime` in the `YYYYMMDDTHHMMSSZ` format.
+    pub fn scope(&self, datetime: &str) -> String {
+        let date = datetime.get(..8).unwrap_or(datetime);
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{}/{}/{}/aws4_request",
+            date, self.region, self.service
+        );
+        out
+    }
+
+    /// Returns the string to sign for the `request` at `datetime`.
+    pub fn string_to_sign(
+        &self,
+        alg: &impl SigningAlgorithm,
+        request: &CanonicalRequest,
+        datetime: &str,
+    ) -> String {
+        let hash = alg.hash(request.to_string().as_bytes());
+
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "AWS4-{}\n{}\n{}\n{}",
+            alg.name(),
+            datetime,
+            self.scope(datetime),
+            hex_encode(&hash)
+        );
+        out
+    }
+
+    /// Returns the hex-encoded signature of the `request` at `datetime`.
+    pub fn signature(
+        &self,
+        alg: &impl SigningAlgorithm,
+        request: &CanonicalRequest,
+        datetime: &str,
+    ) -> String {
+        let date = datetime.get(..8).unwrap_or(datetime);
+
+        let mut key = String::with_capacity(4 + self.secret_key.len());
+        key.push_str("AWS4");
+        key.push_str(self.secret_key);
+
+        let key = alg.hmac(key.as_bytes(), date.as_bytes());
+        let key = alg.hmac(&key, self.region.as_bytes());
+        let key = alg.hmac(&key, self.service.as_bytes());
+        let key = alg.hmac(&key, b"aws4_request");
+
+        let string_to_sign = self.string_to_sign(alg, request, datetime);
+        hex_encode(&alg.hmac(&key, string_to_sign.as_bytes()))
+    }
+
+    /// Returns the value of the `Authorization` header for the `request` at `datetime`.
+    pub fn authorization(
+        &self,
+        alg: &impl SigningAlgorithm,
+        request: &CanonicalRequest,
+        datetime: &str,
+    ) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "AWS4-{} Credential={}/{}, SignedHeaders={}, Signature={}",
+            alg.name(),
+            self.access_key,
+            self.scope(datetime),
+            request.signed_headers(),
+            self.signature(alg, request, datetime)
+        );
+        out
+    }
+}
+
+impl fmt::Debug for AwsV4Signer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsV4Signer")
+            .field("access_key", &self.access_key)
+            .field("secret_key", &format_args!("<redacted>"))
+            .field("region", &self.region)
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+/// Encodes `data` as a lowercase hexadecimal string.
+pub fn hex_encode(data: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let mut out = String::with_capacity(data.len() * 2);
+    for &c in data {
+        out.push(HEX[(c >> 4) as usize] as char);
+        out.push(HEX[(c & 0xf) as usize] as char);
+    }
+    out
+}
+
+/// Appends `data` to `out`, escaping every byte except the unreserved characters of RFC 3986 and,
+/// if `keep_slash` is set, the slash.
+pub fn uri_encode(out: &mut String, data: &[u8], keep_slash: bool) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    for &c in data {
+        if c.is_ascii_alphanumeric()
+            || matches!(c, b'-' | b'.' | b'_' | b'~')
+            || (keep_slash && c == b'/')
+        {
+            out.push(c as char);
+        } else {
+            out.push('%');
+            out.push(HEX[(c >> 4) as usize] as char);
+            out.push(HEX[(c & 0xf) as usize] as char);
+        }
+    }
+}
+
+/// Decodes the percent-encoded bytes of `data`. Invalid escape sequences are kept as is.
+fn uri_decode(data: &[u8]) -> Vec<u8> {
+    fn hex(c: u8) -> Option<u8> {
+        (c as char).to_digit(16).map(|x| x as u8)
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] == b'%' && i + 2 < data.len() {
+            if let (Some(h), Some(l)) = (hex(data[i + 1]), hex(data[i + 2])) {
+                out.push(h << 4 | l);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(data[i]);
+        i += 1;
+    }
+    out
+}
+
+fn canonical_path(path: &[u8]) -> String {
+    let mut out = String::with_capacity(path.len() + 1);
+    if path.first() != Some(&b'/') {
+        out.push('/');
+    }
+    // decode the segments separately to preserve the encoded slashes
+    for (i, segment) in path.split(|&c| c == b'/').enumerate() {
+        if i > 0 {
+            out.push('/');
+        }
+        uri_encode(&mut out, &uri_decode(segment), false);
+    }
+    out
+}
+
+fn canonical_query(query: &[u8]) -> String {
+    let mut args: Vec<(String, String)> = query
+        .split(|&c| c == b'&')
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            let (name, value) = match arg.iter().position(|&c| c == b'=') {
+                Some(pos) => (&arg[..pos], &arg[pos + 1..]),
+                None => (arg, &b""[..]),
+            };
+
+            let mut encoded = (String::new(), String::new());
+            uri_encode(&mut encoded.0, &uri_decode(name), false);
+            uri_encode(&mut encoded.1, &uri_decode(value), false);
+            encoded
+        })
+        .collect();
+
+    args.sort_unstable();
+
+    let mut out = String::with_capacity(query.len());
+    for (i, (name, value)) in args.iter().enumerate() {
+        if i > 0 {
+            out.push('&');
+        }
+        out.push_str(name);
+        out.push('=');
+        out.push_str(value);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    struct Dummy;
+
+    impl SigningAlgorithm for Dummy {
+        fn name(&self) -> &str {
+            "HMAC-DUMMY"
+        }
+
+        fn hash(&self, data: &[u8]) -> Vec<u8> {
+            [data.len() as u8].to_vec()
+        }
+
+        fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+            [key.len() as u8, data.len() as u8].to_vec()
+        }
+    }
+
+    /// A minimal SHA-256 implementation for the known-answer tests.
+    struct Sha256;
+
+    impl Sha256 {
+        const K: [u32; 64] = [
+            0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+            0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+            0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+            0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+            0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+            0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+            0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+            0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+            0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+            0xc67178f2,
+        ];
+
+        fn digest(data: &[u8]) -> Vec<u8> {
+            let mut h: [u32; 8] = [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ];
+
+            let mut msg = data.to_vec();
+            msg.push(0x80);
+            while msg.len() % 64 != 56 {
+                msg.push(0);
+            }
+            msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+            for block in msg.chunks(64) {
+                let mut w = [0u32; 64];
+                for (i, x) in block.chunks(4).enumerate() {
+                    w[i] = u32::from_be_bytes([x[0], x[1], x[2], x[3]]);
+                }
+                for i in 16..64 {
+                    let s0 =
+                        w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+                    let s1 =
+                        w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+                    w[i] = w[i - 16]
+                        .wrapping_add(s0)
+                        .wrapping_add(w[i - 7])
+                        .wrapping_add(s1);
+                }
+
+                let mut v = h;
+                for (k, w) in Self::K.iter().zip(w) {
+                    let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+                    let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+                    let t1 = v[7]
+                        .wrapping_add(s1)
+                        .wrapping_add(ch)
+                        .wrapping_add(*k)
+                        .wrapping_add(w);
+                    let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+                    let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+                    let t2 = s0.wrapping_add(maj);
+
+                    v.copy_within(0..7, 1);
+                    v[4] = v[4].wrapping_add(t1);
+                    v[0] = t1.wrapping_add(t2);
+                }
+
+                for (x, y) in h.iter_mut().zip(v) {
+                    *x = x.wrapping_add(y);
+                }
+            }
+
+            h.iter().flat_map(|x| x.to_be_bytes()).collect()
+        }
+    }
+
+    impl SigningAlgorithm for Sha256 {
+        fn name(&self) -> &str {
+            "HMAC-SHA256"
+        }
+
+        fn hash(&self, data: &[u8]) -> Vec<u8> {
+            Self::digest(data)
+        }
+
+        fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+            let mut block = [0u8; 64];
+            if key.len() > block.len() {
+                block[..32].copy_from_slice(&Self::digest(key));
+            } else {
+                block[..key.len()].copy_from_slice(key);
+            }
+
+            let mut inner: Vec<u8> = block.iter().map(|x| x ^ 0x36).collect();
+            inner.extend_from_slice(data);
+            let mut outer: Vec<u8> = block.iter().map(|x| x ^ 0x5c).collect();
+            outer.extend_from_slice(&Self::digest(&inner));
+            Self::digest(&outer)
+        }
+    }
+
+    #[test]
+    fn test_canonical_uri() {
+        let req = CanonicalRequest::new("GET", "/a%2fb/c d/%7Eü?b=2&a=x%20y&a=1&c&=");
+        assert_eq!(req.path(), "/a%2Fb/c%20d/~%C3%BC");
+        assert_eq!(req.query(), "=&a=1&a=x%20y&b=2&c=");
+
+        let req = CanonicalRequest::new("GET", "");
+        assert_eq!(req.path(), "/");
+        assert_eq!(req.query(), "");
+
+        let req = CanonicalRequest::new("GET", "/%zz%4?a+b=%");
+        assert_eq!(req.path(), "/%25zz%254");
+        assert_eq!(req.query(), "a%2Bb=%25");
+    }
+
+    #[test]
+    fn test_canonical_request() {
+        // get-vanilla from the AWS Signature Version 4 test suite
+        let req = CanonicalRequest::new("GET", "/")
+            .header("Host", "example.amazonaws.com")
+            .header("X-Amz-Date", "20150830T123600Z")
+            .payload_hash(EMPTY_SHA256);
+
+        assert_eq!(
+            req.to_string(),
+            "GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:20150830T123600Z\n\n\
+             host;x-amz-date\n"
+                .to_string()
+                + EMPTY_SHA256
+        );
+    }
+
+    #[test]
+    fn test_canonical_headers() {
+        let headers = [
+            ("X-B", &b"  two   words "[..]),
+            ("Host", b"example.com"),
+            ("x-b", b"next"),
+            ("Ignored", b"value"),
+        ];
+
+        let req = CanonicalRequest::new("GET", "/")
+            .headers_from(headers, &["host", "X-B"])
+            .unwrap();
+        assert_eq!(req.signed_headers(), "host;x-b");
+        assert_eq!(req.headers[1].1, "two words,next");
+
+        let headers = [("Host", &b"\xff"[..])];
+        let req = CanonicalRequest::new("GET", "/").headers_from(headers, &["host"]);
+        assert_eq!(req, Err(SigningError::InvalidHeader));
+    }
+
+    #[test]
+    fn test_aws_v4() {
+        let req = CanonicalRequest::new("GET", "/")
+            .header("Host", "example.amazonaws.com")
+            .payload_hash(EMPTY_SHA256);
+        let signer = AwsV4Signer::new("AKID", "secret", "us-east-1", "service");
+
+        assert_eq!(
+            signer.string_to_sign(&Dummy, &req, "20150830T123600Z"),
+            "AWS4-HMAC-DUMMY\n20150830T123600Z\n20150830/us-east-1/service/aws4_request\n68"
+        );
+        assert_eq!(
+            signer.authorization(&Dummy, &req, "20150830T123600Z"),
+            "AWS4-HMAC-DUMMY Credential=AKID/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host, Signature=024b"
+        );
+    }
+
+    #[test]
+    fn test_aws_v4_get_vanilla() {
+        // get-vanilla from the AWS Signature Version 4 test suite
+        let datetime = "20150830T123600Z";
+        let req = CanonicalRequest::new("GET", "/")
+            .header("Host", "example.amazonaws.com")
+            .header("X-Amz-Date", datetime)
+            .payload_hash(&hex_encode(&Sha256.hash(b"")));
+        let signer = AwsV4Signer::new(
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "us-east-1",
+            "service",
+        );
+
+        assert_eq!(
+            signer.string_to_sign(&Sha256, &req, datetime),
+            "AWS4-HMAC-SHA256\n20150830T123600Z\n20150830/us-east-1/service/aws4_request\n\
+             bb579772317eb040ac9ed261061d46c1f17a8133879d6129b6e1c25292927e63"
+        );
+        assert_eq!(
+            signer.authorization(&Sha256, &req, datetime),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_aws_v4_debug() {
+        let signer = AwsV4Signer::new("AKID", "SECRET", "us-east-1", "service");
+        let mut debug = String::new();
+        write!(debug, "{signer:?}").unwrap();
+        assert!(debug.contains("AKID"));
+        assert!(!debug.contains("SECRET"));
+    }
+}
@@ -0,0 +1,288 @@
+use core::error;
+use core::ffi::c_void;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::slice;
+use core::task::{self, Poll, Waker};
+
+use crate::allocator::{self, AllocError};
+use crate::core::{NgxStr, Status, TemporaryBuffer};
+use crate::ffi::{
+    ngx_http_post_subrequest_t, ngx_http_request_t, ngx_http_subrequest, ngx_int_t, ngx_post_event,
+    ngx_posted_events, ngx_str_t, NGX_HTTP_SUBREQUEST_IN_MEMORY, NGX_HTTP_SUBREQUEST_WAITED,
+};
+use crate::http::{HTTPStatus, Headers, Request};
+use crate::ngx_log_debug_http;
+
+/// An error returned by [Request::subrequest_async].
+#[derive(Debug, PartialEq, Eq)]
+pub enum SubrequestError {
+    /// Memory allocation failed.
+    Alloc,
+    /// `ngx_http_subrequest` failed, e.g. because of the subrequest limit.
+    Create,
+}
+
+impl error::Error for SubrequestError {}
+
+impl fmt::Display for SubrequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubrequestError::Alloc => f.write_str("subrequest allocation failed"),
+            SubrequestError::Create => f.write_str("failed to create subrequest"),
+        }
+    }
+}
+
+impl From<AllocError> for SubrequestError {
+    fn from(_: AllocError) -> Self {
+        SubrequestError::Alloc
+    }
+}
+
+/// The result of a completed subrequest.
+///
+/// The subrequest is allocated from the main request pool, and the result remains valid until the
+/// main request is finalized.
+pub struct SubrequestResult {
+    request: NonNull<ngx_http_request_t>,
+    rc: ngx_int_t,
+}
+
+impl SubrequestResult {
+    /// Returns the finalization code passed to the `post_subrequest` handler.
+    pub fn rc(&self) -> Status {
+        Status(self.rc)
+    }
+
+    /// Returns the response status of the subrequest.
+    pub fn status(&self) -> HTTPStatus {
+        HTTPStatus(unsafe { self.request.as_ref().headers_out.status })
+    }
+
+    /// Returns the subrequest.
+    pub fn request(&self) -> &Request {
+        // SAFETY: the subrequest is valid until the main request is finalized.
+        unsafe { Request::from_ngx_http_request(self.request.as_ptr()) }
+    }
+
+    /// Returns the output headers of the subrequest.
+    pub fn headers_out(&self) -> &Headers {
+        self.request().headers_out()
+    }
+
+    /// Returns the buffer with the response body of the subrequest.
+    ///
+    /// The body is limited by [subrequest_output_buffer_size]; the subrequest fails if the
+    /// response does not fit.
+    ///
+    /// [subrequest_output_buffer_size]: https://nginx.org/en/docs/http/ngx_http_core_module.html#subrequest_output_buffer_size
+    pub fn buffer(&self) -> Option<TemporaryBuffer> {
+        let out = unsafe { self.request.as_ref().out };
+        if out.is_null() || unsafe { (*out).buf }.is_null() {
+            return None;
+        }
+        Some(TemporaryBuffer::from_ngx_buf(unsafe { (*out).buf }))
+    }
+
+    /// Returns the response body of the subrequest.
+    pub fn body(&self) -> &[u8] {
+        let out = unsafe { self.request.as_ref().out };
+        if out.is_null() {
+            return &[];
+        }
+
+        // SAFETY: the in-memory subrequest body is a single memory buffer in the request pool.
+        unsafe {
+            let buf = (*out).buf;
+            if buf.is_null() || (*buf).pos.is_null() {
+                return &[];
+            }
+            slice::from_raw_parts((*buf).pos, (*buf).last.offset_from((*buf).pos) as usize)
+        }
+    }
+}
+
+impl fmt::Debug for SubrequestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubrequestResult")
+            .field("rc", &self.rc)
+            .field("status", &self.status().0)
+            .field("body", &NgxStr::from_bytes(self.body()))
+            .finish()
+    }
+}
+
+impl Request {
+    /// Creates an in-memory subrequest and returns a future resolving when it is complete.
+    ///
+    /// The response body is captured in memory and is available via [SubrequestResult::body].
+    /// The subrequest is processed by the NGINX event loop, and the task awaiting the future is
+    /// woken from the `post_subrequest` handler.
+    ///
+    /// As with any subrequest created with `NGX_HTTP_SUBREQUEST_WAITED`, NGINX posts the parent
+    /// request once the subrequest is finalized. Phase handlers waiting for the result must be
+    /// prepared to be called again and return `NGX_AGAIN` or `NGX_DONE` until the task completes.
+    ///
+    /// See <https://nginx.org/en/docs/dev/development_guide.html#http_subrequests>
+    pub fn subrequest_async(
+        &mut self,
+        uri: &str,
+        args: Option<&str>,
+    ) -> impl Future<Output = Result<SubrequestResult, SubrequestError>> {
+        let result = self.create_subrequest(uri, args);
+        SubrequestFuture {
+            result: Some(result),
+        }
+    }
+
+    fn create_subrequest(
+        &mut self,
+        uri: &str,
+        args: Option<&str>,
+    ) -> Result<NonNull<SubrequestState>, SubrequestError> {
+        let pool = self.pool();
+        let r = ptr::from_mut(self.as_mut());
+
+        // SAFETY: the strings are allocated from the request pool.
+        let mut uri = unsafe { ngx_str_t::from_bytes(pool.as_ptr(), uri.as_bytes()) }
+            .ok_or(SubrequestError::Alloc)?;
+        let mut args = match args {
+            Some(args) => Some(
+                unsafe { ngx_str_t::from_bytes(pool.as_ptr(), args.as_bytes()) }
+                    .ok_or(SubrequestError::Alloc)?,
+            ),
+            None => None,
+        };
+
+        let state = allocator::allocate(
+            SubrequestState {
+                request: ptr::null_mut(),
+                rc: None,
+                waker: None,
+            },
+            &pool,
+        )?;
+
+        let ps = allocator::allocate(
+            ngx_http_post_subrequest_t {
+                handler: Some(post_subrequest_handler),
+                data: state.as_ptr().cast(),
+            },
+            &pool,
+        )?;
+
+        let mut sr: *mut ngx_http_request_t = ptr::null_mut();
+
+        let rc = unsafe {
+            ngx_http_subrequest(
+                r,
+                &mut uri,
+                args.as_mut().map_or(ptr::null_mut(), ptr::from_mut),
+                &mut sr,
+                ps.as_ptr(),
+                (NGX_HTTP_SUBREQUEST_IN_MEMORY | NGX_HTTP_SUBREQUEST_WAITED) as _,
+            )
+        };
+
+        if rc != Status::NGX_OK.into() || sr.is_null() {
+            return Err(SubrequestError::Create);
+        }
+
+        unsafe { (*state.as_ptr()).request = sr };
+
+        ngx_log_debug_http!(self, "http subrequest async \"{}\"", unsafe {
+            NgxStr::from_ngx_str(uri)
+        });
+
+        // The subrequest is posted to the main request and will only run from
+        // `ngx_http_run_posted_requests`, which is called after processing connection events.
+        // Post the connection write event to ensure that happens even if the subrequest was
+        // created outside of the request handlers.
+        unsafe {
+            let c = self.connection();
+            ngx_post_event((*c).write, ptr::addr_of_mut!(ngx_posted_events));
+        }
+
+        Ok(state)
+    }
+}
+
+/// The subrequest state allocated from the request pool.
+///
+/// The state outlives the future, so that the `post_subrequest` handler never accesses freed
+/// memory.
+struct SubrequestState {
+    request: *mut ngx_http_request_t,
+    rc: Option<ngx_int_t>,
+    waker: Option<Waker>,
+}
+
+struct SubrequestFuture {
+    result: Option<Result<NonNull<SubrequestState>, SubrequestError>>,
+}
+
+impl Future for SubrequestFuture {
+    type Output = Result<SubrequestResult, SubrequestError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let state = match this.result.take().expect("polled after completion") {
+            Ok(state) => state,
+            Err(err) => return Poll::Ready(Err(err)),
+        };
+
+        // SAFETY: the state is valid until the main request is finalized.
+        let s = unsafe { &mut *state.as_ptr() };
+
+        if let Some(rc) = s.rc {
+            return Poll::Ready(Ok(SubrequestResult {
+                // SAFETY: checked in Request::create_subrequest
+                request: unsafe { NonNull::new_unchecked(s.request) },
+                rc,
+            }));
+        }
+
+        match s.waker.as_mut() {
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => s.waker = Some(cx.waker().clone()),
+        }
+
+        this.result = Some(Ok(state));
+        Poll::Pending
+    }
+}
+
+impl Drop for SubrequestFuture {
+    fn drop(&mut self) {
+        if let Some(Ok(state)) = self.result.take() {
+            // The state memory is never dropped; release the waker explicitly.
+            unsafe { (*state.as_ptr()).waker = None };
+        }
+    }
+}
+
+unsafe extern "C" fn post_subrequest_handler(
+    r: *mut ngx_http_request_t,
+    data: *mut c_void,
+    rc: ngx_int_t,
+) -> ngx_int_t {
+    let state = &mut *data.cast::<SubrequestState>();
+
+    ngx_log_debug_http!(
+        Request::from_ngx_http_request(r),
+        "http subrequest async done rc:{rc} status:{}",
+        (*r).headers_out.status
+    );
+
+    state.rc = Some(rc);
+
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+
+    rc
+}
@@ -1,3 +1,21 @@
+use core::ffi::CStr;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::core::{NgxStr, Status};
+use crate::ffi::{
+    ngx_addr_t, ngx_conf_t, ngx_http_complex_value, ngx_http_upstream_local_t,
+    ngx_http_upstream_rr_peer_t, ngx_http_upstream_rr_peers_t, ngx_http_upstream_server_t,
+    ngx_http_upstream_srv_conf_t, ngx_int_t, ngx_parse_addr_port, ngx_str_t, ngx_uint_t, time_t,
+    NGX_HTTP_UPSTREAM_CREATE, NGX_LOG_ERR,
+};
+#[cfg(ngx_feature = "http_upstream_zone")]
+use crate::ffi::{ngx_rwlock_rlock, ngx_rwlock_unlock, ngx_rwlock_wlock};
+use crate::http::{HttpModuleMainConf, NgxHttpUpstreamModule, Request};
+use crate::ngx_log_error;
+
 /// Define a static upstream peer initializer
 ///
 /// Initializes the upstream 'get', 'free', and 'session' callbacks and gives the module writer an
@@ -16,11 +34,615 @@ macro_rules! http_upstream_init_peer_pt {
             r: *mut $crate::ffi::ngx_http_request_t,
             us: *mut $crate::ffi::ngx_http_upstream_srv_conf_t,
         ) -> $crate::ffi::ngx_int_t {
-            let status: $crate::core::Status = $handler(
-                unsafe { &mut $crate::http::Request::from_ngx_http_request(r) },
-                us,
-            );
-            status.0
+            $crate::panic::catch(module_path!(), stringify!($name), || {
+                let status: $crate::core::Status = $handler(
+                    unsafe { &mut $crate::http::Request::from_ngx_http_request(r) },
+                    us,
+                );
+                status.0
+            })
+            .unwrap_or($crate::core::Status::NGX_ERROR.0)
         }
     };
 }
+
+/// A local address for outgoing connections resolved with [Request::local_address].
+#[derive(Clone, Copy, Debug)]
+pub struct LocalAddress {
+    addr: NonNull<ngx_addr_t>,
+    transparent: bool,
+}
+
+impl LocalAddress {
+    /// Returns a raw pointer to the underlying [ngx_addr_t].
+    pub fn as_ptr(&self) -> *mut ngx_addr_t {
+        self.addr.as_ptr()
+    }
+
+    /// Returns the text representation of the address.
+    pub fn name(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.addr.as_ref().name) }
+    }
+
+    /// Returns `true` if the address may be non-local, as with the `transparent` parameter of
+    /// the [proxy_bind] directive.
+    ///
+    /// [proxy_bind]: https://nginx.org/en/docs/http/ngx_http_proxy_module.html#proxy_bind
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+}
+
+impl Request {
+    /// Resolves the local address for outgoing connections of the request.
+    ///
+    /// `local` is a location configuration value set with the `ngx_http_upstream_bind_set_slot`
+    /// directive handler, which accepts the same syntax as [proxy_bind]. The handler expects the
+    /// field to be initialized with `NGX_CONF_UNSET_PTR` and merged as a pointer.
+    ///
+    /// Returns `Ok(None)` if the binding is not configured, disabled with `off`, or the address
+    /// evaluated from variables is empty or invalid. Invalid addresses are logged.
+    ///
+    /// [proxy_bind]: https://nginx.org/en/docs/http/ngx_http_proxy_module.html#proxy_bind
+    pub fn local_address(
+        &mut self,
+        local: *const ngx_http_upstream_local_t,
+    ) -> Result<Option<LocalAddress>, Status> {
+        // NGX_CONF_UNSET_PTR
+        if local.is_null() || local as usize == usize::MAX {
+            return Ok(None);
+        }
+
+        // SAFETY: the value is allocated from the configuration pool.
+        let local = unsafe { &*local };
+
+        #[cfg(ngx_feature = "have_transparent_proxy")]
+        let transparent = local.transparent != 0;
+        #[cfg(not(ngx_feature = "have_transparent_proxy"))]
+        let transparent = false;
+
+        if local.value.is_null() {
+            return Ok(NonNull::new(local.addr).map(|addr| LocalAddress { addr, transparent }));
+        }
+
+        let r = ptr::from_mut(self.as_mut());
+        let mut val = ngx_str_t::empty();
+        if unsafe { ngx_http_complex_value(r, local.value, &mut val) } != Status::NGX_OK.into() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        if val.len == 0 {
+            return Ok(None);
+        }
+
+        let mut pool = self.pool();
+        let Some(addr) = NonNull::new(pool.calloc_type::<ngx_addr_t>()) else {
+            return Err(Status::NGX_ERROR);
+        };
+
+        let rc = unsafe { ngx_parse_addr_port(pool.as_ptr(), addr.as_ptr(), val.data, val.len) };
+        if rc == Status::NGX_ERROR.into() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        if rc != Status::NGX_OK.into() {
+            ngx_log_error!(
+                NGX_LOG_ERR,
+                self.log(),
+                "invalid local address \"{}\"",
+                unsafe { NgxStr::from_ngx_str(val) }
+            );
+            return Ok(None);
+        }
+
+        unsafe { (*addr.as_ptr()).name = val };
+
+        Ok(Some(LocalAddress { addr, transparent }))
+    }
+}
+
+/// A read-only view over the peers of an upstream using the round-robin balancer data.
+///
+/// The peers may be located in the shared memory zone configured with the [zone] directive and
+/// modified by other worker processes. The view follows the locking discipline of the NGINX
+/// balancers: the peer list is accessed under the read lock of [UpstreamPeers::read], and the
+/// peer counters are copied under the peer lock in [UpstreamPeer::stats].
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::ngx_http_upstream_srv_conf_t;
+/// # use ngx::http::UpstreamPeers;
+/// # fn example(uscf: &ngx_http_upstream_srv_conf_t) {
+/// // SAFETY: the upstream uses one of the built-in balancers.
+/// let Some(peers) = (unsafe { UpstreamPeers::from_srv_conf(uscf) }) else {
+///     return;
+/// };
+///
+/// let peers = peers.read();
+/// for peer in peers.iter() {
+///     let stats = peer.stats();
+///     println!("{} conns:{} fails:{}", peer.name(), stats.conns, stats.fails);
+/// }
+/// # }
+/// ```
+///
+/// [zone]: https://nginx.org/en/docs/http/ngx_http_upstream_module.html#zone
+#[derive(Clone, Copy)]
+pub struct UpstreamPeers<'a> {
+    peers: NonNull<ngx_http_upstream_rr_peers_t>,
+    _lifetime: PhantomData<&'a ngx_http_upstream_rr_peers_t>,
+}
+
+impl<'a> UpstreamPeers<'a> {
+    /// Creates a view from a pointer to [ngx_http_upstream_rr_peers_t].
+    ///
+    /// # Safety
+    ///
+    /// `peers` is a valid pointer to the peers list, alive for `'a`.
+    pub unsafe fn from_ptr(peers: NonNull<ngx_http_upstream_rr_peers_t>) -> Self {
+        Self {
+            peers,
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// Returns the peers of the upstream configuration.
+    ///
+    /// The peers are available after the configuration is initialized. With the [zone]
+    /// directive, these are the peers in the shared memory.
+    ///
+    /// # Safety
+    ///
+    /// The upstream must use a balancer storing [ngx_http_upstream_rr_peers_t] as the peer data,
+    /// such as any of the balancers bundled with NGINX.
+    ///
+    /// [zone]: https://nginx.org/en/docs/http/ngx_http_upstream_module.html#zone
+    pub unsafe fn from_srv_conf(uscf: &'a ngx_http_upstream_srv_conf_t) -> Option<Self> {
+        NonNull::new(uscf.peer.data.cast()).map(|peers| Self::from_ptr(peers))
+    }
+
+    /// Returns `true` if the peers are located in the shared memory.
+    pub fn is_shared(&self) -> bool {
+        // SAFETY: the list is valid for 'a.
+        is_shared(unsafe { self.peers.as_ref() })
+    }
+
+    /// Locks the peers for reading.
+    pub fn read(&self) -> UpstreamPeersReadGuard<'a> {
+        #[cfg(ngx_feature = "http_upstream_zone")]
+        if self.is_shared() {
+            unsafe { ngx_rwlock_rlock(ptr::addr_of_mut!((*self.peers.as_ptr()).rwlock)) };
+        }
+
+        UpstreamPeersReadGuard(*self)
+    }
+}
+
+impl fmt::Debug for UpstreamPeers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamPeers")
+            .field("peers", &self.peers)
+            .field("shared", &self.is_shared())
+            .finish()
+    }
+}
+
+/// The peers of an upstream locked for reading with [UpstreamPeers::read].
+pub struct UpstreamPeersReadGuard<'a>(UpstreamPeers<'a>);
+
+impl<'a> UpstreamPeersReadGuard<'a> {
+    fn raw(&self) -> &ngx_http_upstream_rr_peers_t {
+        // SAFETY: the list is valid for 'a and locked.
+        unsafe { self.0.peers.as_ref() }
+    }
+
+    /// Returns the upstream name, if known.
+    pub fn name(&self) -> Option<&NgxStr> {
+        let name = self.raw().name;
+        (!name.is_null()).then(|| unsafe { NgxStr::from_ngx_str(*name) })
+    }
+
+    /// Returns the number of peers.
+    pub fn len(&self) -> usize {
+        self.raw().number
+    }
+
+    /// Returns `true` if there are no peers in the list.
+    pub fn is_empty(&self) -> bool {
+        self.raw().peer.is_null()
+    }
+
+    /// Returns the sum of the peer weights.
+    pub fn total_weight(&self) -> ngx_uint_t {
+        self.raw().total_weight
+    }
+
+    /// Returns `true` if the peers have different weights.
+    pub fn is_weighted(&self) -> bool {
+        self.raw().weighted() != 0
+    }
+
+    /// Returns the list of the backup peers.
+    ///
+    /// The backup peers have a separate lock.
+    pub fn backup(&self) -> Option<UpstreamPeers<'a>> {
+        NonNull::new(self.raw().next).map(|peers| unsafe { UpstreamPeers::from_ptr(peers) })
+    }
+
+    /// Returns an iterator over the peers.
+    pub fn iter(&self) -> UpstreamPeerIter<'_> {
+        UpstreamPeerIter {
+            peer: self.raw().peer,
+            shared: self.0.is_shared(),
+            _lifetime: PhantomData,
+        }
+    }
+}
+
+impl Drop for UpstreamPeersReadGuard<'_> {
+    fn drop(&mut self) {
+        #[cfg(ngx_feature = "http_upstream_zone")]
+        if self.0.is_shared() {
+            unsafe { ngx_rwlock_unlock(ptr::addr_of_mut!((*self.0.peers.as_ptr()).rwlock)) };
+        }
+    }
+}
+
+impl fmt::Debug for UpstreamPeersReadGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamPeersReadGuard")
+            .field("name", &self.name())
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the peers of a locked [UpstreamPeersReadGuard].
+pub struct UpstreamPeerIter<'a> {
+    peer: *mut ngx_http_upstream_rr_peer_t,
+    shared: bool,
+    _lifetime: PhantomData<&'a ngx_http_upstream_rr_peer_t>,
+}
+
+impl<'a> Iterator for UpstreamPeerIter<'a> {
+    type Item = UpstreamPeer<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let peer = NonNull::new(self.peer)?;
+        // SAFETY: the list is locked for the iterator lifetime.
+        self.peer = unsafe { peer.as_ref().next };
+
+        Some(UpstreamPeer {
+            peer,
+            shared: self.shared,
+            _lifetime: PhantomData,
+        })
+    }
+}
+
+/// A peer of an upstream.
+///
+/// The configured parameters are accessed directly, while the runtime state is copied with
+/// [UpstreamPeer::stats].
+#[derive(Clone, Copy)]
+pub struct UpstreamPeer<'a> {
+    peer: NonNull<ngx_http_upstream_rr_peer_t>,
+    #[cfg_attr(not(ngx_feature = "http_upstream_zone"), allow(dead_code))]
+    shared: bool,
+    _lifetime: PhantomData<&'a ngx_http_upstream_rr_peer_t>,
+}
+
+impl<'a> UpstreamPeer<'a> {
+    fn raw(&self) -> &'a ngx_http_upstream_rr_peer_t {
+        // SAFETY: the peer list is locked for 'a.
+        unsafe { self.peer.as_ref() }
+    }
+
+    /// Returns the address of the peer.
+    pub fn name(&self) -> &'a NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.raw().name) }
+    }
+
+    /// Returns the `server` directive value the peer was created from.
+    pub fn server(&self) -> &'a NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.raw().server) }
+    }
+
+    /// Returns the configured weight.
+    pub fn weight(&self) -> ngx_int_t {
+        self.raw().weight
+    }
+
+    /// Returns the configured `max_conns` value, 0 if unlimited.
+    pub fn max_conns(&self) -> ngx_uint_t {
+        self.raw().max_conns
+    }
+
+    /// Returns the configured `max_fails` value.
+    pub fn max_fails(&self) -> ngx_uint_t {
+        self.raw().max_fails
+    }
+
+    /// Returns the configured `fail_timeout` in seconds.
+    pub fn fail_timeout(&self) -> time_t {
+        self.raw().fail_timeout
+    }
+
+    /// Returns `true` if the peer is marked as permanently unavailable.
+    pub fn is_down(&self) -> bool {
+        self.raw().down != 0
+    }
+
+    /// Returns a consistent copy of the peer runtime state.
+    pub fn stats(&self) -> UpstreamPeerStats {
+        let peer = self.peer.as_ptr();
+
+        #[cfg(ngx_feature = "http_upstream_zone")]
+        if self.shared {
+            unsafe { ngx_rwlock_wlock(ptr::addr_of_mut!((*peer).lock)) };
+        }
+
+        // SAFETY: the counters are not modified while the peer is locked.
+        let stats = unsafe {
+            UpstreamPeerStats {
+                current_weight: (*peer).current_weight,
+                effective_weight: (*peer).effective_weight,
+                conns: (*peer).conns,
+                fails: (*peer).fails,
+                accessed: (*peer).accessed,
+                checked: (*peer).checked,
+            }
+        };
+
+        #[cfg(ngx_feature = "http_upstream_zone")]
+        if self.shared {
+            unsafe { ngx_rwlock_unlock(ptr::addr_of_mut!((*peer).lock)) };
+        }
+
+        stats
+    }
+}
+
+impl fmt::Debug for UpstreamPeer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamPeer")
+            .field("name", &self.name())
+            .field("weight", &self.weight())
+            .field("down", &self.is_down())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The runtime state of an [UpstreamPeer].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UpstreamPeerStats {
+    /// The current weight of the smooth weighted round-robin algorithm.
+    pub current_weight: ngx_int_t,
+    /// The effective weight, reduced after failures.
+    pub effective_weight: ngx_int_t,
+    /// The number of active connections.
+    pub conns: ngx_uint_t,
+    /// The number of failures within the current `fail_timeout` interval.
+    pub fails: ngx_uint_t,
+    /// The time of the last failure, in seconds since the Epoch.
+    pub accessed: time_t,
+    /// The time the peer was last selected after a failure, in seconds since the Epoch.
+    pub checked: time_t,
+}
+
+#[cfg(ngx_feature = "http_upstream_zone")]
+fn is_shared(peers: &ngx_http_upstream_rr_peers_t) -> bool {
+    !peers.shpool.is_null()
+}
+
+#[cfg(not(ngx_feature = "http_upstream_zone"))]
+fn is_shared(_peers: &ngx_http_upstream_rr_peers_t) -> bool {
+    false
+}
+
+/// An upstream configuration, created with the [upstream] block or implicitly by a directive
+/// such as `proxy_pass`.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::{ngx_conf_t, ngx_int_t, NGX_LOG_EMERG};
+/// # use ngx::core::Status;
+/// # use ngx::http::UpstreamConf;
+/// # use ngx::ngx_conf_log_error;
+/// unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+///     for uscf in UpstreamConf::all(unsafe { &*cf }) {
+///         if uscf.is_explicit() && uscf.servers().is_empty() {
+///             let host = uscf.host();
+///             ngx_conf_log_error!(NGX_LOG_EMERG, cf, "no servers in upstream \"{host}\"");
+///             return Status::NGX_ERROR.into();
+///         }
+///     }
+///
+///     Status::NGX_OK.into()
+/// }
+/// ```
+///
+/// [upstream]: https://nginx.org/en/docs/http/ngx_http_upstream_module.html#upstream
+#[repr(transparent)]
+pub struct UpstreamConf(ngx_http_upstream_srv_conf_t);
+
+impl UpstreamConf {
+    /// Returns the upstream configurations of the `http` block.
+    ///
+    /// The list is complete after the `http` block is parsed, e.g. in the module
+    /// `postconfiguration` handler.
+    pub fn all(cf: &ngx_conf_t) -> &[&UpstreamConf] {
+        let Some(umcf) = NgxHttpUpstreamModule::main_conf(cf) else {
+            return &[];
+        };
+        // SAFETY: the array holds non-null pointers to ngx_http_upstream_srv_conf_t, and
+        // UpstreamConf is a transparent wrapper.
+        unsafe { umcf.upstreams.as_slice() }
+    }
+
+    /// Returns the mutable upstream configurations of the `http` block.
+    pub fn all_mut(cf: &mut ngx_conf_t) -> &mut [&mut UpstreamConf] {
+        let Some(umcf) = NgxHttpUpstreamModule::main_conf_mut(cf) else {
+            return &mut [];
+        };
+        // SAFETY: the array holds distinct non-null pointers to ngx_http_upstream_srv_conf_t.
+        unsafe { umcf.upstreams.as_slice_mut() }
+    }
+
+    /// Creates an upstream configuration reference from a pointer.
+    ///
+    /// # Safety
+    ///
+    /// `uscf` is a valid pointer to [ngx_http_upstream_srv_conf_t], alive for `'a`.
+    pub unsafe fn from_ptr<'a>(uscf: *mut ngx_http_upstream_srv_conf_t) -> &'a mut Self {
+        &mut *uscf.cast()
+    }
+
+    /// Returns the upstream name, or the address for an implicit upstream.
+    pub fn host(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.host) }
+    }
+
+    /// Returns the port of an implicit upstream, 0 if not specified.
+    pub fn port(&self) -> u16 {
+        self.0.port
+    }
+
+    /// Returns the `NGX_HTTP_UPSTREAM_*` flags describing the supported server parameters.
+    pub fn flags(&self) -> ngx_uint_t {
+        self.0.flags
+    }
+
+    /// Returns `true` if the upstream is defined with the [upstream] block.
+    ///
+    /// [upstream]: https://nginx.org/en/docs/http/ngx_http_upstream_module.html#upstream
+    pub fn is_explicit(&self) -> bool {
+        self.0.flags & NGX_HTTP_UPSTREAM_CREATE as ngx_uint_t != 0
+    }
+
+    /// Returns the configuration file and line where the upstream is defined.
+    pub fn location(&self) -> (&CStr, ngx_uint_t) {
+        let file = if self.0.file_name.is_null() {
+            c""
+        } else {
+            unsafe { CStr::from_ptr(self.0.file_name.cast()) }
+        };
+        (file, self.0.line)
+    }
+
+    /// Returns the configured servers.
+    pub fn servers(&self) -> &[UpstreamServer] {
+        match unsafe { self.0.servers.as_ref() } {
+            // SAFETY: the array holds ngx_http_upstream_server_t elements, and UpstreamServer
+            // is a transparent wrapper.
+            Some(servers) => unsafe { servers.as_slice() },
+            None => &[],
+        }
+    }
+
+    /// Returns the mutable configured servers.
+    ///
+    /// The servers can be modified until the upstream is initialized in `init_main_conf` of the
+    /// upstream module.
+    pub fn servers_mut(&mut self) -> &mut [UpstreamServer] {
+        match unsafe { self.0.servers.as_mut() } {
+            Some(servers) => unsafe { servers.as_slice_mut() },
+            None => &mut [],
+        }
+    }
+}
+
+impl AsRef<ngx_http_upstream_srv_conf_t> for UpstreamConf {
+    fn as_ref(&self) -> &ngx_http_upstream_srv_conf_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_http_upstream_srv_conf_t> for UpstreamConf {
+    fn as_mut(&mut self) -> &mut ngx_http_upstream_srv_conf_t {
+        &mut self.0
+    }
+}
+
+impl fmt::Debug for UpstreamConf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamConf")
+            .field("host", &self.host())
+            .field("port", &self.port())
+            .field("servers", &self.servers())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A `server` directive of an upstream configuration.
+#[repr(transparent)]
+pub struct UpstreamServer(ngx_http_upstream_server_t);
+
+impl UpstreamServer {
+    /// Returns the server address as specified in the configuration.
+    pub fn name(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.0.name) }
+    }
+
+    /// Returns the addresses the server name resolved to.
+    pub fn addrs(&self) -> &[ngx_addr_t] {
+        if self.0.addrs.is_null() {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.0.addrs, self.0.naddrs) }
+    }
+
+    /// Returns the configured weight.
+    pub fn weight(&self) -> ngx_uint_t {
+        self.0.weight
+    }
+
+    /// Returns the configured `max_conns` value, 0 if unlimited.
+    pub fn max_conns(&self) -> ngx_uint_t {
+        self.0.max_conns
+    }
+
+    /// Returns the configured `max_fails` value.
+    pub fn max_fails(&self) -> ngx_uint_t {
+        self.0.max_fails
+    }
+
+    /// Returns the configured `fail_timeout` in seconds.
+    pub fn fail_timeout(&self) -> time_t {
+        self.0.fail_timeout
+    }
+
+    /// Returns `true` if the server is marked with the `down` parameter.
+    pub fn is_down(&self) -> bool {
+        self.0.down != 0
+    }
+
+    /// Returns `true` if the server is marked with the `backup` parameter.
+    pub fn is_backup(&self) -> bool {
+        self.0.backup() != 0
+    }
+}
+
+impl AsRef<ngx_http_upstream_server_t> for UpstreamServer {
+    fn as_ref(&self) -> &ngx_http_upstream_server_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_http_upstream_server_t> for UpstreamServer {
+    fn as_mut(&mut self) -> &mut ngx_http_upstream_server_t {
+        &mut self.0
+    }
+}
+
+impl fmt::Debug for UpstreamServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpstreamServer")
+            .field("name", &self.name())
+            .field("naddrs", &self.0.naddrs)
+            .field("weight", &self.weight())
+            .field("down", &self.is_down())
+            .field("backup", &self.is_backup())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,384 @@
+use core::error;
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr;
+use core::slice;
+
+use crate::allocator::AllocError;
+use crate::core::{Callback, Pool, Status};
+use crate::ffi::{
+    ngx_conf_t, ngx_http_add_variable, ngx_http_get_variable_index, ngx_http_request_t, ngx_int_t,
+    ngx_pnalloc, ngx_str_t, ngx_uint_t, ngx_variable_value_t, NGX_HTTP_VAR_CHANGEABLE,
+    NGX_HTTP_VAR_NOCACHEABLE, NGX_HTTP_VAR_NOHASH, NGX_HTTP_VAR_WEAK,
+};
+use crate::http::Request;
+
+/// An error returned by [Variable::register] and [VariableIndex::new].
+#[derive(Debug, PartialEq, Eq)]
+pub enum VariableError {
+    /// Memory allocation failed.
+    Alloc,
+    /// `ngx_http_add_variable` failed, e.g. because of a conflicting variable name.
+    Add,
+    /// `ngx_http_get_variable_index` failed.
+    Index,
+}
+
+impl error::Error for VariableError {}
+
+impl From<AllocError> for VariableError {
+    fn from(_: AllocError) -> Self {
+        VariableError::Alloc
+    }
+}
+
+impl fmt::Display for VariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableError::Alloc => f.write_str("variable allocation failed"),
+            VariableError::Add => f.write_str("failed to add variable"),
+            VariableError::Index => f.write_str("failed to get variable index"),
+        }
+    }
+}
+
+/// The signature of a variable evaluation handler used by default in [Variable].
+pub type VariableGetFn = fn(&mut Request, &mut VariableValue) -> Status;
+
+/// The signature of a variable set handler used by default in [Variable].
+pub type VariableSetFn = fn(&mut Request, &VariableValue);
+
+/// A builder for HTTP variables backed by Rust closures.
+///
+/// The handlers are stored in the configuration pool and are dropped with the configuration.
+/// Registration is expected to happen in the module `preconfiguration` handler or in a directive
+/// handler.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::{ngx_conf_t, ngx_int_t};
+/// # use ngx::http::Variable;
+/// unsafe extern "C" fn preconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+///     let res = Variable::new("request_is_main")
+///         .no_cacheable()
+///         .get(|r, v| {
+///             v.set_static(if r.is_main() { b"1" } else { b"0" });
+///             Status::NGX_OK
+///         })
+///         .register(unsafe { &mut *cf });
+///
+///     match res {
+///         Ok(()) => Status::NGX_OK.into(),
+///         Err(_) => Status::NGX_ERROR.into(),
+///     }
+/// }
+/// ```
+///
+/// Variables: <https://nginx.org/en/docs/dev/development_guide.html#http_variables>
+pub struct Variable<'a, G = VariableGetFn, S = VariableSetFn> {
+    name: &'a str,
+    flags: ngx_uint_t,
+    get: Option<G>,
+    set: Option<S>,
+}
+
+impl<'a> Variable<'a> {
+    /// Creates a builder for a variable with the specified name, without the leading `$`.
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            flags: 0,
+            get: None,
+            set: None,
+        }
+    }
+}
+
+impl<'a, G, S> Variable<'a, G, S>
+where
+    G: Fn(&mut Request, &mut VariableValue) -> Status + 'static,
+    S: Fn(&mut Request, &VariableValue) + 'static,
+{
+    /// Allows redefining the variable, e.g. with the `set` directive.
+    pub fn changeable(mut self) -> Self {
+        self.flags |= NGX_HTTP_VAR_CHANGEABLE as ngx_uint_t;
+        self
+    }
+
+    /// Disables caching of the variable value within a request.
+    pub fn no_cacheable(mut self) -> Self {
+        self.flags |= NGX_HTTP_VAR_NOCACHEABLE as ngx_uint_t;
+        self
+    }
+
+    /// Excludes the variable from the variables hash, making it available only by index.
+    pub fn no_hash(mut self) -> Self {
+        self.flags |= NGX_HTTP_VAR_NOHASH as ngx_uint_t;
+        self
+    }
+
+    /// Allows other modules to redefine the variable without the `changeable` flag.
+    pub fn weak(mut self) -> Self {
+        self.flags |= NGX_HTTP_VAR_WEAK as ngx_uint_t;
+        self
+    }
+
+    /// Sets the variable evaluation handler.
+    ///
+    /// The handler is expected to fill the value and return [Status::NGX_OK], or return
+    /// [Status::NGX_ERROR] on failure.
+    pub fn get<F>(self, handler: F) -> Variable<'a, F, S>
+    where
+        F: Fn(&mut Request, &mut VariableValue) -> Status + 'static,
+    {
+        Variable {
+            name: self.name,
+            flags: self.flags,
+            get: Some(handler),
+            set: self.set,
+        }
+    }
+
+    /// Sets the handler invoked when the variable is assigned, e.g. with the `set` directive.
+    ///
+    /// Implies [Variable::changeable].
+    pub fn set<F>(self, handler: F) -> Variable<'a, G, F>
+    where
+        F: Fn(&mut Request, &VariableValue) + 'static,
+    {
+        Variable {
+            name: self.name,
+            flags: self.flags | NGX_HTTP_VAR_CHANGEABLE as ngx_uint_t,
+            get: self.get,
+            set: Some(handler),
+        }
+    }
+
+    /// Registers the variable in the configuration.
+    pub fn register(self, cf: &mut ngx_conf_t) -> Result<(), VariableError> {
+        let mut name = ngx_str_t {
+            len: self.name.len(),
+            data: self.name.as_ptr().cast_mut(),
+        };
+
+        // The name is copied to the configuration pool.
+        let var = unsafe { ngx_http_add_variable(cf, &mut name, self.flags) };
+        let Some(var) = (unsafe { var.as_mut() }) else {
+            return Err(VariableError::Add);
+        };
+
+        let has_get = self.get.is_some();
+        let has_set = self.set.is_some();
+
+        // SAFETY: the configuration pool is valid for the duration of the configuration parsing.
+        let mut pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+        let handlers = Callback::new_in(
+            VariableHandlers {
+                get: self.get,
+                set: self.set,
+            },
+            &mut pool,
+        )?;
+
+        var.data = handlers.as_ptr() as usize;
+        if has_get {
+            var.get_handler = Some(variable_get_handler::<G, S>);
+        }
+        if has_set {
+            var.set_handler = Some(variable_set_handler::<G, S>);
+        }
+
+        Ok(())
+    }
+}
+
+impl<G, S> fmt::Debug for Variable<'_, G, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Variable")
+            .field("name", &self.name)
+            .field("flags", &self.flags)
+            .field("get", &self.get.is_some())
+            .field("set", &self.set.is_some())
+            .finish()
+    }
+}
+
+/// An index of an HTTP variable, obtained at configuration time.
+///
+/// Reading a variable by index with [Request::indexed_variable] avoids the name lookup on every
+/// request. An index can be obtained for a variable that is not yet defined; NGINX reports unknown
+/// variables at the end of the configuration parsing.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::ngx_conf_t;
+/// # use ngx::http::{Request, VariableError, VariableIndex};
+/// fn configure(cf: &mut ngx_conf_t) -> Result<VariableIndex, VariableError> {
+///     VariableIndex::new(cf, "remote_user")
+/// }
+///
+/// fn handler(request: &mut Request, index: VariableIndex) {
+///     if let Some(user) = request.indexed_variable(index).and_then(|v| v.as_bytes()) {
+///         // ...
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VariableIndex(ngx_uint_t);
+
+impl VariableIndex {
+    /// Returns the index of the variable with the specified name, without the leading `$`.
+    pub fn new(cf: &mut ngx_conf_t, name: &str) -> Result<Self, VariableError> {
+        let mut name = ngx_str_t {
+            len: name.len(),
+            data: name.as_ptr().cast_mut(),
+        };
+
+        // The name is copied to the configuration pool.
+        let index = unsafe { ngx_http_get_variable_index(cf, &mut name) };
+        if index == Status::NGX_ERROR.into() {
+            return Err(VariableError::Index);
+        }
+
+        Ok(Self(index as ngx_uint_t))
+    }
+
+    /// Returns the raw index value.
+    pub fn get(&self) -> ngx_uint_t {
+        self.0
+    }
+}
+
+struct VariableHandlers<G, S> {
+    get: Option<G>,
+    set: Option<S>,
+}
+
+unsafe extern "C" fn variable_get_handler<G, S>(
+    r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) -> ngx_int_t
+where
+    G: Fn(&mut Request, &mut VariableValue) -> Status + 'static,
+    S: 'static,
+{
+    let handlers = Callback::<VariableHandlers<G, S>>::from_ptr(data as *mut c_void);
+    let Some(get) = handlers.get.as_ref() else {
+        return Status::NGX_ERROR.into();
+    };
+    get(
+        Request::from_ngx_http_request(r),
+        VariableValue::from_ptr_mut(v),
+    )
+    .into()
+}
+
+unsafe extern "C" fn variable_set_handler<G, S>(
+    r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) where
+    G: 'static,
+    S: Fn(&mut Request, &VariableValue) + 'static,
+{
+    let handlers = Callback::<VariableHandlers<G, S>>::from_ptr(data as *mut c_void);
+    if let Some(set) = handlers.set.as_ref() {
+        set(
+            Request::from_ngx_http_request(r),
+            VariableValue::from_ptr_mut(v),
+        );
+    }
+}
+
+/// Wrapper struct for an [`ngx_variable_value_t`], the value of an HTTP variable.
+#[repr(transparent)]
+pub struct VariableValue(ngx_variable_value_t);
+
+impl VariableValue {
+    /// Creates a mutable variable value reference from a pointer to [ngx_variable_value_t].
+    ///
+    /// # Safety
+    ///
+    /// `v` must be a valid pointer to a variable value not aliased for `'a`.
+    pub unsafe fn from_ptr_mut<'a>(v: *mut ngx_variable_value_t) -> &'a mut Self {
+        &mut *v.cast::<Self>()
+    }
+
+    /// Returns the value contents, if the value is valid and found.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        if self.0.valid() == 0 || self.0.not_found() != 0 {
+            return None;
+        }
+        if self.0.len() == 0 || self.0.data.is_null() {
+            return Some(&[]);
+        }
+        // SAFETY: a valid value points to `len` bytes of data.
+        Some(unsafe { slice::from_raw_parts(self.0.data, self.0.len() as usize) })
+    }
+
+    /// Copies `value` to the request pool and sets it as the variable value.
+    pub fn set(&mut self, r: &Request, value: &[u8]) -> Result<(), AllocError> {
+        let data = if value.is_empty() {
+            ptr::null_mut()
+        } else {
+            let p = unsafe { ngx_pnalloc(r.as_ref().pool, value.len()) }.cast::<u8>();
+            if p.is_null() {
+                return Err(AllocError);
+            }
+            unsafe { ptr::copy_nonoverlapping(value.as_ptr(), p, value.len()) };
+            p
+        };
+
+        self.set_raw(data, value.len());
+        Ok(())
+    }
+
+    /// Sets a static byte string as the variable value.
+    pub fn set_static(&mut self, value: &'static [u8]) {
+        self.set_raw(value.as_ptr().cast_mut(), value.len());
+    }
+
+    /// Marks the variable value as not found.
+    pub fn set_not_found(&mut self) {
+        self.0.set_valid(0);
+        self.0.set_not_found(1);
+    }
+
+    /// Disables caching of the value within the request.
+    pub fn set_no_cacheable(&mut self, no_cacheable: bool) {
+        self.0.set_no_cacheable(no_cacheable.into());
+    }
+
+    fn set_raw(&mut self, data: *mut u8, len: usize) {
+        self.0.data = data;
+        self.0.set_len(len as _);
+        self.0.set_valid(1);
+        self.0.set_no_cacheable(0);
+        self.0.set_not_found(0);
+    }
+}
+
+impl AsRef<ngx_variable_value_t> for VariableValue {
+    fn as_ref(&self) -> &ngx_variable_value_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_variable_value_t> for VariableValue {
+    fn as_mut(&mut self) -> &mut ngx_variable_value_t {
+        &mut self.0
+    }
+}
+
+impl fmt::Debug for VariableValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VariableValue")
+            .field(
+                "value",
+                &self.as_bytes().map(crate::core::NgxStr::from_bytes),
+            )
+            .finish()
+    }
+}
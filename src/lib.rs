@@ -43,14 +43,20 @@ extern crate std;
 pub mod allocator;
 #[cfg(feature = "async")]
 pub mod async_;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod collections;
 
+mod build_info;
+pub use build_info::*;
+
 /// The core module.
 ///
 /// This module provides fundamental utilities needed to interface with many NGINX primitives.
 /// String conversions, the pool (memory interface) object, and buffer APIs are covered here. These
 /// utilities will generally align with the NGINX 'core' files and APIs.
 pub mod core;
+pub mod event;
 
 /// The ffi module.
 ///
@@ -68,35 +74,121 @@ pub mod http;
 /// This module provides an interface into the NGINX logger framework.
 pub mod log;
 
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(all(unix, feature = "async"))]
+pub mod net;
+pub mod once;
+pub mod panic;
+#[cfg(any(ngx_feature = "pcre", ngx_feature = "pcre2"))]
+pub mod regex;
+pub mod shm;
+#[cfg(ngx_feature = "ssl")]
+pub mod ssl;
 pub mod sync;
+#[cfg(all(ngx_feature = "threads", feature = "std"))]
+pub mod thread;
+#[cfg(feature = "alloc")]
+pub mod util;
 
 /// Define modules exported by this library.
 ///
 /// These are normally generated by the Nginx module system, but need to be
 /// defined when building modules outside of it.
+///
+/// The modules are listed in the order they are added to NGINX. An entry may be conditionally
+/// included with a `#[cfg(...)]` attribute, and may specify the modules it should be inserted
+/// before with `: before(...)`. The order is only applied if the named modules are present;
+/// e.g. an HTTP filter module is usually inserted before `ngx_http_copy_filter_module`, so that
+/// it processes the response before the body is read from a file.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ffi::ngx_module_t;
+/// # #[allow(non_upper_case_globals)]
+/// # static mut ngx_http_example_module: ngx_module_t = ngx_module_t::default();
+/// # #[allow(non_upper_case_globals)]
+/// # static mut ngx_http_example_filter_module: ngx_module_t = ngx_module_t::default();
+/// # #[allow(non_upper_case_globals)]
+/// # static mut ngx_stream_example_module: ngx_module_t = ngx_module_t::default();
+/// ngx::ngx_modules! {
+///     ngx_http_example_module,
+///     ngx_http_example_filter_module: before(ngx_http_copy_filter_module),
+///     #[cfg(ngx_feature = "stream")]
+///     ngx_stream_example_module,
+/// }
+/// ```
 #[macro_export]
 macro_rules! ngx_modules {
-    ($( $mod:ident ),+) => {
+    ($( $tt:tt )+) => {
+        $crate::__ngx_modules!(@munch [] [] [] [] [] $( $tt )+ ,);
+    };
+}
+
+/// Implementation of [ngx_modules]: collects the module table entries, the names and the order,
+/// together with the `cfg` attribute of each entry.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __ngx_modules {
+    (@munch [$( $n:tt )*] [$( $m:tt )*] [$( $name:tt )*] [$( $on:tt )*] [$( $o:tt )*]) => {
         #[no_mangle]
-        #[allow(non_upper_case_globals)]
-        pub static mut ngx_modules: [*const $crate::ffi::ngx_module_t; $crate::count!($( $mod, )+) + 1] = [
-            $( unsafe { &$mod } as *const $crate::ffi::ngx_module_t, )+
+        #[allow(non_upper_case_globals, unused_unsafe)]
+        pub static mut ngx_modules: [
+            *const $crate::ffi::ngx_module_t;
+            <[()]>::len(&[$( $n )*]) + 1
+        ] = [
+            $( $m )*
             ::core::ptr::null()
         ];
 
         #[no_mangle]
         #[allow(non_upper_case_globals)]
-        pub static mut ngx_module_names: [*const ::core::ffi::c_char; $crate::count!($( $mod, )+) + 1] = [
-            $( concat!(stringify!($mod), "\0").as_ptr() as *const ::core::ffi::c_char, )+
+        pub static mut ngx_module_names: [
+            *const ::core::ffi::c_char;
+            <[()]>::len(&[$( $n )*]) + 1
+        ] = [
+            $( $name )*
             ::core::ptr::null()
         ];
 
         #[no_mangle]
         #[allow(non_upper_case_globals)]
-        pub static mut ngx_module_order: [*const ::core::ffi::c_char; 1] = [
+        pub static mut ngx_module_order: [
+            *const ::core::ffi::c_char;
+            <[()]>::len(&[$( $on )*]) + 1
+        ] = [
+            $( $o )*
             ::core::ptr::null()
         ];
     };
+
+    (@munch $n:tt $m:tt $name:tt $on:tt $o:tt , $( $rest:tt )*) => {
+        $crate::__ngx_modules!(@munch $n $m $name $on $o $( $rest )*);
+    };
+
+    (@munch [$( $n:tt )*] [$( $m:tt )*] [$( $name:tt )*] [$( $on:tt )*] [$( $o:tt )*]
+        #[cfg($cfg:meta)] $mod:ident $( : before($( $before:ident ),+ $(,)?) )? , $( $rest:tt )*
+    ) => {
+        $crate::__ngx_modules!(@munch
+            [$( $n )* #[cfg($cfg)] (),]
+            [$( $m )* #[cfg($cfg)] { unsafe { ::core::ptr::addr_of!($mod) } },]
+            [$( $name )* #[cfg($cfg)] { concat!(stringify!($mod), "\0").as_ptr().cast() },]
+            [$( $on )* $(
+                #[cfg($cfg)] (),
+                $( #[cfg($cfg)] { let _ = stringify!($before); }, )+
+            )?]
+            [$( $o )* $(
+                #[cfg($cfg)] { concat!(stringify!($mod), "\0").as_ptr().cast() },
+                $( #[cfg($cfg)] { concat!(stringify!($before), "\0").as_ptr().cast() }, )+
+            )?]
+            $( $rest )*
+        );
+    };
+
+    (@munch $n:tt $m:tt $name:tt $on:tt $o:tt $mod:ident $( $rest:tt )*) => {
+        $crate::__ngx_modules!(@munch $n $m $name $on $o #[cfg(all())] $mod $( $rest )*);
+    };
 }
 
 /// Count number of arguments
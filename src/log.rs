@@ -1,10 +1,15 @@
-use core::cmp;
-use core::fmt::{self, Write};
-use core::mem::MaybeUninit;
+use core::fmt;
 use core::ptr::NonNull;
 
+use crate::core::ArrayString;
 use crate::ffi::{self, ngx_err_t, ngx_log_t, ngx_uint_t, NGX_MAX_ERROR_STR};
 
+pub use self::banner::StartupBanner;
+#[cfg(feature = "log")]
+pub use self::logger::NgxLogger;
+
+mod banner;
+
 /// Size of the static buffer used to format log messages.
 ///
 /// Approximates the remaining space in `u_char[NGX_MAX_ERROR_STR]` after writing the standard
@@ -36,17 +41,20 @@ pub fn check_mask(mask: DebugMask, log_level: usize) -> bool {
 }
 
 /// Format args into a provided buffer
-// May produce incomplete UTF-8 sequences. But any writes to `ngx_log_t` already can be truncated,
-// so nothing we can do here.
+///
+/// A message without arguments is returned as is, without copying it to the buffer. The formatted
+/// message is truncated to the buffer capacity.
 #[inline]
-pub fn write_fmt<'a>(buf: &'a mut [MaybeUninit<u8>], args: fmt::Arguments<'_>) -> &'a [u8] {
+pub fn write_fmt<'a, const N: usize>(
+    buf: &'a mut ArrayString<N>,
+    args: fmt::Arguments<'_>,
+) -> &'a [u8] {
     if let Some(str) = args.as_str() {
         str.as_bytes()
     } else {
-        let mut buf = LogBuf::from(buf);
-        // nothing we can or want to do on errors
-        let _ = buf.write_fmt(args);
-        buf.filled()
+        // the implementation of `fmt::Write` does not fail
+        let _ = fmt::Write::write_fmt(buf, args);
+        buf.as_bytes()
     }
 }
 
@@ -94,24 +102,82 @@ macro_rules! ngx_log_error {
     ( $level:expr, $log:expr, $($arg:tt)+ ) => {
         let log = $log;
         let level = $level as $crate::ffi::ngx_uint_t;
-        if level < unsafe { (*log).log_level } {
-            let mut buf =
-                [const { ::core::mem::MaybeUninit::<u8>::uninit() }; $crate::log::LOG_BUFFER_SIZE];
+        if level <= unsafe { (*log).log_level } {
+            let mut buf = $crate::core::ArrayString::<{ $crate::log::LOG_BUFFER_SIZE }>::new();
             let message = $crate::log::write_fmt(&mut buf, format_args!($($arg)+));
             unsafe { $crate::log::log_error(level, log, 0, message) };
         }
     }
 }
 
+/// Write to logger at the `error` level.
+///
+/// Accepts an optional `tag:` prefix, usually the module name, prepended to the message.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ngx_log_err;
+/// # let log = ngx::log::ngx_cycle_log().as_ptr();
+/// ngx_log_err!(log, "upstream \"{}\" is unavailable", "backend");
+/// ngx_log_err!(tag: "ngx_http_example_module", log, "request failed");
+/// ```
+#[macro_export]
+macro_rules! ngx_log_err {
+    ( tag: $tag:expr, $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_err!($log, "{}: {}", $tag, format_args!($($arg)+));
+    };
+    ( $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_error!($crate::ffi::NGX_LOG_ERR, $log, $($arg)+);
+    };
+}
+
+/// Write to logger at the `warn` level.
+///
+/// Accepts an optional `tag:` prefix, see [`ngx_log_err`].
+#[macro_export]
+macro_rules! ngx_log_warn {
+    ( tag: $tag:expr, $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_warn!($log, "{}: {}", $tag, format_args!($($arg)+));
+    };
+    ( $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_error!($crate::ffi::NGX_LOG_WARN, $log, $($arg)+);
+    };
+}
+
+/// Write to logger at the `notice` level.
+///
+/// Accepts an optional `tag:` prefix, see [`ngx_log_err`].
+#[macro_export]
+macro_rules! ngx_log_notice {
+    ( tag: $tag:expr, $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_notice!($log, "{}: {}", $tag, format_args!($($arg)+));
+    };
+    ( $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_error!($crate::ffi::NGX_LOG_NOTICE, $log, $($arg)+);
+    };
+}
+
+/// Write to logger at the `info` level.
+///
+/// Accepts an optional `tag:` prefix, see [`ngx_log_err`].
+#[macro_export]
+macro_rules! ngx_log_info {
+    ( tag: $tag:expr, $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_info!($log, "{}: {}", $tag, format_args!($($arg)+));
+    };
+    ( $log:expr, $($arg:tt)+ ) => {
+        $crate::ngx_log_error!($crate::ffi::NGX_LOG_INFO, $log, $($arg)+);
+    };
+}
+
 /// Write to logger with the context of currently processed configuration file.
 #[macro_export]
 macro_rules! ngx_conf_log_error {
     ( $level:expr, $cf:expr, $($arg:tt)+ ) => {
         let cf: *mut $crate::ffi::ngx_conf_t = $cf;
         let level = $level as $crate::ffi::ngx_uint_t;
-        if level < unsafe { (*(*cf).log).log_level } {
-            let mut buf =
-                [const { ::core::mem::MaybeUninit::<u8>::uninit() }; $crate::log::LOG_BUFFER_SIZE];
+        if level <= unsafe { (*(*cf).log).log_level } {
+            let mut buf = $crate::core::ArrayString::<{ $crate::log::LOG_BUFFER_SIZE }>::new();
             let message = $crate::log::write_fmt(&mut buf, format_args!($($arg)+));
             unsafe {
                 $crate::ffi::ngx_conf_log_error(
@@ -133,8 +199,7 @@ macro_rules! ngx_log_debug {
     ( mask: $mask:expr, $log:expr, $($arg:tt)+ ) => {
         let log = $log;
         if $crate::log::check_mask($mask, unsafe { (*log).log_level }) {
-            let mut buf =
-                [const { ::core::mem::MaybeUninit::<u8>::uninit() }; $crate::log::LOG_BUFFER_SIZE];
+            let mut buf = $crate::core::ArrayString::<{ $crate::log::LOG_BUFFER_SIZE }>::new();
             let message = $crate::log::write_fmt(&mut buf, format_args!($($arg)+));
             unsafe { $crate::log::log_debug(log, 0, message) };
         }
@@ -243,49 +308,162 @@ impl From<DebugMask> for u32 {
     }
 }
 
-/// Minimal subset of unstable core::io::{BorrowedBuf,BorrowedCursor}
-struct LogBuf<'data> {
-    buf: &'data mut [MaybeUninit<u8>],
-    filled: usize,
-}
+#[cfg(feature = "log")]
+mod logger {
+    use core::cell::Cell;
+    use core::ptr::{self, NonNull};
+
+    use ::log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+    use super::{check_mask, log_debug, log_error, write_fmt, DebugMask, LOG_BUFFER_SIZE};
+    use crate::core::{assert_main_thread, is_main_thread, ArrayString};
+    use crate::ffi::{ngx_log_t, ngx_uint_t, NGX_LOG_ERR, NGX_LOG_INFO, NGX_LOG_WARN};
+
+    /// An implementation of [`log::Log`](::log::Log) writing to the NGINX error log.
+    ///
+    /// The records are written to the log set with [NgxLogger::with_log], e.g. the request
+    /// connection log, or to the log of the current cycle. `Debug` and `Trace` records are written
+    /// at the debug level and require NGINX built with `--with-debug`. The records logged outside
+    /// of the main thread of the process, e.g. from thread pool tasks, are dropped.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// # use ngx::http::Request;
+    /// # use ngx::log::NgxLogger;
+    /// // in the module `init_process` handler
+    /// let _ = NgxLogger::init();
+    ///
+    /// # fn handler(request: &mut Request) {
+    /// // in a request handler
+    /// NgxLogger::with_log(unsafe { (*request.connection()).log }, || {
+    ///     log::info!("messages from other crates go to the request log");
+    /// });
+    /// # }
+    /// ```
+    #[derive(Debug)]
+    pub struct NgxLogger;
 
-impl<'data> LogBuf<'data> {
-    pub fn filled(&self) -> &'data [u8] {
-        // SAFETY: valid bytes have been written to self.buf[..self.filled]
-        unsafe {
-            let buf = self.buf.get_unchecked(..self.filled);
-            // inlined MaybeUninit::slice_assume_init_ref
-            &*(buf as *const [MaybeUninit<u8>] as *const [u8])
+    static LOGGER: NgxLogger = NgxLogger;
+
+    /// The log set with [NgxLogger::with_log].
+    ///
+    /// The slot is confined to the main thread of the process: it is not read or written from the
+    /// other threads.
+    struct CurrentLog(Cell<*mut ngx_log_t>);
+
+    // SAFETY: the cell is only accessed with the methods below, which check the thread.
+    unsafe impl Sync for CurrentLog {}
+
+    static CURRENT: CurrentLog = CurrentLog(Cell::new(ptr::null_mut()));
+
+    impl CurrentLog {
+        /// Returns the current log, or `None` when called outside of the main thread.
+        fn get(&self) -> Option<*mut ngx_log_t> {
+            is_main_thread().then(|| self.0.get())
+        }
+
+        /// Replaces the current log.
+        ///
+        /// # Panics
+        ///
+        /// Panics if called outside of the main thread.
+        fn replace(&self, log: *mut ngx_log_t) -> *mut ngx_log_t {
+            assert_main_thread("NgxLogger::with_log");
+            self.0.replace(log)
         }
     }
 
-    pub fn append(&mut self, buf: &[u8]) -> &mut Self {
-        let n = cmp::min(self.buf.len() - self.filled, buf.len());
-        unsafe {
-            // SAFETY: The source buf has at least n bytes
-            let src = buf.get_unchecked(..n);
-            // SAFETY: &[u8] and &[MaybeUninit<u8>] have the same layout
-            let src: &[MaybeUninit<u8>] = core::mem::transmute(src);
-            // SAFETY: self.buf has at least n bytes available after self.filled
-            self.buf
-                .get_unchecked_mut(self.filled..self.filled + n)
-                .copy_from_slice(src);
+    /// Restores the outer log when the closure returns or unwinds.
+    ///
+    /// The guard is not `Send`, and is dropped on the main thread where it was created.
+    struct CurrentLogGuard(*mut ngx_log_t);
+
+    impl Drop for CurrentLogGuard {
+        fn drop(&mut self) {
+            CURRENT.0.set(self.0);
         }
-        self.filled += n;
-        self
     }
-}
 
-impl<'data> From<&'data mut [MaybeUninit<u8>]> for LogBuf<'data> {
-    fn from(buf: &'data mut [MaybeUninit<u8>]) -> Self {
-        Self { buf, filled: 0 }
+    impl NgxLogger {
+        /// Installs the logger as the global [`log`](::log) backend, enabling all the levels.
+        ///
+        /// The effective level is determined by the `error_log` configuration.
+        pub fn init() -> Result<(), SetLoggerError> {
+            ::log::set_logger(&LOGGER)?;
+            ::log::set_max_level(LevelFilter::Trace);
+            Ok(())
+        }
+
+        /// Calls `f` with the records redirected to the specified log.
+        ///
+        /// # Panics
+        ///
+        /// Panics if called outside of the main thread of the process.
+        pub fn with_log<R>(log: *mut ngx_log_t, f: impl FnOnce() -> R) -> R {
+            let _guard = CurrentLogGuard(CURRENT.replace(log));
+            f()
+        }
+
+        /// Returns the log for the records.
+        ///
+        /// The records from the threads other than the main thread are dropped: the NGINX logs
+        /// are not thread-safe, and the log of a thread pool task is not known.
+        fn current_log() -> Option<NonNull<ngx_log_t>> {
+            NonNull::new(CURRENT.get()?).or_else(|| {
+                // SAFETY: the global cycle pointer is either null or points to a valid cycle.
+                let cycle = unsafe { nginx_sys::ngx_cycle };
+                NonNull::new(unsafe { cycle.as_ref() }?.log)
+            })
+        }
     }
-}
 
-impl fmt::Write for LogBuf<'_> {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.append(s.as_bytes());
-        Ok(())
+    fn ngx_level(level: Level) -> Option<ngx_uint_t> {
+        match level {
+            Level::Error => Some(NGX_LOG_ERR as _),
+            Level::Warn => Some(NGX_LOG_WARN as _),
+            Level::Info => Some(NGX_LOG_INFO as _),
+            Level::Debug | Level::Trace => None,
+        }
+    }
+
+    fn is_enabled(log: NonNull<ngx_log_t>, level: Level) -> bool {
+        let log_level = unsafe { log.as_ref().log_level };
+        match ngx_level(level) {
+            Some(level) => level <= log_level,
+            None => check_mask(DebugMask::All, log_level),
+        }
+    }
+
+    impl Log for NgxLogger {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            Self::current_log().is_some_and(|log| is_enabled(log, metadata.level()))
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            let Some(log) = Self::current_log() else {
+                return;
+            };
+
+            if !is_enabled(log, record.level()) {
+                return;
+            }
+
+            let mut buf = ArrayString::<LOG_BUFFER_SIZE>::new();
+            let message = write_fmt(
+                &mut buf,
+                format_args!("{}: {}", record.target(), record.args()),
+            );
+
+            // SAFETY: the log pointer is valid for the duration of the call.
+            unsafe {
+                match ngx_level(record.level()) {
+                    Some(level) => log_error(level, log.as_ptr(), 0, message),
+                    None => log_debug(log.as_ptr(), 0, message),
+                }
+            }
+        }
+
+        fn flush(&self) {}
     }
 }
 
@@ -317,31 +495,4 @@ mod tests {
         r = check_mask(DebugMask::Alloc, mock.log_level);
         assert!(!r);
     }
-
-    #[test]
-    fn log_buffer() {
-        use core::str;
-
-        let mut buf = [const { MaybeUninit::<u8>::uninit() }; 32];
-        let mut buf = LogBuf::from(&mut buf[..]);
-        let words = ["Hello", "World"];
-
-        // normal write
-        write!(&mut buf, "{} {}!", words[0], words[1]).unwrap();
-        assert_eq!(str::from_utf8(buf.filled()), Ok("Hello World!"));
-
-        // overflow results in truncated output
-        write!(&mut buf, " This is a test, {}", usize::MAX).unwrap();
-        assert_eq!(
-            str::from_utf8(buf.filled()),
-            Ok("Hello World! This is a test, 184")
-        );
-
-        // and any following writes are still safe
-        write!(&mut buf, "test").unwrap();
-        assert_eq!(
-            str::from_utf8(buf.filled()),
-            Ok("Hello World! This is a test, 184")
-        );
-    }
 }
@@ -0,0 +1,169 @@
+use core::ffi::CStr;
+use core::fmt;
+
+use crate::ffi::{ngx_command_t, ngx_cycle_t, ngx_module_t, NGINX_VERSION};
+use crate::ngx_log_notice;
+
+/// Cargo features of this crate enabled in the build.
+const CRATE_FEATURES: &[(&str, bool)] = &[
+    ("alloc", cfg!(feature = "alloc")),
+    ("async", cfg!(feature = "async")),
+    ("capi", cfg!(feature = "capi")),
+    ("log", cfg!(feature = "log")),
+    ("metrics", cfg!(feature = "metrics")),
+    ("serde", cfg!(feature = "serde")),
+    ("std", cfg!(feature = "std")),
+    ("vendored", cfg!(feature = "vendored")),
+];
+
+/// A summary of the module build, logged once at startup.
+///
+/// The banner includes the module name and version, the version and the enabled features of this
+/// crate, the version, the operating system and the features of the NGINX build the module was
+/// compiled against, the directives registered by the module and any additional capabilities
+/// reported by the module. Such a line in the error log is usually the first thing asked for in a
+/// bug report.
+///
+/// The banner is intended to be logged from the `init_module` handler. It is written at the
+/// `notice` level when the first configuration is loaded, and is skipped on reloads and when
+/// testing the configuration.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::ptr;
+/// # use ngx::core::Status;
+/// # use ngx::ffi::{ngx_cycle_t, ngx_int_t, ngx_module_t};
+/// # use ngx::log::StartupBanner;
+/// # #[allow(non_upper_case_globals)]
+/// # static mut ngx_http_example_module: ngx_module_t = ngx_module_t::default();
+/// unsafe extern "C" fn init_module(cycle: *mut ngx_cycle_t) -> ngx_int_t {
+///     let module = unsafe { &*ptr::addr_of!(ngx_http_example_module) };
+///
+///     StartupBanner::new("ngx_http_example_module", module)
+///         .version(env!("CARGO_PKG_VERSION"))
+///         .capabilities(&["brotli", "zstd"])
+///         .log(unsafe { &*cycle });
+///
+///     Status::NGX_OK.into()
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct StartupBanner<'a> {
+    name: &'a str,
+    module: &'a ngx_module_t,
+    version: Option<&'a str>,
+    capabilities: &'a [&'a str],
+}
+
+impl<'a> StartupBanner<'a> {
+    /// Creates a banner for the module.
+    pub const fn new(name: &'a str, module: &'a ngx_module_t) -> Self {
+        Self {
+            name,
+            module,
+            version: None,
+            capabilities: &[],
+        }
+    }
+
+    /// Sets the version of the module.
+    pub const fn version(mut self, version: &'a str) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Sets the additional capabilities compiled into the module.
+    pub const fn capabilities(mut self, capabilities: &'a [&'a str]) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Writes the banner to the log of `cycle`.
+    ///
+    /// Does nothing if `cycle` is not the first cycle of the process or if NGINX was started
+    /// to test the configuration.
+    pub fn log(&self, cycle: &ngx_cycle_t) {
+        // SAFETY: the old cycle of a cycle being initialized is either the previous cycle or
+        // the init cycle with no configuration.
+        let is_first =
+            cycle.old_cycle.is_null() || unsafe { (*cycle.old_cycle).conf_ctx.is_null() };
+
+        if !is_first || unsafe { crate::ffi::ngx_test_config } != 0 {
+            return;
+        }
+
+        ngx_log_notice!(cycle.log, "{}", self);
+    }
+
+    /// Returns the directives registered by the module.
+    fn commands(&self) -> impl Iterator<Item = &ngx_command_t> {
+        let mut cmd = self.module.commands.cast_const();
+
+        core::iter::from_fn(move || {
+            // SAFETY: the commands array is terminated by an entry with an empty name
+            let c = unsafe { cmd.as_ref()? };
+            if c.name.len == 0 {
+                return None;
+            }
+            cmd = unsafe { cmd.add(1) };
+            Some(c)
+        })
+    }
+}
+
+impl fmt::Display for StartupBanner<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(version) = self.version {
+            write!(f, " {version}")?;
+        }
+
+        write!(f, ", ngx {}", env!("CARGO_PKG_VERSION"))?;
+        write_list(
+            f,
+            (" [", "]"),
+            CRATE_FEATURES.iter().filter(|x| x.1).map(|x| x.0),
+        )?;
+
+        let version = CStr::from_bytes_with_nul(NGINX_VERSION)
+            .ok()
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or("unknown");
+        write!(f, ", nginx {version}")?;
+
+        if let Some(os) = option_env!("DEP_NGINX_OS").filter(|x| !x.is_empty()) {
+            write!(f, " ({os})")?;
+        }
+
+        if let Some(features) = option_env!("DEP_NGINX_FEATURES") {
+            write_list(
+                f,
+                (" [", "]"),
+                features.split(',').map(str::trim).filter(|x| !x.is_empty()),
+            )?;
+        }
+
+        write_list(f, (", directives: ", ""), self.commands().map(|x| x.name))?;
+        write_list(f, (", capabilities: ", ""), self.capabilities.iter())
+    }
+}
+
+/// Writes a comma-separated list between `delim`, or nothing if the list is empty.
+fn write_list<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    delim: (&str, &str),
+    items: impl Iterator<Item = T>,
+) -> fmt::Result {
+    let mut empty = true;
+
+    for item in items {
+        let sep = if empty { delim.0 } else { "," };
+        write!(f, "{sep}{item}")?;
+        empty = false;
+    }
+
+    if !empty {
+        f.write_str(delim.1)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,427 @@
+//! Metrics in the Prometheus text format.
+//!
+//! The metrics are declared statically with a [MetricSet] and stored in a shared memory zone as
+//! [Metrics], with a separate slot for each worker process. The values are summed over the
+//! workers when rendered.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use nginx_sys::{ngx_conf_t, ngx_module_t};
+//! # use ngx::http::{HTTPStatus, Request};
+//! # use ngx::metrics::{Metric, MetricSet, Metrics};
+//! # use ngx::shm::{SharedZone, SharedZoneBuilder};
+//! # use ngx::{http_request_handler, ngx_string};
+//! struct ExampleMetrics;
+//!
+//! const REQUESTS: usize = 0;
+//! const ACTIVE: usize = 1;
+//! const LATENCY: usize = 2;
+//!
+//! impl MetricSet for ExampleMetrics {
+//!     const METRICS: &'static [Metric] = &[
+//!         Metric::counter("example_requests_total", "Processed requests."),
+//!         Metric::gauge("example_active_requests", "Requests in progress."),
+//!         Metric::histogram(
+//!             "example_latency_milliseconds",
+//!             "Request processing time.",
+//!             &[1, 10, 100, 1000],
+//!         ),
+//!     ];
+//! }
+//!
+//! static mut ZONE: Option<SharedZone<Metrics<ExampleMetrics>>> = None;
+//!
+//! # fn configure(cf: &mut ngx_conf_t, module: &'static ngx_module_t) {
+//! // at configuration time
+//! let zone = SharedZoneBuilder::new(ngx_string!("example_metrics"), 64 * 1024, module)
+//!     .build(cf)
+//!     .expect("metrics zone");
+//! unsafe { ZONE = Some(zone) };
+//! # }
+//!
+//! // location /metrics { example_metrics; }
+//! http_request_handler!(metrics_handler, |request: &mut Request| {
+//!     let zone = unsafe { (*core::ptr::addr_of!(ZONE)).as_ref() };
+//!     match zone.and_then(|zone| zone.get()) {
+//!         Some(metrics) => metrics.respond(request),
+//!         None => HTTPStatus::SERVICE_UNAVAILABLE.into(),
+//!     }
+//! });
+//!
+//! # fn update(metrics: &Metrics<ExampleMetrics>) {
+//! // in the request processing
+//! metrics.incr(REQUESTS);
+//! metrics.add(ACTIVE, 1);
+//! metrics.observe(LATENCY, 42);
+//! metrics.add(ACTIVE, -1);
+//! # }
+//! ```
+//!
+//! See <https://prometheus.io/docs/instrumenting/exposition_formats/>.
+
+use core::fmt::{self, Write};
+use core::marker::PhantomData;
+
+use crate::allocator::AllocError;
+use crate::core::{ChainBuilder, SlabPool, Status};
+use crate::http::{HTTPStatus, Request};
+use crate::shm::SharedZoneInit;
+use crate::sync::CounterVec;
+
+/// The `Content-Type` of the Prometheus text format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The type of a [Metric].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricKind {
+    /// A monotonically increasing value.
+    Counter,
+    /// A value that can go up and down.
+    Gauge,
+    /// A distribution of the observed values over the buckets with the specified upper bounds.
+    ///
+    /// The bounds must be sorted in increasing order. The `+Inf` bucket is added implicitly.
+    Histogram(&'static [u64]),
+}
+
+/// A static description of a metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: MetricKind,
+}
+
+impl Metric {
+    /// Declares a counter.
+    ///
+    /// `name` must be a valid Prometheus metric name, and should end with `_total`.
+    pub const fn counter(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Counter,
+        }
+    }
+
+    /// Declares a gauge.
+    ///
+    /// `name` must be a valid Prometheus metric name.
+    pub const fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind: MetricKind::Gauge,
+        }
+    }
+
+    /// Declares a histogram with the bucket upper bounds sorted in increasing order.
+    ///
+    /// `name` must be a valid Prometheus metric name.
+    pub const fn histogram(
+        name: &'static str,
+        help: &'static str,
+        buckets: &'static [u64],
+    ) -> Self {
+        let mut i = 1;
+        while i < buckets.len() {
+            assert!(
+                buckets[i - 1] < buckets[i],
+                "histogram buckets must be sorted"
+            );
+            i += 1;
+        }
+
+        Self {
+            name,
+            help,
+            kind: MetricKind::Histogram(buckets),
+        }
+    }
+
+    /// Returns the name of the metric.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the help text of the metric.
+    pub fn help(&self) -> &'static str {
+        self.help
+    }
+
+    /// Returns the type of the metric.
+    pub fn kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    /// Returns the number of counter slots used by the metric.
+    const fn slots(&self) -> usize {
+        match self.kind {
+            MetricKind::Counter | MetricKind::Gauge => 1,
+            // the buckets, the `+Inf` bucket and the sum
+            MetricKind::Histogram(buckets) => buckets.len() + 2,
+        }
+    }
+}
+
+/// A set of metrics stored in a [Metrics] zone.
+///
+/// The metrics are addressed by the index in [MetricSet::METRICS].
+pub trait MetricSet: 'static {
+    /// The metrics of the set.
+    const METRICS: &'static [Metric];
+}
+
+/// Shared memory zone value holding the values of a [MetricSet].
+///
+/// Declare the zone with [SharedZoneBuilder](crate::shm::SharedZoneBuilder). The values are
+/// stored in a [CounterVec] with the slots for each CPU, as the number of worker processes is not
+/// known when the zone is created. The workers beyond the number of CPUs share the slots.
+///
+/// The values are preserved across configuration reloads, if the zone is reused by NGINX. If the
+/// reused zone was created for a different set of metrics, e.g. after a module upgrade, the
+/// updates are ignored and nothing is rendered.
+pub struct Metrics<S> {
+    counters: CounterVec,
+    _set: PhantomData<fn() -> S>,
+}
+
+unsafe impl<S: MetricSet> SharedZoneInit for Metrics<S> {
+    fn init(alloc: &SlabPool) -> Result<Self, AllocError> {
+        let len = Self::slots();
+        // SAFETY: the number of CPUs is detected before any configuration is parsed.
+        let workers = unsafe { nginx_sys::ngx_ncpu } as usize;
+
+        Ok(Self {
+            counters: CounterVec::try_new_in(len, workers, alloc)?,
+            _set: PhantomData,
+        })
+    }
+}
+
+impl<S> fmt::Debug for Metrics<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("counters", &self.counters)
+            .finish()
+    }
+}
+
+impl<S: MetricSet> Metrics<S> {
+    /// Increments the counter or gauge at `index` by one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the metric is a histogram.
+    #[inline]
+    pub fn incr(&self, index: usize) {
+        self.add(index, 1)
+    }
+
+    /// Adds `n` to the counter or gauge at `index`.
+    ///
+    /// A negative `n` is only meaningful for gauges.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the metric is a histogram.
+    pub fn add(&self, index: usize, n: i64) {
+        let Some((metric, slot)) = self.metric(index) else {
+            return;
+        };
+        assert!(
+            !matches!(metric.kind, MetricKind::Histogram(_)),
+            "cannot add to a histogram"
+        );
+        self.counters.add(slot, n as u64);
+    }
+
+    /// Records `value` in the histogram at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the metric is not a histogram.
+    pub fn observe(&self, index: usize, value: u64) {
+        let Some((metric, slot)) = self.metric(index) else {
+            return;
+        };
+        let MetricKind::Histogram(buckets) = metric.kind else {
+            panic!("cannot observe a value of a {:?}", metric.kind);
+        };
+
+        let bucket = buckets.partition_point(|&le| le < value);
+        self.counters.incr(slot + bucket);
+        self.counters.add(slot + buckets.len() + 1, value);
+    }
+
+    /// Returns the current value of the counter or gauge at `index`, summed over the workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds or the metric is a histogram.
+    pub fn get(&self, index: usize) -> i64 {
+        let Some((metric, slot)) = self.metric(index) else {
+            return 0;
+        };
+        assert!(
+            !matches!(metric.kind, MetricKind::Histogram(_)),
+            "cannot get a histogram value"
+        );
+        self.counters.sum(slot) as i64
+    }
+
+    /// Writes the metrics in the Prometheus text format.
+    pub fn render<W: Write>(&self, out: &mut W) -> fmt::Result {
+        if self.counters.len() != Self::slots() {
+            return Ok(());
+        }
+
+        let mut slot = 0;
+
+        for metric in S::METRICS {
+            let name = metric.name;
+            writeln!(out, "# HELP {name} {}", Escaped(metric.help))?;
+
+            match metric.kind {
+                MetricKind::Counter => {
+                    writeln!(out, "# TYPE {name} counter")?;
+                    writeln!(out, "{name} {}", self.counters.sum(slot))?;
+                }
+                MetricKind::Gauge => {
+                    writeln!(out, "# TYPE {name} gauge")?;
+                    writeln!(out, "{name} {}", self.counters.sum(slot) as i64)?;
+                }
+                MetricKind::Histogram(buckets) => {
+                    writeln!(out, "# TYPE {name} histogram")?;
+
+                    // the buckets are cumulative in the output
+                    let mut count = 0u64;
+                    for (i, le) in buckets.iter().enumerate() {
+                        count = count.wrapping_add(self.counters.sum(slot + i));
+                        writeln!(out, "{name}_bucket{{le=\"{le}\"}} {count}")?;
+                    }
+                    count = count.wrapping_add(self.counters.sum(slot + buckets.len()));
+                    writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}")?;
+
+                    let sum = self.counters.sum(slot + buckets.len() + 1);
+                    writeln!(out, "{name}_sum {sum}")?;
+                    writeln!(out, "{name}_count {count}")?;
+                }
+            }
+
+            slot += metric.slots();
+        }
+
+        Ok(())
+    }
+
+    /// Sends the metrics as the response to the request.
+    ///
+    /// Returns the status to return from a content handler.
+    pub fn respond(&self, request: &mut Request) -> Status {
+        if request.discard_body().is_err() {
+            return Status::NGX_ERROR;
+        }
+
+        let mut chain = ChainBuilder::new(request.pool());
+        if self.render(&mut chain).is_err() {
+            return Status::NGX_ERROR;
+        }
+
+        request.set_content_length_n(chain.size());
+
+        let rc = request.send_header_with(HTTPStatus::OK, [("Content-Type", CONTENT_TYPE)]);
+        if rc == Status::NGX_ERROR || rc.0 > Status::NGX_OK.0 || request.header_only() {
+            return rc;
+        }
+
+        request.send_chain(chain)
+    }
+
+    /// Returns the metric at `index` and its first slot.
+    ///
+    /// Returns `None` if the zone was created for a different set of metrics.
+    fn metric(&self, index: usize) -> Option<(&'static Metric, usize)> {
+        let metric = S::METRICS.get(index).expect("metric index out of bounds");
+        if self.counters.len() != Self::slots() {
+            return None;
+        }
+        let slot = S::METRICS[..index].iter().map(Metric::slots).sum();
+        Some((metric, slot))
+    }
+
+    /// Returns the number of counter slots used by the set.
+    fn slots() -> usize {
+        S::METRICS.iter().map(Metric::slots).sum()
+    }
+}
+
+/// Escapes the help text as required by the text format.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::string::String;
+
+    use super::*;
+    use crate::allocator::Global;
+
+    struct TestMetrics;
+
+    impl MetricSet for TestMetrics {
+        const METRICS: &'static [Metric] = &[
+            Metric::counter("test_requests_total", "Requests.\nAll of them."),
+            Metric::histogram("test_latency", "Latency.", &[10, 100]),
+            Metric::gauge("test_active", "Active."),
+        ];
+    }
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::<TestMetrics> {
+            counters: CounterVec::try_new_in(6, 1, &Global).unwrap(),
+            _set: PhantomData,
+        };
+
+        metrics.counters.add(0, 3);
+        // observe 5, 50 and 500
+        metrics.counters.add(1, 1);
+        metrics.counters.add(2, 1);
+        metrics.counters.add(3, 1);
+        metrics.counters.add(4, 555);
+        metrics.counters.add(5, 2u64.wrapping_neg());
+
+        let mut out = String::new();
+        metrics.render(&mut out).unwrap();
+
+        assert_eq!(
+            out,
+            "# HELP test_requests_total Requests.\\nAll of them.\n\
+             # TYPE test_requests_total counter\n\
+             test_requests_total 3\n\
+             # HELP test_latency Latency.\n\
+             # TYPE test_latency histogram\n\
+             test_latency_bucket{le=\"10\"} 1\n\
+             test_latency_bucket{le=\"100\"} 2\n\
+             test_latency_bucket{le=\"+Inf\"} 3\n\
+             test_latency_sum 555\n\
+             test_latency_count 3\n\
+             # HELP test_active Active.\n\
+             # TYPE test_active gauge\n\
+             test_active -2\n"
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Sockets on the NGINX event loop.
+use core::error;
+use core::fmt;
+use core::future;
+use core::mem;
+use core::net::SocketAddr;
+use core::ptr::{self, NonNull};
+use core::task::{self, Poll, Waker};
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use std::boxed::Box;
+
+use nginx_sys::{
+    bind, close, fcntl, getsockname, ngx_close_connection, ngx_connection_t, ngx_err_t,
+    ngx_event_t, ngx_get_connection, ngx_handle_read_event, ngx_handle_write_event, ngx_log_t,
+    ngx_socket_errno, recvfrom, sendto, sockaddr, socket, socklen_t, AF_INET, AF_INET6, EAGAIN,
+    EINTR, F_GETFL, F_SETFL, NGX_LOG_ALERT, O_NONBLOCK, SOCK_DGRAM,
+};
+
+use crate::async_::SockAddr;
+use crate::core::Status;
+use crate::log::log_error;
+use crate::ngx_log_debug;
+
+/// An error returned by the [UdpSocket].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UdpSocketError {
+    /// No free connections are available. The reason has already been logged.
+    NoConnection,
+    /// An I/O error with the system error code.
+    Io(ngx_err_t),
+}
+
+impl error::Error for UdpSocketError {}
+
+impl fmt::Display for UdpSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UdpSocketError::NoConnection => f.write_str("no free connections"),
+            UdpSocketError::Io(err) => write!(f, "udp socket i/o error ({err})"),
+        }
+    }
+}
+
+/// A UDP socket driven by the NGINX event loop.
+///
+/// The socket is registered as an [ngx_connection_t] and uses its read and write events to wake
+/// the current task, so the datagram protocols can be implemented without a separate async
+/// runtime. The socket takes a connection from the `worker_connections` pool.
+///
+/// The socket is closed when dropped.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::net::{UdpSocket, UdpSocketError};
+/// # async fn example() -> Result<(), UdpSocketError> {
+/// let log = ngx::log::ngx_cycle_log();
+/// let mut socket = UdpSocket::bind("0.0.0.0:0".parse().unwrap(), log)?;
+///
+/// socket.send_to(b"ping", "127.0.0.1:5353".parse().unwrap()).await?;
+///
+/// let mut buf = [0u8; 512];
+/// let (n, peer) = socket.recv_from(&mut buf).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct UdpSocket {
+    state: Box<UdpState>,
+}
+
+struct UdpState {
+    connection: *mut ngx_connection_t,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("local_addr", &self.local_addr().ok())
+            .finish()
+    }
+}
+
+impl UdpSocket {
+    /// Creates a socket bound to the specified address.
+    ///
+    /// Use port 0 to bind to an ephemeral port.
+    pub fn bind(addr: SocketAddr, log: NonNull<ngx_log_t>) -> Result<Self, UdpSocketError> {
+        let log = log.as_ptr();
+        // SAFETY: plain C structure, all zeroes is a valid value.
+        let mut sa: SockAddr = unsafe { mem::zeroed() };
+        let socklen = sa.set(&addr);
+
+        let family = match addr {
+            SocketAddr::V4(_) => AF_INET,
+            SocketAddr::V6(_) => AF_INET6,
+        };
+
+        unsafe {
+            let fd = socket(family as _, SOCK_DGRAM as _, 0);
+            if fd == -1 {
+                let err = ngx_socket_errno();
+                log_error(NGX_LOG_ALERT as _, log, err, b"udp: socket() failed");
+                return Err(UdpSocketError::Io(err));
+            }
+
+            let flags = fcntl(fd, F_GETFL as _);
+            if flags == -1 || fcntl(fd, F_SETFL as _, flags | O_NONBLOCK as i32) == -1 {
+                let err = ngx_socket_errno();
+                log_error(NGX_LOG_ALERT as _, log, err, b"udp: fcntl() failed");
+                close(fd);
+                return Err(UdpSocketError::Io(err));
+            }
+
+            if bind(fd, ptr::addr_of!(sa).cast::<sockaddr>(), socklen) == -1 {
+                let err = ngx_socket_errno();
+                close(fd);
+                return Err(UdpSocketError::Io(err));
+            }
+
+            let c = ngx_get_connection(fd, log);
+            if c.is_null() {
+                close(fd);
+                return Err(UdpSocketError::NoConnection);
+            }
+
+            let mut state = Box::new(UdpState {
+                connection: c,
+                read_waker: None,
+                write_waker: None,
+            });
+
+            (*c).data = ptr::from_mut(state.as_mut()).cast();
+            (*c).type_ = SOCK_DGRAM as _;
+            (*(*c).read).handler = Some(udp_read_handler);
+            (*(*c).read).log = log;
+            (*(*c).write).handler = Some(udp_write_handler);
+            (*(*c).write).log = log;
+            // the socket is writable until sendto() reports otherwise
+            (*(*c).write).set_ready(1);
+
+            ngx_log_debug!(log, "udp: bind {addr}, fd:{fd}");
+
+            Ok(Self { state })
+        }
+    }
+
+    /// Returns a raw pointer to the underlying connection.
+    pub fn as_ptr(&self) -> *mut ngx_connection_t {
+        self.state.connection
+    }
+
+    /// Returns the local address of the socket.
+    pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
+        // SAFETY: plain C structure, all zeroes is a valid value.
+        let mut sa: SockAddr = unsafe { mem::zeroed() };
+        let mut socklen = mem::size_of::<SockAddr>() as socklen_t;
+
+        let fd = unsafe { (*self.state.connection).fd };
+        if unsafe { getsockname(fd, ptr::addr_of_mut!(sa).cast(), &mut socklen) } == -1 {
+            return Err(UdpSocketError::Io(ngx_socket_errno()));
+        }
+
+        sa.get().ok_or(UdpSocketError::Io(0))
+    }
+
+    /// Attempts to receive a datagram into `buf`.
+    ///
+    /// Returns the number of bytes received and the address of the sender. The rest of a
+    /// datagram larger than the buffer is discarded.
+    pub fn poll_recv_from(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), UdpSocketError>> {
+        let state = self.state.as_mut();
+        let c = state.connection;
+
+        // SAFETY: the connection is valid until the state is dropped.
+        unsafe {
+            let rev = (*c).read;
+
+            loop {
+                let mut sa: SockAddr = mem::zeroed();
+                let mut socklen = mem::size_of::<SockAddr>() as socklen_t;
+
+                let n = recvfrom(
+                    (*c).fd,
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    0,
+                    ptr::addr_of_mut!(sa).cast(),
+                    &mut socklen,
+                );
+
+                if n >= 0 {
+                    let peer = sa.get().ok_or(UdpSocketError::Io(0))?;
+                    return Poll::Ready(Ok((n as usize, peer)));
+                }
+
+                match ngx_socket_errno() {
+                    err if err == EINTR as ngx_err_t => continue,
+                    err if err == EAGAIN as ngx_err_t => break,
+                    err => return Poll::Ready(Err(UdpSocketError::Io(err))),
+                }
+            }
+
+            (*rev).set_ready(0);
+
+            if ngx_handle_read_event(rev, 0) != Status::NGX_OK.into() {
+                return Poll::Ready(Err(UdpSocketError::Io(0)));
+            }
+        }
+
+        update_waker(&mut state.read_waker, cx.waker());
+        Poll::Pending
+    }
+
+    /// Attempts to send a datagram from `buf` to the specified address.
+    ///
+    /// Returns the number of bytes sent.
+    pub fn poll_send_to(
+        &mut self,
+        cx: &mut task::Context<'_>,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Poll<Result<usize, UdpSocketError>> {
+        let state = self.state.as_mut();
+        let c = state.connection;
+
+        // SAFETY: plain C structure, all zeroes is a valid value.
+        let mut sa: SockAddr = unsafe { mem::zeroed() };
+        let socklen = sa.set(&target);
+
+        // SAFETY: the connection is valid until the state is dropped.
+        unsafe {
+            let wev = (*c).write;
+
+            loop {
+                let n = sendto(
+                    (*c).fd,
+                    buf.as_ptr().cast(),
+                    buf.len(),
+                    0,
+                    ptr::addr_of!(sa).cast(),
+                    socklen,
+                );
+
+                if n >= 0 {
+                    return Poll::Ready(Ok(n as usize));
+                }
+
+                match ngx_socket_errno() {
+                    err if err == EINTR as ngx_err_t => continue,
+                    err if err == EAGAIN as ngx_err_t => break,
+                    err => return Poll::Ready(Err(UdpSocketError::Io(err))),
+                }
+            }
+
+            (*wev).set_ready(0);
+
+            if ngx_handle_write_event(wev, 0) != Status::NGX_OK.into() {
+                return Poll::Ready(Err(UdpSocketError::Io(0)));
+            }
+        }
+
+        update_waker(&mut state.write_waker, cx.waker());
+        Poll::Pending
+    }
+
+    /// Receives a datagram into `buf`.
+    ///
+    /// Returns the number of bytes received and the address of the sender.
+    pub async fn recv_from(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(usize, SocketAddr), UdpSocketError> {
+        future::poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    /// Sends a datagram from `buf` to the specified address.
+    ///
+    /// Returns the number of bytes sent.
+    pub async fn send_to(
+        &mut self,
+        buf: &[u8],
+        target: SocketAddr,
+    ) -> Result<usize, UdpSocketError> {
+        future::poll_fn(|cx| self.poll_send_to(cx, buf, target)).await
+    }
+}
+
+impl Drop for UdpState {
+    fn drop(&mut self) {
+        let c = self.connection;
+        unsafe {
+            ngx_log_debug!((*c).log, "udp: close fd:{}", (*c).fd);
+            ngx_close_connection(c);
+        }
+    }
+}
+
+fn update_waker(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(x) => x.clone_from(waker),
+        None => *slot = Some(waker.clone()),
+    }
+}
+
+unsafe extern "C" fn udp_read_handler(ev: *mut ngx_event_t) {
+    let c: *mut ngx_connection_t = (*ev).data.cast();
+    let state = &mut *(*c).data.cast::<UdpState>();
+
+    if let Some(waker) = state.read_waker.take() {
+        waker.wake();
+    }
+}
+
+unsafe extern "C" fn udp_write_handler(ev: *mut ngx_event_t) {
+    let c: *mut ngx_connection_t = (*ev).data.cast();
+    let state = &mut *(*c).data.cast::<UdpState>();
+
+    if let Some(waker) = state.write_waker.take() {
+        waker.wake();
+    }
+}
@@ -0,0 +1,259 @@
+//! Global state bound to a configuration cycle.
+//!
+//! A module often keeps per-process state in a `static`: a client for an external service, a
+//! cache of the parsed configuration, a pointer to the cycle log. Such state is usually derived
+//! from the cycle and must not outlive it. A worker process uses a single cycle for its lifetime,
+//! but the single process and the master process replace the cycle on each configuration reload
+//! and free the previous one, and a value created for the old cycle becomes stale.
+//!
+//! [CycleLocal] records the generation of the cycle its value was created for, and does not return
+//! the value once the current cycle, `ngx_cycle`, has a different generation. A generation is
+//! assigned to a cycle on first use and is never reused, even if a new cycle is allocated at the
+//! address of a freed one.
+use core::cell::{Cell, RefCell};
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr;
+
+use crate::core::{assert_main_thread, is_main_thread, CycleRef};
+use crate::ffi::{ngx_cycle_t, ngx_pool_cleanup_add, ngx_pool_cleanup_t};
+
+/// A container for global state, (re)initialized for each configuration cycle.
+///
+/// The value is normally set in [ProcessHooks::init_process] and dropped in
+/// [ProcessHooks::exit_process]. [CycleLocal::get_or_init] additionally creates the value on first
+/// use and recreates it after a configuration reload in the single process mode, where
+/// `init_process` is not called again.
+///
+/// The container is not thread-safe and must only be used from the main thread of a process. The
+/// methods panic when called from another thread, see [crate::core::is_main_thread]. Nested
+/// access is checked at runtime as well: an attempt to replace or drop the value from the closure
+/// passed to [CycleLocal::with] panics.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::{CycleRef, ProcessHooks, Status};
+/// # use ngx::ffi::ngx_cycle_t;
+/// # use ngx::once::CycleLocal;
+/// struct Client {
+///     endpoint: String,
+/// }
+///
+/// static CLIENT: CycleLocal<Client> = CycleLocal::new();
+///
+/// struct Module;
+///
+/// impl ProcessHooks for Module {
+///     fn init_process(cycle: &mut ngx_cycle_t) -> Status {
+///         CLIENT.set(cycle, Client { endpoint: "127.0.0.1:8080".into() });
+///         Status::NGX_OK
+///     }
+///
+///     fn exit_process(_cycle: &mut ngx_cycle_t) {
+///         CLIENT.clear();
+///     }
+/// }
+///
+/// fn endpoint_len() -> Option<usize> {
+///     CLIENT.with(|client| client.endpoint.len())
+/// }
+/// ```
+///
+/// [ProcessHooks::init_process]: crate::core::ProcessHooks::init_process
+/// [ProcessHooks::exit_process]: crate::core::ProcessHooks::exit_process
+pub struct CycleLocal<T> {
+    slot: RefCell<Option<Slot<T>>>,
+}
+
+struct Slot<T> {
+    generation: usize,
+    value: T,
+}
+
+// SAFETY: the value can be dropped on another thread only if it is sent there with the container.
+unsafe impl<T: Send> Send for CycleLocal<T> {}
+// SAFETY: the value is only accessed from the main thread of the process, which is checked by each
+// method, so a shared reference does not give access to the value on any other thread.
+unsafe impl<T> Sync for CycleLocal<T> {}
+
+impl<T> CycleLocal<T> {
+    /// Creates an empty container.
+    pub const fn new() -> Self {
+        Self {
+            slot: RefCell::new(None),
+        }
+    }
+
+    /// Sets the value for `cycle`, dropping the previous value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed, or if the cycle generation cannot be allocated.
+    pub fn set(&self, cycle: &ngx_cycle_t, value: T) {
+        assert_main_thread("CycleLocal");
+        let generation = cycle_generation(cycle).expect("cycle generation");
+        let old = self.slot.borrow_mut().replace(Slot { generation, value });
+        // drop the previous value after releasing the borrow
+        drop(old);
+    }
+
+    /// Takes the value out of the container, leaving it empty.
+    ///
+    /// Returns `None` if the value is not set. A stale value is returned as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    pub fn take(&self) -> Option<T> {
+        assert_main_thread("CycleLocal");
+        self.slot.borrow_mut().take().map(|x| x.value)
+    }
+
+    /// Drops the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    pub fn clear(&self) {
+        drop(self.take());
+    }
+
+    /// Returns `true` if the value is set for the current cycle.
+    pub fn is_set(&self) -> bool {
+        assert_main_thread("CycleLocal");
+        let current = current_generation();
+        self.slot
+            .borrow()
+            .as_ref()
+            .is_some_and(|x| Some(x.generation) == current)
+    }
+
+    /// Calls `f` with the value for the current cycle.
+    ///
+    /// Returns `None` if the value is not set, or was set for a different cycle.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        assert_main_thread("CycleLocal");
+        let current = current_generation();
+        let slot = self.slot.borrow();
+        match slot.as_ref() {
+            Some(x) if Some(x.generation) == current => Some(f(&x.value)),
+            _ => None,
+        }
+    }
+
+    /// Calls `f` with the value for the current cycle, creating it with `init` if needed.
+    ///
+    /// A stale value, created for a different cycle, is dropped before calling `init`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no current cycle, or if the value needs to be replaced while it is
+    /// borrowed.
+    pub fn get_or_init<R>(
+        &self,
+        init: impl FnOnce(CycleRef<'static>) -> T,
+        f: impl FnOnce(&T) -> R,
+    ) -> R {
+        let cycle = CycleRef::current().expect("current cycle");
+
+        if !self.is_set() {
+            // drop the stale value before creating the new one
+            self.clear();
+            let value = init(cycle);
+            // SAFETY: the current cycle is valid
+            self.set(unsafe { &*cycle.as_ptr() }, value);
+        }
+
+        let slot = self.slot.borrow();
+        f(&slot.as_ref().expect("initialized value").value)
+    }
+}
+
+impl<T> Default for CycleLocal<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for CycleLocal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("CycleLocal");
+        if !is_main_thread() {
+            return s.finish_non_exhaustive();
+        }
+        match self.slot.try_borrow() {
+            Ok(slot) => s.field("generation", &slot.as_ref().map(|x| x.generation)),
+            Err(_) => s.field("generation", &format_args!("<borrowed>")),
+        };
+        s.finish_non_exhaustive()
+    }
+}
+
+/// The generations assigned to the configuration cycles.
+struct Generations {
+    /// The last generation assigned.
+    last: Cell<usize>,
+    /// The cycle and the generation of the last lookup.
+    cached: Cell<(*const ngx_cycle_t, usize)>,
+}
+
+// SAFETY: only accessed from the main thread; each caller of the CycleLocal methods checks that,
+// and the pool cleanup handlers run on the main thread.
+unsafe impl Sync for Generations {}
+
+static GENERATIONS: Generations = Generations {
+    last: Cell::new(0),
+    cached: Cell::new((ptr::null(), 0)),
+};
+
+/// Returns the generation of the current cycle.
+fn current_generation() -> Option<usize> {
+    // SAFETY: the global cycle pointer is only read, and is either null or points to a valid cycle.
+    let cycle = unsafe { nginx_sys::ngx_cycle.as_ref() }?;
+    cycle_generation(cycle)
+}
+
+/// Returns the generation of `cycle`, assigning a new one on first use.
+///
+/// The generation is stored in a cleanup handler of the cycle pool, which also invalidates the
+/// cached lookup once the cycle is freed.
+fn cycle_generation(cycle: &ngx_cycle_t) -> Option<usize> {
+    let (cached, generation) = GENERATIONS.cached.get();
+    if ptr::eq(cached, cycle) {
+        return Some(generation);
+    }
+
+    // SAFETY: the cleanup handlers of a valid cycle pool form a valid list.
+    let mut cln = unsafe { (*cycle.pool).cleanup };
+    let generation = loop {
+        let Some(c) = (unsafe { cln.as_ref() }) else {
+            break new_generation(cycle)?;
+        };
+        if c.handler.map(|h| h as usize) == Some(cycle_cleanup as usize) {
+            break c.data as usize;
+        }
+        cln = c.next;
+    };
+
+    GENERATIONS.cached.set((cycle, generation));
+    Some(generation)
+}
+
+fn new_generation(cycle: &ngx_cycle_t) -> Option<usize> {
+    // SAFETY: the cycle pool is valid until the cycle is freed.
+    let cln: *mut ngx_pool_cleanup_t = unsafe { ngx_pool_cleanup_add(cycle.pool, 0) };
+    let cln = unsafe { cln.as_mut() }?;
+
+    let generation = GENERATIONS.last.get() + 1;
+    GENERATIONS.last.set(generation);
+
+    cln.handler = Some(cycle_cleanup);
+    cln.data = generation as *mut c_void;
+    Some(generation)
+}
+
+unsafe extern "C" fn cycle_cleanup(data: *mut c_void) {
+    if GENERATIONS.cached.get().1 == data as usize {
+        GENERATIONS.cached.set((ptr::null(), 0));
+    }
+}
@@ -0,0 +1,157 @@
+//! Panic handling for the callbacks invoked by NGINX.
+//!
+//! Unwinding out of an `extern "C"` function aborts the process, so the panics in module code
+//! should be contained before they reach the FFI boundary. The behavior depends on the panic
+//! strategy of the final build:
+//!
+//! * with `panic = "unwind"` and the `std` feature, [catch] stops the unwinding, logs the panic
+//!   at the `alert` level and returns [Panicked];
+//! * with `panic = "abort"` or without `std`, unwinding is not available. [catch] only records
+//!   the module and handler names, so that [log_panic] can write a meaningful error log line
+//!   before the process is terminated.
+//!
+//! The callbacks defined with the handler macros, such as [http_request_handler], and the
+//! [ProcessHooks] are wrapped in [catch] and return `NGX_ERROR` on a panic. Other `extern "C"`
+//! functions in a module should use [catch] directly.
+//!
+//! Use [UNWIND] to check the mode at compile time. In the abort mode, the pre-abort message is
+//! written by the hook installed with [set_hook] or by a custom `#[panic_handler]` calling
+//! [log_panic].
+//!
+//! [http_request_handler]: crate::http_request_handler
+//! [ProcessHooks]: crate::core::ProcessHooks
+use core::cell::Cell;
+use core::error;
+use core::fmt;
+
+use crate::ffi::NGX_LOG_ALERT;
+use crate::ngx_log_error;
+
+/// `true` if panics in [catch] are recovered, `false` if they abort the process.
+pub const UNWIND: bool = cfg!(all(feature = "std", panic = "unwind"));
+
+/// An error returned by [catch] when the closure panicked.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Panicked;
+
+impl error::Error for Panicked {}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("handler panicked")
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Context {
+    module: &'static str,
+    handler: &'static str,
+}
+
+/// The handler currently executed in [catch].
+struct CurrentContext(Cell<Option<Context>>);
+
+// SAFETY: the handlers are invoked from the main thread of a worker process.
+unsafe impl Sync for CurrentContext {}
+
+static CURRENT: CurrentContext = CurrentContext(Cell::new(None));
+
+/// Restores the outer context when the handler returns or unwinds.
+struct ContextGuard(Option<Context>);
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        CURRENT.0.set(self.0);
+    }
+}
+
+/// Runs `f`, preventing a panic from crossing the FFI boundary.
+///
+/// `module` and `handler` identify the callback in the log messages.
+///
+/// In the unwind mode, a panic is logged and converted into [Panicked]. Otherwise, the panic
+/// aborts the process and the function always returns `Ok`; see the [module documentation](self).
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::{ngx_http_request_t, ngx_int_t};
+/// extern "C" fn access_handler(r: *mut ngx_http_request_t) -> ngx_int_t {
+///     ngx::panic::catch("ngx_http_example_module", "access_handler", || {
+///         // handler code
+///         Status::NGX_DECLINED.into()
+///     })
+///     .unwrap_or(Status::NGX_ERROR.into())
+/// }
+/// ```
+pub fn catch<R>(
+    module: &'static str,
+    handler: &'static str,
+    f: impl FnOnce() -> R,
+) -> Result<R, Panicked> {
+    let _guard = ContextGuard(CURRENT.0.replace(Some(Context { module, handler })));
+
+    #[cfg(all(feature = "std", panic = "unwind"))]
+    {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| {
+                    payload
+                        .downcast_ref::<std::string::String>()
+                        .map(|x| x.as_str())
+                })
+                .unwrap_or("Box<dyn Any>");
+            log_alert(format_args!("{module}: panic in {handler}: {msg}"));
+            Panicked
+        })
+    }
+
+    #[cfg(not(all(feature = "std", panic = "unwind")))]
+    {
+        Ok(f())
+    }
+}
+
+/// Writes the panic message to the cycle log before the process is aborted.
+///
+/// The message includes the module and handler names if the panic happened inside of [catch].
+/// Does nothing in the unwind mode, where [catch] logs the recovered panics.
+///
+/// Accepts both [core::panic::PanicInfo] and `std::panic::PanicHookInfo`. Intended to be called
+/// from a `#[panic_handler]` in `no_std` modules.
+pub fn log_panic(info: impl fmt::Display) {
+    if UNWIND {
+        return;
+    }
+
+    match CURRENT.0.get() {
+        Some(Context { module, handler }) => log_alert(format_args!(
+            "{module}: panic in {handler}, aborting: {info}"
+        )),
+        None => log_alert(format_args!("panic, aborting: {info}")),
+    }
+}
+
+/// Installs a panic hook calling [log_panic] before the previously installed hook.
+#[cfg(feature = "std")]
+pub fn set_hook() {
+    let prev = std::panic::take_hook();
+    std::panic::set_hook(std::boxed::Box::new(move |info| {
+        log_panic(info);
+        prev(info);
+    }));
+}
+
+fn log_alert(args: fmt::Arguments<'_>) {
+    // SAFETY: the global cycle pointer is either null or points to a valid cycle.
+    let cycle = unsafe { nginx_sys::ngx_cycle };
+    let Some(cycle) = (unsafe { cycle.as_ref() }) else {
+        return;
+    };
+    if cycle.log.is_null() {
+        return;
+    }
+    ngx_log_error!(NGX_LOG_ALERT, cycle.log, "{}", args);
+}
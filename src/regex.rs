@@ -0,0 +1,403 @@
+//! Regular expressions backed by the PCRE library NGINX is built with.
+//!
+//! The patterns are compiled with [`ngx_regex_compile`] and use the same syntax and options as the
+//! regular expressions in the NGINX configuration, including PCRE JIT if enabled.
+//!
+//! Example:
+//! ```rust,no_run
+//! # use ngx::core::Pool;
+//! # use ngx::regex::{Regex, RegexError};
+//! fn version(pool: &Pool, user_agent: &[u8]) -> Result<Option<usize>, RegexError> {
+//!     let re = Regex::new(pool, r"^curl/(?<major>\d+)\.")?;
+//!
+//!     let Some(captures) = re.captures(pool, user_agent)? else {
+//!         return Ok(None);
+//!     };
+//!
+//!     Ok(captures
+//!         .name("major")
+//!         .and_then(|x| core::str::from_utf8(x).ok()?.parse().ok()))
+//! }
+//! ```
+//!
+//! [`ngx_regex_compile`]: https://nginx.org/en/docs/dev/development_guide.html#regex
+use core::alloc::Layout;
+use core::error;
+use core::ffi::c_int;
+use core::fmt;
+use core::ops::Range;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use nginx_sys::{
+    ngx_int_t, ngx_regex_compile, ngx_regex_compile_t, ngx_regex_t, ngx_str_t, NGX_MAX_CONF_ERRSTR,
+    NGX_OK, NGX_REGEX_CASELESS,
+};
+
+use crate::allocator::{AllocError, Allocator};
+use crate::core::Pool;
+
+/// `PCRE_ERROR_NOMATCH` and `PCRE2_ERROR_NOMATCH`.
+const NO_MATCH: ngx_int_t = -1;
+
+/// Maximum length of a stored compilation error message.
+const COMPILE_ERROR_LEN: usize = 96;
+
+/// An error returned by the regular expression operations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegexError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The pattern could not be compiled.
+    Compile(CompileError),
+    /// The matching failed with the specified PCRE error code, e.g. because of the match limit.
+    Exec(ngx_int_t),
+}
+
+impl error::Error for RegexError {}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegexError::Alloc => f.write_str("regex allocation failed"),
+            RegexError::Compile(err) => fmt::Display::fmt(err, f),
+            RegexError::Exec(rc) => write!(f, "regex execution failed: {rc}"),
+        }
+    }
+}
+
+impl From<AllocError> for RegexError {
+    fn from(_: AllocError) -> Self {
+        RegexError::Alloc
+    }
+}
+
+/// The message of a pattern compilation error, as reported by `ngx_regex_compile`.
+///
+/// Long messages are truncated.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CompileError {
+    message: [u8; COMPILE_ERROR_LEN],
+    len: u8,
+}
+
+impl CompileError {
+    fn new(message: &[u8]) -> Self {
+        let len = message.len().min(COMPILE_ERROR_LEN);
+        let mut err = Self {
+            message: [0; COMPILE_ERROR_LEN],
+            len: len as u8,
+        };
+        err.message[..len].copy_from_slice(&message[..len]);
+        err
+    }
+
+    /// Returns the error message.
+    pub fn message(&self) -> &[u8] {
+        &self.message[..self.len as usize]
+    }
+}
+
+impl fmt::Debug for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CompileError")
+            .field(&self.message().escape_ascii())
+            .finish()
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.message().escape_ascii(), f)
+    }
+}
+
+/// A compiled regular expression.
+///
+/// The expression is allocated from the pool passed to [Regex::new] and is valid as long as the
+/// pool.
+pub struct Regex {
+    re: NonNull<ngx_regex_t>,
+    captures: usize,
+    names: *const u8,
+    named_captures: usize,
+    name_size: usize,
+}
+
+impl Regex {
+    /// Compiles a regular expression.
+    pub fn new(pool: &Pool, pattern: impl AsRef<[u8]>) -> Result<Self, RegexError> {
+        Self::compile(pool, pattern.as_ref(), 0)
+    }
+
+    /// Compiles a case-insensitive regular expression.
+    pub fn new_caseless(pool: &Pool, pattern: impl AsRef<[u8]>) -> Result<Self, RegexError> {
+        Self::compile(pool, pattern.as_ref(), NGX_REGEX_CASELESS as _)
+    }
+
+    fn compile(pool: &Pool, pattern: &[u8], options: usize) -> Result<Self, RegexError> {
+        // PCRE1 expects a null-terminated pattern
+        let data =
+            pool.allocate(Layout::array::<u8>(pattern.len() + 1).map_err(|_| AllocError)?)?;
+        let data = data.as_ptr().cast::<u8>();
+        unsafe {
+            ptr::copy_nonoverlapping(pattern.as_ptr(), data, pattern.len());
+            *data.add(pattern.len()) = 0;
+        }
+
+        let mut errstr = [0u8; NGX_MAX_CONF_ERRSTR as usize];
+
+        // SAFETY: ngx_regex_compile_t is a plain C structure, all zeroes is a valid value.
+        let mut rc: ngx_regex_compile_t = unsafe { core::mem::zeroed() };
+        rc.pattern = ngx_str_t {
+            len: pattern.len(),
+            data,
+        };
+        rc.pool = pool.as_ptr();
+        rc.options = options as _;
+        rc.err = ngx_str_t {
+            len: errstr.len(),
+            data: errstr.as_mut_ptr(),
+        };
+
+        if unsafe { ngx_regex_compile(&mut rc) } != NGX_OK as ngx_int_t {
+            let message = rc.err.as_bytes();
+            return Err(RegexError::Compile(CompileError::new(message)));
+        }
+
+        Ok(Self {
+            re: NonNull::new(rc.regex).ok_or(RegexError::Alloc)?,
+            captures: rc.captures as usize,
+            names: rc.names,
+            named_captures: rc.named_captures as usize,
+            name_size: rc.name_size as usize,
+        })
+    }
+
+    /// Returns the number of capture groups, not including the whole match.
+    pub fn captures_len(&self) -> usize {
+        self.captures
+    }
+
+    /// Returns an iterator over the names and indices of the named capture groups.
+    pub fn capture_names(&self) -> CaptureNames<'_> {
+        let table: &[u8] = if self.names.is_null() {
+            &[]
+        } else {
+            // SAFETY: the name table is owned by the compiled expression.
+            unsafe { slice::from_raw_parts(self.names, self.named_captures * self.name_size) }
+        };
+
+        CaptureNames {
+            table,
+            name_size: self.name_size,
+        }
+    }
+
+    /// Returns the index of the named capture group.
+    pub fn capture_index(&self, name: impl AsRef<[u8]>) -> Option<usize> {
+        let name = name.as_ref();
+        self.capture_names()
+            .find(|(x, _)| *x == name)
+            .map(|(_, index)| index)
+    }
+
+    /// Checks if the expression matches anywhere in the subject.
+    pub fn is_match(&self, subject: impl AsRef<[u8]>) -> Result<bool, RegexError> {
+        Ok(self.exec(subject.as_ref(), &mut [])?.is_some())
+    }
+
+    /// Matches the expression against the subject and returns the capture groups.
+    ///
+    /// The offsets of the groups are stored in memory allocated from `pool`.
+    pub fn captures<'a>(
+        &'a self,
+        pool: &'a Pool,
+        subject: &'a [u8],
+    ) -> Result<Option<Captures<'a>>, RegexError> {
+        // The last third of the vector is used as a workspace by PCRE1.
+        let len = (self.captures + 1) * 3;
+        let layout = Layout::array::<c_int>(len).map_err(|_| AllocError)?;
+        let ovector = pool.allocate_zeroed(layout)?.as_ptr().cast::<c_int>();
+        // SAFETY: the memory is allocated and initialized for `len` elements.
+        let ovector = unsafe { slice::from_raw_parts_mut(ovector, len) };
+
+        let Some(n) = self.exec(subject, ovector)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Captures {
+            regex: self,
+            subject,
+            ovector: &ovector[..n * 2],
+        }))
+    }
+
+    /// Runs the match and returns the number of the captured pairs in `ovector`.
+    fn exec(&self, subject: &[u8], ovector: &mut [c_int]) -> Result<Option<usize>, RegexError> {
+        #[cfg(ngx_feature = "pcre2")]
+        let rc = {
+            let mut s = ngx_str_t {
+                len: subject.len(),
+                data: subject.as_ptr().cast_mut(),
+            };
+            unsafe {
+                nginx_sys::ngx_regex_exec(
+                    self.re.as_ptr(),
+                    &mut s,
+                    ovector.as_mut_ptr(),
+                    ovector.len() as _,
+                )
+            }
+        };
+
+        // ngx_regex_exec is a macro over pcre_exec with PCRE1
+        #[cfg(not(ngx_feature = "pcre2"))]
+        let rc = unsafe {
+            let re = self.re.as_ptr();
+            nginx_sys::pcre_exec(
+                (*re).code,
+                (*re).extra,
+                subject.as_ptr().cast(),
+                subject.len() as c_int,
+                0,
+                0,
+                ovector.as_mut_ptr(),
+                ovector.len() as c_int,
+            ) as ngx_int_t
+        };
+
+        match rc {
+            NO_MATCH => Ok(None),
+            rc if rc < 0 => Err(RegexError::Exec(rc)),
+            // the vector is too small to store all the captures
+            0 => Ok(Some(ovector.len() / 3)),
+            rc => Ok(Some(rc as usize)),
+        }
+    }
+
+    /// Returns a raw pointer to the compiled expression.
+    pub fn as_ptr(&self) -> *mut ngx_regex_t {
+        self.re.as_ptr()
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Regex")
+            .field("captures", &self.captures)
+            .field("named_captures", &self.named_captures)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Iterator over the named capture groups of a [Regex].
+#[derive(Clone, Debug)]
+pub struct CaptureNames<'a> {
+    table: &'a [u8],
+    name_size: usize,
+}
+
+impl<'a> Iterator for CaptureNames<'a> {
+    type Item = (&'a [u8], usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.table.len() < self.name_size || self.name_size < 3 {
+            return None;
+        }
+
+        let (entry, rest) = self.table.split_at(self.name_size);
+        self.table = rest;
+
+        // 2 bytes of the group index in the network byte order, then a null-terminated name
+        let index = u16::from_be_bytes([entry[0], entry[1]]) as usize;
+        let name = &entry[2..];
+        let len = name.iter().position(|x| *x == 0).unwrap_or(name.len());
+        Some((&name[..len], index))
+    }
+}
+
+/// Capture groups of a successful match.
+#[derive(Clone)]
+pub struct Captures<'a> {
+    regex: &'a Regex,
+    subject: &'a [u8],
+    ovector: &'a [c_int],
+}
+
+impl<'a> Captures<'a> {
+    /// Returns the number of capture groups, including the whole match.
+    pub fn len(&self) -> usize {
+        self.regex.captures + 1
+    }
+
+    /// Always returns `false`, as the whole match is always present.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns the capture group with the index `i`, or `None` if the group did not participate in
+    /// the match.
+    ///
+    /// The group 0 is the whole match.
+    pub fn get(&self, i: usize) -> Option<&'a [u8]> {
+        self.subject.get(self.range(i)?)
+    }
+
+    /// Returns the named capture group.
+    pub fn name(&self, name: impl AsRef<[u8]>) -> Option<&'a [u8]> {
+        self.get(self.regex.capture_index(name)?)
+    }
+
+    /// Returns the byte offsets of the capture group with the index `i`.
+    pub fn range(&self, i: usize) -> Option<Range<usize>> {
+        let start = *self.ovector.get(2 * i)?;
+        let end = *self.ovector.get(2 * i + 1)?;
+        if start < 0 || end < start {
+            return None;
+        }
+
+        Some(start as usize..end as usize)
+    }
+
+    /// Returns an iterator over the capture groups.
+    pub fn iter(&self) -> impl Iterator<Item = Option<&'a [u8]>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+impl fmt::Debug for Captures<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.iter().map(|x| x.map(|x| x.escape_ascii())))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_names() {
+        let table = b"\x00\x02major\x00\x00\x01minor\x00";
+        let names = CaptureNames {
+            table,
+            name_size: 8,
+        };
+        assert!(names.eq([(&b"major"[..], 2), (&b"minor"[..], 1)]));
+
+        let names = CaptureNames {
+            table: &table[..10],
+            name_size: 8,
+        };
+        assert_eq!(names.count(), 1);
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let err = CompileError::new(&[b'x'; 200]);
+        assert_eq!(err.message().len(), COMPILE_ERROR_LEN);
+        assert_eq!(CompileError::new(b"missing )").message(), b"missing )");
+    }
+}
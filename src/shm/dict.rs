@@ -0,0 +1,150 @@
+use core::borrow::Borrow;
+use core::hash::Hash;
+use core::time::Duration;
+
+use nginx_sys::ngx_msec_t;
+
+use crate::allocator::AllocError;
+use crate::collections::RbTreeMap;
+use crate::core::SlabPool;
+use crate::event::{current_msec, expires_at, is_expired};
+use crate::shm::SharedZoneInit;
+use crate::sync::RwLock;
+
+/// A hash map in shared memory with optional expiration time for each entry.
+///
+/// The map is built on top of the [RbTreeMap] protected with a [RwLock], and stores all the data
+/// in the zone [SlabPool]. Keys and values must be allocated with the same pool, e.g. as
+/// `NgxString<SlabPool>`.
+///
+/// Expired entries are not visible to readers, and are removed lazily on access.
+///
+/// Expiration time is based on `ngx_current_msec`, which uses a monotonic clock shared by all the
+/// processes and is updated once per event loop iteration.
+pub struct SharedDict<K, V>
+where
+    K: Hash + Ord,
+{
+    map: RwLock<RbTreeMap<K, DictEntry<V>, SlabPool>>,
+    alloc: SlabPool,
+}
+
+struct DictEntry<V> {
+    value: V,
+    expires: Option<ngx_msec_t>,
+}
+
+impl<V> DictEntry<V> {
+    fn is_expired(&self, now: ngx_msec_t) -> bool {
+        self.expires.is_some_and(|x| is_expired(x, now))
+    }
+}
+
+impl<K, V> SharedDict<K, V>
+where
+    K: Hash + Ord,
+{
+    /// Attempts to create a new dictionary with the specified slab pool.
+    pub fn try_new_in(alloc: SlabPool) -> Result<Self, AllocError> {
+        Ok(Self {
+            map: RwLock::new(RbTreeMap::try_new_in(alloc.clone())?),
+            alloc,
+        })
+    }
+
+    /// Returns a reference to the underlying allocator.
+    pub fn allocator(&self) -> &SlabPool {
+        &self.alloc
+    }
+
+    /// Returns `true` if the dictionary contains no entries, including the expired ones.
+    pub fn is_empty(&self) -> bool {
+        self.map.read().is_empty()
+    }
+
+    /// Calls `f` with a reference to the value corresponding to the key and returns the result.
+    ///
+    /// The value cannot be returned by reference as the dictionary lock is released before the
+    /// function returns. Use `f` to copy the data to the process memory.
+    pub fn get_with<Q, R>(&self, key: &Q, f: impl FnOnce(&V) -> R) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let now = current_msec();
+        {
+            let map = self.map.read();
+            let entry = map.get(key)?;
+            if !entry.is_expired(now) {
+                return Some(f(&entry.value));
+            }
+        }
+
+        let mut map = self.map.write();
+        if map.get(key).is_some_and(|x| x.is_expired(now)) {
+            map.remove(key);
+        }
+        None
+    }
+
+    /// Returns `true` if the dictionary contains an unexpired value for the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.get_with(key, |_| ()).is_some()
+    }
+
+    /// Inserts a key-value pair into the dictionary, replacing the existing value.
+    ///
+    /// The entry expires after `ttl` if specified. Lifetime is limited to approximately 24 days.
+    pub fn insert(&self, key: K, value: V, ttl: Option<Duration>) -> Result<(), AllocError> {
+        let expires = ttl.map(expires_at);
+
+        self.map
+            .write()
+            .try_insert(key, DictEntry { value, expires })?;
+        Ok(())
+    }
+
+    /// Removes a key from the dictionary, returning the value if it was present and not expired.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let entry = self.map.write().remove(key)?;
+        if entry.is_expired(current_msec()) {
+            return None;
+        }
+        Some(entry.value)
+    }
+
+    /// Removes all the entries from the dictionary.
+    pub fn clear(&self) {
+        self.map.write().clear()
+    }
+
+    /// Calls `f` for each unexpired entry in the dictionary.
+    ///
+    /// The dictionary is locked for reading during the iteration.
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        let now = current_msec();
+        for (key, entry) in self.map.read().iter() {
+            if !entry.is_expired(now) {
+                f(key, &entry.value)
+            }
+        }
+    }
+}
+
+unsafe impl<K, V> SharedZoneInit for SharedDict<K, V>
+where
+    K: Hash + Ord + Send + Sync,
+    V: Send + Sync,
+{
+    fn init(alloc: &SlabPool) -> Result<Self, AllocError> {
+        Self::try_new_in(alloc.clone())
+    }
+}
@@ -0,0 +1,19 @@
+//! Shared memory zones and data structures.
+//!
+//! This module provides a safer interface for declaring shared memory zones and storing Rust data
+//! structures in the zone slab pool.
+//!
+//! See <https://nginx.org/en/docs/dev/development_guide.html#shared_memory>.
+
+pub use dict::SharedDict;
+pub use settings::{
+    ngx_conf_set_shared_msec_slot, ngx_conf_set_shared_num_slot, ngx_conf_set_shared_size_slot,
+    SharedSetting, SharedSettings,
+};
+pub use snapshot::{SharedSnapshot, SnapshotRef};
+pub use zone::{SharedZone, SharedZoneBuilder, SharedZoneError, SharedZoneInit};
+
+mod dict;
+mod settings;
+mod snapshot;
+mod zone;
@@ -0,0 +1,304 @@
+use core::ffi::{c_char, c_void};
+use core::fmt;
+
+use nginx_sys::{
+    ngx_atoi, ngx_command_t, ngx_conf_t, ngx_int_t, ngx_parse_size, ngx_parse_time, ngx_str_t,
+};
+
+use crate::allocator::AllocError;
+use crate::core::{NgxStr, SlabPool, NGX_CONF_OK};
+use crate::shm::{SharedDict, SharedZone, SharedZoneInit};
+
+/// Shared memory zone value holding the runtime values of [SharedSetting]s.
+///
+/// Declare the zone with [SharedZoneBuilder](crate::shm::SharedZoneBuilder) and attach it to the
+/// settings with [SharedSetting::bind].
+///
+/// The values are kept in a [SharedDict] keyed by the setting name rather than in a fixed set of
+/// atomics: the names are only known once the configuration is parsed, and the zone may be shared
+/// by several modules. A runtime value is also stored together with the default it overrides,
+/// which has to be updated as a single unit. The settings are expected to be read much more often
+/// than written, so the readers only contend on the shared lock.
+pub struct SharedSettings(SharedDict<SettingName, SettingValue>);
+
+/// Maximum length of a setting name.
+const NAME_MAX: usize = 64;
+
+/// A setting name stored inline, so that it can be used as a key in the shared memory.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct SettingName {
+    len: usize,
+    data: [u8; NAME_MAX],
+}
+
+impl SettingName {
+    const EMPTY: Self = Self {
+        len: 0,
+        data: [0; NAME_MAX],
+    };
+
+    const fn new(name: &[u8]) -> Self {
+        assert!(name.len() <= NAME_MAX, "setting name is too long");
+
+        let mut data = [0; NAME_MAX];
+        let mut i = 0;
+        while i < name.len() {
+            data[i] = name[i];
+            i += 1;
+        }
+
+        Self {
+            len: name.len(),
+            data,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl fmt::Debug for SettingName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(NgxStr::from_bytes(self.as_bytes()), f)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SettingValue {
+    /// The configured value the override was made for.
+    default: u64,
+    value: u64,
+}
+
+unsafe impl SharedZoneInit for SharedSettings {
+    fn init(alloc: &SlabPool) -> Result<Self, AllocError> {
+        SharedDict::init(alloc).map(Self)
+    }
+}
+
+impl fmt::Debug for SharedSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedSettings").finish_non_exhaustive()
+    }
+}
+
+/// A numeric setting configurable in `nginx.conf` and adjustable at runtime for all the worker
+/// processes.
+///
+/// The directive value, set with one of the `ngx_conf_set_shared_*_slot` handlers, is used as a
+/// default. A runtime value written with [SharedSetting::set] is stored in a [SharedSettings]
+/// zone and takes precedence over the default until the default is changed with a configuration
+/// reload.
+///
+/// The runtime value is identified by the setting name, which is the directive name when set with
+/// the directive handlers. Settings with the same name and default in different configuration
+/// contexts share the value. Use [SharedSetting::new] to allow runtime values for a setting that
+/// is not specified in the configuration.
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::ptr;
+/// # use nginx_sys::{ngx_command_t, NGX_CONF_TAKE1, NGX_HTTP_MAIN_CONF, NGX_HTTP_MAIN_CONF_OFFSET};
+/// # use ngx::ngx_string;
+/// # use ngx::shm::{ngx_conf_set_shared_num_slot, SharedSetting};
+/// struct MainConfig {
+///     rate: SharedSetting,
+/// }
+///
+/// static mut COMMAND: ngx_command_t = ngx_command_t {
+///     name: ngx_string!("example_rate"),
+///     type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE1) as _,
+///     set: Some(ngx_conf_set_shared_num_slot),
+///     conf: NGX_HTTP_MAIN_CONF_OFFSET,
+///     offset: core::mem::offset_of!(MainConfig, rate),
+///     post: ptr::null_mut(),
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct SharedSetting {
+    name: SettingName,
+    default: Option<u64>,
+    zone: Option<SharedZone<SharedSettings>>,
+}
+
+impl Default for SharedSetting {
+    fn default() -> Self {
+        Self::unset()
+    }
+}
+
+impl SharedSetting {
+    /// Creates an unset setting with the specified name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is longer than 64 bytes.
+    pub const fn new(name: &str) -> Self {
+        Self {
+            name: SettingName::new(name.as_bytes()),
+            default: None,
+            zone: None,
+        }
+    }
+
+    /// Creates an unset and unnamed setting.
+    ///
+    /// The name is assigned when the setting is set with a directive or inherited with
+    /// [SharedSetting::merge].
+    pub const fn unset() -> Self {
+        Self {
+            name: SettingName::EMPTY,
+            default: None,
+            zone: None,
+        }
+    }
+
+    /// Returns `true` if the value was set in the configuration.
+    pub fn is_set(&self) -> bool {
+        self.default.is_some()
+    }
+
+    /// Sets the default value for the setting identified by `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is longer than 64 bytes.
+    pub fn set_default(&mut self, name: &[u8], value: u64) {
+        self.name = SettingName::new(name);
+        self.default = Some(value);
+    }
+
+    /// Inherits the value from the previous level or uses `default` if neither is set.
+    pub fn merge(&mut self, prev: &SharedSetting, default: u64) {
+        if self.name.is_empty() {
+            self.name = prev.name;
+        }
+        if self.default.is_none() {
+            self.default = Some(prev.default.unwrap_or(default));
+        }
+    }
+
+    /// Attaches the zone for the runtime values.
+    pub fn bind(&mut self, zone: SharedZone<SharedSettings>) {
+        self.zone = Some(zone);
+    }
+
+    /// Returns the configured default value.
+    pub fn default_value(&self) -> u64 {
+        self.default.unwrap_or(0)
+    }
+
+    /// Returns the current value.
+    ///
+    /// Returns the configured default if there is no runtime value, the zone is not attached or
+    /// not yet initialized.
+    pub fn get(&self) -> u64 {
+        let default = self.default_value();
+        let Some(settings) = self.zone.as_ref().and_then(SharedZone::get) else {
+            return default;
+        };
+
+        settings
+            .0
+            .get_with(&self.name, |x| *x)
+            .filter(|x| x.default == default)
+            .map_or(default, |x| x.value)
+    }
+
+    /// Sets the runtime value for all the worker processes.
+    ///
+    /// Does nothing if the zone is not attached or not yet initialized.
+    pub fn set(&self, value: u64) -> Result<(), AllocError> {
+        let Some(settings) = self.zone.as_ref().and_then(SharedZone::get) else {
+            return Ok(());
+        };
+
+        let value = SettingValue {
+            default: self.default_value(),
+            value,
+        };
+        settings.0.insert(self.name, value, None)
+    }
+
+    /// Removes the runtime value, restoring the configured default.
+    pub fn reset(&self) {
+        if let Some(settings) = self.zone.as_ref().and_then(SharedZone::get) {
+            settings.0.remove(&self.name);
+        }
+    }
+}
+
+/// Parses the directive argument with `parse` and stores it in the [SharedSetting] at
+/// `cmd.offset`.
+///
+/// # Safety
+///
+/// The arguments must be valid pointers passed to an `ngx_command_t.set` handler.
+unsafe fn set_shared_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+    parse: impl FnOnce(&mut ngx_str_t) -> ngx_int_t,
+) -> *mut c_char {
+    let setting = &mut *conf.cast::<u8>().add((*cmd).offset).cast::<SharedSetting>();
+    if setting.is_set() {
+        return c"is duplicate".as_ptr().cast_mut();
+    }
+
+    if (*cmd).name.len > NAME_MAX {
+        return c"name is too long".as_ptr().cast_mut();
+    }
+
+    let value = &mut *(*(*cf).args).elts.cast::<ngx_str_t>().add(1);
+    let n = parse(value);
+    if n < 0 {
+        return c"invalid value".as_ptr().cast_mut();
+    }
+
+    setting.set_default((*cmd).name.as_bytes(), n as u64);
+    NGX_CONF_OK
+}
+
+/// A directive handler storing a number in a [SharedSetting].
+///
+/// # Safety
+///
+/// The `conf` field at `cmd.offset` must be a [SharedSetting].
+pub unsafe extern "C" fn ngx_conf_set_shared_num_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    set_shared_slot(cf, cmd, conf, |v| ngx_atoi(v.data, v.len))
+}
+
+/// A directive handler storing a size in bytes in a [SharedSetting].
+///
+/// # Safety
+///
+/// The `conf` field at `cmd.offset` must be a [SharedSetting].
+pub unsafe extern "C" fn ngx_conf_set_shared_size_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    set_shared_slot(cf, cmd, conf, |v| ngx_parse_size(v) as ngx_int_t)
+}
+
+/// A directive handler storing a time interval in milliseconds in a [SharedSetting].
+///
+/// # Safety
+///
+/// The `conf` field at `cmd.offset` must be a [SharedSetting].
+pub unsafe extern "C" fn ngx_conf_set_shared_msec_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    set_shared_slot(cf, cmd, conf, |v| ngx_parse_time(v, 0))
+}
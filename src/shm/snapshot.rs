@@ -0,0 +1,293 @@
+use core::fmt;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{self, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+use nginx_sys::ngx_sched_yield;
+
+use crate::allocator::{self, AllocError, Allocator};
+use crate::core::SlabPool;
+use crate::shm::SharedZoneInit;
+use crate::sync::RwLock;
+
+/// Number of attempts to wait for the readers of a retired snapshot.
+const SNAPSHOT_WAIT_SPIN: usize = 4096;
+
+/// A value in shared memory replaced as a whole, with lock-free reads of consistent snapshots.
+///
+/// Intended for the settings updated at runtime, e.g. from an admin endpoint, and read by every
+/// request. [SharedSnapshot::load] returns a reference-counted [SnapshotRef] to the current value
+/// without taking any locks. Writers build a new value and publish it with
+/// [SharedSnapshot::store] or [SharedSnapshot::update]; the readers of the previous value keep
+/// using it until the last [SnapshotRef] is dropped, and the memory is returned to the slab pool.
+///
+/// The readers announce themselves in one of two counters selected by the parity of the current
+/// epoch for the few instructions between loading the pointer and incrementing the reference
+/// count. A writer bumps the epoch after publishing a new value and waits for the counter of the
+/// previous epoch to drain before releasing its reference to the old value. If a process crashes
+/// inside that window, the old value is leaked instead of waiting indefinitely.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::allocator::AllocError;
+/// # use ngx::core::SlabPool;
+/// # use ngx::shm::{SharedSnapshot, SharedZone, SharedZoneInit};
+/// #[derive(Clone, Default)]
+/// struct Limits {
+///     rate: u64,
+///     burst: u64,
+/// }
+///
+/// unsafe impl SharedZoneInit for Limits {
+///     fn init(_alloc: &SlabPool) -> Result<Self, AllocError> {
+///         Ok(Self::default())
+///     }
+/// }
+///
+/// fn handler(zone: &SharedZone<SharedSnapshot<Limits>>) -> Option<u64> {
+///     let limits = zone.get()?.load();
+///     Some(limits.rate + limits.burst)
+/// }
+///
+/// fn admin(zone: &SharedZone<SharedSnapshot<Limits>>, rate: u64) -> Result<(), AllocError> {
+///     let Some(snapshot) = zone.get() else {
+///         return Ok(());
+///     };
+///     snapshot.update(|x| Ok(Limits { rate, ..x.clone() }))
+/// }
+/// ```
+pub struct SharedSnapshot<T> {
+    current: AtomicPtr<Snapshot<T>>,
+    epoch: AtomicU64,
+    readers: [AtomicUsize; 2],
+    writer: RwLock<()>,
+    alloc: SlabPool,
+}
+
+struct Snapshot<T> {
+    refs: AtomicUsize,
+    version: u64,
+    value: T,
+}
+
+// SAFETY: the value is shared between the processes and released by the last reference holder.
+unsafe impl<T: Send + Sync> Send for SharedSnapshot<T> {}
+unsafe impl<T: Send + Sync> Sync for SharedSnapshot<T> {}
+
+impl<T> SharedSnapshot<T> {
+    /// Attempts to create a snapshot cell with the initial value in the specified slab pool.
+    pub fn try_new_in(value: T, alloc: SlabPool) -> Result<Self, AllocError> {
+        let snapshot = Snapshot {
+            refs: AtomicUsize::new(1),
+            version: 0,
+            value,
+        };
+        let current = allocator::allocate(snapshot, &alloc)?;
+
+        Ok(Self {
+            current: AtomicPtr::new(current.as_ptr()),
+            epoch: AtomicU64::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: RwLock::new(()),
+            alloc,
+        })
+    }
+
+    /// Returns a reference to the underlying allocator.
+    pub fn allocator(&self) -> &SlabPool {
+        &self.alloc
+    }
+
+    /// Returns the version of the current value.
+    ///
+    /// The version starts at zero and is incremented by each update. Can be used to refresh a
+    /// process-local copy of the value only when it changes.
+    pub fn version(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// Returns a reference to the current value.
+    pub fn load(&self) -> SnapshotRef<'_, T> {
+        let readers = loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let readers = &self.readers[(epoch & 1) as usize];
+            readers.fetch_add(1, Ordering::SeqCst);
+
+            // The writer of the next epoch waits for the other counter.
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                break readers;
+            }
+
+            readers.fetch_sub(1, Ordering::SeqCst);
+        };
+
+        let current = self.current.load(Ordering::SeqCst);
+        // SAFETY: the writer cannot release the snapshot while the reader counter is raised.
+        unsafe { (*current).refs.fetch_add(1, Ordering::Relaxed) };
+        readers.fetch_sub(1, Ordering::SeqCst);
+
+        SnapshotRef {
+            cell: self,
+            // SAFETY: the current value is always set.
+            snapshot: unsafe { NonNull::new_unchecked(current) },
+        }
+    }
+
+    /// Replaces the current value.
+    pub fn store(&self, value: T) -> Result<(), AllocError> {
+        let _guard = self.writer.write();
+        self.publish(value)
+    }
+
+    /// Replaces the current value with the result of `f`.
+    ///
+    /// The updates are serialized, so `f` always receives the latest value.
+    pub fn update(&self, f: impl FnOnce(&T) -> Result<T, AllocError>) -> Result<(), AllocError> {
+        let _guard = self.writer.write();
+        let current = self.current.load(Ordering::SeqCst);
+        // SAFETY: the current value can only be released by the writer holding the lock.
+        let value = f(unsafe { &(*current).value })?;
+        self.publish(value)
+    }
+
+    /// Publishes a new value. Must be called with the writer lock held.
+    fn publish(&self, value: T) -> Result<(), AllocError> {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        let snapshot = Snapshot {
+            refs: AtomicUsize::new(1),
+            version: epoch + 1,
+            value,
+        };
+        let snapshot = allocator::allocate(snapshot, &self.alloc)?;
+
+        let old = self.current.swap(snapshot.as_ptr(), Ordering::SeqCst);
+        self.epoch.store(epoch + 1, Ordering::SeqCst);
+
+        // Readers of the previous epoch may still be about to take a reference to the old value.
+        if self.wait_readers(&self.readers[(epoch & 1) as usize]) {
+            // SAFETY: the cell owned one reference to the old value.
+            unsafe { self.release(NonNull::new_unchecked(old)) };
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the reader counter drops to zero.
+    ///
+    /// Returns `false` if the counter is stuck, e.g. because a process exited in the middle of
+    /// [SharedSnapshot::load].
+    fn wait_readers(&self, readers: &AtomicUsize) -> bool {
+        for n in 0..SNAPSHOT_WAIT_SPIN {
+            if readers.load(Ordering::SeqCst) == 0 {
+                return true;
+            }
+
+            if n % 64 == 63 {
+                ngx_sched_yield();
+            } else {
+                core::hint::spin_loop();
+            }
+        }
+
+        false
+    }
+
+    /// Drops a reference to the snapshot, releasing the memory if it was the last one.
+    ///
+    /// # Safety
+    ///
+    /// The caller must own a reference to the snapshot.
+    unsafe fn release(&self, snapshot: NonNull<Snapshot<T>>) {
+        if snapshot.as_ref().refs.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+
+        atomic::fence(Ordering::Acquire);
+        ptr::drop_in_place(snapshot.as_ptr());
+        self.alloc
+            .deallocate(snapshot.cast(), core::alloc::Layout::new::<Snapshot<T>>());
+    }
+}
+
+impl<T> Drop for SharedSnapshot<T> {
+    fn drop(&mut self) {
+        let current = *self.current.get_mut();
+        // SAFETY: the cell owns a reference to the current value.
+        unsafe { self.release(NonNull::new_unchecked(current)) };
+    }
+}
+
+impl<T> fmt::Debug for SharedSnapshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedSnapshot")
+            .field("version", &self.version())
+            .finish_non_exhaustive()
+    }
+}
+
+unsafe impl<T> SharedZoneInit for SharedSnapshot<T>
+where
+    T: SharedZoneInit + Send,
+{
+    fn init(alloc: &SlabPool) -> Result<Self, AllocError> {
+        Self::try_new_in(T::init(alloc)?, alloc.clone())
+    }
+}
+
+/// A reference to a value loaded from a [SharedSnapshot].
+///
+/// The value stays unchanged and valid until the reference is dropped, regardless of the
+/// concurrent updates.
+pub struct SnapshotRef<'a, T> {
+    cell: &'a SharedSnapshot<T>,
+    snapshot: NonNull<Snapshot<T>>,
+}
+
+impl<T> SnapshotRef<'_, T> {
+    /// Returns the version of the value.
+    pub fn version(&self) -> u64 {
+        // SAFETY: the reference keeps the snapshot alive.
+        unsafe { self.snapshot.as_ref().version }
+    }
+
+    /// Returns `true` if a newer value was published since the reference was loaded.
+    pub fn is_stale(&self) -> bool {
+        self.cell.version() != self.version()
+    }
+}
+
+impl<T> Deref for SnapshotRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the reference keeps the snapshot alive.
+        unsafe { &self.snapshot.as_ref().value }
+    }
+}
+
+impl<T> Clone for SnapshotRef<'_, T> {
+    fn clone(&self) -> Self {
+        // SAFETY: the reference keeps the snapshot alive.
+        unsafe { self.snapshot.as_ref().refs.fetch_add(1, Ordering::Relaxed) };
+        Self {
+            cell: self.cell,
+            snapshot: self.snapshot,
+        }
+    }
+}
+
+impl<T> Drop for SnapshotRef<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the reference owns one count.
+        unsafe { self.cell.release(self.snapshot) }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SnapshotRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotRef")
+            .field("version", &self.version())
+            .field("value", &**self)
+            .finish()
+    }
+}
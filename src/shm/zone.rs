@@ -0,0 +1,210 @@
+use core::error;
+use core::ffi::c_void;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+
+use nginx_sys::{
+    ngx_conf_t, ngx_int_t, ngx_module_t, ngx_shared_memory_add, ngx_shm_zone_t, ngx_str_t,
+};
+
+use crate::allocator::{self, AllocError};
+use crate::core::{NgxStr, SlabPool, Status};
+
+/// Data structure that can be stored in a shared memory zone.
+///
+/// # Safety
+///
+/// The type will be accessed from multiple processes and must not contain any pointers to the
+/// process-local memory. All the allocations should be made with the zone allocator passed to
+/// [SharedZoneInit::init].
+pub unsafe trait SharedZoneInit: Sized + Sync {
+    /// Creates a new value in a freshly allocated zone.
+    fn init(alloc: &SlabPool) -> Result<Self, AllocError>;
+}
+
+/// An error returned when declaring a shared memory zone.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SharedZoneError {
+    /// `ngx_shared_memory_add` failed. The reason has already been logged.
+    Add,
+    /// The zone with the same name is already defined.
+    Duplicate,
+}
+
+impl error::Error for SharedZoneError {}
+
+impl fmt::Display for SharedZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SharedZoneError::Add => f.write_str("failed to add shared memory zone"),
+            SharedZoneError::Duplicate => f.write_str("duplicate shared memory zone"),
+        }
+    }
+}
+
+/// Builder for a shared memory zone holding a value of type `T`.
+///
+/// The builder registers the zone with `ngx_shared_memory_add` and installs an init callback that
+/// creates the value in the zone slab pool with [SharedZoneInit::init]. The value is preserved if
+/// NGINX reuses the zone memory on configuration reload.
+///
+/// Example:
+/// ```rust,no_run
+/// # use nginx_sys::{ngx_conf_t, ngx_module_t, ngx_str_t};
+/// # use ngx::core::NgxString;
+/// # use ngx::core::SlabPool;
+/// # use ngx::shm::{SharedDict, SharedZone, SharedZoneBuilder};
+/// # fn example(cf: &mut ngx_conf_t, module: &'static ngx_module_t, name: ngx_str_t) {
+/// type Dict = SharedDict<NgxString<SlabPool>, u64>;
+///
+/// let zone: SharedZone<Dict> = SharedZoneBuilder::new(name, 1024 * 1024, module)
+///     .build(cf)
+///     .expect("shared zone");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SharedZoneBuilder {
+    name: ngx_str_t,
+    size: usize,
+    tag: *mut c_void,
+}
+
+impl SharedZoneBuilder {
+    /// Creates a builder for a zone with the specified name and size.
+    ///
+    /// `name` must be allocated from the configuration pool or have a static lifetime. A zero
+    /// `size` declares a reference to a zone that is expected to be defined elsewhere in the
+    /// configuration.
+    pub fn new(name: ngx_str_t, size: usize, module: &'static ngx_module_t) -> Self {
+        Self {
+            name,
+            size,
+            tag: ptr::from_ref(module).cast_mut().cast(),
+        }
+    }
+
+    /// Sets a custom tag for the zone.
+    ///
+    /// Zones with the same name and different tags are reported as conflicting by NGINX.
+    pub fn tag(mut self, tag: *mut c_void) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Adds the zone to the configuration cycle.
+    pub fn build<T: SharedZoneInit>(
+        mut self,
+        cf: &mut ngx_conf_t,
+    ) -> Result<SharedZone<T>, SharedZoneError> {
+        let zone = unsafe { ngx_shared_memory_add(cf, &mut self.name, self.size, self.tag) };
+        let zone = NonNull::new(zone).ok_or(SharedZoneError::Add)?;
+
+        if self.size > 0 {
+            // SAFETY: the zone is allocated from the cycle pool and valid for the cycle lifetime.
+            let shm_zone = unsafe { &mut *zone.as_ptr() };
+            if !shm_zone.data.is_null() {
+                return Err(SharedZoneError::Duplicate);
+            }
+            // Mark the zone as defined. The value is never dereferenced.
+            shm_zone.data = zone.as_ptr().cast();
+            shm_zone.init = Some(shared_zone_init::<T>);
+        }
+
+        Ok(SharedZone {
+            zone,
+            _type: PhantomData,
+        })
+    }
+}
+
+unsafe extern "C" fn shared_zone_init<T: SharedZoneInit>(
+    shm_zone: *mut ngx_shm_zone_t,
+    _data: *mut c_void,
+) -> ngx_int_t {
+    let Some(mut alloc) = SlabPool::from_shm_zone(&*shm_zone) else {
+        return Status::NGX_ERROR.into();
+    };
+
+    // A non-NULL value means that the zone memory is inherited from the previous cycle.
+    if alloc.as_ref().data.is_null() {
+        let Ok(value) = T::init(&alloc) else {
+            return Status::NGX_ERROR.into();
+        };
+
+        let Ok(data) = allocator::allocate(value, &alloc) else {
+            return Status::NGX_ERROR.into();
+        };
+
+        alloc.as_mut().data = data.as_ptr().cast();
+    }
+
+    Status::NGX_OK.into()
+}
+
+/// Handle to a shared memory zone holding a value of type `T`.
+pub struct SharedZone<T> {
+    zone: NonNull<ngx_shm_zone_t>,
+    _type: PhantomData<T>,
+}
+
+impl<T> Clone for SharedZone<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SharedZone<T> {}
+
+impl<T> fmt::Debug for SharedZone<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedZone")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl<T> SharedZone<T> {
+    /// Creates a zone handle from a pointer to [ngx_shm_zone_t].
+    ///
+    /// # Safety
+    ///
+    /// `zone` is a valid pointer to a zone declared with [SharedZoneBuilder] for the same type `T`.
+    pub unsafe fn from_ptr(zone: *mut ngx_shm_zone_t) -> Option<Self> {
+        Some(Self {
+            zone: NonNull::new(zone)?,
+            _type: PhantomData,
+        })
+    }
+
+    /// Returns a raw pointer to the underlying `ngx_shm_zone_t`.
+    pub fn as_ptr(&self) -> *mut ngx_shm_zone_t {
+        self.zone.as_ptr()
+    }
+
+    /// Returns the name of the zone.
+    pub fn name(&self) -> &NgxStr {
+        unsafe { NgxStr::from_ngx_str(self.zone.as_ref().shm.name) }
+    }
+
+    /// Returns the size of the zone.
+    pub fn size(&self) -> usize {
+        unsafe { self.zone.as_ref().shm.size }
+    }
+
+    /// Returns the slab pool allocator of the zone.
+    ///
+    /// Returns `None` until the zone is mapped and initialized.
+    pub fn allocator(&self) -> Option<SlabPool> {
+        unsafe { SlabPool::from_shm_zone(self.zone.as_ref()) }
+    }
+
+    /// Returns a reference to the value stored in the zone.
+    ///
+    /// Returns `None` until the zone is mapped and initialized.
+    pub fn get(&self) -> Option<&T> {
+        let alloc = self.allocator()?;
+        // SAFETY: the data is set in the init callback and is valid while the zone is mapped.
+        unsafe { alloc.as_ref().data.cast::<T>().as_ref() }
+    }
+}
@@ -0,0 +1,463 @@
+//! TLS connection inspection and certificate selection.
+//!
+//! The inspection helpers wrap the `ngx_ssl_get_*` functions used by NGINX for the `$ssl_*`
+//! variables, and work the same with any TLS library supported by NGINX. The certificate
+//! selection requires OpenSSL 1.0.2 or later, or a compatible library.
+use core::any::type_name;
+use core::error;
+use core::ffi::{c_int, c_void};
+use core::fmt;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::core::{Connection, NgxStr, Pool, Status};
+use crate::ffi::{
+    ngx_conf_t, ngx_connection_t, ngx_decode_base64, ngx_int_t, ngx_pnalloc, ngx_pool_t,
+    ngx_ssl_certificate, ngx_ssl_connection_index, ngx_ssl_connection_t, ngx_ssl_get_cipher_name,
+    ngx_ssl_get_client_verify, ngx_ssl_get_protocol, ngx_ssl_get_raw_certificate,
+    ngx_ssl_get_server_name, ngx_ssl_t, ngx_str_t, BIO_free, BIO_new_mem_buf, ERR_clear_error,
+    EVP_PKEY_free, PEM_read_bio_PrivateKey, PEM_read_bio_X509, PEM_read_bio_X509_AUX,
+    SSL_CTX_set_cert_cb, SSL_ctrl, SSL_get_ex_data, SSL_use_PrivateKey, SSL_use_certificate,
+    X509_free, BIO, SSL, SSL_CTRL_CHAIN_CERT, SSL_CTRL_SET_CHAIN,
+};
+
+/// Errors returned by the certificate helpers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SslError {
+    /// The certificate cannot be parsed.
+    InvalidCertificate,
+    /// The private key cannot be parsed.
+    InvalidKey,
+    /// The TLS library rejected the certificate or the key.
+    Rejected,
+    /// Memory allocation failed.
+    Alloc,
+}
+
+impl error::Error for SslError {}
+
+impl fmt::Display for SslError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SslError::InvalidCertificate => write!(f, "invalid certificate"),
+            SslError::InvalidKey => write!(f, "invalid private key"),
+            SslError::Rejected => write!(f, "certificate or key rejected"),
+            SslError::Alloc => write!(f, "memory allocation failed"),
+        }
+    }
+}
+
+type Getter =
+    unsafe extern "C" fn(*mut ngx_connection_t, *mut ngx_pool_t, *mut ngx_str_t) -> ngx_int_t;
+
+/// The TLS state of a connection, [ngx_ssl_connection_t].
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::Connection;
+/// # use ngx::http::{HTTPStatus, Request};
+/// # use ngx::ssl::{ClientVerify, SslConnection};
+/// # fn handler(request: &Request) -> Result<(), HTTPStatus> {
+/// let c = unsafe { Connection::from_ptr(request.connection()) };
+/// let ssl = SslConnection::from_connection(c).ok_or(HTTPStatus::FORBIDDEN)?;
+///
+/// match ssl.client_verify(&request.pool()) {
+///     Some(ClientVerify::Success) => {}
+///     _ => return Err(HTTPStatus::FORBIDDEN),
+/// }
+///
+/// let cert = ssl
+///     .peer_certificate_der(&request.pool())
+///     .ok_or(HTTPStatus::FORBIDDEN)?;
+/// // ... match the certificate against the allowed clients
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct SslConnection<'a> {
+    c: &'a Connection,
+    ssl: NonNull<ngx_ssl_connection_t>,
+}
+
+/// The result of the client certificate verification, as in the `$ssl_client_verify` variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientVerify<'a> {
+    /// The certificate is verified.
+    Success,
+    /// The client did not send a certificate.
+    None,
+    /// The verification failed, with the reason reported by the TLS library.
+    Failed(&'a NgxStr),
+}
+
+impl<'a> SslConnection<'a> {
+    /// Returns the TLS state of the connection, or `None` for a plain text connection.
+    pub fn from_connection(c: &'a Connection) -> Option<Self> {
+        let ssl = NonNull::new(c.as_ref().ssl)?;
+        Some(Self { c, ssl })
+    }
+
+    /// Returns a raw pointer to the underlying [ngx_ssl_connection_t].
+    pub fn as_ptr(&self) -> *mut ngx_ssl_connection_t {
+        self.ssl.as_ptr()
+    }
+
+    /// Returns `true` if the TLS handshake is complete.
+    ///
+    /// Most of the other methods return `None` until the handshake is complete.
+    pub fn handshaked(&self) -> bool {
+        // SAFETY: the TLS state lives as long as the connection.
+        unsafe { self.ssl.as_ref() }.handshaked() != 0
+    }
+
+    /// Returns the negotiated protocol version, e.g. `TLSv1.3`.
+    pub fn protocol(&self) -> Option<&'a NgxStr> {
+        // The value is a static string of the TLS library.
+        self.get(ngx_ssl_get_protocol)
+    }
+
+    /// Returns the name of the negotiated cipher suite.
+    pub fn cipher(&self) -> Option<&'a NgxStr> {
+        // The value is a static string of the TLS library.
+        self.get(ngx_ssl_get_cipher_name)
+    }
+
+    /// Returns the server name requested with SNI.
+    pub fn server_name(&self) -> Option<&'a NgxStr> {
+        // The value is owned by the TLS connection.
+        self.get(ngx_ssl_get_server_name)
+    }
+
+    /// Returns the result of the client certificate verification.
+    ///
+    /// The result is only meaningful if the verification is enabled with `ssl_verify_client`.
+    /// The failure reason is allocated from `pool` and borrows it.
+    pub fn client_verify<'p>(&self, pool: &'p Pool) -> Option<ClientVerify<'p>> {
+        let s = self.get_in(ngx_ssl_get_client_verify, pool)?;
+
+        match s.as_bytes() {
+            b"SUCCESS" => Some(ClientVerify::Success),
+            b"NONE" => Some(ClientVerify::None),
+            x => {
+                let reason = x.strip_prefix(b"FAILED:")?;
+                Some(ClientVerify::Failed(NgxStr::from_bytes(reason)))
+            }
+        }
+    }
+
+    /// Returns the DER encoding of the peer certificate.
+    ///
+    /// Returns `None` if the peer did not send a certificate, or on allocation failure. The
+    /// certificate is allocated from `pool` and borrows it.
+    pub fn peer_certificate_der<'p>(&self, pool: &'p Pool) -> Option<&'p [u8]> {
+        let pem = self.get_in(ngx_ssl_get_raw_certificate, pool)?;
+        pem_to_der(pem.as_bytes(), pool)
+    }
+
+    /// Sets the certificate and the private key for the handshake.
+    ///
+    /// `cert` contains the certificate followed by the intermediate certificates, in PEM format.
+    /// `key` contains the unencrypted private key in PEM format. The certificates are parsed on
+    /// each call, so the PEM data can be kept in a shared memory zone and updated without a
+    /// configuration reload.
+    ///
+    /// Intended to be called from [CertificateCallback::select_certificate].
+    pub fn use_certificate_pem(&self, cert: &[u8], key: &[u8]) -> Result<(), SslError> {
+        // SAFETY: the TLS connection is valid while the connection is alive.
+        let ssl = unsafe { self.ssl.as_ref() }.connection;
+
+        // SAFETY: the objects are released on all paths, and the errors left in the queue by the
+        // PEM parser are cleared.
+        let rc = unsafe { use_certificate_pem(ssl, cert, key) };
+        unsafe { ERR_clear_error() };
+        rc
+    }
+
+    /// Calls a getter for a value that is static, owned by the connection, or allocated from the
+    /// connection pool.
+    fn get(&self, getter: Getter) -> Option<&'a NgxStr> {
+        let s = self.get_raw(getter, self.c.as_ref().pool)?;
+        // SAFETY: the value lives at least as long as the connection.
+        Some(unsafe { NgxStr::from_ngx_str(s) })
+    }
+
+    /// Calls a getter for a value allocated from `pool`.
+    fn get_in<'p>(&self, getter: Getter, pool: &'p Pool) -> Option<&'p NgxStr> {
+        let s = self.get_raw(getter, pool.as_ptr())?;
+        // SAFETY: the value is allocated from the pool, or is static.
+        Some(unsafe { NgxStr::from_ngx_str(s) })
+    }
+
+    fn get_raw(&self, getter: Getter, pool: *mut ngx_pool_t) -> Option<ngx_str_t> {
+        let mut s = ngx_str_t::default();
+        let c = ptr::from_ref(self.c.as_ref()).cast_mut();
+
+        // SAFETY: the getters only read the connection and allocate the value from the pool.
+        let rc = unsafe { getter(c, pool, &mut s) };
+        if rc != Status::NGX_OK.into() || s.len == 0 {
+            return None;
+        }
+        Some(s)
+    }
+}
+
+impl fmt::Debug for SslConnection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SslConnection")
+            .field("handshaked", &self.handshaked())
+            .field("protocol", &self.protocol())
+            .field("cipher", &self.cipher())
+            .field("server_name", &self.server_name())
+            .finish()
+    }
+}
+
+/// Decodes the first PEM block of `pem`.
+fn pem_to_der<'p>(pem: &[u8], pool: &'p Pool) -> Option<&'p [u8]> {
+    // The body of the block is between the "-----BEGIN ...-----" and "-----END ...-----" lines.
+    let mut lines = pem.split(|&b| b == b'\n').map(|x| x.trim_ascii());
+    lines.find(|x| x.starts_with(b"-----BEGIN "))?;
+
+    // SAFETY: the pool is valid.
+    let buf = unsafe { ngx_pnalloc(pool.as_ptr(), pem.len()) }.cast::<u8>();
+    if buf.is_null() {
+        return None;
+    }
+    // SAFETY: the buffer is allocated above with `pem.len()` bytes.
+    let buf = unsafe { slice::from_raw_parts_mut(buf, pem.len()) };
+
+    let mut len = 0;
+    for line in lines {
+        if line.starts_with(b"-----END ") {
+            break;
+        }
+        buf[len..len + line.len()].copy_from_slice(line);
+        len += line.len();
+    }
+
+    let mut src = ngx_str_t {
+        len,
+        data: buf.as_mut_ptr(),
+    };
+    let mut dst = ngx_str_t {
+        len: 0,
+        // The decoded data is never longer than the input, and the decoder writes behind the
+        // read position.
+        data: buf.as_mut_ptr(),
+    };
+
+    // SAFETY: the destination is large enough for the decoded data.
+    let rc = unsafe { ngx_decode_base64(&mut dst, &mut src) };
+    if rc != Status::NGX_OK.into() || dst.len == 0 {
+        return None;
+    }
+
+    // SAFETY: the decoded data is allocated from the pool.
+    Some(unsafe { slice::from_raw_parts(dst.data, dst.len) })
+}
+
+/// A memory BIO over a byte slice.
+struct MemBio(NonNull<BIO>);
+
+impl MemBio {
+    fn new(buf: &[u8]) -> Result<Self, SslError> {
+        let len = c_int::try_from(buf.len()).map_err(|_| SslError::Alloc)?;
+        // SAFETY: the BIO does not outlive the buffer and does not modify it.
+        let bio = unsafe { BIO_new_mem_buf(buf.as_ptr().cast(), len) };
+        NonNull::new(bio).map(Self).ok_or(SslError::Alloc)
+    }
+}
+
+impl Drop for MemBio {
+    fn drop(&mut self) {
+        // SAFETY: the BIO was created in MemBio::new.
+        unsafe { BIO_free(self.0.as_ptr()) };
+    }
+}
+
+unsafe fn use_certificate_pem(ssl: *mut SSL, cert: &[u8], key: &[u8]) -> Result<(), SslError> {
+    let bio = MemBio::new(cert)?;
+
+    let x509 = PEM_read_bio_X509_AUX(bio.0.as_ptr(), ptr::null_mut(), None, ptr::null_mut());
+    if x509.is_null() {
+        return Err(SslError::InvalidCertificate);
+    }
+
+    let rc = SSL_use_certificate(ssl, x509);
+    X509_free(x509);
+    if rc == 0 {
+        return Err(SslError::Rejected);
+    }
+
+    // SSL_clear_chain_certs
+    if SSL_ctrl(ssl, SSL_CTRL_SET_CHAIN as _, 0, ptr::null_mut()) == 0 {
+        return Err(SslError::Rejected);
+    }
+
+    loop {
+        let x509 = PEM_read_bio_X509(bio.0.as_ptr(), ptr::null_mut(), None, ptr::null_mut());
+        if x509.is_null() {
+            break;
+        }
+
+        // SSL_add0_chain_cert, takes the ownership on success
+        if SSL_ctrl(ssl, SSL_CTRL_CHAIN_CERT as _, 0, x509.cast()) == 0 {
+            X509_free(x509);
+            return Err(SslError::Rejected);
+        }
+    }
+
+    let bio = MemBio::new(key)?;
+
+    let pkey = PEM_read_bio_PrivateKey(bio.0.as_ptr(), ptr::null_mut(), None, ptr::null_mut());
+    if pkey.is_null() {
+        return Err(SslError::InvalidKey);
+    }
+
+    let rc = SSL_use_PrivateKey(ssl, pkey);
+    EVP_PKEY_free(pkey);
+    if rc == 0 {
+        return Err(SslError::Rejected);
+    }
+
+    Ok(())
+}
+
+/// Adds a certificate from PEM data to the TLS context, as with the `ssl_certificate` and
+/// `ssl_certificate_key` directives.
+///
+/// `cert` contains the certificate followed by the intermediate certificates, and `key` contains
+/// the unencrypted private key, both in PEM format. Errors are logged to the configuration log.
+pub fn add_certificate_pem(
+    cf: &mut ngx_conf_t,
+    ssl: &mut ngx_ssl_t,
+    cert: &[u8],
+    key: &[u8],
+) -> Result<(), SslError> {
+    // SAFETY: the configuration pool is valid during the configuration parsing.
+    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+
+    let mut cert = data_str(&pool, cert).ok_or(SslError::Alloc)?;
+    let mut key = data_str(&pool, key).ok_or(SslError::Alloc)?;
+
+    // SAFETY: the strings are allocated from the configuration pool.
+    let rc = unsafe { ngx_ssl_certificate(cf, ssl, &mut cert, &mut key, ptr::null_mut()) };
+    if rc != Status::NGX_OK.into() {
+        return Err(SslError::Rejected);
+    }
+
+    Ok(())
+}
+
+/// Creates a `data:` string, which NGINX treats as inline PEM data instead of a file name.
+fn data_str(pool: &Pool, pem: &[u8]) -> Option<ngx_str_t> {
+    const PREFIX: &[u8] = b"data:";
+
+    let len = PREFIX.len() + pem.len();
+    // SAFETY: the pool is valid.
+    let data = unsafe { ngx_pnalloc(pool.as_ptr(), len) }.cast::<u8>();
+    if data.is_null() {
+        return None;
+    }
+
+    // SAFETY: the buffer is allocated above with `len` bytes.
+    let buf = unsafe { slice::from_raw_parts_mut(data, len) };
+    buf[..PREFIX.len()].copy_from_slice(PREFIX);
+    buf[PREFIX.len()..].copy_from_slice(pem);
+
+    Some(ngx_str_t { len, data })
+}
+
+/// A certificate selection callback, called during the TLS handshake before a certificate is
+/// chosen.
+///
+/// The callback has access to the requested server name and can set a certificate with
+/// [SslConnection::use_certificate_pem]. Use [set_certificate_callback] to install the callback
+/// into a TLS context, e.g. into the context of `ngx_http_ssl_module` in the postconfiguration
+/// handler.
+///
+/// NGINX uses the same callback slot for the certificates with variables, so the callback must
+/// not be installed into a context with such certificates. A panic in the callback is handled
+/// with [crate::panic::catch] and aborts the handshake.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::ssl::{set_certificate_callback, CertificateCallback, SslConnection, SslError};
+/// # fn lookup(name: &[u8]) -> Option<(&'static [u8], &'static [u8])> { None }
+/// struct Selector;
+///
+/// impl CertificateCallback for Selector {
+///     fn select_certificate(ssl: SslConnection<'_>) -> Result<(), SslError> {
+///         let Some(name) = ssl.server_name() else {
+///             // keep the configured certificate
+///             return Ok(());
+///         };
+///
+///         match lookup(name.as_bytes()) {
+///             Some((cert, key)) => ssl.use_certificate_pem(cert, key),
+///             None => Ok(()),
+///         }
+///     }
+/// }
+///
+/// # fn postconfiguration(ssl: &mut ngx::ffi::ngx_ssl_t) {
+/// set_certificate_callback::<Selector>(ssl);
+/// # }
+/// ```
+pub trait CertificateCallback {
+    /// Selects the certificate for the connection.
+    ///
+    /// An error aborts the handshake.
+    fn select_certificate(ssl: SslConnection<'_>) -> Result<(), SslError>;
+}
+
+/// Installs the [CertificateCallback] of `C` into the TLS context.
+///
+/// Does nothing if the context is not created.
+pub fn set_certificate_callback<C: CertificateCallback>(ssl: &mut ngx_ssl_t) {
+    if ssl.ctx.is_null() {
+        return;
+    }
+
+    // SAFETY: the context is valid.
+    unsafe {
+        SSL_CTX_set_cert_cb(
+            ssl.ctx.cast(),
+            Some(certificate_callback::<C>),
+            ptr::null_mut(),
+        )
+    };
+}
+
+unsafe extern "C" fn certificate_callback<C: CertificateCallback>(
+    ssl_conn: *mut SSL,
+    _arg: *mut c_void,
+) -> c_int {
+    // ngx_ssl_get_connection
+    let c = SSL_get_ex_data(ssl_conn, ngx_ssl_connection_index).cast::<ngx_connection_t>();
+    if c.is_null() {
+        return 0;
+    }
+
+    let c = Connection::from_ptr(c);
+    let Some(ssl) = SslConnection::from_connection(c) else {
+        return 0;
+    };
+
+    let rc = crate::panic::catch(type_name::<C>(), "select_certificate", || {
+        C::select_certificate(ssl)
+    });
+
+    match rc {
+        Ok(Ok(())) => 1,
+        Ok(Err(err)) => {
+            crate::ngx_log_error!(
+                crate::ffi::NGX_LOG_ERR,
+                c.log().as_ptr(),
+                "certificate selection failed: {err}"
+            );
+            0
+        }
+        Err(_) => 0,
+    }
+}
@@ -22,10 +22,21 @@
 //! > shared between two processes. — end note]
 //!
 //! In practice, this recommendation is applied in all the implementations that matter to us.
+//!
+//! When interoperability is required, [ShmMutex] and [RawShmMutex] wrap the nginx shared memory
+//! mutex, `ngx_shmtx_t`.
 use core::sync::atomic::{self, Ordering};
 
 use nginx_sys::ngx_sched_yield;
 
+pub use self::counter::{Counter, CounterVec};
+pub use self::shmtx::{
+    RawShmMutex, RawShmMutexGuard, ShmMutex, ShmMutexError, ShmMutexGuard, ShmMutexSpin,
+};
+
+mod counter;
+mod shmtx;
+
 const NGX_RWLOCK_SPIN: usize = 2048;
 const NGX_RWLOCK_WLOCK: usize = usize::MAX;
 
@@ -0,0 +1,266 @@
+use core::alloc::Layout;
+use core::mem;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use nginx_sys::{ngx_core_conf_t, ngx_core_module, ngx_cycle_t};
+
+use crate::allocator::{AllocError, Allocator};
+
+/// Assumed size of a cache line.
+const CACHE_LINE_SIZE: usize = 64;
+
+/// A set of counters with a separate slot for each worker process.
+///
+/// Each worker updates only its own slots, selected by `ngx_worker`, so the updates from different
+/// workers never contend for the same cache line. The value of a counter is the sum of the slots.
+///
+/// The type is a non-owning handle to the memory allocated with [CounterVec::try_new_in], usually
+/// from a [SlabPool](crate::core::SlabPool). The handle can be copied and stored in the shared
+/// memory zone along with the counters, and remains valid for the lifetime of the zone.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::allocator::AllocError;
+/// # use ngx::core::SlabPool;
+/// # use ngx::ffi::ngx_cycle_t;
+/// # use ngx::sync::CounterVec;
+/// const REQUESTS: usize = 0;
+/// const ERRORS: usize = 1;
+///
+/// # fn example(cycle: &ngx_cycle_t, pool: SlabPool) -> Result<(), AllocError> {
+/// // in the zone init handler
+/// let stats = CounterVec::try_for_cycle_in(cycle, 2, &pool)?;
+///
+/// // in a request handler
+/// stats.incr(REQUESTS);
+///
+/// // in a status handler
+/// let (requests, errors) = (stats.sum(REQUESTS), stats.sum(ERRORS));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CounterVec {
+    slots: NonNull<AtomicU64>,
+    len: usize,
+    workers: usize,
+    stride: usize,
+}
+
+// SAFETY: the counters are atomic and are designed to be shared between processes.
+unsafe impl Send for CounterVec {}
+unsafe impl Sync for CounterVec {}
+
+impl CounterVec {
+    /// Allocates `len` counters with slots for `workers` worker processes.
+    ///
+    /// The memory is never released, as the counters are expected to live as long as the shared
+    /// memory zone they are allocated from.
+    pub fn try_new_in<A>(len: usize, workers: usize, alloc: &A) -> Result<Self, AllocError>
+    where
+        A: Allocator + ?Sized,
+    {
+        let workers = workers.max(1);
+        // round the row of each worker up to a whole number of cache lines
+        let stride = len
+            .checked_next_multiple_of(CACHE_LINE_SIZE / mem::size_of::<AtomicU64>())
+            .ok_or(AllocError)?;
+        let size = stride
+            .checked_mul(workers)
+            .and_then(|x| x.checked_mul(mem::size_of::<AtomicU64>()))
+            .ok_or(AllocError)?;
+
+        let layout = Layout::from_size_align(size, CACHE_LINE_SIZE).map_err(|_| AllocError)?;
+        let slots = alloc.allocate_zeroed(layout)?.cast();
+
+        Ok(Self {
+            slots,
+            len,
+            workers,
+            stride,
+        })
+    }
+
+    /// Allocates `len` counters with slots for the worker processes configured in `cycle`.
+    pub fn try_for_cycle_in<A>(
+        cycle: &ngx_cycle_t,
+        len: usize,
+        alloc: &A,
+    ) -> Result<Self, AllocError>
+    where
+        A: Allocator + ?Sized,
+    {
+        Self::try_new_in(len, worker_processes(cycle), alloc)
+    }
+
+    /// Returns the number of counters.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no counters.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of per-worker slots of each counter.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Increments the counter at `index` by one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn incr(&self, index: usize) {
+        self.add(index, 1)
+    }
+
+    /// Adds `n` to the counter at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn add(&self, index: usize, n: u64) {
+        // Workers with the number beyond the configured count, e.g. after a configuration reload
+        // that increased `worker_processes`, share the slots with other workers.
+        let worker = unsafe { nginx_sys::ngx_worker } % self.workers;
+        self.slot(worker, index).fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Returns the sum of the slots of the counter at `index`.
+    ///
+    /// The slots are read without synchronization with the writers, thus the value is only
+    /// eventually consistent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn sum(&self, index: usize) -> u64 {
+        self.per_worker(index).fold(0, u64::wrapping_add)
+    }
+
+    /// Returns the values of the counter at `index` for each worker process.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn per_worker(&self, index: usize) -> impl Iterator<Item = u64> + '_ {
+        assert!(index < self.len, "counter index out of bounds");
+        (0..self.workers).map(move |worker| self.slot(worker, index).load(Ordering::Relaxed))
+    }
+
+    fn slot(&self, worker: usize, index: usize) -> &AtomicU64 {
+        assert!(index < self.len, "counter index out of bounds");
+        debug_assert!(worker < self.workers);
+        // SAFETY: the index is within the allocated memory
+        unsafe { self.slots.add(worker * self.stride + index).as_ref() }
+    }
+}
+
+/// A counter with a separate slot for each worker process.
+///
+/// See [CounterVec] for the details.
+#[derive(Clone, Copy, Debug)]
+pub struct Counter(CounterVec);
+
+impl Counter {
+    /// Allocates a counter with slots for `workers` worker processes.
+    pub fn try_new_in<A>(workers: usize, alloc: &A) -> Result<Self, AllocError>
+    where
+        A: Allocator + ?Sized,
+    {
+        CounterVec::try_new_in(1, workers, alloc).map(Self)
+    }
+
+    /// Allocates a counter with slots for the worker processes configured in `cycle`.
+    pub fn try_for_cycle_in<A>(cycle: &ngx_cycle_t, alloc: &A) -> Result<Self, AllocError>
+    where
+        A: Allocator + ?Sized,
+    {
+        CounterVec::try_for_cycle_in(cycle, 1, alloc).map(Self)
+    }
+
+    /// Returns the number of per-worker slots.
+    pub fn workers(&self) -> usize {
+        self.0.workers()
+    }
+
+    /// Increments the counter by one.
+    #[inline]
+    pub fn incr(&self) {
+        self.0.incr(0)
+    }
+
+    /// Adds `n` to the counter.
+    #[inline]
+    pub fn add(&self, n: u64) {
+        self.0.add(0, n)
+    }
+
+    /// Returns the sum of the slots.
+    pub fn sum(&self) -> u64 {
+        self.0.sum(0)
+    }
+
+    /// Returns the values of the counter for each worker process.
+    pub fn per_worker(&self) -> impl Iterator<Item = u64> + '_ {
+        self.0.per_worker(0)
+    }
+}
+
+/// Returns the value of the `worker_processes` directive in `cycle`.
+fn worker_processes(cycle: &ngx_cycle_t) -> usize {
+    if cycle.conf_ctx.is_null() {
+        return 1;
+    }
+
+    // SAFETY: the core module configuration is created before any other module configuration
+    let ccf = unsafe {
+        let index = (*ptr::addr_of!(ngx_core_module)).index;
+        (*cycle.conf_ctx.add(index))
+            .cast::<ngx_core_conf_t>()
+            .as_ref()
+    };
+
+    ccf.map_or(1, |ccf| ccf.worker_processes.max(1) as usize)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::allocator::Global;
+
+    #[test]
+    fn test_counter_vec() {
+        let counters = CounterVec::try_new_in(3, 4, &Global).unwrap();
+        assert_eq!(counters.stride, 8);
+
+        for worker in 0..4 {
+            counters
+                .slot(worker, 1)
+                .fetch_add(worker as u64 + 1, Ordering::Relaxed);
+        }
+        counters.slot(2, 2).fetch_add(5, Ordering::Relaxed);
+
+        assert_eq!(counters.sum(0), 0);
+        assert_eq!(counters.sum(1), 10);
+        assert_eq!(counters.sum(2), 5);
+        assert!(counters.per_worker(1).eq([1, 2, 3, 4]));
+
+        let addr = |worker| counters.slot(worker, 0) as *const _ as usize;
+        assert_eq!(addr(1) - addr(0), CACHE_LINE_SIZE);
+        assert_eq!(addr(0) % CACHE_LINE_SIZE, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_counter_vec_out_of_bounds() {
+        let counters = CounterVec::try_new_in(3, 1, &Global).unwrap();
+        counters.sum(3);
+    }
+}
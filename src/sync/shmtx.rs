@@ -0,0 +1,322 @@
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::error;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use nginx_sys::{
+    ngx_pid_t, ngx_shmtx_create, ngx_shmtx_destroy, ngx_shmtx_force_unlock, ngx_shmtx_lock,
+    ngx_shmtx_sh_t, ngx_shmtx_t, ngx_shmtx_trylock, ngx_shmtx_unlock, ngx_uint_t, NGX_OK,
+};
+
+use crate::allocator::{AllocError, Allocator};
+
+/// Error type for [ShmMutex] creation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShmMutexError {
+    /// Memory allocation error.
+    Alloc,
+    /// The mutex could not be created.
+    Create,
+}
+
+impl fmt::Display for ShmMutexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShmMutexError::Alloc => fmt::Display::fmt(&AllocError, f),
+            ShmMutexError::Create => f.write_str("failed to create shared memory mutex"),
+        }
+    }
+}
+
+impl error::Error for ShmMutexError {}
+
+impl From<AllocError> for ShmMutexError {
+    fn from(_: AllocError) -> Self {
+        ShmMutexError::Alloc
+    }
+}
+
+/// The spin count set by `ngx_shmtx_create`.
+const DEFAULT_SPIN: ngx_uint_t = 2048;
+
+/// Waiting strategy of a [ShmMutex].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShmMutexSpin {
+    /// Spin for up to 2048 iterations, then sleep on a semaphore if available or yield the CPU.
+    #[default]
+    Default,
+    /// Spin for up to the specified number of iterations before sleeping or yielding.
+    Count(usize),
+    /// Never sleep on a semaphore, only spin and yield the CPU.
+    ///
+    /// Suitable for locks that are held for a short time only.
+    NoSemaphore,
+}
+
+/// A reference to an nginx shared memory mutex, [`ngx_shmtx_t`].
+///
+/// The type is interoperable with the mutexes created by nginx, such as the mutex of a slab pool.
+/// Unlike [RwLock](super::RwLock), the lock is owned by a process, not by a thread, and is not
+/// recursive.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::core::SlabPool;
+/// # use ngx::sync::RawShmMutex;
+/// # fn example(mut pool: SlabPool) {
+/// let mutex = unsafe { RawShmMutex::from_ptr(&mut pool.as_mut().mutex) };
+///
+/// let guard = mutex.lock();
+/// // the slab pool is locked until the guard is dropped
+/// drop(guard);
+/// # }
+/// ```
+#[repr(transparent)]
+pub struct RawShmMutex(UnsafeCell<ngx_shmtx_t>);
+
+// SAFETY: the mutex is designed to be shared between processes.
+unsafe impl Send for RawShmMutex {}
+unsafe impl Sync for RawShmMutex {}
+
+impl RawShmMutex {
+    /// Creates a mutex reference from a pointer to an initialized [`ngx_shmtx_t`].
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be valid and point to a mutex created with [`ngx_shmtx_create`] for the
+    /// duration of the lifetime `'a`.
+    pub unsafe fn from_ptr<'a>(mtx: *mut ngx_shmtx_t) -> &'a Self {
+        &*mtx.cast()
+    }
+
+    /// Returns a raw pointer to the underlying [`ngx_shmtx_t`].
+    pub fn as_ptr(&self) -> *mut ngx_shmtx_t {
+        self.0.get()
+    }
+
+    /// Acquires the mutex, blocking the current process until it is able to do so.
+    pub fn lock(&self) -> RawShmMutexGuard<'_> {
+        unsafe { ngx_shmtx_lock(self.as_ptr()) };
+        RawShmMutexGuard(self, PhantomData)
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    pub fn try_lock(&self) -> Option<RawShmMutexGuard<'_>> {
+        if unsafe { ngx_shmtx_trylock(self.as_ptr()) } == 0 {
+            return None;
+        }
+        Some(RawShmMutexGuard(self, PhantomData))
+    }
+
+    /// Releases the mutex if it is held by the process with the specified `pid`.
+    ///
+    /// Returns `true` if the mutex was released. This is intended for the recovery after an
+    /// abnormal termination of a worker process.
+    ///
+    /// # Safety
+    ///
+    /// The data protected by the mutex may be left in an inconsistent state.
+    pub unsafe fn force_unlock(&self, pid: ngx_pid_t) -> bool {
+        ngx_shmtx_force_unlock(self.as_ptr(), pid) != 0
+    }
+}
+
+impl fmt::Debug for RawShmMutex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawShmMutex").finish_non_exhaustive()
+    }
+}
+
+/// RAII structure used to release a [RawShmMutex] when dropped.
+#[must_use = "if unused the mutex will immediately unlock"]
+pub struct RawShmMutexGuard<'a>(&'a RawShmMutex, PhantomData<*const ()>);
+
+impl Drop for RawShmMutexGuard<'_> {
+    fn drop(&mut self) {
+        unsafe { ngx_shmtx_unlock(self.0.as_ptr()) };
+    }
+}
+
+impl fmt::Debug for RawShmMutexGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RawShmMutexGuard").field(self.0).finish()
+    }
+}
+
+/// Mutual exclusion lock over an nginx shared memory mutex.
+///
+/// The lock uses [`ngx_shmtx_t`] and follows the same waiting strategy as the rest of nginx:
+/// spinning on multiprocessor systems, then sleeping on a POSIX semaphore if available or yielding
+/// the CPU. See [ShmMutexSpin] for the available options.
+///
+/// The mutex refers to its own memory and must be created in place, normally in a shared memory
+/// zone with [ShmMutex::try_new_in].
+///
+/// Example:
+/// ```rust,no_run
+/// # use core::ptr::NonNull;
+/// # use ngx::core::SlabPool;
+/// # use ngx::sync::{ShmMutex, ShmMutexError, ShmMutexSpin};
+/// # fn example(pool: SlabPool) -> Result<(), ShmMutexError> {
+/// let counter: NonNull<ShmMutex<u64>> = ShmMutex::try_new_in(0, ShmMutexSpin::Default, &pool)?;
+///
+/// *unsafe { counter.as_ref() }.lock() += 1;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ShmMutex<T: ?Sized> {
+    sh: UnsafeCell<ngx_shmtx_sh_t>,
+    raw: RawShmMutex,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ShmMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ShmMutex<T> {}
+
+impl<T> ShmMutex<T> {
+    /// Allocates a mutex containing `value` from `alloc`.
+    ///
+    /// The mutex is not released automatically; to destroy it, drop the value in place and
+    /// deallocate the memory.
+    ///
+    /// On the platforms without atomic operations the mutex cannot be created, as nginx falls back
+    /// to file locks that require a lock file name.
+    pub fn try_new_in<A>(
+        value: T,
+        spin: ShmMutexSpin,
+        alloc: &A,
+    ) -> Result<NonNull<Self>, ShmMutexError>
+    where
+        A: Allocator + ?Sized,
+    {
+        let ptr = alloc.allocate(Layout::new::<Self>())?.cast();
+
+        // SAFETY: the memory is allocated for `Self` and is not moved afterwards
+        if let Err(err) = unsafe { Self::init(ptr, value, spin) } {
+            unsafe { alloc.deallocate(ptr.cast(), Layout::new::<Self>()) };
+            return Err(err);
+        }
+
+        Ok(ptr)
+    }
+
+    /// Initializes a mutex containing `value` at the specified location.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and properly aligned. The mutex must not be moved after the
+    /// initialization and should be located in a memory shared with the processes using it.
+    pub unsafe fn init(
+        ptr: NonNull<Self>,
+        value: T,
+        spin: ShmMutexSpin,
+    ) -> Result<(), ShmMutexError> {
+        let this = ptr.as_ptr();
+
+        ptr::write(ptr::addr_of_mut!((*this).sh), mem::zeroed());
+        ptr::write(ptr::addr_of_mut!((*this).raw), mem::zeroed());
+
+        let mtx = (*this).raw.as_ptr();
+        // `ngx_shmtx_create` does not initialize the semaphore for this spin value
+        if spin == ShmMutexSpin::NoSemaphore {
+            (*mtx).spin = ngx_uint_t::MAX;
+        }
+
+        if ngx_shmtx_create(mtx, (*this).sh.get(), c"".as_ptr().cast_mut().cast()) != NGX_OK as _ {
+            return Err(ShmMutexError::Create);
+        }
+
+        match spin {
+            ShmMutexSpin::Default => {}
+            ShmMutexSpin::Count(n) => (*mtx).spin = n as ngx_uint_t,
+            // the value only disables the semaphore, restore the default spin count
+            ShmMutexSpin::NoSemaphore => (*mtx).spin = DEFAULT_SPIN,
+        }
+
+        ptr::write(ptr::addr_of_mut!((*this).data), UnsafeCell::new(value));
+        Ok(())
+    }
+}
+
+impl<T: ?Sized> ShmMutex<T> {
+    /// Acquires the mutex, blocking the current process until it is able to do so.
+    pub fn lock(&self) -> ShmMutexGuard<'_, T> {
+        ShmMutexGuard {
+            _raw: self.raw.lock(),
+            data: &self.data,
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    pub fn try_lock(&self) -> Option<ShmMutexGuard<'_, T>> {
+        Some(ShmMutexGuard {
+            _raw: self.raw.try_lock()?,
+            data: &self.data,
+        })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is needed, as the mutable borrow statically guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns the underlying nginx mutex.
+    pub fn raw(&self) -> &RawShmMutex {
+        &self.raw
+    }
+}
+
+impl<T: ?Sized> Drop for ShmMutex<T> {
+    fn drop(&mut self) {
+        unsafe { ngx_shmtx_destroy(self.raw.as_ptr()) };
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ShmMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("ShmMutex");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// RAII structure used to release a [ShmMutex] when dropped.
+///
+/// The protected data can be accessed through this guard via its [Deref] and [DerefMut]
+/// implementations.
+#[must_use = "if unused the mutex will immediately unlock"]
+pub struct ShmMutexGuard<'a, T: ?Sized> {
+    _raw: RawShmMutexGuard<'a>,
+    data: &'a UnsafeCell<T>,
+}
+
+impl<T: ?Sized> Deref for ShmMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock
+        unsafe { &*self.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ShmMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ShmMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,244 @@
+//! Offloading blocking work to NGINX thread pools.
+//!
+//! The thread pools are defined with the [`thread_pool`] directive and started in the worker
+//! processes. A closure submitted with [spawn_blocking] runs on one of the pool threads, and the
+//! returned future completes on the event loop once the pool reports the task as done.
+//!
+//! Example:
+//! ```rust,no_run
+//! use ngx::thread::{spawn_blocking, ThreadError};
+//!
+//! async fn read_hostname() -> Result<Vec<u8>, ThreadError> {
+//!     let contents = spawn_blocking("default", || std::fs::read("/etc/hostname"))?.await;
+//!     Ok(contents.unwrap_or_default())
+//! }
+//! ```
+//!
+//! [`thread_pool`]: https://nginx.org/en/docs/ngx_core_module.html#thread_pool
+use core::error;
+use core::ffi::c_void;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::{self, Poll};
+use std::boxed::Box;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+use nginx_sys::{
+    ngx_conf_t, ngx_cycle, ngx_event_t, ngx_log_t, ngx_str_t, ngx_thread_pool_add,
+    ngx_thread_pool_get, ngx_thread_pool_t, ngx_thread_task_post, ngx_thread_task_t, NGX_OK,
+};
+
+use crate::log::ngx_cycle_log;
+
+/// An error returned when submitting a task to a thread pool.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThreadError {
+    /// The thread pool with the specified name is not configured.
+    NotFound,
+    /// Memory allocation failed.
+    Alloc,
+    /// `ngx_thread_task_post` failed, e.g. because the pool queue overflowed. The reason has
+    /// already been logged.
+    Post,
+}
+
+impl error::Error for ThreadError {}
+
+impl fmt::Display for ThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadError::NotFound => f.write_str("thread pool not found"),
+            ThreadError::Alloc => f.write_str("thread task allocation failed"),
+            ThreadError::Post => f.write_str("failed to post thread task"),
+        }
+    }
+}
+
+/// A reference to an NGINX thread pool.
+///
+/// The pool is owned by the configuration cycle and is valid for its lifetime.
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadPool(NonNull<ngx_thread_pool_t>);
+
+impl ThreadPool {
+    /// Declares the use of a thread pool at the configuration time.
+    ///
+    /// The configuration fails later if a pool with this name is not defined with the
+    /// `thread_pool` directive. The `default` pool is created implicitly, with 32 threads.
+    pub fn add(cf: &mut ngx_conf_t, name: impl AsRef<[u8]>) -> Result<Self, ThreadError> {
+        let mut name =
+            unsafe { ngx_str_t::from_bytes(cf.pool, name.as_ref()) }.ok_or(ThreadError::Alloc)?;
+        let tp = unsafe { ngx_thread_pool_add(cf, &mut name) };
+        NonNull::new(tp).map(Self).ok_or(ThreadError::Alloc)
+    }
+
+    /// Looks up a thread pool by name in the current cycle.
+    pub fn get(name: impl AsRef<[u8]>) -> Option<Self> {
+        let name = name.as_ref();
+        let mut name = ngx_str_t {
+            len: name.len(),
+            data: name.as_ptr().cast_mut(),
+        };
+        // SAFETY: the name is only compared with the configured pools and is not modified.
+        let tp = unsafe { ngx_thread_pool_get(ngx_cycle, &mut name) };
+        NonNull::new(tp).map(Self)
+    }
+
+    /// Returns a raw pointer to the pool.
+    pub fn as_ptr(&self) -> *mut ngx_thread_pool_t {
+        self.0.as_ptr()
+    }
+
+    /// Runs `f` on a thread of the pool.
+    ///
+    /// See [spawn_blocking].
+    pub fn spawn_blocking<F, T>(&self, f: F) -> Result<BlockingTask<T>, ThreadError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let p = Box::into_raw(Box::new(Inner::<T> {
+            // SAFETY: ngx_thread_task_t is a plain C structure, all zeroes is a valid value.
+            task: unsafe { core::mem::zeroed() },
+            func: Some(Box::new(f)),
+            result: None,
+            waker: None,
+            state: State::Running,
+        }));
+
+        // SAFETY: the task is owned by the pool until the completion event.
+        unsafe {
+            (*p).task.ctx = p.cast();
+            (*p).task.handler = Some(run::<T>);
+            (*p).task.event.data = p.cast();
+            (*p).task.event.handler = Some(complete::<T>);
+            (*p).task.event.log = ngx_cycle_log().as_ptr();
+
+            if ngx_thread_task_post(self.as_ptr(), &mut (*p).task) != NGX_OK as _ {
+                drop(Box::from_raw(p));
+                return Err(ThreadError::Post);
+            }
+        }
+
+        // SAFETY: p is a non-null pointer obtained from Box::into_raw.
+        Ok(BlockingTask(unsafe { NonNull::new_unchecked(p) }))
+    }
+}
+
+/// Runs `f` on a thread of the pool `pool_name`.
+///
+/// Returns a future resolving to the result of `f`. If `f` panics, the panic is resumed when the
+/// future is polled. Dropping the future does not cancel the task, but its result is discarded.
+///
+/// Must be called from the main thread of a worker process, as the pools are not started in the
+/// master process.
+pub fn spawn_blocking<F, T>(
+    pool_name: impl AsRef<[u8]>,
+    f: F,
+) -> Result<BlockingTask<T>, ThreadError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    ThreadPool::get(pool_name)
+        .ok_or(ThreadError::NotFound)?
+        .spawn_blocking(f)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Complete,
+    Detached,
+}
+
+/// Task state shared between the pool thread and the event loop.
+///
+/// `func` and `result` are accessed by the pool thread only while the task is queued or running,
+/// and by the main thread only after the completion event. The pool mutex provides the necessary
+/// synchronization. `waker` and `state` are only accessed from the main thread.
+struct Inner<T> {
+    task: ngx_thread_task_t,
+    func: Option<Box<dyn FnOnce() -> T + Send>>,
+    result: Option<thread::Result<T>>,
+    waker: Option<task::Waker>,
+    state: State,
+}
+
+unsafe extern "C" fn run<T>(data: *mut c_void, _log: *mut ngx_log_t) {
+    let inner = data.cast::<Inner<T>>();
+    if let Some(func) = (*inner).func.take() {
+        (*inner).result = Some(panic::catch_unwind(AssertUnwindSafe(func)));
+    }
+}
+
+unsafe extern "C" fn complete<T>(ev: *mut ngx_event_t) {
+    let inner = (*ev).data.cast::<Inner<T>>();
+
+    if (*inner).state == State::Detached {
+        drop(Box::from_raw(inner));
+        return;
+    }
+
+    (*inner).state = State::Complete;
+    if let Some(waker) = (*inner).waker.take() {
+        waker.wake();
+    }
+}
+
+/// Future returned by [spawn_blocking].
+///
+/// Resolves to the result of the closure on the event loop.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct BlockingTask<T>(NonNull<Inner<T>>);
+
+impl<T> Future for BlockingTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let inner = self.0.as_ptr();
+
+        // SAFETY: the task is alive until this future is dropped, and the fields are only
+        // accessed from the main thread after the completion event.
+        unsafe {
+            if (*inner).state != State::Complete {
+                match (*inner).waker {
+                    Some(ref mut waker) => waker.clone_from(cx.waker()),
+                    None => (*inner).waker = Some(cx.waker().clone()),
+                }
+                return Poll::Pending;
+            }
+
+            let result = (*inner).result.take();
+            match result.expect("task polled after completion") {
+                Ok(value) => Poll::Ready(value),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+    }
+}
+
+impl<T> Drop for BlockingTask<T> {
+    fn drop(&mut self) {
+        let inner = self.0.as_ptr();
+
+        // SAFETY: the completion handler frees a detached task.
+        unsafe {
+            if (*inner).state == State::Complete {
+                drop(Box::from_raw(inner));
+            } else {
+                (*inner).state = State::Detached;
+                (*inner).waker = None;
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for BlockingTask<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingTask").finish_non_exhaustive()
+    }
+}
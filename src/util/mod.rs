@@ -0,0 +1,5 @@
+//! Building blocks for the module logic, implemented on top of the lower level interfaces.
+
+pub use rate_limit::{Decision, Rate, RateLimiter};
+
+mod rate_limit;
@@ -0,0 +1,239 @@
+use core::time::Duration;
+
+use nginx_sys::{ngx_msec_int_t, ngx_msec_t};
+
+use crate::allocator::AllocError;
+use crate::collections::LruCache;
+use crate::core::{NgxString, SlabPool};
+use crate::event::current_msec;
+use crate::shm::SharedZoneInit;
+use crate::sync::RwLock;
+
+/// Request rate, as in the `rate` parameter of the `limit_req_zone` directive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rate(u64);
+
+impl Rate {
+    /// Creates a rate of `n` requests per second.
+    pub const fn per_second(n: u32) -> Self {
+        Self(n as u64 * 1000)
+    }
+
+    /// Creates a rate of `n` requests per minute.
+    pub const fn per_minute(n: u32) -> Self {
+        Self(n as u64 * 1000 / 60)
+    }
+
+    /// Returns the rate in requests per 1000 seconds.
+    pub const fn as_millis_per_second(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The outcome of [RateLimiter::acquire].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The request is within the rate.
+    Allow,
+    /// The request exceeds the rate, but fits into the burst and should be delayed.
+    Delay(Duration),
+    /// The request exceeds the burst and should be rejected.
+    Reject,
+}
+
+struct Bucket {
+    // The number of excess requests multiplied by 1000.
+    excess: u64,
+    last: ngx_msec_t,
+}
+
+impl Bucket {
+    /// Leaks the bucket at `rate` since the last update and accounts a request at `now`.
+    fn update(&mut self, now: ngx_msec_t, rate: u64, burst: u32) -> Decision {
+        let elapsed = match now.wrapping_sub(self.last) as ngx_msec_int_t {
+            // the time went backwards by more than a minute, as in limit_req
+            ms if ms < -60000 => 1,
+            ms if ms < 0 => 0,
+            ms => ms as u64,
+        };
+
+        let leaked = rate.saturating_mul(elapsed) / 1000;
+        let excess = (self.excess + 1000).saturating_sub(leaked);
+
+        if excess > u64::from(burst) * 1000 {
+            return Decision::Reject;
+        }
+
+        self.excess = excess;
+        self.last = now;
+
+        let delay = excess * 1000 / rate;
+        if delay == 0 {
+            Decision::Allow
+        } else {
+            Decision::Delay(Duration::from_millis(delay))
+        }
+    }
+}
+
+/// A leaky bucket rate limiter in shared memory, keyed by arbitrary bytes.
+///
+/// The limiter implements the same algorithm as the `limit_req` module: each key has a bucket of
+/// excess requests that leaks at the specified rate. Requests that overflow the bucket by more
+/// than the burst are rejected, and the rest are delayed until the bucket drains.
+///
+/// The buckets are stored in an [LruCache] in the zone slab pool. When the zone is full, the least
+/// recently used buckets are evicted.
+///
+/// Example:
+/// ```rust,no_run
+/// # use ngx::shm::SharedZone;
+/// # use ngx::util::{Decision, Rate, RateLimiter};
+/// # fn example(zone: &SharedZone<RateLimiter>, key: &[u8]) -> Option<()> {
+/// let limiter = zone.get()?;
+///
+/// match limiter.acquire(key, Rate::per_second(10), 5) {
+///     Ok(Decision::Allow) => { /* process the request */ }
+///     Ok(Decision::Delay(_delay)) => { /* retry after the delay */ }
+///     Ok(Decision::Reject) | Err(_) => { /* respond with 429 or 503 */ }
+/// }
+/// # Some(())
+/// # }
+/// ```
+pub struct RateLimiter {
+    buckets: RwLock<LruCache<NgxString<SlabPool>, Bucket, SlabPool>>,
+    alloc: SlabPool,
+}
+
+impl RateLimiter {
+    /// Attempts to create a new rate limiter with the specified slab pool.
+    pub fn try_new_in(alloc: SlabPool) -> Result<Self, AllocError> {
+        // The number of buckets is limited by the zone size.
+        let buckets = LruCache::try_new_in(usize::MAX, alloc.clone())?;
+        Ok(Self {
+            buckets: RwLock::new(buckets),
+            alloc,
+        })
+    }
+
+    /// Accounts a request for the key and returns the decision.
+    ///
+    /// `burst` is the number of requests that can exceed the rate, and should be the same for all
+    /// the calls with the same key. A rejected request is not accounted.
+    ///
+    /// Returns an error if the bucket for a new key cannot be allocated.
+    pub fn acquire(&self, key: &[u8], rate: Rate, burst: u32) -> Result<Decision, AllocError> {
+        let now = current_msec();
+        let rate = rate.0.max(1);
+
+        let mut buckets = self.buckets.write();
+
+        if let Some(bucket) = buckets.get(key) {
+            return Ok(bucket.update(now, rate, burst));
+        }
+
+        // Evict the least recently used buckets until the key fits into the zone.
+        let key = loop {
+            match NgxString::try_from_bytes_in(key, self.alloc.clone()) {
+                Ok(key) => break key,
+                Err(_) if buckets.pop_lru().is_some() => continue,
+                Err(_) => return Err(AllocError),
+            }
+        };
+
+        buckets.try_insert(
+            key,
+            Bucket {
+                excess: 0,
+                last: now,
+            },
+        )?;
+
+        Ok(Decision::Allow)
+    }
+
+    /// Removes the bucket for the key.
+    pub fn reset(&self, key: &[u8]) {
+        self.buckets.write().remove(key);
+    }
+}
+
+unsafe impl SharedZoneInit for RateLimiter {
+    fn init(alloc: &SlabPool) -> Result<Self, AllocError> {
+        Self::try_new_in(alloc.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(last: ngx_msec_t) -> Bucket {
+        Bucket { excess: 0, last }
+    }
+
+    #[test]
+    fn test_bucket_burst() {
+        let rate = Rate::per_second(10).as_millis_per_second();
+        let mut b = bucket(1000);
+
+        // every request within the same millisecond adds 1000 to the excess
+        assert_eq!(
+            b.update(1000, rate, 2),
+            Decision::Delay(Duration::from_millis(100))
+        );
+        assert_eq!(b.excess, 1000);
+        assert_eq!(
+            b.update(1000, rate, 2),
+            Decision::Delay(Duration::from_millis(200))
+        );
+        assert_eq!(b.excess, 2000);
+
+        // a rejected request is not accounted
+        assert_eq!(b.update(1000, rate, 2), Decision::Reject);
+        assert_eq!(b.excess, 2000);
+        assert_eq!(b.last, 1000);
+    }
+
+    #[test]
+    fn test_bucket_leak() {
+        let rate = Rate::per_second(10).as_millis_per_second();
+        let mut b = bucket(1000);
+
+        // 10 r/s leaks one request per 100ms
+        assert_eq!(b.update(1100, rate, 0), Decision::Allow);
+        assert_eq!(b.excess, 0);
+
+        b.excess = 2000;
+        assert_eq!(
+            b.update(1250, rate, 5),
+            Decision::Delay(Duration::from_millis(150))
+        );
+        assert_eq!(b.excess, 1500);
+        assert_eq!(b.last, 1250);
+
+        // the excess does not go below zero
+        assert_eq!(b.update(100_000, rate, 0), Decision::Allow);
+        assert_eq!(b.excess, 0);
+    }
+
+    #[test]
+    fn test_bucket_clock() {
+        let rate = Rate::per_minute(60).as_millis_per_second();
+        let mut b = bucket(ngx_msec_t::MAX - 499);
+
+        // one request leaked over the wrapping clock
+        assert_eq!(b.update(500, rate, 0), Decision::Allow);
+        assert_eq!(b.excess, 0);
+        assert_eq!(b.last, 500);
+
+        // the time going backwards does not leak the bucket
+        b.last = 10_000;
+        b.excess = 500;
+        assert_eq!(
+            b.update(9_000, rate, 2),
+            Decision::Delay(Duration::from_millis(1500))
+        );
+        assert_eq!(b.excess, 1500);
+    }
+}
@@ -0,0 +1,295 @@
+//! Test harness for running NGINX with the modules built from this workspace.
+//!
+//! The utilities here are shared by the integration tests and are intended to serve as an example
+//! for the downstream module authors.
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Result, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const NGINX_BINARY_NAME: &str = "nginx";
+
+/// Convert a CStr to a PathBuf
+pub fn cstr_to_path(val: &std::ffi::CStr) -> Option<PathBuf> {
+    if val.is_empty() {
+        return None;
+    }
+
+    #[cfg(unix)]
+    let str = std::ffi::OsStr::from_bytes(val.to_bytes());
+    #[cfg(not(unix))]
+    let str = std::str::from_utf8(val.to_bytes()).ok()?;
+
+    Some(PathBuf::from(str))
+}
+
+/// Find nginx binary in the build directory
+pub fn find_nginx_binary() -> io::Result<PathBuf> {
+    let path = [
+        // TEST_NGINX_BINARY is specified for tests
+        env::var("TEST_NGINX_BINARY").ok().map(PathBuf::from),
+        // The module is built against an external NGINX source tree
+        env::var("NGINX_BUILD_DIR")
+            .map(PathBuf::from)
+            .map(|x| x.join(NGINX_BINARY_NAME))
+            .ok(),
+        env::var("NGINX_SOURCE_DIR")
+            .map(PathBuf::from)
+            .map(|x| x.join("objs").join(NGINX_BINARY_NAME))
+            .ok(),
+        // Fallback to the build directory exposed by nginx-sys
+        option_env!("DEP_NGINX_BUILD_DIR")
+            .map(PathBuf::from)
+            .map(|x| x.join(NGINX_BINARY_NAME)),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|x| x.is_file())
+    .ok_or(io::ErrorKind::NotFound)?;
+
+    Ok(path)
+}
+
+/// Find a dynamic module built from an example target.
+///
+/// `module` is the NGINX module name used by the NGINX build system, e.g.
+/// `ngx_http_upstream_custom_module`, and `target` is the name of the cargo example target.
+///
+/// The following locations are checked:
+///  * `TEST_NGINX_MODULES`, a directory with the modules built by the NGINX build system,
+///  * the cargo target directory, for the modules built with `cargo build --example`.
+pub fn find_example_module(module: &str, target: &str) -> Option<PathBuf> {
+    let cdylib = format!(
+        "{}{}{}",
+        env::consts::DLL_PREFIX,
+        target,
+        env::consts::DLL_SUFFIX
+    );
+
+    let target_dir = env::var("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(env!("CARGO_MANIFEST_DIR")).join("target"));
+
+    [
+        env::var("TEST_NGINX_MODULES")
+            .map(|x| Path::new(&x).join(format!("{module}.so")))
+            .ok(),
+        Some(target_dir.join("debug").join("examples").join(&cdylib)),
+        Some(target_dir.join("release").join("examples").join(&cdylib)),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|x| x.is_file())
+}
+
+/// Builds an example target as a dynamic module with cargo.
+///
+/// The module is built for the default profile and placed in the cargo target directory, where it
+/// can be found with [find_example_module].
+pub fn build_example_module(target: &str) -> io::Result<()> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(["build", "--package", "examples", "--example", target])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!("cargo build failed: {status}")));
+    }
+
+    Ok(())
+}
+
+/// Returns an unused local TCP port.
+///
+/// The port is not reserved and can be taken by another process before NGINX binds to it.
+pub fn free_port() -> io::Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Expands `%%NAME%%` placeholders in a configuration template.
+///
+/// Panics if any placeholders are left unexpanded.
+pub fn render_config(template: &str, vars: &[(&str, &dyn ToString)]) -> String {
+    let mut config = template.to_string();
+
+    for (name, value) in vars {
+        config = config.replace(&format!("%%{name}%%"), &value.to_string());
+    }
+
+    if let Some(pos) = config.find("%%") {
+        let end = config[pos..].lines().next().unwrap_or_default();
+        panic!("unexpanded template variable: {end}");
+    }
+
+    config
+}
+
+/// harness to test nginx
+pub struct Nginx {
+    pub prefix: tempfile::TempDir,
+    pub bin_path: PathBuf,
+    pub config_path: PathBuf,
+}
+
+impl Default for Nginx {
+    /// create nginx with default
+    fn default() -> Nginx {
+        let binary = find_nginx_binary().expect("nginx binary");
+        Nginx::new(binary).expect("test harness")
+    }
+}
+
+impl Nginx {
+    pub fn new(binary: impl AsRef<Path>) -> io::Result<Nginx> {
+        let prefix = tempfile::tempdir()?;
+        let config = prefix.path().join("nginx.conf");
+
+        fs::create_dir(prefix.path().join("logs"))?;
+
+        Ok(Nginx {
+            prefix,
+            bin_path: binary.as_ref().to_owned(),
+            config_path: config,
+        })
+    }
+
+    /// start nginx process with arguments
+    pub fn cmd(&self, args: &[&str]) -> Result<Output> {
+        let prefix = self.prefix.path().to_string_lossy();
+        let config_path = self.config_path.to_string_lossy();
+        let args = [&["-p", &prefix, "-c", &config_path], args].concat();
+        let result = Command::new(&self.bin_path).args(args).output();
+
+        match result {
+            Err(e) => Err(e),
+
+            Ok(output) => {
+                println!("status: {}", output.status);
+                println!("stdout: {}", String::from_utf8_lossy(&output.stdout));
+                println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
+                Ok(output)
+            }
+        }
+    }
+
+    /// complete stop the nginx binary
+    pub fn stop(&mut self) -> Result<Output> {
+        self.cmd(&["-s", "stop"])
+    }
+
+    /// start the nginx binary
+    pub fn start(&mut self) -> Result<Output> {
+        self.cmd(&[])
+    }
+
+    // make sure we stop existing nginx and start new master process
+    // intentinally ignore failure in stop
+    pub fn restart(&mut self) -> Result<Output> {
+        let _ = self.stop();
+        self.start()
+    }
+
+    // replace config with another config
+    pub fn replace_config<P: AsRef<Path>>(&mut self, from: P) -> Result<u64> {
+        println!(
+            "copying config from: {:?} to: {:?}",
+            from.as_ref(),
+            self.config_path
+        ); // replace with logging
+        fs::copy(from, &self.config_path)
+    }
+
+    /// Writes the configuration file.
+    pub fn write_config(&mut self, config: &str) -> Result<()> {
+        println!("writing config to: {:?}\n{config}", self.config_path);
+        fs::write(&self.config_path, config)
+    }
+}
+
+impl Drop for Nginx {
+    /// Stops the NGINX process left running by a failed test, so that it does not outlive the
+    /// temporary prefix directory.
+    fn drop(&mut self) {
+        if self.prefix.path().join("logs").join("nginx.pid").exists() {
+            let _ = self.stop();
+        }
+    }
+}
+
+/// Waits until the TCP port accepts connections.
+pub fn wait_for_port(port: u16, timeout: Duration) -> Result<()> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let start = Instant::now();
+
+    loop {
+        match TcpStream::connect_timeout(&addr, Duration::from_millis(100)) {
+            Ok(_) => return Ok(()),
+            Err(err) if start.elapsed() > timeout => return Err(err),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+/// A parsed HTTP/1.x response.
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// Returns the value of the first header with the specified name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Sends a simple HTTP/1.0 GET request and reads the response.
+pub fn http_get(port: u16, path: &str, headers: &[(&str, &str)]) -> Result<HttpResponse> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let mut request = format!("GET {path} HTTP/1.0\r\nHost: localhost\r\n");
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+
+    parse_response(&response).ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))
+}
+
+fn parse_response(response: &[u8]) -> Option<HttpResponse> {
+    let split = response.windows(4).position(|x| x == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&response[..split]).ok()?;
+    let mut lines = head.split("\r\n");
+
+    let status = lines.next()?.split(' ').nth(1)?.parse().ok()?;
+    let headers = lines
+        .filter_map(|x| x.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    Some(HttpResponse {
+        status,
+        headers,
+        body: response[split + 4..].to_vec(),
+    })
+}
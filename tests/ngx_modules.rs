@@ -0,0 +1,77 @@
+//! Checks the tables generated by the `ngx_modules!` macro for a dynamic module.
+#![allow(non_upper_case_globals)]
+
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+use ngx::ffi::ngx_module_t;
+
+static mut ngx_http_example_module: ngx_module_t = ngx_module_t::default();
+static mut ngx_http_example_filter_module: ngx_module_t = ngx_module_t::default();
+static mut ngx_http_example_log_module: ngx_module_t = ngx_module_t::default();
+
+// `ngx_stream_example_module` is not defined, and the entry must be removed by the `cfg`.
+ngx::ngx_modules! {
+    ngx_http_example_module,
+    ngx_http_example_filter_module: before(
+        ngx_http_copy_filter_module,
+        ngx_http_headers_filter_module,
+    ),
+    #[cfg(any())]
+    ngx_stream_example_module: before(ngx_stream_upstream_module),
+    #[cfg(all())]
+    ngx_http_example_log_module,
+}
+
+/// Converts a NULL-terminated array of C strings.
+fn strings(list: &[*const c_char]) -> Vec<&str> {
+    let (last, list) = list.split_last().expect("terminator");
+    assert!(last.is_null());
+
+    list.iter()
+        .map(|x| unsafe { CStr::from_ptr(*x) }.to_str().expect("utf-8"))
+        .collect()
+}
+
+#[test]
+fn test_modules() {
+    let modules = unsafe { ptr::addr_of!(ngx_modules).read() };
+
+    assert_eq!(
+        modules,
+        [
+            ptr::addr_of!(ngx_http_example_module),
+            ptr::addr_of!(ngx_http_example_filter_module),
+            ptr::addr_of!(ngx_http_example_log_module),
+            ptr::null(),
+        ]
+    );
+}
+
+#[test]
+fn test_module_names() {
+    let names = unsafe { ptr::addr_of!(ngx_module_names).read() };
+
+    assert_eq!(
+        strings(&names),
+        [
+            "ngx_http_example_module",
+            "ngx_http_example_filter_module",
+            "ngx_http_example_log_module",
+        ]
+    );
+}
+
+#[test]
+fn test_module_order() {
+    let order = unsafe { ptr::addr_of!(ngx_module_order).read() };
+
+    assert_eq!(
+        strings(&order),
+        [
+            "ngx_http_example_filter_module",
+            "ngx_http_copy_filter_module",
+            "ngx_http_headers_filter_module",
+        ]
+    );
+}
@@ -0,0 +1,111 @@
+//! End-to-end test for the `upstream` example module.
+//!
+//! The test loads the module into NGINX, proxies requests through an upstream block configured
+//! with the `custom` directive to another server block, and checks the responses.
+//!
+//! The test is ignored by default, as it needs an NGINX binary built with `--with-compat` and the
+//! module built from the same source tree:
+//!
+//! ```sh
+//! TEST_NGINX_BINARY=/path/to/objs/nginx TEST_NGINX_MODULES=/path/to/objs \
+//!     cargo test --test upstream_module -- --ignored
+//! ```
+mod common;
+
+use std::time::Duration;
+
+use common::{
+    build_example_module, find_example_module, free_port, http_get, render_config, wait_for_port,
+    Nginx,
+};
+
+const MODULE_NAME: &str = "ngx_http_upstream_custom_module";
+const EXAMPLE_NAME: &str = "upstream";
+
+const CONFIG_TEMPLATE: &str = r#"
+load_module %%MODULE%%;
+
+daemon on;
+master_process on;
+worker_processes 1;
+
+error_log logs/error.log debug;
+pid logs/nginx.pid;
+
+events {
+    worker_connections 64;
+}
+
+http {
+    access_log off;
+
+    upstream backend {
+        server 127.0.0.1:%%BACKEND_PORT%%;
+        custom 32;
+    }
+
+    server {
+        listen 127.0.0.1:%%PORT%%;
+        server_name _;
+
+        location / {
+            proxy_pass http://backend;
+        }
+    }
+
+    server {
+        listen 127.0.0.1:%%BACKEND_PORT%%;
+
+        location / {
+            add_header X-Backend $server_port always;
+            return 418 "backend:$uri";
+        }
+    }
+}
+"#;
+
+#[test]
+#[ignore = "requires NGINX and the upstream example built as a dynamic module"]
+fn upstream_custom_module() {
+    let module = find_example_module(MODULE_NAME, EXAMPLE_NAME)
+        .or_else(|| {
+            build_example_module(EXAMPLE_NAME).ok()?;
+            find_example_module(MODULE_NAME, EXAMPLE_NAME)
+        })
+        .unwrap_or_else(|| panic!("dynamic module \"{MODULE_NAME}\" not found"));
+
+    // Stops NGINX on drop if any of the assertions below fails.
+    let mut nginx = Nginx::default();
+
+    let port = free_port().expect("free port");
+    let backend_port = free_port().expect("free port");
+
+    let config = render_config(
+        CONFIG_TEMPLATE,
+        &[
+            ("MODULE", &module.display()),
+            ("PORT", &port),
+            ("BACKEND_PORT", &backend_port),
+        ],
+    );
+    nginx.write_config(&config).expect("write config");
+
+    let output = nginx.restart().expect("Unable to restart NGINX");
+    assert!(output.status.success());
+
+    wait_for_port(port, Duration::from_secs(5)).expect("NGINX is not listening");
+
+    for path in ["/", "/test"] {
+        let response = http_get(port, path, &[]).expect("response");
+
+        assert_eq!(response.status, 418);
+        assert_eq!(
+            response.header("X-Backend"),
+            Some(backend_port.to_string().as_str())
+        );
+        assert_eq!(response.body, format!("backend:{path}").as_bytes());
+    }
+
+    let output = nginx.stop().expect("Unable to stop NGINX");
+    assert!(output.status.success());
+}